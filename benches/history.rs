@@ -0,0 +1,86 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use horizon_logger::__private::History;
+use horizon_logger::{LogEntry, LogLevel};
+use std::collections::VecDeque;
+use std::sync::{Arc, Barrier, Mutex};
+use std::thread;
+
+const ENTRIES_PER_THREAD: usize = 2_000;
+const CAPACITY: usize = 1000;
+
+/// The original single-mutex history, kept here for comparison
+#[derive(Default)]
+struct MutexHistory {
+    entries: Mutex<VecDeque<LogEntry>>,
+}
+
+impl MutexHistory {
+    fn push(&self, mut entry: LogEntry) {
+        let mut entries = self.entries.lock().unwrap();
+        entry.seq = entries.back().map_or(0, |e| e.seq + 1);
+        entries.push_back(entry);
+        if entries.len() > CAPACITY {
+            entries.pop_front();
+        }
+    }
+}
+
+/// Push from `threads` threads at once and wait for all of them
+fn run_contended<S: Send + Sync + 'static>(store: Arc<S>, threads: usize, push: fn(&S, LogEntry)) {
+    let entry = LogEntry::new(LogLevel::INFO, "BENCH", "contended history write");
+    let barrier = Arc::new(Barrier::new(threads));
+
+    let handles: Vec<_> = (0..threads)
+        .map(|_| {
+            let store = store.clone();
+            let barrier = barrier.clone();
+            let entry = entry.clone();
+            thread::spawn(move || {
+                barrier.wait();
+                for _ in 0..ENTRIES_PER_THREAD {
+                    push(&store, entry.clone());
+                }
+            })
+        })
+        .collect();
+
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
+
+fn history_push(c: &mut Criterion) {
+    let mut group = c.benchmark_group("history_push");
+    group.sample_size(20);
+
+    for threads in [8, 32] {
+        group.throughput(Throughput::Elements((threads * ENTRIES_PER_THREAD) as u64));
+
+        group.bench_with_input(BenchmarkId::new("mutex", threads), &threads, |b, &threads| {
+            b.iter(|| run_contended(Arc::new(MutexHistory::default()), threads, MutexHistory::push));
+        });
+
+        group.bench_with_input(BenchmarkId::new("sharded", threads), &threads, |b, &threads| {
+            b.iter(|| run_contended(Arc::new(History::new()), threads, |h, e| {
+                h.push(e);
+            }));
+        });
+    }
+
+    group.finish();
+}
+
+fn history_snapshot(c: &mut Criterion) {
+    let history = History::new();
+    for _ in 0..CAPACITY * 4 {
+        history.push(LogEntry::new(LogLevel::INFO, "BENCH", "snapshot source"));
+    }
+
+    let mut group = c.benchmark_group("history_snapshot");
+    group.bench_function("copied", |b| b.iter(|| history.snapshot()));
+    group.bench_function("shared", |b| b.iter(|| history.snapshot_shared()));
+    group.finish();
+}
+
+criterion_group!(benches, history_push, history_snapshot);
+criterion_main!(benches);
@@ -0,0 +1,47 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use horizon_logger::testing::CaptureLogger;
+use horizon_logger::{HorizonLogger, LogLevel};
+use std::time::Duration;
+
+/// The whole `log()` path, rendering the console line into a discarded writer
+fn log_call(c: &mut Criterion) {
+    let mut group = c.benchmark_group("log_call");
+
+    let with_history = CaptureLogger::new();
+    group.bench_function("history", |b| b.iter(|| with_history.info("BENCH", "tick finished")));
+
+    let component = String::from("BENCH");
+    group.bench_function("history_built_component", |b| b.iter(|| with_history.info(&component, "tick finished")));
+
+    let without_history = CaptureLogger::from_builder(HorizonLogger::builder().history(false));
+    group.bench_function("no_history", |b| b.iter(|| without_history.info("BENCH", "tick finished")));
+
+    let profiled = CaptureLogger::from_builder(HorizonLogger::builder().self_profiling(true));
+    group.bench_function("self_profiling", |b| b.iter(|| profiled.info("BENCH", "tick finished")));
+
+    let coarse = CaptureLogger::from_builder(HorizonLogger::builder().console_coarse_time(Duration::from_millis(10)));
+    group.bench_function("coarse_time", |b| b.iter(|| coarse.info("BENCH", "tick finished")));
+
+    group.finish();
+}
+
+/// A tick's worth of packet diagnostics, logged in one batch or one call each
+fn log_batch(c: &mut Criterion) {
+    let mut group = c.benchmark_group("log_batch");
+    let messages: Vec<String> = (0..500).map(|packet| format!("packet {} acked", packet)).collect();
+
+    let logger = CaptureLogger::new();
+    group.bench_function("batch_500", |b| b.iter(|| logger.log_batch(LogLevel::INFO, "NET", messages.iter().cloned())));
+    group.bench_function("singles_500", |b| {
+        b.iter(|| {
+            for message in &messages {
+                logger.info("NET", message);
+            }
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, log_call, log_batch);
+criterion_main!(benches);
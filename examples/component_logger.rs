@@ -0,0 +1,48 @@
+//! A subsystem that keeps a `ComponentLogger` instead of a logger and a component name
+//!
+//! ```text
+//! cargo run --example component_logger
+//! ```
+
+use horizon_logger::{log_info, ComponentLogger, HorizonLogger, LogLevel};
+
+struct Physics {
+    log: ComponentLogger,
+    tick: u64,
+}
+
+impl Physics {
+    fn new(logger: &HorizonLogger) -> Self {
+        Physics {
+            log: logger.component("PHYSICS"),
+            tick: 0,
+        }
+    }
+
+    fn step(&mut self, bodies: usize) {
+        self.tick += 1;
+        let tick = self.tick;
+        log_info!(self.log, "tick {tick} complete");
+        if bodies > 1000 {
+            self.log.warn(&format!("{} bodies, over the budget of 1000", bodies));
+        }
+    }
+}
+
+/// Cloned into every entity; the clone shares the logger
+struct Entity {
+    id: u32,
+    log: ComponentLogger,
+}
+
+fn main() {
+    let logger = HorizonLogger::new();
+    let mut physics = Physics::new(&logger);
+    physics.step(40);
+    physics.step(1200);
+
+    let entities: Vec<_> = (1..=3).map(|id| Entity { id, log: physics.log.clone() }).collect();
+    for entity in &entities {
+        entity.log.event(LogLevel::DEBUG).field("entity", entity.id).log("woke up");
+    }
+}
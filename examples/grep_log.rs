@@ -0,0 +1,43 @@
+//! Print the entries of a log file written by horizon_logger, like grep and tail
+//!
+//! ```text
+//! cargo run --example grep_log -- server.log --level WARN --component NETWORK --tail 20 timeout
+//! ```
+
+use horizon_logger::reader::LogFileReader;
+use std::collections::VecDeque;
+
+const USAGE: &str = "usage: grep_log FILE [--level LEVEL] [--component PREFIX] [--tail N] [TEXT]";
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1);
+    let mut reader = LogFileReader::open(args.next().ok_or(USAGE)?)?;
+    let mut tail = None;
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(USAGE);
+        match arg.as_str() {
+            "--level" => reader = reader.min_level(value()?.parse()?),
+            "--component" => reader = reader.component(&value()?),
+            "--tail" => tail = Some(value()?.parse::<usize>()?.max(1)),
+            text => reader = reader.contains(text),
+        }
+    }
+
+    let mut last = VecDeque::new();
+    for entry in reader.by_ref() {
+        match tail {
+            None => println!("{}", entry),
+            Some(count) => {
+                if last.len() == count {
+                    last.pop_front();
+                }
+                last.push_back(entry);
+            }
+        }
+    }
+    last.iter().for_each(|entry| println!("{}", entry));
+    if !reader.errors().is_empty() {
+        eprintln!("{}", reader.errors());
+    }
+    Ok(())
+}
@@ -0,0 +1,64 @@
+//! Log lines printed above a progress bar instead of through it
+//!
+//! ```text
+//! cargo run --example progress_bars
+//! ```
+//!
+//! `Bars` stands in for a progress bar library such as indicatif: like
+//! `MultiProgress::println`, its `println` clears the bar, prints the line
+//! and draws the bar again below it. With indicatif the wiring is the same,
+//! `.console_writer(move |line| multi_progress.println(line))`.
+
+use horizon_logger::{log_info, HorizonLogger};
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// One bar on the last line of the terminal
+#[derive(Clone, Default)]
+struct Bars {
+    done: Arc<Mutex<(usize, usize)>>,
+}
+
+impl Bars {
+    fn set(&self, done: usize, total: usize) {
+        *self.done.lock().unwrap() = (done, total);
+        self.draw(&mut io::stdout().lock());
+    }
+
+    fn println(&self, line: &str) {
+        let mut out = io::stdout().lock();
+        let _ = write!(out, "\r\x1b[K{}\n", line);
+        self.draw(&mut out);
+    }
+
+    fn draw(&self, out: &mut impl Write) {
+        let (done, total) = *self.done.lock().unwrap();
+        let filled = (done * 30).checked_div(total).unwrap_or(0);
+        let _ = write!(out, "\r[{}{}] {}/{}", "#".repeat(filled), "-".repeat(30 - filled), done, total);
+        let _ = out.flush();
+    }
+}
+
+fn main() {
+    let bars = Bars::default();
+    let printer = bars.clone();
+    let logger = HorizonLogger::builder()
+        .console_writer(move |line| printer.println(line))
+        .build();
+
+    let textures = ["grass", "stone", "water", "lava", "sand", "snow"];
+    for (i, texture) in textures.iter().enumerate() {
+        bars.set(i, textures.len());
+        thread::sleep(Duration::from_millis(300));
+        log_info!(logger, "ASSETS", "compressed {}.png", texture);
+        if *texture == "lava" {
+            logger.warn("ASSETS", "lava.png has no mipmaps");
+        }
+    }
+    bars.set(textures.len(), textures.len());
+    logger.info("ASSETS", "asset pipeline finished");
+    // Leave the finished bar on its own line
+    println!();
+}
@@ -0,0 +1,161 @@
+use crate::history::component_matches;
+use crate::HorizonLogger;
+use std::borrow::Cow;
+use std::fmt;
+use std::sync::RwLock;
+
+/// Old component names rewritten to their canonical replacements
+#[derive(Default)]
+pub(crate) struct ComponentAliases {
+    /// `(old, canonical)` pairs, longest old name first so the most specific alias wins
+    aliases: RwLock<Vec<(String, String)>>,
+}
+
+impl ComponentAliases {
+    /// The canonical name for `component`, rewriting hierarchical children too
+    pub(crate) fn resolve<'a>(&self, component: &'a str) -> Cow<'a, str> {
+        let Ok(aliases) = self.aliases.read() else {
+            return Cow::Borrowed(component);
+        };
+        aliases
+            .iter()
+            .find(|(old, _)| component_matches(component, old))
+            .map_or(Cow::Borrowed(component), |(old, canonical)| {
+                Cow::Owned(format!("{}{}", canonical, &component[old.len()..]))
+            })
+    }
+
+    fn add(&self, old: &str, canonical: &str) -> Result<(), AliasError> {
+        let error = |kind| AliasError {
+            alias: old.to_string(),
+            canonical: canonical.to_string(),
+            kind,
+        };
+
+        if old.is_empty() || canonical.is_empty() {
+            return Err(error(AliasErrorKind::Empty));
+        }
+        if component_matches(canonical, old) {
+            return Err(error(AliasErrorKind::Cycle));
+        }
+
+        let Ok(mut aliases) = self.aliases.write() else {
+            return Ok(());
+        };
+        for (existing_old, existing_canonical) in aliases.iter() {
+            if existing_old == old {
+                continue;
+            }
+            // Rewritten names must never be rewritten again
+            let feeds_in = component_matches(existing_canonical, old);
+            let feeds_out = component_matches(canonical, existing_old);
+            if feeds_in && feeds_out {
+                return Err(error(AliasErrorKind::Cycle));
+            }
+            if feeds_in || feeds_out {
+                return Err(error(AliasErrorKind::Chained {
+                    existing: existing_old.clone(),
+                }));
+            }
+        }
+
+        aliases.retain(|(existing_old, _)| existing_old != old);
+        aliases.push((old.to_string(), canonical.to_string()));
+        aliases.sort_by_key(|(old, _)| std::cmp::Reverse(old.len()));
+        Ok(())
+    }
+}
+
+/// An alias could not be registered
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AliasError {
+    /// The old name being registered
+    pub alias: String,
+    /// The name it was to be rewritten to
+    pub canonical: String,
+    pub kind: AliasErrorKind,
+}
+
+/// Why an alias was rejected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AliasErrorKind {
+    /// Either name is empty
+    Empty,
+    /// The alias would eventually rewrite a name back into itself
+    Cycle,
+    /// The alias would feed into, or from, the existing alias for `existing`
+    Chained { existing: String },
+}
+
+impl fmt::Display for AliasError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cannot alias `{}` to `{}`: ", self.alias, self.canonical)?;
+        match &self.kind {
+            AliasErrorKind::Empty => write!(f, "component names must not be empty"),
+            AliasErrorKind::Cycle => write!(f, "aliases would form a cycle"),
+            AliasErrorKind::Chained { existing } => {
+                write!(f, "would chain with the existing alias for `{}`", existing)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AliasError {}
+
+impl HorizonLogger {
+    /// Log entries for `old` (and its children) under `canonical` instead
+    ///
+    /// The rewrite applies everywhere: console, sinks, history and stats.
+    /// Aliases may not chain into each other.
+    pub fn add_component_alias(&self, old: &str, canonical: &str) -> Result<(), AliasError> {
+        self.inner.aliases.add(old, canonical)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::CaptureLogger;
+
+    #[test]
+    fn test_alias_rewrites_hierarchy() {
+        let logger = CaptureLogger::new();
+        logger.add_component_alias("DATABASE", "PERSISTENCE").unwrap();
+        logger.add_component_alias("NET/OLD", "NETWORK/LEGACY").unwrap();
+
+        logger.info("DATABASE", "a");
+        logger.info("DATABASE/POOL", "b");
+        logger.info("DATABASEX", "c");
+        logger.info("NET/OLD/TCP", "d");
+
+        let components: Vec<String> = logger.entries().into_iter().map(|e| e.component.into_owned()).collect();
+        assert_eq!(
+            components,
+            vec!["PERSISTENCE", "PERSISTENCE/POOL", "DATABASEX", "NETWORK/LEGACY/TCP"]
+        );
+
+        let stats: Vec<String> = logger.component_stats().into_iter().map(|s| s.component).collect();
+        assert!(stats.contains(&"PERSISTENCE/POOL".to_string()));
+        assert!(!stats.iter().any(|c| c.starts_with("DATABASE/")));
+    }
+
+    #[test]
+    fn test_rejects_cycles_and_chains() {
+        let logger = CaptureLogger::new();
+        let kind = |old, canonical| logger.add_component_alias(old, canonical).unwrap_err().kind;
+
+        assert_eq!(kind("A", "A"), AliasErrorKind::Cycle);
+        assert_eq!(kind("A", "A/B"), AliasErrorKind::Cycle);
+
+        logger.add_component_alias("A", "B").unwrap();
+        assert_eq!(kind("B", "A"), AliasErrorKind::Cycle);
+        assert_eq!(kind("B", "C"), AliasErrorKind::Chained { existing: "A".into() });
+        assert_eq!(kind("Z", "A/X"), AliasErrorKind::Chained { existing: "A".into() });
+        assert_eq!(kind("", "C"), AliasErrorKind::Empty);
+
+        // Re-pointing an existing alias is fine
+        logger.add_component_alias("A", "C").unwrap();
+        logger.info("A", "x");
+        assert_eq!(logger.entries()[0].component, "C");
+    }
+}
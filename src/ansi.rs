@@ -0,0 +1,312 @@
+//! Finding ANSI escape sequences that callers embedded in messages
+//!
+//! Recognizes CSI sequences (`ESC [` parameters, intermediates and a final
+//! byte), OSC sequences (`ESC ]` up to BEL or `ESC \`), other two-byte
+//! escapes, and their single-character C1 forms. A sequence cut short by a
+//! byte that can't continue it ends there; one cut short by the end of the
+//! message runs to the end.
+
+use std::borrow::Cow;
+use std::fmt::Write;
+
+const ESC: char = '\x1b';
+const BEL: char = '\x07';
+/// C1 forms of `ESC [`, `ESC ]` and `ESC \`
+const CSI: char = '\u{9b}';
+const OSC: char = '\u{9d}';
+const ST: char = '\u{9c}';
+
+/// What a sink does with ANSI escape sequences already in a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AnsiPolicy {
+    /// Write them through, for terminals; the console's default
+    Preserve,
+    /// Remove them, keeping the text they styled; the default for sinks
+    #[default]
+    Strip,
+    /// Write them visibly, with ESC as `\x1b`, so nothing can restyle the output
+    Escape,
+}
+
+impl AnsiPolicy {
+    /// `text` with this policy applied, borrowed when nothing changes
+    pub(crate) fn apply(self, text: &str) -> Cow<'_, str> {
+        match self {
+            AnsiPolicy::Preserve => Cow::Borrowed(text),
+            AnsiPolicy::Strip => strip(text),
+            AnsiPolicy::Escape => escape(text),
+        }
+    }
+}
+
+/// `text` without its escape sequences
+pub(crate) fn strip(text: &str) -> Cow<'_, str> {
+    rewrite(text, |_, _| {})
+}
+
+/// Characters a terminal draws two columns wide: East Asian wide and emoji presentation
+const WIDE: &[(char, char)] = &[
+    ('\u{1100}', '\u{115f}'),
+    ('\u{231a}', '\u{231b}'),
+    ('\u{23e9}', '\u{23ec}'),
+    ('\u{23f0}', '\u{23f0}'),
+    ('\u{23f3}', '\u{23f3}'),
+    ('\u{25fd}', '\u{25fe}'),
+    ('\u{2614}', '\u{2615}'),
+    ('\u{2648}', '\u{2653}'),
+    ('\u{267f}', '\u{267f}'),
+    ('\u{2693}', '\u{2693}'),
+    ('\u{26a1}', '\u{26a1}'),
+    ('\u{26aa}', '\u{26ab}'),
+    ('\u{26bd}', '\u{26be}'),
+    ('\u{26c4}', '\u{26c5}'),
+    ('\u{26ce}', '\u{26ce}'),
+    ('\u{26d4}', '\u{26d4}'),
+    ('\u{26ea}', '\u{26ea}'),
+    ('\u{26f2}', '\u{26f3}'),
+    ('\u{26f5}', '\u{26f5}'),
+    ('\u{26fa}', '\u{26fa}'),
+    ('\u{26fd}', '\u{26fd}'),
+    ('\u{2705}', '\u{2705}'),
+    ('\u{270a}', '\u{270b}'),
+    ('\u{2728}', '\u{2728}'),
+    ('\u{274c}', '\u{274c}'),
+    ('\u{274e}', '\u{274e}'),
+    ('\u{2753}', '\u{2755}'),
+    ('\u{2757}', '\u{2757}'),
+    ('\u{2795}', '\u{2797}'),
+    ('\u{27b0}', '\u{27b0}'),
+    ('\u{27bf}', '\u{27bf}'),
+    ('\u{2b1b}', '\u{2b1c}'),
+    ('\u{2b50}', '\u{2b50}'),
+    ('\u{2b55}', '\u{2b55}'),
+    ('\u{2e80}', '\u{303e}'),
+    ('\u{3041}', '\u{a4cf}'),
+    ('\u{ac00}', '\u{d7a3}'),
+    ('\u{f900}', '\u{faff}'),
+    ('\u{fe30}', '\u{fe4f}'),
+    ('\u{ff00}', '\u{ff60}'),
+    ('\u{ffe0}', '\u{ffe6}'),
+    ('\u{1f004}', '\u{1f004}'),
+    ('\u{1f0cf}', '\u{1f0cf}'),
+    ('\u{1f18e}', '\u{1f18e}'),
+    ('\u{1f191}', '\u{1f19a}'),
+    ('\u{1f200}', '\u{1f251}'),
+    ('\u{1f300}', '\u{1f64f}'),
+    ('\u{1f680}', '\u{1f6ff}'),
+    ('\u{1f7e0}', '\u{1f7eb}'),
+    ('\u{1f90c}', '\u{1f9ff}'),
+    ('\u{1fa70}', '\u{1faff}'),
+    ('\u{20000}', '\u{3fffd}'),
+];
+
+/// Columns one character takes on its own: 0 for combining marks and joiners, 2 for wide ones
+fn char_width(c: char) -> usize {
+    if c.is_control() || matches!(c, '\u{300}'..='\u{36f}' | '\u{200b}'..='\u{200f}' | '\u{fe00}'..='\u{fe0f}') {
+        return 0;
+    }
+    // The first range not entirely below `c`
+    let at = WIDE.partition_point(|&(_, high)| high < c);
+    if WIDE.get(at).is_some_and(|&(low, _)| low <= c) {
+        2
+    } else {
+        1
+    }
+}
+
+/// Columns `text` takes up on a terminal, counting wide characters as two and escape sequences as none
+///
+/// A narrow symbol followed by the emoji variation selector (U+FE0F), as in
+/// `⚠️`, is drawn as emoji and counted as two. Covers what terminals commonly
+/// agree on rather than every grapheme cluster.
+pub(crate) fn display_width(text: &str) -> usize {
+    let mut width = 0;
+    let mut previous = 0;
+    for c in strip(text).chars() {
+        if c == '\u{fe0f}' && previous == 1 {
+            width += 1;
+        }
+        previous = char_width(c);
+        width += previous;
+    }
+    width
+}
+
+/// Split `text` after at most `columns` display columns, keeping escape sequences whole
+///
+/// Marks that take no columns stay with the character before them. The
+/// first character is always in the head, even when it alone is wider.
+pub(crate) fn split_at_display(text: &str, columns: usize) -> (&str, &str) {
+    let mut seen = 0;
+    let mut at = 0;
+    while let Some(c) = text[at..].chars().next() {
+        if matches!(c, ESC | CSI | OSC) {
+            at += sequence_len(&text[at..]).max(c.len_utf8());
+            continue;
+        }
+        let mut width = char_width(c);
+        if width == 1 && text[at + c.len_utf8()..].starts_with('\u{fe0f}') {
+            width = 2;
+        }
+        if seen > 0 && seen + width > columns {
+            break;
+        }
+        seen += width;
+        at += c.len_utf8();
+    }
+    text.split_at(at)
+}
+
+/// `text` with the control characters of its escape sequences spelled out
+fn escape(text: &str) -> Cow<'_, str> {
+    rewrite(text, |out, sequence| {
+        for c in sequence.chars() {
+            if c.is_control() {
+                let _ = write!(out, "\\x{:02x}", c as u32);
+            } else {
+                out.push(c);
+            }
+        }
+    })
+}
+
+/// Copy `text`, passing each escape sequence to `sequence` instead
+fn rewrite<'a>(text: &'a str, mut sequence: impl FnMut(&mut String, &str)) -> Cow<'a, str> {
+    let Some(first) = text.find([ESC, CSI, OSC]) else {
+        return Cow::Borrowed(text);
+    };
+    let mut out = String::with_capacity(text.len());
+    out.push_str(&text[..first]);
+    let mut rest = &text[first..];
+    while let Some(start) = rest.find([ESC, CSI, OSC]) {
+        out.push_str(&rest[..start]);
+        let len = sequence_len(&rest[start..]);
+        sequence(&mut out, &rest[start..start + len]);
+        rest = &rest[start + len..];
+    }
+    out.push_str(rest);
+    Cow::Owned(out)
+}
+
+/// Length in bytes of the escape sequence `text` starts with
+fn sequence_len(text: &str) -> usize {
+    let mut chars = text.char_indices();
+    let introducer = match chars.next() {
+        Some((_, ESC)) => match chars.next() {
+            Some((_, '[')) => CSI,
+            Some((_, ']')) => OSC,
+            // A lone ESC at the end
+            None => return text.len(),
+            Some((i, c)) => return i + escape_len(c, &text[i..]),
+        },
+        Some((_, c)) => c,
+        None => return 0,
+    };
+
+    if introducer == CSI {
+        // Parameter bytes, then intermediate bytes, then one final byte
+        for (i, c) in chars {
+            match c {
+                '\x30'..='\x3f' | '\x20'..='\x2f' => {}
+                '\x40'..='\x7e' => return i + 1,
+                _ => return i,
+            }
+        }
+        return text.len();
+    }
+
+    // OSC: a string up to BEL or ST; another escape cuts it short
+    for (i, c) in chars {
+        match c {
+            BEL | ST => return i + c.len_utf8(),
+            ESC if text[i + 1..].starts_with('\\') => return i + 2,
+            ESC | CSI | OSC => return i,
+            _ => {}
+        }
+    }
+    text.len()
+}
+
+/// Length of a two-byte escape `ESC` then `first`, with any intermediates before its final byte
+fn escape_len(first: char, text: &str) -> usize {
+    if !('\x20'..='\x2f').contains(&first) {
+        // ESC followed by something that can't end the sequence is left alone
+        return if ('\x30'..='\x7e').contains(&first) { first.len_utf8() } else { 0 };
+    }
+    for (i, c) in text.char_indices() {
+        match c {
+            '\x20'..='\x2f' => {}
+            '\x30'..='\x7e' => return i + 1,
+            _ => return i,
+        }
+    }
+    text.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply_all(text: &str) -> [String; 3] {
+        [AnsiPolicy::Preserve, AnsiPolicy::Strip, AnsiPolicy::Escape].map(|policy| policy.apply(text).into_owned())
+    }
+
+    #[test]
+    fn test_valid_sequences() {
+        let diff = "\x1b[31m-old\x1b[0m \x1b[1;32m+new\x1b[m";
+        assert_eq!(
+            apply_all(diff),
+            [
+                diff.to_string(),
+                "-old +new".to_string(),
+                "\\x1b[31m-old\\x1b[0m \\x1b[1;32m+new\\x1b[m".to_string(),
+            ]
+        );
+
+        // An OSC 8 hyperlink around styled text, terminated by ST and by BEL
+        let link = "see \x1b]8;;https://example.com\x1b\\\x1b[4mdocs\x1b[24m\x1b]8;;\x07 now";
+        assert_eq!(strip(link), "see docs now");
+        assert_eq!(
+            escape(link),
+            "see \\x1b]8;;https://example.com\\x1b\\\\x1b[4mdocs\\x1b[24m\\x1b]8;;\\x07 now"
+        );
+
+        assert_eq!(strip("\u{9b}33mwarn\u{9b}0m \x1b(Bok \x1b7saved\x1b8"), "warn ok saved");
+        assert!(matches!(strip("plain text"), Cow::Borrowed("plain text")));
+    }
+
+    #[test]
+    fn test_display_width() {
+        assert_eq!(display_width("WARN"), 4);
+        assert_eq!(display_width("\x1b[33m\u{26a0}\x1b[0m"), 1);
+        assert_eq!(display_width("\u{26a0}\u{fe0f}"), 2);
+        assert_eq!(display_width("\u{1f525} \u{65e5}\u{672c}"), 7);
+        assert_eq!(display_width("e\u{301}"), 1);
+    }
+
+    #[test]
+    fn test_split_at_display() {
+        assert_eq!(split_at_display("\x1b[1mbold\x1b[0m", 2), ("\x1b[1mbo", "ld\x1b[0m"));
+        // A wide character that would straddle the edge moves to the tail
+        assert_eq!(split_at_display("\u{65e5}\u{672c}\u{8a9e}", 3), ("\u{65e5}", "\u{672c}\u{8a9e}"));
+        assert_eq!(split_at_display("ok\u{26a0}\u{fe0f}", 3), ("ok", "\u{26a0}\u{fe0f}"));
+        assert_eq!(split_at_display("e\u{301}x", 1), ("e\u{301}", "x"));
+        assert_eq!(split_at_display("\u{1f525}", 1), ("\u{1f525}", ""));
+    }
+
+    #[test]
+    fn test_malformed_sequences() {
+        // Unterminated at the end of the message
+        assert_eq!(strip("red \x1b[31"), "red ");
+        assert_eq!(strip("title \x1b]0;half a title"), "title ");
+        assert_eq!(strip("trailing \x1b"), "trailing ");
+        assert_eq!(escape("trailing \x1b"), "trailing \\x1b");
+
+        // Cut short by a byte that can't continue them
+        assert_eq!(strip("\x1b[31\nnext line"), "\nnext line");
+        assert_eq!(strip("\x1b]0;title\x1b[1mbold"), "bold");
+        assert_eq!(strip("\x1b\u{e9}t\u{e9}"), "\u{e9}t\u{e9}");
+        assert_eq!(strip("\x1b[31m\u{1f600}\x1b[0m"), "\u{1f600}");
+        assert_eq!(escape("\x1b\x1b[0m"), "\\x1b\\x1b[0m");
+    }
+}
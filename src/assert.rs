@@ -0,0 +1,139 @@
+use crate::{ComponentArg, HorizonLogger, LogLevel};
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// What `log_assert!` does after logging a failed condition
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AssertAction {
+    /// Log and carry on
+    #[default]
+    LogOnly,
+    /// Log, flush sinks, then panic
+    Panic,
+    /// Log, flush sinks, then take the fatal path
+    Fatal,
+}
+
+/// Process-wide assertion action, stored as its discriminant
+static ASSERT_ACTION: AtomicU8 = AtomicU8::new(AssertAction::LogOnly as u8);
+
+/// Choose what failed `log_assert!` checks do, for the whole process
+pub fn set_assert_action(action: AssertAction) {
+    ASSERT_ACTION.store(action as u8, Ordering::Relaxed);
+}
+
+/// The assertion action currently in effect
+pub fn assert_action() -> AssertAction {
+    match ASSERT_ACTION.load(Ordering::Relaxed) {
+        1 => AssertAction::Panic,
+        2 => AssertAction::Fatal,
+        _ => AssertAction::LogOnly,
+    }
+}
+
+impl HorizonLogger {
+    /// Log a CRITICAL entry, flush sinks and call the fatal handler
+    ///
+    /// The default handler aborts the process; see `LoggerBuilder::fatal_handler`.
+    pub fn fatal(&self, component: impl ComponentArg, message: &str) -> ! {
+        self.critical(component, message);
+        self.die()
+    }
+
+    fn die(&self) -> ! {
+        self.flush();
+        (self.inner.fatal_handler)()
+    }
+
+    /// Called by `log_assert!` when its condition is false
+    #[doc(hidden)]
+    pub fn assert_failed(
+        &self,
+        level: LogLevel,
+        component: impl ComponentArg,
+        condition: &str,
+        file: &str,
+        line: u32,
+        message: &str,
+    ) {
+        let message = format!("assertion failed: `{}` at {}:{}: {}", condition, file, line, message);
+        self.log(level, component, &message);
+
+        match assert_action() {
+            AssertAction::LogOnly => {}
+            AssertAction::Panic => {
+                self.flush();
+                panic!("{}", message);
+            }
+            AssertAction::Fatal => self.die(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::CaptureLogger;
+    use std::cell::Cell;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    fn fatal_for_test() -> ! {
+        panic!("fatal handler called");
+    }
+
+    fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+        payload
+            .downcast_ref::<String>()
+            .cloned()
+            .or_else(|| payload.downcast_ref::<&str>().map(|s| s.to_string()))
+            .unwrap_or_default()
+    }
+
+    // The action is process-wide, so all three are exercised in one test
+    #[test]
+    fn test_assert_actions() {
+        let logger = CaptureLogger::from_builder(HorizonLogger::builder().fatal_handler(fatal_for_test));
+        let evaluated = Cell::new(0);
+        let check = |ok: bool| {
+            evaluated.set(evaluated.get() + 1);
+            ok
+        };
+        let formatted = Cell::new(0);
+        let id = || {
+            formatted.set(formatted.get() + 1);
+            7
+        };
+
+        assert_eq!(assert_action(), AssertAction::LogOnly);
+        crate::log_assert!(logger, "PHYSICS", check(true), "NaN position for entity {}", id());
+        assert_eq!((evaluated.get(), formatted.get()), (1, 0));
+        assert!(logger.entries().is_empty());
+
+        crate::log_assert!(logger, "PHYSICS", check(false), "NaN position for entity {}", id());
+        assert_eq!((evaluated.get(), formatted.get()), (2, 1));
+        let entry = logger.entries().pop().unwrap();
+        assert_eq!(entry.level, LogLevel::ERROR);
+        assert_eq!(entry.component, "PHYSICS");
+        assert!(entry.message.starts_with("assertion failed: `check(false)` at src/assert.rs:"));
+        assert!(entry.message.ends_with(": NaN position for entity 7"));
+
+        set_assert_action(AssertAction::Panic);
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            crate::log_assert_critical!(logger, "PHYSICS", 1 + 1 == 3);
+        }));
+        let message = panic_message(result.unwrap_err());
+        assert!(message.contains("`1 + 1 == 3`"), "{}", message);
+        assert_eq!(logger.entries().pop().unwrap().level, LogLevel::CRITICAL);
+
+        set_assert_action(AssertAction::Fatal);
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            crate::log_assert!(logger, "NET", false, "socket gone");
+        }));
+        assert_eq!(panic_message(result.unwrap_err()), "fatal handler called");
+        assert!(logger.entries().pop().unwrap().message.ends_with("socket gone"));
+
+        set_assert_action(AssertAction::LogOnly);
+        crate::log_debug_assert!(logger, "NET", check(false), "debug only");
+        let expected = if cfg!(debug_assertions) { 3 } else { 2 };
+        assert_eq!(evaluated.get(), expected);
+    }
+}
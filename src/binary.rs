@@ -0,0 +1,490 @@
+//! Append-only binary history format
+//!
+//! Much cheaper to load than text logs when millions of entries need to be
+//! re-read after a crash. All integers are little-endian.
+//!
+//! ```text
+//! file    := header record*
+//! header  := "HZLOG" version:u8 run_len:u32 run:utf8
+//! record  := body_len:u32 body crc32(body):u32
+//! body    := seq:u64 epoch_micros:i64 level:u8
+//!            component_len:u32 component:utf8
+//!            message_len:u32 message:utf8
+//! ```
+//!
+//! `run` is the run id of the first entry written, empty if the file was
+//! flushed before any; version 1 headers, which end after the version,
+//! are still read. Span ids and backtraces are not stored. A record whose
+//! checksum does not match, or that is cut short, ends reading; everything
+//! before it is kept.
+
+use crate::format::FormatOptions;
+use crate::sink::Sink;
+use crate::{LogEntry, LogLevel, Timestamp};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Identifies a binary history file
+const MAGIC: &[u8; 5] = b"HZLOG";
+
+/// Current format version
+const VERSION: u8 = 2;
+
+/// Longest run id accepted in a header when reading
+const MAX_RUN_LEN: usize = 1024;
+
+/// Field names reported by `detect_format`, in record order
+pub(crate) const FIELDS: &[&str] = &["seq", "ts", "level", "component", "msg"];
+
+/// Bytes of a record body before the variable-length strings
+const FIXED_BODY_LEN: usize = 8 + 8 + 1 + 4 + 4;
+
+/// Largest record body accepted when reading; anything bigger is corruption
+const MAX_BODY_LEN: usize = 16 * 1024 * 1024;
+
+/// Appends entries to a file in the binary history format
+pub struct BinarySink {
+    path: PathBuf,
+    file: Mutex<OpenFile>,
+}
+
+/// The file and whether its header is still to be written
+struct OpenFile {
+    writer: BufWriter<File>,
+    /// The file was empty when opened; written with the first entry, so it can carry its run id
+    needs_header: bool,
+}
+
+impl OpenFile {
+    /// The writer, after writing the header if this file still lacks one
+    fn writer(&mut self, run: Option<&str>) -> io::Result<&mut BufWriter<File>> {
+        if self.needs_header {
+            self.writer.write_all(&header(run.unwrap_or_default()))?;
+            self.needs_header = false;
+        }
+        Ok(&mut self.writer)
+    }
+}
+
+impl BinarySink {
+    /// Open `path` for appending, checking the header if the file is not new
+    pub fn new(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = open_append(&path)?;
+        Ok(BinarySink {
+            path,
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Path the sink writes to
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Open for appending, checking the header of a file that has one
+fn open_append(path: &Path) -> io::Result<OpenFile> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .read(true)
+        .append(true)
+        .open(path)?;
+
+    let needs_header = file.metadata()?.len() == 0;
+    if !needs_header {
+        read_header(&mut file)?;
+    }
+    Ok(OpenFile {
+        writer: BufWriter::new(file),
+        needs_header,
+    })
+}
+
+/// The header for a file started by an entry of run `run`
+fn header(run: &str) -> Vec<u8> {
+    let mut header = Vec::from(MAGIC.as_slice());
+    header.push(VERSION);
+    header.extend_from_slice(&(run.len() as u32).to_le_bytes());
+    header.extend_from_slice(run.as_bytes());
+    header
+}
+
+/// What a file's header says
+pub(crate) struct Header {
+    /// Run id of the first entry, if the header records one
+    pub(crate) run: Option<String>,
+    /// Bytes before the first record
+    pub(crate) len: u64,
+}
+
+pub(crate) fn read_header(reader: &mut impl Read) -> io::Result<Header> {
+    let mut header = [0u8; MAGIC.len() + 1];
+    reader.read_exact(&mut header)?;
+    if &header[..MAGIC.len()] != MAGIC {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "not a binary history file"));
+    }
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+    match header[MAGIC.len()] {
+        1 => Ok(Header {
+            run: None,
+            len: header.len() as u64,
+        }),
+        VERSION => {
+            let mut len = [0u8; 4];
+            reader.read_exact(&mut len)?;
+            let len = u32::from_le_bytes(len) as usize;
+            if len > MAX_RUN_LEN {
+                return Err(invalid("binary history run id too long"));
+            }
+            let mut run = vec![0u8; len];
+            reader.read_exact(&mut run)?;
+            let run = String::from_utf8(run).map_err(|_| invalid("binary history run id is not UTF-8"))?;
+            Ok(Header {
+                run: Some(run).filter(|run| !run.is_empty()),
+                len: (header.len() + 4 + len) as u64,
+            })
+        }
+        version => Err(invalid(&format!("unsupported binary history version {}", version))),
+    }
+}
+
+/// The format version if `bytes` start with a binary history header
+pub(crate) fn sniff_header(bytes: &[u8]) -> Option<u8> {
+    bytes.strip_prefix(MAGIC.as_slice())?.first().copied()
+}
+
+fn lock_error() -> io::Error {
+    io::Error::other("binary sink lock poisoned")
+}
+
+/// Encode one entry as a complete record
+pub(crate) fn encode(entry: &LogEntry) -> Vec<u8> {
+    let component = entry.component.as_bytes();
+    let message = entry.message.as_bytes();
+
+    let mut body = Vec::with_capacity(FIXED_BODY_LEN + component.len() + message.len());
+    body.extend_from_slice(&entry.seq.to_le_bytes());
+    body.extend_from_slice(&entry.timestamp.as_micros().to_le_bytes());
+    body.push(entry.level.to_u8());
+    body.extend_from_slice(&(component.len() as u32).to_le_bytes());
+    body.extend_from_slice(component);
+    body.extend_from_slice(&(message.len() as u32).to_le_bytes());
+    body.extend_from_slice(message);
+
+    let mut record = Vec::with_capacity(body.len() + 8);
+    record.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    record.extend_from_slice(&body);
+    record.extend_from_slice(&crc32(&body).to_le_bytes());
+    record
+}
+
+/// Decode a record body whose checksum has already been verified
+fn decode(body: &[u8]) -> Option<LogEntry> {
+    let mut cursor = body;
+    let seq = u64::from_le_bytes(take(&mut cursor, 8)?.try_into().ok()?);
+    let micros = i64::from_le_bytes(take(&mut cursor, 8)?.try_into().ok()?);
+    let level = LogLevel::from_u8(take(&mut cursor, 1)?[0])?;
+    let component = take_str(&mut cursor)?;
+    let message = take_str(&mut cursor)?;
+    if !cursor.is_empty() {
+        return None;
+    }
+
+    let mut entry = LogEntry::at(Timestamp::from_micros(micros), level, &component, &message);
+    entry.seq = seq;
+    Some(entry)
+}
+
+/// Decode the record at the front of `cursor`, checking its checksum, and advance past it
+pub(crate) fn decode_record(cursor: &mut &[u8]) -> Option<LogEntry> {
+    let len = u32::from_le_bytes(take(cursor, 4)?.try_into().ok()?) as usize;
+    if !(FIXED_BODY_LEN..=MAX_BODY_LEN).contains(&len) {
+        return None;
+    }
+    let body = take(cursor, len)?;
+    let checksum = u32::from_le_bytes(take(cursor, 4)?.try_into().ok()?);
+    if crc32(body) != checksum {
+        return None;
+    }
+    decode(body)
+}
+
+fn take<'a>(cursor: &mut &'a [u8], n: usize) -> Option<&'a [u8]> {
+    if cursor.len() < n {
+        return None;
+    }
+    let (head, rest) = cursor.split_at(n);
+    *cursor = rest;
+    Some(head)
+}
+
+fn take_str(cursor: &mut &[u8]) -> Option<String> {
+    let len = u32::from_le_bytes(take(cursor, 4)?.try_into().ok()?) as usize;
+    String::from_utf8(take(cursor, len)?.to_vec()).ok()
+}
+
+/// CRC-32 (IEEE), computed bitwise; records are small enough that a table isn't worth it
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+impl Sink for BinarySink {
+    fn write(&self, entry: &LogEntry, _options: &FormatOptions) -> io::Result<()> {
+        let record = encode(entry);
+        let mut file = self.file.lock().map_err(|_| lock_error())?;
+        file.writer(entry.run_id.as_deref())?.write_all(&record)
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.file.lock().map_err(|_| lock_error())?.writer(None)?.flush()
+    }
+
+    fn sync(&self) -> io::Result<()> {
+        let mut file = self.file.lock().map_err(|_| lock_error())?;
+        let writer = file.writer(None)?;
+        writer.flush()?;
+        writer.get_ref().sync_data()
+    }
+
+    fn reopen(&self) -> io::Result<()> {
+        let mut file = self.file.lock().map_err(|_| lock_error())?;
+        file.writer(None)?.flush()?;
+        *file = open_append(&self.path)?;
+        Ok(())
+    }
+}
+
+impl Drop for BinarySink {
+    fn drop(&mut self) {
+        if let Ok(file) = self.file.get_mut() {
+            let _ = file.writer(None).and_then(|writer| writer.flush());
+        }
+    }
+}
+
+/// Iterates the entries of a binary history file
+///
+/// Iteration stops at the first truncated or corrupt record; see
+/// `stopped_at` to tell that apart from a clean end of file.
+pub struct BinaryLogReader {
+    reader: BufReader<File>,
+    run: Option<String>,
+    offset: u64,
+    stopped_at: Option<u64>,
+    done: bool,
+}
+
+impl BinaryLogReader {
+    /// Open a file written by `BinarySink`, checking its header
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let header = read_header(&mut reader)?;
+        Ok(BinaryLogReader {
+            reader,
+            run: header.run,
+            offset: header.len,
+            stopped_at: None,
+            done: false,
+        })
+    }
+
+    /// Run id of the entry that started the file, if its header records one
+    pub fn run_id(&self) -> Option<&str> {
+        self.run.as_deref()
+    }
+
+    /// Byte offset of the record that could not be read, if reading stopped early
+    pub fn stopped_at(&self) -> Option<u64> {
+        self.stopped_at
+    }
+
+    /// Read the next record, `Ok(None)` at a clean end of file
+    fn read_record(&mut self) -> Result<Option<LogEntry>, ()> {
+        let mut len = [0u8; 4];
+        match read_full(&mut self.reader, &mut len) {
+            Ok(0) => return Ok(None),
+            Ok(4) => {}
+            _ => return Err(()),
+        }
+        let len = u32::from_le_bytes(len) as usize;
+        if !(FIXED_BODY_LEN..=MAX_BODY_LEN).contains(&len) {
+            return Err(());
+        }
+
+        let mut body = vec![0u8; len + 4];
+        self.reader.read_exact(&mut body).map_err(|_| ())?;
+        let checksum = u32::from_le_bytes(body[len..].try_into().map_err(|_| ())?);
+        let body = &body[..len];
+        if crc32(body) != checksum {
+            return Err(());
+        }
+
+        let entry = decode(body).ok_or(())?;
+        self.offset += (len + 8) as u64;
+        Ok(Some(entry))
+    }
+}
+
+/// Fill `buf` as far as possible, returning how many bytes were read
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+impl Iterator for BinaryLogReader {
+    type Item = LogEntry;
+
+    fn next(&mut self) -> Option<LogEntry> {
+        if self.done {
+            return None;
+        }
+        match self.read_record() {
+            Ok(Some(entry)) => Some(entry),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(()) => {
+                self.done = true;
+                self.stopped_at = Some(self.offset);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::escape::tests::awkward_string;
+    use crate::testing::fixtures::temp_path;
+    use crate::testing::CaptureLogger;
+    use proptest::prelude::*;
+
+    fn write_entries(path: &Path, count: usize) -> Vec<LogEntry> {
+        let logger = CaptureLogger::new();
+        let id = logger.add_sink(BinarySink::new(path).unwrap());
+        for i in 0..count {
+            logger.warn("GAME/COMBAT", &format!("hit {:02} for {:03} dmg ✓", i, i * 7));
+        }
+        logger.flush();
+        logger.remove_sink(id);
+        logger.get_history()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let path = temp_path("round_trip.hzlog");
+        let written = write_entries(&path, 3);
+
+        let mut reader = BinaryLogReader::open(&path).unwrap();
+        assert_eq!(reader.run_id(), written[0].run_id.as_deref());
+        let read: Vec<LogEntry> = reader.by_ref().collect();
+        assert_eq!(reader.stopped_at(), None);
+        assert_eq!(read.len(), 3);
+        for (a, b) in written.iter().zip(&read) {
+            assert_eq!(a.seq, b.seq);
+            assert_eq!(a.timestamp, b.timestamp);
+            assert_eq!(a.level, b.level);
+            assert_eq!(a.component, b.component);
+            assert_eq!(a.message, b.message);
+        }
+
+        // Reopening appends after the existing header
+        write_entries(&path, 2);
+        assert_eq!(BinaryLogReader::open(&path).unwrap().count(), 5);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    proptest! {
+        #[test]
+        fn test_strings_round_trip(component in awkward_string(), message in awkward_string()) {
+            let entry = LogEntry::at(Timestamp::from_micros(-1), LogLevel::DEBUG, &component, &message);
+            let record = encode(&entry);
+            let decoded = decode(&record[4..record.len() - 4]).unwrap();
+            prop_assert_eq!(&*decoded.component, component.as_str());
+            prop_assert_eq!(decoded.message, message);
+        }
+    }
+
+    #[test]
+    fn test_truncated_final_record_is_skipped() {
+        let path = temp_path("truncated.hzlog");
+        write_entries(&path, 4);
+        let len = std::fs::metadata(&path).unwrap().len();
+        OpenOptions::new().write(true).open(&path).unwrap().set_len(len - 3).unwrap();
+
+        let mut reader = BinaryLogReader::open(&path).unwrap();
+        assert_eq!(reader.by_ref().count(), 3);
+        assert!(reader.stopped_at().is_some());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_corruption_keeps_prior_records() {
+        let path = temp_path("corrupt.hzlog");
+        let written = write_entries(&path, 10);
+
+        let mut bytes = std::fs::read(&path).unwrap();
+        let header_len = header(written[0].run_id.as_deref().unwrap()).len();
+        let record_len = (bytes.len() - header_len) / 10;
+        let target = header_len + record_len * 6 + record_len / 2;
+        bytes[target] ^= 0x40;
+        std::fs::write(&path, &bytes).unwrap();
+
+        let mut reader = BinaryLogReader::open(&path).unwrap();
+        let read: Vec<LogEntry> = reader.by_ref().collect();
+        assert_eq!(read.len(), 6);
+        assert_eq!(read[5].message, "hit 05 for 035 dmg ✓");
+        assert_eq!(
+            reader.stopped_at(),
+            Some((header_len + record_len * 6) as u64)
+        );
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_reads_version_1_files() {
+        let path = temp_path("v1.hzlog");
+        let entry = LogEntry::at(Timestamp::from_micros(5), LogLevel::INFO, "GAME", "from v1");
+        let mut bytes = Vec::from(MAGIC.as_slice());
+        bytes.push(1);
+        bytes.extend_from_slice(&encode(&entry));
+        std::fs::write(&path, &bytes).unwrap();
+
+        let mut reader = BinaryLogReader::open(&path).unwrap();
+        assert_eq!(reader.run_id(), None);
+        assert_eq!(reader.next().unwrap().message, "from v1");
+        assert!(reader.next().is_none());
+        assert_eq!(reader.stopped_at(), None);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_rejects_foreign_files() {
+        let path = temp_path("foreign.hzlog");
+        std::fs::write(&path, b"plain text log\n").unwrap();
+        assert!(BinaryLogReader::open(&path).is_err());
+        assert!(BinarySink::new(&path).is_err());
+        let _ = std::fs::remove_file(&path);
+    }
+}
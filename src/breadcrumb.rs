@@ -0,0 +1,166 @@
+//! Notes kept on the logging thread until its next error, see `HorizonLogger::breadcrumb`
+
+use crate::{ComponentArg, HorizonLogger, LogLevel, Timestamp};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+
+/// Breadcrumbs kept per thread; the oldest is dropped beyond this
+pub(crate) const MAX_BREADCRUMBS: usize = 30;
+
+thread_local! {
+    /// This thread's breadcrumbs since its last error, oldest first
+    static BREADCRUMBS: RefCell<VecDeque<Breadcrumb>> = const { RefCell::new(VecDeque::new()) };
+}
+
+/// A note left with `HorizonLogger::breadcrumb`, carried by the next error on its thread
+#[derive(Debug, Clone, PartialEq)]
+pub struct Breadcrumb {
+    pub timestamp: Timestamp,
+    pub component: Cow<'static, str>,
+    pub message: String,
+}
+
+/// Take this thread's breadcrumbs for an entry at `level`; only ERROR and CRITICAL get them
+pub(crate) fn take_for(level: LogLevel) -> Vec<Breadcrumb> {
+    if level < LogLevel::ERROR {
+        return Vec::new();
+    }
+    BREADCRUMBS
+        .try_with(|crumbs| crumbs.try_borrow_mut().map(|mut crumbs| crumbs.drain(..).collect()).unwrap_or_default())
+        .unwrap_or_default()
+}
+
+impl HorizonLogger {
+    /// Note what the current thread is doing, for the next ERROR or CRITICAL it logs
+    ///
+    /// Breadcrumbs are written nowhere by themselves. The last 30 on each
+    /// thread are kept and attached to the thread's next ERROR or CRITICAL
+    /// entry, which takes them: they are shown below it on the console and
+    /// written as `breadcrumbs` in JSON, and are in `LogEntry::breadcrumbs`.
+    /// They are kept per thread, not per logger.
+    pub fn breadcrumb(&self, component: impl ComponentArg, message: &str) {
+        if self.inner.kill_switch.is_off() {
+            return;
+        }
+        let crumb = Breadcrumb {
+            timestamp: self.inner.clock.now(),
+            component: component.to_cow(),
+            message: message.to_string(),
+        };
+        let _ = BREADCRUMBS.try_with(|crumbs| {
+            if let Ok(mut crumbs) = crumbs.try_borrow_mut() {
+                if crumbs.len() == MAX_BREADCRUMBS {
+                    crumbs.pop_front();
+                }
+                crumbs.push_back(crumb);
+            }
+        });
+    }
+
+    /// Drop the current thread's breadcrumbs without attaching them to anything
+    pub fn clear_breadcrumbs(&self) {
+        let _ = BREADCRUMBS.try_with(|crumbs| crumbs.borrow_mut().clear());
+    }
+
+    /// The current thread's breadcrumbs waiting for an error, oldest first
+    pub fn pending_breadcrumbs(&self) -> Vec<Breadcrumb> {
+        BREADCRUMBS
+            .try_with(|crumbs| crumbs.borrow().iter().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::console::tests::SharedBuf;
+    use crate::console::Console;
+    use crate::testing::CaptureLogger;
+    use crate::{ConsoleFields, LogEntry};
+    use std::sync::{Arc, Barrier};
+    use std::thread;
+
+    fn crumbs(entry: &LogEntry) -> Vec<&str> {
+        entry.breadcrumbs.iter().map(|crumb| crumb.message.as_str()).collect()
+    }
+
+    #[test]
+    fn test_breadcrumbs_attach_to_the_next_error_on_their_thread() {
+        let logger = CaptureLogger::new();
+        let barrier = Arc::new(Barrier::new(2));
+        let threads: Vec<_> = ["a", "b"]
+            .into_iter()
+            .map(|name| {
+                let (logger, barrier) = (logger.clone(), barrier.clone());
+                thread::spawn(move || {
+                    logger.breadcrumb("GAME", &format!("{} entered dungeon 3", name));
+                    barrier.wait();
+                    logger.breadcrumb("GAME", &format!("{} opened chest", name));
+                    logger.warn("GAME", &format!("{} low health", name));
+                    barrier.wait();
+                    logger.error("GAME", &format!("{} died", name));
+                    barrier.wait();
+                    logger.breadcrumb("GAME", &format!("{} respawned", name));
+                    logger.error("GAME", &format!("{} died again", name));
+                    logger.critical("GAME", &format!("{} gave up", name));
+                    logger.breadcrumb("GAME", &format!("{} quit", name));
+                    if name == "a" {
+                        logger.clear_breadcrumbs();
+                    }
+                    barrier.wait();
+                    logger.error("GAME", &format!("{} crashed", name));
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let entries = logger.entries();
+        let find = |message: &str| entries.iter().find(|entry| entry.message == message).unwrap();
+        for name in ["a", "b"] {
+            let attached = |message: &str| crumbs(find(&format!("{} {}", name, message)));
+            assert!(attached("low health").is_empty());
+            assert_eq!(attached("died"), [format!("{} entered dungeon 3", name), format!("{} opened chest", name)]);
+            assert_eq!(attached("died again"), [format!("{} respawned", name)]);
+            assert!(attached("gave up").is_empty());
+        }
+        assert!(crumbs(find("a crashed")).is_empty());
+        assert_eq!(crumbs(find("b crashed")), ["b quit"]);
+        assert!(logger.pending_breadcrumbs().is_empty());
+    }
+
+    #[test]
+    fn test_breadcrumbs_are_bounded_and_shown() {
+        let buf = SharedBuf::default();
+        let logger = HorizonLogger::builder()
+            .announce_run(false)
+            .console(Console::new(Box::new(buf.clone()), false))
+            .console_fields(ConsoleFields::COMPONENT | ConsoleFields::MESSAGE)
+            .build();
+        for n in 0..MAX_BREADCRUMBS + 5 {
+            logger.breadcrumb("LOBBY", &format!("step {}", n));
+        }
+        assert_eq!(logger.pending_breadcrumbs().len(), MAX_BREADCRUMBS);
+        assert_eq!(logger.pending_breadcrumbs()[0].message, "step 5");
+        logger.clear_breadcrumbs();
+
+        logger.breadcrumb("LOBBY", "joined queue");
+        logger.breadcrumb("MATCH", "found match");
+        logger.error("NET", "handshake failed");
+        let console = buf.contents();
+        let lines: Vec<_> = console.lines().collect();
+        assert_eq!(lines[0], "[NET] handshake failed");
+        assert!(lines[1].starts_with("    ") && lines[1].ends_with(" [LOBBY] joined queue"), "{}", lines[1]);
+        assert!(lines[2].ends_with(" [MATCH] found match"), "{}", lines[2]);
+        assert_eq!(lines.len(), 3);
+
+        let entry = &logger.get_history()[0];
+        let json: serde_json::Value = serde_json::from_str(&entry.to_json()).unwrap();
+        assert_eq!(json["breadcrumbs"][1]["component"], "MATCH");
+        assert_eq!(json["breadcrumbs"][1]["message"], "found match");
+        assert!(json["breadcrumbs"][0]["timestamp"].is_string());
+        assert!(!entry.to_string().contains("joined queue"));
+    }
+}
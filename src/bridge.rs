@@ -0,0 +1,131 @@
+//! Installing a logger as the global `tracing` subscriber or `log` logger
+//!
+//! Other crates may install theirs first, from their own lazy statics, or
+//! try to after us. Neither order fails: each bridge reports how far it got
+//! as a `BridgeMode`, so an application can warn when its logger misses
+//! events. Events sent before a bridge is installed never reach it.
+
+#[cfg(feature = "log")]
+use crate::component::BorrowedComponent;
+use crate::HorizonLogger;
+#[cfg(feature = "log")]
+use crate::LogLevel;
+
+/// How a bridge was installed, see `init_tracing_bridge` and `init_log_bridge`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BridgeMode {
+    /// As the global subscriber or logger
+    Global,
+    /// Into the `HorizonLayerSlot` of the subscriber that was already global
+    Layered,
+    /// Not at all: another subscriber or logger is global and there is no way in
+    Unavailable,
+}
+
+/// Send `tracing` events at INFO and above to `logger`, as the global subscriber if there is none yet
+///
+/// Also routes `log` records into `tracing` when the `log` global is still
+/// free. If a subscriber is already global, `logger` fills the
+/// `HorizonLayerSlot` in it and gets the events that subscriber's filters
+/// let through; without a slot, or with the slot already filled, nothing
+/// is installed.
+#[cfg(feature = "tracing-bridge")]
+pub fn init_tracing_bridge(logger: HorizonLogger) -> BridgeMode {
+    use crate::{HorizonLayer, HorizonLayerSlot};
+    use tracing::level_filters::LevelFilter;
+    use tracing_log::AsLog;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    let subscriber = tracing_subscriber::registry()
+        .with(tracing_subscriber::EnvFilter::new("info"))
+        .with(HorizonLayer::new(logger.clone()));
+    if tracing::subscriber::set_global_default(subscriber).is_ok() {
+        // Where nothing has claimed the `log` global either, route `log` records here too
+        let _ = tracing_log::LogTracer::builder()
+            .with_max_level(LevelFilter::current().as_log())
+            .init();
+        return BridgeMode::Global;
+    }
+    let has_slot = tracing::dispatcher::get_default(|dispatch| dispatch.is::<HorizonLayerSlot>());
+    if has_slot && HorizonLayerSlot::fill(HorizonLayer::new(logger)) {
+        BridgeMode::Layered
+    } else {
+        BridgeMode::Unavailable
+    }
+}
+
+/// Forwards `log` records to a logger, and to a second `log::Log` if given one
+///
+/// Records are logged under their target. Install it with `install`, or
+/// use `init_log_bridge` when there is nothing to forward to.
+#[cfg(feature = "log")]
+pub struct LogBridge {
+    logger: HorizonLogger,
+    also: Option<Box<dyn log::Log>>,
+}
+
+#[cfg(feature = "log")]
+impl LogBridge {
+    pub fn new(logger: HorizonLogger) -> Self {
+        LogBridge { logger, also: None }
+    }
+
+    /// Forward every record to `other` too, e.g. the logger another crate would have installed
+    pub fn also_to(mut self, other: Box<dyn log::Log>) -> Self {
+        self.also = Some(other);
+        self
+    }
+
+    /// Make this the global `log` logger, unless one is already set
+    ///
+    /// The `log` crate has no way to reach a logger set before, so that
+    /// one keeps all records and this returns `Unavailable`.
+    pub fn install(self) -> BridgeMode {
+        match log::set_boxed_logger(Box::new(self)) {
+            Ok(()) => {
+                log::set_max_level(log::LevelFilter::Trace);
+                BridgeMode::Global
+            }
+            Err(_) => BridgeMode::Unavailable,
+        }
+    }
+}
+
+#[cfg(feature = "log")]
+impl log::Log for LogBridge {
+    fn enabled(&self, metadata: &log::Metadata<'_>) -> bool {
+        self.logger.enabled(LogLevel::from_log(metadata.level()))
+            || self.also.as_ref().is_some_and(|also| also.enabled(metadata))
+    }
+
+    fn log(&self, record: &log::Record<'_>) {
+        let level = LogLevel::from_log(record.level());
+        if self.logger.enabled(level) {
+            let component = BorrowedComponent(record.target());
+            match record.args().as_str() {
+                Some(message) => self.logger.log(level, component, message),
+                None => self.logger.log(level, component, &record.args().to_string()),
+            }
+        }
+        if let Some(also) = &self.also {
+            also.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.logger.flush();
+        if let Some(also) = &self.also {
+            also.flush();
+        }
+    }
+}
+
+/// Send `log` records to `logger`, as the global `log` logger if there is none yet
+///
+/// See `LogBridge::install`. A logger installed by `init` or
+/// `init_tracing_bridge` already gets `log` records through `tracing`, so
+/// this returns `Unavailable` after them.
+#[cfg(feature = "log")]
+pub fn init_log_bridge(logger: HorizonLogger) -> BridgeMode {
+    LogBridge::new(logger).install()
+}
@@ -0,0 +1,732 @@
+use crate::ansi::AnsiPolicy;
+use crate::clock::{Clock, SystemClock};
+use crate::console::{Banner, Bell, CoarseTime, Console, ConsoleColor, ConsoleFields, ConsoleWrap, ConsoleWriter};
+use crate::empty::{EmptyMessagePolicy, EmptyMessages};
+use crate::format::{FormatOptions, MachineTimestamp};
+use crate::glyph::{GlyphConfig, GlyphSet, DEFAULT_GLYPHS};
+use crate::id::{IdGenerator, RandomIdGenerator};
+use crate::kill_switch::KillSwitch;
+use crate::pattern::Pattern;
+use crate::persist::{PersistPolicy, Persistence};
+use crate::preinit::{self, PreinitBuffer};
+use crate::pretty::PrettyLimits;
+use crate::queue::{BackpressurePolicy, SinkQueue};
+use crate::sink::{Sink, SinkOrdering};
+use crate::skew::SkewDetector;
+use crate::template::append_fields;
+use crate::time_render::{HumanTime, TimestampRenderer};
+use crate::unicode::UnicodePolicy;
+use crate::volume::{VolumeLimits, VolumeMonitor};
+use crate::{history, pin, profile, size, stats, FieldValue, HorizonLogger, HorizonLoggerError, LogLevel, LoggerInner};
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+/// Configures a `HorizonLogger` before it is created
+pub struct LoggerBuilder {
+    clock: Box<dyn Clock>,
+    ids: Box<dyn IdGenerator>,
+    format: FormatOptions,
+    backtrace_level: Option<LogLevel>,
+    min_level: LogLevel,
+    pretty: PrettyLimits,
+    console: Option<Console>,
+    console_writer: Option<Box<dyn ConsoleWriter>>,
+    console_fields: ConsoleFields,
+    console_pattern: Option<Pattern>,
+    console_color: ConsoleColor,
+    console_ansi: AnsiPolicy,
+    console_unicode: UnicodePolicy,
+    console_wrap: ConsoleWrap,
+    glyphs: GlyphConfig,
+    coarse_time: Option<Duration>,
+    precise_time_from: LogLevel,
+    console_timestamps: Arc<dyn TimestampRenderer>,
+    bell: Option<Bell>,
+    banner: Option<Banner>,
+    source_snippets: bool,
+    keep_history: bool,
+    global_history: bool,
+    dedup_history: bool,
+    history_max_age: Option<Duration>,
+    persist_history: Option<(PathBuf, PersistPolicy)>,
+    pin_budget: usize,
+    auto_pin: Option<LogLevel>,
+    volume_limits: Option<VolumeLimits>,
+    async_sinks: Option<BackpressurePolicy>,
+    sink_ordering: SinkOrdering,
+    preinit_buffer: usize,
+    group_max_entries: usize,
+    self_profiling: bool,
+    /// Sinks to register once built, each with whether it is optional
+    sinks: Vec<(Arc<dyn Sink>, bool)>,
+    verify_on_build: bool,
+    run_id: Option<String>,
+    /// Fields for every entry, read from the environment in `build`
+    static_fields: Vec<(&'static str, StaticValue)>,
+    empty_messages: EmptyMessagePolicy,
+    clock_skew: Option<Duration>,
+    announce_run: bool,
+    fatal_handler: fn() -> !,
+}
+
+impl LoggerBuilder {
+    pub(crate) fn new() -> Self {
+        LoggerBuilder {
+            clock: Box::new(SystemClock),
+            ids: Box::new(RandomIdGenerator),
+            format: FormatOptions::default(),
+            backtrace_level: None,
+            min_level: LogLevel::DEBUG,
+            pretty: PrettyLimits::default(),
+            console: None,
+            console_writer: None,
+            console_fields: ConsoleFields::default(),
+            console_pattern: None,
+            console_color: ConsoleColor::Auto,
+            console_ansi: AnsiPolicy::Preserve,
+            console_unicode: UnicodePolicy::Wide,
+            console_wrap: ConsoleWrap::Off,
+            glyphs: GlyphConfig::default(),
+            coarse_time: None,
+            precise_time_from: LogLevel::WARN,
+            console_timestamps: Arc::new(HumanTime),
+            bell: None,
+            banner: None,
+            source_snippets: false,
+            keep_history: true,
+            global_history: false,
+            dedup_history: false,
+            history_max_age: None,
+            persist_history: None,
+            pin_budget: pin::DEFAULT_PIN_BUDGET,
+            auto_pin: None,
+            volume_limits: None,
+            async_sinks: None,
+            sink_ordering: SinkOrdering::Strict,
+            preinit_buffer: preinit::DEFAULT_PREINIT_BUFFER,
+            group_max_entries: 256,
+            self_profiling: false,
+            sinks: Vec::new(),
+            verify_on_build: false,
+            run_id: None,
+            static_fields: Vec::new(),
+            empty_messages: EmptyMessagePolicy::Allow,
+            clock_skew: None,
+            announce_run: true,
+            fatal_handler: std::process::abort,
+        }
+    }
+
+    /// Use a custom time source for entry timestamps
+    pub fn clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// How timestamps are written in JSON and logfmt output
+    pub fn machine_timestamp(mut self, style: MachineTimestamp) -> Self {
+        self.format.machine_timestamp = style;
+        self
+    }
+
+    /// Drop entries below `level`
+    pub fn min_level(mut self, level: LogLevel) -> Self {
+        self.min_level = level;
+        self
+    }
+
+    /// Nesting depth and length at which `debug_pretty` output is cut off
+    pub fn pretty_limits(mut self, max_depth: usize, max_len: usize) -> Self {
+        self.pretty = PrettyLimits { max_depth, max_len };
+        self
+    }
+
+    /// Make up run ids and new correlation ids with `ids`; 8 random hex digits by default
+    pub fn id_generator(mut self, ids: impl IdGenerator + 'static) -> Self {
+        self.ids = Box::new(ids);
+        self
+    }
+
+    /// Use a caller-supplied run id instead of one from the `IdGenerator`
+    pub fn run_id(mut self, id: impl Into<String>) -> Self {
+        self.run_id = Some(id.into());
+        self
+    }
+
+    /// Attach `name=value` to every entry, e.g. the service name
+    ///
+    /// Static fields come before an entry's own fields, and show in JSON and
+    /// logfmt output and `LogEntry::fields`. Text output leaves them out,
+    /// unless a console pattern has `{static_fields}`.
+    pub fn static_field(mut self, name: &'static str, value: impl Into<FieldValue>) -> Self {
+        self.static_fields.push((name, StaticValue::Literal(value.into())));
+        self
+    }
+
+    /// Attach fields read from environment variables, given as `(field, variable)`, to every entry
+    ///
+    /// Variables are read once, when the logger is built; a field whose
+    /// variable is unset is left out. See `static_field`.
+    pub fn static_fields_from_env(mut self, mappings: &[(&'static str, &str)]) -> Self {
+        for &(name, var) in mappings {
+            self.static_fields.push((name, StaticValue::Env(var.to_string(), None)));
+        }
+        self
+    }
+
+    /// Attach a field read from environment variable `var`, or `default` if it is unset
+    pub fn static_field_from_env_or(mut self, name: &'static str, var: &str, default: impl Into<String>) -> Self {
+        self.static_fields.push((name, StaticValue::Env(var.to_string(), Some(default.into()))));
+        self
+    }
+
+    /// What happens to entries whose message is empty or only whitespace; logged as they are by default
+    pub fn empty_message_policy(mut self, policy: EmptyMessagePolicy) -> Self {
+        self.empty_messages = policy;
+        self
+    }
+
+    /// Note wall-clock jumps of more than `threshold`, such as NTP steps or a suspend (off by default)
+    ///
+    /// Each entry's wall-clock time is compared with the clock's monotonic
+    /// time since the entry before. When they disagree by more than
+    /// `threshold`, an INFO under `LOGGER` such as `wall clock jumped
+    /// +3602.4s vs 2.1s monotonic` is logged first and the entry gets the
+    /// field `clock_skew=true`.
+    pub fn detect_clock_skew(mut self, threshold: Duration) -> Self {
+        self.clock_skew = Some(threshold);
+        self
+    }
+
+    /// Whether to log the run id as an INFO entry before the first entry (on by default)
+    pub fn announce_run(mut self, enabled: bool) -> Self {
+        self.announce_run = enabled;
+        self
+    }
+
+    /// Whether to keep recent entries in the in-memory history (on by default)
+    ///
+    /// Without history, each entry is only printed and handed to sinks.
+    pub fn history(mut self, enabled: bool) -> Self {
+        self.keep_history = enabled;
+        self
+    }
+
+    /// Keep entries in the process-wide history that every `HorizonLogger::new()` logger shares
+    ///
+    /// Entries logged through any of these loggers show up in the history
+    /// of all of them, numbered in one sequence. The shared history has the
+    /// default settings: `dedup_history`, `history_max_age`, `pin_budget`
+    /// and `auto_pin` don't change it.
+    pub fn global_history(mut self) -> Self {
+        self.global_history = true;
+        self
+    }
+
+    /// Fold an entry into the newest history entry when level, component and message match
+    ///
+    /// Only history is affected; the console and sinks still see every
+    /// occurrence. Folded entries carry a `repeat_count` and `last_timestamp`.
+    pub fn dedup_history(mut self, enabled: bool) -> Self {
+        self.dedup_history = enabled;
+        self
+    }
+
+    /// Evict history entries once their last occurrence is older than `max_age`
+    ///
+    /// Applies on top of the count limit and is enforced whenever an entry
+    /// is stored or the history is read, so no background thread is needed.
+    /// See `HorizonLogger::prune_history_now`.
+    pub fn history_max_age(mut self, max_age: Duration) -> Self {
+        self.history_max_age = Some(max_age);
+        self
+    }
+
+    /// Save the history to `path` as `policy` says, and load the previous session's back at startup
+    ///
+    /// Loaded entries come first in the history, marked with the field
+    /// `prev_session=true` and their original `seq` as `original_seq`,
+    /// and have the previous run's id; see `HistoryQuery::previous_session`.
+    /// They keep their time, level, component and message but no other
+    /// fields, and are never written to sinks. A file that can't be read
+    /// or is from another version is discarded with a WARN under `LOGGER`.
+    pub fn persist_history(mut self, path: impl AsRef<Path>, policy: PersistPolicy) -> Self {
+        self.persist_history = Some((path.as_ref().to_path_buf(), policy));
+        self
+    }
+
+    /// Keep at most `max_pinned` entries pinned with `HorizonLogger::pin_entry`; 50 by default
+    pub fn pin_budget(mut self, max_pinned: usize) -> Self {
+        self.pin_budget = max_pinned;
+        self
+    }
+
+    /// Pin every entry at or above `level` as it is stored, keeping at most `max_pinned` pins
+    ///
+    /// Once the budget is used up each new pin releases the oldest one, as
+    /// with `HorizonLogger::pin_entry`. Entries under `LOGGER` are not pinned.
+    pub fn auto_pin(mut self, level: LogLevel, max_pinned: usize) -> Self {
+        self.auto_pin = Some(level);
+        self.pin_budget = max_pinned;
+        self
+    }
+
+    /// Keep up to `capacity` entries logged before the first sink (default 500), and replay them into it
+    ///
+    /// Once full the oldest are dropped, and the replay starts with a WARN
+    /// saying how many. 0 keeps none; a logger that will never get a sink
+    /// can also let them go with `HorizonLogger::discard_preinit_buffer`.
+    pub fn preinit_buffer(mut self, capacity: usize) -> Self {
+        self.preinit_buffer = capacity;
+        self
+    }
+
+    /// Flush a `LogGroup` early once it has buffered `max` entries (default 256)
+    pub fn group_max_entries(mut self, max: usize) -> Self {
+        self.group_max_entries = max.max(1);
+        self
+    }
+
+    /// Time the logger's own work in each log call, see `HorizonLogger::profile_report`
+    ///
+    /// Costs a few `Instant::now()` calls per entry; nothing when off, the default.
+    pub fn self_profiling(mut self, enabled: bool) -> Self {
+        self.self_profiling = enabled;
+        self
+    }
+
+    /// Register `sink` when the logger is built, see `HorizonLogger::add_sink`
+    pub fn sink(mut self, sink: impl Sink + 'static) -> Self {
+        self.sinks.push((Arc::new(sink), false));
+        self
+    }
+
+    /// Register `sink` as optional when the logger is built, see `HorizonLogger::add_optional_sink`
+    pub fn optional_sink(mut self, sink: impl Sink + 'static) -> Self {
+        self.sinks.push((Arc::new(sink), true));
+        self
+    }
+
+    /// Run `HorizonLogger::self_test` once the sinks are registered
+    ///
+    /// `try_build` then returns the error of a failed test, and `build` panics with it.
+    pub fn verify_on_build(mut self, enabled: bool) -> Self {
+        self.verify_on_build = enabled;
+        self
+    }
+
+    /// Watch logging volume, warning under `LOGGER` when a limit is exceeded
+    ///
+    /// See `HorizonLogger::volume_status` for the measured rates.
+    pub fn volume_limits(mut self, limits: VolumeLimits) -> Self {
+        self.volume_limits = Some(limits);
+        self
+    }
+
+    /// Write sinks from a background thread, shedding lower levels when they fall behind
+    ///
+    /// The logging thread only queues entries for the sinks; see
+    /// `BackpressurePolicy` for what happens as the queue fills, and
+    /// `HorizonLogger::queue_status` for its depth and drop counts. `flush`
+    /// waits for the queue to drain.
+    pub fn async_sinks(mut self, policy: BackpressurePolicy) -> Self {
+        self.async_sinks = Some(policy);
+        self
+    }
+
+    /// Whether every sink sees entries in the same order; `SinkOrdering::Strict` by default
+    ///
+    /// `SinkOrdering::Independent` needs `async_sinks`, or building fails.
+    pub fn sink_ordering(mut self, ordering: SinkOrdering) -> Self {
+        self.sink_ordering = ordering;
+        self
+    }
+
+    /// Capture a backtrace for entries at or above `level`
+    ///
+    /// Capturing is expensive, so keep the threshold high. Backtraces are
+    /// always included in JSON output but only printed on the console when
+    /// `RUST_BACKTRACE` is set.
+    pub fn capture_backtrace(mut self, level: LogLevel) -> Self {
+        self.backtrace_level = Some(level);
+        self
+    }
+
+    /// Choose which fields the console line shows; the message is always included
+    pub fn console_fields(mut self, fields: ConsoleFields) -> Self {
+        self.console_fields = fields;
+        self
+    }
+
+    /// Lay console lines out with `pattern` instead of `console_fields`; lines are not colored
+    pub fn console_pattern(mut self, pattern: Pattern) -> Self {
+        self.console_pattern = Some(pattern);
+        self
+    }
+
+    /// Whether console lines are colored; `ConsoleColor::Auto` by default
+    pub fn console_color(mut self, color: ConsoleColor) -> Self {
+        self.console_color = color;
+        self
+    }
+
+    /// What the console does with ANSI escapes already in messages; they are written through by default
+    pub fn console_ansi_policy(mut self, policy: AnsiPolicy) -> Self {
+        self.console_ansi = policy;
+        self
+    }
+
+    /// How the console writes characters a legacy Windows console's code page can't show
+    ///
+    /// Only applies when stdout is such a console, with the `windows-console`
+    /// feature; `UnicodePolicy::Wide` by default. History, files and machine
+    /// formats always keep the original UTF-8.
+    pub fn console_unicode(mut self, policy: UnicodePolicy) -> Self {
+        self.console_unicode = policy;
+        self
+    }
+
+    /// Wrap long console messages at word boundaries; they are not wrapped by default
+    pub fn console_wrap(mut self, wrap: ConsoleWrap) -> Self {
+        self.console_wrap = wrap;
+        self
+    }
+
+    /// Show `glyph` in a console column after the level for entries at `level`
+    ///
+    /// The column is as wide as the widest glyph, counting wide characters
+    /// as two, and is blank for entries without one. Outside ASCII, the glyph
+    /// is replaced by its `level_glyph_fallback` where the console can't be
+    /// trusted with Unicode, see `console_glyphs`. Files, JSON and history
+    /// never get glyphs.
+    pub fn level_glyph(mut self, level: LogLevel, glyph: &str) -> Self {
+        self.glyphs.level(level, glyph);
+        self
+    }
+
+    /// Show `ascii` for `level` when Unicode glyphs aren't shown; `.`, `+`, `!`, `x` or `X` if not set
+    pub fn level_glyph_fallback(mut self, level: LogLevel, ascii: &str) -> Self {
+        self.glyphs.level_fallback(level, ascii);
+        self
+    }
+
+    /// Glyphs ✔, ⚠, ✖ and ☠ for INFO, WARN, ERROR and CRITICAL, falling back to `+`, `!`, `x` and `X`
+    pub fn default_level_glyphs(mut self) -> Self {
+        for (level, glyph, ascii) in DEFAULT_GLYPHS {
+            self.glyphs.level(level, glyph);
+            self.glyphs.level_fallback(level, ascii);
+        }
+        self
+    }
+
+    /// Show `glyph` for entries under `component` and its subcomponents, in place of their level's glyph
+    ///
+    /// `ascii` is shown instead when Unicode glyphs aren't; an empty one
+    /// leaves the column blank. The longest matching component wins.
+    pub fn component_glyph(mut self, component: &str, glyph: &str, ascii: &str) -> Self {
+        self.glyphs.component(component, glyph, ascii);
+        self
+    }
+
+    /// Whether console glyphs are shown as Unicode or their ASCII fallbacks
+    ///
+    /// `GlyphSet::Auto` by default: ASCII on a legacy Windows console, or
+    /// elsewhere unless `LC_ALL`, `LC_CTYPE` or `LANG` names a UTF-8 locale.
+    /// Decided once, when the logger is built.
+    pub fn console_glyphs(mut self, set: GlyphSet) -> Self {
+        self.glyphs.set(set);
+        self
+    }
+
+    /// Render console times with `renderer`, e.g. in a locale's order; sinks keep their own
+    pub fn console_timestamps(mut self, renderer: impl TimestampRenderer + 'static) -> Self {
+        self.console_timestamps = Arc::new(renderer);
+        self
+    }
+
+    /// Show console times to `granularity`, formatting one per window instead of one per entry
+    ///
+    /// Formatting the local time is a sizeable part of writing a console
+    /// line; at tens of thousands of entries a second, entries in the same
+    /// window (e.g. 10 ms) share the text, showing the window's start. WARN
+    /// and above still show their own time, see `console_precise_time_from`.
+    /// Entries keep their exact time for history, sinks and pattern layouts.
+    pub fn console_coarse_time(mut self, granularity: Duration) -> Self {
+        self.coarse_time = Some(granularity);
+        self
+    }
+
+    /// Show the exact time for console lines at or above `level` under `console_coarse_time`
+    pub fn console_precise_time_from(mut self, level: LogLevel) -> Self {
+        self.precise_time_from = level;
+        self
+    }
+
+    /// Ring the terminal bell after console lines at or above `level`
+    ///
+    /// Rings at most once per `bell_interval` (10 seconds by default), and
+    /// never when stdout is not a terminal. Nothing but the console sees it.
+    pub fn bell_on(mut self, level: LogLevel) -> Self {
+        self.bell = Some(Bell {
+            level,
+            ..self.bell.unwrap_or_else(default_bell)
+        });
+        self
+    }
+
+    /// Minimum time between two bells; has no effect without `bell_on`
+    pub fn bell_interval(mut self, interval: Duration) -> Self {
+        if let Some(bell) = &mut self.bell {
+            bell.interval = interval;
+        }
+        self
+    }
+
+    /// Send a desktop notification (OSC 9) with the message along with each bell
+    ///
+    /// Terminals without OSC 9 support ignore it. Has no effect without `bell_on`.
+    pub fn bell_notification(mut self, enabled: bool) -> Self {
+        if let Some(bell) = &mut self.bell {
+            bell.notify = enabled;
+        }
+        self
+    }
+
+    /// Print console entries at or above `level` framed in a banner
+    ///
+    /// The message gets a line of its own between rules as wide as the
+    /// terminal, capped at `banner_width` (80 by default), with the
+    /// timestamp, thread, component and correlation id below it. Sinks and
+    /// history still get the entry as a single record.
+    pub fn banner_on(mut self, level: LogLevel) -> Self {
+        self.banner = Some(Banner {
+            level,
+            ..self.banner.unwrap_or_else(default_banner)
+        });
+        self
+    }
+
+    /// Widest a banner gets, and its width when stdout is not a terminal; has no effect without `banner_on`
+    pub fn banner_width(mut self, max_width: usize) -> Self {
+        if let Some(banner) = &mut self.banner {
+            banner.max_width = max_width;
+        }
+        self
+    }
+
+    /// Layout of text lines written by sinks that have no pattern of their own
+    pub fn text_pattern(mut self, pattern: Pattern) -> Self {
+        self.format.text_pattern = Some(pattern);
+        self
+    }
+
+    /// Called after `fatal` has logged and flushed; aborts the process by default
+    pub fn fatal_handler(mut self, handler: fn() -> !) -> Self {
+        self.fatal_handler = handler;
+        self
+    }
+
+    /// Show the source line of ERROR and CRITICAL entries on the console, with a line either side
+    ///
+    /// For local development: does nothing unless built with debug
+    /// assertions. Entries carry their call site when logged with `error`
+    /// or `critical` (also on a `ComponentLogger` and through the macros)
+    /// or from `tracing`. Source files are read relative to the working
+    /// directory and cached; entries whose file can't be read are shown
+    /// without. Sink output and history are unchanged.
+    pub fn source_snippets(mut self, enabled: bool) -> Self {
+        self.source_snippets = enabled;
+        self
+    }
+
+    /// Hand console entries to `writer` instead of printing them to stdout
+    ///
+    /// For progress bar libraries that need log lines printed above their
+    /// bars, e.g. `.console_writer(move |line| multi_progress.println(line))`.
+    /// Each entry arrives finished: filtered, laid out by the console
+    /// fields or pattern, wrapped and colored just as it would be printed,
+    /// without the trailing newline. `progress` updates become entries,
+    /// as when stdout is not a terminal, and the bell doesn't ring.
+    ///
+    /// Whatever `writer` returns is ignored.
+    pub fn console_writer<R>(mut self, writer: impl FnMut(&str) -> R + Send + 'static) -> Self {
+        self.console_writer = Some(Box::new(writer));
+        self
+    }
+
+    /// Replace the stdout console, e.g. to capture output in tests
+    pub(crate) fn console(mut self, console: Console) -> Self {
+        self.console = Some(console);
+        self
+    }
+
+    /// Create the logger
+    ///
+    /// # Panics
+    ///
+    /// With `verify_on_build`, if a sink that isn't optional fails the self-test,
+    /// and if the settings don't work together, see `try_build`.
+    pub fn build(self) -> HorizonLogger {
+        match self.try_build() {
+            Ok(logger) => logger,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Create the logger, failing if `verify_on_build` is set and a sink that isn't optional fails the self-test
+    ///
+    /// Also fails with `HorizonLoggerError::InvalidConfig` for
+    /// `SinkOrdering::Independent` without `async_sinks`.
+    pub fn try_build(mut self) -> Result<HorizonLogger, HorizonLoggerError> {
+        if self.sink_ordering == SinkOrdering::Independent && self.async_sinks.is_none() {
+            let reason = "SinkOrdering::Independent needs async_sinks".to_string();
+            return Err(HorizonLoggerError::InvalidConfig(reason));
+        }
+        let sinks = std::mem::take(&mut self.sinks);
+        let verify = self.verify_on_build;
+        let logger = self.assemble();
+        for (sink, optional) in sinks {
+            logger.register_sink(sink, optional);
+        }
+        logger.load_persisted_history();
+        if verify {
+            logger.self_test()?;
+        }
+        Ok(logger)
+    }
+
+    fn assemble(self) -> HorizonLogger {
+        let started = self.clock.now();
+        let static_fields: Vec<_> = self
+            .static_fields
+            .into_iter()
+            .filter_map(|(name, value)| Some((Cow::Borrowed(name), value.resolve()?)))
+            .collect();
+        let mut rendered_static = String::new();
+        append_fields(&mut rendered_static, static_fields.iter());
+        HorizonLogger {
+            inner: Arc::new_cyclic(|weak| LoggerInner {
+                history: if self.global_history {
+                    history::global()
+                } else {
+                    let history = if self.dedup_history {
+                        history::History::with_dedup()
+                    } else {
+                        history::History::new()
+                    };
+                    let history = history.with_pins(self.pin_budget, self.auto_pin);
+                    Arc::new(match self.history_max_age {
+                        Some(max_age) => history.with_max_age(max_age),
+                        None => history,
+                    })
+                },
+                keep_history: self.keep_history,
+                persistence: self
+                    .persist_history
+                    .map(|(path, policy)| Persistence::start(path, policy, weak.clone())),
+                stats: stats::StatsRegistry::new(),
+                sizes: size::SizeTracker::new(),
+                aliases: Default::default(),
+                namespaces: Default::default(),
+                escalations: Default::default(),
+                directives: Default::default(),
+                sampling: Default::default(),
+                volume: VolumeMonitor::new(self.volume_limits),
+                run_id: self.run_id.unwrap_or_else(|| self.ids.next_id()).into(),
+                started,
+                run_announced: AtomicBool::new(!self.announce_run),
+                indent_spans: AtomicBool::new(false),
+                min_level: AtomicU8::new(self.min_level.to_u8()),
+                clock: self.clock,
+                ids: self.ids,
+                format: RwLock::new(Arc::new(self.format)),
+                backtrace_level: self.backtrace_level,
+                pretty: self.pretty,
+                print_backtraces: backtrace_env_enabled(),
+                sinks: RwLock::new(Vec::new()),
+                sink_queue: self
+                    .async_sinks
+                    .map(|policy| SinkQueue::start(policy, self.sink_ordering, weak.clone())),
+                dispatch_lock: Mutex::new(()),
+                preinit: PreinitBuffer::new(self.preinit_buffer),
+                console: self
+                    .console
+                    .unwrap_or_else(|| Console::stdout(self.console_unicode))
+                    .with_writer(self.console_writer)
+                    .with_fields(self.console_fields)
+                    .with_pattern(self.console_pattern)
+                    .with_color(self.console_color)
+                    .with_ansi_policy(self.console_ansi)
+                    .with_wrap(self.console_wrap)
+                    .with_timestamps(self.console_timestamps)
+                    .with_glyphs(self.glyphs.resolve())
+                    .with_coarse_time(self.coarse_time.map(|window| CoarseTime::new(window, self.precise_time_from)))
+                    .with_bell(self.bell)
+                    .with_banner(self.banner)
+                    .with_static_fields(rendered_static)
+                    .with_source_snippets(self.source_snippets),
+                group_lock: RwLock::new(()),
+                group_max_entries: self.group_max_entries,
+                profile: profile::Profiler::new(self.self_profiling),
+                warned_templates: Default::default(),
+                seal: Default::default(),
+                static_fields,
+                empty_messages: EmptyMessages::new(self.empty_messages),
+                skew: SkewDetector::new(self.clock_skew),
+                kill_switch: KillSwitch::from_env(),
+                replay_seq: AtomicU64::new(0),
+                fatal_handler: self.fatal_handler,
+                #[cfg(all(unix, feature = "fork"))]
+                fork_gate: Default::default(),
+                #[cfg(all(unix, feature = "fork"))]
+                heartbeats: Mutex::new(Vec::new()),
+            }),
+        }
+    }
+}
+
+/// Where a static field's value comes from
+enum StaticValue {
+    Literal(FieldValue),
+    /// An environment variable, and the value to use if it is unset
+    Env(String, Option<String>),
+}
+
+impl StaticValue {
+    fn resolve(self) -> Option<FieldValue> {
+        match self {
+            StaticValue::Literal(value) => Some(value),
+            StaticValue::Env(var, default) => std::env::var(&var).ok().or(default).map(FieldValue::Str),
+        }
+    }
+}
+
+fn default_bell() -> Bell {
+    Bell {
+        level: LogLevel::CRITICAL,
+        interval: Duration::from_secs(10),
+        notify: false,
+    }
+}
+
+fn default_banner() -> Banner {
+    Banner {
+        level: LogLevel::CRITICAL,
+        max_width: 80,
+    }
+}
+
+/// Whether `RUST_BACKTRACE` asks for backtraces to be shown
+fn backtrace_env_enabled() -> bool {
+    std::env::var("RUST_BACKTRACE").is_ok_and(|value| value != "0")
+}
+
+impl HorizonLogger {
+    /// Start configuring a new logger
+    pub fn builder() -> LoggerBuilder {
+        LoggerBuilder::new()
+    }
+}
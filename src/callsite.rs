@@ -0,0 +1,129 @@
+//! Debug-build checks the logging macros make on their arguments
+//!
+//! A component such as `"player joined"` or a message such as `"NETWORK"`
+//! almost always means the arguments were swapped or the message left out.
+//! The macros call these only under `cfg(debug_assertions)` in the calling
+//! crate, so release builds don't contain them at all.
+
+use crate::run::LOGGER_COMPONENT;
+use crate::HorizonLog;
+use std::panic::Location;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// What makes `component` look like something other than a component name
+fn component_problem(component: &str) -> Option<&'static str> {
+    if component.chars().any(char::is_whitespace) {
+        Some("contains whitespace")
+    } else if component.chars().any(char::is_alphabetic) && !component.chars().any(char::is_uppercase) {
+        Some("is all lowercase")
+    } else if component.chars().any(|c| !matches!(c, 'A'..='Z' | '0'..='9' | '_' | '/')) {
+        Some("has characters other than A-Z, 0-9, _ and /")
+    } else {
+        None
+    }
+}
+
+/// Whether a message without format arguments is shaped like a component name
+fn looks_like_component(message: &str) -> bool {
+    message.chars().any(|c| c.is_ascii_uppercase())
+        && message.chars().all(|c| matches!(c, 'A'..='Z' | '0'..='9' | '_' | '/'))
+}
+
+/// Warn under `LOGGER` the first time a callsite passes a component that looks wrong
+#[doc(hidden)]
+#[track_caller]
+pub fn check_component(logger: &(impl HorizonLog + ?Sized), component: &str, warned: &AtomicBool) {
+    let Some(problem) = component_problem(component) else {
+        return;
+    };
+    if warned.swap(true, Ordering::Relaxed) {
+        return;
+    }
+    let location = Location::caller();
+    let message = format!(
+        "component {:?} at {}:{} {}; are the component and message swapped?",
+        component,
+        location.file(),
+        location.line(),
+        problem
+    );
+    logger.warn(LOGGER_COMPONENT, &message);
+}
+
+/// Warn under `LOGGER` the first time a callsite logs a message that looks like a component
+#[doc(hidden)]
+#[track_caller]
+pub fn check_message(logger: &(impl HorizonLog + ?Sized), message: &str, warned: &AtomicBool) {
+    if !looks_like_component(message) || warned.swap(true, Ordering::Relaxed) {
+        return;
+    }
+    let location = Location::caller();
+    let message = format!(
+        "message {:?} at {}:{} looks like a component; is the message missing?",
+        message,
+        location.file(),
+        location.line()
+    );
+    logger.warn(LOGGER_COMPONENT, &message);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::CaptureLogger;
+    use crate::{log_error, log_info, log_warn, LogLevel};
+
+    #[test]
+    fn test_component_shapes() {
+        for good in ["NETWORK", "GAME/AI", "DB_POOL", "HTTP2", ""] {
+            assert_eq!(component_problem(good), None, "{}", good);
+        }
+        assert_eq!(component_problem("player joined"), Some("contains whitespace"));
+        assert_eq!(component_problem("network"), Some("is all lowercase"));
+        assert_eq!(component_problem("Network"), Some("has characters other than A-Z, 0-9, _ and /"));
+        assert_eq!(component_problem("NET-0042"), Some("has characters other than A-Z, 0-9, _ and /"));
+        assert!(looks_like_component("NETWORK"));
+        assert!(!looks_like_component("ok"));
+        assert!(!looks_like_component("CONNECTED TO 10.0.0.1"));
+    }
+
+    #[test]
+    fn test_suspicious_arguments_warn_once_per_callsite() {
+        let logger = CaptureLogger::new();
+        for player in ["ana", "bo"] {
+            log_info!(logger, "player joined", "{}", player);
+            log_info!(logger, "NETWORK");
+        }
+        let component = String::from("physics");
+        log_warn!(logger, component, "step took {}ms", 40);
+        log_error!(logger, &component, "step failed");
+        log_info!(logger, "NETWORK", "connected");
+        let evaluated = std::cell::Cell::new(0);
+        let component = || {
+            evaluated.set(evaluated.get() + 1);
+            String::from("STATS")
+        };
+        log_info!(logger, component(), "tick {}", 1);
+        assert_eq!(evaluated.get(), 1);
+
+        let warnings: Vec<_> = logger
+            .entries()
+            .into_iter()
+            .filter(|entry| entry.component == "LOGGER")
+            .inspect(|entry| assert_eq!(entry.level, LogLevel::WARN))
+            .map(|entry| entry.message)
+            .collect();
+        if !cfg!(debug_assertions) {
+            assert!(warnings.is_empty());
+            return;
+        }
+        assert_eq!(warnings.len(), 4, "{:?}", warnings);
+        assert!(warnings[0].starts_with("component \"player joined\" at src/callsite.rs:"), "{}", warnings[0]);
+        assert!(warnings[0].ends_with(" contains whitespace; are the component and message swapped?"));
+        assert!(warnings[1].starts_with("message \"NETWORK\" at src/callsite.rs:"), "{}", warnings[1]);
+        assert!(warnings[1].ends_with(" looks like a component; is the message missing?"));
+        assert!(warnings[2].contains("\"physics\"") && warnings[2].contains("is all lowercase"));
+        assert!(warnings[3].contains("\"physics\""));
+        assert_eq!(logger.history_by_component("physics").len(), 2);
+    }
+}
@@ -0,0 +1,86 @@
+use crate::time::Timestamp;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Source of timestamps for log entries
+///
+/// Swap in a `ManualClock` to make timestamps deterministic in tests, or
+/// implement it over a host-provided time source.
+pub trait Clock: Send + Sync {
+    /// Current wall-clock time
+    fn now(&self) -> Timestamp;
+
+    /// Time elapsed on a clock that never jumps, from any fixed start
+    ///
+    /// Compared with `now` to spot wall-clock jumps, see
+    /// `LoggerBuilder::detect_clock_skew`. Defaults to the process's
+    /// monotonic clock.
+    fn monotonic(&self) -> Duration {
+        static START: OnceLock<Instant> = OnceLock::new();
+        START.get_or_init(Instant::now).elapsed()
+    }
+}
+
+/// The real system clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Timestamp {
+        Timestamp::now()
+    }
+}
+
+/// A clock that only moves when told to
+#[derive(Debug)]
+pub struct ManualClock {
+    now: Mutex<Timestamp>,
+    monotonic: Mutex<Duration>,
+}
+
+impl ManualClock {
+    /// Create a clock frozen at `start`
+    pub fn new(start: impl Into<Timestamp>) -> Self {
+        ManualClock {
+            now: Mutex::new(start.into()),
+            monotonic: Mutex::new(Duration::ZERO),
+        }
+    }
+
+    /// Move the clock forward, wall-clock and monotonic time alike
+    pub fn advance(&self, by: Duration) {
+        if let Ok(mut now) = self.now.lock() {
+            *now = now.saturating_add(by);
+        }
+        if let Ok(mut monotonic) = self.monotonic.lock() {
+            *monotonic = monotonic.saturating_add(by);
+        }
+    }
+
+    /// Jump the wall clock to an arbitrary time, including backwards; monotonic time stays put
+    pub fn set(&self, to: impl Into<Timestamp>) {
+        if let Ok(mut now) = self.now.lock() {
+            *now = to.into();
+        }
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Timestamp {
+        self.now.lock().map(|now| *now).unwrap_or_else(|_| Timestamp::now())
+    }
+
+    fn monotonic(&self) -> Duration {
+        self.monotonic.lock().map(|monotonic| *monotonic).unwrap_or_default()
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for std::sync::Arc<C> {
+    fn now(&self) -> Timestamp {
+        (**self).now()
+    }
+
+    fn monotonic(&self) -> Duration {
+        (**self).monotonic()
+    }
+}
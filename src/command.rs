@@ -0,0 +1,242 @@
+use crate::pipe::PipeHandle;
+use crate::{HorizonLogger, LogLevel};
+use std::ffi::OsStr;
+use std::io;
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::time::Instant;
+
+/// A child started by `spawn_command`, its output logged as it arrives
+///
+/// Dropping the handle leaves the child running and its output still
+/// logged, but its exit is never logged.
+pub struct CommandHandle {
+    logger: HorizonLogger,
+    component: String,
+    program: String,
+    child: Child,
+    output: [Option<PipeHandle>; 2],
+    started: Instant,
+}
+
+impl CommandHandle {
+    /// The child's process id
+    pub fn id(&self) -> u32 {
+        self.child.id()
+    }
+
+    /// Kill the child; `wait` then logs how it ended
+    pub fn kill(&mut self) -> io::Result<()> {
+        self.child.kill()
+    }
+
+    /// Wait for the child to exit and its output to be logged, then log its exit status
+    ///
+    /// Logs INFO for a zero exit code, ERROR otherwise, with `program`,
+    /// `exit_code` or `signal`, and `wall_ms` as fields.
+    pub fn wait(mut self) -> io::Result<ExitStatus> {
+        let status = self.child.wait();
+        for output in self.output.iter_mut().filter_map(Option::take) {
+            output.join();
+        }
+        let status = match status {
+            Ok(status) => status,
+            Err(e) => {
+                let message = format!("waiting for {} failed: {}", self.program, e);
+                self.logger.error(&self.component, &message);
+                return Err(e);
+            }
+        };
+
+        let wall_ms = self.started.elapsed().as_millis() as u64;
+        let event = |level| {
+            self.logger
+                .event(level, &self.component)
+                .field("program", &self.program)
+                .field("wall_ms", wall_ms)
+        };
+        match (status.code(), signal(&status)) {
+            (Some(0), _) => event(LogLevel::INFO)
+                .field("exit_code", 0)
+                .template("{program} exited with code {exit_code} after {wall_ms}ms")
+                .emit(),
+            (Some(code), _) => event(LogLevel::ERROR)
+                .field("exit_code", code)
+                .template("{program} exited with code {exit_code} after {wall_ms}ms")
+                .emit(),
+            (None, Some(signal)) => event(LogLevel::ERROR)
+                .field("signal", signal)
+                .template("{program} was killed by signal {signal} after {wall_ms}ms")
+                .emit(),
+            (None, None) => event(LogLevel::ERROR)
+                .template("{program} ended without an exit code after {wall_ms}ms")
+                .emit(),
+        }
+        Ok(status)
+    }
+}
+
+#[cfg(unix)]
+fn signal(status: &ExitStatus) -> Option<i32> {
+    std::os::unix::process::ExitStatusExt::signal(status)
+}
+
+#[cfg(not(unix))]
+fn signal(_status: &ExitStatus) -> Option<i32> {
+    None
+}
+
+/// `command` as a shell would show it, quoting arguments that need it
+fn command_line(command: &Command) -> String {
+    let mut line = command.get_program().to_string_lossy().into_owned();
+    for arg in command.get_args().map(OsStr::to_string_lossy) {
+        line.push(' ');
+        if arg.is_empty() || arg.contains(|c: char| c.is_whitespace() || c == '"' || c == '\'') {
+            line.push_str(&format!("{:?}", arg));
+        } else {
+            line.push_str(&arg);
+        }
+    }
+    line
+}
+
+impl HorizonLogger {
+    /// Run `command` to completion, logging it, its output and how it exited
+    ///
+    /// Logs the command line at INFO, then each line the child writes, as
+    /// `program: line`: stdout at INFO and stderr at WARN, through
+    /// `pipe_reader`. Its exit is logged as `CommandHandle::wait` describes.
+    /// Stdout and stderr are always captured; stdin is left as configured.
+    /// A child that can't be started is logged at ERROR and its error returned.
+    pub fn run_command(&self, component: &str, command: &mut Command) -> io::Result<ExitStatus> {
+        self.spawn_command(component, command)?.wait()
+    }
+
+    /// Start `command` like `run_command` without waiting for it, for long-running children
+    pub fn spawn_command(&self, component: &str, command: &mut Command) -> io::Result<CommandHandle> {
+        let program = Path::new(command.get_program())
+            .file_name()
+            .unwrap_or(command.get_program())
+            .to_string_lossy()
+            .into_owned();
+        let component = component.to_string();
+        self.event(LogLevel::INFO, &component)
+            .field("command", command_line(command))
+            .template("running {command}")
+            .emit();
+
+        let started = Instant::now();
+        let mut child = match command.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                self.error(&component, &format!("failed to start {}: {}", program, e));
+                return Err(e);
+            }
+        };
+        let prefix = format!("{}: ", program);
+        let stdout = child
+            .stdout
+            .take()
+            .map(|out| self.pipe_lines(out, &component, LogLevel::INFO, prefix.clone()));
+        let stderr = child
+            .stderr
+            .take()
+            .map(|err| self.pipe_lines(err, &component, LogLevel::WARN, prefix));
+
+        Ok(CommandHandle {
+            logger: self.clone(),
+            component,
+            program,
+            child,
+            output: [stdout, stderr],
+            started,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::CaptureLogger;
+    use crate::Format;
+
+    /// `script` run by the platform's shell
+    fn shell(script: &str) -> Command {
+        if cfg!(windows) {
+            let mut command = Command::new("cmd");
+            command.args(["/C", script]);
+            command
+        } else {
+            let mut command = Command::new("/bin/sh");
+            command.args(["-c", script]);
+            command
+        }
+    }
+
+    fn program() -> &'static str {
+        if cfg!(windows) {
+            "cmd"
+        } else {
+            "sh"
+        }
+    }
+
+    #[test]
+    fn test_run_command_logs_output_and_status() {
+        let logger = CaptureLogger::new();
+        let status = logger
+            .run_command("BUILD", &mut shell("echo packing assets&& echo missing icon 1>&2"))
+            .unwrap();
+        assert!(status.success());
+
+        let entries = logger.entries();
+        assert_eq!(entries.len(), 4);
+        assert!(entries[0].message.starts_with("running ") && entries[0].message.ends_with('"'));
+        let line = |level, text: &str| {
+            entries
+                .iter()
+                .any(|e| e.level == level && e.message == format!("{}: {}", program(), text))
+        };
+        assert!(line(LogLevel::INFO, "packing assets"));
+        assert!(line(LogLevel::WARN, "missing icon"));
+
+        let done = &entries[3];
+        assert_eq!(done.level, LogLevel::INFO);
+        assert!(done.message.starts_with(&format!("{} exited with code 0 after ", program())));
+        let json: serde_json::Value = serde_json::from_str(&logger.format_entry(done, Format::Json)).unwrap();
+        assert_eq!(json["fields"]["exit_code"], 0);
+        assert!(json["fields"]["wall_ms"].is_u64());
+    }
+
+    #[test]
+    fn test_failures_are_logged_as_errors() {
+        let logger = CaptureLogger::new();
+        let status = logger.run_command("BUILD", &mut shell("exit 3")).unwrap();
+        assert_eq!(status.code(), Some(3));
+        let done = logger.entries().pop().unwrap();
+        assert_eq!(done.level, LogLevel::ERROR);
+        assert!(done.message.starts_with(&format!("{} exited with code 3 after ", program())));
+
+        let error = logger.run_command("BUILD", &mut Command::new("horizon-no-such-program")).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+        let failed = logger.entries().pop().unwrap();
+        assert_eq!(failed.level, LogLevel::ERROR);
+        assert!(failed.message.starts_with("failed to start horizon-no-such-program: "));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_spawned_command_can_be_killed() {
+        let logger = CaptureLogger::new();
+        let mut child = logger.spawn_command("SERVER", &mut shell("echo listening; exec sleep 30")).unwrap();
+        while !logger.messages().iter().any(|m| m == "sh: listening") {
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        child.kill().unwrap();
+        child.wait().unwrap();
+
+        let done = logger.entries().pop().unwrap();
+        assert_eq!(done.level, LogLevel::ERROR);
+        assert!(done.message.starts_with("sh was killed by signal 9 after "), "{}", done.message);
+    }
+}
@@ -0,0 +1,248 @@
+use crate::namespace::Namespace;
+use crate::{EventBuilder, HorizonLogger, LogLevel};
+use std::borrow::Cow;
+use std::fmt;
+use std::sync::Arc;
+
+/// A component name accepted by the logging methods
+///
+/// String literals and other `&'static str` names are stored in entries
+/// without copying; `String`, `&String` and `Cow` names are copied as
+/// before. A borrowed `&str` that is not `'static` can be passed as
+/// `&name.to_string()`, or by passing the `String` it came from.
+pub trait ComponentArg {
+    /// The name, for filtering and printing
+    fn as_str(&self) -> &str;
+
+    /// The name as stored in a `LogEntry`
+    fn to_cow(&self) -> Cow<'static, str>;
+}
+
+impl ComponentArg for &'static str {
+    fn as_str(&self) -> &str {
+        self
+    }
+
+    fn to_cow(&self) -> Cow<'static, str> {
+        Cow::Borrowed(self)
+    }
+}
+
+impl ComponentArg for String {
+    fn as_str(&self) -> &str {
+        self
+    }
+
+    fn to_cow(&self) -> Cow<'static, str> {
+        Cow::Owned(self.clone())
+    }
+}
+
+impl ComponentArg for Cow<'static, str> {
+    fn as_str(&self) -> &str {
+        self
+    }
+
+    fn to_cow(&self) -> Cow<'static, str> {
+        self.clone()
+    }
+}
+
+/// References to names; a borrowed `Cow` stays borrowed, so re-logging an entry's component is free
+impl<C: ComponentArg> ComponentArg for &C {
+    fn as_str(&self) -> &str {
+        C::as_str(self)
+    }
+
+    fn to_cow(&self) -> Cow<'static, str> {
+        C::to_cow(self)
+    }
+}
+
+/// A name borrowed for one call, copied only if an entry is made
+pub(crate) struct BorrowedComponent<'a>(pub(crate) &'a str);
+
+impl ComponentArg for BorrowedComponent<'_> {
+    fn as_str(&self) -> &str {
+        self.0
+    }
+
+    fn to_cow(&self) -> Cow<'static, str> {
+        Cow::Owned(self.0.to_string())
+    }
+}
+
+/// A logger bound to one component, for subsystems to keep in a field
+///
+/// Created with `HorizonLogger::component`. Cloning costs a reference count
+/// bump, plus a copy of the name if it isn't a `&'static str`. The `*_msg`
+/// methods let it stand in for a logger in the two-argument logging macros,
+/// so `log_info!(self.log, "tick {n} done")` logs under its component.
+#[derive(Clone)]
+pub struct ComponentLogger {
+    logger: HorizonLogger,
+    component: Cow<'static, str>,
+    /// Set when made by a `NamespaceHandle`, whose state gates every entry
+    namespace: Option<Arc<Namespace>>,
+}
+
+impl ComponentLogger {
+    pub(crate) fn new(logger: HorizonLogger, component: Cow<'static, str>, namespace: Option<Arc<Namespace>>) -> Self {
+        ComponentLogger {
+            logger,
+            component,
+            namespace,
+        }
+    }
+
+    /// Whether the namespace, if any, lets an entry at `level` through, counting it if not
+    #[inline]
+    pub(crate) fn namespace_allows(&self, level: LogLevel) -> bool {
+        self.namespace.as_ref().is_none_or(|namespace| namespace.allows(level))
+    }
+
+    /// Whether an entry at `level` would be logged, by the logger and the namespace
+    pub fn enabled(&self, level: LogLevel) -> bool {
+        self.namespace.as_ref().is_none_or(|namespace| namespace.passes(level)) && self.logger.enabled(level)
+    }
+
+    /// The logger entries go to
+    pub fn logger(&self) -> &HorizonLogger {
+        &self.logger
+    }
+
+    /// The component entries are logged under
+    pub fn name(&self) -> &str {
+        &self.component
+    }
+
+    /// Log `message` at `level`
+    #[track_caller]
+    pub fn log(&self, level: LogLevel, message: &str) {
+        if !self.namespace_allows(level) {
+            return;
+        }
+        self.logger.log_from_caller(level, &self.component, message);
+    }
+
+    /// Log a debug message
+    #[track_caller]
+    pub fn debug(&self, message: &str) {
+        self.log(LogLevel::DEBUG, message);
+    }
+
+    /// Log an info message
+    #[track_caller]
+    pub fn info(&self, message: &str) {
+        self.log(LogLevel::INFO, message);
+    }
+
+    /// Log a warning message
+    #[track_caller]
+    pub fn warn(&self, message: &str) {
+        self.log(LogLevel::WARN, message);
+    }
+
+    /// Log an error message
+    #[track_caller]
+    pub fn error(&self, message: &str) {
+        self.log(LogLevel::ERROR, message);
+    }
+
+    /// Log a critical message
+    #[track_caller]
+    pub fn critical(&self, message: &str) {
+        self.log(LogLevel::CRITICAL, message);
+    }
+
+    /// Start an entry at `level`, as `HorizonLogger::event` does
+    pub fn event(&self, level: LogLevel) -> EventBuilder<'_> {
+        let event = self.logger.event(level, &self.component);
+        if self.namespace_allows(level) {
+            event
+        } else {
+            event.discard()
+        }
+    }
+
+    /// Same as `debug`, for `log_debug!`
+    #[track_caller]
+    pub fn debug_msg(&self, message: &str) {
+        self.debug(message);
+    }
+
+    /// Same as `info`, for `log_info!`
+    #[track_caller]
+    pub fn info_msg(&self, message: &str) {
+        self.info(message);
+    }
+
+    /// Same as `warn`, for `log_warn!`
+    #[track_caller]
+    pub fn warn_msg(&self, message: &str) {
+        self.warn(message);
+    }
+
+    /// Same as `error`, for `log_error!`
+    #[track_caller]
+    pub fn error_msg(&self, message: &str) {
+        self.error(message);
+    }
+
+    /// Same as `critical`, for `log_critical!`
+    #[track_caller]
+    pub fn critical_msg(&self, message: &str) {
+        self.critical(message);
+    }
+}
+
+impl fmt::Debug for ComponentLogger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ComponentLogger").field(&self.component).finish()
+    }
+}
+
+impl HorizonLogger {
+    /// A logger that logs everything under `component`
+    pub fn component(&self, component: impl ComponentArg) -> ComponentLogger {
+        ComponentLogger::new(self.clone(), component.to_cow(), None)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::testing::CaptureLogger;
+    use crate::{log_warn, LogEntry, Timestamp};
+
+    #[test]
+    fn test_matches_explicit_component() {
+        let clock = ManualClock::new(Timestamp::from_millis(1_700_000_000_000));
+        let logger = CaptureLogger::from_builder(HorizonLogger::builder().clock(clock));
+        let physics = logger.component("PHYSICS");
+        let per_entity = physics.clone();
+
+        physics.info("tick complete");
+        logger.info("PHYSICS", "tick complete");
+        let bodies = 12;
+        log_warn!(per_entity, "{bodies} bodies asleep");
+        logger.warn("PHYSICS", &format!("{bodies} bodies asleep"));
+        physics.event(LogLevel::ERROR).code("PHYS-0001").log("solver diverged");
+        logger.event(LogLevel::ERROR, "PHYSICS").code("PHYS-0001").log("solver diverged");
+
+        let entries = logger.entries();
+        let plain = |e: &LogEntry| LogEntry { seq: 0, ..e.clone() }.to_json();
+        for pair in entries.chunks(2) {
+            assert_eq!(plain(&pair[0]), plain(&pair[1]));
+        }
+        assert_eq!(entries.len(), 6);
+        assert_eq!(entries[2].message, "12 bodies asleep");
+        assert_eq!(physics.name(), "PHYSICS");
+        assert_eq!(format!("{:?}", per_entity), "ComponentLogger(\"PHYSICS\")");
+
+        fn shareable<T: Clone + Send + Sync>(_: &T) {}
+        shareable(&physics);
+    }
+}
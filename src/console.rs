@@ -0,0 +1,1422 @@
+use crate::ansi::AnsiPolicy;
+use crate::glyph::Glyphs;
+use crate::pattern::Pattern;
+use crate::snippet::SourceCache;
+use crate::time_render::{HumanTime, TimestampRenderer};
+use crate::unicode::{self, UnicodePolicy};
+use crate::{Breadcrumb, ComponentArg, HorizonLogger, LogEntry, LogLevel, Timestamp};
+use std::cell::RefCell;
+use std::fmt::{self, Write as _};
+use std::io::{self, IsTerminal, Write};
+use std::ops::BitOr;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+/// Minimum time between fallback INFO lines when progress can't be drawn in place
+const PROGRESS_FALLBACK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Prefix for the second and later lines of a multi-line entry
+pub(crate) const CONTINUATION: &str = "    ";
+
+/// Erase from the cursor to the end of the line
+const CLEAR_TO_EOL: &str = "\x1b[K";
+
+/// SGR parameters for the colored parts of a line, matching `colored`'s output
+const WHITE: &str = "37";
+const DIMMED: &str = "2";
+const PURPLE: &str = "35";
+const BLUE: &str = "34";
+const RESET: &str = "\x1b[0m";
+
+/// Terminal bell
+const BEL: &str = "\x07";
+
+/// Narrowest a wrapped message gets, however wide the columns before it
+const MIN_WRAP_COLUMNS: usize = 20;
+
+/// Frames banner entries above and below
+const BANNER_RULE: char = '═';
+
+/// Line buffer capacity kept between calls; larger buffers are released
+const LINE_BUFFER_RETAIN: usize = 16 * 1024;
+
+thread_local! {
+    /// Reused buffer for composing console lines on this thread
+    static LINE: RefCell<String> = const { RefCell::new(String::new()) };
+}
+
+/// Receives each console entry in place of stdout, see `LoggerBuilder::console_writer`
+pub(crate) trait ConsoleWriter: Send {
+    /// Print one entry, styled and laid out, without a trailing newline
+    ///
+    /// Multi-line entries, such as wrapped messages or backtraces, arrive
+    /// as one string with the lines separated by `\n`.
+    fn write_line(&mut self, line: &str);
+}
+
+impl<F, R> ConsoleWriter for F
+where
+    F: FnMut(&str) -> R + Send,
+{
+    fn write_line(&mut self, line: &str) {
+        self(line);
+    }
+}
+
+/// Where console text goes
+enum ConsoleOut {
+    /// Written as it is, progress lines and bells included
+    Stream(Box<dyn Write + Send>),
+    /// Handed over a finished entry at a time
+    Writer(Box<dyn ConsoleWriter>),
+}
+
+/// Serializes console writes and remembers whether a progress line is open
+pub(crate) struct Console {
+    state: Mutex<ConsoleState>,
+    is_tty: bool,
+    /// Writes to this process's stdout, so the terminal size can be asked of it
+    on_stdout: bool,
+    /// Progress lines can be redrawn in place: a terminal, written as a stream
+    in_place: bool,
+    fields: ConsoleFields,
+    /// Replaces the `fields` layout when set; changed by `set_console_pattern`
+    pattern: RwLock<Option<Pattern>>,
+    /// A `ConsoleColor`, changed by `set_console_color`
+    color: AtomicU8,
+    bell: Option<Bell>,
+    banner: Option<Banner>,
+    /// Rendered once at build time, for `{static_fields}` in `pattern`
+    static_fields: String,
+    /// Set by `source_snippets` in debug builds
+    snippets: Option<SourceCache>,
+    ansi: AnsiPolicy,
+    wrap: ConsoleWrap,
+    coarse_time: Option<CoarseTime>,
+    timestamps: Arc<dyn TimestampRenderer>,
+    glyphs: Option<Glyphs>,
+}
+
+/// Whether long console messages are wrapped at word boundaries, see `LoggerBuilder::console_wrap`
+///
+/// Wrapped and multi-line messages continue on lines indented to the
+/// message column, or by four spaces when that is past half the width.
+/// Widths are display columns: ANSI escapes take none, and wide
+/// characters such as CJK and emoji take two. Pattern layouts, banners and
+/// every output but the console are never wrapped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConsoleWrap {
+    /// Lines are written whole, for the terminal to break where it likes
+    #[default]
+    Off,
+    /// Wrap at the terminal's width, from `COLUMNS` or the terminal itself; only on terminals
+    Terminal,
+    /// Wrap at this many columns, terminal or not
+    Columns(usize),
+}
+
+/// Whether console lines are colored, see `LoggerBuilder::console_color`
+///
+/// Without the `color` feature lines are always plain. Pattern layouts are
+/// never colored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConsoleColor {
+    /// As `colored` decides from `NO_COLOR`, `CLICOLOR` and whether stdout is a terminal
+    #[default]
+    Auto,
+    /// Colored, terminal or not
+    Always,
+    /// Plain
+    Never,
+}
+
+impl ConsoleColor {
+    /// The mode's name in a watched config file
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            ConsoleColor::Auto => "auto",
+            ConsoleColor::Always => "always",
+            ConsoleColor::Never => "never",
+        }
+    }
+
+    /// The mode named `name`, as in a watched config file
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        [ConsoleColor::Auto, ConsoleColor::Always, ConsoleColor::Never]
+            .into_iter()
+            .find(|mode| mode.as_str().eq_ignore_ascii_case(name))
+    }
+
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => ConsoleColor::Always,
+            2 => ConsoleColor::Never,
+            _ => ConsoleColor::Auto,
+        }
+    }
+}
+
+/// Console timestamps formatted once per window, see `LoggerBuilder::console_coarse_time`
+pub(crate) struct CoarseTime {
+    granularity_micros: i64,
+    /// Entries at or above this level show their own time
+    precise_from: LogLevel,
+    /// Start of the window `text` was formatted for, and the text
+    cache: Mutex<(i64, String)>,
+}
+
+impl CoarseTime {
+    pub(crate) fn new(granularity: Duration, precise_from: LogLevel) -> Self {
+        CoarseTime {
+            granularity_micros: i64::try_from(granularity.as_micros()).unwrap_or(i64::MAX).max(1),
+            precise_from,
+            cache: Mutex::new((i64::MIN, String::new())),
+        }
+    }
+
+    /// Append the start of the window `timestamp` falls in, formatting it only for a new window
+    fn write(&self, out: &mut String, timestamp: &Timestamp, level: LogLevel, timestamps: &dyn TimestampRenderer) {
+        if level >= self.precise_from {
+            timestamps.render(timestamp, out);
+            return;
+        }
+        let window = timestamp.as_micros().div_euclid(self.granularity_micros) * self.granularity_micros;
+        // Another thread refreshing it would format the same text
+        let Ok(mut cache) = self.cache.try_lock() else {
+            timestamps.render(&Timestamp::from_micros(window), out);
+            return;
+        };
+        if cache.0 != window {
+            cache.1.clear();
+            timestamps.render(&Timestamp::from_micros(window), &mut cache.1);
+            cache.0 = window;
+        }
+        out.push_str(&cache.1);
+    }
+}
+
+/// Which entries get a banner, see `LoggerBuilder::banner_on`
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Banner {
+    pub(crate) level: LogLevel,
+    /// Width of the frame when the terminal is wider or unknown
+    pub(crate) max_width: usize,
+}
+
+/// When to ring the terminal bell, see `LoggerBuilder::bell_on`
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Bell {
+    pub(crate) level: LogLevel,
+    /// Minimum time between two bells
+    pub(crate) interval: Duration,
+    /// Also send an OSC 9 desktop notification with the message
+    pub(crate) notify: bool,
+}
+
+struct ConsoleState {
+    out: ConsoleOut,
+    /// The cursor sits at the end of an unterminated progress line
+    progress_open: bool,
+    /// Component and message of the latest progress update
+    last_progress: Option<(String, String)>,
+    /// When a fallback INFO line was last logged for progress
+    last_fallback: Option<Timestamp>,
+    /// Entry time of the latest bell
+    last_bell: Option<Timestamp>,
+}
+
+impl ConsoleState {
+    /// Write a complete line, first terminating any open progress line
+    fn write_line(&mut self, line: &str) {
+        let out = match &mut self.out {
+            ConsoleOut::Stream(out) => out,
+            ConsoleOut::Writer(writer) => {
+                writer.write_line(line);
+                return;
+            }
+        };
+        if self.progress_open {
+            let _ = out.write_all(b"\n");
+            self.progress_open = false;
+        }
+        let _ = writeln!(out, "{}", line);
+    }
+
+    /// The stream written to, unless a `ConsoleWriter` replaced it
+    fn stream(&mut self) -> Option<&mut (dyn Write + Send)> {
+        match &mut self.out {
+            ConsoleOut::Stream(out) => Some(&mut **out),
+            ConsoleOut::Writer(_) => None,
+        }
+    }
+
+    /// Ring the bell for `parts` unless it rang less than `bell.interval` ago
+    fn ring(&mut self, bell: &Bell, parts: &LineParts<'_>) {
+        if self.stream().is_none()
+            || self
+            .last_bell
+            .is_some_and(|last| parts.timestamp.duration_since(last) < bell.interval)
+        {
+            return;
+        }
+        self.last_bell = Some(parts.timestamp);
+        let Some(out) = self.stream() else {
+            return;
+        };
+        let _ = out.write_all(BEL.as_bytes());
+        if bell.notify {
+            // Control characters would end the escape sequence early; they become spaces so words stay apart
+            let text: String = parts
+                .message
+                .chars()
+                .map(|c| if c.is_control() { ' ' } else { c })
+                .take(200)
+                .collect();
+            let _ = write!(out, "\x1b]9;{}{}", text, BEL);
+        }
+        let _ = out.flush();
+    }
+}
+
+impl Console {
+    /// Console writing to stdout, transcoded for a legacy Windows console as `unicode` says
+    pub(crate) fn stdout(unicode: UnicodePolicy) -> Self {
+        Console {
+            on_stdout: true,
+            ..Self::new(unicode::wrap_stdout(Box::new(io::stdout()), unicode), io::stdout().is_terminal())
+        }
+    }
+
+    /// Console writing to an arbitrary target
+    pub(crate) fn new(out: Box<dyn Write + Send>, is_tty: bool) -> Self {
+        Console {
+            state: Mutex::new(ConsoleState {
+                out: ConsoleOut::Stream(out),
+                progress_open: false,
+                last_progress: None,
+                last_fallback: None,
+                last_bell: None,
+            }),
+            is_tty,
+            on_stdout: false,
+            in_place: is_tty,
+            fields: ConsoleFields::default(),
+            pattern: RwLock::new(None),
+            color: AtomicU8::new(ConsoleColor::Auto as u8),
+            bell: None,
+            banner: None,
+            static_fields: String::new(),
+            snippets: None,
+            ansi: AnsiPolicy::Preserve,
+            wrap: ConsoleWrap::Off,
+            coarse_time: None,
+            timestamps: Arc::new(HumanTime),
+            glyphs: None,
+        }
+    }
+}
+
+/// Which parts of an entry appear on its console line
+///
+/// Combine with `|`. The message is always shown, whether or not
+/// `MESSAGE` is included.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ConsoleFields(u8);
+
+impl ConsoleFields {
+    pub const TIMESTAMP: ConsoleFields = ConsoleFields(1 << 0);
+    pub const SEQ: ConsoleFields = ConsoleFields(1 << 1);
+    pub const LEVEL: ConsoleFields = ConsoleFields(1 << 2);
+    pub const THREAD: ConsoleFields = ConsoleFields(1 << 3);
+    pub const COMPONENT: ConsoleFields = ConsoleFields(1 << 4);
+    pub const MESSAGE: ConsoleFields = ConsoleFields(1 << 5);
+
+    /// Whether every field in `other` is included
+    pub fn contains(self, other: ConsoleFields) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl Default for ConsoleFields {
+    /// Timestamp, level, thread, component and message
+    fn default() -> Self {
+        Self::TIMESTAMP | Self::LEVEL | Self::THREAD | Self::COMPONENT | Self::MESSAGE
+    }
+}
+
+impl BitOr for ConsoleFields {
+    type Output = ConsoleFields;
+
+    fn bitor(self, rhs: ConsoleFields) -> ConsoleFields {
+        ConsoleFields(self.0 | rhs.0)
+    }
+}
+
+impl Console {
+    /// Show only the given fields on each line
+    pub(crate) fn with_fields(mut self, fields: ConsoleFields) -> Self {
+        self.fields = fields;
+        self
+    }
+
+    /// Lay lines out with `pattern` instead of the selected fields
+    pub(crate) fn with_pattern(mut self, pattern: Option<Pattern>) -> Self {
+        self.pattern = RwLock::new(pattern);
+        self
+    }
+
+    /// Color lines as `color` says
+    pub(crate) fn with_color(self, color: ConsoleColor) -> Self {
+        self.set_color(color);
+        self
+    }
+
+    pub(crate) fn color(&self) -> ConsoleColor {
+        ConsoleColor::from_u8(self.color.load(Ordering::Relaxed))
+    }
+
+    pub(crate) fn set_color(&self, color: ConsoleColor) {
+        self.color.store(color as u8, Ordering::Relaxed);
+    }
+
+    pub(crate) fn pattern(&self) -> Option<Pattern> {
+        self.pattern.read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+    }
+
+    pub(crate) fn set_pattern(&self, pattern: Option<Pattern>) {
+        *self.pattern.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = pattern;
+    }
+
+    /// Whether lines get ANSI colors right now
+    fn colorize(&self) -> bool {
+        match self.color() {
+            ConsoleColor::Auto => should_colorize(),
+            ConsoleColor::Always => cfg!(feature = "color"),
+            ConsoleColor::Never => false,
+        }
+    }
+
+    pub(crate) fn fields(&self) -> ConsoleFields {
+        self.fields
+    }
+
+    /// Level the bell rings at, if it is enabled on this console
+    pub(crate) fn bell_level(&self) -> Option<LogLevel> {
+        self.bell.map(|bell| bell.level)
+    }
+
+    /// Ring the bell for entries at or above `bell.level`, on terminals only
+    pub(crate) fn with_bell(mut self, bell: Option<Bell>) -> Self {
+        self.bell = bell.filter(|_| self.is_tty);
+        self
+    }
+
+    /// Frame entries at or above `banner.level` in a banner
+    pub(crate) fn with_banner(mut self, banner: Option<Banner>) -> Self {
+        self.banner = banner;
+        self
+    }
+
+    /// The logger's static fields as `name=value` pairs
+    pub(crate) fn with_static_fields(mut self, rendered: String) -> Self {
+        self.static_fields = rendered;
+        self
+    }
+
+    /// Show the source around the call site of ERROR and CRITICAL entries; only in debug builds
+    pub(crate) fn with_source_snippets(mut self, enabled: bool) -> Self {
+        self.snippets = (enabled && cfg!(debug_assertions)).then(SourceCache::default);
+        self
+    }
+
+    /// Whether call sites are worth capturing for `source_snippets`
+    pub(crate) fn shows_snippets(&self) -> bool {
+        self.snippets.is_some()
+    }
+
+    /// What happens to ANSI escapes already in messages
+    pub(crate) fn with_ansi_policy(mut self, ansi: AnsiPolicy) -> Self {
+        self.ansi = ansi;
+        self
+    }
+
+    pub(crate) fn with_wrap(mut self, wrap: ConsoleWrap) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    pub(crate) fn with_coarse_time(mut self, coarse_time: Option<CoarseTime>) -> Self {
+        self.coarse_time = coarse_time;
+        self
+    }
+
+    /// Hand finished entries to `writer` instead of the output stream
+    ///
+    /// Progress updates are then logged as entries, as without a terminal,
+    /// and the bell is not rung.
+    pub(crate) fn with_writer(mut self, writer: Option<Box<dyn ConsoleWriter>>) -> Self {
+        if let (Some(writer), Ok(state)) = (writer, self.state.get_mut()) {
+            state.out = ConsoleOut::Writer(writer);
+            self.in_place = false;
+            self.on_stdout = false;
+        }
+        self
+    }
+
+    pub(crate) fn with_timestamps(mut self, timestamps: Arc<dyn TimestampRenderer>) -> Self {
+        self.timestamps = timestamps;
+        self
+    }
+
+    /// Show a glyph column after the level
+    pub(crate) fn with_glyphs(mut self, glyphs: Option<Glyphs>) -> Self {
+        self.glyphs = glyphs;
+        self
+    }
+
+    /// Append the entry time, see `CoarseTime`
+    fn write_time(&self, out: &mut String, parts: &LineParts<'_>) {
+        match &self.coarse_time {
+            Some(coarse) => coarse.write(out, &parts.timestamp, parts.level, &*self.timestamps),
+            None => self.timestamps.render(&parts.timestamp, out),
+        }
+    }
+
+    /// Banner width for an entry at `level`, if it gets a banner
+    ///
+    /// Terminals are measured by `terminal_width`; anything else uses `max_width`.
+    fn banner_width(&self, level: LogLevel) -> Option<usize> {
+        let banner = self.banner.filter(|banner| level >= banner.level)?;
+        Some(match terminal_width(self.on_stdout).filter(|_| self.is_tty) {
+            Some(columns) => columns.min(banner.max_width),
+            None => banner.max_width,
+        })
+    }
+
+    /// Columns to wrap messages at, if they are wrapped
+    fn wrap_width(&self) -> Option<usize> {
+        match self.wrap {
+            ConsoleWrap::Off => None,
+            ConsoleWrap::Terminal if self.is_tty => terminal_width(self.on_stdout),
+            ConsoleWrap::Terminal => None,
+            ConsoleWrap::Columns(columns) => Some(columns),
+        }
+    }
+
+    /// Render and print one entry, reusing this thread's line buffer
+    pub(crate) fn write_entry(&self, parts: &LineParts<'_>, backtrace: Option<&str>) {
+        if parts.raw {
+            if let Ok(mut state) = self.state.lock() {
+                state.write_line(parts.message);
+            }
+            return;
+        }
+        let message = self.ansi.apply(parts.message);
+        let parts = &LineParts { message: &message, ..*parts };
+        LINE.with(|buffer| {
+            // A fresh buffer if this thread is somehow already rendering
+            let mut owned = String::new();
+            let mut borrowed = buffer.try_borrow_mut();
+            let line = match borrowed.as_deref_mut() {
+                Ok(line) => line,
+                Err(_) => &mut owned,
+            };
+
+            line.clear();
+            let colorize = self.colorize();
+            let banner = self.banner_width(parts.level);
+            match banner {
+                Some(width) => render_banner_head(line, colorize, parts, &*self.timestamps, width),
+                None => self.render_into(line, parts, self.wrap_width()),
+            }
+            let snippet = self.snippets.as_ref().filter(|_| parts.level >= LogLevel::ERROR);
+            if let (Some(cache), Some((file, number))) = (snippet, parts.location) {
+                push_snippet(line, colorize, cache, file, number);
+            }
+            for crumb in parts.breadcrumbs {
+                let mut time = String::new();
+                self.timestamps.render(&crumb.timestamp, &mut time);
+                let message = self.ansi.apply(&crumb.message);
+                line.push('\n');
+                line.push_str(CONTINUATION);
+                paint(line, colorize, DIMMED, format_args!("{} [{}] {}", time, crumb.component, message));
+            }
+            if let Some(backtrace) = backtrace {
+                for frame in backtrace.lines() {
+                    line.push('\n');
+                    line.push_str(CONTINUATION);
+                    paint(line, colorize, DIMMED, format_args!("{}", frame));
+                }
+            }
+            if let Some(width) = banner {
+                line.push('\n');
+                push_rule(line, colorize, parts.level, width);
+            }
+            if let Ok(mut state) = self.state.lock() {
+                state.write_line(line);
+                if let Some(bell) = self.bell.as_ref().filter(|bell| parts.level >= bell.level) {
+                    state.ring(bell, parts);
+                }
+            }
+
+            // Don't keep one huge entry's worth of memory around forever
+            if line.capacity() > LINE_BUFFER_RETAIN {
+                *line = String::new();
+            }
+        });
+    }
+
+    /// Append the colored console line for `parts`, wrapping the message at `wrap` columns
+    fn render_into(&self, out: &mut String, parts: &LineParts<'_>, wrap: Option<usize>) {
+        if let Some(pattern) = self.pattern.read().unwrap_or_else(|poisoned| poisoned.into_inner()).as_ref() {
+            let parts = &LineParts { static_fields: &self.static_fields, ..*parts };
+            pattern.write_into(out, parts, &*self.timestamps);
+            return;
+        }
+        let fields = self.fields;
+        let colorize = self.colorize();
+        let start = out.len();
+        let separate = |out: &mut String| {
+            if out.len() > start {
+                out.push(' ');
+            }
+        };
+
+        if fields.contains(ConsoleFields::TIMESTAMP) {
+            if colorize {
+                push_style(out, WHITE);
+            }
+            self.write_time(out, parts);
+            if colorize {
+                out.push_str(RESET);
+            }
+        }
+        if let Some(seq) = parts.seq.filter(|_| fields.contains(ConsoleFields::SEQ)) {
+            separate(out);
+            paint(out, colorize, DIMMED, format_args!("#{}", seq));
+        }
+        if fields.contains(ConsoleFields::LEVEL) {
+            // A leading level is not padded so the line never starts with a space
+            let name = parts.level.as_str();
+            if out.len() == start {
+                paint(out, colorize, parts.level.ansi_style(), format_args!("{}", name));
+            } else {
+                out.push(' ');
+                let width = crate::level::width();
+                paint(out, colorize, parts.level.ansi_style(), format_args!("{:^width$}", name));
+            }
+        }
+        if let Some(glyphs) = &self.glyphs {
+            let glyph = glyphs.pick(parts.level, parts.component);
+            // Padded by display width so a wide glyph doesn't push the columns after it
+            let padding = glyphs.width().saturating_sub(glyph.map_or(0, crate::ansi::display_width));
+            if let Some(glyph) = glyph {
+                separate(out);
+                paint(out, colorize, parts.level.ansi_style(), format_args!("{}", glyph));
+            } else if out.len() > start {
+                out.push(' ');
+            }
+            // Nothing at all when no glyph would lead the line
+            if out.len() > start {
+                for _ in 0..padding {
+                    out.push(' ');
+                }
+            }
+        }
+        if fields.contains(ConsoleFields::THREAD) {
+            separate(out);
+            paint(out, colorize, PURPLE, format_args!("[{:?}]", std::thread::current().id()));
+        }
+        // Omit the component column entirely when there is none
+        if fields.contains(ConsoleFields::COMPONENT) && !parts.component.is_empty() {
+            separate(out);
+            paint(out, colorize, BLUE, format_args!("[{}]", parts.component));
+        }
+        if let Some(code) = parts.code {
+            separate(out);
+            paint(out, colorize, WHITE, format_args!("[{}]", code));
+        }
+
+        separate(out);
+        for _ in 0..parts.indent {
+            out.push_str("  ");
+        }
+        match wrap {
+            Some(width) => {
+                let column = crate::ansi::display_width(&out[start..]);
+                push_wrapped(out, parts.message, column, width);
+            }
+            None => {
+                for (i, line) in parts.message.split('\n').enumerate() {
+                    if i > 0 {
+                        out.push('\n');
+                        out.push_str(CONTINUATION);
+                    }
+                    out.push_str(line);
+                }
+            }
+        }
+        if let Some(id) = parts.correlation_id {
+            out.push(' ');
+            paint(out, colorize, DIMMED, format_args!("(corr={})", id));
+        }
+    }
+}
+
+/// Append `message` starting at `column`, broken at spaces to fit in `width` columns
+///
+/// A word wider than a whole line is broken where the line ends. When the
+/// columns before the message leave too little room, it starts on the next line.
+fn push_wrapped(out: &mut String, message: &str, column: usize, width: usize) {
+    let indent = if column <= width / 2 { column } else { CONTINUATION.len() };
+    let continuation = width.saturating_sub(indent).max(MIN_WRAP_COLUMNS);
+    let mut room = width.saturating_sub(column);
+    let mut used = 0;
+    let new_line = |out: &mut String, room: &mut usize, used: &mut usize| {
+        out.push('\n');
+        out.extend(std::iter::repeat_n(' ', indent));
+        *room = continuation;
+        *used = 0;
+    };
+    if room < MIN_WRAP_COLUMNS {
+        out.truncate(out.trim_end_matches(' ').len());
+        new_line(out, &mut room, &mut used);
+    }
+
+    for (i, line) in message.split('\n').enumerate() {
+        if i > 0 {
+            new_line(out, &mut room, &mut used);
+        }
+        for mut word in line.split(' ') {
+            let mut len = crate::ansi::display_width(word);
+            if used > 0 && used + 1 + len > room {
+                new_line(out, &mut room, &mut used);
+            } else if used > 0 {
+                out.push(' ');
+                used += 1;
+            }
+            while used + len > room {
+                let (head, tail) = crate::ansi::split_at_display(word, room - used);
+                out.push_str(head);
+                new_line(out, &mut room, &mut used);
+                word = tail;
+                len = crate::ansi::display_width(word);
+            }
+            out.push_str(word);
+            used += len;
+        }
+    }
+}
+
+/// Columns of the terminal: `COLUMNS` if set, otherwise asked of stdout when that is where the console writes
+fn terminal_width(on_stdout: bool) -> Option<usize> {
+    let columns = std::env::var("COLUMNS").ok().and_then(|columns| columns.parse().ok());
+    columns
+        .filter(|&columns: &usize| columns > 0)
+        .or_else(|| on_stdout.then(stdout_columns).flatten())
+}
+
+#[cfg(unix)]
+fn stdout_columns() -> Option<usize> {
+    let mut size = libc::winsize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    // SAFETY: TIOCGWINSZ fills in the one `winsize` it is given
+    let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+    (result == 0 && size.ws_col > 0).then_some(usize::from(size.ws_col))
+}
+
+#[cfg(not(unix))]
+fn stdout_columns() -> Option<usize> {
+    None
+}
+
+/// Append a full-width rule in the level's color
+fn push_rule(out: &mut String, colorize: bool, level: LogLevel, width: usize) {
+    let rule: String = std::iter::repeat_n(BANNER_RULE, width).collect();
+    paint(out, colorize, level.ansi_style(), format_args!("{}", rule));
+}
+
+/// Append a banner's top rule, message line and metadata line
+///
+/// The bottom rule is added by the caller, after any backtrace.
+fn render_banner_head(
+    out: &mut String,
+    colorize: bool,
+    parts: &LineParts<'_>,
+    timestamps: &dyn TimestampRenderer,
+    width: usize,
+) {
+    push_rule(out, colorize, parts.level, width);
+    out.push('\n');
+    paint(out, colorize, parts.level.ansi_style(), format_args!("{}", parts.level.as_str()));
+    out.push(' ');
+    for (i, line) in parts.message.split('\n').enumerate() {
+        if i > 0 {
+            out.push('\n');
+            out.push_str(CONTINUATION);
+        }
+        out.push_str(line);
+    }
+
+    out.push('\n');
+    out.push_str(CONTINUATION);
+    if colorize {
+        push_style(out, WHITE);
+    }
+    timestamps.render(&parts.timestamp, out);
+    if colorize {
+        out.push_str(RESET);
+    }
+    out.push(' ');
+    paint(out, colorize, PURPLE, format_args!("[{:?}]", std::thread::current().id()));
+    if !parts.component.is_empty() {
+        out.push(' ');
+        paint(out, colorize, BLUE, format_args!("[{}]", parts.component));
+    }
+    if let Some(code) = parts.code {
+        out.push(' ');
+        paint(out, colorize, WHITE, format_args!("[{}]", code));
+    }
+    if let Some(id) = parts.correlation_id {
+        out.push(' ');
+        paint(out, colorize, DIMMED, format_args!("(corr={})", id));
+    }
+}
+
+/// The pieces of an entry that make up its console line
+pub(crate) struct LineParts<'a> {
+    pub(crate) timestamp: Timestamp,
+    /// `None` for lines without an entry, such as progress updates
+    pub(crate) seq: Option<u64>,
+    pub(crate) level: LogLevel,
+    pub(crate) severity: u8,
+    pub(crate) component: &'a str,
+    pub(crate) message: &'a str,
+    /// Shown after the component as `[code]`
+    pub(crate) code: Option<&'a str>,
+    /// Shown after the message as `(corr=id)`
+    pub(crate) correlation_id: Option<&'a str>,
+    /// Span depth to indent the message by
+    pub(crate) indent: usize,
+    /// Print `message` alone, verbatim, see `HorizonLogger::raw`
+    pub(crate) raw: bool,
+    /// The logger's static fields for `{static_fields}`; filled in by the console
+    pub(crate) static_fields: &'a str,
+    /// File and line the entry was logged from, for `source_snippets`
+    pub(crate) location: Option<(&'a str, u32)>,
+    /// Shown below the line, see `HorizonLogger::breadcrumb`
+    pub(crate) breadcrumbs: &'a [Breadcrumb],
+}
+
+impl<'a> LineParts<'a> {
+    pub(crate) fn of(entry: &'a LogEntry, indent: usize) -> Self {
+        LineParts {
+            timestamp: entry.timestamp,
+            seq: Some(entry.seq),
+            level: entry.level,
+            severity: entry.severity,
+            component: &entry.component,
+            message: &entry.message,
+            code: entry.code.as_deref(),
+            correlation_id: entry.correlation_id.as_deref(),
+            indent,
+            raw: entry.raw,
+            static_fields: "",
+            location: None,
+            breadcrumbs: &entry.breadcrumbs,
+        }
+    }
+}
+
+/// Whether lines get ANSI colors, as decided by `colored` (`NO_COLOR`, `CLICOLOR`, a terminal)
+#[cfg(feature = "color")]
+fn should_colorize() -> bool {
+    colored::control::SHOULD_COLORIZE.should_colorize()
+}
+
+/// Lines are always plain without the `color` feature
+#[cfg(not(feature = "color"))]
+fn should_colorize() -> bool {
+    false
+}
+
+/// Append the SGR escape that starts `style`
+fn push_style(out: &mut String, style: &str) {
+    out.push_str("\x1b[");
+    out.push_str(style);
+    out.push('m');
+}
+
+/// Append the source around `file:number` as dimmed continuation lines, if it can be read
+fn push_snippet(out: &mut String, colorize: bool, cache: &SourceCache, file: &str, number: u32) {
+    let Some(lines) = cache.snippet(file, number) else {
+        return;
+    };
+    let width = lines.last().map_or(1, |(n, _)| n.to_string().len());
+    out.push('\n');
+    out.push_str(CONTINUATION);
+    paint(out, colorize, DIMMED, format_args!("--> {}:{}", file, number));
+    for (n, text) in &lines {
+        let marker = if *n == number as usize { '>' } else { ' ' };
+        out.push('\n');
+        out.push_str(CONTINUATION);
+        paint(out, colorize, DIMMED, format_args!("{} {:>width$} | {}", marker, n, text, width = width));
+    }
+}
+
+/// Append `text` in `style`, or plain when colors are off
+fn paint(out: &mut String, colorize: bool, style: &str, text: fmt::Arguments<'_>) {
+    if colorize {
+        push_style(out, style);
+    }
+    let _ = out.write_fmt(text);
+    if colorize {
+        out.push_str(RESET);
+    }
+}
+
+impl HorizonLogger {
+    /// Color console lines as `color` says from now on, see `LoggerBuilder::console_color`
+    pub fn set_console_color(&self, color: ConsoleColor) {
+        self.inner.console.set_color(color);
+    }
+
+    /// Whether console lines are colored, see `set_console_color`
+    pub fn console_color(&self) -> ConsoleColor {
+        self.inner.console.color()
+    }
+
+    /// Lay console lines out with `pattern` from now on, or with the console fields for `None`
+    pub fn set_console_pattern(&self, pattern: Option<Pattern>) {
+        self.inner.console.set_pattern(pattern);
+    }
+
+    /// The console pattern in use, if any, see `set_console_pattern`
+    pub fn console_pattern(&self) -> Option<Pattern> {
+        self.inner.console.pattern()
+    }
+
+    /// Show a status line that is redrawn in place instead of scrolling
+    ///
+    /// Progress updates are not stored in history. The line is terminated
+    /// automatically before the next normal entry is printed. When stdout
+    /// is not a terminal, updates are logged as ordinary INFO entries at
+    /// most once every few seconds instead.
+    pub fn progress(&self, component: impl ComponentArg, message: &str) {
+        if self.inner.kill_switch.is_off() {
+            self.inner.kill_switch.suppress(1);
+            return;
+        }
+        let console = &self.inner.console;
+        let now = self.inner.clock.now();
+
+        if !console.in_place {
+            let name = component.as_str();
+            let due = console.state.lock().is_ok_and(|mut state| {
+                state.last_progress = Some((name.to_string(), message.to_string()));
+                let due = state
+                    .last_fallback
+                    .is_none_or(|last| now.duration_since(last) >= PROGRESS_FALLBACK_INTERVAL);
+                if due {
+                    state.last_fallback = Some(now);
+                }
+                due
+            });
+            if due {
+                self.info(component, message);
+            }
+            return;
+        }
+
+        let mut line = String::new();
+        let parts = LineParts {
+            timestamp: now,
+            seq: None,
+            level: LogLevel::INFO,
+            severity: LogLevel::INFO.default_severity(),
+            component: component.as_str(),
+            message,
+            code: None,
+            correlation_id: None,
+            indent: 0,
+            raw: false,
+            static_fields: "",
+            location: None,
+            breadcrumbs: &[],
+        };
+        // Redrawn in place, so never wrapped
+        console.render_into(&mut line, &parts, None);
+        if let Ok(mut state) = console.state.lock() {
+            if let Some(out) = state.stream() {
+                let _ = write!(out, "\r{}{}", line, CLEAR_TO_EOL);
+                let _ = out.flush();
+            }
+            state.progress_open = true;
+            state.last_progress = Some((component.as_str().to_string(), message.to_string()));
+        }
+    }
+
+    /// Finish the current progress line, recording its final state as an INFO entry
+    pub fn progress_done(&self) {
+        let last = self.inner.console.state.lock().ok().and_then(|mut state| {
+            state.last_fallback = None;
+            if state.progress_open {
+                // Replace the transient line with the permanent entry below
+                if let Some(out) = state.stream() {
+                    let _ = write!(out, "\r{}", CLEAR_TO_EOL);
+                }
+                state.progress_open = false;
+            }
+            state.last_progress.take()
+        });
+
+        if let Some((component, message)) = last {
+            self.info(&component, &message);
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::clock::{Clock, ManualClock};
+    use crate::format::human_time;
+    use std::sync::Arc;
+
+    /// A cloneable in-memory writer for inspecting console output
+    #[derive(Clone, Default)]
+    pub(crate) struct SharedBuf(pub(crate) Arc<Mutex<Vec<u8>>>);
+
+    impl SharedBuf {
+        pub(crate) fn contents(&self) -> String {
+            String::from_utf8_lossy(&self.0.lock().unwrap()).into_owned()
+        }
+    }
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn strip_ansi(text: &str) -> String {
+        let mut out = String::new();
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                chars.by_ref().find(|c| c.is_ascii_alphabetic());
+            } else {
+                out.push(c);
+            }
+        }
+        out
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn test_source_snippets_under_errors() {
+        let buf = SharedBuf::default();
+        let logger = HorizonLogger::builder()
+            .announce_run(false)
+            .console(Console::new(Box::new(buf.clone()), false))
+            .console_fields(ConsoleFields::LEVEL | ConsoleFields::MESSAGE)
+            .source_snippets(true)
+            .build();
+        for (level, line) in [(LogLevel::ERROR, 4), (LogLevel::WARN, 4), (LogLevel::ERROR, 1), (LogLevel::ERROR, 99)] {
+            let options = crate::CallOptions {
+                location: Some(("tests/fixtures/matchmaker.rs", line)),
+                ..crate::CallOptions::default()
+            };
+            logger.log_with(level, "LOBBY", "queue too short", options);
+        }
+        let missing = crate::CallOptions {
+            location: Some(("no/such/file.rs", 3)),
+            ..crate::CallOptions::default()
+        };
+        logger.log_with(LogLevel::CRITICAL, "LOBBY", "no sources", missing);
+
+        assert_eq!(
+            strip_ansi(&buf.contents()),
+            concat!(
+                "ERROR queue too short\n",
+                "    --> tests/fixtures/matchmaker.rs:4\n",
+                "      3 |     if queue.len() < 2 {\n",
+                "    > 4 |         return Err(format!(\"only {} players queued\", queue.len()));\n",
+                "      5 |     }\n",
+                "WARN queue too short\n",
+                "ERROR queue too short\n",
+                "    --> tests/fixtures/matchmaker.rs:1\n",
+                "    > 1 | // Source file the console snippet tests point entries at\n",
+                "      2 | fn form_match(queue: &[u32]) -> Result<(), String> {\n",
+                "ERROR queue too short\n",
+                "CRIT no sources\n",
+            )
+        );
+        assert!(logger.get_history().iter().all(|entry| !entry.to_json().contains("matchmaker.rs")));
+
+        // Call sites come from `error` itself
+        let buf = SharedBuf::default();
+        let logger = HorizonLogger::builder()
+            .announce_run(false)
+            .console(Console::new(Box::new(buf.clone()), false))
+            .console_fields(ConsoleFields::MESSAGE)
+            .source_snippets(true)
+            .build();
+        logger.error("DB", "pool exhausted");
+        assert!(buf.contents().contains(r#"logger.error("DB", "pool exhausted");"#), "{}", buf.contents());
+    }
+
+    #[test]
+    fn test_console_fields() {
+        use ConsoleFields as F;
+
+        let clock = ManualClock::new(Timestamp::now());
+        let ts = human_time(&clock.now());
+        let thread = format!("[{:?}]", std::thread::current().id());
+        let cases = [
+            (F::default(), "NET", format!("{ts}  INFO   {thread} [NET] hello")),
+            (F::default(), "", format!("{ts}  INFO   {thread} hello")),
+            (F::MESSAGE, "NET", "hello".to_string()),
+            (F::LEVEL, "NET", "INFO hello".to_string()),
+            (F::LEVEL | F::COMPONENT | F::MESSAGE, "NET", "INFO [NET] hello".to_string()),
+            (F::COMPONENT, "", "hello".to_string()),
+            (F::THREAD | F::COMPONENT, "NET", format!("{thread} [NET] hello")),
+            (F::TIMESTAMP, "NET", format!("{ts} hello")),
+            (F::TIMESTAMP | F::SEQ | F::COMPONENT, "NET", format!("{ts} #0 [NET] hello")),
+            (F::SEQ | F::MESSAGE, "", "#0 hello".to_string()),
+        ];
+
+        for (fields, component, expected) in cases {
+            let buf = SharedBuf::default();
+            let logger = HorizonLogger::builder()
+                .announce_run(false)
+                .clock(ManualClock::new(clock.now()))
+                .console(Console::new(Box::new(buf.clone()), false))
+                .console_fields(fields)
+                .build();
+            logger.info(component, "hello");
+            assert_eq!(strip_ansi(&buf.contents()), format!("{}\n", expected), "{:?}", fields);
+        }
+    }
+
+    #[test]
+    fn test_color_and_pattern_switch_at_runtime() {
+        let buf = SharedBuf::default();
+        let logger = HorizonLogger::builder()
+            .announce_run(false)
+            .console(Console::new(Box::new(buf.clone()), false))
+            .console_fields(ConsoleFields::LEVEL | ConsoleFields::MESSAGE)
+            .console_color(ConsoleColor::Always)
+            .build();
+        logger.info("NET", "colored");
+        logger.set_console_color(ConsoleColor::Never);
+        logger.info("NET", "plain");
+        logger.set_console_pattern(Some("{component}: {message}".parse().unwrap()));
+        logger.info("NET", "patterned");
+        logger.set_console_pattern(None);
+        logger.info("NET", "fields again");
+
+        let contents = buf.contents();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines[0].contains('\x1b'), cfg!(feature = "color"), "{:?}", lines[0]);
+        assert_eq!(&lines[1..], ["INFO plain", "NET: patterned", "INFO fields again"]);
+    }
+
+    fn wrapping_logger(buf: &SharedBuf, fields: ConsoleFields) -> HorizonLogger {
+        HorizonLogger::builder()
+            .announce_run(false)
+            .console(Console::new(Box::new(buf.clone()), false))
+            .console_fields(fields)
+            .console_wrap(ConsoleWrap::Columns(60))
+            .build()
+    }
+
+    #[test]
+    fn test_soft_wrap_snapshot() {
+        let buf = SharedBuf::default();
+        let logger = wrapping_logger(&buf, ConsoleFields::LEVEL | ConsoleFields::COMPONENT | ConsoleFields::MESSAGE);
+        let long = "matchmaking queue over capacity, throttling new connections until the backlog drains";
+        logger.warn("NET", long);
+        logger.info("NET", "short");
+        logger.info("NET", "first line\nsecond line that is long enough to need wrapping at sixty columns");
+        let url = "https://assets.example.com/bundles/4f1c2a9e7b3d5f6a8c0e2b4d6f8a0c2e/skins.pak";
+        logger.error("HTTP", &format!("fetch {} failed", url));
+        logger.set_span_indent(true);
+        let span = logger.span("NET", "sync");
+        logger.info("NET", "inside a span the message column moves right, and so do continuations");
+        drop(span);
+
+        let out = strip_ansi(&buf.contents());
+        assert_eq!(
+            out.rsplit_once(" (").unwrap().0,
+            "\
+WARN [NET] matchmaking queue over capacity, throttling new
+           connections until the backlog drains
+INFO [NET] short
+INFO [NET] first line
+           second line that is long enough to need wrapping
+           at sixty columns
+ERROR [HTTP] fetch
+             https://assets.example.com/bundles/4f1c2a9e7b3d
+             5f6a8c0e2b4d6f8a0c2e/skins.pak failed
+INFO [NET] >> sync
+INFO [NET]   inside a span the message column moves right,
+             and so do continuations
+INFO [NET] << sync"
+        );
+
+        // Only the console wraps
+        let entry = &logger.get_history()[0];
+        assert_eq!(entry.message, long);
+        assert_eq!(logger.format_entry(entry, crate::Format::Text).lines().count(), 1);
+    }
+
+    #[test]
+    fn test_soft_wrap_with_default_fields() {
+        let buf = SharedBuf::default();
+        let logger = wrapping_logger(&buf, ConsoleFields::default());
+        let message = "a message under the full metadata prefix wraps at word boundaries and \
+                       continues four spaces in because the message column is past half the width";
+        logger.info("NET", message);
+
+        let out = strip_ansi(&buf.contents());
+        let lines: Vec<_> = out.lines().collect();
+        assert!(lines.len() > 2, "{:?}", lines);
+        assert!(lines.iter().all(|line| line.chars().count() <= 60), "{:?}", lines);
+        assert!(lines[1..].iter().all(|line| line.starts_with("    ") && !line.starts_with("     ")));
+        let words: Vec<_> = lines.iter().flat_map(|line| line.split_whitespace()).collect();
+        assert!(words.join(" ").ends_with(message), "{:?}", lines);
+    }
+
+    #[test]
+    fn test_wrap_ignores_escape_sequences() {
+        let prefix = "\x1b[1;31mCRIT\x1b[0m \x1b[34m[GPU]\x1b[0m ";
+        assert_eq!(crate::ansi::display_width(prefix), 11);
+
+        let mut out = prefix.to_string();
+        let message = "device \x1b[33mlost\x1b[0m while uploading the shadow atlas, recreating swapchain";
+        push_wrapped(&mut out, message, crate::ansi::display_width(prefix), 60);
+        assert_eq!(
+            strip_ansi(&out),
+            "CRIT [GPU] device lost while uploading the shadow atlas,\n           recreating swapchain"
+        );
+    }
+
+    #[test]
+    fn test_wrap_counts_double_width_characters() {
+        let prefix = "INFO [CHAT] ";
+        // 17 characters would fit in the 28 columns after the prefix, but they take 31
+        let message = "玩家 已加入 🎉🎉 欢迎来到地平线 世界这是一个很长的句子没有空格呢";
+        let mut out = prefix.to_string();
+        push_wrapped(&mut out, message, crate::ansi::display_width(prefix), 40);
+
+        let lines: Vec<_> = out.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "INFO [CHAT] 玩家 已加入 🎉🎉",
+                "            欢迎来到地平线",
+                "            世界这是一个很长的句子没有空",
+                "            格呢",
+            ]
+        );
+        assert!(lines.iter().all(|line| crate::ansi::display_width(line) <= 40), "{:?}", lines);
+    }
+
+    #[test]
+    fn test_progress_is_terminated_before_normal_entries() {
+        let buf = SharedBuf::default();
+        let logger = HorizonLogger::builder()
+            .announce_run(false)
+            .console(Console::new(Box::new(buf.clone()), true))
+            .build();
+
+        logger.progress("IMPORT", "10%");
+        logger.progress("IMPORT", "37%");
+        logger.info("IMPORT", "found texture");
+        logger.progress("IMPORT", "80%");
+        logger.progress_done();
+
+        let out = buf.contents();
+        let lines: Vec<&str> = out.split('\n').collect();
+        assert_eq!(lines.len(), 4, "{:?}", out);
+        assert!(lines[0].starts_with('\r') && lines[0].ends_with(&format!("37%{}", CLEAR_TO_EOL)));
+        assert!(lines[1].ends_with("found texture"));
+        assert!(lines[2].contains("80%") && lines[2].ends_with("[IMPORT] 80%"));
+        assert_eq!(lines[3], "");
+
+        let messages: Vec<String> = logger.get_history().into_iter().map(|e| e.message).collect();
+        assert_eq!(messages, vec!["found texture", "80%"]);
+    }
+
+    #[test]
+    fn test_progress_falls_back_to_periodic_info_without_tty() {
+        let clock = Arc::new(ManualClock::new(Timestamp::now()));
+        let logger = HorizonLogger::builder()
+            .announce_run(false)
+            .clock(clock.clone())
+            .console(Console::new(Box::new(SharedBuf::default()), false))
+            .build();
+
+        logger.progress("IMPORT", "1%");
+        logger.progress("IMPORT", "2%");
+        clock.advance(Duration::from_secs(6));
+        logger.progress("IMPORT", "50%");
+        logger.progress("IMPORT", "99%");
+        logger.progress_done();
+
+        let messages: Vec<String> = logger.get_history().into_iter().map(|e| e.message).collect();
+        assert_eq!(messages, vec!["1%", "50%", "99%"]);
+    }
+
+    #[test]
+    fn test_banner_layout() {
+        let buf = SharedBuf::default();
+        let clock = ManualClock::new(Timestamp::now());
+        let ts = human_time(&clock.now());
+        let thread = format!("[{:?}]", std::thread::current().id());
+        let logger = HorizonLogger::builder()
+            .announce_run(false)
+            .clock(clock)
+            .console(Console::new(Box::new(buf.clone()), false))
+            .console_fields(ConsoleFields::LEVEL | ConsoleFields::MESSAGE)
+            .banner_on(LogLevel::ERROR)
+            .banner_width(24)
+            .build();
+
+        logger.warn("DB", "slow query");
+        let corr = logger.with_correlation("req-7");
+        logger.critical("DB", "connection lost\nretrying");
+        drop(corr);
+
+        let rule = "═".repeat(24);
+        assert_eq!(
+            strip_ansi(&buf.contents()),
+            format!(
+                "WARN slow query\n{rule}\nCRIT connection lost\n    retrying\n    {ts} {thread} [DB] (corr=req-7)\n{rule}\n"
+            )
+        );
+        let history = logger.get_history();
+        assert_eq!(history[1].message, "connection lost\nretrying");
+    }
+
+    #[test]
+    fn test_coarse_time_shares_text_within_window() {
+        let buf = SharedBuf::default();
+        let start = Timestamp::from_millis(1_700_000_000_120);
+        let clock = Arc::new(ManualClock::new(start.saturating_add(Duration::from_millis(3))));
+        let logger = HorizonLogger::builder()
+            .announce_run(false)
+            .clock(clock.clone())
+            .console(Console::new(Box::new(buf.clone()), false))
+            .console_fields(ConsoleFields::TIMESTAMP | ConsoleFields::MESSAGE)
+            .console_coarse_time(Duration::from_millis(10))
+            .build();
+
+        logger.info("NET", "a");
+        clock.advance(Duration::from_micros(4_500));
+        logger.debug("NET", "b");
+        logger.warn("NET", "c");
+        clock.advance(Duration::from_millis(3));
+        logger.info("NET", "d");
+
+        let window = human_time(&start);
+        let next = human_time(&start.saturating_add(Duration::from_millis(10)));
+        let exact = human_time(&start.saturating_add(Duration::from_micros(7_500)));
+        assert_ne!(exact, window);
+        assert_eq!(
+            strip_ansi(&buf.contents()),
+            format!("{window} a\n{window} b\n{exact} c\n{next} d\n")
+        );
+        let history = logger.get_history();
+        assert_eq!(history[0].timestamp, start.saturating_add(Duration::from_millis(3)));
+        assert_eq!(history[1].timestamp, start.saturating_add(Duration::from_micros(7_500)));
+    }
+
+    #[test]
+    fn test_bell_rate_limited_and_tty_only() {
+        let clock = Arc::new(ManualClock::new(Timestamp::from_millis(0)));
+        let console_logger = |buf: &SharedBuf, is_tty: bool| {
+            HorizonLogger::builder()
+                .announce_run(false)
+                .clock(clock.clone())
+                .console(Console::new(Box::new(buf.clone()), is_tty))
+                .console_fields(ConsoleFields::MESSAGE)
+                .bell_on(LogLevel::ERROR)
+                .bell_interval(Duration::from_secs(5))
+                .bell_notification(true)
+                .build()
+        };
+
+        let buf = SharedBuf::default();
+        let logger = console_logger(&buf, true);
+        logger.warn("GAME", "w");
+        logger.error("GAME", "db\ndown");
+        logger.critical("GAME", "storm");
+        clock.set(Timestamp::from_millis(5_000));
+        logger.critical("GAME", "again");
+        assert_eq!(
+            buf.contents(),
+            "w\ndb\n    down\n\x07\x1b]9;db down\x07storm\nagain\n\x07\x1b]9;again\x07"
+        );
+        assert!(!logger.format_entry(&logger.get_history()[3], crate::Format::Text).contains('\x07'));
+
+        let piped = SharedBuf::default();
+        console_logger(&piped, false).critical("GAME", "storm");
+        assert_eq!(piped.contents(), "storm\n");
+    }
+
+    #[test]
+    fn test_console_writer_gets_the_printed_lines() {
+        let clock = Arc::new(ManualClock::new(Timestamp::from_millis(1_700_000_000_000)));
+        let builder = |pattern: Option<&str>| {
+            let builder = HorizonLogger::builder()
+                .announce_run(false)
+                .clock(clock.clone())
+                .min_level(LogLevel::INFO)
+                .console_wrap(ConsoleWrap::Columns(40))
+                .capture_backtrace(LogLevel::CRITICAL);
+            match pattern {
+                Some(pattern) => builder.console_pattern(pattern.parse().unwrap()),
+                None => builder,
+            }
+        };
+        let log = |logger: &HorizonLogger| {
+            logger.debug("NET", "filtered out");
+            logger.info("NET", "connected to the matchmaking service at eu-west-2");
+            logger.warn("GAME", "desync\nat tick 40");
+            logger.progress("ASSETS", "12/40 textures");
+            logger.progress_done();
+            logger.error("", "no component");
+        };
+
+        for pattern in [None, Some("{level} [{component}] {message}")] {
+            let buf = SharedBuf::default();
+            let printed = builder(pattern).console(Console::new(Box::new(buf.clone()), false)).build();
+            log(&printed);
+
+            let lines = Arc::new(Mutex::new(Vec::new()));
+            let received = lines.clone();
+            let written = builder(pattern)
+                .console(Console::new(Box::new(io::sink()), false))
+                .console_writer(move |line| received.lock().unwrap().push(line.to_string()))
+                .build();
+            log(&written);
+
+            let lines = lines.lock().unwrap();
+            assert_eq!(lines.len(), 5);
+            let joined: String = lines.iter().map(|line| format!("{}\n", line)).collect();
+            assert_eq!(joined, buf.contents());
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use crate::{correlation, span, HorizonLogger};
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+/// A thread's correlation ids and open spans, captured to carry them to another thread
+///
+/// Take one with `capture_context` where the work is handed off and
+/// `attach` it where the work runs, so entries logged there are tagged
+/// and indented as if they were logged on the original thread. A span
+/// attached this way stays open on the other thread until the guard drops,
+/// wherever the original span ends.
+#[derive(Debug, Clone, Default)]
+pub struct LogContext {
+    correlation: Vec<(u64, Arc<str>)>,
+    spans: Vec<u64>,
+}
+
+impl LogContext {
+    /// Install this context on the current thread until the guard drops
+    ///
+    /// The thread's own correlation ids and spans are set aside meanwhile
+    /// and restored exactly when the guard drops. Guards nest; drop them in
+    /// reverse order, as scoping does.
+    pub fn attach(&self) -> ContextGuard {
+        ContextGuard {
+            correlation: correlation::replace_stack(self.correlation.clone()),
+            spans: span::replace_stack(self.spans.clone()),
+            _not_send: PhantomData,
+        }
+    }
+
+    /// The innermost correlation id in this context
+    pub fn correlation_id(&self) -> Option<&str> {
+        self.correlation.last().map(|(_, id)| &**id)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.correlation.is_empty() && self.spans.is_empty()
+    }
+}
+
+/// Keeps a `LogContext` installed on the current thread, see `LogContext::attach`
+pub struct ContextGuard {
+    correlation: Vec<(u64, Arc<str>)>,
+    spans: Vec<u64>,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for ContextGuard {
+    fn drop(&mut self) {
+        correlation::replace_stack(std::mem::take(&mut self.correlation));
+        span::replace_stack(std::mem::take(&mut self.spans));
+    }
+}
+
+impl HorizonLogger {
+    /// The current thread's correlation ids and open spans, to `attach` on another thread
+    pub fn capture_context(&self) -> LogContext {
+        LogContext {
+            correlation: correlation::stack(),
+            spans: span::stack(),
+        }
+    }
+
+    /// Spawn a thread that runs `f` with the current thread's context attached
+    pub fn spawn_with_context<F, T>(&self, f: F) -> JoinHandle<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        thread::spawn(self.wrap_fn(f))
+    }
+
+    /// `f` with the current thread's context attached while it runs, for thread pools and task spawners
+    ///
+    /// E.g. `pool.spawn(logger.wrap_fn(move || ...))` with rayon, or
+    /// `spawn_blocking(logger.wrap_fn(...))` with tokio.
+    pub fn wrap_fn<F, T>(&self, f: F) -> impl FnOnce() -> T + Send + 'static
+    where
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let context = self.capture_context();
+        move || {
+            let _context = context.attach();
+            f()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::correlation::current_correlation;
+    use crate::testing::CaptureLogger;
+
+    #[test]
+    fn test_context_follows_work_to_other_threads() {
+        let logger = CaptureLogger::new();
+        let _request = logger.with_correlation("req-7");
+        let span = logger.span("MATCH", "find_match");
+
+        logger.info("MATCH", "scoring on main");
+        let worker = logger.logger().clone();
+        logger
+            .spawn_with_context(move || worker.info("POOL", "scoring on worker"))
+            .join()
+            .unwrap();
+        let worker = logger.logger().clone();
+        thread::spawn(logger.wrap_fn(move || worker.info("POOL", "scoring in pool")))
+            .join()
+            .unwrap();
+        drop(span);
+
+        let entries = logger.entries();
+        let scoring: Vec<_> = entries.iter().filter(|e| e.message.starts_with("scoring")).collect();
+        assert_eq!(scoring.len(), 3);
+        for entry in scoring {
+            assert_eq!(entry.correlation_id.as_deref(), Some("req-7"), "{}", entry.message);
+            assert_eq!(entry.span_id, Some(entries[0].span_id.unwrap()), "{}", entry.message);
+        }
+    }
+
+    #[test]
+    fn test_nested_attach_restores_state() {
+        let logger = CaptureLogger::new();
+        let request = {
+            let _request = logger.with_correlation("req-1");
+            let _span = logger.span("NET", "handle");
+            logger.capture_context()
+        };
+        assert_eq!(request.correlation_id(), Some("req-1"));
+        assert!(logger.capture_context().is_empty());
+
+        let _local = logger.with_correlation("local");
+        {
+            let _request = request.attach();
+            assert_eq!(current_correlation().as_deref(), Some("req-1"));
+            assert_eq!(span::depth(), 1);
+            {
+                let _empty = LogContext::default().attach();
+                assert_eq!((current_correlation(), span::depth()), (None, 0));
+                let _inner = logger.with_correlation("inner");
+                logger.info("NET", "inside");
+            }
+            assert_eq!(current_correlation().as_deref(), Some("req-1"));
+        }
+        assert_eq!((current_correlation().as_deref(), span::depth()), (Some("local"), 0));
+        let entry = logger.entries().pop().unwrap();
+        assert_eq!((entry.correlation_id.as_deref(), entry.span_id), (Some("inner"), None));
+    }
+}
@@ -0,0 +1,146 @@
+use crate::HorizonLogger;
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Source of process-wide unique guard tokens
+static NEXT_TOKEN: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    /// Correlation ids pushed on this thread with their guard tokens, innermost last
+    static CORRELATION_STACK: RefCell<Vec<(u64, Arc<str>)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Innermost correlation id on the current thread
+pub(crate) fn current() -> Option<Arc<str>> {
+    CORRELATION_STACK.with(|stack| stack.borrow().last().map(|(_, id)| id.clone()))
+}
+
+/// This thread's correlation stack, for `capture_context`
+pub(crate) fn stack() -> Vec<(u64, Arc<str>)> {
+    CORRELATION_STACK.with(|stack| stack.borrow().clone())
+}
+
+/// Swap this thread's correlation stack for `ids`, returning the old one
+pub(crate) fn replace_stack(ids: Vec<(u64, Arc<str>)>) -> Vec<(u64, Arc<str>)> {
+    CORRELATION_STACK.with(|stack| stack.replace(ids))
+}
+
+/// The correlation id entries on this thread are currently tagged with
+///
+/// Pass it to `with_correlation` on another thread to carry a request's id
+/// across a thread boundary.
+pub fn current_correlation() -> Option<String> {
+    current().map(|id| id.to_string())
+}
+
+/// Tags entries logged on this thread with a correlation id until dropped
+///
+/// Guards nest; dropping one restores the id that was in effect before it.
+/// Like spans, guards are tied to their thread and therefore are not `Send`.
+pub struct CorrelationGuard {
+    token: u64,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for CorrelationGuard {
+    fn drop(&mut self) {
+        CORRELATION_STACK.with(|stack| {
+            let mut stack = stack.borrow_mut();
+            if let Some(pos) = stack.iter().rposition(|(token, _)| *token == self.token) {
+                stack.remove(pos);
+            }
+        });
+    }
+}
+
+impl HorizonLogger {
+    /// Tag every entry logged on this thread with `id` while the guard lives
+    pub fn with_correlation(&self, id: &str) -> CorrelationGuard {
+        let token = NEXT_TOKEN.fetch_add(1, Ordering::Relaxed);
+        CORRELATION_STACK.with(|stack| stack.borrow_mut().push((token, id.into())));
+        CorrelationGuard {
+            token,
+            _not_send: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::console::tests::SharedBuf;
+    use crate::console::Console;
+    use crate::testing::CaptureLogger;
+    use crate::{ConsoleFields, Format};
+    use std::thread;
+
+    fn correlations(logger: &CaptureLogger) -> Vec<Option<String>> {
+        logger
+            .entries()
+            .into_iter()
+            .map(|e| e.correlation_id.map(|id| id.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_guards_nest_and_restore() {
+        let logger = CaptureLogger::new();
+        {
+            let _request = logger.with_correlation("req-1");
+            logger.info("MATCH", "queued");
+            {
+                let _retry = logger.with_correlation("req-1/retry");
+                assert_eq!(current_correlation().as_deref(), Some("req-1/retry"));
+                logger.info("MATCH", "retrying");
+            }
+            logger.info("MATCH", "matched");
+        }
+        logger.info("MATCH", "idle");
+
+        assert_eq!(
+            correlations(&logger),
+            vec![Some("req-1".into()), Some("req-1/retry".into()), Some("req-1".into()), None]
+        );
+        assert_eq!(current_correlation(), None);
+
+        let entry = &logger.entries()[0];
+        assert!(logger.format_entry(entry, Format::Json).contains(r#","corr":"req-1""#));
+        assert!(logger.format_entry(entry, Format::Logfmt).contains(" corr=req-1"));
+    }
+
+    #[test]
+    fn test_console_suffix() {
+        let buf = SharedBuf::default();
+        let logger = HorizonLogger::builder()
+            .announce_run(false)
+            .console(Console::new(Box::new(buf.clone()), false))
+            .console_fields(ConsoleFields::COMPONENT)
+            .build();
+
+        let _request = logger.with_correlation("abc123");
+        logger.info("MATCH", "queued");
+        assert_eq!(buf.contents(), "[MATCH] queued (corr=abc123)\n");
+    }
+
+    #[test]
+    fn test_forwarding_across_threads() {
+        let logger = CaptureLogger::new();
+        let _request = logger.with_correlation("abc123");
+
+        let id = current_correlation().unwrap();
+        let worker = logger.clone();
+        thread::spawn(move || {
+            // A fresh thread starts untagged until the id is forwarded
+            worker.info("POOL", "untagged");
+            let _guard = worker.with_correlation(&id);
+            worker.info("POOL", "scoring candidates");
+        })
+        .join()
+        .unwrap();
+        logger.info("MATCH", "done");
+
+        assert_eq!(correlations(&logger), vec![None, Some("abc123".into()), Some("abc123".into())]);
+    }
+}
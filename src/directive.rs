@@ -0,0 +1,358 @@
+//! Runtime level changes from filter directives like `warn,network=debug`
+//!
+//! A directive is a comma-separated list of parts. A bare level sets the
+//! logger's minimum level; `target=level` sets the minimum for a component
+//! and its children, the most specific target winning. Targets are matched
+//! case-insensitively and `trace` is accepted as a synonym for `debug`.
+
+use crate::history::component_matches;
+use crate::{HorizonLogger, LogLevel};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::RwLock;
+
+/// Per-component minimum levels set by directives
+pub(crate) struct Directives {
+    /// `(target, level)` pairs with upper-cased targets, longest first
+    targets: RwLock<Vec<(String, LogLevel)>>,
+    /// `targets` is non-empty; checked first so unfiltered calls skip the lock
+    any: AtomicBool,
+    /// Lowest level any target allows
+    floor: AtomicU8,
+}
+
+impl Default for Directives {
+    fn default() -> Self {
+        Directives {
+            targets: RwLock::default(),
+            any: AtomicBool::new(false),
+            floor: AtomicU8::new(u8::MAX),
+        }
+    }
+}
+
+impl Directives {
+    /// Lowest level some target lets through, if any target is set
+    pub(crate) fn floor(&self) -> u8 {
+        self.floor.load(Ordering::Relaxed)
+    }
+
+    /// The target-specific minimum for `component`, if one applies
+    pub(crate) fn level_for(&self, component: &str) -> Option<LogLevel> {
+        if !self.any.load(Ordering::Relaxed) {
+            return None;
+        }
+        let targets = self.targets.read().ok()?;
+        targets
+            .iter()
+            .find(|(target, _)| matches_target(component, target))
+            .map(|(_, level)| *level)
+    }
+}
+
+/// `component_matches`, ignoring ASCII case
+fn matches_target(component: &str, target: &str) -> bool {
+    if component.bytes().any(|b| b.is_ascii_lowercase()) {
+        component_matches(&component.to_ascii_uppercase(), target)
+    } else {
+        component_matches(component, target)
+    }
+}
+
+/// Lower-case level names as written in directives
+fn directive_name(level: LogLevel) -> &'static str {
+    match level {
+        LogLevel::DEBUG => "debug",
+        LogLevel::INFO => "info",
+        LogLevel::WARN => "warn",
+        LogLevel::ERROR => "error",
+        LogLevel::CRITICAL => "critical",
+    }
+}
+
+/// One parsed part of a directive
+struct Part {
+    /// `None` for the logger's minimum level
+    target: Option<String>,
+    level: LogLevel,
+}
+
+fn parse(directive: &str) -> Result<Vec<Part>, DirectiveError> {
+    let error = |token: &str, kind| DirectiveError {
+        token: token.to_string(),
+        kind,
+    };
+
+    let mut parts = Vec::new();
+    for token in directive.split(',').map(str::trim) {
+        if token.is_empty() {
+            return Err(error(token, DirectiveErrorKind::Empty));
+        }
+        let (target, level) = match token.split_once('=') {
+            Some((target, level)) => (Some(target.trim()), level.trim()),
+            None => (None, token),
+        };
+        if target.is_some_and(|target| target.is_empty() || target.contains(char::is_whitespace)) {
+            return Err(error(token, DirectiveErrorKind::InvalidTarget));
+        }
+        let level = if level.eq_ignore_ascii_case("trace") {
+            LogLevel::DEBUG
+        } else {
+            level.parse().map_err(|_| error(token, DirectiveErrorKind::UnknownLevel))?
+        };
+        parts.push(Part {
+            target: target.map(|target| target.to_ascii_uppercase()),
+            level,
+        });
+    }
+    Ok(parts)
+}
+
+/// A directive that was rejected; nothing in it was applied
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirectiveError {
+    /// The part of the directive at fault
+    pub token: String,
+    pub kind: DirectiveErrorKind,
+}
+
+/// Why a directive was rejected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DirectiveErrorKind {
+    /// The directive, or one of its comma-separated parts, is empty
+    Empty,
+    /// The part before `=` is empty or contains whitespace
+    InvalidTarget,
+    /// The level does not name any log level
+    UnknownLevel,
+}
+
+impl fmt::Display for DirectiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid directive `{}`: ", self.token)?;
+        match self.kind {
+            DirectiveErrorKind::Empty => write!(f, "empty directive"),
+            DirectiveErrorKind::InvalidTarget => write!(f, "invalid target"),
+            DirectiveErrorKind::UnknownLevel => write!(f, "unknown log level"),
+        }
+    }
+}
+
+impl std::error::Error for DirectiveError {}
+
+/// One level changed by `apply_directive`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LevelChange {
+    /// Upper-cased component, or `None` for the logger's minimum level
+    pub target: Option<String>,
+    /// Level in effect for the target before, possibly inherited
+    pub old: LogLevel,
+    pub new: LogLevel,
+}
+
+/// What `apply_directive` changed, one line per target when displayed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AppliedChange {
+    pub changes: Vec<LevelChange>,
+}
+
+impl fmt::Display for AppliedChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, change) in self.changes.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(
+                f,
+                "{}: {} -> {}",
+                change.target.as_deref().unwrap_or("default"),
+                directive_name(change.old),
+                directive_name(change.new)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl HorizonLogger {
+    /// Apply a filter directive such as `warn` or `network=debug,game/combat=info`
+    ///
+    /// Every part is validated before any is applied, and the parts take
+    /// effect together. Target levels persist until replaced by a later
+    /// directive; `set_min_level` leaves them alone.
+    pub fn apply_directive(&self, directive: &str) -> Result<AppliedChange, DirectiveError> {
+        let parts = parse(directive)?;
+        let directives = &self.inner.directives;
+        let Ok(mut targets) = directives.targets.write() else {
+            return Ok(AppliedChange::default());
+        };
+
+        let mut applied = AppliedChange::default();
+        for part in parts {
+            let global = self.min_level();
+            match part.target {
+                None => {
+                    self.set_min_level(part.level);
+                    applied.changes.push(LevelChange {
+                        target: None,
+                        old: global,
+                        new: part.level,
+                    });
+                }
+                Some(target) => {
+                    let old = targets
+                        .iter()
+                        .find(|(existing, _)| component_matches(&target, existing))
+                        .map_or(global, |(_, level)| *level);
+                    targets.retain(|(existing, _)| *existing != target);
+                    targets.push((target.clone(), part.level));
+                    targets.sort_by_key(|(target, _)| std::cmp::Reverse(target.len()));
+                    applied.changes.push(LevelChange {
+                        target: Some(target),
+                        old,
+                        new: part.level,
+                    });
+                }
+            }
+        }
+
+        let floor = targets.iter().map(|(_, level)| level.to_u8()).min().unwrap_or(u8::MAX);
+        directives.floor.store(floor, Ordering::Relaxed);
+        directives.any.store(!targets.is_empty(), Ordering::Relaxed);
+        Ok(applied)
+    }
+
+    /// Replace the whole level configuration with `directive`
+    ///
+    /// Unlike `apply_directive`, targets missing from `directive` are
+    /// removed. Without a bare level the logger's minimum level is kept.
+    /// Every target whose effective level changed is reported.
+    pub fn replace_directives(&self, directive: &str) -> Result<AppliedChange, DirectiveError> {
+        let parts = parse(directive)?;
+        let directives = &self.inner.directives;
+        let Ok(mut targets) = directives.targets.write() else {
+            return Ok(AppliedChange::default());
+        };
+
+        let old_global = self.min_level();
+        let mut new_global = old_global;
+        let mut new_targets: Vec<(String, LogLevel)> = Vec::new();
+        for part in parts {
+            match part.target {
+                None => new_global = part.level,
+                Some(target) => {
+                    new_targets.retain(|(existing, _)| *existing != target);
+                    new_targets.push((target, part.level));
+                }
+            }
+        }
+        new_targets.sort_by_key(|(target, _)| std::cmp::Reverse(target.len()));
+
+        let mut applied = AppliedChange::default();
+        if new_global != old_global {
+            applied.changes.push(LevelChange {
+                target: None,
+                old: old_global,
+                new: new_global,
+            });
+        }
+        let effective = |list: &[(String, LogLevel)], global: LogLevel, target: &str| {
+            list.iter()
+                .find(|(existing, _)| component_matches(target, existing))
+                .map_or(global, |(_, level)| *level)
+        };
+        let mut changed: Vec<&String> = targets.iter().chain(new_targets.iter()).map(|(target, _)| target).collect();
+        changed.sort();
+        changed.dedup();
+        for target in changed {
+            let old = effective(&targets, old_global, target);
+            let new = effective(&new_targets, new_global, target);
+            if old != new {
+                applied.changes.push(LevelChange {
+                    target: Some(target.clone()),
+                    old,
+                    new,
+                });
+            }
+        }
+
+        self.set_min_level(new_global);
+        *targets = new_targets;
+        let floor = targets.iter().map(|(_, level)| level.to_u8()).min().unwrap_or(u8::MAX);
+        directives.floor.store(floor, Ordering::Relaxed);
+        directives.any.store(!targets.is_empty(), Ordering::Relaxed);
+        Ok(applied)
+    }
+
+    /// The effective level configuration as a directive, e.g. `info,NETWORK=debug`
+    ///
+    /// The result can be passed back to `apply_directive`.
+    pub fn current_directives(&self) -> String {
+        let mut out = directive_name(self.min_level()).to_string();
+        if let Ok(targets) = self.inner.directives.targets.read() {
+            let mut targets: Vec<_> = targets.iter().collect();
+            targets.sort();
+            for (target, level) in targets {
+                out.push_str(&format!(",{}={}", target, directive_name(*level)));
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::CaptureLogger;
+
+    fn logged(logger: &CaptureLogger) -> Vec<String> {
+        logger.entries().into_iter().map(|e| format!("{} {}", e.level, e.component)).collect()
+    }
+
+    #[test]
+    fn test_targets_override_default() {
+        let logger = CaptureLogger::new();
+        let applied = logger.apply_directive("warn, network=debug, game/combat=trace").unwrap();
+        assert_eq!(
+            applied.to_string(),
+            "default: debug -> warn\nNETWORK: warn -> debug\nGAME/COMBAT: warn -> debug"
+        );
+        assert!(logger.enabled(LogLevel::DEBUG));
+
+        logger.debug("NETWORK/TCP", "a");
+        logger.debug("Game/Combat", "b");
+        logger.debug("GAME", "c");
+        logger.info("STORAGE", "d");
+        logger.warn("STORAGE", "e");
+        assert_eq!(logged(&logger), vec!["DEBUG NETWORK/TCP", "DEBUG Game/Combat", "WARN STORAGE"]);
+
+        // A more specific target may also be stricter than its parent
+        let applied = logger.apply_directive("network/tcp=error").unwrap();
+        assert_eq!(applied.to_string(), "NETWORK/TCP: debug -> error");
+        logger.warn("NETWORK/TCP", "f");
+        logger.debug("NETWORK/UDP", "g");
+        assert_eq!(logged(&logger).len(), 4);
+
+        assert_eq!(logger.current_directives(), "warn,GAME/COMBAT=debug,NETWORK=debug,NETWORK/TCP=error");
+    }
+
+    #[test]
+    fn test_invalid_directive_changes_nothing() {
+        let logger = CaptureLogger::new();
+        logger.apply_directive("info,network=warn").unwrap();
+
+        let err = logger.apply_directive("error,network=loud").unwrap_err();
+        assert_eq!(
+            err,
+            DirectiveError {
+                token: "network=loud".into(),
+                kind: DirectiveErrorKind::UnknownLevel,
+            }
+        );
+        assert_eq!(err.to_string(), "invalid directive `network=loud`: unknown log level");
+        assert_eq!(logger.apply_directive("=debug").unwrap_err().kind, DirectiveErrorKind::InvalidTarget);
+        assert_eq!(logger.apply_directive("info,").unwrap_err().kind, DirectiveErrorKind::Empty);
+
+        assert_eq!(logger.current_directives(), "info,NETWORK=warn");
+    }
+}
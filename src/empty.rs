@@ -0,0 +1,127 @@
+//! Entries whose message is empty or blank, see `LoggerBuilder::empty_message_policy`
+
+use crate::{FieldValue, HorizonLogger};
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Message logged instead of a blank one under `EmptyMessagePolicy::Replace`
+pub(crate) const REPLACEMENT: &str = "<empty message>";
+
+/// What happens to entries whose message is only whitespace and control characters, or nothing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EmptyMessagePolicy {
+    /// Log them as they are
+    #[default]
+    Allow,
+    /// Discard them, counted in `HorizonLogger::empty_messages_dropped`
+    Drop,
+    /// Log `<empty message>` instead, with a `callsite` field of `file:line` when the call site is known
+    ///
+    /// The call site is known for entries logged with the level methods,
+    /// on a `ComponentLogger`, a `LogGroup` or through the macros.
+    Replace,
+}
+
+/// The logger's policy and what it dropped
+pub(crate) struct EmptyMessages {
+    pub(crate) policy: EmptyMessagePolicy,
+    dropped: AtomicU64,
+}
+
+/// What to log in place of a message
+pub(crate) enum Checked<'a> {
+    Keep(&'a str),
+    Drop,
+    /// `REPLACEMENT`, with these fields following the entry's own
+    Replace(Vec<(Cow<'static, str>, FieldValue)>),
+}
+
+impl EmptyMessages {
+    pub(crate) fn new(policy: EmptyMessagePolicy) -> Self {
+        EmptyMessages {
+            policy,
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// Apply the policy to `message`, logged from `location` if known
+    pub(crate) fn check<'a>(&self, message: &'a str, location: Option<(&str, u32)>) -> Checked<'a> {
+        if self.policy == EmptyMessagePolicy::Allow || !is_blank(message) {
+            return Checked::Keep(message);
+        }
+        match self.policy {
+            EmptyMessagePolicy::Drop => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                Checked::Drop
+            }
+            _ => Checked::Replace(
+                location
+                    .map(|(file, line)| (Cow::Borrowed("callsite"), FieldValue::Str(format!("{}:{}", file, line))))
+                    .into_iter()
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Nothing but whitespace and control characters
+fn is_blank(message: &str) -> bool {
+    message.chars().all(|c| c.is_whitespace() || c.is_control())
+}
+
+impl HorizonLogger {
+    /// Entries discarded for a blank message under `EmptyMessagePolicy::Drop`
+    pub fn empty_messages_dropped(&self) -> u64 {
+        self.inner.empty_messages.dropped.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::CaptureLogger;
+    use crate::{log_warn, LogLevel};
+
+    fn capture(policy: EmptyMessagePolicy) -> CaptureLogger {
+        CaptureLogger::from_builder(HorizonLogger::builder().empty_message_policy(policy))
+    }
+
+    #[test]
+    fn test_empty_message_policies() {
+        let blanks = ["", "   ", "\t\n", "\u{7}\r\n", "\u{a0}"];
+
+        let logger = capture(EmptyMessagePolicy::Allow);
+        for blank in blanks {
+            logger.info("NET", blank);
+        }
+        assert_eq!(logger.messages(), blanks);
+
+        let logger = capture(EmptyMessagePolicy::Drop);
+        for blank in blanks {
+            logger.warn("NET", blank);
+        }
+        logger.info("NET", " kept ");
+        assert_eq!(logger.messages(), [" kept "]);
+        assert_eq!(logger.empty_messages_dropped(), 5);
+        assert_eq!(logger.component_stats()[0].total_messages(), 1);
+
+        let logger = capture(EmptyMessagePolicy::Replace);
+        let line = line!() + 1;
+        logger.error("NET", "  ");
+        log_warn!(logger, "NET", "{}", "");
+        logger.component("GAME").info("\n");
+        logger.event(LogLevel::INFO, "NET").log("");
+        let entries = logger.entries();
+        assert!(entries.iter().all(|entry| entry.message == "<empty message>"));
+        let callsite = |i: usize| {
+            let field = entries[i].fields.iter().find(|(name, _)| name == "callsite");
+            field.map(|(_, value)| value.to_string())
+        };
+        assert_eq!(callsite(0), Some(format!("src/empty.rs:{}", line)));
+        assert_eq!(callsite(1), Some(format!("src/empty.rs:{}", line + 1)));
+        assert_eq!(callsite(2), Some(format!("src/empty.rs:{}", line + 2)));
+        // Events don't know their call site
+        assert_eq!(callsite(3), None);
+        assert_eq!(logger.empty_messages_dropped(), 0);
+    }
+}
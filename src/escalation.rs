@@ -0,0 +1,211 @@
+use crate::history::component_matches;
+use crate::{CallOptions, HorizonLogger, LogLevel, Timestamp};
+use std::collections::VecDeque;
+use std::sync::{Mutex, RwLock};
+use std::time::Duration;
+
+/// Escalate bursts of entries under a component to a single CRITICAL entry
+///
+/// Fires when `threshold` entries at or above `level` are logged under
+/// `component_prefix` within `window`, then stays quiet for another `window`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EscalationRule {
+    /// Component the rule watches, matched hierarchically
+    pub component_prefix: String,
+    pub level: LogLevel,
+    pub threshold: usize,
+    pub window: Duration,
+}
+
+impl EscalationRule {
+    pub fn new(component_prefix: &str, level: LogLevel, threshold: usize, window: Duration) -> Self {
+        EscalationRule {
+            component_prefix: component_prefix.to_string(),
+            level,
+            threshold,
+            window,
+        }
+    }
+
+    fn message(&self) -> String {
+        format!(
+            "{}: {} {}s in {:?}, escalating",
+            self.component_prefix,
+            self.threshold,
+            self.level.as_str().to_lowercase(),
+            self.window
+        )
+    }
+}
+
+/// A rule and the recent matches it has seen
+struct Watch {
+    rule: EscalationRule,
+    state: Mutex<WatchState>,
+}
+
+#[derive(Default)]
+struct WatchState {
+    /// Times of the latest matches, at most `threshold` of them
+    recent: VecDeque<Timestamp>,
+    /// No escalation is logged before this time
+    quiet_until: Option<Timestamp>,
+}
+
+impl Watch {
+    /// Count a matching entry, returning whether the rule fires
+    fn observe(&self, at: Timestamp) -> bool {
+        let Ok(mut state) = self.state.lock() else {
+            return false;
+        };
+        if state.recent.len() == self.rule.threshold {
+            state.recent.pop_front();
+        }
+        state.recent.push_back(at);
+
+        let burst = state.recent.len() == self.rule.threshold
+            && state.recent.front().is_some_and(|oldest| at.duration_since(*oldest) <= self.rule.window);
+        if !burst || state.quiet_until.is_some_and(|until| at < until) {
+            return false;
+        }
+
+        state.recent.clear();
+        state.quiet_until = Some(at.saturating_add(self.rule.window));
+        true
+    }
+}
+
+/// The escalation rules registered on a logger
+#[derive(Default)]
+pub(crate) struct Escalations {
+    watches: RwLock<Vec<Watch>>,
+}
+
+impl Escalations {
+    /// Component and message for every rule that an entry makes fire
+    pub(crate) fn observe(&self, level: LogLevel, component: &str, at: Timestamp) -> Vec<(String, String)> {
+        let Ok(watches) = self.watches.read() else {
+            return Vec::new();
+        };
+        watches
+            .iter()
+            .filter(|watch| watch.rule.level <= level)
+            .filter(|watch| component_matches(component, &watch.rule.component_prefix))
+            .filter(|watch| watch.observe(at))
+            .map(|watch| (watch.rule.component_prefix.clone(), watch.rule.message()))
+            .collect()
+    }
+}
+
+impl HorizonLogger {
+    /// Watch for bursts of entries and log a CRITICAL entry when one happens
+    ///
+    /// The escalation entry reaches the console, history and sinks like any
+    /// other, but is never counted by escalation rules itself.
+    pub fn add_escalation(&self, rule: EscalationRule) {
+        if rule.threshold == 0 {
+            return;
+        }
+        if let Ok(mut watches) = self.inner.escalations.watches.write() {
+            watches.push(Watch {
+                rule,
+                state: Mutex::default(),
+            });
+        }
+    }
+
+    /// Forget every rule's recent matches and cooldown
+    pub fn reset_escalations(&self) {
+        if let Ok(watches) = self.inner.escalations.watches.read() {
+            for watch in watches.iter() {
+                if let Ok(mut state) = watch.state.lock() {
+                    *state = WatchState::default();
+                }
+            }
+        }
+    }
+
+    /// Log the escalations an entry triggered
+    pub(crate) fn escalate(&self, level: LogLevel, component: &str, at: Timestamp) {
+        for (component, message) in self.inner.escalations.observe(level, component, at) {
+            let options = CallOptions {
+                escalate: false,
+                ..CallOptions::default()
+            };
+            self.log_with(LogLevel::CRITICAL, &component, &message, options);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::testing::CaptureLogger;
+    use std::sync::Arc;
+
+    fn escalations(logger: &CaptureLogger) -> Vec<String> {
+        logger
+            .entries()
+            .into_iter()
+            .filter(|e| e.level == LogLevel::CRITICAL)
+            .map(|e| format!("[{}] {}", e.component, e.message))
+            .collect()
+    }
+
+    #[test]
+    fn test_threshold_within_window() {
+        let clock = Arc::new(ManualClock::new(Timestamp::from_millis(0)));
+        let logger = CaptureLogger::from_builder(HorizonLogger::builder().clock(clock.clone()));
+        logger.add_escalation(EscalationRule::new("DATABASE", LogLevel::ERROR, 3, Duration::from_secs(60)));
+
+        logger.error("DATABASE/POOL", "write failed");
+        logger.error("DATABASE", "write failed");
+        logger.error("NETWORK", "timeout");
+        logger.warn("DATABASE", "slow query");
+
+        // The first two slide out of the window before the third arrives
+        clock.set(Timestamp::from_millis(61_000));
+        logger.error("DATABASE", "write failed");
+        assert!(escalations(&logger).is_empty());
+
+        logger.error("DATABASE", "write failed");
+        logger.critical("DATABASE", "disk full");
+        assert_eq!(
+            escalations(&logger),
+            vec!["[DATABASE] disk full", "[DATABASE] DATABASE: 3 errors in 60s, escalating"]
+        );
+    }
+
+    #[test]
+    fn test_cooldown_and_reset() {
+        let clock = Arc::new(ManualClock::new(Timestamp::from_millis(0)));
+        let logger = CaptureLogger::from_builder(HorizonLogger::builder().clock(clock.clone()));
+        logger.add_escalation(EscalationRule::new("DATABASE", LogLevel::ERROR, 2, Duration::from_secs(10)));
+        let burst = |logger: &CaptureLogger| {
+            for _ in 0..4 {
+                logger.error("DATABASE", "write failed");
+            }
+        };
+
+        burst(&logger);
+        assert_eq!(escalations(&logger).len(), 1);
+
+        // Still cooling down
+        clock.set(Timestamp::from_millis(9_000));
+        burst(&logger);
+        assert_eq!(escalations(&logger).len(), 1);
+
+        clock.set(Timestamp::from_millis(10_000));
+        logger.error("DATABASE", "write failed");
+        assert_eq!(escalations(&logger).len(), 2);
+
+        // Reset forgets both the cooldown and the pending match
+        logger.error("DATABASE", "write failed");
+        logger.reset_escalations();
+        logger.error("DATABASE", "write failed");
+        assert_eq!(escalations(&logger).len(), 2);
+        logger.error("DATABASE", "write failed");
+        assert_eq!(escalations(&logger).len(), 3);
+    }
+}
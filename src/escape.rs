@@ -0,0 +1,203 @@
+//! Escaping shared by every sink that writes strings into a wire format
+//!
+//! The contract each output keeps, whatever the string holds:
+//!
+//! - JSON (`push_json_str`) escapes exactly as `serde_json` does: `"` and `\`
+//!   take a backslash, `\b \f \n \r \t` their short forms, and other
+//!   characters below U+0020, NUL included, become `\u00XX`. Everything else,
+//!   astral-plane characters too, is written as UTF-8.
+//! - logfmt (`push_logfmt_value`) writes a value bare when it is non-empty and
+//!   has no whitespace, `=`, `"`, `\` or control characters. Otherwise it is
+//!   quoted and escaped as a JSON string, with DEL and the C1 controls also as
+//!   `\u00XX`, so a quoted value is always a valid JSON string literal and a
+//!   line never holds a tab, newline or NUL.
+//! - HTML (`escape_html`) replaces `< > & " '` with entities.
+//! - Binary records need no escaping: strings are length-prefixed UTF-8.
+
+use std::fmt::Write;
+
+/// Append `value` as a quoted JSON string, escaped the way `serde_json` does
+pub(crate) fn push_json_str(out: &mut String, value: &str) {
+    push_quoted(out, value, |c| c < ' ');
+}
+
+/// Append a logfmt value, quoting it when it is empty or contains special characters
+pub(crate) fn push_logfmt_value(out: &mut String, value: &str) {
+    let needs_quotes = value.is_empty()
+        || value
+            .chars()
+            .any(|c| c == '=' || c == '"' || c == '\\' || c.is_whitespace() || c.is_control());
+
+    if needs_quotes {
+        push_quoted(out, value, char::is_control);
+    } else {
+        out.push_str(value);
+    }
+}
+
+/// Append `value` between double quotes, writing the characters `hex` picks as `\u00XX`
+fn push_quoted(out: &mut String, value: &str, hex: fn(char) -> bool) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{8}' => out.push_str("\\b"),
+            '\u{c}' => out.push_str("\\f"),
+            c if hex(c) => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// `text` with the characters HTML gives meaning to replaced by entities
+#[cfg(feature = "http-debug")]
+pub(crate) fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '&' => out.push_str("&amp;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::ansi::AnsiPolicy;
+    use crate::format::{format_entry, Format, FormatOptions};
+    use crate::{LogEntry, LogLevel, Timestamp};
+    use proptest::prelude::*;
+
+    /// Strings drawn mostly from the characters escaping gets wrong
+    pub(crate) fn awkward_string() -> impl Strategy<Value = String> {
+        let awkward = prop_oneof![
+            prop::sample::select(vec![
+                '"', '\\', '=', ' ', '\0', '\t', '\n', '\r', '\x1b', '\u{7f}', '\u{a0}', '\u{2028}'
+            ]),
+            prop::char::range('\0', '\u{1f}'),
+            prop::char::range('\u{80}', '\u{9f}'),
+            prop::char::range('\u{10000}', char::MAX),
+            any::<char>(),
+        ];
+        prop::collection::vec(awkward, 0..24).prop_map(String::from_iter)
+    }
+
+    /// A logfmt line read the way a downstream consumer would, independently of `reader`
+    fn parse_logfmt(line: &str) -> Vec<(String, String)> {
+        assert!(!line.contains(['\n', '\r', '\t', '\0']), "raw control character in {:?}", line);
+        let mut pairs = Vec::new();
+        let mut chars = line.chars().peekable();
+        while chars.peek().is_some() {
+            let key: String = chars.by_ref().take_while(|&c| c != '=').collect();
+            let mut value = String::new();
+            if chars.peek() == Some(&'"') {
+                chars.next();
+                loop {
+                    match chars.next().expect("unterminated value") {
+                        '"' => break,
+                        '\\' => match chars.next().expect("dangling backslash") {
+                            'n' => value.push('\n'),
+                            'r' => value.push('\r'),
+                            't' => value.push('\t'),
+                            'b' => value.push('\u{8}'),
+                            'f' => value.push('\u{c}'),
+                            'u' => {
+                                let hex: String = chars.by_ref().take(4).collect();
+                                value.push(char::from_u32(u32::from_str_radix(&hex, 16).unwrap()).unwrap());
+                            }
+                            c => value.push(c),
+                        },
+                        c => value.push(c),
+                    }
+                }
+                assert!(matches!(chars.next(), None | Some(' ')), "value ran into the next key in {:?}", line);
+            } else {
+                value = chars.by_ref().take_while(|&c| c != ' ').collect();
+            }
+            pairs.push((key, value));
+        }
+        pairs
+    }
+
+    fn entry(component: &str, message: &str) -> LogEntry {
+        LogEntry::at(Timestamp::from_micros(1_700_000_000_000_000), LogLevel::INFO, component, message)
+    }
+
+    fn preserving() -> FormatOptions {
+        FormatOptions {
+            ansi: AnsiPolicy::Preserve,
+            ..FormatOptions::default()
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_json_str_matches_serde_json(value in awkward_string()) {
+            let mut out = String::new();
+            push_json_str(&mut out, &value);
+            prop_assert_eq!(&out, &serde_json::to_string(&value).unwrap());
+            prop_assert_eq!(serde_json::from_str::<String>(&out).unwrap(), value);
+        }
+
+        #[test]
+        fn test_json_entry_round_trips(component in awkward_string(), message in awkward_string()) {
+            let line = format_entry(&entry(&component, &message), Format::Json, &preserving());
+            let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+            prop_assert_eq!(json["component"].as_str(), Some(component.as_str()));
+            prop_assert_eq!(json["message"].as_str(), Some(message.as_str()));
+        }
+
+        #[test]
+        fn test_logfmt_value_round_trips(value in awkward_string()) {
+            let mut out = String::from("k=");
+            push_logfmt_value(&mut out, &value);
+            prop_assert_eq!(parse_logfmt(&out), vec![("k".to_string(), value.clone())]);
+            if out.starts_with("k=\"") {
+                prop_assert_eq!(serde_json::from_str::<String>(&out[2..]).unwrap(), value);
+            }
+        }
+
+        #[test]
+        fn test_logfmt_entry_round_trips(component in awkward_string(), message in awkward_string()) {
+            let line = format_entry(&entry(&component, &message), Format::Logfmt, &preserving());
+            let pairs = parse_logfmt(&line);
+            let get = |key: &str| pairs.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str());
+            prop_assert_eq!(get("component"), Some(component.as_str()));
+            prop_assert_eq!(get("msg"), Some(message.as_str()));
+        }
+    }
+
+    #[test]
+    fn test_logfmt_quoting() {
+        let quoted = |value: &str| {
+            let mut out = String::new();
+            push_logfmt_value(&mut out, value);
+            out
+        };
+        assert_eq!(quoted("plain"), "plain");
+        assert_eq!(quoted(""), "\"\"");
+        assert_eq!(quoted("a\tb"), "\"a\\tb\"");
+        assert_eq!(quoted("no\u{a0}break"), "\"no\u{a0}break\"");
+        assert_eq!(quoted("nul\0 del\u{7f} nel\u{85}"), "\"nul\\u0000 del\\u007f nel\\u0085\"");
+        assert_eq!(quoted("astral \u{1f600}"), "\"astral \u{1f600}\"");
+    }
+
+    #[cfg(feature = "http-debug")]
+    #[test]
+    fn test_escape_html() {
+        assert_eq!(escape_html("<b a=\"1\">&'</b>"), "&lt;b a=&quot;1&quot;&gt;&amp;&#39;&lt;/b&gt;");
+    }
+}
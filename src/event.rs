@@ -0,0 +1,267 @@
+use crate::run::LOGGER_COMPONENT;
+use crate::template::{self, FieldValue, Fields};
+use crate::{CallOptions, ComponentArg, HorizonLogger, LogLevel};
+use std::borrow::Cow;
+
+/// An entry being put together before it is logged, see `HorizonLogger::event`
+#[must_use = "nothing is logged until `log` is called"]
+pub struct EventBuilder<'a> {
+    logger: &'a HorizonLogger,
+    level: LogLevel,
+    component: Cow<'static, str>,
+    severity: Option<u8>,
+    code: Option<&'static str>,
+    template: Option<&'static str>,
+    fields: Fields,
+    /// Nothing is logged, see `discard`
+    discarded: bool,
+}
+
+impl EventBuilder<'_> {
+    /// Set a finer severity than the level's default, e.g. 45 within ERROR's 40..49
+    ///
+    /// Only the level decides whether the entry is logged.
+    pub fn severity(mut self, severity: u8) -> Self {
+        self.severity = Some(severity);
+        self
+    }
+
+    /// Attach a stable event code such as `NET-0042`, see `define_events!`
+    pub fn code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    /// Build the message from `template` when emitted, see `field`
+    ///
+    /// `{name}` is replaced by the field `name`, and fields the template
+    /// doesn't name are appended as ` name=value`. A placeholder without a
+    /// field renders as `{name?}`, and the first time that happens for a
+    /// template a WARN is logged under `LOGGER`. JSON output keeps the
+    /// template and the fields as `template` and `fields`.
+    pub fn template(mut self, template: &'static str) -> Self {
+        self.template = Some(template);
+        self
+    }
+
+    /// Attach a named value, for the template or appended to the message
+    pub fn field(mut self, name: &'static str, value: impl Into<FieldValue>) -> Self {
+        self.fields.push((Cow::Borrowed(name), value.into()));
+        self
+    }
+
+    /// Log the entry with the message rendered from its template
+    pub fn emit(self) {
+        let Some(template) = self.template else {
+            return self.log("");
+        };
+        let (message, missing) = template::render(template, &self.fields);
+        self.send(&message);
+
+        if !missing.is_empty() && self.logger.inner.warned_templates.first(template) {
+            let message = format!("template \"{}\" logged without field(s) {}", template, missing.join(", "));
+            self.logger.log(LogLevel::WARN, LOGGER_COMPONENT, &message);
+        }
+    }
+
+    /// Log the entry with `message`, followed by any fields as ` name=value`
+    pub fn log(self, message: &str) {
+        if self.fields.is_empty() {
+            return self.send(message);
+        }
+        let mut message = message.to_string();
+        template::append_fields(&mut message, self.fields.iter());
+        self.send(&message);
+    }
+
+    /// Log nothing when emitted, for an entry already known to be held back
+    pub(crate) fn discard(mut self) -> Self {
+        self.discarded = true;
+        self
+    }
+
+    fn send(&self, message: &str) {
+        if self.discarded {
+            return;
+        }
+        let options = CallOptions {
+            severity: self.severity,
+            code: self.code,
+            template: self.template,
+            fields: &self.fields,
+            ..CallOptions::default()
+        };
+        self.logger.log_with(self.level, &self.component, message, options);
+    }
+}
+
+impl HorizonLogger {
+    /// Start an entry at `level` under `component`, to log with `EventBuilder::log`
+    pub fn event(&self, level: LogLevel, component: impl ComponentArg) -> EventBuilder<'_> {
+        EventBuilder {
+            logger: self,
+            level,
+            component: component.to_cow(),
+            severity: None,
+            code: None,
+            template: None,
+            fields: Fields::new(),
+            discarded: false,
+        }
+    }
+}
+
+/// Declare a catalog of events, each logged by its own function
+///
+/// Every event has a stable code, a level, a component and a message
+/// template whose `{placeholders}` name the function's arguments:
+///
+/// ```
+/// mod events {
+///     horizon_logger::define_events! {
+///         /// A player was removed from the server
+///         pub fn player_kick(reason: &str) = "NET-0042", WARN, "NETWORK", "player kicked: {reason}";
+///         pub fn save_failed(slot: u32) = "STORE-0007", ERROR, "STORAGE", "saving slot {slot} failed";
+///     }
+/// }
+///
+/// let logger = horizon_logger::testing::CaptureLogger::new();
+/// events::player_kick(&logger, "idle");
+/// assert_eq!(logger.history_by_code("NET-0042")[0].message, "player kicked: idle");
+/// ```
+///
+/// A code used twice in one block fails to compile:
+///
+/// ```compile_fail
+/// horizon_logger::define_events! {
+///     pub fn joined() = "NET-0001", INFO, "NETWORK", "player joined";
+///     pub fn left() = "NET-0001", INFO, "NETWORK", "player left";
+/// }
+/// ```
+#[macro_export]
+macro_rules! define_events {
+    ($($(#[$meta:meta])* $vis:vis fn $name:ident($($arg:ident: $ty:ty),* $(,)?) = $code:literal, $level:ident, $component:literal, $template:literal;)*) => {
+        $(
+            $(#[$meta])*
+            #[doc = concat!("\n\nEvent `", $code, "`, logged at ", stringify!($level), " under `", $component, "`.")]
+            $vis fn $name(logger: &$crate::HorizonLogger, $($arg: $ty),*) {
+                logger
+                    .event($crate::LogLevel::$level, $component)
+                    .code($code)
+                    .log(&format!($template));
+            }
+        )*
+        const _: () = assert!($crate::unique_event_codes(&[$($code),*]), "duplicate code in define_events!");
+    };
+}
+
+/// Whether no code appears twice, checked at compile time by `define_events!`
+#[doc(hidden)]
+pub const fn unique_event_codes(codes: &[&str]) -> bool {
+    let mut i = 0;
+    while i < codes.len() {
+        let mut j = i + 1;
+        while j < codes.len() {
+            if same_bytes(codes[i].as_bytes(), codes[j].as_bytes()) {
+                return false;
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+    true
+}
+
+const fn same_bytes(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::console::tests::SharedBuf;
+    use crate::console::Console;
+    use crate::sink::SeverityFilter;
+    use crate::testing::fixtures::CollectSink;
+    use crate::testing::CaptureLogger;
+    use crate::HistoryQuery;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_severity_in_entries_queries_and_sinks() {
+        let logger = CaptureLogger::new();
+        let severe = Arc::new(CollectSink::default());
+        logger.add_sink(SeverityFilter::new(severe.clone(), 45));
+
+        logger.error("GAME", "retrying save");
+        logger.event(LogLevel::ERROR, "GAME").severity(45).log("save lost");
+        logger.event(LogLevel::WARN, "GAME").severity(49).log("desync");
+        logger.set_min_level(LogLevel::ERROR);
+        logger.event(LogLevel::WARN, "GAME").severity(60).log("filtered by level");
+
+        let severities: Vec<u8> = logger.entries().iter().map(|e| e.severity).collect();
+        assert_eq!(severities, vec![40, 45, 49]);
+        assert!(logger.entries()[1].to_json().contains(r#""level":"ERROR","severity":45,"#));
+
+        let query = HistoryQuery::new().min_severity(45).min_level(LogLevel::ERROR);
+        let messages: Vec<_> = logger.query_history(&query).into_iter().map(|e| e.message).collect();
+        assert_eq!(messages, vec!["save lost"]);
+        assert_eq!(severe.messages(), vec!["save lost", "desync"]);
+    }
+
+    #[test]
+    fn test_severity_pattern_placeholder() {
+        let buf = SharedBuf::default();
+        let logger = HorizonLogger::builder()
+            .announce_run(false)
+            .console(Console::new(Box::new(buf.clone()), false))
+            .console_pattern("{level}/{severity} {message}".parse().unwrap())
+            .build();
+        logger.info("GAME", "tick");
+        logger.event(LogLevel::ERROR, "GAME").severity(45).log("save lost");
+        assert_eq!(buf.contents(), "INFO/20 tick\nERROR/45 save lost\n");
+    }
+
+    mod events {
+        crate::define_events! {
+            pub fn player_kick(reason: &str) = "NET-0042", WARN, "NETWORK", "player kicked: {reason}";
+            pub fn save_failed(slot: u32, error: &str) = "STORE-0007", ERROR, "STORAGE", "saving slot {slot} failed: {error}";
+        }
+    }
+
+    #[test]
+    fn test_event_codes() {
+        let buf = SharedBuf::default();
+        let logger = HorizonLogger::builder()
+            .announce_run(false)
+            .console(Console::new(Box::new(buf.clone()), false))
+            .build();
+        events::player_kick(&logger, "idle");
+        events::save_failed(&logger, 2, "disk full");
+        logger.event(LogLevel::WARN, "NETWORK").code("NET-0042").log("player kicked: spam");
+        logger.warn("NETWORK", "uncoded");
+
+        let kicks: Vec<_> = logger.history_by_code("NET-0042").into_iter().map(|e| e.message).collect();
+        assert_eq!(kicks, vec!["player kicked: idle", "player kicked: spam"]);
+        let failed = &logger.history_by_code("STORE-0007")[0];
+        assert_eq!((failed.level, failed.component.as_str()), (LogLevel::ERROR, "STORAGE"));
+        assert!(failed.to_json().contains(r#""message":"saving slot 2 failed: disk full","code":"STORE-0007","#));
+        assert!(buf.contents().contains("[NETWORK] [NET-0042] player kicked: idle\n"), "{}", buf.contents());
+    }
+
+    #[test]
+    fn test_unique_event_codes() {
+        assert!(unique_event_codes(&["NET-0001", "NET-0002", "NET-00010"]));
+        assert!(!unique_event_codes(&["NET-0001", "NET-0002", "NET-0001"]));
+    }
+}
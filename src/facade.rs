@@ -0,0 +1,208 @@
+use crate::component::BorrowedComponent;
+use crate::testing::CaptureLogger;
+use crate::{ComponentLogger, HorizonLogger, LogLevel};
+use std::sync::Arc;
+
+/// Something to log to, for code that shouldn't depend on a `HorizonLogger`
+///
+/// Libraries can take `impl HorizonLog` (or `&dyn HorizonLog`) and let
+/// their users pass a `HorizonLogger`, a `ComponentLogger`, a
+/// `CaptureLogger` in tests or a `NullLogger` in benchmarks. The logging
+/// macros accept any implementor. Only `log` and `enabled` need
+/// implementing.
+///
+/// `HorizonLogger`'s own methods of the same names take precedence where
+/// both are in scope; the ones on `ComponentLogger` take no component, so
+/// call them as `HorizonLog::info(&logger, ...)` in that case.
+pub trait HorizonLog {
+    /// Log `message` at `level` under `component`
+    #[track_caller]
+    fn log(&self, level: LogLevel, component: &str, message: &str);
+
+    /// Whether entries at `level` could be logged, to skip building messages that wouldn't be
+    fn enabled(&self, level: LogLevel) -> bool;
+
+    /// Log a debug message
+    #[track_caller]
+    fn debug(&self, component: &str, message: &str) {
+        self.log(LogLevel::DEBUG, component, message);
+    }
+
+    /// Log an info message
+    #[track_caller]
+    fn info(&self, component: &str, message: &str) {
+        self.log(LogLevel::INFO, component, message);
+    }
+
+    /// Log a warning message
+    #[track_caller]
+    fn warn(&self, component: &str, message: &str) {
+        self.log(LogLevel::WARN, component, message);
+    }
+
+    /// Log an error message
+    #[track_caller]
+    fn error(&self, component: &str, message: &str) {
+        self.log(LogLevel::ERROR, component, message);
+    }
+
+    /// Log a critical message
+    #[track_caller]
+    fn critical(&self, component: &str, message: &str) {
+        self.log(LogLevel::CRITICAL, component, message);
+    }
+
+    /// Log a debug message without a component, for `log_debug!`
+    #[track_caller]
+    fn debug_msg(&self, message: &str) {
+        self.log(LogLevel::DEBUG, "", message);
+    }
+
+    /// Log an info message without a component, for `log_info!`
+    #[track_caller]
+    fn info_msg(&self, message: &str) {
+        self.log(LogLevel::INFO, "", message);
+    }
+
+    /// Log a warning message without a component, for `log_warn!`
+    #[track_caller]
+    fn warn_msg(&self, message: &str) {
+        self.log(LogLevel::WARN, "", message);
+    }
+
+    /// Log an error message without a component, for `log_error!`
+    #[track_caller]
+    fn error_msg(&self, message: &str) {
+        self.log(LogLevel::ERROR, "", message);
+    }
+
+    /// Log a critical message without a component, for `log_critical!`
+    #[track_caller]
+    fn critical_msg(&self, message: &str) {
+        self.log(LogLevel::CRITICAL, "", message);
+    }
+}
+
+/// A logger that discards everything, for benchmarks and callers that don't want logs
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NullLogger;
+
+impl HorizonLog for NullLogger {
+    fn log(&self, _level: LogLevel, _component: &str, _message: &str) {}
+
+    fn enabled(&self, _level: LogLevel) -> bool {
+        false
+    }
+}
+
+impl HorizonLog for HorizonLogger {
+    #[track_caller]
+    fn log(&self, level: LogLevel, component: &str, message: &str) {
+        HorizonLogger::log_from_caller(self, level, BorrowedComponent(component), message);
+    }
+
+    fn enabled(&self, level: LogLevel) -> bool {
+        HorizonLogger::enabled(self, level)
+    }
+}
+
+/// Entries go under the logger's component, or under `name/component` when one is given
+impl HorizonLog for ComponentLogger {
+    #[track_caller]
+    fn log(&self, level: LogLevel, component: &str, message: &str) {
+        if component.is_empty() {
+            ComponentLogger::log(self, level, message);
+        } else if self.namespace_allows(level) {
+            let component = format!("{}/{}", self.name(), component);
+            HorizonLog::log(self.logger(), level, &component, message);
+        }
+    }
+
+    fn enabled(&self, level: LogLevel) -> bool {
+        ComponentLogger::enabled(self, level)
+    }
+}
+
+impl HorizonLog for CaptureLogger {
+    #[track_caller]
+    fn log(&self, level: LogLevel, component: &str, message: &str) {
+        HorizonLog::log(self.logger(), level, component, message);
+    }
+
+    fn enabled(&self, level: LogLevel) -> bool {
+        self.logger().enabled(level)
+    }
+}
+
+impl<L: HorizonLog + ?Sized> HorizonLog for &L {
+    #[track_caller]
+    fn log(&self, level: LogLevel, component: &str, message: &str) {
+        (**self).log(level, component, message);
+    }
+
+    fn enabled(&self, level: LogLevel) -> bool {
+        (**self).enabled(level)
+    }
+}
+
+impl<L: HorizonLog + ?Sized> HorizonLog for Arc<L> {
+    #[track_caller]
+    fn log(&self, level: LogLevel, component: &str, message: &str) {
+        (**self).log(level, component, message);
+    }
+
+    fn enabled(&self, level: LogLevel) -> bool {
+        (**self).enabled(level)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{log_info, log_warn};
+
+    /// Library code that only knows it has something to log to
+    fn load_map(log: &impl HorizonLog, name: &str, tiles: usize) -> usize {
+        log_info!(log, "MAP", "loading {}", name);
+        if log.enabled(LogLevel::DEBUG) {
+            log.debug("MAP", &format!("{} tiles", tiles));
+        }
+        if tiles > 1000 {
+            log_warn!(log, "map {name} is large");
+        }
+        tiles
+    }
+
+    #[test]
+    fn test_generic_code_runs_against_every_logger() {
+        let capture = CaptureLogger::new();
+        assert_eq!(load_map(&capture, "dunes", 4096), 4096);
+        assert_eq!(capture.messages(), ["loading dunes", "4096 tiles", "map dunes is large"]);
+        assert_eq!(capture.entries()[2].component, "");
+
+        let real = CaptureLogger::from_builder(HorizonLogger::builder().min_level(LogLevel::INFO));
+        let real: &HorizonLogger = real.logger();
+        load_map(real, "caves", 10);
+        load_map(&Arc::new(real.clone()), "caves", 10);
+        assert_eq!(real.get_history().len(), 2);
+
+        assert_eq!(load_map(&NullLogger, "void", 5000), 5000);
+        assert!(!NullLogger.enabled(LogLevel::CRITICAL));
+
+        let objects: [&dyn HorizonLog; 2] = [&NullLogger, &*capture];
+        for log in objects {
+            load_map(&log, "shared", 1);
+        }
+        assert_eq!(capture.messages().len(), 5);
+    }
+
+    #[test]
+    fn test_component_logger_nests_components() {
+        let capture = CaptureLogger::new();
+        let world = capture.component("WORLD");
+        load_map(&world, "dunes", 2000);
+
+        let components: Vec<_> = capture.entries().iter().map(|e| e.component.to_string()).collect();
+        assert_eq!(components, ["WORLD/MAP", "WORLD/MAP", "WORLD"]);
+    }
+}
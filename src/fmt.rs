@@ -0,0 +1,120 @@
+//! Canonical human-readable forms for values that show up in messages
+//!
+//! Formatting durations and sizes the same way everywhere keeps logs
+//! greppable: `fmt::duration` gives `850ns`, `12.3µs`, `12.3ms`, `1.45s`,
+//! `3m 12s` or `1h 02m 03s`, and `fmt::bytes` gives `512 B`, `150.0 KiB` or
+//! `1.2 GiB`. Each value is written in the largest unit it reaches after
+//! rounding, so 999.96µs is `1.0ms` rather than `1000.0µs`.
+
+use std::fmt;
+use std::time::Duration;
+
+/// A duration written in its canonical form, see `duration`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HumanDuration(pub Duration);
+
+/// A byte count written in its canonical binary-unit form, see `bytes`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HumanBytes(pub u64);
+
+/// Format `duration` as e.g. `12.3ms`, `1.45s` or `3m 12s`
+pub fn duration(duration: Duration) -> HumanDuration {
+    HumanDuration(duration)
+}
+
+/// Format `bytes` as e.g. `1023 B`, `150.0 KiB` or `1.2 GiB`
+pub fn bytes(bytes: u64) -> HumanBytes {
+    HumanBytes(bytes)
+}
+
+/// `value` rounded to `decimals` places, if that stays below `limit`
+fn rounded_below(value: f64, decimals: i32, limit: f64) -> Option<f64> {
+    let scale = 10f64.powi(decimals);
+    let rounded = (value * scale).round() / scale;
+    (rounded < limit).then_some(rounded)
+}
+
+impl fmt::Display for HumanDuration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let nanos = self.0.as_nanos();
+        if nanos < 1_000 {
+            return write!(f, "{}ns", nanos);
+        }
+        let nanos = nanos as f64;
+        if let Some(micros) = rounded_below(nanos / 1e3, 1, 1_000.0) {
+            return write!(f, "{:.1}µs", micros);
+        }
+        if let Some(millis) = rounded_below(nanos / 1e6, 1, 1_000.0) {
+            return write!(f, "{:.1}ms", millis);
+        }
+        if let Some(secs) = rounded_below(nanos / 1e9, 2, 60.0) {
+            return write!(f, "{:.2}s", secs);
+        }
+
+        let secs = (nanos / 1e9).round() as u64;
+        let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+        if hours > 0 {
+            write!(f, "{}h {:02}m {:02}s", hours, minutes, seconds)
+        } else {
+            write!(f, "{}m {}s", minutes, seconds)
+        }
+    }
+}
+
+impl fmt::Display for HumanBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const UNITS: [&str; 6] = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+
+        if self.0 < 1024 {
+            return write!(f, "{} B", self.0);
+        }
+        let mut value = self.0 as f64 / 1024.0;
+        for unit in &UNITS[..UNITS.len() - 1] {
+            if let Some(rounded) = rounded_below(value, 1, 1024.0) {
+                return write!(f, "{:.1} {}", rounded, unit);
+            }
+            value /= 1024.0;
+        }
+        write!(f, "{:.1} {}", value, UNITS[UNITS.len() - 1])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_duration_boundaries() {
+        let cases = [
+            (Duration::from_nanos(999), "999ns"),
+            (Duration::from_nanos(1_000), "1.0µs"),
+            (Duration::from_micros(999), "999.0µs"),
+            (Duration::from_nanos(999_960), "1.0ms"),
+            (Duration::from_micros(12_340), "12.3ms"),
+            (Duration::from_micros(999_960), "1.00s"),
+            (Duration::from_millis(1_450), "1.45s"),
+            (Duration::from_millis(59_996), "1m 0s"),
+            (Duration::from_secs(192), "3m 12s"),
+            (Duration::from_secs(3_723), "1h 02m 03s"),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(duration(value).to_string(), expected, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn test_bytes_boundaries() {
+        let cases = [
+            (0, "0 B"),
+            (1_023, "1023 B"),
+            (1_024, "1.0 KiB"),
+            (153_600, "150.0 KiB"),
+            (1_048_525, "1.0 MiB"),
+            (1_288_490_189, "1.2 GiB"),
+            (u64::MAX, "16.0 EiB"),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(bytes(value).to_string(), expected, "{}", value);
+        }
+    }
+}
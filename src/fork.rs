@@ -0,0 +1,119 @@
+//! Hooks for keeping a logger usable across `fork()`
+//!
+//! Call `prepare_fork` on the forking thread right before `fork()`, then
+//! `after_fork_child` in the child and `after_fork_parent` in the parent.
+//!
+//! What is fork-safe:
+//! - Sink output: `prepare_fork` waits for in-flight sink writes to finish,
+//!   blocks new ones and flushes every sink, so no buffered bytes are
+//!   duplicated into the child and no sink lock is held across the fork.
+//! - Files: the child reopens every sink's file so it gets its own
+//!   descriptor instead of sharing the parent's.
+//! - Spans: the forking thread's span stack is carried into the child as-is.
+//! - Background threads: only the forking thread survives a fork, so the
+//!   child starts its own sink queue worker and lanes, heartbeats, and each
+//!   sink's threads (`Sink::restart_after_fork`): network and SQLite
+//!   writers, interval syncs and rotation compression. Work the parent had
+//!   queued stays with the parent. Pipe readers, config watches, the debug
+//!   HTTP server and periodic history persistence are the parent's alone
+//!   and are not started again.
+//!
+//! What is not: history, stats and stdout use short-lived locks that are
+//! not paused. If another thread holds one of them at the instant of the
+//! fork, the child will deadlock on first use, so fork from a quiet moment
+//! or `exec` promptly.
+
+use crate::HorizonLogger;
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Weak};
+use std::thread;
+use std::time::Duration;
+
+/// Lets `prepare_fork` drain and block sink writers without holding a lock
+#[derive(Default)]
+pub(crate) struct ForkGate {
+    paused: AtomicBool,
+    in_flight: AtomicUsize,
+}
+
+/// Proof that a writer passed the gate; leaves it on drop
+pub(crate) struct GatePass<'a>(&'a ForkGate);
+
+impl ForkGate {
+    /// Wait while paused, then register as an in-flight writer
+    pub(crate) fn enter(&self) -> GatePass<'_> {
+        loop {
+            self.in_flight.fetch_add(1, Ordering::SeqCst);
+            if !self.paused.load(Ordering::SeqCst) {
+                return GatePass(self);
+            }
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            while self.paused.load(Ordering::SeqCst) {
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+    }
+
+    fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+        while self.in_flight.load(Ordering::SeqCst) > 0 {
+            thread::yield_now();
+        }
+    }
+
+    fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+    }
+}
+
+impl Drop for GatePass<'_> {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl HorizonLogger {
+    /// Flush and pause all sinks ahead of `fork()`
+    ///
+    /// Other threads logging in the meantime block at the sink stage until
+    /// `after_fork_parent` is called.
+    pub fn prepare_fork(&self) {
+        if let Some(handle) = &self.inner.sink_queue {
+            handle.queue().drain();
+        }
+        self.inner.fork_gate.pause();
+        for sink in self.sinks_snapshot() {
+            let _ = sink.flush();
+        }
+    }
+
+    /// Resume sinks in the parent after `fork()`
+    pub fn after_fork_parent(&self) {
+        self.inner.fork_gate.resume();
+    }
+
+    /// Reopen files, start background threads again and resume sinks in the child after `fork()`
+    ///
+    /// Returns the first reopen or restart error, but resumes logging regardless.
+    pub fn after_fork_child(&self) -> io::Result<()> {
+        // Only the forking thread exists in the child, so nothing is in flight
+        self.inner.fork_gate.in_flight.store(0, Ordering::SeqCst);
+
+        let mut result = self.reopen_files();
+        for sink in self.sinks_snapshot() {
+            if let Err(e) = sink.restart_after_fork() {
+                result = result.and(Err(e));
+            }
+        }
+        if let Some(handle) = &self.inner.sink_queue {
+            handle.restart_after_fork(Arc::downgrade(&self.inner));
+        }
+        let heartbeats = self.inner.heartbeats.lock().map(|heartbeats| heartbeats.clone()).unwrap_or_default();
+        for heartbeat in heartbeats.iter().filter_map(Weak::upgrade) {
+            heartbeat.restart(self);
+        }
+        self.inner.fork_gate.resume();
+        result
+    }
+}
@@ -0,0 +1,470 @@
+use crate::ansi::AnsiPolicy;
+use crate::console::LineParts;
+use crate::escape::{push_json_str, push_logfmt_value};
+use crate::pattern::Pattern;
+use crate::template::append_fields;
+use crate::time_render::{HumanTime, TimestampRenderer};
+use crate::{FieldValue, LogEntry, LogLevel};
+use crate::Timestamp;
+use std::borrow::Cow;
+use std::fmt::{self, Write};
+use std::sync::Arc;
+
+/// Human-readable timestamp: local time with chrono, otherwise ISO 8601 UTC
+#[cfg(any(test, feature = "http-debug"))]
+pub(crate) fn human_time(timestamp: &Timestamp) -> String {
+    let mut out = String::new();
+    write_human_time(&mut out, timestamp);
+    out
+}
+
+/// Append `human_time` without allocating
+pub(crate) fn write_human_time(out: &mut String, timestamp: &Timestamp) {
+    // Written field by field; chrono's `format` renders into a temporary String
+    #[cfg(feature = "chrono")]
+    {
+        use chrono::{Datelike, Timelike};
+        let local = timestamp.to_local();
+        let _ = write!(
+            out,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:03}",
+            local.year(),
+            local.month(),
+            local.day(),
+            local.hour(),
+            local.minute(),
+            local.second(),
+            local.nanosecond() % 1_000_000_000 / 1_000_000
+        );
+    }
+
+    #[cfg(not(feature = "chrono"))]
+    timestamp.write_iso8601_millis(out);
+}
+
+/// Append the time of day, `HH:MM:SS`, in the same zone as `human_time`
+pub(crate) fn write_human_clock(out: &mut String, timestamp: &Timestamp) {
+    #[cfg(feature = "chrono")]
+    {
+        use chrono::Timelike;
+        let local = timestamp.to_local();
+        let _ = write!(out, "{:02}:{:02}:{:02}", local.hour(), local.minute(), local.second());
+    }
+
+    #[cfg(not(feature = "chrono"))]
+    {
+        let of_day = timestamp.as_micros().div_euclid(1_000_000).rem_euclid(86_400);
+        let _ = write!(out, "{:02}:{:02}:{:02}", of_day / 3600, of_day / 60 % 60, of_day % 60);
+    }
+}
+
+/// How timestamps are written in machine-readable formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MachineTimestamp {
+    /// Integer milliseconds since the Unix epoch
+    EpochMillis,
+    /// Integer microseconds since the Unix epoch
+    EpochMicros,
+    /// RFC 3339 in UTC with microsecond precision
+    #[default]
+    Rfc3339,
+}
+
+/// Output format for a rendered entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Plain single-line text, as shown by `Display`
+    Text,
+    /// One JSON object per line
+    Json,
+    /// `key=value` pairs
+    Logfmt,
+}
+
+/// How the level and message of `Format::Text` lines are marked up
+#[derive(Clone, Default)]
+pub enum ColorCodes {
+    /// ANSI escapes in the console's level colors
+    Ansi,
+    /// Plain text
+    #[default]
+    None,
+    /// A prefix and suffix per level, e.g. `("^1", "^7")` for an overlay with Quake-style color codes
+    Custom(Arc<dyn Fn(LogLevel) -> (String, String) + Send + Sync>),
+}
+
+impl ColorCodes {
+    /// The prefix and suffix for `level`, if any
+    fn wrap(&self, level: LogLevel) -> Option<(String, String)> {
+        match self {
+            ColorCodes::Ansi => Some((format!("\x1b[{}m", level.ansi_style()), "\x1b[0m".to_string())),
+            ColorCodes::None => None,
+            ColorCodes::Custom(translate) => Some(translate(level)),
+        }
+    }
+}
+
+impl fmt::Debug for ColorCodes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ColorCodes::Ansi => f.write_str("Ansi"),
+            ColorCodes::None => f.write_str("None"),
+            ColorCodes::Custom(_) => f.write_str("Custom(..)"),
+        }
+    }
+}
+
+/// Options applied when rendering entries
+#[derive(Debug, Clone, Default)]
+pub struct FormatOptions {
+    pub machine_timestamp: MachineTimestamp,
+    /// Layout of `Format::Text` lines; the `Display` layout when unset
+    pub text_pattern: Option<Pattern>,
+    /// Markup around the level and message of `Format::Text` lines; none by default
+    pub color_codes: ColorCodes,
+    /// What happens to ANSI escapes already in the message; stripped by default
+    pub ansi: AnsiPolicy,
+    /// How `Format::Text` lines show the entry time; `HumanTime` when unset
+    pub timestamps: Option<Arc<dyn TimestampRenderer>>,
+}
+
+/// Render an entry in the given format
+pub fn format_entry(entry: &LogEntry, format: Format, options: &FormatOptions) -> String {
+    if entry.raw && format == Format::Text {
+        return entry.message.clone();
+    }
+    let rewritten;
+    let entry = match options.ansi.apply(&entry.message) {
+        Cow::Borrowed(_) => entry,
+        Cow::Owned(message) => {
+            rewritten = LogEntry { message, ..entry.clone() };
+            &rewritten
+        }
+    };
+    match format {
+        Format::Text => {
+            let colors = options.color_codes.wrap(entry.level);
+            let (prefix, suffix) = colors.as_ref().map_or(("", ""), |(p, s)| (p.as_str(), s.as_str()));
+            let timestamps = options.timestamps.as_deref().unwrap_or(&HumanTime);
+            let mut out = String::new();
+            match &options.text_pattern {
+                Some(pattern) => pattern.write_marked(&mut out, &LineParts::of(entry, 0), timestamps, prefix, suffix),
+                None if colors.is_none() && options.timestamps.is_none() => return entry.to_string(),
+                None => {
+                    let _ = write_text(&mut out, entry, timestamps, prefix, suffix);
+                }
+            }
+            out
+        }
+        Format::Json => json(entry, options),
+        Format::Logfmt => logfmt(entry, options),
+    }
+}
+
+impl fmt::Display for LogEntry {
+    /// Plain single-line rendering without colors or thread info
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_text(f, self, &HumanTime, "", "")
+    }
+}
+
+/// The `Display` layout, with the level and message between `prefix` and `suffix`
+fn write_text(
+    out: &mut impl Write,
+    entry: &LogEntry,
+    timestamps: &dyn TimestampRenderer,
+    prefix: &str,
+    suffix: &str,
+) -> fmt::Result {
+    let mut time = String::new();
+    timestamps.render(&entry.timestamp, &mut time);
+    write!(
+        out,
+        "{} {}{:^width$}{} ",
+        time,
+        prefix,
+        entry.level.as_str(),
+        suffix,
+        width = crate::level::width()
+    )?;
+    if !entry.component.is_empty() {
+        write!(out, "[{}] ", entry.component)?;
+    }
+    if let Some(code) = &entry.code {
+        write!(out, "[{}] ", code)?;
+    }
+    write!(out, "{}{}{}", prefix, entry.message, suffix)
+}
+
+impl LogEntry {
+    /// Render the entry as a single-line JSON object
+    pub fn to_json(&self) -> String {
+        json(self, &FormatOptions::default())
+    }
+
+    /// Render the entry as a logfmt line
+    pub fn to_logfmt(&self) -> String {
+        logfmt(self, &FormatOptions::default())
+    }
+}
+
+/// A timestamp rendered for a machine format, either numeric or textual
+enum MachineTime {
+    Number(i64),
+    Text(String),
+}
+
+fn machine_time(timestamp: &Timestamp, style: MachineTimestamp) -> MachineTime {
+    match style {
+        MachineTimestamp::EpochMillis => MachineTime::Number(timestamp.as_millis()),
+        MachineTimestamp::EpochMicros => MachineTime::Number(timestamp.as_micros()),
+        MachineTimestamp::Rfc3339 => MachineTime::Text(timestamp.to_rfc3339()),
+    }
+}
+
+fn push_json_time(out: &mut String, key: &str, timestamp: &Timestamp, style: MachineTimestamp) {
+    let _ = write!(out, "\"{}\":", key);
+    match machine_time(timestamp, style) {
+        MachineTime::Number(n) => {
+            let _ = write!(out, "{}", n);
+        }
+        MachineTime::Text(text) => push_json_str(out, &text),
+    }
+}
+
+fn json(entry: &LogEntry, options: &FormatOptions) -> String {
+    let mut out = String::from("{");
+
+    push_json_time(&mut out, "timestamp", &entry.timestamp, options.machine_timestamp);
+    out.push_str(",\"level\":");
+    push_json_str(&mut out, entry.level.as_str());
+    let _ = write!(out, ",\"severity\":{}", entry.severity);
+    out.push_str(",\"component\":");
+    push_json_str(&mut out, &entry.component);
+    out.push_str(",\"message\":");
+    push_json_str(&mut out, &entry.message);
+    if let Some(code) = &entry.code {
+        out.push_str(",\"code\":");
+        push_json_str(&mut out, code);
+    }
+    if let Some(template) = &entry.template {
+        out.push_str(",\"template\":");
+        push_json_str(&mut out, template);
+    }
+    if !entry.fields.is_empty() {
+        out.push_str(",\"fields\":");
+        push_json_fields(&mut out, &entry.fields);
+    }
+    let _ = write!(out, ",\"seq\":{}", entry.seq);
+    if let Some(run_id) = &entry.run_id {
+        out.push_str(",\"run\":");
+        push_json_str(&mut out, run_id);
+    }
+    if let Some(span_id) = entry.span_id {
+        let _ = write!(out, ",\"span_id\":{}", span_id);
+    }
+    if let Some(parent_id) = entry.parent_id {
+        let _ = write!(out, ",\"parent_id\":{}", parent_id);
+    }
+    if let Some(group_id) = entry.group_id {
+        let _ = write!(out, ",\"group_id\":{}", group_id);
+    }
+    if let Some(correlation_id) = &entry.correlation_id {
+        out.push_str(",\"corr\":");
+        push_json_str(&mut out, correlation_id);
+    }
+    if let Some(backtrace) = &entry.backtrace {
+        out.push_str(",\"backtrace\":");
+        push_json_str(&mut out, backtrace);
+    }
+    if !entry.breadcrumbs.is_empty() {
+        out.push_str(",\"breadcrumbs\":[");
+        for (i, crumb) in entry.breadcrumbs.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            out.push('{');
+            push_json_time(&mut out, "timestamp", &crumb.timestamp, options.machine_timestamp);
+            out.push_str(",\"component\":");
+            push_json_str(&mut out, &crumb.component);
+            out.push_str(",\"message\":");
+            push_json_str(&mut out, &crumb.message);
+            out.push('}');
+        }
+        out.push(']');
+    }
+    if entry.repeat_count > 1 {
+        let _ = write!(out, ",\"repeat_count\":{},", entry.repeat_count);
+        push_json_time(&mut out, "last_timestamp", &entry.last_timestamp, options.machine_timestamp);
+    }
+    if let Some(rate) = entry.sampled {
+        out.push_str(",\"sampled\":");
+        push_json_str(&mut out, &rate.to_string());
+    }
+
+    out.push('}');
+    out
+}
+
+/// Append `fields` as a JSON object; numbers and booleans stay bare, non-finite floats become strings
+pub(crate) fn push_json_fields(out: &mut String, fields: &[(Cow<'static, str>, FieldValue)]) {
+    out.push('{');
+    for (i, (name, value)) in fields.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        push_json_str(out, name);
+        out.push(':');
+        match value {
+            FieldValue::Str(s) => push_json_str(out, s),
+            FieldValue::Float(x) if !x.is_finite() => push_json_str(out, &x.to_string()),
+            value => {
+                let _ = write!(out, "{}", value);
+            }
+        }
+    }
+    out.push('}');
+}
+
+fn logfmt(entry: &LogEntry, options: &FormatOptions) -> String {
+    let mut out = String::new();
+
+    match machine_time(&entry.timestamp, options.machine_timestamp) {
+        MachineTime::Number(n) => {
+            let _ = write!(out, "ts={}", n);
+        }
+        MachineTime::Text(text) => {
+            out.push_str("ts=");
+            push_logfmt_value(&mut out, &text);
+        }
+    }
+    out.push_str(" level=");
+    push_logfmt_value(&mut out, entry.level.as_str());
+    let _ = write!(out, " severity={}", entry.severity);
+    out.push_str(" component=");
+    push_logfmt_value(&mut out, &entry.component);
+    out.push_str(" msg=");
+    push_logfmt_value(&mut out, &entry.message);
+    if let Some(code) = &entry.code {
+        out.push_str(" code=");
+        push_logfmt_value(&mut out, code);
+    }
+    let _ = write!(out, " seq={}", entry.seq);
+    if let Some(run_id) = &entry.run_id {
+        out.push_str(" run=");
+        push_logfmt_value(&mut out, run_id);
+    }
+    if let Some(span_id) = entry.span_id {
+        let _ = write!(out, " span_id={}", span_id);
+    }
+    if let Some(parent_id) = entry.parent_id {
+        let _ = write!(out, " parent_id={}", parent_id);
+    }
+    if let Some(group_id) = entry.group_id {
+        let _ = write!(out, " group_id={}", group_id);
+    }
+    if let Some(correlation_id) = &entry.correlation_id {
+        out.push_str(" corr=");
+        push_logfmt_value(&mut out, correlation_id);
+    }
+    if let Some(rate) = entry.sampled {
+        let _ = write!(out, " sampled={}", rate);
+    }
+    append_fields(&mut out, entry.fields.iter());
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::{HorizonLogger, LogLevel};
+
+    fn fixed_entry() -> LogEntry {
+        let clock = ManualClock::new(Timestamp::from_micros(1_700_000_000_123_456));
+        let logger = HorizonLogger::builder().clock(clock).run_id("r1").announce_run(false).build();
+        logger.info("NETWORK", "player \"joined\"");
+        logger.get_history().remove(0)
+    }
+
+    fn options(machine_timestamp: MachineTimestamp) -> FormatOptions {
+        FormatOptions {
+            machine_timestamp,
+            ..FormatOptions::default()
+        }
+    }
+
+    #[test]
+    fn test_json_timestamp_styles() {
+        let entry = fixed_entry();
+
+        assert_eq!(
+            format_entry(&entry, Format::Json, &options(MachineTimestamp::EpochMillis)),
+            r#"{"timestamp":1700000000123,"level":"INFO","severity":20,"component":"NETWORK","message":"player \"joined\"","seq":0,"run":"r1"}"#
+        );
+        assert_eq!(
+            format_entry(&entry, Format::Json, &options(MachineTimestamp::EpochMicros)),
+            r#"{"timestamp":1700000000123456,"level":"INFO","severity":20,"component":"NETWORK","message":"player \"joined\"","seq":0,"run":"r1"}"#
+        );
+        assert_eq!(
+            format_entry(&entry, Format::Json, &options(MachineTimestamp::Rfc3339)),
+            r#"{"timestamp":"2023-11-14T22:13:20.123456Z","level":"INFO","severity":20,"component":"NETWORK","message":"player \"joined\"","seq":0,"run":"r1"}"#
+        );
+    }
+
+    #[test]
+    fn test_logfmt_timestamp_styles() {
+        let entry = fixed_entry();
+
+        assert_eq!(
+            format_entry(&entry, Format::Logfmt, &options(MachineTimestamp::EpochMillis)),
+            r#"ts=1700000000123 level=INFO severity=20 component=NETWORK msg="player \"joined\"" seq=0 run=r1"#
+        );
+        assert_eq!(
+            format_entry(&entry, Format::Logfmt, &options(MachineTimestamp::Rfc3339)),
+            r#"ts=2023-11-14T22:13:20.123456Z level=INFO severity=20 component=NETWORK msg="player \"joined\"" seq=0 run=r1"#
+        );
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_text_uses_local_time() {
+        let entry = fixed_entry();
+        let local = entry.timestamp.to_local().format("%Y-%m-%d %H:%M:%S%.3f");
+        assert_eq!(entry.to_string(), format!("{}  INFO   [NETWORK] player \"joined\"", local));
+    }
+
+    #[cfg(not(feature = "chrono"))]
+    #[test]
+    fn test_text_uses_utc_without_chrono() {
+        assert_eq!(
+            fixed_entry().to_string(),
+            "2023-11-14T22:13:20.123Z  INFO   [NETWORK] player \"joined\""
+        );
+    }
+
+    #[test]
+    fn test_logger_uses_configured_machine_timestamp() {
+        let clock = ManualClock::new(Timestamp::from_millis(42));
+        let logger = HorizonLogger::builder()
+            .announce_run(false)
+            .clock(clock)
+            .machine_timestamp(MachineTimestamp::EpochMillis)
+            .build();
+        logger.warn("GAME", "x");
+
+        let entry = logger.get_history().remove(0);
+        assert_eq!(entry.level, LogLevel::WARN);
+        assert!(logger.format_entry(&entry, Format::Json).starts_with(r#"{"timestamp":42,"#));
+    }
+
+    #[test]
+    fn test_json_strings_match_serde_json() {
+        for value in ["plain", "quote \" and \\", "tab\tnew\nline\r", "\u{0}\u{8}\u{c}\u{1f}\u{7f}", "héllo ✓"] {
+            let mut out = String::new();
+            push_json_str(&mut out, value);
+            assert_eq!(out, serde_json::to_string(value).unwrap());
+        }
+    }
+}
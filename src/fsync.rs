@@ -0,0 +1,198 @@
+use crate::{HorizonLogger, LogLevel};
+use std::fs::File;
+use std::io::{self, Write};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// When a `FileSink` asks the OS to write its file to disk
+///
+/// Flushing only reaches the OS page cache, which a power loss or kernel
+/// crash discards. A sync waits until the disk has the data: well under a
+/// millisecond on most SSDs, but tens of milliseconds on spinning disks and
+/// network filesystems, and the sink's lock is held meanwhile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SyncPolicy {
+    /// Only on `HorizonLogger::sync_all`; the OS writes the file back on its own schedule
+    #[default]
+    Never,
+    /// After every entry at or above this level, before `log` returns
+    ///
+    /// Costs a full sync on the logging thread for each such entry, so keep
+    /// it to rare levels such as CRITICAL.
+    OnLevel(LogLevel),
+    /// Every interval, from a background thread that stops when the sink is dropped
+    ///
+    /// Logging never waits for the disk, but up to one interval of entries
+    /// can be lost.
+    Interval(Duration),
+}
+
+/// A file `FileSink` writes to, with the sync it needs beyond `Write`
+pub(crate) trait SyncFile: Write + Send {
+    /// Wait until the data written so far is on disk
+    fn sync_data(&self) -> io::Result<()>;
+}
+
+impl SyncFile for File {
+    fn sync_data(&self) -> io::Result<()> {
+        File::sync_data(self)
+    }
+}
+
+/// The thread behind `SyncPolicy::Interval`, stopped and joined when dropped
+pub(crate) struct IntervalSync {
+    /// Starts a thread, which runs until its sender is dropped
+    spawn: Box<dyn Fn() -> (Sender<()>, JoinHandle<()>) + Send + Sync>,
+    worker: Mutex<Option<(Sender<()>, JoinHandle<()>)>>,
+}
+
+impl IntervalSync {
+    /// Call `sync` on `target` every `interval`
+    pub(crate) fn start<T: Send + 'static>(
+        interval: Duration,
+        target: Arc<Mutex<T>>,
+        sync: fn(&mut T) -> io::Result<()>,
+    ) -> Self {
+        let spawn = move || {
+            let (stop, stopped) = mpsc::channel::<()>();
+            let target = target.clone();
+            let thread = thread::Builder::new()
+                .name("horizon-file-sync".into())
+                .spawn(move || {
+                    while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                        if let Ok(mut target) = target.lock() {
+                            let _ = sync(&mut target);
+                        }
+                    }
+                })
+                .expect("failed to spawn the file sync thread");
+            (stop, thread)
+        };
+        IntervalSync {
+            worker: Mutex::new(Some(spawn())),
+            spawn: Box::new(spawn),
+        }
+    }
+
+    /// Start a new thread in the child after `fork()`, where the old one is gone
+    pub(crate) fn restart(&self) {
+        let mut worker = self.worker.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some((_, thread)) = worker.replace((self.spawn)()) {
+            // Joining would wait forever for a thread that didn't survive the fork
+            std::mem::forget(thread);
+        }
+    }
+}
+
+impl Drop for IntervalSync {
+    fn drop(&mut self) {
+        let worker = self.worker.get_mut().unwrap_or_else(|poisoned| poisoned.into_inner()).take();
+        if let Some((stop, thread)) = worker {
+            // Disconnecting wakes the thread immediately
+            drop(stop);
+            let _ = thread.join();
+        }
+    }
+}
+
+impl HorizonLogger {
+    /// Flush every sink and wait until the OS has written their files to disk
+    ///
+    /// For durability points such as autosaves, whatever each sink's
+    /// `SyncPolicy`. Returns the first error, after trying every sink.
+    pub fn sync_all(&self) -> io::Result<()> {
+        let mut result = Ok(());
+        for sink in self.sinks_snapshot() {
+            if let Err(e) = sink.sync() {
+                result = result.and(Err(e));
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FileSink;
+    use crate::testing::fixtures::temp_path;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A file that counts its syncs
+    struct CountingFile {
+        file: File,
+        syncs: Arc<AtomicUsize>,
+    }
+
+    impl Write for CountingFile {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.file.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.file.flush()
+        }
+    }
+
+    impl SyncFile for CountingFile {
+        fn sync_data(&self) -> io::Result<()> {
+            self.syncs.fetch_add(1, Ordering::SeqCst);
+            self.file.sync_data()
+        }
+    }
+
+    fn counting_sink(name: &str, policy: SyncPolicy) -> (FileSink, PathBuf, Arc<AtomicUsize>) {
+        let path = temp_path(name);
+        let sink = FileSink::new(&path).unwrap().with_sync_policy(policy);
+        let syncs = Arc::new(AtomicUsize::new(0));
+        let file = File::options().append(true).open(&path).unwrap();
+        sink.replace_file(Box::new(CountingFile { file, syncs: syncs.clone() }));
+        (sink, path, syncs)
+    }
+
+    #[test]
+    fn test_sync_on_level() {
+        let (sink, path, syncs) = counting_sink("sync_on_level.log", SyncPolicy::OnLevel(LogLevel::CRITICAL));
+        let logger = HorizonLogger::builder().announce_run(false).build();
+        logger.add_sink(sink);
+
+        logger.error("STORAGE", "save slow");
+        assert_eq!(syncs.load(Ordering::SeqCst), 0);
+        logger.critical("STORAGE", "disk failing");
+        assert_eq!(syncs.load(Ordering::SeqCst), 1);
+        // The synced data includes everything before it
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(text.contains("save slow") && text.contains("disk failing"));
+
+        logger.info("SAVE", "autosave done");
+        logger.sync_all().unwrap();
+        assert_eq!(syncs.load(Ordering::SeqCst), 2);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_interval_sync_stops_with_logger() {
+        let (sink, path, syncs) = counting_sink("sync_interval.log", SyncPolicy::Interval(Duration::from_millis(5)));
+        let logger = HorizonLogger::builder().announce_run(false).build();
+        logger.add_sink(sink);
+        logger.info("GAME", "tick");
+        for _ in 0..200 {
+            if syncs.load(Ordering::SeqCst) > 0 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert!(syncs.load(Ordering::SeqCst) > 0);
+
+        // Dropping the logger drops the sink, which joins the thread before its final sync
+        drop(logger);
+        let after = syncs.load(Ordering::SeqCst);
+        thread::sleep(Duration::from_millis(30));
+        assert_eq!(syncs.load(Ordering::SeqCst), after);
+        assert!(std::fs::read_to_string(&path).unwrap().contains("tick"));
+        let _ = std::fs::remove_file(&path);
+    }
+}
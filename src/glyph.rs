@@ -0,0 +1,253 @@
+//! Icons in their own console column after the level, see `LoggerBuilder::level_glyph`
+//!
+//! Glyphs are console decoration only: files, JSON, history and every
+//! other output never see them, and pattern layouts and banners don't
+//! show them. Each glyph has an ASCII fallback, used when the terminal or
+//! locale can't be trusted with anything else.
+
+use crate::history::component_matches;
+use crate::{unicode, LogLevel};
+
+/// Standard glyphs for `LoggerBuilder::default_level_glyphs`, with their ASCII fallbacks
+pub(crate) const DEFAULT_GLYPHS: [(LogLevel, &str, &str); 4] = [
+    (LogLevel::INFO, "✔", "+"),
+    (LogLevel::WARN, "⚠", "!"),
+    (LogLevel::ERROR, "✖", "x"),
+    (LogLevel::CRITICAL, "☠", "X"),
+];
+
+/// Fallbacks for level glyphs outside ASCII that weren't given one, indexed by `LogLevel as usize`
+const ASCII_FALLBACKS: [&str; LogLevel::COUNT] = [".", "+", "!", "x", "X"];
+
+/// Which form of each glyph the console shows, see `LoggerBuilder::console_glyphs`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GlyphSet {
+    /// Unicode when the terminal and locale handle it, ASCII otherwise
+    #[default]
+    Auto,
+    /// Always the Unicode glyphs
+    Unicode,
+    /// Always the ASCII fallbacks
+    Ascii,
+}
+
+/// A glyph and what stands in for it on consoles without Unicode
+#[derive(Debug, Clone, Default)]
+struct Glyph {
+    unicode: String,
+    ascii: Option<String>,
+}
+
+/// Glyphs set on the builder, resolved to one form per glyph in `build`
+#[derive(Debug, Clone, Default)]
+pub(crate) struct GlyphConfig {
+    set: GlyphSet,
+    levels: [Option<Glyph>; LogLevel::COUNT],
+    /// Component prefixes and their glyphs, in the order they were set
+    components: Vec<(String, Glyph)>,
+}
+
+impl GlyphConfig {
+    pub(crate) fn set(&mut self, set: GlyphSet) {
+        self.set = set;
+    }
+
+    pub(crate) fn level(&mut self, level: LogLevel, glyph: &str) {
+        let slot = self.levels[level as usize].get_or_insert_with(Glyph::default);
+        slot.unicode = glyph.to_string();
+    }
+
+    pub(crate) fn level_fallback(&mut self, level: LogLevel, ascii: &str) {
+        let slot = self.levels[level as usize].get_or_insert_with(Glyph::default);
+        slot.ascii = Some(ascii.to_string());
+    }
+
+    /// Set the glyph of `prefix`, replacing one set for it before
+    pub(crate) fn component(&mut self, prefix: &str, glyph: &str, ascii: &str) {
+        let glyph = Glyph {
+            unicode: glyph.to_string(),
+            ascii: Some(ascii.to_string()),
+        };
+        match self.components.iter_mut().find(|(existing, _)| existing == prefix) {
+            Some((_, existing)) => *existing = glyph,
+            None => self.components.push((prefix.to_string(), glyph)),
+        }
+    }
+
+    /// The glyphs the console shows, or `None` when none are set
+    pub(crate) fn resolve(self) -> Option<Glyphs> {
+        if self.levels.iter().all(Option::is_none) && self.components.is_empty() {
+            return None;
+        }
+        let unicode = match self.set {
+            GlyphSet::Auto => unicode_supported(|name| std::env::var(name).ok()),
+            GlyphSet::Unicode => true,
+            GlyphSet::Ascii => false,
+        };
+        let pick = |glyph: Glyph, fallback: &str| match glyph.ascii {
+            _ if unicode => glyph.unicode,
+            Some(ascii) => ascii,
+            None if glyph.unicode.is_ascii() => glyph.unicode,
+            None => fallback.to_string(),
+        };
+
+        let mut levels: [Option<String>; LogLevel::COUNT] = Default::default();
+        for (index, glyph) in self.levels.into_iter().enumerate() {
+            levels[index] = glyph.map(|glyph| pick(glyph, ASCII_FALLBACKS[index]));
+        }
+        let mut components: Vec<(String, String)> =
+            self.components.into_iter().map(|(prefix, glyph)| (prefix, pick(glyph, ""))).collect();
+        // Longest prefix first, so the most specific one matches
+        components.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+        let width = levels
+            .iter()
+            .flatten()
+            .chain(components.iter().map(|(_, glyph)| glyph))
+            .map(|glyph| crate::ansi::display_width(glyph))
+            .max()
+            .unwrap_or(0);
+        Some(Glyphs { levels, components, width })
+    }
+}
+
+/// Glyphs in the form the console shows them
+#[derive(Debug)]
+pub(crate) struct Glyphs {
+    levels: [Option<String>; LogLevel::COUNT],
+    /// Longest prefix first
+    components: Vec<(String, String)>,
+    /// Columns of the widest glyph, which every glyph is padded to
+    width: usize,
+}
+
+impl Glyphs {
+    /// The glyph for an entry: its component's if it has one, else its level's
+    pub(crate) fn pick(&self, level: LogLevel, component: &str) -> Option<&str> {
+        self.components
+            .iter()
+            .find(|(prefix, _)| component_matches(component, prefix))
+            .map(|(_, glyph)| glyph.as_str())
+            .or(self.levels[level as usize].as_deref())
+            .filter(|glyph| !glyph.is_empty())
+    }
+
+    pub(crate) fn width(&self) -> usize {
+        self.width
+    }
+}
+
+/// Whether the console can be trusted to draw glyphs outside ASCII
+///
+/// Not on a legacy Windows console; elsewhere only in a UTF-8 locale, from
+/// the first of `LC_ALL`, `LC_CTYPE` and `LANG` that is set.
+fn unicode_supported(var: impl Fn(&str) -> Option<String>) -> bool {
+    if unicode::legacy_console().is_some() {
+        return false;
+    }
+    if cfg!(windows) {
+        return true;
+    }
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .into_iter()
+        .find_map(|name| var(name).filter(|value| !value.is_empty()));
+    locale.is_some_and(|locale| {
+        let locale = locale.to_ascii_lowercase();
+        locale.contains("utf-8") || locale.contains("utf8")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::console::tests::SharedBuf;
+    use crate::console::Console;
+    use crate::{ConsoleFields, HorizonLogger};
+
+    fn glyph_logger(buf: &SharedBuf, set: GlyphSet) -> HorizonLogger {
+        let fields = ConsoleFields::SEQ | ConsoleFields::LEVEL | ConsoleFields::COMPONENT | ConsoleFields::MESSAGE;
+        HorizonLogger::builder()
+            .announce_run(false)
+            .console(Console::new(Box::new(buf.clone()), false))
+            .console_fields(fields)
+            .default_level_glyphs()
+            .component_glyph("NET", "🌐", "@")
+            .console_glyphs(set)
+            .build()
+    }
+
+    fn log_all(logger: &HorizonLogger) {
+        logger.debug("GAME", "tick");
+        logger.info("GAME", "player joined");
+        logger.warn("GAME", "slow frame");
+        logger.error("GAME", "save failed");
+        logger.critical("GAME", "world lost");
+        logger.warn("NET/UDP", "packet loss");
+    }
+
+    #[test]
+    fn test_unicode_glyphs() {
+        let buf = SharedBuf::default();
+        let logger = glyph_logger(&buf, GlyphSet::Unicode);
+        log_all(&logger);
+        // The globe is two columns wide, so everything else is padded to two
+        assert_eq!(
+            buf.contents(),
+            "#0  DEBUG     [GAME] tick\n\
+             #1  INFO   ✔  [GAME] player joined\n\
+             #2  WARN   ⚠  [GAME] slow frame\n\
+             #3  ERROR  ✖  [GAME] save failed\n\
+             #4  CRIT   ☠  [GAME] world lost\n\
+             #5  WARN   🌐 [NET/UDP] packet loss\n"
+        );
+        let history = logger.get_history();
+        assert!(history.iter().all(|entry| !entry.to_string().contains('⚠') && !entry.to_json().contains('⚠')));
+    }
+
+    #[test]
+    fn test_ascii_fallbacks() {
+        let buf = SharedBuf::default();
+        log_all(&glyph_logger(&buf, GlyphSet::Ascii));
+        assert_eq!(
+            buf.contents(),
+            "#0  DEBUG    [GAME] tick\n\
+             #1  INFO   + [GAME] player joined\n\
+             #2  WARN   ! [GAME] slow frame\n\
+             #3  ERROR  x [GAME] save failed\n\
+             #4  CRIT   X [GAME] world lost\n\
+             #5  WARN   @ [NET/UDP] packet loss\n"
+        );
+
+        let buf = SharedBuf::default();
+        let logger = HorizonLogger::builder()
+            .announce_run(false)
+            .console(Console::new(Box::new(buf.clone()), false))
+            .console_fields(ConsoleFields::COMPONENT | ConsoleFields::MESSAGE)
+            .level_glyph(LogLevel::WARN, "⚠️")
+            .level_glyph(LogLevel::ERROR, "✖")
+            .level_glyph_fallback(LogLevel::ERROR, "ERR")
+            .level_glyph(LogLevel::INFO, "i")
+            .console_glyphs(GlyphSet::Ascii)
+            .build();
+        logger.warn("GAME", "slow frame");
+        logger.error("GAME", "save failed");
+        logger.info("GAME", "saved");
+        logger.debug("GAME", "tick");
+        assert_eq!(buf.contents(), "!   [GAME] slow frame\nERR [GAME] save failed\ni   [GAME] saved\n[GAME] tick\n");
+    }
+
+    #[test]
+    fn test_unicode_detection() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| vars.iter().find(|(var, _)| *var == name).map(|(_, value)| value.to_string())
+        };
+        assert!(unicode_supported(env(&[("LANG", "en_US.UTF-8")])));
+        assert!(unicode_supported(env(&[("LC_ALL", "de_DE.utf8"), ("LANG", "C")])));
+        assert!(unicode_supported(env(&[("LC_ALL", ""), ("LC_CTYPE", "C.UTF-8")])));
+        if !cfg!(windows) {
+            assert!(!unicode_supported(env(&[("LC_ALL", "C"), ("LANG", "en_US.UTF-8")])));
+            assert!(!unicode_supported(env(&[("LANG", "POSIX")])));
+            assert!(!unicode_supported(env(&[])));
+        }
+        assert!(GlyphConfig::default().resolve().is_none());
+    }
+}
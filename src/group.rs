@@ -0,0 +1,313 @@
+use crate::empty::{Checked, REPLACEMENT};
+use crate::{breadcrumb, console, reentry, ComponentArg, HorizonLogger, LogEntry, LogLevel};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+/// Source of process-wide unique group ids
+static NEXT_GROUP_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Related entries output together as one block, see `HorizonLogger::group`
+///
+/// Entries are buffered until the group is committed or dropped, then
+/// written between a `[[ title` header and a `]] title (n entries, elapsed)`
+/// footer with no other entry in between. A group that buffers
+/// `LoggerBuilder::group_max_entries` entries is flushed early, and the
+/// rest follow in a block marked `(continued)`.
+pub struct LogGroup {
+    logger: HorizonLogger,
+    id: u64,
+    component: Cow<'static, str>,
+    title: String,
+    start: Instant,
+    buffered: RefCell<Buffered>,
+}
+
+#[derive(Default)]
+struct Buffered {
+    entries: Vec<LogEntry>,
+    /// Entries already flushed in earlier blocks
+    flushed: usize,
+}
+
+impl LogGroup {
+    /// Id attached to every entry of this group, see `LogEntry::group_id`
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Buffer an entry at `level` under the group's component
+    ///
+    /// Level filters apply now; the entry is timestamped now and numbered
+    /// when its block is written.
+    #[track_caller]
+    pub fn log(&self, level: LogLevel, message: &str) {
+        if self.logger.inner.kill_switch.is_off() {
+            self.logger.inner.kill_switch.suppress(1);
+            return;
+        }
+        if !self.logger.enabled(level) || !self.logger.level_passes(level, &self.component) {
+            return;
+        }
+        let location = self.logger.caller_location(level);
+        let (message, callsite) = match self.logger.inner.empty_messages.check(message, location) {
+            Checked::Keep(message) => (message, Vec::new()),
+            Checked::Drop => return,
+            Checked::Replace(callsite) => (REPLACEMENT, callsite),
+        };
+        let timestamp = self.logger.inner.clock.now();
+        let mut entry = self.logger.new_entry(timestamp, level, self.component.clone(), message, None);
+        entry.group_id = Some(self.id);
+        entry.fields.extend(callsite);
+        entry.breadcrumbs = breadcrumb::take_for(level);
+
+        let full = {
+            let Ok(mut buffered) = self.buffered.try_borrow_mut() else {
+                return;
+            };
+            buffered.entries.push(entry);
+            buffered.entries.len() >= self.logger.inner.group_max_entries
+        };
+        if full {
+            self.flush(false);
+        }
+    }
+
+    /// Buffer a debug message
+    #[track_caller]
+    pub fn debug(&self, message: &str) {
+        self.log(LogLevel::DEBUG, message);
+    }
+
+    /// Buffer an info message
+    #[track_caller]
+    pub fn info(&self, message: &str) {
+        self.log(LogLevel::INFO, message);
+    }
+
+    /// Buffer a warning message
+    #[track_caller]
+    pub fn warn(&self, message: &str) {
+        self.log(LogLevel::WARN, message);
+    }
+
+    /// Buffer an error message
+    #[track_caller]
+    pub fn error(&self, message: &str) {
+        self.log(LogLevel::ERROR, message);
+    }
+
+    /// Buffer a critical message
+    #[track_caller]
+    pub fn critical(&self, message: &str) {
+        self.log(LogLevel::CRITICAL, message);
+    }
+
+    /// Write the buffered entries now; dropping the group does the same
+    pub fn commit(self) {}
+
+    /// Write the buffered entries as one block between a header and a footer
+    ///
+    /// The header and footer are logged at the block's highest level, so
+    /// they pass the same filters as its entries.
+    fn flush(&self, last: bool) {
+        let Ok(mut buffered) = self.buffered.try_borrow_mut() else {
+            return;
+        };
+        let entries = std::mem::take(&mut buffered.entries);
+        let Some(level) = entries.iter().map(|e| e.level).max() else {
+            return;
+        };
+        let continued = if buffered.flushed > 0 { " (continued)" } else { "" };
+        buffered.flushed += entries.len();
+        let so_far = if last { "" } else { " so far" };
+        let elapsed = crate::fmt::duration(self.start.elapsed());
+        let header = format!("[[ {}{}", self.title, continued);
+        let footer = format!("]] {} ({} entries{}, {})", self.title, buffered.flushed, so_far, elapsed);
+        drop(buffered);
+
+        let marker = |message: &str| {
+            let timestamp = self.logger.inner.clock.now();
+            let mut entry = self.logger.new_entry(timestamp, level, self.component.clone(), message, None);
+            entry.group_id = Some(self.id);
+            entry
+        };
+        let mut block = Vec::with_capacity(entries.len() + 2);
+        block.push(marker(&header));
+        block.extend(entries);
+        block.push(marker(&footer));
+        self.logger.write_block(block);
+    }
+}
+
+impl Drop for LogGroup {
+    fn drop(&mut self) {
+        self.flush(true);
+    }
+}
+
+impl HorizonLogger {
+    /// Start a group of entries under `component`, output together as one block
+    ///
+    /// ```
+    /// let logger = horizon_logger::HorizonLogger::new();
+    /// let group = logger.group("SAVEGAME", "loading slot 3");
+    /// group.info("reading header");
+    /// group.info("restoring inventory");
+    /// group.commit();
+    /// ```
+    pub fn group(&self, component: impl ComponentArg, title: &str) -> LogGroup {
+        let component = match self.inner.aliases.resolve(component.as_str()) {
+            Cow::Borrowed(_) => component.to_cow(),
+            Cow::Owned(renamed) => Cow::Owned(renamed),
+        };
+        LogGroup {
+            logger: self.clone(),
+            id: NEXT_GROUP_ID.fetch_add(1, Ordering::Relaxed),
+            component,
+            title: title.to_string(),
+            start: Instant::now(),
+            buffered: RefCell::default(),
+        }
+    }
+
+    /// Number, print and hand to sinks and history every entry of `block`, in order
+    ///
+    /// `block` starts with its header and ends with its footer, which
+    /// escalation rules do not count. Holds the group lock for writing
+    /// throughout, so normal log calls on other threads wait until the
+    /// whole block is out.
+    fn write_block(&self, block: Vec<LogEntry>) {
+        if self.inner.kill_switch.is_off() {
+            self.inner.kill_switch.suppress(block.len() as u64);
+            return;
+        }
+        self.announce_run();
+
+        let Some(guard) = reentry::enter() else {
+            // Committed from inside a sink; the block is recorded once that write returns
+            for entry in block {
+                reentry::defer(reentry::Deferred {
+                    logger: self.clone(),
+                    entry,
+                    indent: 0,
+                });
+            }
+            return;
+        };
+
+        let observed: Vec<_> = block[1..block.len() - 1]
+            .iter()
+            .map(|e| (e.level, e.component.clone(), e.timestamp))
+            .collect();
+        {
+            let _block = self.inner.group_lock.write();
+            if self.inner.seal.rejects(block.len() as u64) {
+                return;
+            }
+            let preinit = self.inner.preinit.is_active();
+            let sinks = self.sinks_snapshot();
+            let buffering = preinit && sinks.is_empty();
+            for mut entry in block {
+                entry.seq = self.inner.history.reserve_seq();
+                self.inner.console.write_entry(&console::LineParts::of(&entry, 0), None);
+                self.inner.stats.record(entry.level, &entry.component, entry.message.len());
+                self.inner.sizes.record(entry.level, &entry.component, &entry.message);
+                if buffering {
+                    self.buffer_or_write(&entry);
+                } else {
+                    self.write_sinks(&sinks, &entry);
+                }
+                if self.inner.keep_history {
+                    self.inner.history.store(entry);
+                }
+            }
+        }
+
+        for deferred in reentry::take() {
+            deferred.logger.record(deferred.entry, deferred.indent);
+        }
+        drop(guard);
+
+        for (level, component, timestamp) in observed {
+            self.escalate(level, &component, timestamp);
+        }
+        self.check_volume(self.inner.clock.now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::console::tests::SharedBuf;
+    use crate::console::Console;
+    use crate::ConsoleFields;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn test_block_is_contiguous() {
+        let buf = SharedBuf::default();
+        let logger = HorizonLogger::builder()
+            .announce_run(false)
+            .console(Console::new(Box::new(buf.clone()), false))
+            .console_fields(ConsoleFields::COMPONENT)
+            .build();
+        logger.set_min_level(LogLevel::INFO);
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let noise = thread::spawn({
+            let logger = logger.clone();
+            let stop = stop.clone();
+            move || {
+                while !stop.load(Ordering::Relaxed) {
+                    logger.info("NETWORK", "tick");
+                }
+            }
+        });
+
+        let group = logger.group("SAVEGAME", "loading slot 3");
+        for step in 0..20 {
+            group.info(&format!("step {}", step));
+            thread::yield_now();
+        }
+        group.debug("filtered");
+        let id = group.id();
+        group.commit();
+        stop.store(true, Ordering::Relaxed);
+        noise.join().unwrap();
+
+        let history = logger.get_history();
+        let grouped: Vec<_> = history.iter().filter(|e| e.group_id == Some(id)).collect();
+        assert_eq!(grouped.len(), 22);
+        assert!(grouped.windows(2).all(|pair| pair[1].seq == pair[0].seq + 1));
+        assert_eq!(grouped[0].message, "[[ loading slot 3");
+        assert_eq!(grouped[1].message, "step 0");
+        assert!(grouped[21].message.starts_with("]] loading slot 3 (20 entries, "), "{}", grouped[21].message);
+
+        let console = buf.contents();
+        let start = console.find("[SAVEGAME] [[ loading slot 3").unwrap();
+        let block: Vec<_> = console[start..].lines().take(22).collect();
+        assert!(block.iter().all(|line| line.starts_with("[SAVEGAME]")), "{:?}", block);
+    }
+
+    #[test]
+    fn test_full_group_flushes_early() {
+        let logger = crate::testing::CaptureLogger::from_builder(HorizonLogger::builder().group_max_entries(2));
+        let group = logger.group("SAVEGAME", "loading slot 3");
+        group.info("header");
+        group.warn("missing icon");
+        assert_eq!(logger.messages().len(), 4);
+        group.info("inventory");
+        drop(group);
+
+        let messages = logger.messages();
+        assert!(messages[3].starts_with("]] loading slot 3 (2 entries so far, "), "{:?}", messages);
+        assert_eq!(messages[4], "[[ loading slot 3 (continued)");
+        assert!(messages[6].starts_with("]] loading slot 3 (3 entries, "), "{:?}", messages);
+        let levels: Vec<_> = logger.entries().iter().map(|e| e.level).collect();
+        assert_eq!(levels[..4], [LogLevel::WARN, LogLevel::INFO, LogLevel::WARN, LogLevel::WARN]);
+    }
+}
@@ -0,0 +1,242 @@
+//! Self-describing headers for log files
+//!
+//! Every file a `FileSink` creates starts with a comment line naming its
+//! format, version, the run of the entry that started it and the fields,
+//! for example:
+//!
+//! ```text
+//! # horizon-logger format=jsonl v=2 run=1a2b3c4d fields=timestamp,level,component,message,seq,...
+//! ```
+//!
+//! `run` is left out when the file was flushed before any entry, or when
+//! the run id has whitespace in it. Binary files start with their own
+//! magic, version and run id instead. Either way
+//! `detect_format` tells them apart by reading only the header.
+
+use crate::binary;
+use crate::format::Format;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+
+/// Version of the text, JSON and logfmt file formats, bumped when fields change
+pub const FORMAT_VERSION: u32 = 2;
+
+/// Start of every text header line
+const PREFIX: &str = "# horizon-logger ";
+
+/// Longest header line `detect_format` will read
+const MAX_HEADER_LEN: u64 = 4096;
+
+/// Name of a line format in headers
+fn format_name(format: Format) -> &'static str {
+    match format {
+        Format::Text => "text",
+        Format::Json => "jsonl",
+        Format::Logfmt => "logfmt",
+    }
+}
+
+/// Fields a line format may contain, in the order they are written
+fn format_fields(format: Format) -> &'static str {
+    match format {
+        Format::Text => "ts,level,component,msg",
+        Format::Json => {
+            "timestamp,level,component,message,code,template,fields,seq,run,span_id,parent_id,group_id,corr,backtrace,repeat_count,last_timestamp"
+        }
+        Format::Logfmt => "ts,level,component,msg,code,seq,run,span_id,parent_id,group_id,corr",
+    }
+}
+
+/// The header line for a file of `format` started by an entry of run `run`, including the trailing newline
+pub(crate) fn header_line(format: Format, run: Option<&str>) -> String {
+    let run = run
+        .filter(|run| !run.is_empty() && !run.contains(char::is_whitespace))
+        .map(|run| format!("run={} ", run))
+        .unwrap_or_default();
+    format!(
+        "{}format={} v={} {}fields={}\n",
+        PREFIX,
+        format_name(format),
+        FORMAT_VERSION,
+        run,
+        format_fields(format)
+    )
+}
+
+/// What kind of log file a header describes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    /// One entry per line, as written by `FileSink`
+    Lines(Format),
+    /// The `BinarySink` record format
+    Binary,
+}
+
+/// A log file's format, as announced by its header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatInfo {
+    pub kind: FileKind,
+    pub version: u32,
+    /// Field names the format may contain, in the order they are written
+    pub fields: Vec<String>,
+    /// Run id of the entry that started the file, if the header records one
+    pub run: Option<String>,
+}
+
+/// `detect_format` could not identify a file
+#[derive(Debug)]
+pub enum DetectError {
+    Io(io::Error),
+    /// The file does not start with a horizon-logger header
+    NoHeader,
+    /// The header names a format this version does not know
+    UnknownFormat(String),
+    /// The header is present but cannot be parsed
+    Malformed(String),
+}
+
+impl fmt::Display for DetectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DetectError::Io(e) => write!(f, "cannot read log file: {}", e),
+            DetectError::NoHeader => write!(f, "no horizon-logger header"),
+            DetectError::UnknownFormat(name) => write!(f, "unknown log format `{}`", name),
+            DetectError::Malformed(header) => write!(f, "malformed header: {}", header),
+        }
+    }
+}
+
+impl std::error::Error for DetectError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DetectError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for DetectError {
+    fn from(e: io::Error) -> Self {
+        DetectError::Io(e)
+    }
+}
+
+/// Identify a log file from its header, without reading any entries
+pub fn detect_format(path: impl AsRef<Path>) -> Result<FormatInfo, DetectError> {
+    let mut reader = BufReader::new(File::open(path)?);
+
+    if let Some(version) = binary::sniff_header(reader.fill_buf()?) {
+        return Ok(FormatInfo {
+            kind: FileKind::Binary,
+            version: version.into(),
+            fields: binary::FIELDS.iter().map(|f| f.to_string()).collect(),
+            run: binary::read_header(&mut reader).ok().and_then(|header| header.run),
+        });
+    }
+
+    let mut line = String::new();
+    reader.take(MAX_HEADER_LEN).read_line(&mut line)?;
+    parse_header(line.trim_end_matches(['\n', '\r']))
+}
+
+fn parse_header(line: &str) -> Result<FormatInfo, DetectError> {
+    let rest = line.strip_prefix(PREFIX).ok_or(DetectError::NoHeader)?;
+    let malformed = || DetectError::Malformed(line.to_string());
+
+    let (mut kind, mut version, mut fields, mut run) = (None, None, None, None);
+    for pair in rest.split_whitespace() {
+        let (key, value) = pair.split_once('=').ok_or_else(malformed)?;
+        match key {
+            "format" => {
+                kind = Some(match value {
+                    "text" => Format::Text,
+                    "jsonl" => Format::Json,
+                    "logfmt" => Format::Logfmt,
+                    other => return Err(DetectError::UnknownFormat(other.to_string())),
+                })
+            }
+            "v" => version = Some(value.parse().map_err(|_| malformed())?),
+            "fields" => fields = Some(value.split(',').map(str::to_string).collect()),
+            "run" => run = Some(value.to_string()),
+            // Keys added by later versions
+            _ => {}
+        }
+    }
+
+    Ok(FormatInfo {
+        kind: FileKind::Lines(kind.ok_or_else(malformed)?),
+        version: version.ok_or_else(malformed)?,
+        fields: fields.unwrap_or_default(),
+        run,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixtures::temp_path;
+    use crate::testing::CaptureLogger;
+    use crate::{BinarySink, FileSink};
+
+    #[test]
+    fn test_header_lines() {
+        assert_eq!(
+            header_line(Format::Json, None),
+            "# horizon-logger format=jsonl v=2 fields=timestamp,level,component,message,code,template,fields,seq,run,\
+             span_id,parent_id,group_id,corr,backtrace,repeat_count,last_timestamp\n"
+        );
+        assert_eq!(header_line(Format::Text, None), "# horizon-logger format=text v=2 fields=ts,level,component,msg\n");
+        assert_eq!(
+            header_line(Format::Text, Some("1a2b3c4d")),
+            "# horizon-logger format=text v=2 run=1a2b3c4d fields=ts,level,component,msg\n"
+        );
+        assert!(!header_line(Format::Text, Some("play test")).contains("run="));
+        assert_eq!(
+            header_line(Format::Logfmt, None),
+            "# horizon-logger format=logfmt v=2 fields=ts,level,component,msg,code,seq,run,span_id,parent_id,group_id,corr\n"
+        );
+    }
+
+    #[test]
+    fn test_parse_header() {
+        for format in [Format::Text, Format::Json, Format::Logfmt] {
+            let info = parse_header(header_line(format, Some("abc")).trim_end()).unwrap();
+            assert_eq!(info.kind, FileKind::Lines(format));
+            assert_eq!(info.run.as_deref(), Some("abc"));
+            assert_eq!(info.version, FORMAT_VERSION);
+            assert_eq!(info.fields.join(","), format_fields(format));
+        }
+
+        let future = parse_header("# horizon-logger format=jsonl v=9 fields=a,b schema=x").unwrap();
+        assert_eq!((future.version, future.fields.len()), (9, 2));
+
+        assert!(matches!(parse_header("2024-01-01 INFO hello"), Err(DetectError::NoHeader)));
+        assert!(matches!(parse_header("# horizon-logger format=csv v=2"), Err(DetectError::UnknownFormat(f)) if f == "csv"));
+        assert!(matches!(parse_header("# horizon-logger format=text"), Err(DetectError::Malformed(_))));
+    }
+
+    #[test]
+    fn test_detect_written_files() {
+        let lines = temp_path("detect.jsonl");
+        let binary = temp_path("detect.hzlog");
+
+        let logger = CaptureLogger::new();
+        logger.add_sink(FileSink::new(&lines).unwrap().with_format(Format::Json));
+        logger.add_sink(BinarySink::new(&binary).unwrap());
+        logger.info("TEST", "hello");
+        logger.flush();
+
+        let info = detect_format(&lines).unwrap();
+        assert_eq!((info.kind, info.version), (FileKind::Lines(Format::Json), FORMAT_VERSION));
+        assert_eq!(info.run.as_deref(), Some(logger.run_id()));
+        let info = detect_format(&binary).unwrap();
+        assert_eq!((info.kind, info.version), (FileKind::Binary, 2));
+        assert_eq!(info.run.as_deref(), Some(logger.run_id()));
+        assert_eq!(info.fields, vec!["seq", "ts", "level", "component", "msg"]);
+
+        let _ = std::fs::remove_file(&lines);
+        let _ = std::fs::remove_file(&binary);
+    }
+}
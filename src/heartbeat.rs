@@ -0,0 +1,156 @@
+use crate::{HorizonLogger, LogLevel};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Stops a heartbeat started with `start_heartbeat` when dropped
+pub struct HeartbeatHandle(Arc<Heartbeat>);
+
+/// A running heartbeat, which `after_fork_child` can start again
+pub(crate) struct Heartbeat {
+    interval: Duration,
+    component: String,
+    worker: Mutex<Option<(Sender<()>, JoinHandle<()>)>>,
+}
+
+impl HeartbeatHandle {
+    /// Stop the heartbeat and wait for its thread to exit
+    pub fn stop(self) {
+        // Dropping the handle does it
+    }
+}
+
+impl Drop for HeartbeatHandle {
+    fn drop(&mut self) {
+        let worker = self.0.worker.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).take();
+        if let Some((stop, thread)) = worker {
+            // Disconnecting wakes the thread immediately
+            drop(stop);
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Heartbeat {
+    /// Start a new thread in the child after `fork()`, unless the heartbeat was stopped
+    #[cfg(all(unix, feature = "fork"))]
+    pub(crate) fn restart(&self, logger: &HorizonLogger) {
+        let mut worker = self.worker.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if worker.is_some() {
+            if let Some((_, thread)) = worker.replace(self.spawn(logger)) {
+                // Its thread stayed behind in the parent
+                std::mem::forget(thread);
+            }
+        }
+    }
+
+    fn spawn(&self, logger: &HorizonLogger) -> (Sender<()>, JoinHandle<()>) {
+        let logger = logger.clone();
+        let (interval, component) = (self.interval, self.component.clone());
+        let (stop, stopped) = mpsc::channel::<()>();
+        let (mut logged, mut dropped) = logger.heartbeat_counters();
+
+        let thread = thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                let (now_logged, now_dropped) = logger.heartbeat_counters();
+                let uptime = logger.inner.clock.now().duration_since(logger.inner.started);
+                let message = format!(
+                    "heartbeat: up {}, {} entries and {} dropped since last heartbeat",
+                    crate::fmt::duration(uptime),
+                    // Counters restart from zero after `reset_stats`
+                    now_logged.checked_sub(logged).unwrap_or(now_logged),
+                    now_dropped.checked_sub(dropped).unwrap_or(now_dropped)
+                );
+                logger.log(LogLevel::INFO, &component, &message);
+                // Count from after the heartbeat so it does not report itself
+                (logged, dropped) = logger.heartbeat_counters();
+            }
+        });
+        (stop, thread)
+    }
+}
+
+impl HorizonLogger {
+    /// Log an INFO heartbeat under `component` every `interval` until the handle is dropped
+    ///
+    /// Each heartbeat reports the logger's uptime and the entries logged and
+    /// dropped since the previous one, taken from the stats counters. It is
+    /// logged like any other entry, so it also shows the sinks are alive.
+    pub fn start_heartbeat(&self, interval: Duration, component: &str) -> HeartbeatHandle {
+        let heartbeat = Arc::new(Heartbeat {
+            interval,
+            component: component.to_string(),
+            worker: Mutex::new(None),
+        });
+        *heartbeat.worker.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(heartbeat.spawn(self));
+        #[cfg(all(unix, feature = "fork"))]
+        if let Ok(mut heartbeats) = self.inner.heartbeats.lock() {
+            heartbeats.retain(|heartbeat| heartbeat.strong_count() > 0);
+            heartbeats.push(Arc::downgrade(&heartbeat));
+        }
+        HeartbeatHandle(heartbeat)
+    }
+
+    /// Entries logged and dropped so far
+    fn heartbeat_counters(&self) -> (u64, u64) {
+        let logged = self.inner.stats.snapshot().iter().map(|stats| stats.total_messages()).sum();
+        (logged, self.inner.stats.dropped())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::sink::Sink;
+    use crate::testing::CaptureLogger;
+    use crate::{FormatOptions, LogEntry, Timestamp};
+    use std::io;
+    use std::sync::Arc;
+
+    struct FailingSink;
+
+    impl Sink for FailingSink {
+        fn write(&self, entry: &LogEntry, _options: &FormatOptions) -> io::Result<()> {
+            if entry.component == "GAME" {
+                return Err(io::Error::other("disk full"));
+            }
+            Ok(())
+        }
+    }
+
+    fn heartbeats(logger: &CaptureLogger) -> Vec<String> {
+        logger
+            .entries()
+            .into_iter()
+            .filter(|e| e.component == "HEARTBEAT")
+            .map(|e| e.message)
+            .collect()
+    }
+
+    #[test]
+    fn test_heartbeat_reports_and_stops() {
+        let clock = Arc::new(ManualClock::new(Timestamp::from_millis(0)));
+        let logger = CaptureLogger::from_builder(HorizonLogger::builder().clock(clock.clone()));
+        logger.add_sink(FailingSink);
+        clock.set(Timestamp::from_millis(125_000));
+
+        let heartbeat = logger.start_heartbeat(Duration::from_millis(50), "HEARTBEAT");
+        logger.info("GAME", "tick");
+        logger.info("GAME", "tick");
+        while heartbeats(&logger).is_empty() {
+            thread::sleep(Duration::from_millis(5));
+        }
+        heartbeat.stop();
+
+        let after_stop = heartbeats(&logger);
+        assert!(after_stop[0].starts_with("heartbeat: up 2m 5s, 2 entries and 2 dropped"), "{}", after_stop[0]);
+        for later in &after_stop[1..] {
+            assert!(later.ends_with("0 entries and 0 dropped since last heartbeat"), "{}", later);
+        }
+
+        thread::sleep(Duration::from_millis(120));
+        assert_eq!(heartbeats(&logger).len(), after_stop.len());
+    }
+}
@@ -0,0 +1,1110 @@
+use crate::ansi;
+use crate::persist;
+use crate::pin::{PinError, Pins, DEFAULT_PIN_BUDGET};
+use crate::{HorizonLogger, LogEntry, LogLevel, Timestamp};
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+/// Number of entries kept in the log history unless `HorizonLogger::set_history_capacity` says otherwise
+pub(crate) const HISTORY_CAPACITY: usize = 1000;
+
+/// Number of independently locked history shards
+const SHARD_COUNT: usize = 16;
+
+/// Entries stored beyond the capacity before the oldest are evicted together
+const EVICT_SLACK: usize = 64;
+
+/// Round-robin source for assigning threads to shards
+static NEXT_SHARD: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    /// Shard used by the current thread
+    static SHARD: usize = NEXT_SHARD.fetch_add(1, Ordering::Relaxed) % SHARD_COUNT;
+}
+
+/// Bounded history of recent entries
+///
+/// Writers append to a per-thread shard so concurrent threads rarely touch
+/// the same lock; readers merge all shards by sequence number. The shards
+/// share one bound: once they hold `EVICT_SLACK` entries more than
+/// the capacity between them, the oldest are evicted from whichever
+/// shards hold them, so the newest capacity entries overall are
+/// always retained. Pinned entries are also kept in a separate store that
+/// eviction skips.
+///
+/// Entries are stored as `Arc<LogEntry>` and never changed in place: a
+/// dedup repeat or a pin swaps in an updated copy, so readers only clone
+/// the `Arc`s and a snapshot never changes after it is taken.
+pub struct History {
+    shards: Vec<Shard>,
+    /// Entries held by all shards together
+    stored: AtomicUsize,
+    /// Entries kept besides pinned ones, `HISTORY_CAPACITY` unless changed
+    capacity: AtomicUsize,
+    /// Held by the one thread evicting the oldest entries, see `evict_oldest`
+    evicting: Mutex<()>,
+    next_seq: AtomicU64,
+    /// Where the newest entry lives, tracked only when dedup is enabled
+    dedup: Option<Mutex<Option<Newest>>>,
+    /// Entries last logged longer ago than this are evicted
+    max_age: Option<Duration>,
+    pub(crate) pins: Pins,
+}
+
+/// The process-wide history, see `LoggerBuilder::global_history`
+pub(crate) fn global() -> Arc<History> {
+    static GLOBAL: OnceLock<Arc<History>> = OnceLock::new();
+    GLOBAL.get_or_init(|| Arc::new(History::new())).clone()
+}
+
+/// Location of the newest stored entry
+struct Newest {
+    shard: usize,
+    seq: u64,
+}
+
+/// One shard, padded so neighbouring locks don't share a cache line
+#[repr(align(128))]
+struct Shard(Mutex<VecDeque<Arc<LogEntry>>>);
+
+impl History {
+    pub fn new() -> Self {
+        History {
+            shards: (0..SHARD_COUNT).map(|_| Shard(Mutex::new(VecDeque::new()))).collect(),
+            stored: AtomicUsize::new(0),
+            capacity: AtomicUsize::new(HISTORY_CAPACITY),
+            evicting: Mutex::new(()),
+            next_seq: AtomicU64::new(0),
+            dedup: None,
+            max_age: None,
+            pins: Pins::new(DEFAULT_PIN_BUDGET, None),
+        }
+    }
+
+    /// History that folds an entry identical to the newest one into it
+    ///
+    /// Pushes are serialized while dedup is enabled.
+    pub(crate) fn with_dedup() -> Self {
+        History {
+            dedup: Some(Mutex::new(None)),
+            ..Self::new()
+        }
+    }
+
+    pub(crate) fn dedups(&self) -> bool {
+        self.dedup.is_some()
+    }
+
+    /// Number of entries kept besides pinned ones
+    pub(crate) fn capacity(&self) -> usize {
+        self.capacity.load(Ordering::Relaxed)
+    }
+
+    /// Keep the newest `capacity` entries from now on, evicting any beyond it at once
+    pub(crate) fn set_capacity(&self, capacity: usize) {
+        self.capacity.store(capacity, Ordering::Relaxed);
+        let _evicting = self.evicting.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        self.evict_beyond(capacity);
+    }
+
+    /// Also evict entries whose last occurrence is older than `max_age`
+    pub(crate) fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Keep up to `budget` pinned entries, pinning entries at or above `auto_level` as they are stored
+    pub(crate) fn with_pins(mut self, budget: usize, auto_level: Option<LogLevel>) -> Self {
+        self.pins = Pins::new(budget, auto_level);
+        self
+    }
+
+    /// Evict entries that are too old at `now`, if a maximum age is set
+    pub(crate) fn prune(&self, now: Timestamp) {
+        if self.max_age.is_none() {
+            return;
+        }
+        for shard in &self.shards {
+            if let Ok(mut entries) = shard.0.lock() {
+                self.evict_expired(&mut entries, now);
+            }
+        }
+    }
+
+    /// Drop expired entries from the front of one shard, which holds them in logging order
+    ///
+    /// Only the front is checked, so an entry stamped earlier than the one
+    /// before it (the clock stepped back) waits for that one to expire, and
+    /// the history never has a gap in its sequence numbers.
+    fn evict_expired(&self, entries: &mut VecDeque<Arc<LogEntry>>, now: Timestamp) {
+        let Some(max_age) = self.max_age else {
+            return;
+        };
+        while entries
+            .front()
+            .is_some_and(|oldest| now.duration_since(oldest.last_timestamp) > max_age)
+        {
+            entries.pop_front();
+            self.stored.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Assign the next sequence number and store the entry, evicting the oldest; returns the seq
+    pub fn push(&self, mut entry: LogEntry) -> u64 {
+        entry.seq = self.reserve_seq();
+        let seq = entry.seq;
+        self.store(entry);
+        seq
+    }
+
+    /// Take the next sequence number for an entry that will be stored later
+    pub(crate) fn reserve_seq(&self) -> u64 {
+        self.next_seq.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Store an entry numbered by `reserve_seq`, evicting the oldest
+    ///
+    /// Entries are stored after they have been printed and handed to sinks,
+    /// so the logger never has to copy them; the `Arc` is their one allocation.
+    pub(crate) fn store(&self, entry: LogEntry) {
+        let entry = self.prepare(entry);
+        let Some(newest) = &self.dedup else {
+            self.push_to_shard(entry);
+            return;
+        };
+        let Ok(mut newest) = newest.lock() else {
+            self.push_to_shard(entry);
+            return;
+        };
+
+        if let Some(current) = newest.as_mut() {
+            if self.fold_into(current, &entry) {
+                return;
+            }
+        }
+        let seq = entry.seq;
+        let shard = self.push_to_shard(entry);
+        *newest = Some(Newest { shard, seq });
+    }
+
+    /// `store` for entries numbered in order, taking the shard lock once unless repeats are folded
+    pub(crate) fn store_all(&self, entries: Vec<LogEntry>) {
+        if self.dedup.is_some() {
+            for entry in entries {
+                self.store(entry);
+            }
+            return;
+        }
+        let entries: Vec<_> = entries.into_iter().map(|entry| self.prepare(entry)).collect();
+        let shard = SHARD.with(|shard| *shard);
+        if let Ok(mut stored) = self.shards[shard].0.lock() {
+            let Some(now) = entries.last().map(|entry| entry.timestamp) else {
+                return;
+            };
+            self.stored.fetch_add(entries.len(), Ordering::Relaxed);
+            stored.extend(entries);
+            self.evict_expired(&mut stored, now);
+        }
+        self.evict_oldest();
+    }
+
+    /// Strip ANSI escapes from `entry` and pin it if it qualifies, ready to be stored
+    fn prepare(&self, mut entry: LogEntry) -> Arc<LogEntry> {
+        if let Cow::Owned(message) = ansi::strip(&entry.message) {
+            entry.message = message;
+            entry.ansi_stripped = true;
+        }
+        entry.pinned = self.pins.auto_pins(&entry);
+        let entry = Arc::new(entry);
+        if entry.pinned {
+            if let Some(released) = self.pins.insert(entry.clone()) {
+                self.mark(released, false);
+            }
+        }
+        entry
+    }
+
+    /// Fold `entry` into the newest entry if they match, returning whether it did
+    ///
+    /// The folded entry takes the new entry's seq so checkpoints still see the repeat.
+    /// Snapshots holding the stored entry keep the version they saw.
+    fn fold_into(&self, newest: &mut Newest, entry: &LogEntry) -> bool {
+        let Ok(mut entries) = self.shards[newest.shard].0.lock() else {
+            return false;
+        };
+        let Some(stored) = entries.back_mut().filter(|stored| {
+            stored.seq == newest.seq
+                && !stored.pinned
+                && !entry.pinned
+                && stored.level == entry.level
+                && stored.severity == entry.severity
+                && stored.code == entry.code
+                && stored.group_id == entry.group_id
+                && stored.component == entry.component
+                && stored.message == entry.message
+        }) else {
+            return false;
+        };
+
+        let stored = Arc::make_mut(stored);
+        stored.seq = entry.seq;
+        stored.repeat_count += 1;
+        stored.last_timestamp = entry.timestamp;
+        newest.seq = entry.seq;
+        true
+    }
+
+    /// Number and store an entry from an earlier session, without pinning or folding it
+    pub(crate) fn restore(&self, mut entry: LogEntry) {
+        entry.seq = self.reserve_seq();
+        self.push_to_shard(Arc::new(entry));
+    }
+
+    /// Append to the current thread's shard, returning the shard
+    fn push_to_shard(&self, entry: Arc<LogEntry>) -> usize {
+        let shard = SHARD.with(|shard| *shard);
+        if let Ok(mut entries) = self.shards[shard].0.lock() {
+            let now = entry.timestamp;
+            entries.push_back(entry);
+            self.stored.fetch_add(1, Ordering::Relaxed);
+            self.evict_expired(&mut entries, now);
+        }
+        self.evict_oldest();
+        shard
+    }
+
+    /// Evict the oldest entries across all shards down to the capacity, once `EVICT_SLACK` more are stored
+    ///
+    /// Called with no shard locked; shards are locked one at a time. Finds
+    /// the newest seq to evict among the seqs of every shard, then drops
+    /// everything up to it, so the cost is spread over `EVICT_SLACK`
+    /// entries. A shard can hold entries slightly out of seq order, when
+    /// threads sharing it reserved seqs in one order and stored in another,
+    /// so neither step relies on the order. A thread finding another
+    /// already evicting leaves it to them.
+    fn evict_oldest(&self) {
+        let capacity = self.capacity();
+        if self.stored.load(Ordering::Relaxed) <= capacity.saturating_add(EVICT_SLACK) {
+            return;
+        }
+        let Ok(_evicting) = self.evicting.try_lock() else {
+            return;
+        };
+        self.evict_beyond(capacity);
+    }
+
+    /// Evict all but the newest `capacity` entries, with `evicting` held
+    fn evict_beyond(&self, capacity: usize) {
+        let mut seqs: Vec<u64> = Vec::with_capacity(self.stored.load(Ordering::Relaxed));
+        for shard in &self.shards {
+            if let Ok(entries) = shard.0.lock() {
+                seqs.extend(entries.iter().map(|entry| entry.seq));
+            }
+        }
+        let excess = seqs.len().saturating_sub(capacity);
+        if excess == 0 {
+            return;
+        }
+        let cutoff = *seqs.select_nth_unstable(excess - 1).1;
+        for shard in &self.shards {
+            if let Ok(mut entries) = shard.0.lock() {
+                let before = entries.len();
+                entries.retain(|entry| entry.seq > cutoff);
+                self.stored.fetch_sub(before - entries.len(), Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Sequence number the next entry will receive
+    pub(crate) fn next_seq(&self) -> u64 {
+        self.next_seq.load(Ordering::Relaxed)
+    }
+
+    /// Pin the stored entry numbered `seq`
+    pub(crate) fn pin(&self, seq: u64) -> Result<(), PinError> {
+        if self.pins.contains(seq) {
+            return Ok(());
+        }
+        if self.pins.budget() == 0 {
+            return Err(PinError::NoBudget);
+        }
+        let entry = self.mark(seq, true).ok_or(PinError::NotFound(seq))?;
+        if let Some(released) = self.pins.insert(entry) {
+            self.mark(released, false);
+        }
+        Ok(())
+    }
+
+    /// Unpin the entry numbered `seq`, returning whether it was pinned
+    pub(crate) fn unpin(&self, seq: u64) -> bool {
+        let unpinned = self.pins.remove(seq);
+        if unpinned {
+            self.mark(seq, false);
+        }
+        unpinned
+    }
+
+    /// Replace the stored entry numbered `seq` with one with `pinned` set, returning it
+    fn mark(&self, seq: u64, pinned: bool) -> Option<Arc<LogEntry>> {
+        for shard in &self.shards {
+            let Ok(mut entries) = shard.0.lock() else {
+                continue;
+            };
+            // Threads sharing a shard can store out of sequence order, so no binary search
+            if let Some(i) = entries.iter().position(|e| e.seq == seq) {
+                if entries[i].pinned != pinned {
+                    Arc::make_mut(&mut entries[i]).pinned = pinned;
+                }
+                return Some(entries[i].clone());
+            }
+        }
+        None
+    }
+
+    /// The newest capacity entries and any pinned ones, oldest first
+    pub fn snapshot(&self) -> Vec<LogEntry> {
+        self.snapshot_shared().into_iter().map(Arc::unwrap_or_clone).collect()
+    }
+
+    /// `snapshot` without copying the entries
+    pub fn snapshot_shared(&self) -> Vec<Arc<LogEntry>> {
+        let mut merged = self.recent();
+        let pinned = self.pins.entries();
+        if pinned.is_empty() {
+            return merged;
+        }
+        // `recent` sorted these by seq, unlike the shards they came from
+        let evicted: Vec<_> = pinned
+            .into_iter()
+            .filter(|pin| merged.binary_search_by_key(&pin.seq, |e| e.seq).is_err())
+            .collect();
+        merged.extend(evicted);
+        order_by_seq(&mut merged);
+        merged
+    }
+
+    /// The newest capacity entries, oldest first
+    fn recent(&self) -> Vec<Arc<LogEntry>> {
+        let mut merged: Vec<Arc<LogEntry>> = Vec::new();
+        for shard in &self.shards {
+            if let Ok(entries) = shard.0.lock() {
+                merged.extend(entries.iter().cloned());
+            }
+        }
+
+        order_by_seq(&mut merged);
+        let excess = merged.len().saturating_sub(self.capacity());
+        merged.drain(..excess);
+        merged
+    }
+
+    /// Entries with `seq >= from`, or an error if some of them were evicted
+    fn since(&self, from: u64) -> Result<Vec<Arc<LogEntry>>, HistoryOverflow> {
+        // Pinned entries outlive the ones around them, so they don't count
+        let entries = self.recent();
+        let oldest = entries.first().map_or(self.next_seq(), |e| e.seq);
+        if oldest > from {
+            return Err(HistoryOverflow {
+                evicted: oldest - from,
+            });
+        }
+
+        Ok(entries.into_iter().filter(|e| e.seq >= from).collect())
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Put entries in history order, by `seq` and then run, dropping any entry that appears twice
+///
+/// Timestamps play no part, so a wall clock stepping backwards can't
+/// reorder the history. Entries loaded from an earlier session are
+/// renumbered, so the run only decides between entries that share a seq.
+fn order_by_seq(entries: &mut Vec<Arc<LogEntry>>) {
+    entries.sort_unstable_by(|a, b| a.seq.cmp(&b.seq).then_with(|| a.run_id.cmp(&b.run_id)));
+    entries.dedup_by(|a, b| a.seq == b.seq && a.run_id == b.run_id);
+}
+
+/// Hierarchical component match: `NETWORK` matches `NETWORK` and `NETWORK/WEBSOCKET`
+pub(crate) fn component_matches(component: &str, prefix: &str) -> bool {
+    prefix.is_empty()
+        || component
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// `component_matches`, ignoring ASCII case on both sides
+pub(crate) fn component_matches_ignore_case(component: &str, prefix: &str) -> bool {
+    prefix.is_empty()
+        || component
+            .get(..prefix.len())
+            .is_some_and(|head| head.eq_ignore_ascii_case(prefix))
+            && component[prefix.len()..].chars().next().is_none_or(|c| c == '/')
+}
+
+/// Filter over the history; unset conditions match everything
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HistoryQuery {
+    /// Only entries at or above this level
+    pub min_level: Option<LogLevel>,
+    /// Only entries with at least this `LogEntry::severity`
+    pub min_severity: Option<u8>,
+    /// Only entries under this component (see `history_by_component`)
+    pub component: Option<String>,
+    /// Only entries with this `LogEntry::code`
+    pub code: Option<String>,
+    /// Only entries whose message contains this text
+    pub contains: Option<String>,
+    /// Only entries loaded from the previous session (`true`) or logged in this one (`false`)
+    pub previous_session: Option<bool>,
+    /// Keep only the newest `tail` matches
+    pub tail: Option<usize>,
+}
+
+impl HistoryQuery {
+    /// Query matching every entry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only entries at or above `level`
+    pub fn min_level(mut self, level: LogLevel) -> Self {
+        self.min_level = Some(level);
+        self
+    }
+
+    /// Only entries with a severity of at least `severity`
+    pub fn min_severity(mut self, severity: u8) -> Self {
+        self.min_severity = Some(severity);
+        self
+    }
+
+    /// Only entries under component `prefix`
+    pub fn component(mut self, prefix: &str) -> Self {
+        self.component = Some(prefix.to_string());
+        self
+    }
+
+    /// Only entries with event code `code`
+    pub fn code(mut self, code: &str) -> Self {
+        self.code = Some(code.to_string());
+        self
+    }
+
+    /// Only entries whose message contains `text`
+    pub fn contains(mut self, text: &str) -> Self {
+        self.contains = Some(text.to_string());
+        self
+    }
+
+    /// Only entries loaded by `persist_history` from the previous session, or only this session's
+    pub fn previous_session(mut self, previous: bool) -> Self {
+        self.previous_session = Some(previous);
+        self
+    }
+
+    /// Keep only the newest `count` matches
+    pub fn tail(mut self, count: usize) -> Self {
+        self.tail = Some(count);
+        self
+    }
+
+    /// Whether one entry passes the filters (`tail` is applied separately)
+    pub fn matches(&self, entry: &LogEntry) -> bool {
+        self.min_level.is_none_or(|min| entry.level >= min)
+            && self.min_severity.is_none_or(|min| entry.severity >= min)
+            && self
+                .component
+                .as_deref()
+                .is_none_or(|prefix| component_matches(&entry.component, prefix))
+            && self.code.as_deref().is_none_or(|code| entry.code.as_deref() == Some(code))
+            && self
+                .contains
+                .as_deref()
+                .is_none_or(|text| entry.message.contains(text))
+            && self
+                .previous_session
+                .is_none_or(|previous| persist::is_previous_session(entry) == previous)
+    }
+}
+
+/// Marker for a point in a logger's history
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint {
+    seq: u64,
+}
+
+impl Checkpoint {
+    /// Sequence number the next entry after this checkpoint receives
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+}
+
+/// Matched history entries with the entries around them, see `history_context`
+#[derive(Debug, Clone)]
+pub struct ContextBlock {
+    /// The matches and their context, oldest first
+    pub entries: Vec<LogEntry>,
+    /// Sequence numbers of the entries in `entries` that matched
+    pub matched: Vec<u64>,
+    /// Some of the context asked for had already been evicted
+    pub context_evicted: bool,
+}
+
+/// Entries logged after a checkpoint were evicted before they could be read
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryOverflow {
+    /// Number of entries lost since the checkpoint
+    pub evicted: u64,
+}
+
+impl fmt::Display for HistoryOverflow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "history overflow, cannot verify: {} entries evicted since checkpoint",
+            self.evicted
+        )
+    }
+}
+
+impl std::error::Error for HistoryOverflow {}
+
+impl HorizonLogger {
+    /// Keep the newest `capacity` entries in the history from now on (1000 by default)
+    ///
+    /// Shrinking evicts the oldest entries at once; pinned ones stay either
+    /// way. A global history is shared, so every logger sharing it changes.
+    pub fn set_history_capacity(&self, capacity: usize) {
+        self.inner.history.set_capacity(capacity);
+    }
+
+    /// Number of entries the history keeps, see `set_history_capacity`
+    pub fn history_capacity(&self) -> usize {
+        self.inner.history.capacity()
+    }
+
+    /// Mark the current end of the history
+    pub fn history_checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            seq: self.inner.history.next_seq(),
+        }
+    }
+
+    /// Entries whose component is `prefix` or one of its `/`-separated children
+    ///
+    /// An empty prefix matches every entry, including component-less ones;
+    /// component-less entries match nothing else.
+    pub fn history_by_component(&self, prefix: &str) -> Vec<LogEntry> {
+        self.query_history(&HistoryQuery::new().component(prefix))
+    }
+
+    /// Entries logged with event code `code`, oldest first
+    pub fn history_by_code(&self, code: &str) -> Vec<LogEntry> {
+        self.query_history(&HistoryQuery::new().code(code))
+    }
+
+    /// Entries matching every condition of `query`, oldest first
+    pub fn query_history(&self, query: &HistoryQuery) -> Vec<LogEntry> {
+        self.query_history_shared(query).into_iter().map(Arc::unwrap_or_clone).collect()
+    }
+
+    /// `query_history` without copying the entries, see `get_history_shared`
+    pub fn query_history_shared(&self, query: &HistoryQuery) -> Vec<Arc<LogEntry>> {
+        let mut entries: Vec<_> = self
+            .get_history_shared()
+            .into_iter()
+            .filter(|e| query.matches(e))
+            .collect();
+
+        if let Some(tail) = query.tail {
+            let excess = entries.len().saturating_sub(tail);
+            entries.drain(..excess);
+        }
+        entries
+    }
+
+    /// Entries matching `predicate`, each with up to `before` entries before it and `after` after it
+    ///
+    /// Context only runs over entries logged one after another: it stops
+    /// where entries are missing, such as before a pinned entry that outlived
+    /// its neighbours, and the block is then marked `context_evicted`.
+    /// Matches whose context overlaps or touches share one block, so no
+    /// entry appears twice. Blocks are oldest first.
+    pub fn history_context(&self, predicate: impl Fn(&LogEntry) -> bool, before: usize, after: usize) -> Vec<ContextBlock> {
+        let entries = self.get_history_shared();
+        // The seq of an entry's first occurrence, for entries that folded repeats
+        let first_seq = |entry: &LogEntry| entry.seq.saturating_sub(u64::from(entry.repeat_count.saturating_sub(1)));
+        // Whether the entry at `i` was logged right after the one before it
+        let follows = |i: usize| i > 0 && first_seq(&entries[i]) == entries[i - 1].seq + 1;
+
+        let mut ranges: Vec<(usize, usize, Vec<usize>, bool)> = Vec::new();
+        for (i, entry) in entries.iter().enumerate() {
+            if !predicate(entry) {
+                continue;
+            }
+            let mut start = i;
+            while start > i.saturating_sub(before) && follows(start) {
+                start -= 1;
+            }
+            let mut end = i;
+            while end < i + after && end + 1 < entries.len() && follows(end + 1) {
+                end += 1;
+            }
+            // Short of what was asked for with earlier entries missing, or cut off by a gap after
+            let evicted = (i - start < before && first_seq(&entries[start]) > 0)
+                || (end - i < after && end + 1 < entries.len());
+            match ranges.last_mut() {
+                Some((_, last_end, matched, last_evicted))
+                    if start <= *last_end || (start == *last_end + 1 && follows(start)) =>
+                {
+                    *last_end = end;
+                    *last_evicted |= evicted;
+                    matched.push(i);
+                }
+                _ => ranges.push((start, end, vec![i], evicted)),
+            }
+        }
+
+        ranges
+            .into_iter()
+            .map(|(start, end, matched, context_evicted)| ContextBlock {
+                context_evicted,
+                matched: matched.iter().map(|&i| entries[i].seq).collect(),
+                entries: entries[start..=end].iter().map(|e| LogEntry::clone(e)).collect(),
+            })
+            .collect()
+    }
+
+    /// `history_context` for every entry at or above `level`
+    pub fn history_context_at_level(&self, level: LogLevel, before: usize, after: usize) -> Vec<ContextBlock> {
+        self.history_context(|entry| entry.level >= level, before, after)
+    }
+
+    /// Entries logged since `checkpoint` that are still in the history
+    pub fn entries_since(&self, checkpoint: &Checkpoint) -> Vec<LogEntry> {
+        self.try_entries_since(checkpoint).unwrap_or_else(|_| {
+            self.get_history_shared()
+                .into_iter()
+                .filter(|e| e.seq >= checkpoint.seq)
+                .map(Arc::unwrap_or_clone)
+                .collect()
+        })
+    }
+
+    /// Entries logged since `checkpoint`, failing if any were already evicted
+    pub fn try_entries_since(&self, checkpoint: &Checkpoint) -> Result<Vec<LogEntry>, HistoryOverflow> {
+        self.prune_history_now();
+        let entries = self.inner.history.since(checkpoint.seq)?;
+        Ok(entries.into_iter().map(Arc::unwrap_or_clone).collect())
+    }
+
+    /// Evict history entries older than `history_max_age` right away
+    ///
+    /// Reads already prune before returning; this frees the memory of an
+    /// idle logger at a time of the caller's choosing.
+    pub fn prune_history_now(&self) {
+        self.inner.history.prune(self.inner.clock.now());
+    }
+
+    /// Panic if anything at or above `level` was logged since `checkpoint`
+    ///
+    /// Also panics when entries were evicted in the meantime, since the
+    /// assertion can no longer be verified.
+    pub fn assert_no_entries_at_or_above(&self, checkpoint: &Checkpoint, level: LogLevel) {
+        let entries = match self.try_entries_since(checkpoint) {
+            Ok(entries) => entries,
+            Err(overflow) => panic!("{}", overflow),
+        };
+
+        let offending: Vec<String> = entries
+            .iter()
+            .filter(|e| e.level >= level)
+            .map(|e| format!("  #{} {}", e.seq, e))
+            .collect();
+
+        if !offending.is_empty() {
+            panic!(
+                "{} entries at or above {} since checkpoint:\n{}",
+                offending.len(),
+                level.as_str(),
+                offending.join("\n")
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::testing::fixtures::CountingSink;
+    use crate::testing::CaptureLogger;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_entries_since_checkpoint() {
+        let logger = CaptureLogger::new();
+        logger.warn("TEST", "before");
+
+        let checkpoint = logger.history_checkpoint();
+        logger.info("TEST", "after");
+
+        let entries = logger.entries_since(&checkpoint);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].message, "after");
+        assert_eq!(entries[0].seq, checkpoint.seq());
+        logger.assert_no_entries_at_or_above(&checkpoint, LogLevel::WARN);
+    }
+
+    #[test]
+    #[should_panic(expected = "1 entries at or above ERROR since checkpoint:\n  #1 ")]
+    fn test_assert_lists_offending_entries() {
+        let logger = CaptureLogger::new();
+        logger.info("TEST", "before");
+        let checkpoint = logger.history_checkpoint();
+        logger.error("TEST", "boom");
+
+        logger.assert_no_entries_at_or_above(&checkpoint, LogLevel::ERROR);
+    }
+
+    #[test]
+    fn test_context_blocks() {
+        let logger = CaptureLogger::new();
+        for i in 0..30 {
+            match i {
+                5 | 12 | 14 | 27 => logger.error("GAME", &i.to_string()),
+                _ => logger.info("GAME", &i.to_string()),
+            }
+        }
+
+        let blocks = logger.history_context_at_level(LogLevel::ERROR, 3, 2);
+        let summary: Vec<_> = blocks
+            .iter()
+            .map(|b| (b.entries.first().unwrap().seq, b.entries.last().unwrap().seq, b.matched.clone(), b.context_evicted))
+            .collect();
+        // 12 and 14 share context; 27 runs out of entries after it
+        assert_eq!(summary, [(2, 7, vec![5], false), (9, 16, vec![12, 14], false), (24, 29, vec![27], false)]);
+        assert!(blocks[1].entries.windows(2).all(|pair| pair[1].seq == pair[0].seq + 1));
+
+        // Blocks that only touch are merged too
+        let touching = logger.history_context(|e| e.message == "5" || e.message == "9", 1, 2);
+        assert_eq!(touching.len(), 1);
+        assert_eq!((touching[0].entries.len(), touching[0].matched.clone()), (8, vec![5, 9]));
+
+        // Context at the start of a history that never evicted is complete
+        assert!(!logger.history_context(|e| e.seq == 1, 5, 0)[0].context_evicted);
+    }
+
+    #[test]
+    fn test_context_evicted() {
+        let logger = CaptureLogger::new();
+        for i in 0..HISTORY_CAPACITY + 10 {
+            logger.info("GAME", &i.to_string());
+        }
+
+        let blocks = logger.history_context(|e| e.seq == 12 || e.seq == 500, 5, 1);
+        assert_eq!(blocks.len(), 2);
+        assert!(blocks[0].context_evicted);
+        assert_eq!(blocks[0].entries[0].seq, 10);
+        assert!(!blocks[1].context_evicted);
+    }
+
+    #[test]
+    fn test_context_stops_at_gaps() {
+        let logger = CaptureLogger::from_builder(HorizonLogger::builder().auto_pin(LogLevel::ERROR, 4));
+        logger.info("GAME", "before error");
+        logger.error("GAME", "pinned error");
+        for i in 0..HISTORY_CAPACITY + 10 {
+            logger.info("GAME", &i.to_string());
+        }
+        logger.error("GAME", "recent error");
+
+        let blocks = logger.history_context_at_level(LogLevel::ERROR, 2, 3);
+        assert_eq!(blocks.len(), 2);
+        // The pinned error's neighbours were evicted; the live entries much later are not its context
+        assert_eq!(blocks[0].entries.len(), 1);
+        assert_eq!((blocks[0].matched.clone(), blocks[0].context_evicted), (vec![1], true));
+        let recent: Vec<&str> = blocks[1].entries.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(recent, ["1008", "1009", "recent error"]);
+        assert!(!blocks[1].context_evicted);
+
+        // Folded repeats are not gaps
+        let logger = CaptureLogger::from_builder(HorizonLogger::builder().dedup_history(true));
+        for message in ["a", "b", "b", "b", "c"] {
+            logger.info("GAME", message);
+        }
+        let blocks = logger.history_context(|e| e.message == "c", 2, 0);
+        assert_eq!(blocks[0].entries.len(), 3);
+        assert!(!blocks[0].context_evicted);
+    }
+
+    #[test]
+    #[should_panic(expected = "history overflow, cannot verify")]
+    fn test_assert_fails_on_overflow() {
+        let logger = CaptureLogger::new();
+        let checkpoint = logger.history_checkpoint();
+        for i in 0..HISTORY_CAPACITY + 1 {
+            logger.debug("TEST", &i.to_string());
+        }
+
+        assert!(logger.try_entries_since(&checkpoint).is_err());
+        logger.assert_no_entries_at_or_above(&checkpoint, LogLevel::ERROR);
+    }
+
+    #[test]
+    fn test_history_by_component_prefix() {
+        let logger = CaptureLogger::new();
+        logger.info("NETWORK", "a");
+        logger.info("NETWORK/WEBSOCKET", "b");
+        logger.info("NETWORKING", "c");
+        logger.info_msg("d");
+        crate::log_info!(logger, "e");
+
+        let messages = |prefix| -> Vec<String> {
+            logger.history_by_component(prefix).into_iter().map(|e| e.message).collect()
+        };
+        assert_eq!(messages("NETWORK"), vec!["a", "b"]);
+        assert_eq!(messages("NETWORK/WEBSOCKET"), vec!["b"]);
+        assert_eq!(messages(""), vec!["a", "b", "c", "d", "e"]);
+        assert_eq!(logger.get_history()[3].component, "");
+        assert_eq!(logger.get_history()[3].to_string().matches('[').count(), 0);
+    }
+
+    #[test]
+    fn test_query_history() {
+        let logger = CaptureLogger::new();
+        logger.info("NETWORK", "connected 1");
+        logger.warn("NETWORK", "connected 2");
+        logger.warn("NETWORK/UDP", "connected 3");
+        logger.warn("GAME", "connected 4");
+        logger.error("NETWORK", "dropped");
+
+        let query = HistoryQuery::new()
+            .min_level(LogLevel::WARN)
+            .component("NETWORK")
+            .contains("connected")
+            .tail(1);
+        let messages: Vec<String> = logger.query_history(&query).into_iter().map(|e| e.message).collect();
+        assert_eq!(messages, vec!["connected 3"]);
+    }
+
+    #[test]
+    fn test_concurrent_writers_merge_by_seq() {
+        let logger = CaptureLogger::new();
+
+        let threads: Vec<_> = (0..8)
+            .map(|t| {
+                let logger = logger.clone();
+                std::thread::spawn(move || {
+                    for i in 0..200 {
+                        logger.debug("TEST", &format!("{}-{}", t, i));
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let history = logger.get_history();
+        assert_eq!(history.len(), HISTORY_CAPACITY);
+        let seqs: Vec<u64> = history.iter().map(|e| e.seq).collect();
+        let expected: Vec<u64> = (600..1600).collect();
+        assert_eq!(seqs, expected);
+    }
+
+    #[test]
+    fn test_new_loggers_share_the_global_history() {
+        let a = HorizonLogger::new();
+        let b = HorizonLogger::new();
+        let own = HorizonLogger::builder().build();
+        a.info("TEST", "logged through a");
+        own.info("TEST", "logged through own");
+
+        let has = |logger: &HorizonLogger, message: &str| logger.get_history().iter().any(|e| e.message == message);
+        assert!(has(&a, "logged through a") && has(&b, "logged through a"));
+        assert!(!has(&own, "logged through a"));
+        assert!(!has(&b, "logged through own"));
+    }
+
+    #[test]
+    fn test_shards_share_one_bound() {
+        let history = Arc::new(History::new());
+        // More threads than shards, so every shard gets entries
+        let threads: Vec<_> = (0..SHARD_COUNT * 2)
+            .map(|t| {
+                let history = history.clone();
+                std::thread::spawn(move || {
+                    for i in 0..400 {
+                        history.push(LogEntry::new(LogLevel::INFO, "TEST", &format!("{}-{}", t, i)));
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        history.push(LogEntry::new(LogLevel::INFO, "TEST", "last"));
+
+        let retained: usize = history.shards.iter().map(|shard| shard.0.lock().unwrap().len()).sum();
+        assert!(retained <= HISTORY_CAPACITY + EVICT_SLACK, "{} entries retained", retained);
+        assert_eq!(retained, history.stored.load(Ordering::Relaxed));
+        let total = (SHARD_COUNT * 2 * 400 + 1) as u64;
+        let seqs: Vec<u64> = history.snapshot_shared().iter().map(|e| e.seq).collect();
+        assert_eq!(seqs, (total - HISTORY_CAPACITY as u64..total).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_pin_every_entry_from_shared_shards() {
+        let budget = SHARD_COUNT * 2 * 30;
+        let history = Arc::new(History::new().with_pins(budget, None));
+        // Entries are stored out of sequence order, as when threads sharing a shard race
+        let threads: Vec<_> = (0..SHARD_COUNT * 2)
+            .map(|t| {
+                let history = history.clone();
+                std::thread::spawn(move || {
+                    for i in 0..15 {
+                        let seqs = [history.reserve_seq(), history.reserve_seq()];
+                        for seq in seqs.into_iter().rev() {
+                            let mut entry = LogEntry::new(LogLevel::INFO, "TEST", &format!("{}-{}", t, i));
+                            entry.seq = seq;
+                            history.store(entry);
+                        }
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        for seq in 0..budget as u64 {
+            assert_eq!(history.pin(seq), Ok(()), "seq {}", seq);
+        }
+        let snapshot = history.snapshot_shared();
+        assert_eq!(snapshot.len(), budget);
+        assert!(snapshot.iter().all(|entry| entry.pinned));
+    }
+
+    fn dedup_logger(clock: &Arc<ManualClock>) -> CaptureLogger {
+        CaptureLogger::from_builder(HorizonLogger::builder().clock(clock.clone()).dedup_history(true))
+    }
+
+    #[test]
+    fn test_dedup_counts_repeats() {
+        let clock = Arc::new(ManualClock::new(crate::Timestamp::from_millis(1_000)));
+        let logger = dedup_logger(&clock);
+        let sink = Arc::new(CountingSink::default());
+        logger.add_sink(sink.clone());
+
+        let checkpoint = logger.history_checkpoint();
+        for _ in 0..3 {
+            logger.warn("NETWORK", "connection retry failed");
+            clock.advance(Duration::from_millis(500));
+        }
+
+        let history = logger.entries();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].repeat_count, 3);
+        assert_eq!(history[0].timestamp.as_millis(), 1_000);
+        assert_eq!(history[0].last_timestamp.as_millis(), 2_000);
+        assert_eq!(sink.count(), 3);
+        assert_eq!(logger.entries_since(&checkpoint).len(), 1);
+
+        let json = logger.format_entry(&history[0], crate::Format::Json);
+        assert!(json.contains(r#""repeat_count":3,"last_timestamp":"1970-01-01T00:00:02.000000Z""#), "{}", json);
+    }
+
+    #[test]
+    fn test_shared_snapshots_never_change() {
+        let clock = Arc::new(ManualClock::new(crate::Timestamp::from_millis(1_000)));
+        let logger = dedup_logger(&clock);
+        logger.warn("NETWORK", "connection retry failed");
+
+        let before = logger.get_history_shared();
+        assert!(Arc::ptr_eq(&before[0], &logger.get_history_shared()[0]));
+
+        logger.warn("NETWORK", "connection retry failed");
+        logger.pin_entry(before[0].seq + 1).unwrap();
+        let after = logger.get_history_shared();
+        assert_eq!((before[0].repeat_count, before[0].pinned), (1, false));
+        assert_eq!((after[0].repeat_count, after[0].pinned), (2, true));
+        assert_eq!(logger.query_history_shared(&HistoryQuery::new().component("NETWORK")).len(), 1);
+    }
+
+    #[test]
+    fn test_dedup_resets_on_different_entry() {
+        let clock = Arc::new(ManualClock::new(crate::Timestamp::now()));
+        let logger = dedup_logger(&clock);
+
+        logger.warn("NETWORK", "retry");
+        logger.warn("NETWORK", "retry");
+        logger.info("NETWORK", "retry");
+        logger.warn("NETWORK", "retry");
+        logger.warn("GAME", "retry");
+
+        let counts: Vec<u32> = logger.entries().iter().map(|e| e.repeat_count).collect();
+        assert_eq!(counts, vec![2, 1, 1, 1]);
+        let seqs: Vec<u64> = logger.entries().iter().map(|e| e.seq).collect();
+        assert_eq!(seqs, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_dedup_with_eviction() {
+        let clock = Arc::new(ManualClock::new(crate::Timestamp::now()));
+        let logger = dedup_logger(&clock);
+
+        for i in 0..HISTORY_CAPACITY {
+            logger.info("TEST", &format!("entry {}", i));
+        }
+        // Repeats of the newest entry don't push anything out
+        for _ in 0..10 {
+            logger.info("TEST", &format!("entry {}", HISTORY_CAPACITY - 1));
+        }
+        let history = logger.entries();
+        assert_eq!(history.len(), HISTORY_CAPACITY);
+        assert_eq!(history[0].message, "entry 0");
+        assert_eq!(history.last().unwrap().repeat_count, 11);
+
+        logger.info("TEST", "new");
+        let history = logger.entries();
+        assert_eq!(history.len(), HISTORY_CAPACITY);
+        assert_eq!(history[0].message, "entry 1");
+        assert_eq!(history[HISTORY_CAPACITY - 2].repeat_count, 11);
+    }
+
+    #[test]
+    fn test_history_max_age() {
+        let clock = Arc::new(ManualClock::new(Timestamp::from_millis(0)));
+        let logger = CaptureLogger::from_builder(
+            HorizonLogger::builder()
+                .clock(clock.clone())
+                .history_max_age(Duration::from_secs(900)),
+        );
+        logger.info("PLAYER", "joined");
+        clock.advance(Duration::from_secs(600));
+        logger.info("PLAYER", "moved");
+
+        clock.advance(Duration::from_secs(301));
+        logger.prune_history_now();
+        let stored: Vec<_> = logger.inner.history.snapshot().into_iter().map(|e| e.message).collect();
+        assert_eq!(stored, vec!["moved"]);
+
+        // Reads prune too, without anything new being logged
+        clock.advance(Duration::from_secs(600));
+        assert!(logger.get_history().is_empty());
+        let checkpoint = Checkpoint { seq: 1 };
+        assert_eq!(logger.try_entries_since(&checkpoint).unwrap_err().evicted, 1);
+    }
+}
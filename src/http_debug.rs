@@ -0,0 +1,316 @@
+//! Minimal HTTP endpoint for browsing the history during playtests
+//!
+//! Serves `GET /logs` with optional query parameters `level` (minimum
+//! level), `component` (prefix), `q` (message substring) and `tail`
+//! (newest N). The response is JSON for `Accept: application/json`, plain
+//! text for `Accept: text/plain`, and an HTML table otherwise.
+//!
+//! Requests are handled one at a time on a single background thread that
+//! only reads history snapshots, so it never slows down logging.
+
+use crate::escape::escape_html;
+use crate::format::Format;
+use crate::{HistoryQuery, HorizonLogger, LogEntry};
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// How often the accept loop checks for shutdown
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Time allowed for a client to send its request
+const READ_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Largest request head accepted
+const MAX_REQUEST_BYTES: usize = 8192;
+
+/// Running debug server; stops when dropped
+pub struct DebugServerHandle {
+    addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl DebugServerHandle {
+    /// Address the server is listening on
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Stop accepting connections and wait for the server thread
+    pub fn shutdown(mut self) {
+        self.stop_and_join();
+    }
+
+    fn stop_and_join(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for DebugServerHandle {
+    fn drop(&mut self) {
+        self.stop_and_join();
+    }
+}
+
+impl HorizonLogger {
+    /// Serve the history over HTTP at `addr`
+    pub fn serve_debug(&self, addr: SocketAddr) -> io::Result<DebugServerHandle> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        let addr = listener.local_addr()?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let logger = self.clone();
+        let thread_stop = stop.clone();
+
+        let thread = thread::Builder::new()
+            .name("horizon-debug-http".into())
+            .spawn(move || {
+                while !thread_stop.load(Ordering::Relaxed) {
+                    match listener.accept() {
+                        Ok((stream, _)) => {
+                            let _ = handle_connection(&logger, stream);
+                        }
+                        Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                            thread::sleep(POLL_INTERVAL);
+                        }
+                        Err(_) => thread::sleep(POLL_INTERVAL),
+                    }
+                }
+            })?;
+
+        Ok(DebugServerHandle {
+            addr,
+            stop,
+            thread: Some(thread),
+        })
+    }
+}
+
+/// Response body flavours
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Flavour {
+    Html,
+    Json,
+    Text,
+}
+
+fn handle_connection(logger: &HorizonLogger, mut stream: TcpStream) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+
+    let head = read_head(&mut stream)?;
+    let mut lines = head.lines();
+    let request_line = lines.next().unwrap_or_default();
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = (parts.next().unwrap_or_default(), parts.next().unwrap_or_default());
+
+    let accept = lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("accept"))
+        .map(|(_, value)| value.trim().to_ascii_lowercase())
+        .unwrap_or_default();
+
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    if method != "GET" {
+        return respond(&mut stream, "405 Method Not Allowed", "text/plain", "method not allowed\n", logger.run_id());
+    }
+    if path != "/logs" {
+        return respond(&mut stream, "404 Not Found", "text/plain", "not found\n", logger.run_id());
+    }
+
+    let query = match parse_query(query) {
+        Ok(query) => query,
+        Err(message) => return respond(&mut stream, "400 Bad Request", "text/plain", &message, logger.run_id()),
+    };
+    let entries = logger.query_history_shared(&query);
+
+    let flavour = if accept.contains("application/json") {
+        Flavour::Json
+    } else if accept.contains("text/plain") {
+        Flavour::Text
+    } else {
+        Flavour::Html
+    };
+
+    let (content_type, body) = match flavour {
+        Flavour::Json => ("application/json", render_json(logger, &entries)),
+        Flavour::Text => ("text/plain; charset=utf-8", render_text(&entries)),
+        Flavour::Html => ("text/html; charset=utf-8", render_html(logger.run_id(), &entries)),
+    };
+    respond(&mut stream, "200 OK", content_type, &body, logger.run_id())
+}
+
+/// Read up to the blank line ending the request head
+fn read_head(stream: &mut TcpStream) -> io::Result<String> {
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") && head.len() < MAX_REQUEST_BYTES {
+        let n = stream.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        head.extend_from_slice(&buf[..n]);
+    }
+    Ok(String::from_utf8_lossy(&head).into_owned())
+}
+
+fn respond(stream: &mut TcpStream, status: &str, content_type: &str, body: &str, run_id: &str) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nX-Run-Id: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        run_id,
+        body
+    )?;
+    stream.flush()
+}
+
+/// Map URL parameters onto a history query
+fn parse_query(query: &str) -> Result<HistoryQuery, String> {
+    let mut result = HistoryQuery::new();
+
+    for pair in query.split('&').filter(|p| !p.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let value = percent_decode(value);
+        match key {
+            "level" => {
+                result.min_level = Some(value.parse().map_err(|e| format!("{}\n", e))?);
+            }
+            "component" => result.component = Some(value),
+            "q" => result.contains = Some(value),
+            "tail" => {
+                result.tail = Some(value.parse().map_err(|_| format!("invalid tail `{}`\n", value))?);
+            }
+            _ => {}
+        }
+    }
+
+    Ok(result)
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                match u8::from_str_radix(value.get(i + 1..i + 3).unwrap_or(""), 16) {
+                    Ok(byte) => {
+                        out.push(byte);
+                        i += 2;
+                    }
+                    Err(_) => out.push(b'%'),
+                }
+            }
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn render_json(logger: &HorizonLogger, entries: &[Arc<LogEntry>]) -> String {
+    let items: Vec<String> = entries
+        .iter()
+        .map(|e| logger.format_entry(e, Format::Json))
+        .collect();
+    format!("[{}]", items.join(","))
+}
+
+fn render_text(entries: &[Arc<LogEntry>]) -> String {
+    entries.iter().map(|e| format!("{}\n", e)).collect()
+}
+
+fn render_html(run_id: &str, entries: &[Arc<LogEntry>]) -> String {
+    let mut html = format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>Horizon logs - run {run}</title></head><body>\
+         <h1>Run {run}</h1>\
+         <table border=\"1\"><tr><th>Seq</th><th>Time</th><th>Level</th><th>Component</th><th>Message</th></tr>",
+        run = escape_html(run_id)
+    );
+    for e in entries {
+        html.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            e.seq,
+            crate::format::human_time(&e.timestamp),
+            escape_html(e.level.as_str()),
+            escape_html(&e.component),
+            escape_html(&e.message)
+        ));
+    }
+    html.push_str("</table></body></html>");
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::CaptureLogger;
+    use crate::LogLevel;
+
+    fn get(addr: SocketAddr, target: &str, accept: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: x\r\nAccept: {}\r\n\r\n", target, accept).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    fn body(response: &str) -> &str {
+        response.split_once("\r\n\r\n").unwrap().1
+    }
+
+    #[test]
+    fn test_parse_query() {
+        let query = parse_query("level=warn&component=GAME%2FCOMBAT&q=hit+roll&tail=5").unwrap();
+        assert_eq!(
+            query,
+            HistoryQuery::new()
+                .min_level(LogLevel::WARN)
+                .component("GAME/COMBAT")
+                .contains("hit roll")
+                .tail(5)
+        );
+        assert!(parse_query("tail=lots").is_err());
+    }
+
+    #[test]
+    fn test_serves_filtered_history() {
+        let logger = CaptureLogger::new();
+        logger.info("NETWORK", "connected");
+        logger.warn("NETWORK", "lag <spike>");
+        logger.warn("GAME", "slow tick");
+
+        let server = logger.serve_debug("127.0.0.1:0".parse().unwrap()).unwrap();
+        let addr = server.local_addr();
+
+        let json = get(addr, "/logs?level=warn&component=NETWORK", "application/json");
+        assert!(json.starts_with("HTTP/1.1 200 OK"));
+        let parsed: serde_json::Value = serde_json::from_str(body(&json)).unwrap();
+        assert_eq!(parsed.as_array().unwrap().len(), 1);
+        assert_eq!(parsed[0]["message"], "lag <spike>");
+
+        let text = get(addr, "/logs?tail=1", "text/plain");
+        assert!(body(&text).ends_with("[GAME] slow tick\n"));
+
+        let html = get(addr, "/logs?q=spike", "text/html");
+        assert!(body(&html).contains("<td>lag &lt;spike&gt;</td>"));
+
+        assert!(get(addr, "/other", "").starts_with("HTTP/1.1 404"));
+
+        server.shutdown();
+        assert!(TcpStream::connect(addr).is_err());
+    }
+}
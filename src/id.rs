@@ -0,0 +1,112 @@
+use crate::correlation::CorrelationGuard;
+use crate::HorizonLogger;
+use std::cell::Cell;
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+
+/// Source of the identifiers the logger makes up: run ids and new correlation ids
+///
+/// Swap in `testing::SequentialIdGenerator` for stable ids in snapshot tests
+/// or replays, or implement it over an approved random source. It is called
+/// from any thread, so keep it cheap.
+pub trait IdGenerator: Send + Sync {
+    /// A fresh identifier
+    fn next_id(&self) -> String;
+}
+
+/// 8 hex digits from a per-thread random generator, seeded randomly for each thread
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RandomIdGenerator;
+
+impl IdGenerator for RandomIdGenerator {
+    fn next_id(&self) -> String {
+        format!("{:08x}", random_u64() >> 32)
+    }
+}
+
+impl<G: IdGenerator + ?Sized> IdGenerator for std::sync::Arc<G> {
+    fn next_id(&self) -> String {
+        (**self).next_id()
+    }
+}
+
+thread_local! {
+    static RNG: Cell<u64> = Cell::new(RandomState::new().hash_one(std::thread::current().id()) | 1);
+}
+
+/// The next value of this thread's xorshift generator
+pub(crate) fn random_u64() -> u64 {
+    RNG.with(|rng| {
+        let mut x = rng.get();
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        rng.set(x);
+        x
+    })
+}
+
+impl HorizonLogger {
+    /// A fresh identifier from the logger's `IdGenerator`
+    pub fn new_id(&self) -> String {
+        self.inner.ids.next_id()
+    }
+
+    /// Tag entries on this thread with a new correlation id from the `IdGenerator`
+    ///
+    /// Read it back with `current_correlation`, e.g. to pass to another thread.
+    pub fn with_new_correlation(&self) -> CorrelationGuard {
+        self.with_correlation(&self.new_id())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{CaptureLogger, SequentialIdGenerator};
+    use std::collections::HashSet;
+    use std::thread;
+
+    #[test]
+    fn test_random_ids() {
+        let ids: HashSet<_> = (0..100).map(|_| RandomIdGenerator.next_id()).collect();
+        assert_eq!(ids.len(), 100);
+        assert!(ids.iter().all(|id| id.len() == 8 && id.chars().all(|c| c.is_ascii_hexdigit())));
+    }
+
+    #[test]
+    fn test_sequential_ids_across_threads() {
+        let builder = HorizonLogger::builder().id_generator(SequentialIdGenerator::new());
+        let logger = CaptureLogger::from_builder(builder);
+        assert_eq!(logger.run_id(), "00000001");
+
+        let workers: Vec<_> = (0..4)
+            .map(|worker| {
+                let logger = logger.logger().clone();
+                thread::spawn(move || {
+                    for request in 0..25 {
+                        let _request = logger.with_new_correlation();
+                        logger.info("NET", &format!("worker {} request {}", worker, request));
+                    }
+                })
+            })
+            .collect();
+        workers.into_iter().for_each(|worker| worker.join().unwrap());
+
+        let entries = logger.entries();
+        let ids: Vec<_> = entries.iter().map(|e| e.correlation_id.as_deref().unwrap().to_string()).collect();
+        assert_eq!(ids.iter().collect::<HashSet<_>>().len(), 100);
+        let mut sorted = ids.clone();
+        sorted.sort();
+        assert_eq!(sorted, (2..102).map(|n| format!("{:08x}", n)).collect::<Vec<_>>());
+        for worker in 0..4 {
+            let prefix = format!("worker {} ", worker);
+            let own: Vec<_> = entries
+                .iter()
+                .filter(|e| e.message.starts_with(&prefix))
+                .map(|e| &e.correlation_id)
+                .collect();
+            assert!(own.windows(2).all(|pair| pair[0] < pair[1]));
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use crate::HorizonLogger;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+/// Environment variable that builds loggers already disabled when set to `1`
+const DISABLE_VAR: &str = "HORIZON_LOG_DISABLE";
+
+/// Whether `emergency_disable` turned the logger off, and what it dropped since
+#[derive(Default)]
+pub(crate) struct KillSwitch {
+    disabled: AtomicBool,
+    suppressed: AtomicU64,
+}
+
+impl KillSwitch {
+    /// Disabled if `HORIZON_LOG_DISABLE=1`
+    pub(crate) fn from_env() -> Self {
+        let switch = KillSwitch::default();
+        if std::env::var(DISABLE_VAR).is_ok_and(|value| value.trim() == "1") {
+            switch.disabled.store(true, Ordering::SeqCst);
+            let _ = writeln!(io::stderr(), "horizon_logger: {}=1, logging is disabled", DISABLE_VAR);
+        }
+        switch
+    }
+
+    /// Whether the logger is off; the only cost of the switch while it is on
+    #[inline]
+    pub(crate) fn is_off(&self) -> bool {
+        self.disabled.load(Ordering::Relaxed)
+    }
+
+    /// Count entries dropped because the logger is off
+    pub(crate) fn suppress(&self, entries: u64) {
+        self.suppressed.fetch_add(entries, Ordering::Relaxed);
+    }
+}
+
+impl HorizonLogger {
+    /// Turn the whole logger into a no-op until `emergency_enable`, for when logging itself is the problem
+    ///
+    /// From now on nothing reaches the console, sinks or history, `enabled`
+    /// is false for every level, and `flush` doesn't touch the sinks.
+    /// Entries are only counted, see `suppressed_entries`. The switch is
+    /// noted on stderr. Returns false if the logger was already disabled.
+    pub fn emergency_disable(&self) -> bool {
+        let switch = &self.inner.kill_switch;
+        if switch.disabled.swap(true, Ordering::SeqCst) {
+            return false;
+        }
+        switch.suppressed.store(0, Ordering::Relaxed);
+        let _ = writeln!(io::stderr(), "horizon_logger: emergency_disable, logging is off");
+        true
+    }
+
+    /// Turn the logger back on after `emergency_disable` or `HORIZON_LOG_DISABLE=1`
+    ///
+    /// Returns false if it wasn't disabled.
+    pub fn emergency_enable(&self) -> bool {
+        let switch = &self.inner.kill_switch;
+        if !switch.disabled.swap(false, Ordering::SeqCst) {
+            return false;
+        }
+        let suppressed = switch.suppressed.load(Ordering::Relaxed);
+        let _ = writeln!(
+            io::stderr(),
+            "horizon_logger: emergency_enable, logging is on again after suppressing {} entries",
+            suppressed
+        );
+        true
+    }
+
+    pub fn is_emergency_disabled(&self) -> bool {
+        self.inner.kill_switch.is_off()
+    }
+
+    /// Entries dropped while the logger was last disabled, including now
+    pub fn suppressed_entries(&self) -> u64 {
+        self.inner.kill_switch.suppressed.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::CaptureLogger;
+    use crate::{log_info, LogLevel};
+
+    #[test]
+    fn test_disable_suppresses_and_enable_restores() {
+        let logger = CaptureLogger::new();
+        logger.info("NET", "before");
+        let group = logger.group("NET", "handshake");
+        group.info("hello sent");
+
+        assert!(logger.emergency_disable());
+        assert!(!logger.emergency_disable());
+        assert!(logger.is_emergency_disabled());
+        assert!(!logger.enabled(LogLevel::CRITICAL));
+        for n in 0..5 {
+            log_info!(logger, "NET", "packet {}", n);
+        }
+        logger.critical("NET", "collector unreachable");
+        logger.progress("NET", "50%");
+        group.warn("keeps buffering nothing");
+        drop(group);
+        // The five packets, the critical, the progress update, the warning and the group's block of three
+        assert_eq!(logger.suppressed_entries(), 11);
+        assert_eq!(logger.messages(), ["before"]);
+
+        assert!(logger.emergency_enable());
+        assert!(!logger.emergency_enable());
+        logger.info("NET", "after");
+        assert_eq!(logger.messages(), ["before", "after"]);
+        assert_eq!(logger.suppressed_entries(), 11);
+        assert_eq!(logger.component_stats()[0].total_messages(), 2);
+
+        logger.emergency_disable();
+        assert_eq!(logger.suppressed_entries(), 0);
+    }
+}
@@ -0,0 +1,169 @@
+//! A `tracing_subscriber` layer that feeds `tracing` events into a logger
+//!
+//! `HorizonLayer` composes with other layers in an existing registry, so
+//! `tracing` events get HorizonLogger's console output, history and sinks
+//! without installing a second global subscriber:
+//!
+//! ```no_run
+//! use horizon_logger::{HorizonLayer, HorizonLogger};
+//! use tracing_subscriber::prelude::*;
+//!
+//! let logger = HorizonLogger::new();
+//! tracing_subscriber::registry().with(HorizonLayer::new(logger.clone())).init();
+//! ```
+//!
+//! An event's component is its own `component` field, else the `component`
+//! field of the innermost enclosing span that has one, else its target.
+//!
+//! A subscriber built before the logger exists can hold a
+//! `HorizonLayerSlot` instead, for `init_tracing_bridge` to fill later.
+
+use crate::{CallOptions, HorizonLogger, LogLevel};
+use std::borrow::Cow;
+use std::fmt::{self, Write};
+use std::sync::OnceLock;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// Logs every `tracing` event it sees through a `HorizonLogger`
+pub struct HorizonLayer {
+    logger: HorizonLogger,
+}
+
+impl HorizonLayer {
+    pub fn new(logger: HorizonLogger) -> Self {
+        HorizonLayer { logger }
+    }
+}
+
+/// A place for `init_tracing_bridge` to attach to a subscriber installed before it runs
+///
+/// Add one when building a global subscriber that may be set before the
+/// logger; it ignores everything until the bridge fills it, then acts as a
+/// `HorizonLayer`. There is one slot per process, filled at most once.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HorizonLayerSlot;
+
+static SLOT: OnceLock<HorizonLayer> = OnceLock::new();
+
+impl HorizonLayerSlot {
+    /// Make `layer` the slot's layer; false if it is already filled
+    pub(crate) fn fill(layer: HorizonLayer) -> bool {
+        SLOT.set(layer).is_ok()
+    }
+}
+
+impl<S> Layer<S> for HorizonLayerSlot
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if let Some(layer) = SLOT.get() {
+            layer.on_new_span(attrs, id, ctx);
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        if let Some(layer) = SLOT.get() {
+            layer.on_event(event, ctx);
+        }
+    }
+}
+
+/// The `component` field of a span, kept in its extensions
+struct SpanComponent(String);
+
+/// Collects an event's message, `component` and remaining fields
+#[derive(Default)]
+struct EventVisitor {
+    message: String,
+    component: Option<String>,
+    /// ` key=value` for every other field
+    fields: String,
+}
+
+impl Visit for EventVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "message" => self.message.push_str(value),
+            "component" => self.component = Some(value.to_string()),
+            name => {
+                let _ = write!(self.fields, " {}={}", name, value);
+            }
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        match field.name() {
+            // `format_args!` messages print without quotes
+            "message" => {
+                let _ = write!(self.message, "{:?}", value);
+            }
+            "component" => self.component = Some(format!("{:?}", value)),
+            name => {
+                let _ = write!(self.fields, " {}={:?}", name, value);
+            }
+        }
+    }
+}
+
+/// Finds a span's `component` field
+struct ComponentVisitor(Option<String>);
+
+impl Visit for ComponentVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "component" {
+            self.0 = Some(value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "component" {
+            self.0 = Some(format!("{:?}", value));
+        }
+    }
+}
+
+impl<S> Layer<S> for HorizonLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut visitor = ComponentVisitor(None);
+        attrs.record(&mut visitor);
+        if let (Some(component), Some(span)) = (visitor.0, ctx.span(id)) {
+            span.extensions_mut().insert(SpanComponent(component));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let level = LogLevel::from_tracing(*metadata.level());
+        if !self.logger.enabled(level) {
+            return;
+        }
+
+        let mut visitor = EventVisitor::default();
+        event.record(&mut visitor);
+        let component = visitor.component.or_else(|| {
+            ctx.event_scope(event)?
+                .find_map(|span| span.extensions().get::<SpanComponent>().map(|c| c.0.clone()))
+        });
+        let component = component.map_or(Cow::Borrowed(metadata.target()), Cow::Owned);
+
+        let options = CallOptions {
+            location: metadata.file().zip(metadata.line()),
+            ..CallOptions::default()
+        };
+        if visitor.message.is_empty() {
+            self.logger.log_with(level, &component, visitor.fields.trim_start(), options);
+        } else {
+            visitor.message.push_str(&visitor.fields);
+            self.logger.log_with(level, &component, &visitor.message, options);
+        }
+    }
+}
@@ -0,0 +1,292 @@
+use crate::{HorizonLogger, LogLevel};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::RwLock;
+
+/// Built-in display names, indexed by `LogLevel as usize`
+const DEFAULT_NAMES: [&str; LogLevel::COUNT] = ["DEBUG", "INFO", "WARN", "ERROR", "CRIT"];
+
+/// Minimum width of the level column
+const MIN_LEVEL_WIDTH: usize = 7;
+
+/// Display names currently in effect for each level
+static LEVEL_NAMES: RwLock<[&str; LogLevel::COUNT]> = RwLock::new(DEFAULT_NAMES);
+
+/// Width of the level column, wide enough for the longest configured name
+static LEVEL_WIDTH: AtomicUsize = AtomicUsize::new(MIN_LEVEL_WIDTH);
+
+/// Current display name of a level
+pub(crate) fn name(level: LogLevel) -> &'static str {
+    LEVEL_NAMES
+        .read()
+        .map(|names| names[level as usize])
+        .unwrap_or(DEFAULT_NAMES[level as usize])
+}
+
+/// Width the level column is centered in
+pub(crate) fn width() -> usize {
+    LEVEL_WIDTH.load(Ordering::Relaxed)
+}
+
+/// Replace the display name of a level for the whole process
+pub(crate) fn set_name(level: LogLevel, custom: &str) {
+    let Ok(mut names) = LEVEL_NAMES.write() else {
+        return;
+    };
+
+    // Names live for the rest of the process; this is a rare configuration call
+    names[level as usize] = if custom == DEFAULT_NAMES[level as usize] {
+        DEFAULT_NAMES[level as usize]
+    } else {
+        Box::leak(custom.to_string().into_boxed_str())
+    };
+
+    let longest = names.iter().map(|n| n.chars().count()).max().unwrap_or(0);
+    LEVEL_WIDTH.store(longest.max(MIN_LEVEL_WIDTH), Ordering::Relaxed);
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+/// A string did not name any log level
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseLevelError(pub String);
+
+impl fmt::Display for ParseLevelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown log level `{}`", self.0)
+    }
+}
+
+impl std::error::Error for ParseLevelError {}
+
+impl FromStr for LogLevel {
+    type Err = ParseLevelError;
+
+    /// Accepts level names case-insensitively, including custom names
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let wanted = s.trim();
+        let builtin = match wanted.to_ascii_uppercase().as_str() {
+            "DEBUG" => Some(LogLevel::DEBUG),
+            "INFO" => Some(LogLevel::INFO),
+            "WARN" | "WARNING" => Some(LogLevel::WARN),
+            "ERROR" => Some(LogLevel::ERROR),
+            "CRIT" | "CRITICAL" => Some(LogLevel::CRITICAL),
+            _ => None,
+        };
+
+        builtin
+            .or_else(|| {
+                LogLevel::ALL
+                    .into_iter()
+                    .find(|level| name(*level).eq_ignore_ascii_case(wanted))
+            })
+            .ok_or_else(|| ParseLevelError(s.to_string()))
+    }
+}
+
+/// Conversions to and from other level schemes
+///
+/// Every integration maps levels through these, and each is an exhaustive
+/// match, so a new level cannot be added without deciding all of them.
+impl LogLevel {
+    /// Stable numbering used in binary logs and numeric config:
+    /// DEBUG = 0, INFO = 1, WARN = 2, ERROR = 3, CRITICAL = 4
+    pub const fn to_u8(self) -> u8 {
+        match self {
+            LogLevel::DEBUG => 0,
+            LogLevel::INFO => 1,
+            LogLevel::WARN => 2,
+            LogLevel::ERROR => 3,
+            LogLevel::CRITICAL => 4,
+        }
+    }
+
+    /// The level numbered `n` by `to_u8`
+    pub const fn from_u8(n: u8) -> Option<LogLevel> {
+        match n {
+            0 => Some(LogLevel::DEBUG),
+            1 => Some(LogLevel::INFO),
+            2 => Some(LogLevel::WARN),
+            3 => Some(LogLevel::ERROR),
+            4 => Some(LogLevel::CRITICAL),
+            _ => None,
+        }
+    }
+
+    /// Canonical numeric severity: DEBUG 10, INFO 20, WARN 30, ERROR 40, CRITICAL 50
+    ///
+    /// Entries can carry a finer severity within their level's band, see
+    /// `HorizonLogger::event`.
+    pub const fn default_severity(self) -> u8 {
+        match self {
+            LogLevel::DEBUG => 10,
+            LogLevel::INFO => 20,
+            LogLevel::WARN => 30,
+            LogLevel::ERROR => 40,
+            LogLevel::CRITICAL => 50,
+        }
+    }
+
+    /// RFC 5424 severity: debug (7), informational (6), warning (4), error (3), critical (2)
+    pub const fn to_syslog_severity(self) -> u8 {
+        match self {
+            LogLevel::DEBUG => 7,
+            LogLevel::INFO => 6,
+            LogLevel::WARN => 4,
+            LogLevel::ERROR => 3,
+            LogLevel::CRITICAL => 2,
+        }
+    }
+
+    /// The `tracing` level; CRITICAL has no equivalent and becomes ERROR
+    #[cfg(feature = "tracing-bridge")]
+    pub fn to_tracing(self) -> tracing::Level {
+        match self {
+            LogLevel::DEBUG => tracing::Level::DEBUG,
+            LogLevel::INFO => tracing::Level::INFO,
+            LogLevel::WARN => tracing::Level::WARN,
+            LogLevel::ERROR | LogLevel::CRITICAL => tracing::Level::ERROR,
+        }
+    }
+
+    /// The level for a `tracing` level; TRACE becomes DEBUG
+    #[cfg(feature = "tracing-bridge")]
+    pub fn from_tracing(level: tracing::Level) -> LogLevel {
+        match level {
+            tracing::Level::TRACE | tracing::Level::DEBUG => LogLevel::DEBUG,
+            tracing::Level::INFO => LogLevel::INFO,
+            tracing::Level::WARN => LogLevel::WARN,
+            tracing::Level::ERROR => LogLevel::ERROR,
+        }
+    }
+
+    /// The `log` crate level; CRITICAL has no equivalent and becomes Error
+    #[cfg(feature = "log")]
+    pub fn to_log(self) -> log::Level {
+        match self {
+            LogLevel::DEBUG => log::Level::Debug,
+            LogLevel::INFO => log::Level::Info,
+            LogLevel::WARN => log::Level::Warn,
+            LogLevel::ERROR | LogLevel::CRITICAL => log::Level::Error,
+        }
+    }
+
+    /// The level for a `log` crate level; Trace becomes DEBUG
+    #[cfg(feature = "log")]
+    pub fn from_log(level: log::Level) -> LogLevel {
+        match level {
+            log::Level::Trace | log::Level::Debug => LogLevel::DEBUG,
+            log::Level::Info => LogLevel::INFO,
+            log::Level::Warn => LogLevel::WARN,
+            log::Level::Error => LogLevel::ERROR,
+        }
+    }
+}
+
+impl HorizonLogger {
+    /// Rename a level everywhere it is displayed: console, files, JSON and `Display`
+    ///
+    /// Level names are process-wide, so this affects every logger. The
+    /// level column widens to fit the longest name.
+    pub fn set_level_name(&self, level: LogLevel, name: &str) {
+        set_name(level, name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LogEntry;
+
+    #[test]
+    fn test_parse_builtin_names() {
+        assert_eq!("warn".parse(), Ok(LogLevel::WARN));
+        assert_eq!("Warning".parse(), Ok(LogLevel::WARN));
+        assert_eq!("CRIT".parse(), Ok(LogLevel::CRITICAL));
+        assert_eq!(
+            "loud".parse::<LogLevel>(),
+            Err(ParseLevelError("loud".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_conversion_matrix() {
+        // One row per level: adding a level must add a row here
+        let matrix = [
+            (LogLevel::DEBUG, 0, 7),
+            (LogLevel::INFO, 1, 6),
+            (LogLevel::WARN, 2, 4),
+            (LogLevel::ERROR, 3, 3),
+            (LogLevel::CRITICAL, 4, 2),
+        ];
+        assert_eq!(matrix.len(), LogLevel::COUNT);
+        for (level, (expected, n, severity)) in LogLevel::ALL.into_iter().zip(matrix) {
+            assert_eq!(level, expected);
+            assert_eq!(level.to_u8(), n);
+            assert_eq!(level as u8, n);
+            assert_eq!(LogLevel::from_u8(n), Some(level));
+            assert_eq!(level.to_syslog_severity(), severity);
+        }
+        assert_eq!(LogLevel::from_u8(LogLevel::COUNT as u8), None);
+    }
+
+    #[cfg(feature = "tracing-bridge")]
+    #[test]
+    fn test_tracing_conversions() {
+        let matrix = [
+            tracing::Level::DEBUG,
+            tracing::Level::INFO,
+            tracing::Level::WARN,
+            tracing::Level::ERROR,
+            tracing::Level::ERROR,
+        ];
+        for (level, tracing) in LogLevel::ALL.into_iter().zip(matrix) {
+            assert_eq!(level.to_tracing(), tracing);
+        }
+
+        for level in [tracing::Level::DEBUG, tracing::Level::INFO, tracing::Level::WARN, tracing::Level::ERROR] {
+            assert_eq!(LogLevel::from_tracing(level).to_tracing(), level);
+        }
+        assert_eq!(LogLevel::from_tracing(tracing::Level::TRACE), LogLevel::DEBUG);
+    }
+
+    #[cfg(feature = "log")]
+    #[test]
+    fn test_log_conversions() {
+        let levels = [log::Level::Debug, log::Level::Info, log::Level::Warn, log::Level::Error];
+        for level in levels {
+            assert_eq!(LogLevel::from_log(level).to_log(), level);
+        }
+        assert_eq!(LogLevel::from_log(log::Level::Trace), LogLevel::DEBUG);
+        assert_eq!(LogLevel::CRITICAL.to_log(), log::Level::Error);
+    }
+
+    // Level names are global, so everything touching a custom name lives in one test
+    #[test]
+    fn test_custom_level_name() {
+        assert_eq!(LogLevel::CRITICAL.to_string(), "CRIT");
+        assert_eq!(width(), MIN_LEVEL_WIDTH);
+
+        let logger = HorizonLogger::new();
+        logger.set_level_name(LogLevel::CRITICAL, "FATALITY");
+
+        assert_eq!(LogLevel::CRITICAL.to_string(), "FATALITY");
+        assert_eq!("fatality".parse(), Ok(LogLevel::CRITICAL));
+        assert_eq!("critical".parse(), Ok(LogLevel::CRITICAL));
+        assert_eq!(width(), 8);
+
+        let entry = LogEntry::new(LogLevel::WARN, "GAME", "x");
+        assert!(entry.to_string().contains("   WARN   [GAME] x"));
+        let entry = LogEntry::new(LogLevel::CRITICAL, "GAME", "x");
+        assert!(entry.to_json().contains(r#""level":"FATALITY""#));
+
+        logger.set_level_name(LogLevel::CRITICAL, "CRIT");
+        assert_eq!(LogLevel::CRITICAL.to_string(), "CRIT");
+        assert_eq!(width(), MIN_LEVEL_WIDTH);
+    }
+}
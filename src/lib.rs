@@ -1,12 +1,176 @@
-use chrono::Local;
-use colored::*;
-use once_cell::sync::Lazy;
-use std::sync::Mutex;
-use tracing::{Level, Subscriber};
-use tracing_subscriber::FmtSubscriber;
+use std::backtrace::Backtrace;
+use std::borrow::Cow;
+use std::panic::Location;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+
+mod alias;
+mod ansi;
+mod assert;
+mod batch;
+mod binary;
+mod breadcrumb;
+#[cfg(any(feature = "tracing-bridge", feature = "log"))]
+mod bridge;
+mod builder;
+mod callsite;
+mod clock;
+mod command;
+mod component;
+mod context;
+mod console;
+mod correlation;
+mod directive;
+mod escalation;
+mod empty;
+mod escape;
+mod event;
+mod facade;
+#[cfg(all(unix, feature = "fork"))]
+mod fork;
+pub mod fmt;
+mod format;
+mod fsync;
+mod glyph;
+mod group;
+mod heartbeat;
+mod header;
+mod history;
+#[cfg(feature = "http-debug")]
+mod http_debug;
+mod id;
+mod kill_switch;
+#[cfg(feature = "tracing-bridge")]
+mod layer;
+mod level;
+mod namespace;
+mod network;
+mod pattern;
+mod persist;
+mod preinit;
+mod preset;
+mod pin;
+mod profile;
+pub mod reader;
+#[cfg(feature = "oslog")]
+mod oslog;
+#[cfg(feature = "otel")]
+mod otel;
+mod pipe;
+mod pretty;
+mod queue;
+mod reentry;
+mod reload;
+mod replay;
+mod rotate;
+mod run;
+mod sampling;
+mod self_test;
+mod shutdown;
+#[cfg(all(unix, feature = "signal"))]
+mod signal;
+mod sink;
+mod size;
+mod skew;
+mod snippet;
+mod span;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod stats;
+mod template;
+mod time;
+mod time_render;
+mod timer;
+mod unicode;
+mod volume;
+pub mod testing;
+
+pub use alias::{AliasError, AliasErrorKind};
+pub use ansi::AnsiPolicy;
+pub use assert::{assert_action, set_assert_action, AssertAction};
+pub use batch::PreparedEntry;
+pub use binary::{BinaryLogReader, BinarySink};
+pub use breadcrumb::Breadcrumb;
+#[cfg(any(feature = "tracing-bridge", feature = "log"))]
+pub use bridge::BridgeMode;
+#[cfg(feature = "tracing-bridge")]
+pub use bridge::init_tracing_bridge;
+#[cfg(feature = "log")]
+pub use bridge::{init_log_bridge, LogBridge};
+pub use builder::LoggerBuilder;
+#[doc(hidden)]
+pub use callsite::{check_component, check_message};
+pub use clock::{Clock, ManualClock, SystemClock};
+pub use command::CommandHandle;
+pub use component::{ComponentArg, ComponentLogger};
+pub use console::{ConsoleColor, ConsoleFields, ConsoleWrap};
+pub use context::{ContextGuard, LogContext};
+pub use correlation::{current_correlation, CorrelationGuard};
+pub use directive::{AppliedChange, DirectiveError, DirectiveErrorKind, LevelChange};
+pub use empty::EmptyMessagePolicy;
+pub use escalation::EscalationRule;
+pub use event::EventBuilder;
+#[doc(hidden)]
+pub use event::unique_event_codes;
+pub use format::{format_entry, ColorCodes, Format, FormatOptions, MachineTimestamp};
+pub use facade::{HorizonLog, NullLogger};
+pub use fsync::SyncPolicy;
+pub use glyph::GlyphSet;
+pub use group::LogGroup;
+pub use heartbeat::HeartbeatHandle;
+pub use header::{detect_format, DetectError, FileKind, FormatInfo, FORMAT_VERSION};
+pub use history::{Checkpoint, ContextBlock, HistoryOverflow, HistoryQuery};
+#[cfg(feature = "http-debug")]
+pub use http_debug::DebugServerHandle;
+pub use id::{IdGenerator, RandomIdGenerator};
+#[cfg(feature = "tracing-bridge")]
+pub use layer::{HorizonLayer, HorizonLayerSlot};
+pub use level::ParseLevelError;
+pub use pin::PinError;
+pub use namespace::NamespaceHandle;
+pub use network::NetworkSink;
+pub use pattern::{Pattern, PatternError};
+pub use persist::PersistPolicy;
+pub use preset::{ConfigDescription, Preset};
+pub use profile::ProfileReport;
+#[cfg(feature = "oslog")]
+pub use oslog::OsLogSink;
+#[cfg(feature = "otel")]
+pub use otel::OtelSink;
+pub use pipe::PipeHandle;
+pub use queue::{BackpressurePolicy, QueueStatus};
+pub use rotate::{Rotation, RotationCompression};
+#[cfg(all(unix, feature = "signal"))]
+pub use signal::Signal;
+pub use sink::{FileSink, RoutedSink, SeverityFilter, Sink, SinkError, SinkId, SinkOrdering, SinkRoute};
+pub use size::{LargeMessage, SizeBucket, SizeReport};
+pub use reload::ConfigWatchHandle;
+pub use replay::ReplayOptions;
+pub use sampling::SampleRate;
+pub use self_test::{HorizonLoggerError, SelfTestReport, SinkCheck};
+pub use span::LogSpan;
+pub use template::FieldValue;
+#[cfg(feature = "sqlite")]
+pub use sqlite::{SqliteLogReader, SqliteSink, SqliteSinkBuilder};
+pub use stats::ComponentStats;
+pub use timer::ScopeTimer;
+pub use unicode::UnicodePolicy;
+pub use time::Timestamp;
+pub use time_render::{CompactTime, HumanTime, TimestampRenderer};
+pub use volume::{ComponentVolume, VolumeLimits, VolumeStatus};
+
+#[doc(hidden)]
+pub mod __private {
+    //! Internals exposed for the crate's benchmarks; not a stable API
+    pub use crate::history::History;
+}
+
+/// Maximum message length in bytes; longer captured lines are split into chunks
+pub const MAX_MESSAGE_LEN: usize = 8192;
+
 
 /// Log levels with corresponding colors
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum LogLevel {
     DEBUG,   // Cyan
     INFO,    // Green
@@ -16,172 +180,867 @@ pub enum LogLevel {
 }
 
 impl LogLevel {
-    fn color(&self) -> ColoredString {
+    /// Number of log levels
+    pub const COUNT: usize = 5;
+
+    /// All levels from least to most severe
+    pub const ALL: [LogLevel; LogLevel::COUNT] = [
+        LogLevel::DEBUG,
+        LogLevel::INFO,
+        LogLevel::WARN,
+        LogLevel::ERROR,
+        LogLevel::CRITICAL,
+    ];
+
+    /// Short name used in console and machine output
+    ///
+    /// Defaults to `DEBUG`, `INFO`, `WARN`, `ERROR` and `CRIT`; see
+    /// `HorizonLogger::set_level_name`.
+    pub fn as_str(&self) -> &'static str {
+        level::name(*self)
+    }
+
+    /// SGR parameters for this level's console color
+    fn ansi_style(&self) -> &'static str {
         match self {
-            LogLevel::DEBUG => format!("{:^7}", "DEBUG").cyan(),
-            LogLevel::INFO => format!("{:^7}", "INFO").green(),
-            LogLevel::WARN => format!("{:^7}", "WARN").yellow(),
-            LogLevel::ERROR => format!("{:^7}", "ERROR").red(),
-            LogLevel::CRITICAL => format!("{:^7}", "CRIT").on_red().white(),
+            LogLevel::DEBUG => "36",
+            LogLevel::INFO => "32",
+            LogLevel::WARN => "33",
+            LogLevel::ERROR => "31",
+            LogLevel::CRITICAL => "41;37",
         }
     }
 }
 
 /// Log entry structure for storing log history
 #[derive(Debug, Clone)]
-struct LogEntry {
-    timestamp: String,
-    level: LogLevel,
-    component: String,
-    message: String,
+pub struct LogEntry {
+    /// Position in the logger's history, assigned in logging order
+    pub seq: u64,
+    /// Wall-clock time from the logger's clock; informational only, history is ordered by `seq`
+    pub timestamp: Timestamp,
+    pub level: LogLevel,
+    /// Finer-grained severity for sorting; `level.default_severity()` unless set with `event`
+    pub severity: u8,
+    /// Borrowed when logged with a `&'static str` component, see `ComponentArg`
+    pub component: Cow<'static, str>,
+    pub message: String,
+    /// Stable event code such as `NET-0042`, see `EventBuilder::code` and `define_events!`
+    pub code: Option<Arc<str>>,
+    /// Template `message` was rendered from, see `EventBuilder::template`
+    pub template: Option<Arc<str>>,
+    /// Named values attached with `EventBuilder::field`, in order
+    pub fields: Vec<(Cow<'static, str>, FieldValue)>,
+    /// Run the entry was logged in, see `HorizonLogger::run_id`
+    pub run_id: Option<Arc<str>>,
+    /// Innermost span active on the logging thread
+    pub span_id: Option<u64>,
+    /// Parent of `span_id`, if it is nested
+    pub parent_id: Option<u64>,
+    /// Group the entry was committed in, see `HorizonLogger::group`
+    pub group_id: Option<u64>,
+    /// Correlation id in effect on the logging thread, see `with_correlation`
+    pub correlation_id: Option<Arc<str>>,
+    /// Rendered backtrace, captured for levels chosen with `capture_backtrace`
+    pub backtrace: Option<String>,
+    /// Breadcrumbs left on the logging thread before this ERROR or CRITICAL, see `HorizonLogger::breadcrumb`
+    pub breadcrumbs: Vec<Breadcrumb>,
+    /// Occurrences folded into this entry by history dedup; 1 otherwise
+    pub repeat_count: u32,
+    /// When the last folded occurrence was logged; equals `timestamp` unless repeated
+    pub last_timestamp: Timestamp,
+    /// Rate the entry's component was sampled at when it was kept, see `set_component_sampling`
+    pub sampled: Option<SampleRate>,
+    /// Whether ANSI escapes were stripped from `message` when it was stored in history
+    pub ansi_stripped: bool,
+    /// Whether the entry is protected from history eviction, see `HorizonLogger::pin_entry`
+    pub pinned: bool,
+    /// Text output is the message alone, verbatim, see `HorizonLogger::raw`
+    pub raw: bool,
 }
 
-/// Global log history
-static LOG_HISTORY: Lazy<Mutex<Vec<LogEntry>>> = Lazy::new(|| Mutex::new(Vec::new()));
+impl LogEntry {
+    /// Create an entry stamped with the current local time
+    ///
+    /// The sequence number is assigned when the entry is stored in a history.
+    pub fn new(level: LogLevel, component: &str, message: &str) -> Self {
+        Self::at(Timestamp::now(), level, component, message)
+    }
+
+    /// Create an entry with an explicit timestamp
+    pub fn at(timestamp: Timestamp, level: LogLevel, component: &str, message: &str) -> Self {
+        Self::with_component(timestamp, level, Cow::Owned(component.to_string()), message)
+    }
 
-/// Initialize the logging system
-pub fn init() {
-    let subscriber = FmtSubscriber::builder()
-        .with_env_filter("info")
-        .with_target(false)
-        .with_thread_ids(true)
-        .with_thread_names(true)
-        .with_file(true)
-        .with_line_number(true)
-        .with_level(true)
-        .init();
+    pub(crate) fn with_component(timestamp: Timestamp, level: LogLevel, component: Cow<'static, str>, message: &str) -> Self {
+        LogEntry {
+            seq: 0,
+            timestamp,
+            level,
+            severity: level.default_severity(),
+            component,
+            message: message.to_string(),
+            code: None,
+            template: None,
+            fields: Vec::new(),
+            run_id: None,
+            span_id: None,
+            parent_id: None,
+            group_id: None,
+            correlation_id: None,
+            backtrace: None,
+            breadcrumbs: Vec::new(),
+            repeat_count: 1,
+            last_timestamp: timestamp,
+            sampled: None,
+            ansi_stripped: false,
+            pinned: false,
+            raw: false,
+        }
+    }
+}
+
+/// Send `tracing` events at INFO and above to a new `HorizonLogger`, see `init_tracing_bridge`
+///
+/// A subscriber set before this is kept; add a `HorizonLayerSlot` to it for
+/// the logger to attach to, or a `HorizonLayer` if the logger exists first.
+#[cfg(feature = "tracing-bridge")]
+pub fn init() -> BridgeMode {
+    init_tracing_bridge(HorizonLogger::new())
 }
 
 /// Main logging implementation
-pub struct HorizonLogger;
+///
+/// Cloning is cheap and clones share the same history.
+#[derive(Clone)]
+pub struct HorizonLogger {
+    inner: Arc<LoggerInner>,
+}
+
+/// State shared between clones of a logger
+struct LoggerInner {
+    /// The logger's own, or with `global_history` the process-wide one
+    history: Arc<history::History>,
+    /// Entries are stored in `history`; otherwise it only numbers them
+    keep_history: bool,
+    /// Set by `persist_history`
+    persistence: Option<persist::Persistence>,
+    stats: stats::StatsRegistry,
+    sizes: size::SizeTracker,
+    aliases: alias::ComponentAliases,
+    namespaces: namespace::Namespaces,
+    escalations: escalation::Escalations,
+    directives: directive::Directives,
+    sampling: sampling::Samplers,
+    volume: volume::VolumeMonitor,
+    run_id: Arc<str>,
+    /// When the logger was built, by its clock
+    started: Timestamp,
+    /// The run announcement has been logged, or was disabled
+    run_announced: AtomicBool,
+    indent_spans: AtomicBool,
+    /// Entries below this level (as `LogLevel::to_u8`) are dropped
+    min_level: AtomicU8,
+    clock: Box<dyn Clock>,
+    /// Makes up run ids and new correlation ids
+    ids: Box<dyn id::IdGenerator>,
+    /// Replaced as a whole by `set_text_pattern`
+    format: RwLock<Arc<FormatOptions>>,
+    /// Minimum level that captures a backtrace
+    backtrace_level: Option<LogLevel>,
+    /// Cut-off for `debug_pretty` renderings
+    pretty: pretty::PrettyLimits,
+    /// Print captured backtraces on the console (`RUST_BACKTRACE` is set)
+    print_backtraces: bool,
+    sinks: RwLock<sink::SinkList>,
+    /// Set by `async_sinks`; sinks are written on the logging thread otherwise
+    sink_queue: Option<queue::SinkQueueHandle>,
+    /// Held while an entry is written to the sinks without a queue, for `SinkOrdering::Strict`
+    dispatch_lock: Mutex<()>,
+    /// Entries logged before the first sink, unless `preinit_buffer(0)` or discarded
+    preinit: preinit::PreinitBuffer,
+    console: console::Console,
+    /// Held for writing while a group's block is output, so no other entry lands inside it
+    group_lock: RwLock<()>,
+    /// Entries a group buffers before it is flushed early
+    group_max_entries: usize,
+    profile: profile::Profiler,
+    warned_templates: template::WarnedTemplates,
+    seal: shutdown::Seal,
+    /// Set with `static_field` and `static_fields_from_env`; the start of every entry's fields
+    static_fields: Vec<(Cow<'static, str>, FieldValue)>,
+    empty_messages: empty::EmptyMessages,
+    skew: skew::SkewDetector,
+    kill_switch: kill_switch::KillSwitch,
+    /// Sequence numbers of replayed entries, apart from the history's
+    replay_seq: AtomicU64,
+    fatal_handler: fn() -> !,
+    #[cfg(all(unix, feature = "fork"))]
+    fork_gate: fork::ForkGate,
+    /// Heartbeats started on this logger, for `after_fork_child` to restart
+    #[cfg(all(unix, feature = "fork"))]
+    heartbeats: Mutex<Vec<std::sync::Weak<heartbeat::Heartbeat>>>,
+}
+
+/// Per-call tweaks to the logging pipeline
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct CallOptions<'a> {
+    /// Span depth used for console indentation; `None` uses the current depth
+    pub(crate) depth: Option<usize>,
+    /// Whether this call may capture a backtrace
+    pub(crate) backtrace: bool,
+    /// Whether escalation rules count this entry
+    pub(crate) escalate: bool,
+    /// Whether level filters, sampling, volume limits and a sealed logger can drop this entry
+    pub(crate) filtered: bool,
+    /// Text output is the message alone, see `HorizonLogger::raw`
+    pub(crate) raw: bool,
+    /// Overrides the level's default severity
+    pub(crate) severity: Option<u8>,
+    /// Event code attached to the entry
+    pub(crate) code: Option<&'static str>,
+    /// Template the message was rendered from, see `EventBuilder::template`
+    pub(crate) template: Option<&'static str>,
+    pub(crate) fields: &'a [(Cow<'static, str>, FieldValue)],
+    /// File and line logged from, for the console's `source_snippets`
+    pub(crate) location: Option<(&'a str, u32)>,
+}
+
+impl Default for CallOptions<'_> {
+    fn default() -> Self {
+        CallOptions {
+            depth: None,
+            backtrace: true,
+            escalate: true,
+            filtered: true,
+            raw: false,
+            severity: None,
+            code: None,
+            template: None,
+            fields: &[],
+            location: None,
+        }
+    }
+}
 
 impl HorizonLogger {
     /// Create new logger instance
+    ///
+    /// Every logger made this way shares one process-wide history, so each
+    /// sees what the others logged; see `LoggerBuilder::global_history`.
+    /// Loggers from `builder` keep their own unless asked.
     pub fn new() -> Self {
-        HorizonLogger
+        Self::builder().global_history().build()
     }
 
     /// Log a debug message
-    pub fn debug(&self, component: &str, message: &str) {
-        self.log(LogLevel::DEBUG, component, message);
+    #[track_caller]
+    pub fn debug(&self, component: impl ComponentArg, message: &str) {
+        self.log_from_caller(LogLevel::DEBUG, component, message);
     }
 
     /// Log an info message
-    pub fn info(&self, component: &str, message: &str) {
-        self.log(LogLevel::INFO, component, message);
+    #[track_caller]
+    pub fn info(&self, component: impl ComponentArg, message: &str) {
+        self.log_from_caller(LogLevel::INFO, component, message);
     }
 
     /// Log a warning message
-    pub fn warn(&self, component: &str, message: &str) {
-        self.log(LogLevel::WARN, component, message);
+    #[track_caller]
+    pub fn warn(&self, component: impl ComponentArg, message: &str) {
+        self.log_from_caller(LogLevel::WARN, component, message);
     }
 
     /// Log an error message
-    pub fn error(&self, component: &str, message: &str) {
-        self.log(LogLevel::ERROR, component, message);
+    #[track_caller]
+    pub fn error(&self, component: impl ComponentArg, message: &str) {
+        self.log_from_caller(LogLevel::ERROR, component, message);
     }
 
     /// Log a critical message
-    pub fn critical(&self, component: &str, message: &str) {
-        self.log(LogLevel::CRITICAL, component, message);
+    #[track_caller]
+    pub fn critical(&self, component: impl ComponentArg, message: &str) {
+        self.log_from_caller(LogLevel::CRITICAL, component, message);
+    }
+
+    /// Log a debug message without a component
+    #[track_caller]
+    pub fn debug_msg(&self, message: &str) {
+        self.log_from_caller(LogLevel::DEBUG, "", message);
+    }
+
+    /// Log an info message without a component
+    #[track_caller]
+    pub fn info_msg(&self, message: &str) {
+        self.log_from_caller(LogLevel::INFO, "", message);
+    }
+
+    /// Log a warning message without a component
+    #[track_caller]
+    pub fn warn_msg(&self, message: &str) {
+        self.log_from_caller(LogLevel::WARN, "", message);
+    }
+
+    /// Log an error message without a component
+    #[track_caller]
+    pub fn error_msg(&self, message: &str) {
+        self.log_from_caller(LogLevel::ERROR, "", message);
+    }
+
+    /// Log a critical message without a component
+    #[track_caller]
+    pub fn critical_msg(&self, message: &str) {
+        self.log_from_caller(LogLevel::CRITICAL, "", message);
+    }
+
+    /// Log the message built by `message`, calling it only if the entry passes the filters
+    ///
+    /// The closure runs after level, directive and sampling checks, so any
+    /// side effects in it may not happen at all.
+    pub fn log_lazy<M: AsRef<str>>(&self, level: LogLevel, component: impl ComponentArg, message: impl FnOnce() -> M) {
+        self.log_lazy_with(level, component, message, CallOptions::default());
+    }
+
+    /// Log a debug message built only if it will be logged, see `log_lazy`
+    pub fn debug_with<M: AsRef<str>>(&self, component: impl ComponentArg, message: impl FnOnce() -> M) {
+        self.log_lazy(LogLevel::DEBUG, component, message);
+    }
+
+    /// Log an info message built only if it will be logged, see `log_lazy`
+    pub fn info_with<M: AsRef<str>>(&self, component: impl ComponentArg, message: impl FnOnce() -> M) {
+        self.log_lazy(LogLevel::INFO, component, message);
+    }
+
+    /// Log a warning message built only if it will be logged, see `log_lazy`
+    pub fn warn_with<M: AsRef<str>>(&self, component: impl ComponentArg, message: impl FnOnce() -> M) {
+        self.log_lazy(LogLevel::WARN, component, message);
+    }
+
+    /// Log an error message built only if it will be logged, see `log_lazy`
+    pub fn error_with<M: AsRef<str>>(&self, component: impl ComponentArg, message: impl FnOnce() -> M) {
+        self.log_lazy(LogLevel::ERROR, component, message);
+    }
+
+    /// Log a critical message built only if it will be logged, see `log_lazy`
+    pub fn critical_with<M: AsRef<str>>(&self, component: impl ComponentArg, message: impl FnOnce() -> M) {
+        self.log_lazy(LogLevel::CRITICAL, component, message);
+    }
+
+    /// Indent console messages by span nesting depth
+    pub fn set_span_indent(&self, enabled: bool) {
+        self.inner.indent_spans.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Drop entries below `level` from now on
+    pub fn set_min_level(&self, level: LogLevel) {
+        self.inner.min_level.store(level.to_u8(), Ordering::Relaxed);
+    }
+
+    /// Lowest level currently logged
+    pub fn min_level(&self) -> LogLevel {
+        LogLevel::from_u8(self.inner.min_level.load(Ordering::Relaxed)).unwrap_or(LogLevel::DEBUG)
+    }
+
+    /// Whether entries at `level` would be logged, for at least some component
+    pub fn enabled(&self, level: LogLevel) -> bool {
+        if self.inner.kill_switch.is_off() {
+            return false;
+        }
+        let min = self.inner.min_level.load(Ordering::Relaxed);
+        level.to_u8() >= min.min(self.inner.directives.floor())
     }
 
     /// Internal logging function
-    fn log(&self, level: LogLevel, component: &str, message: &str) {
-        let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S%.3f").to_string();
-        let thread_info = format!("[{:?}]", std::thread::current().id()).purple();
-        
-        // Format the log message with colors
-        let formatted_component = format!("[{}]", component).blue();
-        
-        println!("{} {} {} {} {}", 
-            timestamp.white(),
-            level.color(),
-            thread_info,
-            formatted_component,
-            message
-        );
+    pub(crate) fn log(&self, level: LogLevel, component: impl ComponentArg, message: &str) {
+        self.log_with(level, component, message, CallOptions::default());
+    }
+
+    /// `log`, noting the caller's file and line for source snippets and `EmptyMessagePolicy::Replace`
+    #[track_caller]
+    pub(crate) fn log_from_caller(&self, level: LogLevel, component: impl ComponentArg, message: &str) {
+        let options = CallOptions {
+            location: self.caller_location(level),
+            ..CallOptions::default()
+        };
+        self.log_with(level, component, message, options);
+    }
+
+    /// The caller's file and line, if anything would use them
+    #[track_caller]
+    pub(crate) fn caller_location(&self, level: LogLevel) -> Option<(&'static str, u32)> {
+        let caller = Location::caller();
+        let snippet = level >= LogLevel::ERROR && self.inner.console.shows_snippets();
+        (snippet || self.inner.empty_messages.policy == EmptyMessagePolicy::Replace)
+            .then_some((caller.file(), caller.line()))
+    }
+
+    /// Log without capturing a backtrace, even if the level is configured for one
+    pub fn log_no_backtrace(&self, level: LogLevel, component: impl ComponentArg, message: &str) {
+        let options = CallOptions {
+            backtrace: false,
+            ..CallOptions::default()
+        };
+        self.log_with(level, component, message, options);
+    }
+
+    /// Log `preformatted` so the console and text sinks print exactly it, on its own line
+    ///
+    /// For lines another tool matches byte for byte, such as a handshake.
+    /// The entry is otherwise a normal one: it passes the level and
+    /// component filters, is stored in history, and machine formats such as
+    /// JSON escape it as usual. Text output skips the pattern, colors and
+    /// ANSI policy, so colors and sanitizing the string are up to the caller.
+    pub fn raw(&self, component: impl ComponentArg, level: LogLevel, preformatted: &str) {
+        let options = CallOptions {
+            backtrace: false,
+            raw: true,
+            ..CallOptions::default()
+        };
+        self.log_with(level, component, preformatted, options);
+    }
+
+    /// Logging function with per-call options
+    pub(crate) fn log_with(&self, level: LogLevel, component: impl ComponentArg, message: &str, options: CallOptions) {
+        self.log_lazy_with(level, component, || message, options);
+    }
 
-        // Store in history
-        let entry = LogEntry {
+    /// `log_with`, building the message only once the entry passed the filters
+    fn log_lazy_with<M: AsRef<str>>(
+        &self,
+        level: LogLevel,
+        component: impl ComponentArg,
+        message: impl FnOnce() -> M,
+        options: CallOptions,
+    ) {
+        if self.inner.kill_switch.is_off() {
+            self.inner.kill_switch.suppress(1);
+            return;
+        }
+        if options.filtered && !self.enabled(level) {
+            return;
+        }
+
+        self.announce_run();
+
+        let resolved = self.inner.aliases.resolve(component.as_str());
+        // Only a renamed component has to be copied into the entry
+        let stored_component = || match &resolved {
+            Cow::Borrowed(_) => component.to_cow(),
+            Cow::Owned(renamed) => Cow::Owned(renamed.clone()),
+        };
+        let component = &*resolved;
+        if options.filtered && !self.level_passes(level, component) {
+            return;
+        }
+        let sampling = match options.filtered {
+            true => self.inner.sampling.sample(level, component),
+            false => sampling::Sampled::Unsampled,
+        };
+        let sampled = match sampling {
+            sampling::Sampled::Unsampled => None,
+            sampling::Sampled::Kept(rate) => Some(rate),
+            sampling::Sampled::Dropped => {
+                self.inner.stats.record_sampled_out(component);
+                return;
+            }
+        };
+        let mut lap = self.inner.profile.start();
+        let message = message();
+        let replacement;
+        let (message, options) = match self.inner.empty_messages.check(message.as_ref(), options.location) {
+            empty::Checked::Keep(message) => (message, options),
+            empty::Checked::Drop => return,
+            empty::Checked::Replace(callsite) => {
+                replacement = [options.fields, &callsite].concat();
+                (empty::REPLACEMENT, CallOptions { fields: &replacement, ..options })
+            }
+        };
+        if options.filtered && !self.inner.volume.allows(level, component, message.len()) {
+            self.inner.stats.record_dropped();
+            return;
+        }
+        let depth = options.depth.unwrap_or_else(span::depth);
+        let indent = if self.inner.indent_spans.load(Ordering::Relaxed) { depth } else { 0 };
+        let timestamp = self.inner.clock.now();
+        let flagged;
+        let options = match self.inner.skew.check(timestamp, || self.inner.clock.monotonic()) {
+            None => options,
+            Some(skew) => {
+                self.log(LogLevel::INFO, run::LOGGER_COMPONENT, &skew.to_string());
+                flagged = [options.fields, &[skew::SKEW_FIELD]].concat();
+                CallOptions { fields: &flagged, ..options }
+            }
+        };
+        let severity = options.severity.unwrap_or(level.default_severity());
+        let backtrace = (options.backtrace && self.inner.backtrace_level.is_some_and(|min| level >= min))
+            .then(|| Backtrace::force_capture().to_string());
+
+        // A sink logging from inside `write` would re-enter sink dispatch; queue it instead
+        let Some(guard) = reentry::enter() else {
+            let mut entry = self.new_entry(timestamp, level, stored_component(), message, backtrace);
+            entry.sampled = sampled;
+            entry.severity = severity;
+            entry.code = options.code.map(Arc::from);
+            entry.template = options.template.map(Arc::from);
+            entry.fields.extend_from_slice(options.fields);
+            entry.raw = options.raw;
+            entry.breadcrumbs = breadcrumb::take_for(level);
+            reentry::defer(reentry::Deferred {
+                logger: self.clone(),
+                entry,
+                indent,
+            });
+            return;
+        };
+
+        // The console only needs borrowed parts; the owned entry is built once, if
+        // history or a sink wants it, and moved into history after the sinks
+        let block = self.inner.group_lock.read();
+        // Checked under the group lock, which `shutdown_with_summary` waits on after sealing
+        if options.filtered && self.inner.seal.rejects(1) {
+            return;
+        }
+        self.inner.profile.mark(&mut lap, profile::Phase::Format);
+        let seq = self.inner.history.reserve_seq();
+        let correlation_id = correlation::current();
+        let mut breadcrumbs = breadcrumb::take_for(level);
+        let parts = console::LineParts {
             timestamp,
+            seq: Some(seq),
             level,
-            component: component.to_string(),
-            message: message.to_string(),
+            severity,
+            component,
+            message,
+            code: options.code,
+            correlation_id: correlation_id.as_deref(),
+            indent,
+            raw: options.raw,
+            static_fields: "",
+            location: options.location,
+            breadcrumbs: &breadcrumbs,
         };
+        self.inner.console.write_entry(&parts, backtrace.as_deref().filter(|_| self.inner.print_backtraces));
+        self.inner.stats.record(level, component, message.len());
+        self.inner.sizes.record(level, component, message);
+        self.inner.profile.mark(&mut lap, profile::Phase::Console);
 
-        if let Ok(mut history) = LOG_HISTORY.lock() {
-            history.push(entry);
-            
-            // Keep only last 1000 entries
-            if history.len() > 1000 {
-                history.remove(0);
+        // Read before the sinks so a first sink added in between is always seen by one of them
+        let preinit = self.inner.preinit.is_active();
+        let sinks = self.sinks_snapshot();
+        let buffering = preinit && sinks.is_empty();
+        if self.inner.keep_history || !sinks.is_empty() || buffering {
+            let mut entry = self.new_entry(timestamp, level, stored_component(), message, backtrace);
+            entry.seq = seq;
+            entry.sampled = sampled;
+            entry.severity = severity;
+            entry.code = options.code.map(Arc::from);
+            entry.template = options.template.map(Arc::from);
+            entry.fields.extend_from_slice(options.fields);
+            entry.raw = options.raw;
+            entry.breadcrumbs = std::mem::take(&mut breadcrumbs);
+            self.inner.profile.mark(&mut lap, profile::Phase::Format);
+            if buffering {
+                self.buffer_or_write(&entry);
+            } else {
+                self.write_sinks(&sinks, &entry);
+            }
+            self.inner.profile.mark(&mut lap, profile::Phase::Sinks);
+            if self.inner.keep_history {
+                self.inner.history.store(entry);
+                self.inner.profile.mark(&mut lap, profile::Phase::History);
             }
         }
+        drop(block);
+        self.inner.profile.finish(lap);
+
+        // Entries logged by sinks skip the sinks, so this cannot loop
+        for deferred in reentry::take() {
+            deferred.logger.record(deferred.entry, deferred.indent);
+        }
+        drop(guard);
+
+        // Outside the guard so escalations reach the sinks too
+        if options.escalate {
+            self.escalate(level, component, timestamp);
+        }
+        self.check_volume(timestamp);
+        self.report_released_pins();
+    }
+
+    /// Whether `level` passes the directive for `component`, or the minimum level
+    pub(crate) fn level_passes(&self, level: LogLevel, component: &str) -> bool {
+        let min_level = self.inner.directives.level_for(component).unwrap_or_else(|| self.min_level());
+        level >= min_level
+    }
+
+    /// An unnumbered entry tagged with the run, current span and correlation id
+    pub(crate) fn new_entry(
+        &self,
+        timestamp: Timestamp,
+        level: LogLevel,
+        component: Cow<'static, str>,
+        message: &str,
+        backtrace: Option<String>,
+    ) -> LogEntry {
+        let mut entry = LogEntry::with_component(timestamp, level, component, message);
+        entry.run_id = Some(self.inner.run_id.clone());
+        (entry.span_id, entry.parent_id) = span::current_ids();
+        entry.correlation_id = correlation::current();
+        entry.backtrace = backtrace;
+        entry.fields = self.inner.static_fields.clone();
+        entry
+    }
+
+    /// Number, print, count and store an entry without handing it to sinks
+    fn record(&self, mut entry: LogEntry, indent: usize) {
+        if self.inner.kill_switch.is_off() {
+            self.inner.kill_switch.suppress(1);
+            return;
+        }
+        entry.seq = self.inner.history.reserve_seq();
+        let backtrace = entry.backtrace.as_deref().filter(|_| self.inner.print_backtraces);
+        self.inner.console.write_entry(&console::LineParts::of(&entry, indent), backtrace);
+        self.inner.stats.record(entry.level, &entry.component, entry.message.len());
+        self.inner.sizes.record(entry.level, &entry.component, &entry.message);
+        if self.inner.keep_history {
+            self.inner.history.store(entry);
+        }
+    }
+
+    /// Render an entry using this logger's format options
+    pub fn format_entry(&self, entry: &LogEntry, format: Format) -> String {
+        format::format_entry(entry, format, &self.format_options())
+    }
+
+    /// Options sinks currently render with
+    pub(crate) fn format_options(&self) -> Arc<FormatOptions> {
+        self.inner.format.read().map(|options| options.clone()).unwrap_or_default()
+    }
+
+    /// Change what sinks that have no options of their own do with ANSI escapes in messages
+    ///
+    /// Sinks strip them by default. The console has its own policy, see
+    /// `LoggerBuilder::console_ansi_policy`, and history always strips them.
+    pub fn set_ansi_policy(&self, policy: AnsiPolicy) {
+        if let Ok(mut format) = self.inner.format.write() {
+            let mut options = FormatOptions::clone(&format);
+            options.ansi = policy;
+            *format = Arc::new(options);
+        }
     }
 
-    /// Get log history
+    /// Change the markup of text lines written by sinks that have no options of their own
+    ///
+    /// Sinks write plain text by default; the console keeps its own colors.
+    pub fn set_color_codes(&self, color_codes: ColorCodes) {
+        if let Ok(mut format) = self.inner.format.write() {
+            let mut options = FormatOptions::clone(&format);
+            options.color_codes = color_codes;
+            *format = Arc::new(options);
+        }
+    }
+
+    /// Change the layout of text lines written by sinks that have no pattern of their own
+    ///
+    /// `None` restores the default layout. Sinks with their own pattern and
+    /// the console are not affected.
+    pub fn set_text_pattern(&self, pattern: Option<Pattern>) {
+        if let Ok(mut format) = self.inner.format.write() {
+            let mut options = FormatOptions::clone(&format);
+            options.text_pattern = pattern;
+            *format = Arc::new(options);
+        }
+    }
+
+    /// Get log history, in `seq` order whatever the entries' timestamps
+    ///
+    /// Copies every entry; `get_history_shared` is cheaper for frequent reads.
     pub fn get_history(&self) -> Vec<LogEntry> {
-        LOG_HISTORY.lock()
-            .map(|history| history.clone())
-            .unwrap_or_default()
+        self.get_history_shared().into_iter().map(Arc::unwrap_or_clone).collect()
+    }
+
+    /// Get log history as the stored entries themselves, without copying them
+    ///
+    /// Each clone of an `Arc` is a reference count increment, so polling this
+    /// several times a second costs little however long the messages are.
+    /// The entries are shared with the history and never change: a dedup
+    /// repeat or a pin stores an updated copy instead, so a snapshot keeps
+    /// the `repeat_count` and `pinned` it had when it was taken.
+    pub fn get_history_shared(&self) -> Vec<Arc<LogEntry>> {
+        self.prune_history_now();
+        self.inner.history.snapshot_shared()
+    }
+}
+
+impl Default for HorizonLogger {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 // Convenience macros
+//
+// The two-argument form logs without a component; inline format arguments
+// (`log_info!(logger, "took {ms}ms")`) are supported there. Any `HorizonLog`
+// works as the logger. The component can be any `AsRef<str>` and is
+// evaluated once; a literal one is stored without copying. Debug builds
+// warn once per callsite about components and messages that look swapped,
+// see `callsite`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __log {
+    ($name:literal, $method:ident, $msg_method:ident $(,)?) => {
+        compile_error!(concat!(
+            $name, "! needs a logger and a message, e.g. ", $name, "!(logger, \"NETWORK\", \"connected\")"
+        ))
+    };
+    ($name:literal, $method:ident, $msg_method:ident, $logger:expr $(,)?) => {
+        compile_error!(concat!($name, "! needs a message, e.g. ", $name, "!(logger, \"NETWORK\", \"connected\")"))
+    };
+    ($name:literal, $method:ident, $msg_method:ident, $logger:expr, $message:literal $(,)?) => {{
+        #[allow(unused_imports)]
+        use $crate::HorizonLog as _;
+        match &$logger {
+            logger => {
+                #[cfg(debug_assertions)]
+                {
+                    static WARNED: ::std::sync::atomic::AtomicBool = ::std::sync::atomic::AtomicBool::new(false);
+                    $crate::check_message(logger, $message, &WARNED);
+                }
+                logger.$msg_method(&format!($message))
+            }
+        }
+    }};
+    ($name:literal, $method:ident, $msg_method:ident, $logger:expr, $component:expr $(,)?) => {
+        compile_error!(concat!(
+            $name, "! needs a message after the component, e.g. ", $name, "!(logger, \"NETWORK\", \"connected\")"
+        ))
+    };
+    ($name:literal, $method:ident, $msg_method:ident, $logger:expr, $component:literal, $($arg:tt)+) => {{
+        #[allow(unused_imports)]
+        use $crate::HorizonLog as _;
+        match &$logger {
+            logger => {
+                #[cfg(debug_assertions)]
+                {
+                    static WARNED: ::std::sync::atomic::AtomicBool = ::std::sync::atomic::AtomicBool::new(false);
+                    $crate::check_component(logger, $component, &WARNED);
+                }
+                logger.$method($component, &format!($($arg)+))
+            }
+        }
+    }};
+    ($name:literal, $method:ident, $msg_method:ident, $logger:expr, $component:expr, $($arg:tt)+) => {{
+        match (&$logger, &$component) {
+            (logger, component) => {
+                let component: &str = ::core::convert::AsRef::<str>::as_ref(component);
+                #[cfg(debug_assertions)]
+                {
+                    static WARNED: ::std::sync::atomic::AtomicBool = ::std::sync::atomic::AtomicBool::new(false);
+                    $crate::check_component(logger, component, &WARNED);
+                }
+                $crate::HorizonLog::$method(logger, component, &format!($($arg)+))
+            }
+        }
+    }};
+}
+
 #[macro_export]
 macro_rules! log_debug {
-    ($logger:expr, $component:expr, $($arg:tt)*) => {
-        $logger.debug($component, &format!($($arg)*))
-    }
+    ($($arg:tt)*) => {
+        $crate::__log!("log_debug", debug, debug_msg, $($arg)*)
+    };
 }
 
 #[macro_export]
 macro_rules! log_info {
-    ($logger:expr, $component:expr, $($arg:tt)*) => {
-        $logger.info($component, &format!($($arg)*))
-    }
+    ($($arg:tt)*) => {
+        $crate::__log!("log_info", info, info_msg, $($arg)*)
+    };
 }
 
 #[macro_export]
 macro_rules! log_warn {
-    ($logger:expr, $component:expr, $($arg:tt)*) => {
-        $logger.warn($component, &format!($($arg)*))
-    }
+    ($($arg:tt)*) => {
+        $crate::__log!("log_warn", warn, warn_msg, $($arg)*)
+    };
 }
 
 #[macro_export]
 macro_rules! log_error {
-    ($logger:expr, $component:expr, $($arg:tt)*) => {
-        $logger.error($component, &format!($($arg)*))
-    }
+    ($($arg:tt)*) => {
+        $crate::__log!("log_error", error, error_msg, $($arg)*)
+    };
 }
 
 #[macro_export]
 macro_rules! log_critical {
-    ($logger:expr, $component:expr, $($arg:tt)*) => {
-        $logger.critical($component, &format!($($arg)*))
-    }
+    ($($arg:tt)*) => {
+        $crate::__log!("log_critical", critical, critical_msg, $($arg)*)
+    };
 }
 
-// Tests
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Pretty-print a value at DEBUG, without evaluating or rendering it when DEBUG is filtered out
+#[macro_export]
+macro_rules! log_debug_pretty {
+    ($logger:expr, $component:expr, $label:expr, $value:expr $(,)?) => {
+        if $logger.enabled($crate::LogLevel::DEBUG) {
+            $logger.debug_pretty($component, $label, &$value);
+        }
+    };
+}
 
-    #[test]
-    fn test_logger() {
-        let logger = HorizonLogger::new();
-        
-        logger.debug("TEST", "This is a debug message");
-        logger.info("TEST", "This is an info message");
-        logger.warn("TEST", "This is a warning message");
-        logger.error("TEST", "This is an error message");
-        logger.critical("TEST", "This is a critical message");
-        
-        let history = logger.get_history();
-        assert_eq!(history.len(), 5);
-    }
+/// Log an ERROR with the condition, file and line when `$cond` is false
+///
+/// What happens next depends on `set_assert_action`. The condition is
+/// evaluated once; the message is only formatted on failure.
+#[macro_export]
+macro_rules! log_assert {
+    ($logger:expr, $component:expr, $cond:expr $(,)?) => {
+        $crate::log_assert!($logger, $component, $cond, "condition was false")
+    };
+    ($logger:expr, $component:expr, $cond:expr, $($arg:tt)+) => {
+        if !($cond) {
+            $logger.assert_failed(
+                $crate::LogLevel::ERROR,
+                $component,
+                stringify!($cond),
+                file!(),
+                line!(),
+                &format!($($arg)+),
+            );
+        }
+    };
+}
+
+/// `log_assert!` logging at CRITICAL
+#[macro_export]
+macro_rules! log_assert_critical {
+    ($logger:expr, $component:expr, $cond:expr $(,)?) => {
+        $crate::log_assert_critical!($logger, $component, $cond, "condition was false")
+    };
+    ($logger:expr, $component:expr, $cond:expr, $($arg:tt)+) => {
+        if !($cond) {
+            $logger.assert_failed(
+                $crate::LogLevel::CRITICAL,
+                $component,
+                stringify!($cond),
+                file!(),
+                line!(),
+                &format!($($arg)+),
+            );
+        }
+    };
+}
+
+/// `log_assert!` that is only checked in debug builds, like `debug_assert!`
+#[macro_export]
+macro_rules! log_debug_assert {
+    ($($arg:tt)*) => {
+        if cfg!(debug_assertions) {
+            $crate::log_assert!($($arg)*);
+        }
+    };
 }
 
 // horizon_logger/src/examples.rs
@@ -202,4 +1061,100 @@ pub fn example_usage() {
     // Multiple components
     logger.info("GAME/COMBAT", "Player dealt 50 damage");
     logger.debug("NETWORK/WEBSOCKET", "Processing message batch");
+
+    // Without a component
+    logger.info_msg("Server ready");
+    let port = 8080;
+    log_info!(logger, "Listening on port {port}");
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::CaptureLogger;
+
+    #[test]
+    fn test_logger() {
+        let logger = CaptureLogger::new();
+        
+        logger.debug("TEST", "This is a debug message");
+        logger.info("TEST", "This is an info message");
+        logger.warn("TEST", "This is a warning message");
+        logger.error("TEST", "This is an error message");
+        logger.critical("TEST", "This is a critical message");
+        
+        let history = logger.get_history();
+        assert_eq!(history.len(), 5);
+    }
+
+    #[test]
+    fn test_backtrace_captured_at_threshold() {
+        let logger = HorizonLogger::builder()
+            .announce_run(false)
+            .capture_backtrace(LogLevel::ERROR)
+            .build();
+
+        logger.warn("TEST", "below threshold");
+        logger.error("TEST", "at threshold");
+        logger.log_no_backtrace(LogLevel::CRITICAL, "TEST", "skipped");
+
+        let history = logger.get_history();
+        assert!(history[0].backtrace.is_none());
+        let backtrace = history[1].backtrace.as_deref().unwrap();
+        assert!(backtrace.contains("test_backtrace_captured_at_threshold"));
+        assert!(history[1].to_json().contains("\"backtrace\":"));
+        assert!(history[2].backtrace.is_none());
+    }
+
+    #[test]
+    fn test_lazy_messages_built_only_when_logged() {
+        let logger = CaptureLogger::from_builder(HorizonLogger::builder().min_level(LogLevel::INFO));
+        logger.apply_directive("info,ai=debug").unwrap();
+        logger.set_component_sampling("AI/PATH", SampleRate::OneIn(2));
+        let built = std::cell::Cell::new(0);
+        let message = || {
+            built.set(built.get() + 1);
+            format!("path graph: {} nodes", built.get())
+        };
+
+        logger.debug_with("GAME", message);
+        logger.debug_with("AI", message);
+        logger.info_with("GAME", message);
+        logger.debug_with("AI/PATH", message);
+        logger.debug_with("AI/PATH", message);
+        logger.log_lazy(LogLevel::CRITICAL, "AI", || "static message");
+
+        assert_eq!(built.get(), 3);
+        assert_eq!(
+            logger.messages(),
+            vec!["path graph: 1 nodes", "path graph: 2 nodes", "path graph: 3 nodes", "static message"]
+        );
+    }
+
+    #[test]
+    fn test_raw_lines_are_byte_exact() {
+        let buf = console::tests::SharedBuf(Default::default());
+        let builder = HorizonLogger::builder()
+            .console(console::Console::new(Box::new(buf.clone()), true))
+            .min_level(LogLevel::INFO)
+            .announce_run(false);
+        let logger = builder.build();
+        let lines = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = lines.clone();
+        logger.tee_formatted(move |_, line| seen.lock().unwrap().push(line.to_string()));
+        logger.set_text_pattern(Some("{level} {message}".parse().unwrap()));
+
+        let handshake = "HORIZON-READY port=7777 \"name\"\tslot=\x1b[1m3";
+        logger.raw("NET", LogLevel::INFO, handshake);
+        logger.raw("NET", LogLevel::DEBUG, "filtered out");
+
+        assert_eq!(buf.contents(), format!("{}\n", handshake));
+        assert_eq!(*lines.lock().unwrap(), [handshake]);
+        let entry = logger.get_history().remove(0);
+        assert_eq!((entry.level, &*entry.component), (LogLevel::INFO, "NET"));
+        let json: serde_json::Value = serde_json::from_str(&logger.format_entry(&entry, Format::Json)).unwrap();
+        assert_eq!(json["message"], "HORIZON-READY port=7777 \"name\"\tslot=3");
+        assert!(logger.format_entry(&entry, Format::Json).contains(r#"port=7777 \"name\"\tslot"#));
+    }
 }
@@ -0,0 +1,290 @@
+//! Component namespaces for plugins, see `HorizonLogger::register_namespace`
+//!
+//! The host registers a prefix per plugin and hands the plugin the handle,
+//! keeping a clone to control it. Component loggers made from the handle
+//! share the namespace's state, so checking it on each call is one atomic
+//! load rather than a match on the component name.
+
+use crate::history::component_matches;
+use crate::run::LOGGER_COMPONENT;
+use crate::{ComponentLogger, ComponentStats, HorizonLogger, LogLevel};
+use std::borrow::Cow;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// `Namespace::gate` while muted; above every level
+const MUTED: u8 = u8::MAX - 1;
+
+/// `Namespace::gate` once revoked
+const REVOKED: u8 = u8::MAX;
+
+/// One registered namespace, shared by its handles and component loggers
+pub(crate) struct Namespace {
+    prefix: String,
+    /// Lowest level let through (as `LogLevel::to_u8`), or `MUTED` or `REVOKED`
+    gate: AtomicU8,
+    /// Level set with `set_level`, put back by `unmute`
+    level: AtomicU8,
+    /// Entries held back by the level or a mute
+    suppressed: AtomicU64,
+    /// Serializes the host's changes to `gate`
+    control: Mutex<()>,
+}
+
+impl Namespace {
+    fn new(prefix: String) -> Self {
+        Namespace {
+            prefix,
+            gate: AtomicU8::new(LogLevel::DEBUG.to_u8()),
+            level: AtomicU8::new(LogLevel::DEBUG.to_u8()),
+            suppressed: AtomicU64::new(0),
+            control: Mutex::new(()),
+        }
+    }
+
+    /// Whether an entry at `level` may be logged, counting it if not
+    #[inline]
+    pub(crate) fn allows(&self, level: LogLevel) -> bool {
+        let gate = self.gate.load(Ordering::Relaxed);
+        if level.to_u8() >= gate {
+            return true;
+        }
+        if gate != REVOKED {
+            self.suppressed.fetch_add(1, Ordering::Relaxed);
+        }
+        false
+    }
+
+    /// `allows` without counting, for `enabled`
+    #[inline]
+    pub(crate) fn passes(&self, level: LogLevel) -> bool {
+        level.to_u8() >= self.gate.load(Ordering::Relaxed)
+    }
+
+    /// Change the gate unless the namespace is revoked, returning whether it was
+    fn update(&self, gate: impl FnOnce(u8) -> u8) -> bool {
+        let _control = self.control.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let current = self.gate.load(Ordering::Relaxed);
+        if current == REVOKED {
+            return false;
+        }
+        self.gate.store(gate(current), Ordering::Relaxed);
+        true
+    }
+}
+
+/// A namespace registered with `HorizonLogger::register_namespace`
+///
+/// Cloning shares the namespace: the plugin makes component loggers with
+/// its handle while the host changes the level, mutes or revokes it on
+/// another. Entries logged under the prefix some other way, such as
+/// `logger.info("PLUGIN/economy", ..)` or through `ComponentLogger::logger`,
+/// are not affected.
+#[derive(Clone)]
+pub struct NamespaceHandle {
+    logger: HorizonLogger,
+    namespace: Arc<Namespace>,
+}
+
+impl NamespaceHandle {
+    /// The prefix every component in the namespace starts with
+    pub fn prefix(&self) -> &str {
+        &self.namespace.prefix
+    }
+
+    /// A logger for `name` within the namespace, `prefix/name`, or for the prefix itself if `name` is empty
+    pub fn component(&self, name: &str) -> ComponentLogger {
+        let component = match name {
+            "" => self.namespace.prefix.clone(),
+            name => format!("{}/{}", self.namespace.prefix, name),
+        };
+        ComponentLogger::new(self.logger.clone(), Cow::Owned(component), Some(self.namespace.clone()))
+    }
+
+    /// Only log entries at or above `level` from the namespace, on top of the logger's own filters
+    ///
+    /// Takes effect once unmuted; does nothing once revoked.
+    pub fn set_level(&self, level: LogLevel) {
+        let level = level.to_u8();
+        self.namespace.update(|gate| {
+            self.namespace.level.store(level, Ordering::Relaxed);
+            if gate == MUTED {
+                MUTED
+            } else {
+                level
+            }
+        });
+    }
+
+    /// The level set with `set_level`; DEBUG, letting everything through, at first
+    pub fn level(&self) -> LogLevel {
+        LogLevel::from_u8(self.namespace.level.load(Ordering::Relaxed)).unwrap_or(LogLevel::DEBUG)
+    }
+
+    /// Hold back every entry from the namespace until `unmute`
+    pub fn mute(&self) {
+        self.namespace.update(|_| MUTED);
+    }
+
+    /// Let the namespace log at its level again
+    pub fn unmute(&self) {
+        self.namespace.update(|_| self.namespace.level.load(Ordering::Relaxed));
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.namespace.gate.load(Ordering::Relaxed) == MUTED
+    }
+
+    /// Stop the namespace logging for good, with a WARN under `LOGGER` the first time
+    ///
+    /// Its log calls return straight away from then on, and registering
+    /// the prefix again gives back the revoked namespace.
+    pub fn revoke(&self) {
+        if !self.namespace.update(|_| REVOKED) {
+            return;
+        }
+        let message = format!(
+            "namespace {} revoked, {} entries suppressed before",
+            self.namespace.prefix,
+            self.suppressed()
+        );
+        self.logger.log(LogLevel::WARN, LOGGER_COMPONENT, &message);
+    }
+
+    pub fn is_revoked(&self) -> bool {
+        self.namespace.gate.load(Ordering::Relaxed) == REVOKED
+    }
+
+    /// Entries held back by the namespace's level or a mute; calls after `revoke` aren't counted
+    pub fn suppressed(&self) -> u64 {
+        self.namespace.suppressed.load(Ordering::Relaxed)
+    }
+
+    /// The logger's stats summed over the components in the namespace, under the prefix
+    ///
+    /// Components past the stats tracking limit are counted under `<other>`
+    /// instead, and so are missing here.
+    pub fn stats(&self) -> ComponentStats {
+        let mut total = ComponentStats {
+            component: self.namespace.prefix.clone(),
+            messages: [0; LogLevel::COUNT],
+            bytes: [0; LogLevel::COUNT],
+            sampled_out: 0,
+        };
+        for stats in self.logger.component_stats() {
+            if !component_matches(&stats.component, &self.namespace.prefix) {
+                continue;
+            }
+            for level in 0..LogLevel::COUNT {
+                total.messages[level] += stats.messages[level];
+                total.bytes[level] += stats.bytes[level];
+            }
+            total.sampled_out += stats.sampled_out;
+        }
+        total
+    }
+}
+
+impl fmt::Debug for NamespaceHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("NamespaceHandle").field(&self.namespace.prefix).finish()
+    }
+}
+
+/// Namespaces registered with a logger, kept so registering a prefix twice shares its state
+#[derive(Default)]
+pub(crate) struct Namespaces(Mutex<Vec<Arc<Namespace>>>);
+
+impl HorizonLogger {
+    /// Register `prefix` as a namespace, e.g. `PLUGIN/economy`, and get its handle
+    ///
+    /// Registering the same prefix again returns a handle to the same
+    /// namespace, revoked or muted as it is.
+    pub fn register_namespace(&self, prefix: &str) -> NamespaceHandle {
+        let prefix = prefix.trim_end_matches('/');
+        let mut namespaces = self.inner.namespaces.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let namespace = match namespaces.iter().find(|namespace| namespace.prefix == prefix) {
+            Some(namespace) => namespace.clone(),
+            None => {
+                let namespace = Arc::new(Namespace::new(prefix.to_string()));
+                namespaces.push(namespace.clone());
+                namespace
+            }
+        };
+        NamespaceHandle {
+            logger: self.clone(),
+            namespace,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::CaptureLogger;
+    use crate::{log_info, HorizonLog};
+
+    #[test]
+    fn test_plugins_are_controlled_separately() {
+        let logger = CaptureLogger::new();
+        let economy = logger.register_namespace("PLUGIN/economy");
+        let weather = logger.register_namespace("PLUGIN/weather/");
+        let market = economy.component("market");
+        let storms = weather.component("storms");
+        assert_eq!(market.name(), "PLUGIN/economy/market");
+        assert_eq!(weather.component("").name(), "PLUGIN/weather");
+
+        market.info("prices updated");
+        storms.info("storm forming");
+        economy.mute();
+        assert!(economy.is_muted());
+        market.critical("bank run");
+        let trades = 3;
+        log_info!(market, "{trades} trades");
+        market.event(LogLevel::WARN).log("event while muted");
+        HorizonLog::log(&market, LogLevel::ERROR, "ledger", "nested while muted");
+        assert!(!HorizonLog::enabled(&market, LogLevel::CRITICAL));
+        storms.warn("storm landed");
+        assert_eq!(economy.suppressed(), 4);
+        assert_eq!(weather.suppressed(), 0);
+
+        weather.set_level(LogLevel::WARN);
+        storms.info("drizzle");
+        storms.error("flooding");
+        economy.set_level(LogLevel::ERROR);
+        economy.unmute();
+        market.warn("below the level");
+        market.error("ledger mismatch");
+
+        weather.revoke();
+        weather.revoke();
+        assert!(weather.is_revoked());
+        storms.critical("after revoke");
+        weather.unmute();
+        weather.set_level(LogLevel::DEBUG);
+        logger.register_namespace("PLUGIN/weather").component("rain").critical("re-registered");
+        assert!(logger.register_namespace("PLUGIN/weather").is_revoked());
+        assert_eq!(weather.suppressed(), 1);
+
+        assert_eq!(
+            logger.messages(),
+            [
+                "prices updated",
+                "storm forming",
+                "storm landed",
+                "flooding",
+                "ledger mismatch",
+                "namespace PLUGIN/weather revoked, 1 entries suppressed before"
+            ]
+        );
+        assert_eq!(logger.history_by_component("LOGGER").len(), 1);
+
+        let stats = economy.stats();
+        assert_eq!(stats.component, "PLUGIN/economy");
+        assert_eq!(stats.total_messages(), 2);
+        assert_eq!(stats.messages_at(LogLevel::ERROR), 1);
+        assert_eq!(weather.stats().total_messages(), 3);
+        assert_eq!(economy.level(), LogLevel::ERROR);
+    }
+}
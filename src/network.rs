@@ -0,0 +1,325 @@
+//! Ship entries to a collector over UDP or TCP
+//!
+//! Entries are formatted on the logging thread and queued; a background
+//! thread does all socket I/O. Over TCP the queue also absorbs entries
+//! while the collector is unreachable, and the connection is retried with
+//! exponential backoff.
+
+use crate::format::{format_entry, Format, FormatOptions};
+use crate::sink::Sink;
+use crate::LogEntry;
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::net::{SocketAddr, TcpStream, UdpSocket};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Entries held while the collector is unreachable, by default
+const DEFAULT_QUEUE_CAPACITY: usize = 10_000;
+
+/// First reconnect delay, doubled after every failed attempt
+const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Longest delay between reconnect attempts
+const MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Time allowed for a single TCP connect attempt
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Longest `flush` waits for the queue to drain
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Sends each entry as one line to a remote collector
+pub struct NetworkSink {
+    addr: SocketAddr,
+    format: Format,
+    capacity: usize,
+    /// Sends over UDP rather than TCP
+    udp: bool,
+    shared: Arc<Shared>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+struct Shared {
+    state: Mutex<Queue>,
+    changed: Condvar,
+    dropped: AtomicU64,
+    /// A UDP socket is bound, or a TCP connection is up
+    connected: AtomicBool,
+}
+
+struct Queue {
+    lines: VecDeque<String>,
+    /// The worker holds a line it has not finished sending
+    sending: bool,
+    closed: bool,
+}
+
+enum Transport {
+    Udp(UdpSocket),
+    Tcp(Option<TcpStream>),
+}
+
+impl NetworkSink {
+    /// Send one datagram per entry to `addr`; nothing is retried
+    pub fn udp(addr: SocketAddr) -> io::Result<Self> {
+        Self::start(addr, Transport::udp(addr)?)
+    }
+
+    /// Stream newline-delimited entries to `addr`, reconnecting when the connection drops
+    pub fn tcp(addr: SocketAddr) -> io::Result<Self> {
+        Self::start(addr, Transport::Tcp(None))
+    }
+
+    fn start(addr: SocketAddr, transport: Transport) -> io::Result<Self> {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(Queue {
+                lines: VecDeque::new(),
+                sending: false,
+                closed: false,
+            }),
+            changed: Condvar::new(),
+            dropped: AtomicU64::new(0),
+            connected: AtomicBool::new(matches!(transport, Transport::Udp(_))),
+        });
+
+        let udp = matches!(transport, Transport::Udp(_));
+        let thread = spawn(&shared, addr, transport)?;
+        Ok(NetworkSink {
+            addr,
+            format: Format::Text,
+            capacity: DEFAULT_QUEUE_CAPACITY,
+            udp,
+            shared,
+            thread: Mutex::new(Some(thread)),
+        })
+    }
+
+    /// Send entries in the given format instead of plain text
+    pub fn with_format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Maximum number of entries waiting to be sent; newer entries are dropped beyond it
+    pub fn with_queue_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Entries dropped because the queue was full
+    pub fn dropped(&self) -> u64 {
+        self.shared.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> io::Result<MutexGuard<'_, Queue>> {
+        self.state
+            .lock()
+            .map_err(|_| io::Error::other("network sink lock poisoned"))
+    }
+
+    /// Wait for the next line; `None` once the sink is closed and drained
+    fn next_line(&self) -> Option<String> {
+        let mut queue = self.lock().ok()?;
+        loop {
+            if let Some(line) = queue.lines.pop_front() {
+                queue.sending = true;
+                return Some(line);
+            }
+            if queue.closed {
+                return None;
+            }
+            queue = self.changed.wait(queue).ok()?;
+        }
+    }
+
+    /// Finish the line taken by `next_line`, putting it back if it wasn't sent
+    fn finish(&self, unsent: Option<String>) {
+        if let Ok(mut queue) = self.lock() {
+            if let Some(line) = unsent {
+                queue.lines.push_front(line);
+            }
+            queue.sending = false;
+        }
+        self.changed.notify_all();
+    }
+
+    /// Sleep for `delay` unless the sink is closed first; returns whether it was closed
+    fn wait_closed(&self, delay: Duration) -> bool {
+        let Ok(queue) = self.lock() else {
+            return true;
+        };
+        self.changed
+            .wait_timeout_while(queue, delay, |queue| !queue.closed)
+            .map_or(true, |(queue, _)| queue.closed)
+    }
+}
+
+impl Transport {
+    /// A UDP socket on any local port, of `addr`'s family
+    fn udp(addr: SocketAddr) -> io::Result<Self> {
+        let local: SocketAddr = if addr.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0u16; 8], 0).into()
+        };
+        Ok(Transport::Udp(UdpSocket::bind(local)?))
+    }
+}
+
+/// Start the thread doing the sink's socket I/O
+fn spawn(shared: &Arc<Shared>, addr: SocketAddr, transport: Transport) -> io::Result<JoinHandle<()>> {
+    let worker = shared.clone();
+    thread::Builder::new()
+        .name("horizon-network-sink".into())
+        .spawn(move || run(&worker, addr, transport))
+}
+
+fn run(shared: &Shared, addr: SocketAddr, mut transport: Transport) {
+    let mut backoff = INITIAL_BACKOFF;
+
+    loop {
+        if let Transport::Tcp(stream @ None) = &mut transport {
+            match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+                Ok(connected) => {
+                    *stream = Some(connected);
+                    shared.connected.store(true, Ordering::Relaxed);
+                    backoff = INITIAL_BACKOFF;
+                }
+                Err(_) => {
+                    if shared.wait_closed(backoff) {
+                        return;
+                    }
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                    continue;
+                }
+            }
+        }
+
+        let Some(line) = shared.next_line() else {
+            return;
+        };
+
+        // Datagrams are fire-and-forget, so a failed UDP send is simply lost
+        let failed = send(&mut transport, addr, &line).is_err();
+        if failed && matches!(transport, Transport::Tcp(_)) {
+            transport = Transport::Tcp(None);
+            shared.connected.store(false, Ordering::Relaxed);
+            shared.finish(Some(line));
+        } else {
+            shared.finish(None);
+        }
+    }
+}
+
+fn send(transport: &mut Transport, addr: SocketAddr, line: &str) -> io::Result<()> {
+    match transport {
+        Transport::Udp(socket) => socket.send_to(line.as_bytes(), addr).map(|_| ()),
+        Transport::Tcp(stream) => {
+            let stream = stream.as_mut().ok_or(io::ErrorKind::NotConnected)?;
+            if peer_closed(stream)? {
+                return Err(io::ErrorKind::ConnectionReset.into());
+            }
+            stream.write_all(line.as_bytes())?;
+            stream.write_all(b"\n")
+        }
+    }
+}
+
+/// Whether the collector has closed its end; a write would otherwise appear to succeed once
+fn peer_closed(stream: &TcpStream) -> io::Result<bool> {
+    stream.set_nonblocking(true)?;
+    let result = match stream.peek(&mut [0u8; 1]) {
+        Ok(0) => Ok(true),
+        Ok(_) => Ok(false),
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(false),
+        Err(e) => Err(e),
+    };
+    stream.set_nonblocking(false)?;
+    result
+}
+
+impl Sink for NetworkSink {
+    fn write(&self, entry: &LogEntry, options: &FormatOptions) -> io::Result<()> {
+        let line = format_entry(entry, self.format, options);
+        let mut queue = self.shared.lock()?;
+        if queue.lines.len() >= self.capacity {
+            self.shared.dropped.fetch_add(1, Ordering::Relaxed);
+            return Ok(());
+        }
+        queue.lines.push_back(line);
+        drop(queue);
+        self.shared.changed.notify_all();
+        Ok(())
+    }
+
+    /// Wait briefly for queued entries to be sent
+    fn flush(&self) -> io::Result<()> {
+        let deadline = Instant::now() + FLUSH_TIMEOUT;
+        let mut queue = self.shared.lock()?;
+        while !queue.lines.is_empty() || queue.sending {
+            let Some(remaining) = deadline.checked_duration_since(Instant::now()) else {
+                break;
+            };
+            queue = self
+                .shared
+                .changed
+                .wait_timeout(queue, remaining)
+                .map_err(|_| io::Error::other("network sink lock poisoned"))?
+                .0;
+        }
+        Ok(())
+    }
+
+    /// Queue the probe, wait for it to be sent, and check the collector is connected
+    fn self_test(&self, probe: &LogEntry, options: &FormatOptions) -> io::Result<()> {
+        self.write(probe, options)?;
+        self.flush()?;
+        let sent = self.shared.lock()?.lines.is_empty();
+        if !sent || !self.shared.connected.load(Ordering::Relaxed) {
+            return Err(io::Error::new(io::ErrorKind::NotConnected, format!("not connected to {}", self.addr)));
+        }
+        Ok(())
+    }
+
+    /// Start a new sender thread with its own socket, dropping the lines the parent had queued
+    ///
+    /// Over TCP the child connects to the collector itself, so its lines
+    /// never interleave with the parent's on one connection.
+    fn restart_after_fork(&self) -> io::Result<()> {
+        let transport = if self.udp {
+            Transport::udp(self.addr)?
+        } else {
+            Transport::Tcp(None)
+        };
+        if let Ok(mut queue) = self.shared.lock() {
+            queue.lines.clear();
+            queue.sending = false;
+        }
+        self.shared.connected.store(self.udp, Ordering::Relaxed);
+        let thread = spawn(&self.shared, self.addr, transport)?;
+        let mut current = self.thread.lock().map_err(|_| io::Error::other("network sink lock poisoned"))?;
+        if let Some(parents) = current.replace(thread) {
+            // The parent's sender thread is not in this process and can't be joined
+            std::mem::forget(parents);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for NetworkSink {
+    fn drop(&mut self) {
+        if let Ok(mut queue) = self.shared.lock() {
+            queue.closed = true;
+        }
+        self.shared.changed.notify_all();
+        let thread = self.thread.get_mut().unwrap_or_else(|poisoned| poisoned.into_inner()).take();
+        if let Some(thread) = thread {
+            let _ = thread.join();
+        }
+    }
+}
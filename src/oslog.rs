@@ -0,0 +1,198 @@
+//! Forward entries to Apple's unified logging system
+//!
+//! Each component becomes an `os_log` category under the subsystem given to
+//! `OsLogSink::new`, so entries can be filtered in Console.app or with
+//! `log stream --predicate 'subsystem == "..."'`. Messages are logged as
+//! `%{public}s` and are never redacted to `<private>`.
+//!
+//! On targets other than macOS the sink accepts entries and discards them.
+
+use crate::format::FormatOptions;
+use crate::sink::Sink;
+use crate::LogEntry;
+use std::io;
+
+#[cfg(target_os = "macos")]
+mod ffi {
+    use std::ffi::{c_char, c_void};
+
+    pub(super) type OsLog = *mut c_void;
+
+    pub(super) const OS_LOG_TYPE_DEFAULT: u8 = 0x00;
+    pub(super) const OS_LOG_TYPE_INFO: u8 = 0x01;
+    pub(super) const OS_LOG_TYPE_DEBUG: u8 = 0x02;
+    pub(super) const OS_LOG_TYPE_ERROR: u8 = 0x10;
+    pub(super) const OS_LOG_TYPE_FAULT: u8 = 0x11;
+
+    extern "C" {
+        /// Marks the image the format string lives in
+        pub(super) static __dso_handle: c_void;
+
+        pub(super) fn os_log_create(subsystem: *const c_char, category: *const c_char) -> OsLog;
+        pub(super) fn os_log_type_enabled(log: OsLog, kind: u8) -> bool;
+
+        /// What the `os_log_with_type` macro expands to
+        pub(super) fn _os_log_impl(
+            dso: *const c_void,
+            log: OsLog,
+            kind: u8,
+            format: *const c_char,
+            buf: *const u8,
+            size: u32,
+        );
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod imp {
+    use super::ffi;
+    use crate::LogLevel;
+    use std::collections::HashMap;
+    use std::ffi::CString;
+    use std::sync::Mutex;
+
+    /// The one format string every entry is logged with
+    static FORMAT: &std::ffi::CStr = c"%{public}s";
+
+    /// An `os_log_t`; handles are immutable and safe to use from any thread
+    #[derive(Clone, Copy)]
+    struct Handle(ffi::OsLog);
+
+    unsafe impl Send for Handle {}
+
+    pub(super) struct OsLog {
+        subsystem: CString,
+        /// One handle per component, created on first use and never released
+        categories: Mutex<HashMap<String, Handle>>,
+    }
+
+    /// The `os_log` type a level is logged as
+    pub(super) fn log_type(level: LogLevel) -> u8 {
+        match level {
+            LogLevel::DEBUG => ffi::OS_LOG_TYPE_DEBUG,
+            LogLevel::INFO => ffi::OS_LOG_TYPE_INFO,
+            LogLevel::WARN => ffi::OS_LOG_TYPE_DEFAULT,
+            LogLevel::ERROR => ffi::OS_LOG_TYPE_ERROR,
+            LogLevel::CRITICAL => ffi::OS_LOG_TYPE_FAULT,
+        }
+    }
+
+    /// Interior NULs would cut a C string short
+    fn c_string(s: &str) -> CString {
+        CString::new(s.replace('\0', "\u{FFFD}")).unwrap_or_default()
+    }
+
+    impl OsLog {
+        pub(super) fn new(subsystem: &str) -> Self {
+            OsLog {
+                subsystem: c_string(subsystem),
+                categories: Mutex::default(),
+            }
+        }
+
+        fn handle(&self, component: &str) -> Option<Handle> {
+            let mut categories = self.categories.lock().ok()?;
+            if let Some(handle) = categories.get(component) {
+                return Some(*handle);
+            }
+            let category = c_string(component);
+            let log = unsafe { ffi::os_log_create(self.subsystem.as_ptr(), category.as_ptr()) };
+            if log.is_null() {
+                return None;
+            }
+            categories.insert(component.to_string(), Handle(log));
+            Some(Handle(log))
+        }
+
+        pub(super) fn write(&self, level: LogLevel, component: &str, message: &str) {
+            let Some(Handle(log)) = self.handle(component) else {
+                return;
+            };
+            let kind = log_type(level);
+            if !unsafe { ffi::os_log_type_enabled(log, kind) } {
+                return;
+            }
+
+            let message = c_string(message);
+            // Argument buffer for one public string: summary flags (has
+            // non-scalar arguments), argument count, then the argument's
+            // descriptor (string, public), size and pointer
+            let mut buf = [0u8; 12];
+            buf[..4].copy_from_slice(&[0x02, 0x01, 0x22, 0x08]);
+            buf[4..].copy_from_slice(&(message.as_ptr() as u64).to_ne_bytes());
+            unsafe {
+                ffi::_os_log_impl(
+                    &ffi::__dso_handle,
+                    log,
+                    kind,
+                    FORMAT.as_ptr(),
+                    buf.as_ptr(),
+                    buf.len() as u32,
+                );
+            }
+        }
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+mod imp {
+    use crate::LogLevel;
+
+    pub(super) struct OsLog;
+
+    impl OsLog {
+        pub(super) fn new(_subsystem: &str) -> Self {
+            OsLog
+        }
+
+        pub(super) fn write(&self, _level: LogLevel, _component: &str, _message: &str) {}
+    }
+}
+
+/// Sends entries to the unified log, one category per component
+pub struct OsLogSink {
+    log: imp::OsLog,
+}
+
+impl OsLogSink {
+    /// Log under `subsystem`, conventionally a reverse-DNS identifier like `com.example.server`
+    pub fn new(subsystem: &str) -> Self {
+        OsLogSink {
+            log: imp::OsLog::new(subsystem),
+        }
+    }
+}
+
+impl Sink for OsLogSink {
+    fn write(&self, entry: &LogEntry, _options: &FormatOptions) -> io::Result<()> {
+        // The unified log records its own timestamp, level and category
+        self.log.write(entry.level, &entry.component, &entry.message);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::CaptureLogger;
+
+    #[test]
+    fn test_other_outputs_unaffected() {
+        let logger = CaptureLogger::new();
+        logger.add_sink(OsLogSink::new("com.far-beyond.horizon-logger.tests"));
+        logger.debug("GAME", "tick");
+        logger.critical("GAME", "100% \0 %s %{private}s");
+        assert_eq!(logger.entries().len(), 2);
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn test_level_mapping() {
+        use crate::LogLevel;
+        let types: Vec<u8> = [LogLevel::DEBUG, LogLevel::INFO, LogLevel::WARN, LogLevel::ERROR, LogLevel::CRITICAL]
+            .into_iter()
+            .map(imp::log_type)
+            .collect();
+        assert_eq!(types, vec![0x02, 0x01, 0x00, 0x10, 0x11]);
+    }
+}
@@ -0,0 +1,138 @@
+//! Export entries as OpenTelemetry log records
+//!
+//! `OtelSink` turns each entry into a `LogRecord` and emits it through a
+//! logger from the application's own `LoggerProvider`, so the exporter,
+//! batching and resource are configured there:
+//!
+//! ```text
+//! let provider = SdkLoggerProvider::builder().with_batch_exporter(otlp_exporter).build();
+//! logger.add_sink(OtelSink::new(&provider));
+//! ```
+//!
+//! Each record carries:
+//! - severity number and text from the level and `LogEntry::severity`
+//! - the message as its body
+//! - `component`, `thread.name`, `run_id`, `seq`, and `code` / `corr` when set,
+//!   as attributes
+//! - the trace and span ids of the OpenTelemetry span current on the
+//!   thread, if any
+
+use crate::format::FormatOptions;
+use crate::sink::Sink;
+use crate::{LogEntry, LogLevel};
+use opentelemetry::logs::{AnyValue, LogRecord, Logger, LoggerProvider, Severity};
+use opentelemetry::trace::TraceContextExt;
+use opentelemetry::Context;
+use std::io;
+use std::time::{Duration, SystemTime};
+
+/// Scope name of the OpenTelemetry logger the sink emits through
+const SCOPE: &str = "horizon_logger";
+
+/// Emits entries through an OpenTelemetry `Logger`
+pub struct OtelSink<L> {
+    logger: L,
+}
+
+impl<L: Logger + Send + Sync> OtelSink<L> {
+    /// Emit through a logger taken from `provider`
+    pub fn new<P: LoggerProvider<Logger = L>>(provider: &P) -> Self {
+        OtelSink {
+            logger: provider.logger(SCOPE),
+        }
+    }
+}
+
+/// The OpenTelemetry severity for an entry
+///
+/// Each level's ten severity steps (e.g. ERROR's 40..49) are spread over
+/// the four OpenTelemetry steps of the matching range, ERROR to ERROR4.
+fn severity(level: LogLevel, severity: u8) -> Severity {
+    let base = match level {
+        LogLevel::DEBUG => Severity::Debug,
+        LogLevel::INFO => Severity::Info,
+        LogLevel::WARN => Severity::Warn,
+        LogLevel::ERROR => Severity::Error,
+        LogLevel::CRITICAL => Severity::Fatal,
+    };
+    let step = severity.saturating_sub(level.default_severity()).min(9) * 4 / 10;
+    match (base, step) {
+        (Severity::Debug, 1) => Severity::Debug2,
+        (Severity::Debug, 2) => Severity::Debug3,
+        (Severity::Debug, 3) => Severity::Debug4,
+        (Severity::Info, 1) => Severity::Info2,
+        (Severity::Info, 2) => Severity::Info3,
+        (Severity::Info, 3) => Severity::Info4,
+        (Severity::Warn, 1) => Severity::Warn2,
+        (Severity::Warn, 2) => Severity::Warn3,
+        (Severity::Warn, 3) => Severity::Warn4,
+        (Severity::Error, 1) => Severity::Error2,
+        (Severity::Error, 2) => Severity::Error3,
+        (Severity::Error, 3) => Severity::Error4,
+        (Severity::Fatal, 1) => Severity::Fatal2,
+        (Severity::Fatal, 2) => Severity::Fatal3,
+        (Severity::Fatal, 3) => Severity::Fatal4,
+        (base, _) => base,
+    }
+}
+
+fn system_time(micros: i64) -> SystemTime {
+    let offset = Duration::from_micros(micros.unsigned_abs());
+    if micros >= 0 {
+        SystemTime::UNIX_EPOCH + offset
+    } else {
+        SystemTime::UNIX_EPOCH - offset
+    }
+}
+
+impl<L: Logger + Send + Sync> Sink for OtelSink<L> {
+    fn write(&self, entry: &LogEntry, _options: &FormatOptions) -> io::Result<()> {
+        let mut record = self.logger.create_log_record();
+        record.set_timestamp(system_time(entry.timestamp.as_micros()));
+        record.set_observed_timestamp(SystemTime::now());
+        record.set_severity_number(severity(entry.level, entry.severity));
+        record.set_severity_text(entry.level.as_str());
+        record.set_body(AnyValue::from(entry.message.clone()));
+
+        if !entry.component.is_empty() {
+            record.add_attribute("component", entry.component.to_string());
+        }
+        if let Some(name) = std::thread::current().name() {
+            record.add_attribute("thread.name", name.to_string());
+        }
+        if let Some(run_id) = &entry.run_id {
+            record.add_attribute("run_id", run_id.to_string());
+        }
+        record.add_attribute("seq", entry.seq as i64);
+        if let Some(code) = &entry.code {
+            record.add_attribute("code", code.to_string());
+        }
+        if let Some(correlation_id) = &entry.correlation_id {
+            record.add_attribute("corr", correlation_id.to_string());
+        }
+
+        let span = Context::map_current(|cx| cx.span().span_context().clone());
+        if span.is_valid() {
+            record.set_trace_context(span.trace_id(), span.span_id(), Some(span.trace_flags()));
+        }
+
+        self.logger.emit(record);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_severity_steps() {
+        assert_eq!(severity(LogLevel::INFO, 20), Severity::Info);
+        assert_eq!(severity(LogLevel::ERROR, 42), Severity::Error);
+        assert_eq!(severity(LogLevel::ERROR, 45), Severity::Error3);
+        assert_eq!(severity(LogLevel::ERROR, 49), Severity::Error4);
+        assert_eq!(severity(LogLevel::CRITICAL, 50), Severity::Fatal);
+        // Below the level's own range counts as its base
+        assert_eq!(severity(LogLevel::WARN, 5), Severity::Warn);
+    }
+}
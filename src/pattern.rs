@@ -0,0 +1,308 @@
+//! Text line patterns such as `{time} {level} {component} {message}`
+//!
+//! Placeholders:
+//! - `{timestamp}`: date and time, as in the default text format or by the output's `TimestampRenderer`
+//! - `{time}`: time of day only, `HH:MM:SS`
+//! - `{level}`, `{severity}`, `{component}`, `{message}`, `{seq}`
+//! - `{thread}`: the thread that logged the entry
+//! - `{corr}`: the correlation id, or nothing
+//! - `{code}`: the event code, or nothing
+//! - `{static_fields}`: the logger's static fields as `name=value`; console patterns only
+//!
+//! Write `{{` and `}}` for literal braces.
+
+use crate::console::LineParts;
+use crate::format::write_human_clock;
+use crate::time_render::TimestampRenderer;
+use std::fmt::{self, Write};
+
+/// One piece of a parsed pattern
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Piece {
+    Literal(String),
+    Timestamp,
+    Time,
+    Level,
+    Severity,
+    Component,
+    Message,
+    Seq,
+    Thread,
+    Correlation,
+    Code,
+    StaticFields,
+}
+
+/// A parsed text line pattern; see the module docs for placeholders
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    pieces: Vec<Piece>,
+}
+
+/// A pattern that could not be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatternError {
+    /// A `{name}` this version does not know
+    UnknownPlaceholder(String),
+    /// A `{` without its `}`, or a lone `}`
+    Unbalanced,
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatternError::UnknownPlaceholder(name) => write!(f, "unknown placeholder `{{{}}}`", name),
+            PatternError::Unbalanced => write!(f, "unbalanced braces in pattern"),
+        }
+    }
+}
+
+impl std::error::Error for PatternError {}
+
+impl Pattern {
+    pub fn new(pattern: &str) -> Result<Self, PatternError> {
+        let mut pieces = Vec::new();
+        let mut literal = String::new();
+        let mut chars = pattern.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(PatternError::Unbalanced),
+                        }
+                    }
+                    let piece = match name.as_str() {
+                        "timestamp" => Piece::Timestamp,
+                        "time" => Piece::Time,
+                        "level" => Piece::Level,
+                        "severity" => Piece::Severity,
+                        "component" => Piece::Component,
+                        "message" => Piece::Message,
+                        "seq" => Piece::Seq,
+                        "thread" => Piece::Thread,
+                        "corr" => Piece::Correlation,
+                        "code" => Piece::Code,
+                        "static_fields" => Piece::StaticFields,
+                        _ => return Err(PatternError::UnknownPlaceholder(name)),
+                    };
+                    if !literal.is_empty() {
+                        pieces.push(Piece::Literal(std::mem::take(&mut literal)));
+                    }
+                    pieces.push(piece);
+                }
+                '}' => return Err(PatternError::Unbalanced),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            pieces.push(Piece::Literal(literal));
+        }
+        Ok(Pattern { pieces })
+    }
+
+    /// Append the line for `parts`, without colors
+    pub(crate) fn write_into(&self, out: &mut String, parts: &LineParts<'_>, timestamps: &dyn TimestampRenderer) {
+        self.write_marked(out, parts, timestamps, "", "");
+    }
+
+    /// Append the line for `parts` with the level and message between `prefix` and `suffix`
+    pub(crate) fn write_marked(
+        &self,
+        out: &mut String,
+        parts: &LineParts<'_>,
+        timestamps: &dyn TimestampRenderer,
+        prefix: &str,
+        suffix: &str,
+    ) {
+        for piece in &self.pieces {
+            match piece {
+                Piece::Literal(text) => out.push_str(text),
+                Piece::Timestamp => timestamps.render(&parts.timestamp, out),
+                Piece::Time => write_human_clock(out, &parts.timestamp),
+                Piece::Level => {
+                    out.push_str(prefix);
+                    out.push_str(parts.level.as_str());
+                    out.push_str(suffix);
+                }
+                Piece::Severity => {
+                    let _ = write!(out, "{}", parts.severity);
+                }
+                Piece::Component => out.push_str(parts.component),
+                Piece::Message => {
+                    out.push_str(prefix);
+                    out.push_str(parts.message);
+                    out.push_str(suffix);
+                }
+                Piece::Seq => {
+                    if let Some(seq) = parts.seq {
+                        let _ = write!(out, "{}", seq);
+                    }
+                }
+                Piece::Thread => {
+                    let _ = write!(out, "{:?}", std::thread::current().id());
+                }
+                Piece::Correlation => out.push_str(parts.correlation_id.unwrap_or_default()),
+                Piece::Code => out.push_str(parts.code.unwrap_or_default()),
+                Piece::StaticFields => out.push_str(parts.static_fields),
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for Pattern {
+    type Err = PatternError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Pattern::new(s)
+    }
+}
+
+impl fmt::Display for Pattern {
+    /// The pattern as it would be written, literal braces doubled
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for piece in &self.pieces {
+            let name = match piece {
+                Piece::Literal(text) => {
+                    f.write_str(&text.replace('{', "{{").replace('}', "}}"))?;
+                    continue;
+                }
+                Piece::Timestamp => "timestamp",
+                Piece::Time => "time",
+                Piece::Level => "level",
+                Piece::Severity => "severity",
+                Piece::Component => "component",
+                Piece::Message => "message",
+                Piece::Seq => "seq",
+                Piece::Thread => "thread",
+                Piece::Correlation => "corr",
+                Piece::Code => "code",
+                Piece::StaticFields => "static_fields",
+            };
+            write!(f, "{{{}}}", name)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::console::tests::SharedBuf;
+    use crate::console::Console;
+    use crate::format::FormatOptions;
+    use crate::sink::Sink;
+    use crate::testing::fixtures::temp_path;
+    use crate::{FileSink, Format, HorizonLogger, LogEntry, ManualClock, Timestamp};
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    /// Renders entries as text with whatever options the logger passes
+    #[derive(Default)]
+    struct TextSink(Mutex<Vec<String>>);
+
+    impl Sink for TextSink {
+        fn write(&self, entry: &LogEntry, options: &FormatOptions) -> io::Result<()> {
+            let line = crate::format_entry(entry, Format::Text, options);
+            self.0.lock().unwrap().push(line);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(Pattern::new("{lvl}"), Err(PatternError::UnknownPlaceholder("lvl".into())));
+        assert_eq!(Pattern::new("{level"), Err(PatternError::Unbalanced));
+        assert_eq!(Pattern::new("level}"), Err(PatternError::Unbalanced));
+        assert!(Pattern::new("{{literal}} {level}").is_ok());
+    }
+
+    #[test]
+    fn test_display_round_trips() {
+        let written = "{{{time}}} [{level}] {corr}{message} {static_fields}";
+        let pattern = Pattern::new(written).unwrap();
+        assert_eq!(pattern.to_string(), written);
+        assert_eq!(Pattern::new(&pattern.to_string()), Ok(pattern));
+    }
+
+    #[test]
+    fn test_patterns_per_destination() {
+        let path = temp_path("pattern.log");
+
+        let buf = SharedBuf::default();
+        // 01:02:03.004 UTC
+        let clock = ManualClock::new(Timestamp::from_millis(3_723_004));
+        let logger = HorizonLogger::builder()
+            .announce_run(false)
+            .clock(clock)
+            .console(Console::new(Box::new(buf.clone()), false))
+            .console_pattern("{level} {component}: {message}".parse().unwrap())
+            .build();
+        let file = FileSink::new(&path).unwrap().with_pattern("#{seq} {{{level}}} {message}".parse().unwrap());
+        logger.add_sink(file);
+        let text = Arc::new(TextSink::default());
+        logger.add_sink(text.clone());
+
+        logger.info("GAME", "tick");
+        // The logger-wide pattern only reaches sinks without their own
+        logger.set_text_pattern(Some("{component}|{message}".parse().unwrap()));
+        logger.info("GAME", "tock");
+        logger.flush();
+
+        assert_eq!(buf.contents(), "INFO GAME: tick\nINFO GAME: tock\n");
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(written.lines().skip(1).collect::<Vec<_>>(), vec!["#0 {INFO} tick", "#1 {INFO} tock"]);
+        let rendered = text.0.lock().unwrap().clone();
+        assert!(rendered[0].ends_with("INFO   [GAME] tick"), "{}", rendered[0]);
+        assert_eq!(rendered[1], "GAME|tock");
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_static_fields_only_where_placed() {
+        let console_line = |pattern: Option<&str>| {
+            let buf = SharedBuf::default();
+            let mut builder = HorizonLogger::builder()
+                .announce_run(false)
+                .console(Console::new(Box::new(buf.clone()), false))
+                .static_field("service", "matchmaker")
+                .static_field("shard", 3u64);
+            if let Some(pattern) = pattern {
+                builder = builder.console_pattern(pattern.parse().unwrap());
+            }
+            builder.build().info("LOBBY", "queue formed");
+            buf.contents()
+        };
+
+        assert_eq!(console_line(Some("{message} {static_fields}")), "queue formed service=matchmaker shard=3\n");
+        assert_eq!(console_line(Some("{level} {message}")), "INFO queue formed\n");
+        assert!(!console_line(None).contains("matchmaker"));
+    }
+
+    #[cfg(not(feature = "chrono"))]
+    #[test]
+    fn test_time_of_day_in_utc_without_chrono() {
+        use crate::testing::CaptureLogger;
+        let logger = CaptureLogger::from_builder(
+            HorizonLogger::builder().clock(ManualClock::new(Timestamp::from_millis(3_723_004))),
+        );
+        logger.info("GAME", "tick");
+        let pattern: Pattern = "{time} {timestamp}".parse().unwrap();
+        let mut out = String::new();
+        pattern.write_into(&mut out, &LineParts::of(&logger.entries()[0], 0), &crate::HumanTime);
+        assert_eq!(out, "01:02:03 1970-01-01T01:02:03.004Z");
+    }
+}
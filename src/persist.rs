@@ -0,0 +1,333 @@
+//! Keeping the history across restarts, see `LoggerBuilder::persist_history`
+//!
+//! The file starts with its own header and the run id, followed by the
+//! entries as `BinarySink` records, so like a binary history file it
+//! keeps each entry's time, level, component and message but not its
+//! fields. It is always replaced whole: written to a temporary file next
+//! to it, synced and renamed over it, so a crash mid-write leaves the
+//! previous version in place.
+
+use crate::binary::{decode_record, encode};
+use crate::run::LOGGER_COMPONENT;
+use crate::{FieldValue, HorizonLogger, LogEntry, LogLevel, LoggerInner};
+use std::borrow::Cow;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::Duration;
+
+/// Identifies a persisted history file
+const MAGIC: &[u8; 6] = b"HZHIST";
+
+/// Current format version
+const VERSION: u8 = 1;
+
+/// Field marking entries loaded from the previous session
+pub(crate) const PREV_SESSION: &str = "prev_session";
+
+/// When `persist_history` writes the history to its file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PersistPolicy {
+    /// In `shutdown_with_summary`, after the summary
+    OnShutdown,
+    /// Every interval from a background thread, and in `shutdown_with_summary`
+    Periodic(Duration),
+}
+
+/// The logger's persistence file; owned by the logger, stopping the periodic writer when dropped
+pub(crate) struct Persistence {
+    path: PathBuf,
+    /// Keeps the periodic writer from overlapping with a write at shutdown
+    writing: Mutex<()>,
+    /// The last periodic write failed, so the next failure isn't reported again
+    failing: AtomicBool,
+    /// Dropping it wakes the periodic writer, which then exits
+    _stop: Option<Sender<()>>,
+}
+
+impl Persistence {
+    /// Persist to `path`, starting the writer thread for a periodic policy
+    pub(crate) fn start(path: PathBuf, policy: PersistPolicy, logger: Weak<LoggerInner>) -> Self {
+        let stop = match policy {
+            PersistPolicy::OnShutdown => None,
+            PersistPolicy::Periodic(interval) => {
+                let (stop, stopped) = mpsc::channel::<()>();
+                thread::spawn(move || {
+                    while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                        let Some(inner) = logger.upgrade() else {
+                            return;
+                        };
+                        HorizonLogger { inner }.persist_periodically();
+                    }
+                });
+                Some(stop)
+            }
+        };
+        Persistence {
+            path,
+            writing: Mutex::new(()),
+            failing: AtomicBool::new(false),
+            _stop: stop,
+        }
+    }
+}
+
+/// Whether `entry` was loaded from the previous session's persisted history
+pub(crate) fn is_previous_session(entry: &LogEntry) -> bool {
+    entry.fields.iter().any(|(name, value)| name == PREV_SESSION && *value == FieldValue::Bool(true))
+}
+
+/// Why a persisted history was discarded
+enum LoadError {
+    Io(io::Error),
+    NotHistory,
+    Version(u8),
+    Corrupt,
+}
+
+impl HorizonLogger {
+    /// Write this session's history to the persistence file, if there is one
+    pub(crate) fn persist_history_now(&self) -> io::Result<()> {
+        let Some(persistence) = &self.inner.persistence else {
+            return Ok(());
+        };
+        let _writing = persistence.writing.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut bytes = Vec::from(MAGIC.as_slice());
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&(self.inner.run_id.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(self.inner.run_id.as_bytes());
+        for entry in self.inner.history.snapshot_shared() {
+            if !is_previous_session(&entry) {
+                bytes.extend_from_slice(&encode(&entry));
+            }
+        }
+        replace_file(&persistence.path, &bytes)
+    }
+
+    /// A periodic write, with a WARN when writing starts failing
+    fn persist_periodically(&self) {
+        let Some(persistence) = &self.inner.persistence else {
+            return;
+        };
+        match self.persist_history_now() {
+            Ok(()) => persistence.failing.store(false, Ordering::Relaxed),
+            Err(e) => {
+                if !persistence.failing.swap(true, Ordering::Relaxed) {
+                    let message = format!("could not persist history to {}: {}", persistence.path.display(), e);
+                    self.log(LogLevel::WARN, LOGGER_COMPONENT, &message);
+                }
+            }
+        }
+    }
+
+    /// Put the previous session's persisted entries at the start of the history
+    ///
+    /// A missing file is a first run; any other file that can't be read
+    /// whole is discarded with a WARN.
+    pub(crate) fn load_persisted_history(&self) {
+        let Some(persistence) = self.inner.persistence.as_ref().filter(|_| self.inner.keep_history) else {
+            return;
+        };
+        let (run_id, entries) = match read_persisted(&persistence.path) {
+            Ok(loaded) => loaded,
+            Err(LoadError::Io(e)) if e.kind() == io::ErrorKind::NotFound => return,
+            Err(e) => {
+                let reason = match e {
+                    LoadError::Io(e) => e.to_string(),
+                    LoadError::NotHistory => "not a persisted history".to_string(),
+                    LoadError::Version(version) => format!("unsupported version {}", version),
+                    LoadError::Corrupt => "corrupt".to_string(),
+                };
+                let message = format!("discarded persisted history {}: {}", persistence.path.display(), reason);
+                self.log(LogLevel::WARN, LOGGER_COMPONENT, &message);
+                return;
+            }
+        };
+
+        let run_id: Arc<str> = run_id.into();
+        let skip = entries.len().saturating_sub(self.inner.history.capacity());
+        for mut entry in entries.into_iter().skip(skip) {
+            entry.fields.push((Cow::Borrowed(PREV_SESSION), FieldValue::Bool(true)));
+            entry.fields.push((Cow::Borrowed("original_seq"), entry.seq.into()));
+            entry.run_id = Some(run_id.clone());
+            self.inner.history.restore(entry);
+        }
+    }
+}
+
+/// The run id and entries of a persisted history
+fn read_persisted(path: &Path) -> Result<(String, Vec<LogEntry>), LoadError> {
+    let bytes = fs::read(path).map_err(LoadError::Io)?;
+    let mut cursor = bytes.strip_prefix(MAGIC.as_slice()).ok_or(LoadError::NotHistory)?;
+    let (&version, rest) = cursor.split_first().ok_or(LoadError::Corrupt)?;
+    if version != VERSION {
+        return Err(LoadError::Version(version));
+    }
+    cursor = rest;
+
+    let len = cursor.get(..4).ok_or(LoadError::Corrupt)?;
+    let len = u32::from_le_bytes(len.try_into().map_err(|_| LoadError::Corrupt)?) as usize;
+    let run_id = cursor.get(4..4 + len).ok_or(LoadError::Corrupt)?;
+    let run_id = String::from_utf8(run_id.to_vec()).map_err(|_| LoadError::Corrupt)?;
+    cursor = &cursor[4 + len..];
+
+    let mut entries = Vec::new();
+    while !cursor.is_empty() {
+        entries.push(decode_record(&mut cursor).ok_or(LoadError::Corrupt)?);
+    }
+    Ok((run_id, entries))
+}
+
+/// Replace `path` with `bytes` by writing and syncing a temporary file, then renaming it
+fn replace_file(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    let temp = PathBuf::from(temp);
+    let mut file = File::create(&temp)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    drop(file);
+    fs::rename(&temp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::HISTORY_CAPACITY;
+    use crate::testing::fixtures::{temp_path, CountingSink};
+    use crate::testing::CaptureLogger;
+    use crate::HistoryQuery;
+
+    fn persisting(path: &Path, policy: PersistPolicy, run_id: &str) -> CaptureLogger {
+        CaptureLogger::from_builder(HorizonLogger::builder().run_id(run_id).persist_history(path, policy))
+    }
+
+    #[test]
+    fn test_history_survives_a_restart() {
+        let path = temp_path("restart.hist");
+        let first = persisting(&path, PersistPolicy::OnShutdown, "run-1");
+        first.info("NET", "connected");
+        first.error("GAME", "desync");
+        first.shutdown_with_summary("restart").unwrap();
+        drop(first);
+
+        let count = Arc::new(CountingSink::default());
+        let second = CaptureLogger::from_builder(
+            HorizonLogger::builder()
+                .run_id("run-2")
+                .persist_history(&path, PersistPolicy::OnShutdown)
+                .sink(count.clone()),
+        );
+        second.info("NET", "reconnected");
+        let entries = second.entries();
+        assert_eq!(entries.len(), 4);
+        assert_eq!(&entries[..2].iter().map(|e| e.message.as_str()).collect::<Vec<_>>(), &["connected", "desync"]);
+        assert!(entries[2].message.starts_with("shutdown (restart)"));
+        assert_eq!(entries[3].message, "reconnected");
+        assert!(entries[..3].iter().all(|e| is_previous_session(e) && e.run_id.as_deref() == Some("run-1")));
+        assert!(!is_previous_session(&entries[3]));
+        assert_eq!(entries[3].run_id.as_deref(), Some("run-2"));
+        assert!(entries.windows(2).all(|pair| pair[0].seq < pair[1].seq));
+        assert_eq!(entries[1].level, LogLevel::ERROR);
+        // Only the live entry reached the sink
+        assert_eq!(count.count(), 1);
+
+        let previous = second.query_history(&HistoryQuery::new().previous_session(true));
+        assert_eq!(previous.len(), 3);
+        let current = second.query_history(&HistoryQuery::new().previous_session(false));
+        assert_eq!(current.len(), 1);
+
+        // The next session only gets this one's entries back
+        second.shutdown_with_summary("restart").unwrap();
+        let third = persisting(&path, PersistPolicy::OnShutdown, "run-3");
+        let messages = third.messages();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0], "reconnected");
+        assert!(third.entries().iter().all(|e| e.run_id.as_deref() == Some("run-2")));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_loaded_entries_are_evicted_first() {
+        let path = temp_path("capacity.hist");
+        let first = persisting(&path, PersistPolicy::OnShutdown, "run-1");
+        for n in 0..HISTORY_CAPACITY + 10 {
+            first.info("NET", &format!("old {}", n));
+        }
+        first.shutdown_with_summary("restart").unwrap();
+
+        let second = persisting(&path, PersistPolicy::OnShutdown, "run-2");
+        let entries = second.entries();
+        assert_eq!(entries.len(), HISTORY_CAPACITY);
+        // The newest of the old session, the summary last
+        assert_eq!(entries[0].message, "old 11");
+        for n in 0..5 {
+            second.info("NET", &format!("new {}", n));
+        }
+        let entries = second.entries();
+        assert_eq!(entries.len(), HISTORY_CAPACITY);
+        assert_eq!(entries[0].message, "old 16");
+        assert!(entries[HISTORY_CAPACITY - 6].message.starts_with("shutdown"));
+        assert_eq!(entries[HISTORY_CAPACITY - 1].message, "new 4");
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_unreadable_files_are_discarded_with_one_warning() {
+        let path = temp_path("corrupt.hist");
+        let first = persisting(&path, PersistPolicy::OnShutdown, "run-1");
+        first.info("NET", "connected");
+        first.shutdown_with_summary("restart").unwrap();
+        let good = fs::read(&path).unwrap();
+
+        let mut truncated = good.clone();
+        truncated.truncate(good.len() - 3);
+        let mut flipped = good.clone();
+        *flipped.last_mut().unwrap() ^= 0xFF;
+        let mut future = good.clone();
+        future[MAGIC.len()] = VERSION + 1;
+        let cases = [
+            (truncated, "corrupt"),
+            (flipped, "corrupt"),
+            (future, "unsupported version 2"),
+            (b"not a history".to_vec(), "not a persisted history"),
+        ];
+        for (bytes, reason) in cases {
+            fs::write(&path, bytes).unwrap();
+            let logger = persisting(&path, PersistPolicy::OnShutdown, "run-2");
+            let entries = logger.entries();
+            assert_eq!(entries.len(), 1, "{}", reason);
+            assert_eq!(entries[0].level, LogLevel::WARN);
+            assert!(entries[0].message.ends_with(reason), "{}", entries[0].message);
+        }
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_periodic_writes_replace_the_file() {
+        let path = temp_path("periodic.hist");
+        let logger = persisting(&path, PersistPolicy::Periodic(Duration::from_millis(10)), "run-1");
+        logger.info("NET", "connected");
+        let read = || read_persisted(&path).ok().map(|(_, entries)| entries.len());
+        while read() != Some(1) {
+            thread::sleep(Duration::from_millis(5));
+        }
+        logger.info("NET", "lagging");
+        while read() != Some(2) {
+            thread::sleep(Duration::from_millis(5));
+        }
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(".tmp");
+        assert!(!Path::new(&temp).exists());
+
+        // Without a shutdown, as after a crash, the periodic write is what survives
+        drop(logger);
+        let restarted = persisting(&path, PersistPolicy::OnShutdown, "run-2");
+        assert_eq!(restarted.messages(), ["connected", "lagging"]);
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,199 @@
+use crate::run::LOGGER_COMPONENT;
+use crate::{HorizonLogger, LogEntry, LogLevel};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Pins kept unless `LoggerBuilder::pin_budget` or `auto_pin` says otherwise
+pub(crate) const DEFAULT_PIN_BUDGET: usize = 50;
+
+/// Pinned entries, kept apart from the shards so eviction never reaches them
+pub(crate) struct Pins {
+    entries: Mutex<BTreeMap<u64, Arc<LogEntry>>>,
+    budget: usize,
+    /// Entries at or above this level are pinned as they are stored
+    auto_level: Option<LogLevel>,
+    /// Pins released to stay within the budget and not yet reported
+    released: Mutex<Vec<u64>>,
+    any_released: AtomicBool,
+}
+
+impl Pins {
+    pub(crate) fn new(budget: usize, auto_level: Option<LogLevel>) -> Self {
+        Pins {
+            entries: Mutex::new(BTreeMap::new()),
+            budget,
+            auto_level,
+            released: Mutex::new(Vec::new()),
+            any_released: AtomicBool::new(false),
+        }
+    }
+
+    /// Whether `entry` is pinned as it is stored
+    ///
+    /// The logger's own entries never are, so reporting a released pin can't release another.
+    pub(crate) fn auto_pins(&self, entry: &LogEntry) -> bool {
+        self.budget > 0 && self.auto_level.is_some_and(|min| entry.level >= min) && entry.component != LOGGER_COMPONENT
+    }
+
+    pub(crate) fn contains(&self, seq: u64) -> bool {
+        self.entries.lock().is_ok_and(|entries| entries.contains_key(&seq))
+    }
+
+    pub(crate) fn budget(&self) -> usize {
+        self.budget
+    }
+
+    /// Keep `entry`, releasing and returning the oldest pin if that exceeds the budget
+    pub(crate) fn insert(&self, entry: Arc<LogEntry>) -> Option<u64> {
+        let mut entries = self.entries.lock().ok()?;
+        entries.insert(entry.seq, entry);
+        if entries.len() <= self.budget {
+            return None;
+        }
+        let (released, _) = entries.pop_first()?;
+        drop(entries);
+        if let Ok(mut pending) = self.released.lock() {
+            pending.push(released);
+            self.any_released.store(true, Ordering::Release);
+        }
+        Some(released)
+    }
+
+    pub(crate) fn remove(&self, seq: u64) -> bool {
+        self.entries.lock().is_ok_and(|mut entries| entries.remove(&seq).is_some())
+    }
+
+    /// Pinned entries, oldest first
+    pub(crate) fn entries(&self) -> Vec<Arc<LogEntry>> {
+        match self.entries.lock() {
+            Ok(entries) => entries.values().cloned().collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Pins released since the last call
+    fn take_released(&self) -> Vec<u64> {
+        if !self.any_released.swap(false, Ordering::Acquire) {
+            return Vec::new();
+        }
+        self.released.lock().map(|mut pending| std::mem::take(&mut *pending)).unwrap_or_default()
+    }
+}
+
+/// `pin_entry` could not pin an entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PinError {
+    /// No entry with this sequence number is in the history; it may already have been evicted
+    NotFound(u64),
+    /// The pin budget is zero
+    NoBudget,
+}
+
+impl fmt::Display for PinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PinError::NotFound(seq) => write!(f, "entry #{} is not in the history", seq),
+            PinError::NoBudget => write!(f, "the pin budget is zero"),
+        }
+    }
+}
+
+impl std::error::Error for PinError {}
+
+impl HorizonLogger {
+    /// Keep the history entry numbered `seq` however many entries are logged after it
+    ///
+    /// Pinned entries stay in `get_history` and queries, merged by sequence
+    /// number, with `LogEntry::pinned` set. Up to `LoggerBuilder::pin_budget`
+    /// entries stay pinned; past that the oldest pin is released, with a WARN
+    /// under `LOGGER`. Pinning a pinned entry does nothing.
+    pub fn pin_entry(&self, seq: u64) -> Result<(), PinError> {
+        let result = self.inner.history.pin(seq);
+        self.report_released_pins();
+        result
+    }
+
+    /// Let a pinned entry be evicted again, returning whether it was pinned
+    pub fn unpin_entry(&self, seq: u64) -> bool {
+        self.inner.history.unpin(seq)
+    }
+
+    /// Pinned entries, oldest first, including ones already evicted from the rest of the history
+    pub fn pinned_entries(&self) -> Vec<LogEntry> {
+        self.inner.history.pins.entries().into_iter().map(Arc::unwrap_or_clone).collect()
+    }
+
+    /// Log a WARN for each pin released to stay within the budget since the last report
+    pub(crate) fn report_released_pins(&self) {
+        let pins = &self.inner.history.pins;
+        for seq in pins.take_released() {
+            let message = format!("released pinned entry #{} to stay within the pin budget of {}", seq, pins.budget());
+            self.log(LogLevel::WARN, LOGGER_COMPONENT, &message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::HISTORY_CAPACITY;
+    use crate::testing::CaptureLogger;
+
+    fn flood(logger: &HorizonLogger, count: usize) {
+        for i in 0..count {
+            logger.debug("GAME", &format!("tick {}", i));
+        }
+    }
+
+    #[test]
+    fn test_pinned_entries_survive_eviction() {
+        let logger = CaptureLogger::new();
+        logger.info("STARTUP", "server v1.2 starting");
+        logger.error("STORAGE", "first save failed");
+        logger.error("STORAGE", "second save failed");
+        let [banner, first_error, second_error] = [0, 1, 2].map(|i| logger.entries()[i].seq);
+        logger.pin_entry(banner).unwrap();
+        logger.pin_entry(first_error).unwrap();
+        logger.pin_entry(first_error).unwrap();
+        flood(&logger, HISTORY_CAPACITY * 2);
+
+        let history = logger.get_history();
+        assert_eq!(history.len(), HISTORY_CAPACITY + 2);
+        assert_eq!((history[0].seq, history[1].seq), (banner, first_error));
+        assert!(history[0].pinned && history[1].pinned && !history[2].pinned);
+        assert!(history.windows(2).all(|pair| pair[0].seq < pair[1].seq));
+        assert!(history.iter().all(|e| e.seq != second_error));
+        assert_eq!(logger.pin_entry(second_error), Err(PinError::NotFound(second_error)));
+        assert_eq!(logger.history_by_component("STORAGE")[0].message, "first save failed");
+
+        assert!(logger.unpin_entry(first_error));
+        assert!(!logger.unpin_entry(first_error));
+        let messages: Vec<_> = logger.get_history().into_iter().take(2).map(|e| e.message).collect();
+        assert_eq!(messages, ["server v1.2 starting", "tick 1000"]);
+    }
+
+    #[test]
+    fn test_auto_pin_budget() {
+        let logger = CaptureLogger::from_builder(HorizonLogger::builder().auto_pin(LogLevel::CRITICAL, 2));
+        logger.critical("NETWORK", "link down");
+        flood(&logger, 10);
+        assert!(logger.entries()[0].pinned);
+        logger.critical("NETWORK", "link down again");
+        logger.critical("STORAGE", "disk failing");
+
+        let pinned: Vec<_> = logger.pinned_entries().into_iter().map(|e| e.message).collect();
+        assert_eq!(pinned, ["link down again", "disk failing"]);
+        let entries = logger.entries();
+        let released = entries.last().unwrap();
+        assert_eq!((released.level, &*released.component), (LogLevel::WARN, LOGGER_COMPONENT));
+        assert_eq!(released.message, "released pinned entry #0 to stay within the pin budget of 2");
+        assert!(!released.pinned && !entries[0].pinned);
+
+        flood(&logger, HISTORY_CAPACITY * 2);
+        let history = logger.get_history();
+        assert_eq!(history.len(), HISTORY_CAPACITY + 2);
+        assert_eq!(history[0].message, "link down again");
+    }
+}
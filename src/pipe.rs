@@ -0,0 +1,184 @@
+use crate::{HorizonLogger, LogLevel, MAX_MESSAGE_LEN};
+use std::borrow::Cow;
+use std::io::{BufRead, BufReader, ErrorKind, Read};
+use std::thread::{self, JoinHandle};
+
+/// Handle to a background thread capturing lines from a reader
+pub struct PipeHandle {
+    thread: JoinHandle<usize>,
+}
+
+impl PipeHandle {
+    /// Wait for the reader to hit EOF and return the number of lines captured
+    pub fn join(self) -> usize {
+        self.thread.join().unwrap_or(0)
+    }
+}
+
+impl HorizonLogger {
+    /// Log every line read from `reader` under the given component and level
+    ///
+    /// Useful for capturing the stdout/stderr of child processes. Non-UTF8
+    /// bytes are converted lossily and lines longer than `MAX_MESSAGE_LEN`
+    /// are split into several entries.
+    pub fn pipe_reader(
+        &self,
+        reader: impl Read + Send + 'static,
+        component: &str,
+        level: LogLevel,
+    ) -> PipeHandle {
+        self.pipe_lines(reader, component, level, String::new())
+    }
+
+    /// `pipe_reader`, starting every entry with `prefix`
+    pub(crate) fn pipe_lines(
+        &self,
+        reader: impl Read + Send + 'static,
+        component: &str,
+        level: LogLevel,
+        prefix: String,
+    ) -> PipeHandle {
+        let logger = self.clone();
+        let component = component.to_string();
+
+        let thread = thread::spawn(move || {
+            let mut reader = BufReader::new(reader);
+            let mut line = Vec::new();
+            let mut carry = Vec::new();
+            let mut lines = 0;
+
+            loop {
+                match read_line_chunk(&mut reader, &mut line, &mut carry) {
+                    Ok(Chunk::Eof) => break,
+                    Ok(Chunk::Partial) => {
+                        logger.log(level, &component, &prefixed(&prefix, &line));
+                    }
+                    Ok(Chunk::EndOfLine) => {
+                        logger.log(level, &component, &prefixed(&prefix, &line));
+                        lines += 1;
+                    }
+                    Err(e) => {
+                        logger.warn(&component, &format!("Pipe read failed: {}", e));
+                        break;
+                    }
+                }
+            }
+
+            lines
+        });
+
+        PipeHandle { thread }
+    }
+}
+
+/// `line` as text, after `prefix`
+fn prefixed<'a>(prefix: &str, line: &'a [u8]) -> Cow<'a, str> {
+    match String::from_utf8_lossy(line) {
+        text if prefix.is_empty() => text,
+        text => Cow::Owned(format!("{}{}", prefix, text)),
+    }
+}
+
+/// Result of reading one chunk of a line
+#[derive(Debug, PartialEq)]
+enum Chunk {
+    /// A complete line (or the final unterminated line at EOF)
+    EndOfLine,
+    /// The first part of a line that exceeded `MAX_MESSAGE_LEN`
+    Partial,
+    /// No more data
+    Eof,
+}
+
+/// Read up to one line into `buf`, stopping early at `MAX_MESSAGE_LEN` bytes
+///
+/// `carry` holds the bytes of a multi-byte character that straddled the end
+/// of the previous partial chunk.
+fn read_line_chunk(
+    reader: &mut impl BufRead,
+    buf: &mut Vec<u8>,
+    carry: &mut Vec<u8>,
+) -> std::io::Result<Chunk> {
+    buf.clear();
+    buf.append(carry);
+
+    loop {
+        let available = match reader.fill_buf() {
+            Ok(available) => available,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+
+        if available.is_empty() {
+            return Ok(if buf.is_empty() { Chunk::Eof } else { Chunk::EndOfLine });
+        }
+
+        let room = MAX_MESSAGE_LEN - buf.len();
+        let end = available.len().min(room + 1);
+
+        if let Some(pos) = available[..end].iter().position(|&b| b == b'\n') {
+            buf.extend_from_slice(&available[..pos]);
+            reader.consume(pos + 1);
+            if buf.last() == Some(&b'\r') {
+                buf.pop();
+            }
+            return Ok(Chunk::EndOfLine);
+        }
+
+        // Only split once we know more than `MAX_MESSAGE_LEN` bytes precede
+        // the newline, so lines of exactly the limit stay in one piece
+        if available.len() > room {
+            buf.extend_from_slice(&available[..room]);
+            reader.consume(room);
+            *carry = split_off_incomplete_utf8(buf);
+            return Ok(Chunk::Partial);
+        }
+
+        let taken = available.len();
+        buf.extend_from_slice(available);
+        reader.consume(taken);
+    }
+}
+
+/// Remove a truncated trailing UTF-8 sequence from a partial chunk
+fn split_off_incomplete_utf8(buf: &mut Vec<u8>) -> Vec<u8> {
+    match std::str::from_utf8(buf) {
+        Err(e) if e.error_len().is_none() && e.valid_up_to() > 0 => buf.split_off(e.valid_up_to()),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::CaptureLogger;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_pipe_captures_lines() {
+        let logger = CaptureLogger::new();
+        let input = Cursor::new(b"first\r\nsecond\n\xffbad\nno newline".to_vec());
+
+        let lines = logger.pipe_reader(input, "CHILD", LogLevel::INFO).join();
+
+        let messages: Vec<String> = logger.get_history().into_iter().map(|e| e.message).collect();
+        assert_eq!(lines, 4);
+        assert_eq!(messages, vec!["first", "second", "\u{fffd}bad", "no newline"]);
+    }
+
+    #[test]
+    fn test_pipe_chunks_long_lines() {
+        let logger = CaptureLogger::new();
+        let mut input = "é".repeat(MAX_MESSAGE_LEN).into_bytes();
+        input.push(b'\n');
+
+        let lines = logger.pipe_reader(Cursor::new(input), "CHILD", LogLevel::INFO).join();
+
+        let history = logger.get_history();
+        assert_eq!(lines, 1);
+        assert_eq!(history.len(), 2);
+        assert!(history.iter().all(|e| e.message.len() <= MAX_MESSAGE_LEN));
+        assert!(history.iter().all(|e| !e.message.contains('\u{fffd}')));
+        assert_eq!(history.iter().map(|e| e.message.chars().count()).sum::<usize>(), MAX_MESSAGE_LEN);
+    }
+}
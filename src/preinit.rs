@@ -0,0 +1,217 @@
+//! Buffering of entries logged before the first sink is added
+//!
+//! Entries logged while the logger has no sinks are also kept in a bounded
+//! buffer, `DEFAULT_PREINIT_BUFFER` of them unless
+//! `LoggerBuilder::preinit_buffer` says otherwise. The first `add_sink`
+//! replays them into the new sink, in logging order and with their original
+//! timestamps, before the sink sees any newer entry. Later sinks only get
+//! entries from the time they were added.
+
+use crate::run::LOGGER_COMPONENT;
+use crate::sink::{Registered, Sink};
+use crate::{reentry, HorizonLogger, LogEntry, LogLevel};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Entries kept for the first sink unless `LoggerBuilder::preinit_buffer` says otherwise
+pub(crate) const DEFAULT_PREINIT_BUFFER: usize = 500;
+
+/// Entries waiting for the first sink; oldest are dropped once full
+struct Buffered {
+    entries: VecDeque<LogEntry>,
+    capacity: usize,
+    dropped: u64,
+}
+
+#[derive(Default)]
+pub(crate) struct PreinitBuffer {
+    /// Still buffering; cleared by the first sink or `discard_preinit_buffer`
+    active: AtomicBool,
+    buffered: Mutex<Option<Buffered>>,
+}
+
+impl PreinitBuffer {
+    pub(crate) fn new(capacity: usize) -> Self {
+        let buffered = (capacity > 0).then(|| Buffered {
+            entries: VecDeque::new(),
+            capacity,
+            dropped: 0,
+        });
+        PreinitBuffer {
+            active: AtomicBool::new(buffered.is_some()),
+            buffered: Mutex::new(buffered),
+        }
+    }
+
+    pub(crate) fn is_active(&self) -> bool {
+        self.active.load(Ordering::Acquire)
+    }
+
+    /// Keep a copy of `entry`, returning false once buffering has ended
+    fn push(&self, entry: &LogEntry) -> bool {
+        let Ok(mut buffered) = self.buffered.lock() else {
+            return false;
+        };
+        let Some(buffered) = buffered.as_mut() else {
+            return false;
+        };
+        if buffered.entries.len() == buffered.capacity {
+            buffered.entries.pop_front();
+            buffered.dropped += 1;
+        }
+        buffered.entries.push_back(entry.clone());
+        true
+    }
+
+    /// End buffering, returning what was buffered
+    fn take(&self) -> Option<Buffered> {
+        // Release pairs with `is_active`, so a logger that sees buffering ended also sees the new sink
+        if !self.active.swap(false, Ordering::AcqRel) {
+            return None;
+        }
+        self.buffered.lock().ok()?.take()
+    }
+}
+
+impl HorizonLogger {
+    /// Register `sink`, first replaying the pre-init buffer into it if this is the first sink
+    pub(crate) fn install_sink(&self, registered: Registered) {
+        let Ok(mut sinks) = self.inner.sinks.write() else {
+            return;
+        };
+        if let Some(buffered) = self.inner.preinit.take() {
+            // Sinks that log while replaying are deferred like during any log call
+            let guard = reentry::enter();
+            self.replay_preinit(&registered.sink, buffered);
+            drop(guard);
+        }
+        sinks.push(registered);
+        drop(sinks);
+
+        for deferred in reentry::take() {
+            deferred.logger.record(deferred.entry, deferred.indent);
+        }
+    }
+
+    fn replay_preinit(&self, sink: &Arc<dyn Sink>, buffered: Buffered) {
+        let sinks = std::slice::from_ref(sink);
+        let mut entries = Vec::from(buffered.entries);
+        // Threads reserve sequence numbers and buffer in separate steps
+        entries.sort_by_key(|entry| entry.seq);
+
+        if buffered.dropped > 0 {
+            let timestamp = entries.first().map_or_else(|| self.inner.clock.now(), |entry| entry.timestamp);
+            let message = format!(
+                "{} entries logged before the first sink was added were dropped",
+                buffered.dropped
+            );
+            let mut notice = LogEntry::at(timestamp, LogLevel::WARN, LOGGER_COMPONENT, &message);
+            notice.run_id = Some(self.inner.run_id.clone());
+            self.write_sinks(sinks, &notice);
+        }
+        for entry in &entries {
+            self.write_sinks(sinks, entry);
+        }
+    }
+
+    /// Buffer an entry logged while there were no sinks, or write it if one was just added
+    pub(crate) fn buffer_or_write(&self, entry: &LogEntry) {
+        // Holding the read lock keeps `install_sink` from replaying in between
+        let sinks = match self.inner.sinks.read() {
+            Ok(sinks) if sinks.is_empty() && self.inner.preinit.push(entry) => return,
+            Ok(sinks) => sinks.iter().map(|registered| registered.sink.clone()).collect::<Vec<_>>(),
+            Err(_) => return,
+        };
+        self.write_sinks(&sinks, entry);
+    }
+
+    /// Stop buffering entries for the first sink and forget those buffered so far
+    pub fn discard_preinit_buffer(&self) {
+        drop(self.inner.preinit.take());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixtures::CollectSink;
+    use crate::testing::CaptureLogger;
+    use std::thread;
+
+    #[test]
+    fn test_replay_into_first_sink_with_overflow() {
+        let logger = CaptureLogger::from_builder(HorizonLogger::builder().preinit_buffer(3));
+        for i in 0..5 {
+            logger.info("BOOT", &format!("early {}", i));
+        }
+
+        let first = Arc::new(CollectSink::default());
+        logger.add_sink(first.clone());
+        logger.info("BOOT", "late");
+        let second = Arc::new(CollectSink::default());
+        logger.add_sink(second.clone());
+        logger.info("BOOT", "later");
+
+        assert_eq!(
+            first.messages(),
+            vec![
+                "2 entries logged before the first sink was added were dropped",
+                "early 2",
+                "early 3",
+                "early 4",
+                "late",
+                "later"
+            ]
+        );
+        assert_eq!(first.entries()[1].seq, 2);
+        assert_eq!(second.messages(), vec!["later"]);
+    }
+
+    #[test]
+    fn test_replay_ordered_against_concurrent_logging() {
+        let logger = CaptureLogger::from_builder(HorizonLogger::builder().preinit_buffer(10_000).history(false));
+        let worker = logger.clone();
+        let writer = thread::spawn(move || {
+            for i in 0..2_000 {
+                worker.info("BOOT", &i.to_string());
+            }
+        });
+
+        thread::sleep(std::time::Duration::from_millis(1));
+        let sink = Arc::new(CollectSink::default());
+        logger.add_sink(sink.clone());
+        writer.join().unwrap();
+
+        let seqs: Vec<u64> = sink.entries().iter().map(|e| e.seq).collect();
+        assert_eq!(seqs, (0..2_000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_buffered_by_default() {
+        let logger = CaptureLogger::new();
+        for i in 0..DEFAULT_PREINIT_BUFFER + 1 {
+            logger.info("BOOT", &format!("early {}", i));
+        }
+
+        let sink = Arc::new(CollectSink::default());
+        logger.add_sink(sink.clone());
+        let messages = sink.messages();
+        assert_eq!(messages.len(), DEFAULT_PREINIT_BUFFER + 1);
+        assert_eq!(messages[0], "1 entries logged before the first sink was added were dropped");
+        assert_eq!(messages[1], "early 1");
+    }
+
+    #[test]
+    fn test_discard() {
+        let logger = CaptureLogger::from_builder(HorizonLogger::builder().preinit_buffer(10));
+        logger.info("BOOT", "early");
+        logger.discard_preinit_buffer();
+        logger.info("BOOT", "still early");
+
+        let sink = Arc::new(CollectSink::default());
+        logger.add_sink(sink.clone());
+        logger.info("BOOT", "late");
+        assert_eq!(sink.messages(), vec!["late"]);
+    }
+}
@@ -0,0 +1,248 @@
+//! Ready-made builder configurations for each deployment environment
+//!
+//! | Preset    | Min level | Backtraces from | History        | Console bell    | File with `builder_with_dir` |
+//! |-----------|-----------|-----------------|----------------|-----------------|------------------------------|
+//! | `Dev`     | DEBUG     | ERROR           | kept           | CRITICAL        | none                         |
+//! | `Staging` | INFO      | ERROR           | kept           | off             | JSON                         |
+//! | `Prod`    | WARN      | CRITICAL        | kept, deduped  | off             | JSON, rotated                |
+//!
+//! All presets keep the default console fields and RFC 3339 machine
+//! timestamps. `builder` writes no file in any environment, since a file
+//! needs a path; `builder_with_dir` adds the file above as `server.log`
+//! in the given directory. Production rotates it daily and at 100 MiB,
+//! keeping 5 closed files. There are no crash dumps: the crate has no
+//! such feature, so CRITICAL only gets a backtrace.
+
+use crate::console::ConsoleFields;
+use crate::format::MachineTimestamp;
+use crate::{FileSink, Format, HorizonLogger, LogLevel, LoggerBuilder, Rotation};
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+/// Name of the file `Preset::builder_with_dir` writes
+const FILE_NAME: &str = "server.log";
+
+/// Size at which the `Prod` file rotates
+const PROD_MAX_BYTES: u64 = 100 * 1024 * 1024;
+
+/// Deployment environment to configure a logger for; see the module docs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    Dev,
+    Staging,
+    Prod,
+}
+
+impl Preset {
+    /// Preset named by the environment variable `var`
+    ///
+    /// Accepts `dev`/`development`, `staging`/`stage` and `prod`/`production`,
+    /// ignoring case. Falls back to `Prod` when the variable is unset or
+    /// unrecognized, so a misconfigured server never logs at DEBUG.
+    pub fn from_env(var: &str) -> Preset {
+        std::env::var(var).map_or(Preset::Prod, |value| Preset::from_name(&value))
+    }
+
+    fn from_name(name: &str) -> Preset {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "dev" | "development" => Preset::Dev,
+            "staging" | "stage" => Preset::Staging,
+            _ => Preset::Prod,
+        }
+    }
+
+    /// A builder configured for this environment, open to further changes
+    pub fn builder(self) -> LoggerBuilder {
+        let builder = HorizonLogger::builder();
+        match self {
+            Preset::Dev => builder
+                .min_level(LogLevel::DEBUG)
+                .capture_backtrace(LogLevel::ERROR)
+                .bell_on(LogLevel::CRITICAL),
+            Preset::Staging => builder.min_level(LogLevel::INFO).capture_backtrace(LogLevel::ERROR),
+            Preset::Prod => builder
+                .min_level(LogLevel::WARN)
+                .capture_backtrace(LogLevel::CRITICAL)
+                .dedup_history(true),
+        }
+    }
+
+    /// `builder`, plus this environment's file as `server.log` in `dir`; see the module docs
+    ///
+    /// `dir` must exist. Fails if the file cannot be opened.
+    pub fn builder_with_dir(self, dir: impl AsRef<Path>) -> io::Result<LoggerBuilder> {
+        let path = dir.as_ref().join(FILE_NAME);
+        let builder = self.builder();
+        Ok(match self {
+            Preset::Dev => builder,
+            Preset::Staging => builder.sink(FileSink::new(path)?.with_format(Format::Json)),
+            Preset::Prod => {
+                let rotation = Rotation::new().max_bytes(PROD_MAX_BYTES).every(Duration::from_secs(24 * 60 * 60));
+                builder.sink(FileSink::new(path)?.with_format(Format::Json).with_rotation(rotation))
+            }
+        })
+    }
+}
+
+/// A logger's effective configuration, as returned by `describe_config`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigDescription {
+    /// Level configuration in `apply_directive` syntax
+    pub directives: String,
+    pub min_level: LogLevel,
+    /// Lowest level that captures a backtrace
+    pub backtrace_level: Option<LogLevel>,
+    pub keep_history: bool,
+    pub dedup_history: bool,
+    pub machine_timestamp: MachineTimestamp,
+    pub console_fields: ConsoleFields,
+    /// Level at which the console rings the bell, if it is a terminal
+    pub bell_level: Option<LogLevel>,
+    pub sinks: usize,
+    pub run_id: String,
+}
+
+impl HorizonLogger {
+    /// A builder pre-configured for `preset`
+    pub fn preset(preset: Preset) -> LoggerBuilder {
+        preset.builder()
+    }
+
+    /// Describe how this logger is configured
+    pub fn describe_config(&self) -> ConfigDescription {
+        ConfigDescription {
+            directives: self.current_directives(),
+            min_level: self.min_level(),
+            backtrace_level: self.inner.backtrace_level,
+            keep_history: self.inner.keep_history,
+            dedup_history: self.inner.history.dedups(),
+            machine_timestamp: self.format_options().machine_timestamp,
+            console_fields: self.inner.console.fields(),
+            bell_level: self.inner.console.bell_level(),
+            sinks: self.inner.sinks.read().map_or(0, |sinks| sinks.len()),
+            run_id: self.run_id().to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::console::Console;
+    use crate::sink::Sink;
+    use crate::testing::fixtures::temp_dir;
+    use crate::{FormatOptions, LogEntry};
+    use std::io;
+
+    struct Discard;
+
+    impl Sink for Discard {
+        fn write(&self, _entry: &LogEntry, _options: &FormatOptions) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Build a preset with a terminal console that discards its output
+    fn describe(preset: Preset) -> ConfigDescription {
+        HorizonLogger::preset(preset)
+            .run_id("r1")
+            .console(Console::new(Box::new(io::sink()), true))
+            .build()
+            .describe_config()
+    }
+
+    #[test]
+    fn test_preset_contents() {
+        let base = ConfigDescription {
+            directives: String::new(),
+            min_level: LogLevel::DEBUG,
+            backtrace_level: None,
+            keep_history: true,
+            dedup_history: false,
+            machine_timestamp: MachineTimestamp::Rfc3339,
+            console_fields: ConsoleFields::default(),
+            bell_level: None,
+            sinks: 0,
+            run_id: "r1".into(),
+        };
+
+        assert_eq!(
+            describe(Preset::Dev),
+            ConfigDescription {
+                directives: "debug".into(),
+                backtrace_level: Some(LogLevel::ERROR),
+                bell_level: Some(LogLevel::CRITICAL),
+                ..base.clone()
+            }
+        );
+        assert_eq!(
+            describe(Preset::Staging),
+            ConfigDescription {
+                directives: "info".into(),
+                min_level: LogLevel::INFO,
+                backtrace_level: Some(LogLevel::ERROR),
+                ..base.clone()
+            }
+        );
+        assert_eq!(
+            describe(Preset::Prod),
+            ConfigDescription {
+                directives: "warn".into(),
+                min_level: LogLevel::WARN,
+                backtrace_level: Some(LogLevel::CRITICAL),
+                dedup_history: true,
+                ..base
+            }
+        );
+    }
+
+    #[test]
+    fn test_preset_files() {
+        let dir = temp_dir("preset_files");
+        let build = |preset: Preset| {
+            let builder = preset.builder_with_dir(&dir).unwrap();
+            builder.announce_run(false).console(Console::new(Box::new(io::sink()), false)).build()
+        };
+
+        let dev = build(Preset::Dev);
+        assert_eq!(dev.describe_config().sinks, 0);
+        dev.warn("GAME", "not in a file");
+        dev.flush();
+        assert!(!dir.join(FILE_NAME).exists());
+
+        for preset in [Preset::Staging, Preset::Prod] {
+            let logger = build(preset);
+            assert_eq!(logger.describe_config().sinks, 1);
+            logger.warn("GAME", "in the file");
+            logger.flush();
+            let contents = std::fs::read_to_string(dir.join(FILE_NAME)).unwrap();
+            assert!(contents.starts_with("# horizon-logger format=jsonl "), "{}", contents);
+            let last: serde_json::Value = serde_json::from_str(contents.lines().last().unwrap()).unwrap();
+            assert_eq!(last["message"], "in the file");
+            drop(logger);
+            std::fs::remove_file(dir.join(FILE_NAME)).unwrap();
+        }
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_presets_stay_adjustable() {
+        let logger = HorizonLogger::preset(Preset::Prod)
+            .min_level(LogLevel::ERROR)
+            .console(Console::new(Box::new(io::sink()), false))
+            .build();
+        logger.add_sink(Discard);
+        let config = logger.describe_config();
+        assert_eq!((config.min_level, config.sinks, config.bell_level), (LogLevel::ERROR, 1, None));
+    }
+
+    #[test]
+    fn test_names() {
+        assert_eq!(Preset::from_name("Development"), Preset::Dev);
+        assert_eq!(Preset::from_name(" stage "), Preset::Staging);
+        assert_eq!(Preset::from_name("production"), Preset::Prod);
+        assert_eq!(Preset::from_name("qa"), Preset::Prod);
+        assert_eq!(Preset::from_env("HORIZON_LOGGER_TEST_UNSET_PRESET"), Preset::Prod);
+    }
+}
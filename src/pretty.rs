@@ -0,0 +1,142 @@
+use crate::{ComponentArg, HorizonLogger, LogLevel};
+use std::fmt;
+
+/// Marker appended where `debug_pretty` output was cut off
+const TRUNCATED: &str = "… truncated";
+
+/// Indentation `{:#?}` adds per nesting level
+const DEBUG_INDENT: usize = 4;
+
+/// How much of a `{:#?}` rendering `debug_pretty` keeps
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct PrettyLimits {
+    pub(crate) max_depth: usize,
+    pub(crate) max_len: usize,
+}
+
+impl Default for PrettyLimits {
+    fn default() -> Self {
+        PrettyLimits {
+            max_depth: 6,
+            max_len: 4096,
+        }
+    }
+}
+
+/// Render `value` with `{:#?}`, eliding anything nested too deeply or past the length limit
+fn render(value: &impl fmt::Debug, limits: PrettyLimits) -> String {
+    let full = format!("{:#?}", value);
+    let max_indent = limits.max_depth * DEBUG_INDENT;
+
+    let mut out = String::new();
+    let mut eliding = false;
+    for line in full.lines() {
+        let indent = line.len() - line.trim_start_matches(' ').len();
+        if indent > max_indent {
+            if !eliding {
+                out.push_str(&" ".repeat(max_indent + DEBUG_INDENT));
+                out.push_str(TRUNCATED);
+                out.push('\n');
+                eliding = true;
+            }
+            continue;
+        }
+        eliding = false;
+        out.push_str(line);
+        out.push('\n');
+    }
+    out.pop();
+
+    if out.len() > limits.max_len {
+        let mut cut = limits.max_len;
+        while !out.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        out.truncate(cut);
+        out.push('\n');
+        out.push_str(TRUNCATED);
+    }
+    out
+}
+
+impl HorizonLogger {
+    /// Log `value` pretty-printed at DEBUG, cut off at the configured depth and length
+    ///
+    /// Nothing is rendered when DEBUG is filtered out.
+    pub fn debug_pretty(&self, component: impl ComponentArg, label: &str, value: &impl fmt::Debug) {
+        if !self.enabled(LogLevel::DEBUG) {
+            return;
+        }
+        let rendered = render(value, self.inner.pretty);
+        self.log(LogLevel::DEBUG, component, &format!("{} = {}", label, rendered));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::CaptureLogger;
+    use std::cell::Cell;
+
+    #[derive(Debug)]
+    #[allow(dead_code)]
+    struct Node {
+        name: String,
+        child: Option<Box<Node>>,
+    }
+
+    fn chain(depth: usize) -> Node {
+        (0..depth).rev().fold(Node { name: "leaf".into(), child: None }, |child, i| Node {
+            name: format!("n{}", i),
+            child: Some(Box::new(child)),
+        })
+    }
+
+    #[test]
+    fn test_depth_limit() {
+        let logger = CaptureLogger::from_builder(HorizonLogger::builder().pretty_limits(3, 100_000));
+        logger.debug_pretty("AI", "tree", &chain(20));
+
+        let message = logger.entries().pop().unwrap().message;
+        assert!(message.starts_with("tree = Node {\n    name: \"n0\",\n"));
+        assert!(message.contains(TRUNCATED));
+        assert!(!message.contains("\"n3\""), "{}", message);
+        assert!(!message.contains("leaf"));
+        assert_eq!(message.matches(TRUNCATED).count(), 1);
+        let deepest = message.lines().map(|l| l.len() - l.trim_start().len()).max().unwrap();
+        assert_eq!(deepest, 4 * DEBUG_INDENT);
+    }
+
+    #[test]
+    fn test_length_limit() {
+        let logger = CaptureLogger::from_builder(HorizonLogger::builder().pretty_limits(100, 200));
+        logger.debug_pretty("AI", "big", &vec!["padding"; 1000]);
+
+        let message = logger.entries().pop().unwrap().message;
+        assert!(message.ends_with(TRUNCATED));
+        assert!(message.len() < 200 + "big = ".len() + TRUNCATED.len() + 2);
+    }
+
+    struct Counted<'a>(&'a Cell<usize>);
+
+    impl fmt::Debug for Counted<'_> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            self.0.set(self.0.get() + 1);
+            f.write_str("Counted")
+        }
+    }
+
+    #[test]
+    fn test_macro_skips_rendering_when_filtered() {
+        let renders = Cell::new(0);
+        let logger = CaptureLogger::from_builder(HorizonLogger::builder().min_level(LogLevel::INFO));
+        crate::log_debug_pretty!(logger, "AI", "state", Counted(&renders));
+        assert_eq!(renders.get(), 0);
+        assert!(logger.entries().is_empty());
+
+        logger.set_min_level(LogLevel::DEBUG);
+        crate::log_debug_pretty!(logger, "AI", "state", Counted(&renders));
+        assert_eq!(renders.get(), 1);
+        assert_eq!(logger.messages(), vec!["state = Counted"]);
+    }
+}
@@ -0,0 +1,215 @@
+use crate::run::LOGGER_COMPONENT;
+use crate::HorizonLogger;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Where a log call spends its time, see `ProfileReport`
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Phase {
+    Format,
+    Console,
+    History,
+    Sinks,
+}
+
+/// Time spent inside log calls, kept when `LoggerBuilder::self_profiling` is on
+#[derive(Default)]
+pub(crate) struct Profiler {
+    enabled: bool,
+    calls: AtomicU64,
+    phases: [AtomicU64; 4],
+    max: AtomicU64,
+}
+
+impl Profiler {
+    pub(crate) fn new(enabled: bool) -> Self {
+        Profiler {
+            enabled,
+            ..Profiler::default()
+        }
+    }
+
+    /// Start timing a call; `None`, at no cost, when profiling is off
+    pub(crate) fn start(&self) -> Option<Lap> {
+        self.enabled.then(|| {
+            let now = Instant::now();
+            Lap { start: now, last: now }
+        })
+    }
+
+    /// Charge the time since the previous mark to `phase`
+    pub(crate) fn mark(&self, lap: &mut Option<Lap>, phase: Phase) {
+        if let Some(lap) = lap {
+            let now = Instant::now();
+            self.phases[phase as usize].fetch_add(nanos(now - lap.last), Ordering::Relaxed);
+            lap.last = now;
+        }
+    }
+
+    /// Count a finished call, ending at its last mark
+    pub(crate) fn finish(&self, lap: Option<Lap>) {
+        if let Some(lap) = lap {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+            self.max.fetch_max(nanos(lap.last - lap.start), Ordering::Relaxed);
+        }
+    }
+
+    fn report(&self) -> ProfileReport {
+        let phase = |phase: Phase| Duration::from_nanos(self.phases[phase as usize].load(Ordering::Relaxed));
+        ProfileReport {
+            enabled: self.enabled,
+            calls: self.calls.load(Ordering::Relaxed),
+            format: phase(Phase::Format),
+            console: phase(Phase::Console),
+            history: phase(Phase::History),
+            sinks: phase(Phase::Sinks),
+            max_call: Duration::from_nanos(self.max.load(Ordering::Relaxed)),
+        }
+    }
+
+    fn reset(&self) {
+        self.calls.store(0, Ordering::Relaxed);
+        for phase in &self.phases {
+            phase.store(0, Ordering::Relaxed);
+        }
+        self.max.store(0, Ordering::Relaxed);
+    }
+}
+
+fn nanos(duration: Duration) -> u64 {
+    duration.as_nanos().try_into().unwrap_or(u64::MAX)
+}
+
+/// A log call being timed
+pub(crate) struct Lap {
+    start: Instant,
+    last: Instant,
+}
+
+/// Time the logger spent inside log calls that were output, see `profile_report`
+///
+/// Calls dropped by filters, sampling or volume limits are not counted.
+/// `format` covers evaluating the message and building the stored entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ProfileReport {
+    /// Whether `LoggerBuilder::self_profiling` is on; everything else is zero otherwise
+    pub enabled: bool,
+    pub calls: u64,
+    pub format: Duration,
+    pub console: Duration,
+    pub history: Duration,
+    pub sinks: Duration,
+    /// Longest single call
+    pub max_call: Duration,
+}
+
+impl ProfileReport {
+    /// Time across all phases
+    pub fn total(&self) -> Duration {
+        self.format + self.console + self.history + self.sinks
+    }
+
+    /// Average time per call
+    pub fn mean_call(&self) -> Duration {
+        if self.calls == 0 {
+            return Duration::ZERO;
+        }
+        self.total() / self.calls.min(u32::MAX as u64) as u32
+    }
+}
+
+impl fmt::Display for ProfileReport {
+    /// One line per phase with its share of the total
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.total();
+        writeln!(f, "{:<10} {:>12} {:>7}", "PHASE", "TIME", "SHARE")?;
+        for (name, time) in [("format", self.format), ("console", self.console), ("history", self.history), ("sinks", self.sinks)] {
+            let share = if total.is_zero() { 0.0 } else { time.as_secs_f64() / total.as_secs_f64() * 100.0 };
+            writeln!(f, "{:<10} {:>12} {:>6.1}%", name, crate::fmt::duration(time), share)?;
+        }
+        write!(
+            f,
+            "{} calls, {} total, {} mean, {} max",
+            self.calls,
+            crate::fmt::duration(total),
+            crate::fmt::duration(self.mean_call()),
+            crate::fmt::duration(self.max_call)
+        )
+    }
+}
+
+impl HorizonLogger {
+    /// Time spent inside log calls since the logger was built or `reset_profile`
+    pub fn profile_report(&self) -> ProfileReport {
+        self.inner.profile.report()
+    }
+
+    /// Zero the counters behind `profile_report`
+    pub fn reset_profile(&self) {
+        self.inner.profile.reset();
+    }
+
+    /// Log `profile_report` as a table at INFO under `LOGGER`, one entry per line
+    ///
+    /// The report is read before logging, so these entries are not in it.
+    pub fn log_profile_report(&self) {
+        let report = self.profile_report();
+        if !report.enabled {
+            self.info(LOGGER_COMPONENT, "self profiling is off; enable it with LoggerBuilder::self_profiling");
+            return;
+        }
+        for line in report.to_string().lines() {
+            self.info(LOGGER_COMPONENT, line);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sink::Sink;
+    use crate::testing::CaptureLogger;
+    use crate::{FormatOptions, LogEntry, LogLevel};
+    use std::io;
+
+    struct SlowSink;
+
+    impl Sink for SlowSink {
+        fn write(&self, _entry: &LogEntry, _options: &FormatOptions) -> io::Result<()> {
+            std::thread::sleep(Duration::from_millis(2));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_phases_and_reset() {
+        let logger = CaptureLogger::from_builder(HorizonLogger::builder().self_profiling(true));
+        logger.set_min_level(LogLevel::INFO);
+        logger.add_sink(SlowSink);
+        logger.info("GAME", "tick");
+        logger.info("GAME", "tock");
+        logger.debug("GAME", "filtered");
+
+        let report = logger.profile_report();
+        assert_eq!(report.calls, 2);
+        assert!(report.sinks >= Duration::from_millis(4), "{:?}", report);
+        assert!(report.max_call >= Duration::from_millis(2) && report.max_call <= report.total());
+        assert!(report.sinks > report.format + report.console + report.history);
+
+        logger.log_profile_report();
+        let messages = logger.messages();
+        assert!(messages[messages.len() - 6].starts_with("PHASE"), "{:?}", messages);
+        assert!(messages.last().unwrap().starts_with(&format!("{} calls, ", report.calls)));
+
+        logger.reset_profile();
+        assert_eq!(logger.profile_report(), ProfileReport { enabled: true, ..ProfileReport::default() });
+    }
+
+    #[test]
+    fn test_off_by_default() {
+        let logger = CaptureLogger::new();
+        logger.info("GAME", "tick");
+        assert_eq!(logger.profile_report(), ProfileReport::default());
+    }
+}
@@ -0,0 +1,523 @@
+//! Writing sinks from a background thread, degrading when they fall behind
+//!
+//! Set up with `LoggerBuilder::async_sinks`. Entries are queued for one
+//! worker thread instead of being written by the logging thread. Once the
+//! queue reaches its high-water mark, lower levels are shed so that errors
+//! still get through: DEBUG and INFO are dropped, WARN is sampled, and
+//! ERROR and CRITICAL wait for room, then go to stderr if there is none.
+//! The console and history are not affected. With
+//! `SinkOrdering::Independent` the worker passes each entry on to one
+//! thread per sink instead of writing the sinks itself.
+
+use crate::run::LOGGER_COMPONENT;
+use crate::sink::{Sink, SinkOrdering};
+use crate::{HorizonLogger, LogEntry, LogLevel, LoggerInner};
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, SyncSender};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, Weak};
+use std::time::{Duration, Instant};
+
+/// Minimum time between warnings that the queue degraded, and between warnings that it recovered
+const NOTICE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How long `flush` waits for the queue to drain
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Queue size and thresholds for `LoggerBuilder::async_sinks`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackpressurePolicy {
+    /// Entries the queue holds at most
+    pub capacity: usize,
+    /// Queue length from which DEBUG and INFO are dropped and WARN is sampled
+    pub high_water: usize,
+    /// Keep one WARN in this many while over the high-water mark
+    pub warn_sample: u32,
+    /// How long ERROR and CRITICAL wait for room in a full queue before going to stderr
+    pub error_timeout: Duration,
+}
+
+impl Default for BackpressurePolicy {
+    fn default() -> Self {
+        BackpressurePolicy {
+            capacity: 10_000,
+            high_water: 8_000,
+            warn_sample: 10,
+            error_timeout: Duration::from_millis(100),
+        }
+    }
+}
+
+impl BackpressurePolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn capacity(mut self, entries: usize) -> Self {
+        self.capacity = entries;
+        self
+    }
+
+    pub fn high_water(mut self, entries: usize) -> Self {
+        self.high_water = entries;
+        self
+    }
+
+    pub fn warn_sample(mut self, one_in: u32) -> Self {
+        self.warn_sample = one_in;
+        self
+    }
+
+    pub fn error_timeout(mut self, timeout: Duration) -> Self {
+        self.error_timeout = timeout;
+        self
+    }
+
+    /// Queue length at which a degraded queue counts as recovered
+    fn low_water(&self) -> usize {
+        self.high_water / 2
+    }
+}
+
+/// The sink queue as of now, from `HorizonLogger::queue_status`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueueStatus {
+    /// Entries waiting for the worker
+    pub depth: usize,
+    pub capacity: usize,
+    /// Over the high-water mark and not yet drained below half of it
+    pub degraded: bool,
+    /// Entries dropped for a full or degraded queue, indexed by `LogLevel as usize`
+    pub dropped: [u64; LogLevel::COUNT],
+    /// ERROR and CRITICAL entries written to stderr because the queue stayed full
+    pub stderr_fallbacks: u64,
+}
+
+impl QueueStatus {
+    pub fn dropped(&self, level: LogLevel) -> u64 {
+        self.dropped[level as usize]
+    }
+}
+
+/// An entry with the sinks registered when it was logged
+type Job = (Vec<Arc<dyn Sink>>, LogEntry);
+
+#[derive(Default)]
+struct QueueState {
+    jobs: VecDeque<Job>,
+    /// The worker is writing a job it took off the queue
+    busy: bool,
+    closed: bool,
+}
+
+/// A sink's own writer thread, with `SinkOrdering::Independent`
+struct Lane {
+    sink: Arc<dyn Sink>,
+    entries: SyncSender<LogEntry>,
+}
+
+/// Shared between the logger and its worker thread
+pub(crate) struct SinkQueue {
+    policy: BackpressurePolicy,
+    ordering: SinkOrdering,
+    state: Mutex<QueueState>,
+    /// Signalled when a job is queued, taken or finished, and on close
+    changed: Condvar,
+    degraded: AtomicBool,
+    dropped: [AtomicU64; LogLevel::COUNT],
+    /// Dropped since the queue last degraded, for the recovery notice
+    dropped_while_degraded: AtomicU64,
+    stderr_fallbacks: AtomicU64,
+    /// Entries handed to lanes and not yet written
+    in_lanes: AtomicUsize,
+    /// WARN entries seen while degraded, for sampling
+    warns_seen: AtomicU64,
+    /// When the last degraded and recovered notices were logged
+    last_notices: Mutex<[Option<Instant>; 2]>,
+}
+
+/// Owned by the logger; closes the queue so the worker exits when the logger is dropped
+pub(crate) struct SinkQueueHandle(Arc<SinkQueue>);
+
+impl Drop for SinkQueueHandle {
+    fn drop(&mut self) {
+        self.0.lock().closed = true;
+        self.0.changed.notify_all();
+    }
+}
+
+impl SinkQueue {
+    /// Start the worker thread for `logger`
+    pub(crate) fn start(
+        policy: BackpressurePolicy,
+        ordering: SinkOrdering,
+        logger: Weak<LoggerInner>,
+    ) -> SinkQueueHandle {
+        let queue = Arc::new(SinkQueue {
+            policy,
+            ordering,
+            state: Mutex::new(QueueState::default()),
+            changed: Condvar::new(),
+            degraded: AtomicBool::new(false),
+            dropped: Default::default(),
+            dropped_while_degraded: AtomicU64::new(0),
+            stderr_fallbacks: AtomicU64::new(0),
+            in_lanes: AtomicUsize::new(0),
+            warns_seen: AtomicU64::new(0),
+            last_notices: Mutex::new([None; 2]),
+        });
+        queue.spawn_worker(logger);
+        SinkQueueHandle(queue)
+    }
+
+    fn spawn_worker(self: &Arc<Self>, logger: Weak<LoggerInner>) {
+        let worker = self.clone();
+        std::thread::Builder::new()
+            .name("horizon-sinks".into())
+            .spawn(move || worker.run(logger))
+            .expect("failed to spawn sink queue thread");
+    }
+
+    fn lock(&self) -> MutexGuard<'_, QueueState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn run(self: Arc<Self>, logger: Weak<LoggerInner>) {
+        let mut lanes = Vec::new();
+        loop {
+            let mut state = self.lock();
+            while state.jobs.is_empty() && !state.closed {
+                state = self.changed.wait(state).unwrap_or_else(|poisoned| poisoned.into_inner());
+            }
+            let Some((sinks, entry)) = state.jobs.pop_front() else {
+                return;
+            };
+            state.busy = true;
+            let depth = state.jobs.len();
+            drop(state);
+            self.changed.notify_all();
+
+            let Some(inner) = logger.upgrade() else {
+                return;
+            };
+            let logger = HorizonLogger { inner };
+            if depth <= self.policy.low_water() && self.degraded.swap(false, Ordering::SeqCst) {
+                let dropped = self.dropped_while_degraded.swap(0, Ordering::Relaxed);
+                if self.notice_due(1) {
+                    logger.log(
+                        LogLevel::WARN,
+                        LOGGER_COMPONENT,
+                        &format!("sink queue recovered at {} entries after dropping {}", depth, dropped),
+                    );
+                }
+            }
+            match self.ordering {
+                SinkOrdering::Strict => logger.write_sinks_now(&sinks, &entry),
+                SinkOrdering::Independent => self.dispatch(&mut lanes, &sinks, entry, &logger),
+            }
+            drop(logger);
+
+            self.lock().busy = false;
+            self.changed.notify_all();
+        }
+    }
+
+    /// Hand `entry` to the lane of each of `sinks`, starting lanes for new sinks and ending those of removed ones
+    fn dispatch(
+        self: &Arc<Self>,
+        lanes: &mut Vec<Lane>,
+        sinks: &[Arc<dyn Sink>],
+        entry: LogEntry,
+        logger: &HorizonLogger,
+    ) {
+        let same = |a: &Arc<dyn Sink>, b: &Arc<dyn Sink>| std::ptr::addr_eq(Arc::as_ptr(a), Arc::as_ptr(b));
+        lanes.retain(|lane| sinks.iter().any(|sink| same(sink, &lane.sink)));
+        for sink in sinks {
+            let lane = match lanes.iter().position(|lane| same(sink, &lane.sink)) {
+                Some(i) => &lanes[i],
+                None => {
+                    lanes.push(self.start_lane(sink.clone(), Arc::downgrade(&logger.inner)));
+                    &lanes[lanes.len() - 1]
+                }
+            };
+            self.in_lanes.fetch_add(1, Ordering::SeqCst);
+            if lane.entries.send(entry.clone()).is_err() {
+                self.lane_done();
+            }
+        }
+    }
+
+    fn start_lane(self: &Arc<Self>, sink: Arc<dyn Sink>, logger: Weak<LoggerInner>) -> Lane {
+        let (entries, received) = mpsc::sync_channel::<LogEntry>(self.policy.capacity.max(1));
+        let queue = self.clone();
+        let lane_sink = sink.clone();
+        std::thread::Builder::new()
+            .name("horizon-sink-lane".into())
+            .spawn(move || {
+                for entry in received {
+                    if let Some(inner) = logger.upgrade() {
+                        HorizonLogger { inner }.write_sinks_now(std::slice::from_ref(&lane_sink), &entry);
+                    }
+                    queue.lane_done();
+                }
+            })
+            .expect("failed to spawn sink lane thread");
+        Lane { sink, entries }
+    }
+
+    fn lane_done(&self) {
+        self.in_lanes.fetch_sub(1, Ordering::SeqCst);
+        drop(self.lock());
+        self.changed.notify_all();
+    }
+
+    /// Queue `entry` for `sinks`, or shed it by the policy
+    pub(crate) fn push(&self, logger: &HorizonLogger, sinks: Vec<Arc<dyn Sink>>, entry: &LogEntry) {
+        let policy = &self.policy;
+        let level = entry.level;
+        let mut state = self.lock();
+
+        if state.jobs.len() >= policy.high_water {
+            if !self.degraded.swap(true, Ordering::SeqCst) {
+                self.warns_seen.store(0, Ordering::Relaxed);
+                if self.notice_due(0) {
+                    drop(state);
+                    logger.log(
+                        LogLevel::WARN,
+                        LOGGER_COMPONENT,
+                        &format!(
+                            "sink queue reached {} of {} entries, dropping DEBUG and INFO and keeping 1 WARN in {}",
+                            policy.high_water, policy.capacity, policy.warn_sample
+                        ),
+                    );
+                    state = self.lock();
+                }
+            }
+            let shed = match level {
+                LogLevel::DEBUG | LogLevel::INFO => true,
+                LogLevel::WARN => {
+                    let seen = self.warns_seen.fetch_add(1, Ordering::Relaxed);
+                    !seen.is_multiple_of(policy.warn_sample.max(1) as u64)
+                }
+                LogLevel::ERROR | LogLevel::CRITICAL => false,
+            };
+            if shed {
+                self.drop_entry(level);
+                return;
+            }
+        }
+
+        if state.jobs.len() >= policy.capacity {
+            if level < LogLevel::ERROR {
+                self.drop_entry(level);
+                return;
+            }
+            let (waited, _) = self
+                .changed
+                .wait_timeout_while(state, policy.error_timeout, |state| {
+                    state.jobs.len() >= policy.capacity && !state.closed
+                })
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            state = waited;
+            if state.jobs.len() >= policy.capacity {
+                drop(state);
+                self.stderr_fallbacks.fetch_add(1, Ordering::Relaxed);
+                let _ = writeln!(io::stderr(), "horizon_logger: sink queue full, {}", entry);
+                return;
+            }
+        }
+
+        state.jobs.push_back((sinks, entry.clone()));
+        drop(state);
+        self.changed.notify_all();
+    }
+
+    fn drop_entry(&self, level: LogLevel) {
+        self.dropped[level as usize].fetch_add(1, Ordering::Relaxed);
+        self.dropped_while_degraded.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Whether notice `kind` (0 degraded, 1 recovered) may be logged, marking it logged if so
+    fn notice_due(&self, kind: usize) -> bool {
+        let mut last = self.last_notices.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let now = Instant::now();
+        let due = last[kind].is_none_or(|at| now.duration_since(at) >= NOTICE_INTERVAL);
+        if due {
+            last[kind] = Some(now);
+        }
+        due
+    }
+
+    /// Wait until every queued entry was written, up to `DRAIN_TIMEOUT`
+    pub(crate) fn drain(&self) {
+        let state = self.lock();
+        let _ = self
+            .changed
+            .wait_timeout_while(state, DRAIN_TIMEOUT, |state| {
+                let pending = !state.jobs.is_empty() || state.busy || self.in_lanes.load(Ordering::SeqCst) > 0;
+                pending && !state.closed
+            });
+    }
+
+    fn status(&self) -> QueueStatus {
+        QueueStatus {
+            depth: self.lock().jobs.len(),
+            capacity: self.policy.capacity,
+            degraded: self.degraded.load(Ordering::SeqCst),
+            dropped: std::array::from_fn(|level| self.dropped[level].load(Ordering::Relaxed)),
+            stderr_fallbacks: self.stderr_fallbacks.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl SinkQueueHandle {
+    pub(crate) fn queue(&self) -> &SinkQueue {
+        &self.0
+    }
+
+    /// Start a new worker in the child after `fork()`, dropping the entries the parent had queued
+    ///
+    /// Lanes are started again as entries arrive for their sinks.
+    #[cfg(all(unix, feature = "fork"))]
+    pub(crate) fn restart_after_fork(&self, logger: Weak<LoggerInner>) {
+        let mut state = self.0.lock();
+        state.jobs.clear();
+        state.busy = false;
+        drop(state);
+        self.0.in_lanes.store(0, Ordering::SeqCst);
+        self.0.spawn_worker(logger);
+    }
+}
+
+impl HorizonLogger {
+    /// Depth and drop counts of the sink queue, or `None` if sinks are written on the logging thread
+    ///
+    /// See `LoggerBuilder::async_sinks`.
+    pub fn queue_status(&self) -> Option<QueueStatus> {
+        self.inner.sink_queue.as_ref().map(|handle| handle.queue().status())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::CaptureLogger;
+    use crate::SinkId;
+    use std::io;
+
+    /// Holds every write until opened, like a sink stuck on a slow disk
+    #[derive(Clone, Default)]
+    struct SlowSink {
+        open: Arc<(Mutex<bool>, Condvar)>,
+        written: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl SlowSink {
+        fn open(&self) {
+            *self.open.0.lock().unwrap() = true;
+            self.open.1.notify_all();
+        }
+    }
+
+    impl Sink for SlowSink {
+        fn write(&self, entry: &LogEntry, _options: &crate::FormatOptions) -> io::Result<()> {
+            let (open, cvar) = &*self.open;
+            let _open = cvar.wait_while(open.lock().unwrap(), |open| !*open).unwrap();
+            self.written.lock().unwrap().push(entry.message.to_string());
+            Ok(())
+        }
+    }
+
+    fn wait_for_depth(logger: &HorizonLogger, depth: usize) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while logger.queue_status().unwrap().depth != depth {
+            assert!(Instant::now() < deadline, "queue never reached {} entries", depth);
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn test_tiers_engage_in_order() {
+        let policy = BackpressurePolicy::new()
+            .capacity(6)
+            .high_water(3)
+            .warn_sample(2)
+            .error_timeout(Duration::from_millis(20));
+        let logger = CaptureLogger::from_builder(HorizonLogger::builder().async_sinks(policy));
+        let sink = SlowSink::default();
+        let _id: SinkId = logger.add_sink(sink.clone());
+
+        // The worker takes the first entry and blocks on it
+        logger.info("DB", "first");
+        wait_for_depth(&logger, 0);
+        for n in 0..3 {
+            logger.info("DB", &format!("queued {}", n));
+        }
+        assert!(!logger.queue_status().unwrap().degraded);
+
+        // At the high-water mark: shed DEBUG and INFO, sample WARN
+        logger.debug("DB", "shed debug");
+        logger.info("DB", "shed info");
+        for n in 0..4 {
+            logger.warn("DB", &format!("warn {}", n));
+        }
+        let status = logger.queue_status().unwrap();
+        assert!(status.degraded);
+        assert_eq!(status.depth, 5);
+        assert_eq!(status.dropped(LogLevel::DEBUG), 1);
+        assert_eq!(status.dropped(LogLevel::INFO), 1);
+        assert_eq!(status.dropped(LogLevel::WARN), 2);
+        assert!(logger.messages().iter().any(|m| m.starts_with("sink queue reached 3 of 6 entries")));
+
+        // Errors fill the queue, then wait out the timeout and go to stderr
+        logger.error("DB", "error 0");
+        let started = Instant::now();
+        logger.critical("DB", "critical 0");
+        assert!(started.elapsed() >= Duration::from_millis(20));
+        let status = logger.queue_status().unwrap();
+        assert_eq!(status.depth, 6);
+        assert_eq!(status.stderr_fallbacks, 1);
+        assert_eq!(status.dropped(LogLevel::ERROR), 0);
+
+        sink.open();
+        logger.flush();
+        let status = logger.queue_status().unwrap();
+        assert!(!status.degraded);
+        assert_eq!(status.depth, 0);
+        let written = sink.written.lock().unwrap().clone();
+        assert_eq!(
+            written[..7],
+            ["first", "queued 0", "queued 1", "queued 2", "warn 0", "warn 2", "error 0"]
+        );
+        assert!(written[7..].iter().any(|m| m.starts_with("sink queue recovered")));
+        assert!(logger.messages().iter().any(|m| m == "critical 0"));
+    }
+
+    #[test]
+    fn test_independent_lanes_dont_wait_on_a_slow_sink() {
+        let builder = HorizonLogger::builder()
+            .async_sinks(BackpressurePolicy::default())
+            .sink_ordering(SinkOrdering::Independent);
+        let logger = CaptureLogger::from_builder(builder);
+        let slow = SlowSink::default();
+        logger.add_sink(slow.clone());
+        let fast = SlowSink::default();
+        fast.open();
+        logger.add_sink(fast.clone());
+
+        for n in 0..3 {
+            logger.info("DB", &format!("row {}", n));
+        }
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while fast.written.lock().unwrap().len() < 3 {
+            assert!(Instant::now() < deadline, "fast sink waited on the slow one");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert!(slow.written.lock().unwrap().is_empty());
+
+        slow.open();
+        logger.flush();
+        assert_eq!(*slow.written.lock().unwrap(), ["row 0", "row 1", "row 2"]);
+    }
+}
@@ -0,0 +1,870 @@
+//! Reading back the files `FileSink` and `BinarySink` write
+//!
+//! `LogFileReader::open` detects the format from the file's header and
+//! yields its entries one at a time. Records that can't be parsed, such as
+//! a line cut short by a crash, are skipped and counted in `errors` rather
+//! than ending the read.
+//!
+//! ```no_run
+//! use horizon_logger::reader::LogFileReader;
+//! use horizon_logger::LogLevel;
+//!
+//! let mut reader = LogFileReader::open("server.log")?.min_level(LogLevel::WARN).component("NETWORK");
+//! for entry in reader.by_ref() {
+//!     println!("{}", entry);
+//! }
+//! if !reader.errors().is_empty() {
+//!     eprintln!("{}", reader.errors());
+//! }
+//! # Ok::<(), horizon_logger::DetectError>(())
+//! ```
+//!
+//! Text files can only be read in the default layout; the timestamp,
+//! level, component, code and message come back, and lines that don't start
+//! with a timestamp continue the previous message. JSON and logfmt carry
+//! every field they hold, and numeric timestamps are read as milliseconds or
+//! microseconds by their magnitude.
+
+use crate::header::{detect_format, DetectError, FileKind, FormatInfo, FORMAT_VERSION};
+use crate::{BinaryLogReader, Format, HistoryQuery, LogEntry, LogLevel, SampleRate, Timestamp};
+use std::borrow::Cow;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Lines};
+use std::path::Path;
+use std::sync::Arc;
+
+/// How many unreadable records `ReadErrors` keeps the details of
+const MAX_KEPT_ERRORS: usize = 32;
+
+/// Where an unreadable record starts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Location {
+    /// Line number in a text, JSON or logfmt file, counting from 1
+    Line(u64),
+    /// Byte offset in a binary file
+    Offset(u64),
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Location::Line(line) => write!(f, "line {}", line),
+            Location::Offset(offset) => write!(f, "byte {}", offset),
+        }
+    }
+}
+
+/// A record `LogFileReader` skipped
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BadRecord {
+    pub location: Location,
+    pub reason: String,
+}
+
+/// Records skipped so far, see `LogFileReader::errors`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReadErrors {
+    /// Every skipped record
+    pub count: u64,
+    /// Details of the first few
+    pub records: Vec<BadRecord>,
+}
+
+impl ReadErrors {
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    fn push(&mut self, location: Location, reason: impl Into<String>) {
+        self.count += 1;
+        if self.records.len() < MAX_KEPT_ERRORS {
+            self.records.push(BadRecord {
+                location,
+                reason: reason.into(),
+            });
+        }
+    }
+}
+
+impl fmt::Display for ReadErrors {
+    /// A one-line summary naming the first skipped record
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.records.first() {
+            None => write!(f, "no unreadable records"),
+            Some(first) => write!(
+                f,
+                "{} unreadable record(s), first at {}: {}",
+                self.count, first.location, first.reason
+            ),
+        }
+    }
+}
+
+enum Source {
+    Lines {
+        lines: Lines<BufReader<File>>,
+        format: Format,
+        line: u64,
+    },
+    Binary(BinaryLogReader),
+    Done,
+}
+
+/// Iterates the entries of a log file written by this crate, see the module docs
+pub struct LogFileReader {
+    source: Source,
+    info: FormatInfo,
+    query: HistoryQuery,
+    start: Option<Timestamp>,
+    end: Option<Timestamp>,
+    /// A text entry that later lines may still continue
+    pending: Option<LogEntry>,
+    errors: ReadErrors,
+}
+
+impl LogFileReader {
+    /// Open `path`, detecting its format from the header
+    ///
+    /// A line file without a header is read as JSON if it starts with `{`,
+    /// logfmt if it starts with `ts=`, and text otherwise.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, DetectError> {
+        let path = path.as_ref();
+        let info = match detect_format(path) {
+            Ok(info) => info,
+            Err(DetectError::NoHeader) => FormatInfo {
+                kind: FileKind::Lines(sniff(path)?),
+                version: FORMAT_VERSION,
+                fields: Vec::new(),
+                run: None,
+            },
+            Err(e) => return Err(e),
+        };
+        let source = match info.kind {
+            FileKind::Binary => Source::Binary(BinaryLogReader::open(path)?),
+            FileKind::Lines(format) => Source::Lines {
+                lines: BufReader::new(File::open(path)?).lines(),
+                format,
+                line: 0,
+            },
+        };
+        Ok(LogFileReader {
+            source,
+            info,
+            query: HistoryQuery::default(),
+            start: None,
+            end: None,
+            pending: None,
+            errors: ReadErrors::default(),
+        })
+    }
+
+    /// The file's format, as announced by its header
+    pub fn format(&self) -> &FormatInfo {
+        &self.info
+    }
+
+    /// Records skipped so far because they could not be read
+    pub fn errors(&self) -> &ReadErrors {
+        &self.errors
+    }
+
+    /// Only entries matching `query`; its `tail` is ignored, as entries are read in order
+    pub fn query(mut self, query: HistoryQuery) -> Self {
+        self.query = query;
+        self
+    }
+
+    /// Only entries at or above `level`
+    pub fn min_level(mut self, level: LogLevel) -> Self {
+        self.query.min_level = Some(level);
+        self
+    }
+
+    /// Only entries under component `prefix`
+    pub fn component(mut self, prefix: &str) -> Self {
+        self.query.component = Some(prefix.to_string());
+        self
+    }
+
+    /// Only entries whose message contains `text`
+    pub fn contains(mut self, text: &str) -> Self {
+        self.query.contains = Some(text.to_string());
+        self
+    }
+
+    /// Only entries logged at or after `start` and before `end`
+    pub fn time_range(mut self, start: Timestamp, end: Timestamp) -> Self {
+        self.start = Some(start);
+        self.end = Some(end);
+        self
+    }
+
+    fn matches(&self, entry: &LogEntry) -> bool {
+        self.query.matches(entry)
+            && self.start.is_none_or(|start| entry.timestamp >= start)
+            && self.end.is_none_or(|end| entry.timestamp < end)
+    }
+
+    /// The next entry in the file, filtered or not
+    fn read(&mut self) -> Option<LogEntry> {
+        match &mut self.source {
+            Source::Binary(reader) => {
+                let entry = reader.next();
+                if entry.is_none() {
+                    if let Some(offset) = reader.stopped_at() {
+                        self.errors.push(Location::Offset(offset), "truncated or corrupt record");
+                    }
+                    self.source = Source::Done;
+                }
+                entry
+            }
+            Source::Lines { lines, format, line } => loop {
+                let text = match lines.next() {
+                    Some(Ok(text)) => text,
+                    Some(Err(e)) => {
+                        self.errors.push(Location::Line(*line + 1), e.to_string());
+                        if e.kind() == io::ErrorKind::InvalidData {
+                            // Not UTF-8; the next line may still be fine
+                            *line += 1;
+                            continue;
+                        }
+                        let pending = self.pending.take();
+                        self.source = Source::Done;
+                        return pending;
+                    }
+                    None => {
+                        let pending = self.pending.take();
+                        self.source = Source::Done;
+                        return pending;
+                    }
+                };
+                *line += 1;
+                if text.is_empty() || (*line == 1 && text.starts_with("# horizon-logger ")) {
+                    continue;
+                }
+
+                let parsed = match format {
+                    Format::Json => parse_json(&text),
+                    Format::Logfmt => parse_logfmt(&text),
+                    Format::Text => match parse_text(&text) {
+                        Some(entry) => match self.pending.replace(entry) {
+                            Some(previous) => return Some(previous),
+                            None => continue,
+                        },
+                        None => match &mut self.pending {
+                            Some(previous) => {
+                                previous.message.push('\n');
+                                previous.message.push_str(&text);
+                                continue;
+                            }
+                            None => Err("not a log line".to_string()),
+                        },
+                    },
+                };
+                match parsed {
+                    Ok(entry) => return Some(entry),
+                    Err(reason) => self.errors.push(Location::Line(*line), reason),
+                }
+            },
+            Source::Done => None,
+        }
+    }
+}
+
+impl Iterator for LogFileReader {
+    type Item = LogEntry;
+
+    fn next(&mut self) -> Option<LogEntry> {
+        while let Some(entry) = self.read() {
+            if self.matches(&entry) {
+                return Some(entry);
+            }
+        }
+        None
+    }
+}
+
+/// Guess the format of a line file without a header
+fn sniff(path: &Path) -> io::Result<Format> {
+    let mut first = String::new();
+    for line in BufReader::new(File::open(path)?).lines() {
+        first = line?;
+        if !first.is_empty() {
+            break;
+        }
+    }
+    Ok(if first.starts_with('{') {
+        Format::Json
+    } else if first.starts_with("ts=") {
+        Format::Logfmt
+    } else {
+        Format::Text
+    })
+}
+
+/// An entry with only a timestamp and level to fill in
+fn blank_entry() -> LogEntry {
+    LogEntry::at(Timestamp::from_micros(0), LogLevel::INFO, "", "")
+}
+
+/// Finish an entry whose fields were read in any order
+fn complete(
+    mut entry: LogEntry,
+    timestamp: Option<Timestamp>,
+    level: Option<LogLevel>,
+    severity: Option<u8>,
+) -> Result<LogEntry, String> {
+    entry.timestamp = timestamp.ok_or("no timestamp")?;
+    entry.level = level.ok_or("no level")?;
+    entry.severity = severity.unwrap_or(entry.level.default_severity());
+    if entry.repeat_count <= 1 {
+        entry.last_timestamp = entry.timestamp;
+    }
+    Ok(entry)
+}
+
+/// A numeric machine timestamp: microseconds if it is too large to be milliseconds of a plausible date
+fn numeric_time(n: i64) -> Timestamp {
+    if n.unsigned_abs() >= 100_000_000_000_000 {
+        Timestamp::from_micros(n)
+    } else {
+        Timestamp::from_millis(n)
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("bad `{}`: {}", key, value))
+}
+
+fn parse_time(key: &str, value: &str) -> Result<Timestamp, String> {
+    match value.parse::<i64>() {
+        Ok(n) => Ok(numeric_time(n)),
+        Err(_) => Timestamp::parse_rfc3339(value).ok_or_else(|| format!("bad `{}`: {}", key, value)),
+    }
+}
+
+fn parse_sample_rate(value: &str) -> Result<SampleRate, String> {
+    match value.strip_prefix("1/") {
+        Some(n) => parse_number("sampled", n).map(SampleRate::OneIn),
+        None => parse_number("sampled", value).map(SampleRate::Probability),
+    }
+}
+
+fn parse_level(value: &str) -> Result<LogLevel, String> {
+    value.parse().map_err(|e: crate::ParseLevelError| e.to_string())
+}
+
+/// Read one line of `Format::Json`
+fn parse_json(line: &str) -> Result<LogEntry, String> {
+    let mut parser = json::Parser::new(line);
+    let object = parser.object()?;
+    parser.end()?;
+
+    let mut entry = blank_entry();
+    let (mut timestamp, mut level, mut severity) = (None, None, None);
+    for (key, value) in object {
+        let text = || value.as_text().ok_or_else(|| format!("bad `{}`", key));
+        match key.as_str() {
+            "timestamp" => timestamp = Some(parse_time(&key, text()?)?),
+            "level" => level = Some(parse_level(text()?)?),
+            "severity" => severity = Some(parse_number(&key, text()?)?),
+            "component" => entry.component = Cow::Owned(text()?.to_string()),
+            "message" => entry.message = text()?.to_string(),
+            "code" => entry.code = Some(Arc::from(text()?)),
+            "template" => entry.template = Some(Arc::from(text()?)),
+            "fields" => {
+                let json::Value::Object(fields) = value else {
+                    return Err("bad `fields`".to_string());
+                };
+                entry.fields = fields
+                    .into_iter()
+                    .filter_map(|(name, value)| Some((Cow::Owned(name), value.into_field()?)))
+                    .collect();
+            }
+            "seq" => entry.seq = parse_number(&key, text()?)?,
+            "run" => entry.run_id = Some(Arc::from(text()?)),
+            "span_id" => entry.span_id = Some(parse_number(&key, text()?)?),
+            "parent_id" => entry.parent_id = Some(parse_number(&key, text()?)?),
+            "group_id" => entry.group_id = Some(parse_number(&key, text()?)?),
+            "corr" => entry.correlation_id = Some(Arc::from(text()?)),
+            "backtrace" => entry.backtrace = Some(text()?.to_string()),
+            "repeat_count" => entry.repeat_count = parse_number(&key, text()?)?,
+            "last_timestamp" => entry.last_timestamp = parse_time(&key, text()?)?,
+            "sampled" => entry.sampled = Some(parse_sample_rate(text()?)?),
+            // Fields added by later versions
+            _ => {}
+        }
+    }
+    complete(entry, timestamp, level, severity)
+}
+
+/// Read one line of `Format::Logfmt`
+fn parse_logfmt(line: &str) -> Result<LogEntry, String> {
+    let mut entry = blank_entry();
+    let (mut timestamp, mut level, mut severity) = (None, None, None);
+    let mut rest = line;
+    loop {
+        rest = rest.trim_start_matches(' ');
+        if rest.is_empty() {
+            break;
+        }
+        let (key, after) = rest.split_once('=').ok_or_else(|| format!("no value after `{}`", rest))?;
+        let (value, after) = logfmt_value(after)?;
+        rest = after;
+        match key {
+            "ts" => timestamp = Some(parse_time(key, &value)?),
+            "level" => level = Some(parse_level(&value)?),
+            "severity" => severity = Some(parse_number(key, &value)?),
+            "component" => entry.component = Cow::Owned(value),
+            "msg" => entry.message = value,
+            "code" => entry.code = Some(Arc::from(value)),
+            "seq" => entry.seq = parse_number(key, &value)?,
+            "run" => entry.run_id = Some(Arc::from(value)),
+            "span_id" => entry.span_id = Some(parse_number(key, &value)?),
+            "parent_id" => entry.parent_id = Some(parse_number(key, &value)?),
+            "group_id" => entry.group_id = Some(parse_number(key, &value)?),
+            "corr" => entry.correlation_id = Some(Arc::from(value)),
+            "sampled" => entry.sampled = Some(parse_sample_rate(&value)?),
+            _ => {}
+        }
+    }
+    complete(entry, timestamp, level, severity)
+}
+
+/// A logfmt value, quoted or bare, and what follows it
+fn logfmt_value(text: &str) -> Result<(String, &str), String> {
+    let Some(quoted) = text.strip_prefix('"') else {
+        let end = text.find(' ').unwrap_or(text.len());
+        return Ok((text[..end].to_string(), &text[end..]));
+    };
+    let mut value = String::new();
+    let mut chars = quoted.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((value, &quoted[i + 1..])),
+            '\\' => match chars.next().map(|(_, c)| c) {
+                Some('n') => value.push('\n'),
+                Some('r') => value.push('\r'),
+                Some('t') => value.push('\t'),
+                Some('b') => value.push('\u{8}'),
+                Some('f') => value.push('\u{c}'),
+                Some('u') => {
+                    // `\uXXXX`, or `\u{XXXX}` as written before values were escaped as JSON strings
+                    let code = if quoted[i + 2..].starts_with('{') {
+                        let code: String = chars.by_ref().map(|(_, c)| c).take_while(|&c| c != '}').skip(1).collect();
+                        u32::from_str_radix(&code, 16).ok()
+                    } else {
+                        json::hex4(&mut chars)
+                    };
+                    value.push(code.and_then(char::from_u32).ok_or("bad \\u escape")?);
+                }
+                Some(c) => value.push(c),
+                None => break,
+            },
+            c => value.push(c),
+        }
+    }
+    Err("unterminated quoted value".to_string())
+}
+
+/// Read one line of `Format::Text` in the `Display` layout, `None` if it doesn't start an entry
+fn parse_text(line: &str) -> Option<LogEntry> {
+    let (timestamp, rest) = parse_human_time(line)?;
+    let rest = rest.strip_prefix(' ')?.trim_start_matches(' ');
+    let (level, rest) = rest.split_once(' ').unwrap_or((rest, ""));
+    let level = level.parse().ok()?;
+    let mut rest = rest.trim_start_matches(' ');
+
+    let mut bracketed = |allow_spaces: bool| {
+        let inner = rest.strip_prefix('[')?;
+        let (value, after) = inner.split_once("] ")?;
+        if !allow_spaces && value.contains(' ') {
+            return None;
+        }
+        rest = after;
+        Some(value.to_string())
+    };
+    let component = bracketed(true).unwrap_or_default();
+    // A second bracketed word is the event code
+    let code = bracketed(false);
+
+    let mut entry = LogEntry::at(timestamp, level, &component, rest);
+    entry.code = code.map(Arc::from);
+    Some(entry)
+}
+
+/// A timestamp in `human_time` form and the text after it
+fn parse_human_time(text: &str) -> Option<(Timestamp, &str)> {
+    // ISO 8601 in UTC, as written without chrono
+    let first = text.split(' ').next()?;
+    if let Some(timestamp) = Timestamp::parse_rfc3339(first) {
+        return Some((timestamp, &text[first.len()..]));
+    }
+    let head = text.get(..23)?;
+    Some((parse_local_time(head)?, &text[23..]))
+}
+
+/// `YYYY-MM-DD HH:MM:SS.mmm` in local time, as written with chrono
+#[cfg(feature = "chrono")]
+fn parse_local_time(text: &str) -> Option<Timestamp> {
+    use chrono::TimeZone;
+    let naive = chrono::NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.3f").ok()?;
+    Some(chrono::Local.from_local_datetime(&naive).earliest()?.into())
+}
+
+#[cfg(not(feature = "chrono"))]
+fn parse_local_time(_text: &str) -> Option<Timestamp> {
+    None
+}
+
+/// Just enough JSON for the lines `Format::Json` writes
+mod json {
+    use crate::FieldValue;
+
+    pub(super) enum Value {
+        Str(String),
+        /// The number as written
+        Number(String),
+        Bool(bool),
+        Null,
+        Object(Vec<(String, Value)>),
+        Array,
+    }
+
+    impl Value {
+        /// Strings and numbers as text
+        pub(super) fn as_text(&self) -> Option<&str> {
+            match self {
+                Value::Str(s) | Value::Number(s) => Some(s),
+                _ => None,
+            }
+        }
+
+        pub(super) fn into_field(self) -> Option<FieldValue> {
+            Some(match self {
+                Value::Str(s) => FieldValue::Str(s),
+                Value::Bool(b) => FieldValue::Bool(b),
+                Value::Number(n) if n.contains(['.', 'e', 'E']) => FieldValue::Float(n.parse().ok()?),
+                // Integers too large for `i64` are the only ones known to be unsigned
+                Value::Number(n) => match n.parse() {
+                    Ok(n) => FieldValue::Int(n),
+                    Err(_) => FieldValue::UInt(n.parse().ok()?),
+                },
+                Value::Null | Value::Object(_) | Value::Array => return None,
+            })
+        }
+    }
+
+    /// Four hex digits of a `\u` escape
+    pub(super) fn hex4(chars: &mut impl Iterator<Item = (usize, char)>) -> Option<u32> {
+        let digits: String = chars.take(4).map(|(_, c)| c).collect();
+        u32::from_str_radix(&digits, 16).ok()
+    }
+
+    pub(super) struct Parser<'a> {
+        text: &'a str,
+        pos: usize,
+    }
+
+    impl<'a> Parser<'a> {
+        pub(super) fn new(text: &'a str) -> Self {
+            Parser { text, pos: 0 }
+        }
+
+        fn skip_space(&mut self) {
+            let rest = &self.text[self.pos..];
+            self.pos += rest.len() - rest.trim_start().len();
+        }
+
+        fn peek(&mut self) -> Option<char> {
+            self.skip_space();
+            self.text[self.pos..].chars().next()
+        }
+
+        fn expect(&mut self, c: char) -> Result<(), String> {
+            if self.peek() != Some(c) {
+                return Err(self.error(&format!("expected `{}`", c)));
+            }
+            self.pos += 1;
+            Ok(())
+        }
+
+        fn error(&self, what: &str) -> String {
+            if self.pos >= self.text.len() {
+                format!("{} at end of line; truncated?", what)
+            } else {
+                format!("{} at column {}", what, self.pos + 1)
+            }
+        }
+
+        /// Nothing but whitespace may follow the value
+        pub(super) fn end(&mut self) -> Result<(), String> {
+            match self.peek() {
+                None => Ok(()),
+                Some(_) => Err(self.error("trailing characters")),
+            }
+        }
+
+        pub(super) fn object(&mut self) -> Result<Vec<(String, Value)>, String> {
+            self.expect('{')?;
+            let mut members = Vec::new();
+            if self.peek() == Some('}') {
+                self.pos += 1;
+                return Ok(members);
+            }
+            loop {
+                if self.peek() != Some('"') {
+                    return Err(self.error("expected a key"));
+                }
+                let key = self.string()?;
+                self.expect(':')?;
+                members.push((key, self.value()?));
+                match self.peek() {
+                    Some(',') => self.pos += 1,
+                    Some('}') => {
+                        self.pos += 1;
+                        return Ok(members);
+                    }
+                    _ => return Err(self.error("expected `,` or `}`")),
+                }
+            }
+        }
+
+        fn value(&mut self) -> Result<Value, String> {
+            match self.peek() {
+                Some('{') => self.object().map(Value::Object),
+                Some('[') => {
+                    self.pos += 1;
+                    if self.peek() != Some(']') {
+                        loop {
+                            self.value()?;
+                            match self.peek() {
+                                Some(',') => self.pos += 1,
+                                Some(']') => break,
+                                _ => return Err(self.error("expected `,` or `]`")),
+                            }
+                        }
+                    }
+                    self.pos += 1;
+                    Ok(Value::Array)
+                }
+                Some('"') => self.string().map(Value::Str),
+                Some('-' | '0'..='9') => {
+                    let rest = &self.text[self.pos..];
+                    let len = rest
+                        .find(|c: char| !matches!(c, '-' | '+' | '.' | 'e' | 'E' | '0'..='9'))
+                        .unwrap_or(rest.len());
+                    self.pos += len;
+                    Ok(Value::Number(rest[..len].to_string()))
+                }
+                _ => {
+                    let words = [("true", Value::Bool(true)), ("false", Value::Bool(false)), ("null", Value::Null)];
+                    for (word, value) in words {
+                        if self.text[self.pos..].starts_with(word) {
+                            self.pos += word.len();
+                            return Ok(value);
+                        }
+                    }
+                    Err(self.error("expected a value"))
+                }
+            }
+        }
+
+        fn string(&mut self) -> Result<String, String> {
+            self.expect('"')?;
+            let mut out = String::new();
+            let mut chars = self.text[self.pos..].char_indices();
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '"' => {
+                        self.pos += i + 1;
+                        return Ok(out);
+                    }
+                    '\\' => {
+                        let escaped = match chars.next().map(|(_, c)| c) {
+                            Some('n') => '\n',
+                            Some('r') => '\r',
+                            Some('t') => '\t',
+                            Some('b') => '\u{8}',
+                            Some('f') => '\u{c}',
+                            Some('u') => {
+                                let high = hex4(&mut chars).ok_or("bad \\u escape")?;
+                                let code = if (0xd800..0xdc00).contains(&high) {
+                                    // The second half of a surrogate pair follows
+                                    let low = match (chars.next(), chars.next()) {
+                                        (Some((_, '\\')), Some((_, 'u'))) => hex4(&mut chars).ok_or("bad \\u escape")?,
+                                        _ => return Err("unpaired surrogate".to_string()),
+                                    };
+                                    0x10000 + ((high - 0xd800) << 10) + (low.wrapping_sub(0xdc00) & 0x3ff)
+                                } else {
+                                    high
+                                };
+                                char::from_u32(code).ok_or("bad \\u escape")?
+                            }
+                            Some(c) => c,
+                            None => break,
+                        };
+                        out.push(escaped);
+                    }
+                    c => out.push(c),
+                }
+            }
+            self.pos = self.text.len();
+            Err(self.error("unterminated string"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixtures::temp_path;
+    use crate::testing::CaptureLogger;
+    use crate::{BinarySink, FileSink, HorizonLogger, MachineTimestamp, ManualClock};
+    use std::io::Write;
+    use std::time::Duration;
+
+    /// Log a few entries a millisecond apart through `sink` and return them as logged
+    fn write_entries(sink: impl crate::Sink + 'static) -> Vec<LogEntry> {
+        let clock = Arc::new(ManualClock::new(Timestamp::from_micros(1_700_000_000_123_456)));
+        let logger = CaptureLogger::from_builder(HorizonLogger::builder().clock(clock.clone()));
+        logger.add_sink(sink);
+        logger.info("NETWORK", "client connected: id=7 addr=\"10.0.0.1\"");
+        clock.advance(Duration::from_millis(1));
+        logger.event(LogLevel::WARN, "GAME/PHYSICS").code("PHYS-0001").log("step took 21ms");
+        clock.advance(Duration::from_millis(1));
+        logger.error("", "multi\nline");
+        clock.advance(Duration::from_millis(1));
+        logger
+            .event(LogLevel::INFO, "GAME")
+            .template("player {player} dealt {amount} damage")
+            .field("player", "bob \u{1f600}")
+            .field("amount", 50)
+            .emit();
+        logger.flush();
+        logger.entries()
+    }
+
+    fn shared_fields(entry: &LogEntry) -> (LogLevel, &str, &str, Option<&str>) {
+        (entry.level, &entry.component, &entry.message, entry.code.as_deref())
+    }
+
+    #[test]
+    fn test_round_trips() {
+        for (name, format) in [("text", Format::Text), ("json", Format::Json), ("logfmt", Format::Logfmt)] {
+            let path = temp_path(&format!("reader_round_trip.{}", name));
+            let logged = write_entries(FileSink::new(&path).unwrap().with_format(format));
+
+            let mut reader = LogFileReader::open(&path).unwrap();
+            assert_eq!(reader.format().kind, FileKind::Lines(format));
+            let read: Vec<_> = reader.by_ref().collect();
+            assert!(reader.errors().is_empty(), "{}: {}", name, reader.errors());
+            assert_eq!(read.len(), logged.len(), "{}", name);
+            for (read, logged) in read.iter().zip(&logged) {
+                assert_eq!(shared_fields(read), shared_fields(logged), "{}", name);
+                assert_eq!(read.timestamp.as_millis(), logged.timestamp.as_millis(), "{}", name);
+                if format != Format::Text {
+                    assert_eq!((read.seq, read.severity, &read.run_id), (logged.seq, logged.severity, &logged.run_id));
+                    assert_eq!(read.timestamp, logged.timestamp);
+                }
+            }
+            if format == Format::Json {
+                assert_eq!(read[3].template, logged[3].template);
+                assert_eq!(read[3].fields, logged[3].fields);
+            }
+            let _ = std::fs::remove_file(&path);
+        }
+
+        let path = temp_path("reader_round_trip.bin");
+        let logged = write_entries(BinarySink::new(&path).unwrap());
+        let read: Vec<_> = LogFileReader::open(&path).unwrap().collect();
+        // Binary records have no event code
+        let without_code = |e: &LogEntry| (e.timestamp, e.level, e.component.to_string(), e.message.clone());
+        let read: Vec<_> = read.iter().map(without_code).collect();
+        assert_eq!(read, logged.iter().map(without_code).collect::<Vec<_>>());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_machine_timestamps() {
+        for style in [MachineTimestamp::EpochMillis, MachineTimestamp::EpochMicros] {
+            let path = temp_path("reader_epoch.jsonl");
+            let options = crate::FormatOptions {
+                machine_timestamp: style,
+                ..crate::FormatOptions::default()
+            };
+            let logged = write_entries(FileSink::new(&path).unwrap().with_format(Format::Json).with_options(options));
+            let read: Vec<_> = LogFileReader::open(&path).unwrap().collect();
+            let expected = match style {
+                MachineTimestamp::EpochMillis => Timestamp::from_millis(logged[0].timestamp.as_millis()),
+                _ => logged[0].timestamp,
+            };
+            assert_eq!(read[0].timestamp, expected);
+            let _ = std::fs::remove_file(&path);
+        }
+    }
+
+    #[test]
+    fn test_corrupt_records_are_skipped() {
+        let path = temp_path("reader_corrupt.jsonl");
+        write_entries(FileSink::new(&path).unwrap().with_format(Format::Json));
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let mut lines: Vec<_> = contents.lines().collect();
+        lines.insert(2, "{\"level\":\"INFO\"}");
+        lines.insert(3, "not json at all");
+        let truncated = &lines[6][..lines[6].len() / 2];
+        let contents = format!("{}\n{}", lines[..6].join("\n"), truncated);
+        std::fs::write(&path, contents).unwrap();
+
+        let mut reader = LogFileReader::open(&path).unwrap();
+        let read: Vec<_> = reader.by_ref().map(|e| e.message).collect();
+        assert_eq!(read, ["client connected: id=7 addr=\"10.0.0.1\"", "step took 21ms", "multi\nline"]);
+        let errors = reader.errors();
+        assert_eq!(errors.count, 3);
+        let locations: Vec<_> = errors.records.iter().map(|r| r.location).collect();
+        assert_eq!(locations, [Location::Line(3), Location::Line(4), Location::Line(7)]);
+        assert!(errors.records[2].reason.contains("truncated"), "{}", errors);
+        assert_eq!(errors.to_string(), "3 unreadable record(s), first at line 3: no timestamp");
+        let _ = std::fs::remove_file(&path);
+
+        // A binary file cut off mid-record
+        let path = temp_path("reader_corrupt.bin");
+        write_entries(BinarySink::new(&path).unwrap());
+        let len = std::fs::metadata(&path).unwrap().len();
+        std::fs::OpenOptions::new().write(true).open(&path).unwrap().set_len(len - 3).unwrap();
+        let mut reader = LogFileReader::open(&path).unwrap();
+        assert_eq!(reader.by_ref().count(), 3);
+        assert!(matches!(reader.errors().records[0].location, Location::Offset(_)));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_filters() {
+        let path = temp_path("reader_filters.log");
+        let logged = write_entries(FileSink::new(&path).unwrap().with_format(Format::Logfmt));
+
+        let components = |reader: LogFileReader| -> Vec<String> { reader.map(|e| e.component.into_owned()).collect() };
+        assert_eq!(components(LogFileReader::open(&path).unwrap().min_level(LogLevel::WARN)), ["GAME/PHYSICS", ""]);
+        assert_eq!(components(LogFileReader::open(&path).unwrap().component("GAME")), ["GAME/PHYSICS", "GAME"]);
+        assert_eq!(components(LogFileReader::open(&path).unwrap().contains("player")), ["GAME"]);
+        let range = LogFileReader::open(&path).unwrap().time_range(logged[1].timestamp, logged[3].timestamp);
+        assert_eq!(range.count(), 2);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_files_without_header() {
+        let path = temp_path("reader_no_header.log");
+        let mut file = File::create(&path).unwrap();
+        writeln!(file, "ts=2023-11-14T22:13:20.123456Z level=WARN component=GAME msg=\"lag spike\"").unwrap();
+        writeln!(file, "ts=1700000000123 level=INFO msg=ok").unwrap();
+        drop(file);
+
+        let read: Vec<_> = LogFileReader::open(&path).unwrap().collect();
+        assert_eq!(read.len(), 2);
+        assert_eq!(shared_fields(&read[0]), (LogLevel::WARN, "GAME", "lag spike", None));
+        assert_eq!(read[1].timestamp, Timestamp::from_millis(1_700_000_000_123));
+        let _ = std::fs::remove_file(&path);
+    }
+}
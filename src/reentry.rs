@@ -0,0 +1,92 @@
+use crate::{HorizonLogger, LogEntry};
+use std::cell::{Cell, RefCell};
+use std::marker::PhantomData;
+
+/// An entry logged from inside another log call, recorded once that call returns
+pub(crate) struct Deferred {
+    pub(crate) logger: HorizonLogger,
+    pub(crate) entry: LogEntry,
+    pub(crate) indent: usize,
+}
+
+thread_local! {
+    /// Whether this thread is currently inside a log call
+    static ACTIVE: Cell<bool> = const { Cell::new(false) };
+    /// Entries logged by sinks while `ACTIVE` was set
+    static DEFERRED: RefCell<Vec<Deferred>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Marks the current thread as inside a log call until dropped
+pub(crate) struct Guard {
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        ACTIVE.with(|active| active.set(false));
+    }
+}
+
+/// Enter a log call, or `None` if this thread is already inside one
+pub(crate) fn enter() -> Option<Guard> {
+    ACTIVE.with(|active| {
+        if active.replace(true) {
+            None
+        } else {
+            Some(Guard { _not_send: PhantomData })
+        }
+    })
+}
+
+/// Queue an entry logged from inside a log call
+pub(crate) fn defer(entry: Deferred) {
+    DEFERRED.with(|queue| queue.borrow_mut().push(entry));
+}
+
+/// The queued entries, oldest first
+pub(crate) fn take() -> Vec<Deferred> {
+    DEFERRED.with(|queue| std::mem::take(&mut *queue.borrow_mut()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::CaptureLogger;
+    use crate::{FormatOptions, LogEntry, LogLevel, Sink};
+    use std::io;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Logs through the same logger on every write, like a webhook reporting its own failures
+    struct ChattySink {
+        logger: CaptureLogger,
+        writes: Arc<AtomicUsize>,
+    }
+
+    impl Sink for ChattySink {
+        fn write(&self, entry: &LogEntry, _format: &FormatOptions) -> io::Result<()> {
+            self.writes.fetch_add(1, Ordering::Relaxed);
+            self.logger.error("WEBHOOK", &format!("failed to post #{}", entry.seq));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_sink_logging_does_not_recurse() {
+        let logger = CaptureLogger::new();
+        let writes = Arc::new(AtomicUsize::new(0));
+        logger.add_sink(ChattySink {
+            logger: logger.clone(),
+            writes: writes.clone(),
+        });
+
+        logger.info("GAME", "match started");
+        logger.info("GAME", "match ended");
+
+        assert_eq!(
+            logger.messages(),
+            vec!["match started", "failed to post #0", "match ended", "failed to post #2"]
+        );
+        assert_eq!(logger.entries()[1].level, LogLevel::ERROR);
+        assert_eq!(writes.load(Ordering::Relaxed), 2);
+    }
+}
@@ -0,0 +1,374 @@
+//! Reloading levels and console settings from a file on a live logger
+//!
+//! The file holds a filter directive as accepted by `replace_directives`,
+//! with one or more parts per line, and `name: value` settings, one per
+//! line. `#` starts a comment anywhere in a line, patterns included:
+//!
+//! ```text
+//! # default level
+//! info
+//! network=debug
+//! game/combat=warn, storage=error
+//!
+//! color: never
+//! console_pattern: {time} {level} [{component}] {message}
+//! history_capacity: 5000
+//! ```
+//!
+//! - `color`: `auto`, `always` or `never`, as `set_console_color`
+//! - `console_pattern`: a `Pattern` for console lines, as `set_console_pattern`
+//! - `history_capacity`: entries the history keeps, as `set_history_capacity`
+//!
+//! A setting missing from the file goes back to what it was when the
+//! watch started, as levels missing from it do. Sinks stay as the program
+//! set them up.
+
+use crate::run::LOGGER_COMPONENT;
+use crate::{ConsoleColor, HorizonLogger, LogLevel, Pattern};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime};
+
+/// Stops a watch started with `watch_config` when dropped
+pub struct ConfigWatchHandle {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ConfigWatchHandle {
+    /// Stop watching and wait for the polling thread to exit
+    pub fn stop_watching(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        // Disconnecting wakes the thread immediately
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for ConfigWatchHandle {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// The settings a config file can hold besides levels
+#[derive(Debug, Clone, PartialEq)]
+struct Settings {
+    color: ConsoleColor,
+    console_pattern: Option<Pattern>,
+    history_capacity: usize,
+}
+
+impl Settings {
+    /// The settings `logger` has now
+    fn of(logger: &HorizonLogger) -> Self {
+        Settings {
+            color: logger.console_color(),
+            console_pattern: logger.console_pattern(),
+            history_capacity: logger.history_capacity(),
+        }
+    }
+
+    /// Give `logger` the settings that differ from `current`, one line for each
+    fn apply(&self, logger: &HorizonLogger, current: &Settings) -> Vec<String> {
+        let pattern = |pattern: &Option<Pattern>| pattern.as_ref().map_or("none".to_string(), Pattern::to_string);
+        let mut changes = Vec::new();
+        if self.color != current.color {
+            logger.set_console_color(self.color);
+            changes.push(format!("color: {} -> {}", current.color.as_str(), self.color.as_str()));
+        }
+        if self.console_pattern != current.console_pattern {
+            logger.set_console_pattern(self.console_pattern.clone());
+            let (old, new) = (pattern(&current.console_pattern), pattern(&self.console_pattern));
+            changes.push(format!("console_pattern: {} -> {}", old, new));
+        }
+        if self.history_capacity != current.history_capacity {
+            logger.set_history_capacity(self.history_capacity);
+            changes.push(format!("history_capacity: {} -> {}", current.history_capacity, self.history_capacity));
+        }
+        changes
+    }
+}
+
+/// A config file's contents: the directive, lines joined by commas, and the settings
+#[derive(Debug, PartialEq)]
+struct Config {
+    directive: String,
+    settings: Settings,
+}
+
+/// Read a config file, with any setting it leaves out as in `baseline`
+fn parse_config(contents: &str, baseline: &Settings) -> Result<Config, String> {
+    let mut directive = Vec::new();
+    let mut settings = baseline.clone();
+    for line in contents.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        let setting = line.split_once(':').map(|(name, value)| (name.trim(), value.trim()));
+        match setting {
+            Some(("color", value)) => {
+                settings.color = ConsoleColor::from_name(value).ok_or_else(|| format!("unknown color mode {}", value))?;
+            }
+            Some(("console_pattern", value)) => {
+                let pattern = value.parse().map_err(|e| format!("console_pattern: {}", e))?;
+                settings.console_pattern = Some(pattern);
+            }
+            Some(("history_capacity", value)) => {
+                let capacity = value.parse().map_err(|_| format!("history_capacity: not a number: {}", value))?;
+                settings.history_capacity = capacity;
+            }
+            _ if line.is_empty() => {}
+            _ => directive.push(line),
+        }
+    }
+    Ok(Config {
+        directive: directive.join(","),
+        settings,
+    })
+}
+
+/// What a file looked like when last read, to notice edits
+type Version = Option<(SystemTime, u64)>;
+
+fn version(path: &Path) -> Version {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+struct Watch {
+    logger: HorizonLogger,
+    path: PathBuf,
+    /// The settings when the watch started, for those the file leaves out
+    baseline: Settings,
+    /// The version last applied or rejected
+    version: Version,
+    /// A newer version seen on the previous poll, applied once it stops changing
+    pending: Version,
+    /// The last problem reported, so a broken file is only warned about once
+    last_error: Option<String>,
+}
+
+impl Watch {
+    /// Apply the file if it changed and has not changed since the previous poll
+    ///
+    /// Waiting one poll skips files caught halfway through being written.
+    fn poll(&mut self) {
+        let version = version(&self.path);
+        if version.is_some() && version == self.version {
+            return;
+        }
+        if version != self.pending {
+            self.pending = version;
+            return;
+        }
+        self.load();
+    }
+
+    fn load(&mut self) {
+        self.version = version(&self.path);
+
+        // Settings are checked before any level changes, so a bad file changes nothing
+        let applied = fs::read_to_string(&self.path)
+            .map_err(|e| e.to_string())
+            .and_then(|contents| parse_config(&contents, &self.baseline))
+            .and_then(|config| {
+                let levels = self.logger.replace_directives(&config.directive).map_err(|e| e.to_string())?;
+                Ok((levels, config.settings))
+            });
+        match applied {
+            Ok((levels, settings)) => {
+                self.last_error = None;
+                let mut changes: Vec<String> = levels.to_string().lines().map(String::from).collect();
+                changes.extend(settings.apply(&self.logger, &Settings::of(&self.logger)));
+                if !changes.is_empty() {
+                    let message = format!("config {} reloaded: {}", self.path.display(), changes.join("; "));
+                    self.logger.log(LogLevel::INFO, LOGGER_COMPONENT, &message);
+                }
+            }
+            Err(error) if self.last_error.as_ref() != Some(&error) => {
+                let message = format!("config {} not applied, keeping the previous one: {}", self.path.display(), error);
+                self.logger.log(LogLevel::WARN, LOGGER_COMPONENT, &message);
+                self.last_error = Some(error);
+            }
+            Err(_) => {}
+        }
+    }
+}
+
+impl HorizonLogger {
+    /// Apply the levels and settings in `path` now and again whenever the file changes
+    ///
+    /// The file is checked every `poll_interval` by modification time and
+    /// size, and reloaded once it has been left unchanged for one interval.
+    /// Each reload replaces the whole level configuration and the color
+    /// mode, console pattern and history capacity, logging an INFO under
+    /// `LOGGER` listing what changed. A file that cannot be read or parsed
+    /// leaves everything as it was and is warned about once per distinct
+    /// error.
+    pub fn watch_config(&self, path: impl AsRef<Path>, poll_interval: Duration) -> ConfigWatchHandle {
+        let mut watch = Watch {
+            logger: self.clone(),
+            path: path.as_ref().to_path_buf(),
+            baseline: Settings::of(self),
+            version: None,
+            pending: None,
+            last_error: None,
+        };
+        watch.load();
+
+        let (stop, stopped) = mpsc::channel::<()>();
+        let thread = thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(poll_interval) {
+                watch.poll();
+            }
+        });
+
+        ConfigWatchHandle {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixtures::temp_path;
+    use crate::testing::CaptureLogger;
+    use std::time::Instant;
+
+    fn logger_messages(logger: &CaptureLogger) -> Vec<String> {
+        logger
+            .entries()
+            .into_iter()
+            .filter(|e| e.component == LOGGER_COMPONENT)
+            .map(|e| format!("{} {}", e.level, e.message))
+            .collect()
+    }
+
+    /// Wait for the watcher to log its `count`th message
+    fn wait_for(logger: &CaptureLogger, count: usize) -> Vec<String> {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            let messages = logger_messages(logger);
+            if messages.len() >= count {
+                return messages;
+            }
+            assert!(Instant::now() < deadline, "{:?}", messages);
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    fn baseline() -> Settings {
+        Settings {
+            color: ConsoleColor::Auto,
+            console_pattern: None,
+            history_capacity: 1000,
+        }
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let config = parse_config("# levels\ninfo\n\nnetwork=debug # chatty\na=warn, b=error\n", &baseline()).unwrap();
+        assert_eq!(config.directive, "info,network=debug,a=warn, b=error");
+        assert_eq!(config.settings, baseline());
+
+        let contents = "info\ncolor: Never\nconsole_pattern: {level}: {message} # short\nhistory_capacity: 50\n";
+        let config = parse_config(contents, &baseline()).unwrap();
+        assert_eq!(config.directive, "info");
+        let expected = Settings {
+            color: ConsoleColor::Never,
+            console_pattern: Some("{level}: {message}".parse().unwrap()),
+            history_capacity: 50,
+        };
+        assert_eq!(config.settings, expected);
+
+        assert_eq!(parse_config("color: loud", &baseline()), Err("unknown color mode loud".to_string()));
+        assert_eq!(
+            parse_config("console_pattern: {lvl}", &baseline()),
+            Err("console_pattern: unknown placeholder `{lvl}`".to_string())
+        );
+        assert_eq!(
+            parse_config("history_capacity: lots", &baseline()),
+            Err("history_capacity: not a number: lots".to_string())
+        );
+    }
+
+    #[test]
+    fn test_staged_edits() {
+        let path = temp_path("logging.conf");
+        fs::write(&path, "info\nnetwork=debug\n").unwrap();
+        let logger = CaptureLogger::new();
+
+        let watch = logger.watch_config(&path, Duration::from_millis(10));
+        assert_eq!(logger.current_directives(), "info,NETWORK=debug");
+        let messages = logger_messages(&logger);
+        assert!(messages[0].ends_with("reloaded: default: debug -> info"), "{:?}", messages);
+
+        // A broken edit is reported once and changes nothing
+        fs::write(&path, "info\nnetwork=loud\n").unwrap();
+        let messages = wait_for(&logger, 2);
+        assert!(messages[1].starts_with("WARN config") && messages[1].ends_with("unknown log level"), "{:?}", messages);
+        fs::write(&path, "info\nnetwork=loud \n").unwrap();
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(logger_messages(&logger).len(), 2);
+        assert_eq!(logger.current_directives(), "info,NETWORK=debug");
+
+        fs::write(&path, "info\ngame=debug,game/ai=warn\n").unwrap();
+        let messages = wait_for(&logger, 3);
+        assert!(
+            messages[2].ends_with("reloaded: GAME: info -> debug; GAME/AI: info -> warn; NETWORK: debug -> info"),
+            "{:?}",
+            messages
+        );
+        assert_eq!(logger.current_directives(), "info,GAME=debug,GAME/AI=warn");
+
+        watch.stop_watching();
+        fs::write(&path, "error\n").unwrap();
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(logger.current_directives(), "info,GAME=debug,GAME/AI=warn");
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_staged_settings() {
+        let path = temp_path("settings.conf");
+        fs::write(&path, "debug\ncolor: never\nhistory_capacity: 200\n").unwrap();
+        let logger = CaptureLogger::new();
+
+        let watch = logger.watch_config(&path, Duration::from_millis(10));
+        assert_eq!(logger.console_color(), ConsoleColor::Never);
+        assert_eq!(logger.history_capacity(), 200);
+        let messages = logger_messages(&logger);
+        let expected = "reloaded: color: auto -> never; history_capacity: 1000 -> 200";
+        assert!(messages[0].ends_with(expected), "{:?}", messages);
+
+        // A bad setting keeps the level change beside it from applying too
+        fs::write(&path, "info\ncolor: never\nconsole_pattern: {level\n").unwrap();
+        let messages = wait_for(&logger, 2);
+        assert!(messages[1].ends_with("console_pattern: unbalanced braces in pattern"), "{:?}", messages);
+        assert_eq!(logger.min_level(), LogLevel::DEBUG);
+        assert_eq!(logger.console_pattern(), None);
+
+        // Settings left out go back to what they were when the watch started
+        fs::write(&path, "debug\nconsole_pattern: {level} {message}\n").unwrap();
+        let messages = wait_for(&logger, 3);
+        let expected = concat!(
+            "reloaded: color: never -> auto; console_pattern: none -> {level} {message}; ",
+            "history_capacity: 200 -> 1000"
+        );
+        assert!(messages[2].ends_with(expected), "{:?}", messages);
+        assert_eq!(logger.console_color(), ConsoleColor::Auto);
+        assert_eq!(logger.console_pattern().map(|p| p.to_string()), Some("{level} {message}".to_string()));
+        assert_eq!(logger.history_capacity(), 1000);
+
+        watch.stop_watching();
+        let _ = fs::remove_file(&path);
+    }
+}
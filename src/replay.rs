@@ -0,0 +1,189 @@
+//! Re-emitting a recorded session's entries alongside live ones
+//!
+//! For replay debugging: while a recorded game session is re-run, its
+//! original entries are written to the current sinks again, paced like
+//! the original if wanted, so old and new lines interleave in the output.
+
+use crate::reader::LogFileReader;
+use crate::run::LOGGER_COMPONENT;
+use crate::{DetectError, FieldValue, HorizonLogger, LogEntry, LogLevel, Timestamp};
+use std::borrow::Cow;
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+/// How `HorizonLogger::replay` re-emits entries
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayOptions {
+    /// Put in front of each entry's component; `REPLAY/` by default
+    pub component_prefix: String,
+    /// Multiple of the original pace; 0 replays as fast as possible, 2.0 twice as fast as recorded
+    pub speed: f64,
+}
+
+impl Default for ReplayOptions {
+    fn default() -> Self {
+        ReplayOptions {
+            component_prefix: "REPLAY/".to_string(),
+            speed: 0.0,
+        }
+    }
+}
+
+impl ReplayOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn component_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.component_prefix = prefix.into();
+        self
+    }
+
+    pub fn speed(mut self, speed: f64) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// How long to wait before an entry recorded `gap` after the previous one
+    fn delay(&self, gap: Duration) -> Option<Duration> {
+        if self.speed > 0.0 && !gap.is_zero() {
+            Some(gap.div_f64(self.speed))
+        } else {
+            None
+        }
+    }
+}
+
+impl HorizonLogger {
+    /// Write recorded entries to the current sinks again, marked as replayed
+    ///
+    /// Each entry is stamped with the current time and gets the component
+    /// prefix and the fields `replay=true`, `original_seq` and
+    /// `recorded_at` (milliseconds since the epoch). Its `seq` counts
+    /// replayed entries from 1, separately from live entries. With a
+    /// `speed`, the gaps between the original timestamps are slept out on
+    /// this thread. Replayed entries skip the console, history and stats.
+    /// Returns how many entries were written.
+    pub fn replay(&self, entries: impl IntoIterator<Item = LogEntry>, options: ReplayOptions) -> u64 {
+        let mut previous: Option<Timestamp> = None;
+        let mut replayed = 0;
+        for mut entry in entries {
+            if self.inner.kill_switch.is_off() {
+                self.inner.kill_switch.suppress(1);
+                continue;
+            }
+            let recorded_at = entry.timestamp;
+            if let Some(delay) = previous.and_then(|previous| options.delay(recorded_at.duration_since(previous))) {
+                std::thread::sleep(delay);
+            }
+            previous = Some(recorded_at);
+
+            let now = self.inner.clock.now();
+            entry.fields.push((Cow::Borrowed("replay"), FieldValue::Bool(true)));
+            entry.fields.push((Cow::Borrowed("original_seq"), entry.seq.into()));
+            entry.fields.push((Cow::Borrowed("recorded_at"), recorded_at.as_millis().into()));
+            entry.seq = self.inner.replay_seq.fetch_add(1, Ordering::Relaxed) + 1;
+            entry.component = Cow::Owned(format!("{}{}", options.component_prefix, entry.component));
+            entry.timestamp = now;
+            entry.last_timestamp = now;
+
+            let _block = self.inner.group_lock.read();
+            self.write_sinks(&self.sinks_snapshot(), &entry);
+            replayed += 1;
+        }
+        replayed
+    }
+
+    /// Replay the entries of a file written by `FileSink` or `BinarySink`, see `replay`
+    ///
+    /// Records that can't be read back are skipped, with a WARN under
+    /// `LOGGER` saying how many.
+    pub fn replay_from_file(&self, path: impl AsRef<Path>, options: ReplayOptions) -> Result<u64, DetectError> {
+        let mut reader = LogFileReader::open(path)?;
+        let replayed = self.replay(reader.by_ref(), options);
+        if !reader.errors().is_empty() {
+            let message = format!("replay skipped {}", reader.errors());
+            self.log(LogLevel::WARN, LOGGER_COMPONENT, &message);
+        }
+        Ok(replayed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixtures::{temp_path, CollectSink};
+    use crate::testing::CaptureLogger;
+    use crate::{FileSink, Format};
+    use std::sync::Arc;
+
+    fn field<'a>(entry: &'a LogEntry, name: &str) -> Option<&'a FieldValue> {
+        entry.fields.iter().find(|(key, _)| key == name).map(|(_, value)| value)
+    }
+
+    #[test]
+    fn test_replay_marks_and_keeps_order() {
+        let recorded: Vec<LogEntry> = [(0, "NET", "connected"), (250, "GAME", "round 1"), (900, "NET", "lagging")]
+            .into_iter()
+            .enumerate()
+            .map(|(seq, (offset, component, message))| {
+                let timestamp = Timestamp::from_millis(1_700_000_000_000 + offset);
+                let mut entry = LogEntry::at(timestamp, LogLevel::INFO, component, message);
+                entry.seq = 40 + seq as u64;
+                entry
+            })
+            .collect();
+
+        let logger = CaptureLogger::new();
+        let collect = Arc::new(CollectSink::default());
+        logger.add_sink(collect.clone());
+        logger.info("GAME", "live before");
+        assert_eq!(logger.replay(recorded, ReplayOptions::new()), 3);
+        logger.info("GAME", "live after");
+        assert_eq!(logger.replay([LogEntry::new(LogLevel::WARN, "NET", "again")], ReplayOptions::new()), 1);
+
+        let written = collect.entries();
+        let lines: Vec<_> = written.iter().map(|e| format!("{} {}", e.component, e.message)).collect();
+        assert_eq!(
+            lines,
+            [
+                "GAME live before",
+                "REPLAY/NET connected",
+                "REPLAY/GAME round 1",
+                "REPLAY/NET lagging",
+                "GAME live after",
+                "REPLAY/NET again"
+            ]
+        );
+        let replayed: Vec<_> = written.iter().filter(|e| e.component.starts_with("REPLAY/")).collect();
+        assert_eq!(replayed.iter().map(|e| e.seq).collect::<Vec<_>>(), [1, 2, 3, 4]);
+        assert!(replayed.iter().all(|e| field(e, "replay") == Some(&FieldValue::Bool(true))));
+        assert_eq!(field(replayed[1], "original_seq"), Some(&FieldValue::UInt(41)));
+        assert_eq!(field(replayed[2], "recorded_at"), Some(&FieldValue::Int(1_700_000_000_900)));
+        assert_eq!(logger.messages(), ["live before", "live after"]);
+    }
+
+    #[test]
+    fn test_replay_from_file() {
+        let path = temp_path("replay.jsonl");
+        let recording = CaptureLogger::new();
+        recording.add_sink(FileSink::new(&path).unwrap().with_format(Format::Json));
+        recording.info("NET", "connected");
+        recording.error("GAME", "desync");
+        recording.flush();
+
+        let logger = CaptureLogger::new();
+        let collect = Arc::new(CollectSink::default());
+        logger.add_sink(collect.clone());
+        let options = ReplayOptions::new().component_prefix("OLD/");
+        assert_eq!(logger.replay_from_file(&path, options).unwrap(), 2);
+
+        let written = collect.entries();
+        assert_eq!(written[0].component, "OLD/NET");
+        assert_eq!(written[1].component, "OLD/GAME");
+        assert_eq!(written[1].level, LogLevel::ERROR);
+        assert_eq!(written[1].message, "desync");
+        let _ = std::fs::remove_file(&path);
+    }
+}
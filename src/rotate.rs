@@ -0,0 +1,757 @@
+//! Starting a new file when a `FileSink`'s current one grows too large or too old, see `FileSink::with_rotation`
+//!
+//! The sink writes to its own path. Rotating renames that file to the
+//! next free number, `server.log.1`, `server.log.2` and so on, so a higher
+//! number is newer and a closed file keeps its name; the new file gets its
+//! own header. With `Rotation::numbered_files` each file is written under
+//! its number from the start instead. With compression, each closed file
+//! is then compressed on a background thread to `server.log.1.gz` (or
+//! `.zst`).
+//!
+//! With `Rotation::run_id` every name is based on `server-<run>.log`
+//! instead, so each run rotates its own files.
+//!
+//! `server.log.current` always leads to the file being written: a symlink
+//! on unix, replaced by renaming a new one over it, and elsewhere, or
+//! where symlinks fail, a text file holding its path.
+
+use crate::run::run_path;
+use crate::Timestamp;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Closed files kept by default, see `Rotation::max_files`
+const DEFAULT_MAX_FILES: usize = 5;
+
+/// When a `FileSink` starts a new file and how many closed ones it keeps
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rotation {
+    max_bytes: Option<u64>,
+    interval: Option<Duration>,
+    max_files: usize,
+    compression: RotationCompression,
+    numbered: bool,
+    run_id: Option<String>,
+}
+
+/// What happens to a file rotation has closed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RotationCompression {
+    /// Left as it was written
+    #[default]
+    None,
+    /// Compressed to `.gz`
+    #[cfg(feature = "compression")]
+    Gzip,
+    /// Compressed to `.zst` at `level`, 1 (fastest) to 22 (smallest)
+    #[cfg(feature = "compression")]
+    Zstd { level: i32 },
+}
+
+impl RotationCompression {
+    /// Extension of compressed files, `None` when files are not compressed
+    #[cfg(feature = "compression")]
+    fn extension(self) -> Option<&'static str> {
+        match self {
+            RotationCompression::None => None,
+            RotationCompression::Gzip => Some("gz"),
+            RotationCompression::Zstd { .. } => Some("zst"),
+        }
+    }
+}
+
+impl Rotation {
+    /// Never rotate until `max_bytes` or `every` is set; keeps 5 closed files
+    pub fn new() -> Self {
+        Rotation {
+            max_bytes: None,
+            interval: None,
+            max_files: DEFAULT_MAX_FILES,
+            compression: RotationCompression::None,
+            numbered: false,
+            run_id: None,
+        }
+    }
+
+    /// Start a new file before an entry would take the current one past `bytes`
+    ///
+    /// A file always takes at least one entry, however large.
+    pub fn max_bytes(mut self, bytes: u64) -> Self {
+        self.max_bytes = Some(bytes);
+        self
+    }
+
+    /// Start a new file for each `interval` since the Unix epoch, by entry timestamps
+    ///
+    /// Windows are aligned to the epoch, so a day starts a new file at
+    /// midnight UTC. An existing file counts from when it was last modified.
+    pub fn every(mut self, interval: Duration) -> Self {
+        self.interval = Some(interval).filter(|interval| !interval.is_zero());
+        self
+    }
+
+    /// Keep this many closed files, compressed or not; older ones are deleted
+    pub fn max_files(mut self, max_files: usize) -> Self {
+        self.max_files = max_files;
+        self
+    }
+
+    /// Compress closed files in the background as `compression` says; not by default
+    pub fn compression(mut self, compression: RotationCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Write each file under its number from the start, `server.log.1`, `server.log.2`, ...
+    ///
+    /// Nothing is renamed, so a reader holding a file open keeps reading
+    /// the one it opened; follow `server.log.current` to the newest. A new
+    /// sink carries on in the newest file an earlier run left.
+    pub fn numbered_files(mut self) -> Self {
+        self.numbered = true;
+        self
+    }
+
+    /// Name the files after a run, `server-<run>.log`, `server-<run>.log.1`, ...
+    ///
+    /// Pass `HorizonLogger::run_id`, so a run never appends to or rotates
+    /// files another run wrote. `max_files` only counts this run's files;
+    /// `server.log.current` still leads to the one being written.
+    pub fn run_id(mut self, run_id: impl Into<String>) -> Self {
+        self.run_id = Some(run_id.into());
+        self
+    }
+
+    /// The window of `interval`s that `timestamp` falls in, if rotating by time
+    pub(crate) fn window(&self, timestamp: Timestamp) -> Option<i64> {
+        let interval = i64::try_from(self.interval?.as_micros()).unwrap_or(i64::MAX);
+        Some(timestamp.as_micros().div_euclid(interval))
+    }
+}
+
+impl Default for Rotation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The form a closed file is in, from its name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Closed {
+    Plain,
+    Compressed,
+    /// A compressed copy still being written, or cut short by a crash
+    Partial,
+}
+
+/// The number and form of a closed file named after `base`, e.g. `server.log.3.gz`
+fn parse_closed(name: &str, base: &str) -> Option<(u64, Closed)> {
+    let rest = name.strip_prefix(base)?.strip_prefix('.')?;
+    let (number, suffix) = rest.split_once('.').unwrap_or((rest, ""));
+    if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let closed = match suffix {
+        "" => Closed::Plain,
+        "gz" | "zst" => Closed::Compressed,
+        "gz.partial" | "zst.partial" => Closed::Partial,
+        _ => return None,
+    };
+    Some((number.parse().ok()?, closed))
+}
+
+/// `path` with `.suffix` added to its file name
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(".");
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Starts new files for one `FileSink` and looks after the closed ones
+pub(crate) struct Rotator {
+    policy: Rotation,
+    /// The sink's path, or with `Rotation::run_id` the run's name for it
+    path: PathBuf,
+    /// `path.current` for the sink's own path
+    pointer: PathBuf,
+    /// The file to write first, decided before `recover` tidied the directory
+    first: PathBuf,
+    /// Held while deleting old files and while a compressed file replaces its original
+    files: Arc<Mutex<()>>,
+    #[cfg(feature = "compression")]
+    compressor: Option<compress::Compressor>,
+}
+
+impl Rotator {
+    /// Rotate `path` by `policy`, tidying what an interrupted compression left behind
+    pub(crate) fn new(path: &Path, policy: Rotation) -> Self {
+        let base = match &policy.run_id {
+            Some(run_id) => run_path(path, run_id),
+            None => path.to_path_buf(),
+        };
+        let mut rotator = Rotator {
+            #[cfg(feature = "compression")]
+            compressor: policy.compression.extension().map(|_| compress::Compressor::start(policy.compression)),
+            policy,
+            first: base.clone(),
+            path: base,
+            pointer: with_suffix(path, "current"),
+            files: Arc::new(Mutex::new(())),
+        };
+        if rotator.policy.numbered {
+            rotator.first = rotator.numbered(rotator.resumed().unwrap_or_else(|| rotator.next_number()));
+        }
+        rotator.recover();
+        rotator
+    }
+
+    pub(crate) fn policy(&self) -> &Rotation {
+        &self.policy
+    }
+
+    /// Start compressing on a new thread in the child after `fork()`
+    pub(crate) fn restart_after_fork(&self) {
+        #[cfg(feature = "compression")]
+        if let Some(compressor) = &self.compressor {
+            compressor.restart();
+        }
+    }
+
+    /// The file a new sink writes to: its path or the run's, or with `numbered_files` the newest number
+    pub(crate) fn first_file(&self) -> &Path {
+        &self.first
+    }
+
+    /// Whether an entry `line_len` bytes long at `timestamp` goes in a new file
+    ///
+    /// `len` is the current file's size and `window` the one its entries
+    /// fall in. Only asked of a file that already has entries.
+    pub(crate) fn due(&self, len: u64, window: Option<i64>, line_len: usize, timestamp: Timestamp) -> bool {
+        let too_big = self.policy.max_bytes.is_some_and(|max| len + line_len as u64 > max);
+        let too_old = self.policy.window(timestamp).zip(window).is_some_and(|(now, file)| now > file);
+        too_big || too_old
+    }
+
+    /// Close `active` as the newest numbered file, delete the oldest beyond `max_files` and queue compression
+    ///
+    /// Returns the file to write next. The caller flushes `active` first
+    /// and opens the next one after.
+    pub(crate) fn rotate(&self, active: &Path) -> io::Result<PathBuf> {
+        let number = self.next_number();
+        let (closed, next) = if self.policy.numbered {
+            (active.to_path_buf(), self.numbered(number))
+        } else {
+            let closed = self.numbered(number);
+            match fs::rename(active, &closed) {
+                // Deleted from outside; there is nothing to keep
+                Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(self.path.clone()),
+                result => result?,
+            }
+            (closed, self.path.clone())
+        };
+        self.delete_oldest(None);
+        #[cfg(feature = "compression")]
+        if let Some(compressor) = &self.compressor {
+            compressor.queue(closed, self.files.clone());
+        }
+        #[cfg(not(feature = "compression"))]
+        let _ = closed;
+        Ok(next)
+    }
+
+    /// Point `server.log.current` at `active`, replacing whatever is there
+    ///
+    /// Failing only costs the pointer, so it is noted on stderr rather than
+    /// failing the write that rotated.
+    pub(crate) fn point_at(&self, active: &Path) {
+        let pointer = &self.pointer;
+        let staged = with_suffix(pointer, "tmp");
+        let _ = fs::remove_file(&staged);
+        #[cfg(unix)]
+        let linked = match active.file_name() {
+            // Relative, so the link still resolves if the directory moves
+            Some(name) => std::os::unix::fs::symlink(name, &staged),
+            None => Err(io::ErrorKind::InvalidInput.into()),
+        };
+        #[cfg(not(unix))]
+        let linked: io::Result<()> = Err(io::ErrorKind::Unsupported.into());
+        let staged = linked.or_else(|_| fs::write(&staged, format!("{}\n", active.display()))).map(|_| staged);
+        if let Err(e) = staged.and_then(|staged| fs::rename(staged, pointer)) {
+            let _ = writeln!(io::stderr(), "horizon_logger: cannot update {}: {}", pointer.display(), e);
+        }
+    }
+
+    /// `path.number`
+    fn numbered(&self, number: u64) -> PathBuf {
+        with_suffix(&self.path, &number.to_string())
+    }
+
+    /// One past the highest number in use
+    fn next_number(&self) -> u64 {
+        self.closed_files().iter().map(|(number, _, _)| number + 1).max().unwrap_or(1)
+    }
+
+    /// With `numbered_files`, the newest file if it was still being written
+    ///
+    /// Any compressed form, even a partial one, means it had been closed.
+    fn resumed(&self) -> Option<u64> {
+        let closed = self.closed_files();
+        let newest = closed.iter().map(|(number, _, _)| *number).max()?;
+        let mut forms = closed.iter().filter(|(number, _, _)| *number == newest);
+        forms.all(|(_, closed, _)| *closed == Closed::Plain).then_some(newest)
+    }
+
+    /// Closed files next to the sink's file, as number, form and path
+    ///
+    /// With `numbered_files` this includes the one being written.
+    fn closed_files(&self) -> Vec<(u64, Closed, PathBuf)> {
+        let (Some(base), Some(dir)) = (self.path.file_name().and_then(|name| name.to_str()), self.path.parent()) else {
+            return Vec::new();
+        };
+        let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+        let Ok(listing) = fs::read_dir(dir) else {
+            return Vec::new();
+        };
+        listing
+            .flatten()
+            .filter_map(|file| {
+                let name = file.file_name();
+                let (number, closed) = parse_closed(name.to_str()?, base)?;
+                Some((number, closed, file.path()))
+            })
+            .collect()
+    }
+
+    /// Delete every form of the closed files but the newest `max_files`, never `active`
+    fn delete_oldest(&self, active: Option<&Path>) {
+        let _files = self.files.lock();
+        let mut closed = self.closed_files();
+        closed.retain(|(_, _, path)| Some(path.as_path()) != active);
+        let mut numbers: Vec<u64> = closed.iter().map(|(number, _, _)| *number).collect();
+        numbers.sort_unstable_by(|a, b| b.cmp(a));
+        numbers.dedup();
+        let Some(&oldest_kept) = numbers.get(self.policy.max_files.saturating_sub(1)) else {
+            return;
+        };
+        let oldest_kept = if self.policy.max_files == 0 { u64::MAX } else { oldest_kept };
+        closed.retain(|(number, _, _)| *number < oldest_kept);
+        for (_, _, path) in closed {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    /// Finish what a crash during compression left: drop partial copies and originals already compressed
+    fn recover(&self) {
+        let closed = self.closed_files();
+        let compressed: Vec<u64> = closed
+            .iter()
+            .filter(|(_, closed, _)| *closed == Closed::Compressed)
+            .map(|(number, _, _)| *number)
+            .collect();
+        for (number, closed, path) in &closed {
+            match closed {
+                Closed::Partial => {
+                    let _ = fs::remove_file(path);
+                }
+                // The compressed copy is only renamed into place once complete
+                Closed::Plain if compressed.contains(number) => {
+                    let _ = fs::remove_file(path);
+                }
+                Closed::Plain if *path == self.first => {}
+                Closed::Plain => {
+                    #[cfg(feature = "compression")]
+                    if let Some(compressor) = &self.compressor {
+                        compressor.queue(path.clone(), self.files.clone());
+                    }
+                }
+                Closed::Compressed => {}
+            }
+        }
+        self.delete_oldest(Some(&self.first));
+    }
+}
+
+#[cfg(feature = "compression")]
+mod compress {
+    use super::{with_suffix, RotationCompression};
+    use std::fs::{self, File};
+    use std::io::{self, Write};
+    use std::path::{Path, PathBuf};
+    use std::sync::mpsc::{self, Sender};
+    use std::sync::{Arc, Mutex};
+    use std::thread::{self, JoinHandle};
+
+    /// Added to a compressed file's name while it is being written
+    const PARTIAL_SUFFIX: &str = "partial";
+
+    /// A closed file to compress, and the lock to hold while it replaces the original
+    type Job = (PathBuf, Arc<Mutex<()>>);
+
+    /// The one thread compressing a sink's closed files, finishing the queue when dropped
+    pub(super) struct Compressor {
+        compression: RotationCompression,
+        worker: Mutex<Option<(Sender<Job>, JoinHandle<()>)>>,
+    }
+
+    impl Compressor {
+        pub(super) fn start(compression: RotationCompression) -> Self {
+            Compressor {
+                compression,
+                worker: Mutex::new(Some(spawn(compression))),
+            }
+        }
+
+        pub(super) fn queue(&self, plain: PathBuf, files: Arc<Mutex<()>>) {
+            if let Some((jobs, _)) = &*self.worker.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) {
+                let _ = jobs.send((plain, files));
+            }
+        }
+
+        /// Start a new thread in the child after `fork()`; files the parent queued are left to it
+        pub(super) fn restart(&self) {
+            let mut worker = self.worker.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            if let Some((_, thread)) = worker.replace(spawn(self.compression)) {
+                // Only the forking thread made it into the child, so this one never ends
+                std::mem::forget(thread);
+            }
+        }
+    }
+
+    impl Drop for Compressor {
+        fn drop(&mut self) {
+            let worker = self.worker.get_mut().unwrap_or_else(|poisoned| poisoned.into_inner()).take();
+            if let Some((jobs, thread)) = worker {
+                // Disconnecting ends the thread once the queue is done
+                drop(jobs);
+                let _ = thread.join();
+            }
+        }
+    }
+
+    fn spawn(compression: RotationCompression) -> (Sender<Job>, JoinHandle<()>) {
+        let (jobs, queued) = mpsc::channel::<Job>();
+        let thread = thread::Builder::new()
+            .name("horizon-log-compress".into())
+            .spawn(move || {
+                lower_priority();
+                for (plain, files) in queued {
+                    if let Err(e) = compress(&plain, compression, &files) {
+                        let _ = writeln!(io::stderr(), "horizon_logger: cannot compress {}: {}", plain.display(), e);
+                    }
+                }
+            })
+            .expect("failed to spawn the log compression thread");
+        (jobs, thread)
+    }
+
+    /// Let logging threads go first; only this thread is affected, and only on Linux
+    fn lower_priority() {
+        #[cfg(target_os = "linux")]
+        // SAFETY: plain call; on Linux `who = 0` is the calling thread
+        unsafe {
+            libc::setpriority(libc::PRIO_PROCESS, 0, 10);
+        }
+    }
+
+    /// Compress `plain` next to itself, then delete it
+    ///
+    /// The copy is written under a partial name, synced and only then
+    /// renamed into place, so a crash leaves the original or the whole
+    /// compressed file, and at worst both.
+    fn compress(plain: &Path, compression: RotationCompression, files: &Mutex<()>) -> io::Result<()> {
+        let Some(extension) = compression.extension() else {
+            return Ok(());
+        };
+        let target = with_suffix(plain, extension);
+        let partial = with_suffix(&target, PARTIAL_SUFFIX);
+        let mut input = match File::open(plain) {
+            // Deleted as one of the oldest before its turn came
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            input => input?,
+        };
+        let written = File::create(&partial).and_then(|output| {
+            let output = match compression {
+                RotationCompression::None => output,
+                RotationCompression::Gzip => {
+                    let mut encoder = flate2::write::GzEncoder::new(output, flate2::Compression::default());
+                    io::copy(&mut input, &mut encoder)?;
+                    encoder.finish()?
+                }
+                RotationCompression::Zstd { level } => {
+                    let mut encoder = zstd::Encoder::new(output, level)?;
+                    io::copy(&mut input, &mut encoder)?;
+                    encoder.finish()?
+                }
+            };
+            output.sync_all()
+        });
+        if let Err(e) = written {
+            let _ = fs::remove_file(&partial);
+            return Err(e);
+        }
+
+        let _files = files.lock();
+        if !plain.exists() {
+            let _ = fs::remove_file(&partial);
+            return Ok(());
+        }
+        fs::rename(&partial, &target)?;
+        sync_dir(&target);
+        fs::remove_file(plain)
+    }
+
+    /// Make a rename in `path`'s directory durable, where the OS allows it
+    fn sync_dir(path: &Path) {
+        #[cfg(unix)]
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            let _ = File::open(dir).and_then(|dir| dir.sync_all());
+        }
+        #[cfg(not(unix))]
+        let _ = path;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixtures::temp_dir;
+    use crate::{FileSink, Format, HorizonLogger, ManualClock};
+    use std::sync::Arc;
+
+    /// File names in `dir`, sorted
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> =
+            fs::read_dir(dir).unwrap().map(|file| file.unwrap().file_name().into_string().unwrap()).collect();
+        names.sort();
+        names
+    }
+
+    /// Numbers of the closed files in `dir` in the form `closed`, oldest first
+    fn numbers(dir: &Path, closed: Closed) -> Vec<u64> {
+        let mut numbers: Vec<u64> = names(dir)
+            .iter()
+            .filter_map(|name| parse_closed(name, "server.log"))
+            .filter(|(_, form)| *form == closed)
+            .map(|(number, _)| number)
+            .collect();
+        numbers.sort_unstable();
+        numbers
+    }
+
+    /// The messages in the text of a log file, after its header
+    fn messages(text: &str) -> Vec<String> {
+        assert!(text.starts_with('#'), "no header in {:?}", text);
+        text.lines().skip(1).map(|line| line.rsplit("] ").next().unwrap().to_string()).collect()
+    }
+
+    /// `entry 00` to `entry {count - 1}`, the last `kept` of them
+    fn last_entries(count: usize, kept: usize) -> Vec<String> {
+        (count - kept..count).map(|n| format!("entry {:02}", n)).collect()
+    }
+
+    #[test]
+    fn test_closed_file_names() {
+        assert_eq!(parse_closed("server.log.3", "server.log"), Some((3, Closed::Plain)));
+        assert_eq!(parse_closed("server.log.12.gz", "server.log"), Some((12, Closed::Compressed)));
+        assert_eq!(parse_closed("server.log.2.zst.partial", "server.log"), Some((2, Closed::Partial)));
+        for other in ["server.log", "server.log.", "server.log.x", "server.log.1.bak", "server.logs.1", "game.log.1"] {
+            assert_eq!(parse_closed(other, "server.log"), None, "{}", other);
+        }
+    }
+
+    #[test]
+    fn test_size_and_time_rotation_keep_max_files() {
+        let dir = temp_dir("rotate_size");
+        let path = dir.join("server.log");
+        let clock = Arc::new(ManualClock::new(Timestamp::from_millis(0)));
+        let logger = HorizonLogger::builder().announce_run(false).clock(clock.clone()).build();
+        let rotation = Rotation::new().max_bytes(300).every(Duration::from_secs(3600)).max_files(2);
+        logger.add_sink(FileSink::new(&path).unwrap().with_format(Format::Text).with_rotation(rotation));
+        for n in 0..30 {
+            logger.info("GAME", &format!("entry {:02}", n));
+        }
+        logger.flush();
+
+        // Two closed files kept, numbered on from the ones deleted
+        let closed = numbers(&dir, Closed::Plain);
+        assert_eq!(closed.len(), 2);
+        assert!(closed[0] > 1 && closed[1] == closed[0] + 1, "{:?}", closed);
+        let mut written = Vec::new();
+        for number in &closed {
+            let file = dir.join(format!("server.log.{}", number));
+            assert!(fs::metadata(&file).unwrap().len() <= 300);
+            written.extend(messages(&fs::read_to_string(file).unwrap()));
+        }
+        written.extend(messages(&fs::read_to_string(&path).unwrap()));
+        assert_eq!(written, last_entries(30, written.len()));
+
+        // A new hour starts a new file, however small the current one
+        clock.set(Timestamp::from_millis(3_600_000));
+        logger.info("GAME", "next hour");
+        logger.flush();
+        assert_eq!(numbers(&dir, Closed::Plain), [closed[1], closed[1] + 1]);
+        assert_eq!(messages(&fs::read_to_string(&path).unwrap()), ["next hour"]);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    /// The file `server.log.current` leads to
+    fn resolve(pointer: &Path) -> PathBuf {
+        match fs::read_link(pointer) {
+            Ok(target) => pointer.with_file_name(target),
+            Err(_) => PathBuf::from(fs::read_to_string(pointer).unwrap().trim_end()),
+        }
+    }
+
+    #[test]
+    fn test_current_pointer_follows_rotation() {
+        let dir = temp_dir("rotate_current");
+        let path = dir.join("server.log");
+        let pointer = dir.join("server.log.current");
+        let logger = HorizonLogger::builder().announce_run(false).build();
+        // Every entry after the first rotates
+        let rotation = Rotation::new().max_bytes(1).numbered_files();
+        let sink = Arc::new(FileSink::new(&path).unwrap().with_rotation(rotation.clone()));
+        logger.add_sink(sink.clone());
+        assert_eq!(sink.current_path(), dir.join("server.log.1"));
+        assert_eq!(resolve(&pointer), sink.current_path());
+
+        for n in 0..3 {
+            logger.info("GAME", &format!("entry {:02}", n));
+            logger.flush();
+            let current = sink.current_path();
+            assert_eq!(current, dir.join(format!("server.log.{}", n + 1)));
+            assert_eq!(resolve(&pointer), current);
+            assert_eq!(messages(&fs::read_to_string(&current).unwrap()), last_entries(n + 1, 1));
+            if n == 1 {
+                // Deleted from outside; the next rotation puts it back
+                fs::remove_file(&pointer).unwrap();
+            }
+        }
+        assert_eq!(names(&dir), ["server.log.1", "server.log.2", "server.log.3", "server.log.current"]);
+
+        // A new sink carries on in the newest file
+        drop(logger);
+        drop(sink);
+        let sink = FileSink::new(&path).unwrap().with_rotation(rotation);
+        assert_eq!(sink.current_path(), dir.join("server.log.3"));
+        assert!(!path.exists());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_current_pointer_without_numbered_files() {
+        let dir = temp_dir("rotate_current_plain");
+        let path = dir.join("server.log");
+        let logger = HorizonLogger::builder().announce_run(false).build();
+        let sink = Arc::new(FileSink::new(&path).unwrap().with_rotation(Rotation::new().max_bytes(1)));
+        logger.add_sink(sink.clone());
+        for n in 0..3 {
+            logger.info("GAME", &format!("entry {:02}", n));
+        }
+        logger.flush();
+        assert_eq!(sink.current_path(), path);
+        assert_eq!(resolve(&dir.join("server.log.current")), path);
+        assert_eq!(messages(&fs::read_to_string(&path).unwrap()), last_entries(3, 1));
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_files_named_after_the_run() {
+        let dir = temp_dir("rotate_run");
+        let path = dir.join("server.log");
+        let logger = HorizonLogger::builder().announce_run(false).run_id("1a2b3c4d").build();
+        let rotation = Rotation::new().max_bytes(1).run_id(logger.run_id());
+        let sink = Arc::new(FileSink::new(&path).unwrap().with_rotation(rotation));
+        logger.add_sink(sink.clone());
+        for n in 0..3 {
+            logger.info("GAME", &format!("entry {:02}", n));
+        }
+        logger.flush();
+
+        let run_path = logger.run_file_path(&path);
+        assert_eq!(run_path, dir.join("server-1a2b3c4d.log"));
+        assert_eq!(sink.current_path(), run_path);
+        assert_eq!(resolve(&dir.join("server.log.current")), run_path);
+        assert_eq!(
+            names(&dir),
+            ["server-1a2b3c4d.log", "server-1a2b3c4d.log.1", "server-1a2b3c4d.log.2", "server.log.current"]
+        );
+        assert_eq!(messages(&fs::read_to_string(&run_path).unwrap()), last_entries(3, 1));
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[cfg(feature = "compression")]
+    fn decompress(path: &Path, compression: RotationCompression) -> String {
+        use std::io::Read;
+
+        let mut text = String::new();
+        let file = fs::File::open(path).unwrap();
+        match compression {
+            RotationCompression::Gzip => flate2::read::GzDecoder::new(file).read_to_string(&mut text),
+            _ => zstd::Decoder::new(file).unwrap().read_to_string(&mut text),
+        }
+        .unwrap();
+        text
+    }
+
+    #[cfg(feature = "compression")]
+    const CODECS: [(RotationCompression, &str); 2] =
+        [(RotationCompression::Gzip, "gz"), (RotationCompression::Zstd { level: 3 }, "zst")];
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_compressed_rotation() {
+        for (compression, extension) in CODECS {
+            let dir = temp_dir(&format!("rotate_{}", extension));
+            let path = dir.join("server.log");
+            let logger = HorizonLogger::builder().announce_run(false).build();
+            let rotation = Rotation::new().max_bytes(200).max_files(3).compression(compression);
+            let sink = logger.add_sink(FileSink::new(&path).unwrap().with_rotation(rotation));
+            for n in 0..30 {
+                logger.info("GAME", &format!("entry {:02}", n));
+            }
+            logger.flush();
+            // Dropping the sink waits for queued compression
+            logger.remove_sink(sink);
+            drop(logger);
+
+            // Only compressed files are left, three of them
+            let closed = numbers(&dir, Closed::Compressed);
+            assert_eq!(closed.len(), 3);
+            assert_eq!(names(&dir).len(), 5, "{:?}", names(&dir));
+            let mut written = Vec::new();
+            for number in closed {
+                let file = dir.join(format!("server.log.{}.{}", number, extension));
+                written.extend(messages(&decompress(&file, compression)));
+            }
+            written.extend(messages(&fs::read_to_string(&path).unwrap()));
+            assert_eq!(written, last_entries(30, written.len()));
+            let _ = fs::remove_dir_all(dir);
+        }
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_recovery_after_crash() {
+        for (compression, extension) in CODECS {
+            let dir = temp_dir(&format!("rotate_recover_{}", extension));
+            // 1 was closed but not compressed yet, and a copy of 2 was renamed into place before 2 was deleted
+            fs::write(dir.join("server.log.1"), "# old\n").unwrap();
+            fs::write(dir.join("server.log.2"), "# older\n").unwrap();
+            fs::write(dir.join(format!("server.log.2.{}", extension)), "").unwrap();
+            fs::write(dir.join(format!("server.log.2.{}.partial", extension)), "cut short").unwrap();
+
+            let rotation = Rotation::new().compression(compression);
+            drop(FileSink::new(dir.join("server.log")).unwrap().with_rotation(rotation));
+            let compressed = |number: u64| format!("server.log.{}.{}", number, extension);
+            let kept = ["server.log".to_string(), compressed(1), compressed(2), "server.log.current".into()];
+            assert_eq!(names(&dir), kept);
+            assert_eq!(decompress(&dir.join(compressed(1)), compression), "# old\n");
+            let _ = fs::remove_dir_all(dir);
+        }
+    }
+}
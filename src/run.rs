@@ -0,0 +1,79 @@
+use crate::{HorizonLogger, LogLevel};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+
+/// Component of the entry announcing a run
+pub(crate) const LOGGER_COMPONENT: &str = "LOGGER";
+
+impl HorizonLogger {
+    /// Identifier of this process run, stamped on every entry
+    pub fn run_id(&self) -> &str {
+        &self.inner.run_id
+    }
+
+    /// `path` with the run id inserted before the extension, e.g. `server-1a2b3c4d.log`
+    ///
+    /// `Rotation::run_id` names a rotating sink's files the same way.
+    pub fn run_file_path(&self, path: impl AsRef<Path>) -> PathBuf {
+        run_path(path.as_ref(), self.run_id())
+    }
+
+    /// Log the run id once, just before the first entry, so sinks added after `build` see it
+    pub(crate) fn announce_run(&self) {
+        if self.inner.run_announced.swap(true, Ordering::Relaxed) {
+            return;
+        }
+        let message = format!("run {} started (pid {})", self.run_id(), std::process::id());
+        self.log(LogLevel::INFO, LOGGER_COMPONENT, &message);
+    }
+}
+
+/// `path` with `run_id` inserted before the extension
+pub(crate) fn run_path(path: &Path, run_id: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let mut name = format!("{}-{}", stem, run_id);
+    if let Some(extension) = path.extension() {
+        name.push('.');
+        name.push_str(&extension.to_string_lossy());
+    }
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Format;
+
+    #[test]
+    fn test_run_id_on_entries() {
+        let logger = HorizonLogger::builder().run_id("playtest-7").build();
+        logger.warn("GAME", "x");
+
+        let history = logger.get_history();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].component, LOGGER_COMPONENT);
+        assert!(history[0].message.starts_with("run playtest-7 started"));
+        assert!(history.iter().all(|e| e.run_id.as_deref() == Some("playtest-7")));
+        assert!(logger
+            .format_entry(&history[1], Format::Json)
+            .contains(r#""seq":1,"run":"playtest-7""#));
+        assert!(logger.format_entry(&history[1], Format::Logfmt).contains(" run=playtest-7"));
+    }
+
+    #[test]
+    fn test_generated_run_id() {
+        let a = HorizonLogger::new();
+        let b = HorizonLogger::new();
+        assert_eq!(a.run_id().len(), 8);
+        assert!(a.run_id().chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(a.run_id(), b.run_id());
+        assert_eq!(a.clone().run_id(), a.run_id());
+    }
+
+    #[test]
+    fn test_run_file_path() {
+        let logger = HorizonLogger::builder().run_id("abc").build();
+        assert_eq!(logger.run_file_path("logs/server.log"), Path::new("logs/server-abc.log"));
+        assert_eq!(logger.run_file_path("server"), Path::new("server-abc"));
+    }
+}
@@ -0,0 +1,171 @@
+//! Statistical sampling of high-volume components
+//!
+//! A component with a sample rate keeps only part of its entries below
+//! ERROR. Kept entries carry the rate in `LogEntry::sampled` (`sampled=1/100`
+//! in JSON and logfmt) so counts can be scaled back up, and the entries
+//! sampled away are counted per component in `ComponentStats::sampled_out`.
+
+use crate::history::component_matches;
+use crate::id::random_u64;
+use crate::{HorizonLogger, LogLevel};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+/// How much of a component's output to keep
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SampleRate {
+    /// Keep the first entry of every `n`, by count so bursts stay proportional; 0 keeps all
+    OneIn(u32),
+    /// Keep each entry independently with this probability, clamped to `0.0..=1.0`
+    Probability(f64),
+}
+
+impl fmt::Display for SampleRate {
+    /// `1/100` or the probability, e.g. `0.25`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SampleRate::OneIn(n) => write!(f, "1/{}", n.max(&1)),
+            SampleRate::Probability(p) => write!(f, "{}", p.clamp(0.0, 1.0)),
+        }
+    }
+}
+
+struct Rule {
+    rate: SampleRate,
+    /// Entries seen, for `OneIn`
+    seen: AtomicU64,
+}
+
+impl Rule {
+    fn keep(&self) -> bool {
+        match self.rate {
+            SampleRate::OneIn(n) => self.seen.fetch_add(1, Ordering::Relaxed).is_multiple_of(u64::from(n.max(1))),
+            SampleRate::Probability(p) => random_unit() < p,
+        }
+    }
+}
+
+/// A uniform value in `0.0..1.0` from the per-thread generator ids come from
+fn random_unit() -> f64 {
+    (random_u64() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Per-component sample rates
+#[derive(Default)]
+pub(crate) struct Samplers {
+    /// `(component, rule)` pairs, longest component first so the most specific rate wins
+    rules: RwLock<Vec<(String, Arc<Rule>)>>,
+    /// `rules` is non-empty; checked first so unsampled calls skip the lock
+    any: AtomicBool,
+}
+
+/// Whether an entry survives sampling
+pub(crate) enum Sampled {
+    /// No rate applies to the entry
+    Unsampled,
+    Kept(SampleRate),
+    Dropped,
+}
+
+impl Samplers {
+    pub(crate) fn sample(&self, level: LogLevel, component: &str) -> Sampled {
+        if level >= LogLevel::ERROR || !self.any.load(Ordering::Relaxed) {
+            return Sampled::Unsampled;
+        }
+        let rule = match self.rules.read() {
+            Ok(rules) => rules
+                .iter()
+                .find(|(target, _)| component_matches(component, target))
+                .map(|(_, rule)| rule.clone()),
+            Err(_) => None,
+        };
+        match rule {
+            None => Sampled::Unsampled,
+            Some(rule) if rule.keep() => Sampled::Kept(rule.rate),
+            Some(_) => Sampled::Dropped,
+        }
+    }
+
+    fn set(&self, component: &str, rate: Option<SampleRate>) {
+        let Ok(mut rules) = self.rules.write() else {
+            return;
+        };
+        rules.retain(|(target, _)| target != component);
+        if let Some(rate) = rate {
+            let rule = Rule {
+                rate,
+                seen: AtomicU64::new(0),
+            };
+            rules.push((component.to_string(), Arc::new(rule)));
+            rules.sort_by_key(|(target, _)| std::cmp::Reverse(target.len()));
+        }
+        self.any.store(!rules.is_empty(), Ordering::Relaxed);
+    }
+}
+
+impl HorizonLogger {
+    /// Keep only a sample of the entries below ERROR from `component` and its children
+    ///
+    /// Sampling happens after level filtering, so it only thins out entries
+    /// that would otherwise be logged. ERROR and CRITICAL are always kept.
+    pub fn set_component_sampling(&self, component: &str, rate: SampleRate) {
+        self.inner.sampling.set(component, Some(rate));
+    }
+
+    /// Stop sampling `component`
+    pub fn clear_component_sampling(&self, component: &str) {
+        self.inner.sampling.set(component, None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::CaptureLogger;
+
+    #[test]
+    fn test_one_in_keeps_every_nth() {
+        let logger = CaptureLogger::new();
+        logger.set_component_sampling("NETWORK/PACKETS", SampleRate::OneIn(100));
+
+        for i in 0..1_000 {
+            logger.debug("NETWORK/PACKETS/UDP", &i.to_string());
+        }
+        logger.error("NETWORK/PACKETS", "checksum mismatch");
+        logger.debug("NETWORK", "unsampled");
+
+        let entries = logger.entries();
+        let kept: Vec<_> = entries.iter().filter(|e| e.level == LogLevel::DEBUG).map(|e| e.message.as_str()).collect();
+        assert_eq!(kept.len(), 11);
+        assert_eq!(&kept[..3], ["0", "100", "200"]);
+        assert_eq!(entries[0].sampled, Some(SampleRate::OneIn(100)));
+        assert!(entries[0].to_json().contains("\"sampled\":\"1/100\""));
+        assert!(entries[0].to_logfmt().ends_with(" sampled=1/100"));
+        assert!(entries.iter().filter(|e| e.level == LogLevel::ERROR).all(|e| e.sampled.is_none()));
+        assert!(entries.last().unwrap().sampled.is_none());
+
+        let stats = logger.component_stats();
+        let packets = stats.iter().find(|s| s.component == "NETWORK/PACKETS/UDP").unwrap();
+        assert_eq!((packets.total_messages(), packets.sampled_out), (10, 990));
+
+        logger.clear_component_sampling("NETWORK/PACKETS");
+        logger.debug("NETWORK/PACKETS", "all again");
+        assert!(logger.entries().last().unwrap().sampled.is_none());
+    }
+
+    #[test]
+    fn test_probability() {
+        let logger = CaptureLogger::new();
+        logger.set_component_sampling("NETWORK", SampleRate::Probability(0.25));
+        for _ in 0..4_000 {
+            logger.debug("NETWORK", "packet");
+        }
+        let kept = logger.entries().len();
+        assert!((800..1_200).contains(&kept), "{}", kept);
+
+        logger.set_component_sampling("NETWORK", SampleRate::Probability(0.0));
+        logger.info("NETWORK", "gone");
+        assert_eq!(logger.entries().len(), kept);
+    }
+}
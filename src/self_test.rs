@@ -0,0 +1,199 @@
+use crate::run::LOGGER_COMPONENT;
+use crate::sink::SinkId;
+use crate::{HorizonLogger, LogLevel};
+use std::borrow::Cow;
+use std::fmt;
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Component of the probe entries written by `self_test`
+const SELFTEST_COMPONENT: &str = "SELFTEST";
+
+/// Numbers successive probes so each one can be told apart
+static NEXT_PROBE: AtomicU64 = AtomicU64::new(1);
+
+/// How one sink did in `self_test`
+#[derive(Debug)]
+pub struct SinkCheck {
+    pub id: SinkId,
+    /// Registered with `add_optional_sink`, so a failure doesn't fail the test
+    pub optional: bool,
+    /// Time to write, flush and verify the probe
+    pub latency: Duration,
+    pub result: io::Result<()>,
+}
+
+/// The outcome of `self_test`, one check per sink in registration order
+#[derive(Debug, Default)]
+pub struct SelfTestReport {
+    pub sinks: Vec<SinkCheck>,
+}
+
+impl SelfTestReport {
+    /// Whether every sink that isn't optional passed
+    pub fn passed(&self) -> bool {
+        self.failures().next().is_none()
+    }
+
+    /// Checks of sinks that aren't optional and failed
+    pub fn failures(&self) -> impl Iterator<Item = &SinkCheck> {
+        self.sinks.iter().filter(|check| !check.optional && check.result.is_err())
+    }
+}
+
+impl fmt::Display for SelfTestReport {
+    /// One line per sink
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, check) in self.sinks.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            let optional = if check.optional { " (optional)" } else { "" };
+            let latency = crate::fmt::duration(check.latency);
+            match &check.result {
+                Ok(()) => write!(f, "{}{}: ok in {}", check.id, optional, latency)?,
+                Err(e) => write!(f, "{}{}: failed after {}: {}", check.id, optional, latency, e)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Errors from setting up a logger
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum HorizonLoggerError {
+    /// A sink that isn't optional failed `self_test`
+    SelfTestFailed(SelfTestReport),
+    /// Builder settings that don't work together
+    InvalidConfig(String),
+}
+
+impl fmt::Display for HorizonLoggerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HorizonLoggerError::SelfTestFailed(report) => {
+                write!(f, "logging self-test failed")?;
+                for check in report.failures() {
+                    if let Err(e) = &check.result {
+                        write!(f, "; {}: {}", check.id, e)?;
+                    }
+                }
+                Ok(())
+            }
+            HorizonLoggerError::InvalidConfig(reason) => write!(f, "invalid logger configuration: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for HorizonLoggerError {}
+
+impl HorizonLogger {
+    /// Check that every sink really delivers entries
+    ///
+    /// Writes an INFO probe under `SELFTEST` through each sink, bypassing
+    /// its filters, flushes it, and verifies it arrived: file sinks read the
+    /// line back from their path, network sinks check the collector is
+    /// connected. Probes are not shown on the console or kept in history.
+    /// Each failed sink is also logged under `LOGGER`, at ERROR or, for an
+    /// optional sink, WARN. Fails if any sink that isn't optional fails.
+    pub fn self_test(&self) -> Result<SelfTestReport, HorizonLoggerError> {
+        let sinks: Vec<_> = match self.inner.sinks.read() {
+            Ok(sinks) => sinks.iter().map(|r| (r.id, r.sink.clone(), r.optional)).collect(),
+            Err(_) => Vec::new(),
+        };
+        let options = self.format_options();
+
+        let mut report = SelfTestReport::default();
+        for (id, sink, optional) in sinks {
+            let message = format!("self-test probe {}-{}", self.inner.run_id, NEXT_PROBE.fetch_add(1, Ordering::Relaxed));
+            let probe = self.new_entry(self.inner.clock.now(), LogLevel::INFO, Cow::Borrowed(SELFTEST_COMPONENT), &message, None);
+
+            let start = Instant::now();
+            let result = sink.self_test(&probe, &options);
+            report.sinks.push(SinkCheck {
+                id,
+                optional,
+                latency: start.elapsed(),
+                result,
+            });
+        }
+
+        for check in &report.sinks {
+            if let Err(e) = &check.result {
+                let level = if check.optional { LogLevel::WARN } else { LogLevel::ERROR };
+                self.log(level, LOGGER_COMPONENT, &format!("self-test: {} failed: {}", check.id, e));
+            }
+        }
+        if report.passed() {
+            Ok(report)
+        } else {
+            Err(HorizonLoggerError::SelfTestFailed(report))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixtures::temp_dir;
+    use crate::testing::CaptureLogger;
+    use crate::{FileSink, FormatOptions, LogEntry, Sink, SinkRoute, RoutedSink};
+
+    struct Broken;
+
+    impl Sink for Broken {
+        fn write(&self, _entry: &LogEntry, _options: &FormatOptions) -> io::Result<()> {
+            Err(io::ErrorKind::PermissionDenied.into())
+        }
+    }
+
+    #[test]
+    fn test_file_sinks() {
+        let dir = temp_dir("self_test");
+        let logger = CaptureLogger::new();
+        // Routed elsewhere, so only the probe bypassing the route reaches it
+        let good = logger.add_sink(RoutedSink::new(FileSink::new(dir.join("game.log")).unwrap(), SinkRoute::only(&["GAME"])));
+        let report = logger.self_test().unwrap();
+        assert_eq!(report.sinks.len(), 1);
+        assert!(report.sinks[0].id == good && report.sinks[0].result.is_ok());
+        let contents = std::fs::read_to_string(dir.join("game.log")).unwrap();
+        assert!(contents.lines().last().unwrap().contains("[SELFTEST] self-test probe "), "{}", contents);
+        assert!(logger.entries().is_empty());
+
+        // The directory is deleted under a running sink: writes still succeed, but land nowhere
+        let gone = temp_dir("self_test_gone");
+        let lost = logger.add_sink(FileSink::new(gone.join("audio.log")).unwrap());
+        std::fs::remove_dir_all(&gone).unwrap();
+        logger.add_optional_sink(Broken);
+
+        let Err(HorizonLoggerError::SelfTestFailed(report)) = logger.self_test() else {
+            panic!("self-test passed");
+        };
+        let failures: Vec<_> = report.failures().map(|check| check.id).collect();
+        assert_eq!(failures, [lost]);
+        assert!(report.sinks[2].optional && report.sinks[2].result.is_err());
+        let messages = logger.messages();
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].starts_with(&format!("self-test: {} failed: cannot read back ", lost)), "{:?}", messages);
+        assert_eq!(logger.entries()[1].level, LogLevel::WARN);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_verify_on_build() {
+        let dir = temp_dir("verify_on_build");
+        let logger = HorizonLogger::builder()
+            .sink(FileSink::new(dir.join("server.log")).unwrap())
+            .optional_sink(Broken)
+            .verify_on_build(true)
+            .try_build()
+            .unwrap();
+        assert_eq!(logger.describe_config().sinks, 2);
+
+        let error = HorizonLogger::builder().sink(Broken).verify_on_build(true).try_build().err().unwrap();
+        assert!(error.to_string().starts_with("logging self-test failed; sink #"), "{}", error);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
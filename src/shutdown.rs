@@ -0,0 +1,202 @@
+use crate::run::LOGGER_COMPONENT;
+use crate::{CallOptions, FieldValue, HorizonLogger, LogLevel};
+use std::borrow::Cow;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Once;
+
+/// Entries logged to any sealed logger in this process, reported at exit
+static LATE_ENTRIES: AtomicU64 = AtomicU64::new(0);
+
+static REPORT_AT_EXIT: Once = Once::new();
+
+/// Whether `shutdown_with_summary` has sealed the logger, and what arrived since
+#[derive(Default)]
+pub(crate) struct Seal {
+    sealed: AtomicBool,
+    late: AtomicU64,
+}
+
+impl Seal {
+    /// Whether an entry must be discarded, counting it if so
+    pub(crate) fn rejects(&self, entries: u64) -> bool {
+        if !self.sealed.load(Ordering::SeqCst) {
+            return false;
+        }
+        self.late.fetch_add(entries, Ordering::Relaxed);
+        LATE_ENTRIES.fetch_add(entries, Ordering::Relaxed);
+        true
+    }
+}
+
+#[cfg(any(unix, windows))]
+fn report_at_exit() {
+    extern "C" {
+        fn atexit(callback: extern "C" fn()) -> std::ffi::c_int;
+    }
+
+    extern "C" fn report() {
+        let late = LATE_ENTRIES.load(Ordering::Relaxed);
+        if late > 0 {
+            // Not eprintln, which would panic across the C boundary if stderr is gone
+            let _ = writeln!(io::stderr(), "horizon_logger: {} entries logged after shutdown were discarded", late);
+        }
+    }
+
+    REPORT_AT_EXIT.call_once(|| {
+        // SAFETY: `report` is a plain function that lives for the whole process
+        unsafe {
+            atexit(report);
+        }
+    });
+}
+
+#[cfg(not(any(unix, windows)))]
+fn report_at_exit() {}
+
+impl HorizonLogger {
+    /// Log a final summary of the run, flush and sync the sinks, and seal the logger
+    ///
+    /// The summary is an INFO entry under `LOGGER` with the uptime, the
+    /// entries logged at each level, the dropped and sampled-out counts and
+    /// `reason`, also attached as fields. No level filter, sampling or volume
+    /// limit applies to it, and it is stored in history. Entries already on
+    /// their way to the sinks are written first, so it is the last line of
+    /// every log; file sinks sync as their `SyncPolicy` says, and the
+    /// history is saved if `persist_history` is set. Afterwards nothing more
+    /// is logged: see `logged_after_seal` and the count printed to stderr at
+    /// process exit. Returns the first error saving the history or from the
+    /// sinks. Calling it again does nothing.
+    pub fn shutdown_with_summary(&self, reason: &str) -> io::Result<()> {
+        if self.inner.seal.sealed.swap(true, Ordering::SeqCst) {
+            return Ok(());
+        }
+        report_at_exit();
+        // Entries that passed the seal before it closed hold the group lock until they are out
+        drop(self.inner.group_lock.write());
+
+        let mut totals = [0u64; LogLevel::COUNT];
+        let mut sampled_out = 0;
+        for stats in self.component_stats() {
+            for (total, count) in totals.iter_mut().zip(stats.messages) {
+                *total += count;
+            }
+            sampled_out += stats.sampled_out;
+        }
+        let dropped = self.dropped_entries();
+        let uptime = self.inner.clock.now().duration_since(self.inner.started);
+
+        let levels: Vec<_> = LogLevel::ALL
+            .iter()
+            .map(|&level| format!("{} {}", level.as_str(), totals[level as usize]))
+            .collect();
+        let message = format!(
+            "shutdown ({}): up {}, {} entries ({}), {} dropped, {} sampled out",
+            reason,
+            crate::fmt::duration(uptime),
+            totals.iter().sum::<u64>(),
+            levels.join(", "),
+            dropped,
+            sampled_out
+        );
+
+        let mut fields = vec![
+            (Cow::Borrowed("reason"), FieldValue::from(reason)),
+            (Cow::Borrowed("uptime_ms"), FieldValue::from(uptime.as_millis() as u64)),
+        ];
+        for level in LogLevel::ALL {
+            fields.push((Cow::Owned(format!("{:?}", level).to_lowercase()), totals[level as usize].into()));
+        }
+        fields.push((Cow::Borrowed("dropped"), dropped.into()));
+        fields.push((Cow::Borrowed("sampled_out"), sampled_out.into()));
+
+        let options = CallOptions {
+            filtered: false,
+            escalate: false,
+            backtrace: false,
+            fields: &fields,
+            ..CallOptions::default()
+        };
+        self.log_with(LogLevel::INFO, LOGGER_COMPONENT, &message, options);
+
+        if let Some(handle) = &self.inner.sink_queue {
+            handle.queue().drain();
+        }
+        let mut result = self.persist_history_now();
+        for sink in self.sinks_snapshot() {
+            if let Err(e) = sink.close() {
+                result = result.and(Err(e));
+            }
+        }
+        result
+    }
+
+    /// Whether `shutdown_with_summary` has been called
+    pub fn is_sealed(&self) -> bool {
+        self.inner.seal.sealed.load(Ordering::SeqCst)
+    }
+
+    /// Entries discarded because they were logged after `shutdown_with_summary`
+    pub fn logged_after_seal(&self) -> u64 {
+        self.inner.seal.late.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::testing::CaptureLogger;
+    use crate::{Format, Timestamp};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    #[test]
+    fn test_summary_is_the_last_entry() {
+        let clock = Arc::new(ManualClock::new(Timestamp::from_millis(0)));
+        let builder = HorizonLogger::builder().clock(clock.clone()).min_level(LogLevel::WARN);
+        let logger = CaptureLogger::from_builder(builder);
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let seen = lines.clone();
+        logger.tee_formatted(move |_, line| seen.lock().unwrap().push(line.to_string()));
+
+        logger.info("GAME", "filtered out");
+        logger.warn("GAME", "lag spike");
+        logger.error("STORAGE", "save failed");
+        logger.error("STORAGE", "save failed again");
+        clock.advance(Duration::from_secs(90));
+        logger.shutdown_with_summary("server stopping").unwrap();
+
+        let summary = logger.entries().pop().unwrap();
+        assert_eq!((summary.level, &*summary.component), (LogLevel::INFO, LOGGER_COMPONENT));
+        assert_eq!(
+            summary.message,
+            "shutdown (server stopping): up 1m 30s, 3 entries (DEBUG 0, INFO 0, WARN 1, ERROR 2, CRIT 0), \
+             0 dropped, 0 sampled out"
+        );
+        assert!(lines.lock().unwrap().last().unwrap().ends_with(&summary.message));
+        let json: serde_json::Value = serde_json::from_str(&logger.format_entry(&summary, Format::Json)).unwrap();
+        assert_eq!(json["fields"]["reason"], "server stopping");
+        assert_eq!(json["fields"]["uptime_ms"], 90_000);
+        assert_eq!(json["fields"]["error"], 2);
+    }
+
+    #[test]
+    fn test_sealed_logger_discards_entries() {
+        let logger = CaptureLogger::new();
+        logger.info("GAME", "last tick");
+        assert!(!logger.is_sealed());
+        logger.shutdown_with_summary("test over").unwrap();
+        assert!(logger.is_sealed());
+
+        logger.error("GAME", "too late");
+        logger.group("GAME", "late group").info("also too late");
+        logger.shutdown_with_summary("again").unwrap();
+
+        let messages = logger.messages();
+        assert_eq!(messages.len(), 2);
+        assert!(messages[1].starts_with("shutdown (test over): "));
+        assert_eq!(logger.logged_after_seal(), 4);
+        assert!(LATE_ENTRIES.load(Ordering::Relaxed) >= 4);
+    }
+}
@@ -0,0 +1,216 @@
+//! Reopening files when a signal arrives, for external log rotation
+//!
+//! With `reopen_on_signal(Signal::Hup)`, a SIGHUP makes the logger call
+//! `reopen_files`. The signal handler only writes the signal number to a
+//! pipe; a watcher thread reads it and does the reopening, so nothing
+//! unsafe runs in signal context.
+//!
+//! With logrotate, send the signal from `postrotate`:
+//!
+//! ```text
+//! /var/log/horizon/*.log {
+//!     daily
+//!     rotate 7
+//!     postrotate
+//!         kill -HUP "$(cat /run/horizon.pid)"
+//!     endscript
+//! }
+//! ```
+
+use crate::run::LOGGER_COMPONENT;
+use crate::{HorizonLogger, LogLevel, LoggerInner};
+use std::io;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+
+/// Signals that can trigger `reopen_files`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Signal {
+    Hup,
+    Usr1,
+}
+
+impl Signal {
+    fn number(self) -> libc::c_int {
+        match self {
+            Signal::Hup => libc::SIGHUP,
+            Signal::Usr1 => libc::SIGUSR1,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Option<Signal> {
+        [Signal::Hup, Signal::Usr1].into_iter().find(|signal| signal.number() == libc::c_int::from(byte))
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Signal::Hup => "SIGHUP",
+            Signal::Usr1 => "SIGUSR1",
+        }
+    }
+}
+
+/// Write end of the self-pipe, or -1 before the watcher starts
+static PIPE_WRITE: AtomicI32 = AtomicI32::new(-1);
+
+/// Loggers to reopen per signal, and the signals with a handler installed
+struct Watcher {
+    loggers: Vec<(Signal, Weak<LoggerInner>)>,
+    installed: Vec<Signal>,
+}
+
+static WATCHER: Mutex<Watcher> = Mutex::new(Watcher {
+    loggers: Vec::new(),
+    installed: Vec::new(),
+});
+
+#[cfg(any(target_os = "linux", target_os = "emscripten"))]
+unsafe fn errno() -> *mut libc::c_int {
+    libc::__errno_location()
+}
+
+#[cfg(target_os = "android")]
+unsafe fn errno() -> *mut libc::c_int {
+    libc::__errno()
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "emscripten", target_os = "android")))]
+unsafe fn errno() -> *mut libc::c_int {
+    libc::__error()
+}
+
+extern "C" fn on_signal(signal: libc::c_int) {
+    // Only async-signal-safe calls here; errno is restored for the interrupted code
+    unsafe {
+        let saved = *errno();
+        let byte = signal as u8;
+        libc::write(PIPE_WRITE.load(Ordering::Relaxed), (&byte as *const u8).cast(), 1);
+        *errno() = saved;
+    }
+}
+
+/// Create the pipe and start the thread reading it, once per process
+fn start_watcher() -> io::Result<()> {
+    if PIPE_WRITE.load(Ordering::Relaxed) >= 0 {
+        return Ok(());
+    }
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let [read, write] = fds;
+    unsafe {
+        libc::fcntl(read, libc::F_SETFD, libc::FD_CLOEXEC);
+        libc::fcntl(write, libc::F_SETFD, libc::FD_CLOEXEC);
+        // A full pipe already has a reopen pending; never block the handler
+        libc::fcntl(write, libc::F_SETFL, libc::O_NONBLOCK);
+    }
+    PIPE_WRITE.store(write, Ordering::Relaxed);
+
+    thread::Builder::new().name("horizon-logger-signals".into()).spawn(move || {
+        let mut byte = 0u8;
+        loop {
+            let read = unsafe { libc::read(read, (&mut byte as *mut u8).cast(), 1) };
+            if read == 1 {
+                if let Some(signal) = Signal::from_byte(byte) {
+                    reopen_for(signal);
+                }
+            } else if read == 0 || io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
+                return;
+            }
+        }
+    })?;
+    Ok(())
+}
+
+fn reopen_for(signal: Signal) {
+    let loggers: Vec<Arc<LoggerInner>> = {
+        let Ok(mut watcher) = WATCHER.lock() else {
+            return;
+        };
+        watcher.loggers.retain(|(_, logger)| logger.strong_count() > 0);
+        watcher
+            .loggers
+            .iter()
+            .filter(|(registered, _)| *registered == signal)
+            .filter_map(|(_, logger)| logger.upgrade())
+            .collect()
+    };
+    for inner in loggers {
+        let logger = HorizonLogger { inner };
+        if let Err(e) = logger.reopen_files() {
+            let message = format!("reopening files on {} failed: {}", signal.name(), e);
+            logger.log(LogLevel::ERROR, LOGGER_COMPONENT, &message);
+        }
+    }
+}
+
+fn install(signal: Signal) -> io::Result<()> {
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        if libc::sigaction(signal.number(), &action, std::ptr::null_mut()) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+impl HorizonLogger {
+    /// Call `reopen_files` whenever the process receives `signal`
+    ///
+    /// Replaces any handler already installed for `signal`. The handler
+    /// stays installed for the life of the process; the logger is no longer
+    /// reopened once it is dropped.
+    pub fn reopen_on_signal(&self, signal: Signal) -> io::Result<()> {
+        let mut watcher = WATCHER.lock().map_err(|_| io::Error::other("signal watcher lock poisoned"))?;
+        start_watcher()?;
+        if !watcher.installed.contains(&signal) {
+            install(signal)?;
+            watcher.installed.push(signal);
+        }
+        watcher.loggers.push((signal, Arc::downgrade(&self.inner)));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixtures::temp_path;
+    use crate::testing::CaptureLogger;
+    use crate::FileSink;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_sighup_reopens_renamed_file() {
+        let path = temp_path("sighup.log");
+        let rotated = temp_path("sighup.log.1");
+
+        let logger = CaptureLogger::new();
+        logger.add_sink(FileSink::new(&path).unwrap());
+        logger.reopen_on_signal(Signal::Hup).unwrap();
+        logger.info("GAME", "before rotation");
+        logger.flush();
+
+        std::fs::rename(&path, &rotated).unwrap();
+        unsafe { libc::raise(libc::SIGHUP) };
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !path.exists() {
+            assert!(Instant::now() < deadline, "file was not reopened");
+            thread::sleep(Duration::from_millis(5));
+        }
+        logger.info("GAME", "after rotation");
+        logger.flush();
+
+        let old = std::fs::read_to_string(&rotated).unwrap();
+        let new = std::fs::read_to_string(&path).unwrap();
+        assert!(old.contains("before rotation") && !old.contains("after rotation"), "{}", old);
+        assert!(new.contains("after rotation"), "{}", new);
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(&rotated);
+    }
+}
@@ -0,0 +1,944 @@
+use crate::ansi::AnsiPolicy;
+use crate::format::{format_entry, Format, FormatOptions};
+use crate::fsync::{IntervalSync, SyncFile, SyncPolicy};
+use crate::header::header_line;
+use crate::history::component_matches_ignore_case;
+use crate::pattern::Pattern;
+use crate::rotate::{Rotation, Rotator};
+use crate::{HorizonLogger, LogEntry, LogLevel, Timestamp};
+use std::fs::{self, File, OpenOptions};
+use std::fmt;
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Source of process-wide unique sink ids
+static NEXT_SINK_ID: AtomicU64 = AtomicU64::new(1);
+
+/// A destination that receives every logged entry
+///
+/// A sink may log from inside `write`, even through the same logger. Such
+/// entries are recorded in history, console and stats once the outer call
+/// returns, but are never handed to sinks, so they cannot recurse or deadlock.
+/// An error or panic from `write` never reaches the caller of `log`: the
+/// entry is counted in `dropped_entries` and the failure kept as
+/// `last_sink_error`.
+pub trait Sink: Send + Sync {
+    /// Write one entry, rendered with the logger's format options
+    fn write(&self, entry: &LogEntry, options: &FormatOptions) -> io::Result<()>;
+
+    /// Push buffered output to the OS
+    fn flush(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Reopen any underlying files at their configured paths
+    fn reopen(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Start the sink's own background threads again, in the child after `fork()`
+    ///
+    /// Only the forking thread survives a fork. A sink with threads starts
+    /// new ones here and leaves whatever the parent had queued to the
+    /// parent. See `HorizonLogger::after_fork_child`.
+    fn restart_after_fork(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Flush, then wait until the OS has written any underlying files to disk
+    ///
+    /// See `HorizonLogger::sync_all`. By default the same as `flush`.
+    fn sync(&self) -> io::Result<()> {
+        self.flush()
+    }
+
+    /// Flush before the logger is sealed by `HorizonLogger::shutdown_with_summary`
+    ///
+    /// By default the same as `flush`.
+    fn close(&self) -> io::Result<()> {
+        self.flush()
+    }
+
+    /// Write `probe` whatever the sink's filters, flush, and confirm it arrived
+    ///
+    /// See `HorizonLogger::self_test`. By default a probe has arrived once
+    /// `write` and `flush` succeed.
+    fn self_test(&self, probe: &LogEntry, options: &FormatOptions) -> io::Result<()> {
+        self.write(probe, options)?;
+        self.flush()
+    }
+}
+
+/// Lets callers keep a handle to a sink they registered, e.g. to read its counters
+impl<S: Sink + ?Sized> Sink for Arc<S> {
+    fn write(&self, entry: &LogEntry, options: &FormatOptions) -> io::Result<()> {
+        (**self).write(entry, options)
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        (**self).flush()
+    }
+
+    fn reopen(&self) -> io::Result<()> {
+        (**self).reopen()
+    }
+
+    fn restart_after_fork(&self) -> io::Result<()> {
+        (**self).restart_after_fork()
+    }
+
+    fn sync(&self) -> io::Result<()> {
+        (**self).sync()
+    }
+
+    fn close(&self) -> io::Result<()> {
+        (**self).close()
+    }
+
+    fn self_test(&self, probe: &LogEntry, options: &FormatOptions) -> io::Result<()> {
+        (**self).self_test(probe, options)
+    }
+}
+
+/// Passes on only entries with at least a given `LogEntry::severity`
+pub struct SeverityFilter<S> {
+    sink: S,
+    min_severity: u8,
+}
+
+impl<S: Sink> SeverityFilter<S> {
+    pub fn new(sink: S, min_severity: u8) -> Self {
+        SeverityFilter { sink, min_severity }
+    }
+}
+
+impl<S: Sink> Sink for SeverityFilter<S> {
+    fn write(&self, entry: &LogEntry, options: &FormatOptions) -> io::Result<()> {
+        if entry.severity < self.min_severity {
+            return Ok(());
+        }
+        self.sink.write(entry, options)
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.sink.flush()
+    }
+
+    fn reopen(&self) -> io::Result<()> {
+        self.sink.reopen()
+    }
+
+    fn restart_after_fork(&self) -> io::Result<()> {
+        self.sink.restart_after_fork()
+    }
+
+    fn sync(&self) -> io::Result<()> {
+        self.sink.sync()
+    }
+
+    fn close(&self) -> io::Result<()> {
+        self.sink.close()
+    }
+
+    fn self_test(&self, probe: &LogEntry, options: &FormatOptions) -> io::Result<()> {
+        self.sink.self_test(probe, options)
+    }
+}
+
+/// Which components a sink receives, see `RoutedSink` and `FileSink::with_route`
+///
+/// Prefixes match hierarchically and ignore case, like level directives:
+/// `AUDIO` covers `AUDIO/MIXER` but not `AUDIOVISUAL`. An exclusion wins
+/// over an inclusion, and no inclusions means every component.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SinkRoute {
+    pub include_prefixes: Vec<String>,
+    pub exclude_prefixes: Vec<String>,
+}
+
+impl SinkRoute {
+    /// Only `components` and their subcomponents
+    pub fn only(components: &[&str]) -> Self {
+        SinkRoute {
+            include_prefixes: components.iter().map(|c| c.to_string()).collect(),
+            exclude_prefixes: Vec::new(),
+        }
+    }
+
+    /// Also leave out `component` and its subcomponents
+    pub fn excluding(mut self, component: &str) -> Self {
+        self.exclude_prefixes.push(component.to_string());
+        self
+    }
+
+    /// Whether an entry under `component` is routed to the sink
+    pub fn matches(&self, component: &str) -> bool {
+        let under = |prefixes: &[String]| prefixes.iter().any(|prefix| component_matches_ignore_case(component, prefix));
+        !under(&self.exclude_prefixes) && (self.include_prefixes.is_empty() || under(&self.include_prefixes))
+    }
+}
+
+/// Passes on only entries whose component matches a `SinkRoute`
+pub struct RoutedSink<S> {
+    sink: S,
+    route: SinkRoute,
+}
+
+impl<S: Sink> RoutedSink<S> {
+    pub fn new(sink: S, route: SinkRoute) -> Self {
+        RoutedSink { sink, route }
+    }
+}
+
+impl<S: Sink> Sink for RoutedSink<S> {
+    fn write(&self, entry: &LogEntry, options: &FormatOptions) -> io::Result<()> {
+        if !self.route.matches(&entry.component) {
+            return Ok(());
+        }
+        self.sink.write(entry, options)
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.sink.flush()
+    }
+
+    fn reopen(&self) -> io::Result<()> {
+        self.sink.reopen()
+    }
+
+    fn restart_after_fork(&self) -> io::Result<()> {
+        self.sink.restart_after_fork()
+    }
+
+    fn sync(&self) -> io::Result<()> {
+        self.sink.sync()
+    }
+
+    fn close(&self) -> io::Result<()> {
+        self.sink.close()
+    }
+
+    fn self_test(&self, probe: &LogEntry, options: &FormatOptions) -> io::Result<()> {
+        self.sink.self_test(probe, options)
+    }
+}
+
+/// Hands each entry's plain-text line to a callback, see `tee_formatted`
+struct Tee<F>(F);
+
+impl<F: Fn(LogLevel, &str) + Send + Sync> Sink for Tee<F> {
+    fn write(&self, entry: &LogEntry, options: &FormatOptions) -> io::Result<()> {
+        let line = format_entry(entry, Format::Text, options);
+        panic::catch_unwind(AssertUnwindSafe(|| (self.0)(entry.level, &line)))
+            .map_err(|_| io::Error::other("tee callback panicked"))
+    }
+}
+
+/// A failed sink write, see `HorizonLogger::last_sink_error`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SinkError {
+    /// `ErrorKind::Other` for a panic
+    pub kind: io::ErrorKind,
+    pub message: String,
+}
+
+impl SinkError {
+    fn from_panic(payload: Box<dyn std::any::Any + Send>) -> Self {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown payload".to_string());
+        SinkError {
+            kind: io::ErrorKind::Other,
+            message: format!("sink panicked: {}", message),
+        }
+    }
+}
+
+impl From<&io::Error> for SinkError {
+    fn from(error: &io::Error) -> Self {
+        SinkError {
+            kind: error.kind(),
+            message: error.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for SinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// Whether every sink sees entries in the same order, see `LoggerBuilder::sink_ordering`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SinkOrdering {
+    /// One entry is written to every sink, in registration order, before the next entry reaches any
+    ///
+    /// If any sink is handed the entry with `seq` a before the one with
+    /// `seq` b, every sink is. Entries from one thread arrive in increasing
+    /// `seq` order; entries logged concurrently from different threads may
+    /// arrive out of `seq` order, but in the same order at every sink.
+    #[default]
+    Strict,
+    /// Each sink is written from its own thread, so a slow sink doesn't hold up the others
+    ///
+    /// Every sink still sees entries from one thread in increasing `seq`
+    /// order, but sinks may see entries from different threads in different
+    /// orders. Only with `LoggerBuilder::async_sinks`.
+    Independent,
+}
+
+/// Handle returned by `add_sink`, used to remove the sink again
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SinkId(u64);
+
+impl fmt::Display for SinkId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "sink #{}", self.0)
+    }
+}
+
+/// A sink as registered with `add_sink` or `add_optional_sink`
+pub(crate) struct Registered {
+    pub(crate) id: SinkId,
+    pub(crate) sink: Arc<dyn Sink>,
+    /// A failed self-test of this sink doesn't fail `self_test`
+    pub(crate) optional: bool,
+}
+
+/// Registered sinks in registration order
+pub(crate) type SinkList = Vec<Registered>;
+
+impl HorizonLogger {
+    /// Register a sink to receive all subsequent entries
+    ///
+    /// The first sink also receives the pre-init buffer, if one is configured.
+    pub fn add_sink(&self, sink: impl Sink + 'static) -> SinkId {
+        self.register_sink(Arc::new(sink), false)
+    }
+
+    /// Register a sink whose failure doesn't fail `self_test`, e.g. a best-effort collector
+    pub fn add_optional_sink(&self, sink: impl Sink + 'static) -> SinkId {
+        self.register_sink(Arc::new(sink), true)
+    }
+
+    pub(crate) fn register_sink(&self, sink: Arc<dyn Sink>, optional: bool) -> SinkId {
+        let id = SinkId(NEXT_SINK_ID.fetch_add(1, Ordering::Relaxed));
+        self.install_sink(Registered { id, sink, optional });
+        id
+    }
+
+    /// Call `callback` with the plain-text line of every entry sinks receive
+    ///
+    /// The line is what a text `FileSink` writes, without the newline:
+    /// rendered with `text_pattern` if one is set, and never colored. The
+    /// callback is registered as a sink, so remove it with `remove_sink`;
+    /// a panic in it is caught and counted in `dropped_entries`.
+    pub fn tee_formatted(&self, callback: impl Fn(LogLevel, &str) + Send + Sync + 'static) -> SinkId {
+        self.add_sink(Tee(callback))
+    }
+
+    /// Unregister a sink, returning whether it was present
+    pub fn remove_sink(&self, id: SinkId) -> bool {
+        let Ok(mut sinks) = self.inner.sinks.write() else {
+            return false;
+        };
+        let before = sinks.len();
+        sinks.retain(|registered| registered.id != id);
+        sinks.len() != before
+    }
+
+    /// Flush every sink, unless `emergency_disable` turned the logger off
+    pub fn flush(&self) {
+        if self.inner.kill_switch.is_off() {
+            return;
+        }
+        if let Some(handle) = &self.inner.sink_queue {
+            handle.queue().drain();
+        }
+        for sink in self.sinks_snapshot() {
+            let _ = sink.flush();
+        }
+    }
+
+    /// Reopen every sink's files at their configured paths, creating them if missing
+    ///
+    /// Use after an external tool such as logrotate renamed the files. Each
+    /// sink swaps files between two whole lines, so concurrent entries all
+    /// land in one file or the other. Returns the first error, after trying
+    /// every sink.
+    pub fn reopen_files(&self) -> io::Result<()> {
+        let mut result = Ok(());
+        for sink in self.sinks_snapshot() {
+            if let Err(e) = sink.reopen() {
+                result = result.and(Err(e));
+            }
+        }
+        result
+    }
+
+    /// Current sinks, cloned so no lock is held while writing
+    pub(crate) fn sinks_snapshot(&self) -> Vec<Arc<dyn Sink>> {
+        self.inner
+            .sinks
+            .read()
+            .map(|sinks| sinks.iter().map(|registered| registered.sink.clone()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Hand an entry to each of `sinks`
+    pub(crate) fn write_sinks(&self, sinks: &[Arc<dyn Sink>], entry: &LogEntry) {
+        match &self.inner.sink_queue {
+            Some(handle) if !sinks.is_empty() => handle.queue().push(self, sinks.to_vec(), entry),
+            Some(_) => {}
+            None => {
+                // Strict ordering, the only one without a queue
+                let _dispatch = self.inner.dispatch_lock.lock();
+                self.write_sinks_now(sinks, entry);
+            }
+        }
+    }
+
+    /// `write_sinks` for several entries in order, taking the dispatch lock once
+    pub(crate) fn write_sinks_all(&self, sinks: &[Arc<dyn Sink>], entries: &[LogEntry]) {
+        match &self.inner.sink_queue {
+            Some(handle) if !sinks.is_empty() => {
+                for entry in entries {
+                    handle.queue().push(self, sinks.to_vec(), entry);
+                }
+            }
+            Some(_) => {}
+            None => {
+                let _dispatch = self.inner.dispatch_lock.lock();
+                for entry in entries {
+                    self.write_sinks_now(sinks, entry);
+                }
+            }
+        }
+    }
+
+    /// Write an entry to each of `sinks` on this thread
+    pub(crate) fn write_sinks_now(&self, sinks: &[Arc<dyn Sink>], entry: &LogEntry) {
+        #[cfg(all(unix, feature = "fork"))]
+        let _pass = self.inner.fork_gate.enter();
+
+        let options = self.format_options();
+        for sink in sinks {
+            match panic::catch_unwind(AssertUnwindSafe(|| sink.write(entry, &options))) {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => self.inner.stats.record_sink_error(SinkError::from(&e)),
+                Err(payload) => self.inner.stats.record_sink_error(SinkError::from_panic(payload)),
+            }
+        }
+    }
+}
+
+/// Appends entries to a file, one line each
+///
+/// Each new file starts with a header line naming its format; see
+/// `detect_format`. With `with_rotation`, the sink starts a new file as
+/// the current one grows too large or too old.
+pub struct FileSink {
+    path: PathBuf,
+    format: Format,
+    /// Used instead of the logger's options when set
+    options: Option<FormatOptions>,
+    route: SinkRoute,
+    file: Arc<Mutex<OpenFile>>,
+    sync: SyncPolicy,
+    /// Running while `sync` is `SyncPolicy::Interval`
+    interval_sync: Option<IntervalSync>,
+    /// Set by `with_rotation`
+    rotator: Option<Rotator>,
+}
+
+/// The current file and whether its header is still to be written
+struct OpenFile {
+    /// The sink's path, unless rotating with `Rotation::numbered_files`
+    path: PathBuf,
+    writer: BufWriter<Box<dyn SyncFile>>,
+    /// The file was empty when opened; written lazily so `with_format` can change it and the first entry names its run
+    needs_header: bool,
+    /// Bytes in the file, including what is still buffered
+    len: u64,
+    /// Bytes of the header this sink wrote, then counted in `len`
+    header_len: u64,
+    /// Rotation window of the newest entry, see `Rotation::window`
+    window: Option<i64>,
+}
+
+impl OpenFile {
+    /// Open `path`, counting an existing file as written when it was last modified
+    fn open(path: &Path, rotation: Option<&Rotation>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let metadata = file.metadata()?;
+        Ok(OpenFile {
+            path: path.to_path_buf(),
+            needs_header: metadata.len() == 0,
+            len: metadata.len(),
+            header_len: 0,
+            window: rotation.and_then(|rotation| modified_window(&metadata, rotation)),
+            writer: BufWriter::new(Box::new(file)),
+        })
+    }
+
+    /// The writer, after writing the header if this file still lacks one
+    ///
+    /// `run` is the run id of the entry about to be written, if any.
+    fn writer(&mut self, format: Format, run: Option<&str>) -> io::Result<&mut BufWriter<Box<dyn SyncFile>>> {
+        if self.needs_header {
+            let header = header_line(format, run);
+            self.writer.write_all(header.as_bytes())?;
+            self.len += header.len() as u64;
+            self.header_len = header.len() as u64;
+            self.needs_header = false;
+        }
+        Ok(&mut self.writer)
+    }
+
+    /// Whether anything but the header has been written; a file that wasn't empty when opened has
+    fn has_entries(&self) -> bool {
+        !self.needs_header && self.len > self.header_len
+    }
+
+    /// Flush and wait for the disk; a header not yet written is left for the first entry
+    fn sync(&mut self) -> io::Result<()> {
+        self.writer.flush()?;
+        self.writer.get_ref().sync_data()
+    }
+}
+
+/// The rotation window a non-empty file was last written in
+fn modified_window(metadata: &fs::Metadata, rotation: &Rotation) -> Option<i64> {
+    let modified = metadata.modified().ok().filter(|_| metadata.len() > 0)?;
+    rotation.window(Timestamp::from(modified))
+}
+
+impl FileSink {
+    /// Open `path` for appending, creating it if needed
+    pub fn new(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenFile::open(&path, None)?;
+        Ok(FileSink {
+            path,
+            format: Format::Text,
+            options: None,
+            route: SinkRoute::default(),
+            file: Arc::new(Mutex::new(file)),
+            sync: SyncPolicy::Never,
+            interval_sync: None,
+            rotator: None,
+        })
+    }
+
+    /// Start a new file as `rotation` says, keeping closed ones next to it as `path.1`, `path.2`, ...
+    ///
+    /// Higher numbers are newer. Files an earlier run left are kept and
+    /// counted towards `Rotation::max_files`. `path.current` leads to the
+    /// file being written, see `current_path`.
+    pub fn with_rotation(mut self, rotation: Rotation) -> Self {
+        let rotator = Rotator::new(&self.path, rotation);
+        if let Ok(mut file) = self.file.lock() {
+            let first = rotator.first_file();
+            if first != self.path && file.len == 0 {
+                // Created by `new`, but never written with numbered files
+                let _ = fs::remove_file(&self.path);
+            }
+            match OpenFile::open(first, Some(rotator.policy())) {
+                Ok(opened) => *file = opened,
+                Err(e) => {
+                    let _ = writeln!(io::stderr(), "horizon_logger: cannot open {}: {}", first.display(), e);
+                }
+            }
+            rotator.point_at(&file.path);
+        }
+        self.rotator = Some(rotator);
+        self
+    }
+
+    /// Sync the file to disk as `policy` says; never by default
+    pub fn with_sync_policy(mut self, policy: SyncPolicy) -> Self {
+        self.sync = policy;
+        self.interval_sync = match policy {
+            SyncPolicy::Interval(interval) => Some(IntervalSync::start(interval, self.file.clone(), OpenFile::sync)),
+            SyncPolicy::Never | SyncPolicy::OnLevel(_) => None,
+        };
+        self
+    }
+
+    /// Write to `file` from now on, e.g. a wrapper that counts syncs
+    #[cfg(test)]
+    pub(crate) fn replace_file(&self, file: Box<dyn SyncFile>) {
+        self.file.lock().unwrap().writer = BufWriter::new(file);
+    }
+
+    /// Write entries in the given format instead of plain text
+    pub fn with_format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Apply `policy` to ANSI escapes in messages instead of the logger's policy
+    pub fn with_ansi_policy(mut self, policy: AnsiPolicy) -> Self {
+        self.options.get_or_insert_with(FormatOptions::default).ansi = policy;
+        self
+    }
+
+    /// Render with `options` instead of the logger's
+    pub fn with_options(mut self, options: FormatOptions) -> Self {
+        self.options = Some(options);
+        self
+    }
+
+    /// Write only entries whose component matches `route`
+    pub fn with_route(mut self, route: SinkRoute) -> Self {
+        self.route = route;
+        self
+    }
+
+    /// Write only entries under `components`, e.g. `&["AUDIO"]` for `AUDIO` and `AUDIO/MIXER`
+    pub fn only_components(self, components: &[&str]) -> Self {
+        self.with_route(SinkRoute::only(components))
+    }
+
+    /// Write text lines laid out with `pattern`, whatever the logger's pattern
+    pub fn with_pattern(mut self, pattern: Pattern) -> Self {
+        self.format = Format::Text;
+        self.options.get_or_insert_with(FormatOptions::default).text_pattern = Some(pattern);
+        self
+    }
+
+    /// Path the sink was created with
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The file being written now: `path`, unless rotating with `Rotation::numbered_files`
+    pub fn current_path(&self) -> PathBuf {
+        match self.file.lock() {
+            Ok(file) => file.path.clone(),
+            Err(_) => self.path.clone(),
+        }
+    }
+
+    /// Close the current file as the newest numbered one, open the next and point `path.current` at it
+    fn rotate(&self, file: &mut OpenFile, rotator: &Rotator) -> io::Result<()> {
+        match self.sync {
+            SyncPolicy::Never => file.writer.flush()?,
+            _ => file.sync()?,
+        }
+        let next = rotator.rotate(&file.path)?;
+        *file = OpenFile::open(&next, Some(rotator.policy()))?;
+        rotator.point_at(&next);
+        Ok(())
+    }
+}
+
+fn lock_error() -> io::Error {
+    io::Error::other("file sink lock poisoned")
+}
+
+impl Sink for FileSink {
+    fn write(&self, entry: &LogEntry, options: &FormatOptions) -> io::Result<()> {
+        if !self.route.matches(&entry.component) {
+            return Ok(());
+        }
+        let mut line = format_entry(entry, self.format, self.options.as_ref().unwrap_or(options));
+        line.push('\n');
+        let mut file = self.file.lock().map_err(|_| lock_error())?;
+        if let Some(rotator) = &self.rotator {
+            if file.has_entries() && rotator.due(file.len, file.window, line.len(), entry.timestamp) {
+                self.rotate(&mut file, rotator)?;
+            }
+            file.window = file.window.max(rotator.policy().window(entry.timestamp));
+        }
+        file.writer(self.format, entry.run_id.as_deref())?.write_all(line.as_bytes())?;
+        file.len += line.len() as u64;
+        match self.sync {
+            SyncPolicy::OnLevel(level) if entry.level >= level => file.sync(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Write the probe, flush, and read it back from the end of the file at `path`
+    ///
+    /// Fails when the file was deleted or replaced since it was opened, since
+    /// entries would then be written where nothing can read them.
+    fn self_test(&self, probe: &LogEntry, options: &FormatOptions) -> io::Result<()> {
+        let mut line = format_entry(probe, self.format, self.options.as_ref().unwrap_or(options));
+        line.push('\n');
+        let mut file = self.file.lock().map_err(|_| lock_error())?;
+        let writer = file.writer(self.format, probe.run_id.as_deref())?;
+        writer.write_all(line.as_bytes())?;
+        writer.flush()?;
+        file.len += line.len() as u64;
+
+        let path = &file.path;
+        let mut written = File::open(path)
+            .map_err(|e| io::Error::new(e.kind(), format!("cannot read back {}: {}", path.display(), e)))?;
+        let mut tail = vec![0u8; line.len()];
+        let read = written
+            .seek(SeekFrom::End(-(line.len() as i64)))
+            .and_then(|_| written.read_exact(&mut tail));
+        if read.is_err() || tail != line.as_bytes() {
+            return Err(io::Error::other(format!("probe not found at the end of {}", path.display())));
+        }
+        Ok(())
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.file.lock().map_err(|_| lock_error())?.writer(self.format, None)?.flush()
+    }
+
+    fn sync(&self) -> io::Result<()> {
+        self.file.lock().map_err(|_| lock_error())?.sync()
+    }
+
+    /// Flush, and sync too unless the policy is `SyncPolicy::Never`
+    fn close(&self) -> io::Result<()> {
+        match self.sync {
+            SyncPolicy::Never => self.flush(),
+            _ => self.sync(),
+        }
+    }
+
+    /// Reopen the file, e.g. after log rotation; a new file gets its own header
+    ///
+    /// With `with_rotation`, also puts back `path.current` if it was deleted.
+    fn reopen(&self) -> io::Result<()> {
+        let mut file = self.file.lock().map_err(|_| lock_error())?;
+        file.writer(self.format, None)?.flush()?;
+        *file = OpenFile::open(&file.path, self.rotator.as_ref().map(Rotator::policy))?;
+        if let Some(rotator) = &self.rotator {
+            rotator.point_at(&file.path);
+        }
+        Ok(())
+    }
+
+    /// Start the interval sync and compression threads again
+    fn restart_after_fork(&self) -> io::Result<()> {
+        if let Some(interval_sync) = &self.interval_sync {
+            interval_sync.restart();
+        }
+        if let Some(rotator) = &self.rotator {
+            rotator.restart_after_fork();
+        }
+        Ok(())
+    }
+}
+
+impl Drop for FileSink {
+    fn drop(&mut self) {
+        self.interval_sync.take();
+        if let Ok(mut file) = self.file.lock() {
+            let _ = file.writer(self.format, None).and_then(|writer| writer.flush());
+            if self.sync != SyncPolicy::Never {
+                let _ = file.sync();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::console::tests::SharedBuf;
+    use crate::console::Console;
+    use crate::testing::fixtures::{temp_path, CollectSink};
+    use crate::testing::CaptureLogger;
+    use crate::ColorCodes;
+
+    #[test]
+    fn test_file_sink_writes_lines() {
+        let path = temp_path("file_sink.log");
+        let logger = CaptureLogger::new();
+        let id = logger.add_sink(FileSink::new(&path).unwrap().with_format(Format::Json));
+
+        logger.info("TEST", "first");
+        logger.flush();
+        assert!(logger.remove_sink(id));
+        logger.info("TEST", "not written");
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<_> = contents.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("# horizon-logger format=jsonl v=2 "));
+        let json: serde_json::Value = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!(json["message"], "first");
+        assert_eq!(json["level"], LogLevel::INFO.as_str());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_tee_matches_file_sink() {
+        let path = temp_path("tee.log");
+        let logger = CaptureLogger::from_builder(HorizonLogger::builder().text_pattern("{level} {component}: {message}".parse().unwrap()));
+        logger.set_min_level(LogLevel::INFO);
+        logger.add_sink(FileSink::new(&path).unwrap());
+        let teed = Arc::new(Mutex::new(String::new()));
+        let tee = logger.tee_formatted({
+            let teed = teed.clone();
+            move |level, line| {
+                assert_ne!(level, LogLevel::DEBUG);
+                if line.contains("boom") {
+                    panic!("tee callback failed");
+                }
+                let mut teed = teed.lock().unwrap();
+                teed.push_str(line);
+                teed.push('\n');
+            }
+        });
+
+        logger.info("NETWORK", "connected");
+        logger.debug("NETWORK", "filtered");
+        logger.warn("GAME", "boom");
+        logger.error("GAME", "lag spike: 120ms");
+        logger.flush();
+        assert!(logger.remove_sink(tee));
+        logger.info("GAME", "after removal");
+        logger.flush();
+
+        assert_eq!(logger.dropped_entries(), 1);
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let written: Vec<_> = contents.lines().skip(1).filter(|line| !line.contains("boom")).take(2).collect();
+        assert_eq!(*teed.lock().unwrap(), format!("{}\n", written.join("\n")));
+        assert_eq!(written, ["INFO NETWORK: connected", "ERROR GAME: lag spike: 120ms"]);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_custom_color_codes() {
+        let path = temp_path("overlay.log");
+        let logger = CaptureLogger::new();
+        let overlay = FormatOptions {
+            text_pattern: Some("{level} {component}: {message}".parse().unwrap()),
+            color_codes: ColorCodes::Custom(Arc::new(|level| {
+                let color = match level {
+                    LogLevel::ERROR | LogLevel::CRITICAL => "^1",
+                    LogLevel::WARN => "^3",
+                    _ => "^2",
+                };
+                (color.to_string(), "^7".to_string())
+            })),
+            ..FormatOptions::default()
+        };
+        logger.set_color_codes(ColorCodes::Ansi);
+        logger.add_sink(FileSink::new(&path).unwrap().with_options(overlay));
+        logger.info("NETWORK", "connected");
+        logger.error("GAME", "lag spike");
+        logger.flush();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<_> = contents.lines().skip(1).collect();
+        assert_eq!(lines, ["^2INFO^7 NETWORK: ^2connected^7", "^1ERROR^7 GAME: ^1lag spike^7"]);
+        assert!(!contents.contains('\x1b'));
+
+        let ansi = format_entry(&logger.get_history()[1], Format::Text, &logger.format_options());
+        assert!(ansi.contains("ERROR \x1b[0m [GAME] ") && ansi.ends_with("\x1b[31mlag spike\x1b[0m"), "{:?}", ansi);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_ansi_policies() {
+        let stripped = temp_path("ansi_strip.log");
+        let escaped = temp_path("ansi_escape.log");
+        let buf = SharedBuf::default();
+        let logger = HorizonLogger::builder()
+            .announce_run(false)
+            .console(Console::new(Box::new(buf.clone()), false))
+            .console_pattern("{message}".parse().unwrap())
+            .build();
+        logger.add_sink(FileSink::new(&stripped).unwrap().with_format(Format::Json));
+        logger.add_sink(FileSink::new(&escaped).unwrap().with_ansi_policy(AnsiPolicy::Escape));
+        logger.info("TOOLS", "\x1b[31m-old\x1b[0m \x1b[32m+new\x1b[0m");
+        logger.info("TOOLS", "plain");
+        logger.flush();
+
+        assert_eq!(buf.contents(), "\x1b[31m-old\x1b[0m \x1b[32m+new\x1b[0m\nplain\n");
+        let json = std::fs::read_to_string(&stripped).unwrap();
+        assert!(json.contains(r#""message":"-old +new""#) && !json.contains('\x1b'), "{}", json);
+        let text = std::fs::read_to_string(&escaped).unwrap();
+        assert!(text.contains("[TOOLS] \\x1b[31m-old\\x1b[0m \\x1b[32m+new\\x1b[0m\n"), "{}", text);
+
+        let history = logger.get_history();
+        assert_eq!((history[0].message.as_str(), history[0].ansi_stripped), ("-old +new", true));
+        assert_eq!((history[1].message.as_str(), history[1].ansi_stripped), ("plain", false));
+        let _ = std::fs::remove_file(&stripped);
+        let _ = std::fs::remove_file(&escaped);
+    }
+
+    #[test]
+    fn test_overlapping_routes() {
+        let audio = temp_path("route_audio.log");
+        let physics = temp_path("route_physics.log");
+        let logger = CaptureLogger::new();
+        logger.add_sink(SeverityFilter::new(FileSink::new(&audio).unwrap().only_components(&["audio"]), LogLevel::INFO.default_severity()));
+        logger.add_sink(FileSink::new(&physics).unwrap().with_pattern("{component} {message}".parse().unwrap()).only_components(&["PHYSICS", "AUDIO/MIXER"]));
+        let rest = Arc::new(CollectSink::default());
+        logger.add_sink(RoutedSink::new(
+            rest.clone(),
+            SinkRoute::default().excluding("PHYSICS").excluding("AUDIO/MIXER"),
+        ));
+
+        for (component, message) in [
+            ("AUDIO", "device opened"),
+            ("AUDIO/MIXER", "48 voices"),
+            ("PHYSICS", "step"),
+            ("AUDIOVISUAL", "cutscene"),
+            ("PHYSICS/CLOTH", "solver"),
+            ("NETWORK", "connected"),
+        ] {
+            logger.info(component, message);
+        }
+        logger.debug("AUDIO", "below the threshold");
+        logger.flush();
+
+        let lines = |path: &Path| -> Vec<String> {
+            let contents = std::fs::read_to_string(path).unwrap();
+            contents.lines().skip(1).map(|line| line.split_once("] ").map_or(line, |(_, rest)| rest).to_string()).collect()
+        };
+        assert_eq!(lines(&audio), ["device opened", "48 voices"]);
+        assert_eq!(
+            std::fs::read_to_string(&physics).unwrap().lines().skip(1).collect::<Vec<_>>(),
+            ["AUDIO/MIXER 48 voices", "PHYSICS step", "PHYSICS/CLOTH solver"]
+        );
+        assert_eq!(rest.messages(), ["device opened", "cutscene", "connected", "below the threshold"]);
+        let _ = std::fs::remove_file(&audio);
+        let _ = std::fs::remove_file(&physics);
+    }
+
+    #[test]
+    fn test_reopen_writes_header_to_new_file() {
+        let path = temp_path("rotated.log");
+        let rotated = temp_path("rotated.log.1");
+        let logger = CaptureLogger::new();
+        let sink = Arc::new(FileSink::new(&path).unwrap().with_format(Format::Logfmt));
+        logger.add_sink(sink.clone());
+
+        logger.info("TEST", "before rotation");
+        logger.flush();
+        std::fs::rename(&path, &rotated).unwrap();
+        logger.reopen_files().unwrap();
+        logger.info("TEST", "after rotation");
+        logger.flush();
+
+        // Appending to a file that already has a header doesn't repeat it
+        sink.reopen().unwrap();
+        logger.info("TEST", "after reopen");
+        logger.flush();
+
+        let header = header_line(Format::Logfmt, Some(logger.run_id()));
+        let old = std::fs::read_to_string(&rotated).unwrap();
+        let new = std::fs::read_to_string(&path).unwrap();
+        assert!(old.starts_with(&header) && old.lines().count() == 2, "{}", old);
+        assert!(new.starts_with(&header) && new.lines().count() == 3, "{}", new);
+        assert!(new.contains("msg=\"after rotation\""));
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(&rotated);
+    }
+}
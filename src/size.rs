@@ -0,0 +1,250 @@
+//! Message size histogram and the largest messages, for finding what bloats log files
+//!
+//! Sizes are message bytes, the part of an entry a call site controls. The
+//! histogram has log2 buckets: 0 bytes, 1, 2–3, 4–7 and so on, with
+//! everything from 2 GiB up in the last one.
+
+use crate::{ComponentArg, HorizonLogger, LogLevel};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// Number of histogram buckets
+const BUCKET_COUNT: usize = 33;
+
+/// Largest messages kept
+pub(crate) const TOP_MESSAGES: usize = 10;
+
+/// Characters of a message kept in `LargeMessage::preview`
+const PREVIEW_CHARS: usize = 60;
+
+/// Messages of `min_bytes..=max_bytes`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SizeBucket {
+    pub min_bytes: u64,
+    /// `u64::MAX` for the last bucket
+    pub max_bytes: u64,
+    pub count: u64,
+}
+
+/// One of the largest messages logged
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LargeMessage {
+    pub component: String,
+    pub level: LogLevel,
+    pub bytes: usize,
+    /// The start of the message, with `…` if it was cut
+    pub preview: String,
+}
+
+/// Message sizes since the logger was built or `reset_size_report`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SizeReport {
+    /// Non-empty buckets, smallest first
+    pub buckets: Vec<SizeBucket>,
+    /// The largest messages, largest first
+    pub largest: Vec<LargeMessage>,
+}
+
+impl SizeReport {
+    /// Messages counted
+    pub fn total(&self) -> u64 {
+        self.buckets.iter().map(|bucket| bucket.count).sum()
+    }
+}
+
+/// Bucket counters and the largest messages
+pub(crate) struct SizeTracker {
+    buckets: [AtomicU64; BUCKET_COUNT],
+    largest: Mutex<Vec<LargeMessage>>,
+    /// Size a message must exceed to join `largest`; zero until it is full
+    threshold: AtomicUsize,
+}
+
+impl SizeTracker {
+    pub(crate) fn new() -> Self {
+        SizeTracker {
+            buckets: std::array::from_fn(|_| AtomicU64::new(0)),
+            largest: Mutex::new(Vec::with_capacity(TOP_MESSAGES + 1)),
+            threshold: AtomicUsize::new(0),
+        }
+    }
+
+    /// Account for one message
+    pub(crate) fn record(&self, level: LogLevel, component: &str, message: &str) {
+        let bytes = message.len();
+        self.buckets[bucket_index(bytes)].fetch_add(1, Ordering::Relaxed);
+        if bytes == 0 || bytes <= self.threshold.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let Ok(mut largest) = self.largest.lock() else {
+            return;
+        };
+        // Another thread may have raised the bar meanwhile
+        if largest.len() == TOP_MESSAGES && bytes <= largest[TOP_MESSAGES - 1].bytes {
+            return;
+        }
+        let at = largest.partition_point(|kept| kept.bytes >= bytes);
+        largest.insert(
+            at,
+            LargeMessage {
+                component: component.to_string(),
+                level,
+                bytes,
+                preview: preview(message),
+            },
+        );
+        largest.truncate(TOP_MESSAGES);
+        if largest.len() == TOP_MESSAGES {
+            self.threshold.store(largest[TOP_MESSAGES - 1].bytes, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn report(&self) -> SizeReport {
+        let buckets = self
+            .buckets
+            .iter()
+            .enumerate()
+            .map(|(i, count)| SizeBucket {
+                min_bytes: bucket_min(i),
+                max_bytes: if i + 1 == BUCKET_COUNT { u64::MAX } else { bucket_min(i + 1).max(1) - 1 },
+                count: count.load(Ordering::Relaxed),
+            })
+            .filter(|bucket| bucket.count > 0)
+            .collect();
+        let largest = self.largest.lock().map(|largest| largest.clone()).unwrap_or_default();
+        SizeReport { buckets, largest }
+    }
+
+    pub(crate) fn reset(&self) {
+        for bucket in &self.buckets {
+            bucket.store(0, Ordering::Relaxed);
+        }
+        if let Ok(mut largest) = self.largest.lock() {
+            largest.clear();
+            self.threshold.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+/// 0 for empty messages, otherwise one more than the index of the highest set bit
+fn bucket_index(bytes: usize) -> usize {
+    ((usize::BITS - bytes.leading_zeros()) as usize).min(BUCKET_COUNT - 1)
+}
+
+fn bucket_min(index: usize) -> u64 {
+    match index {
+        0 => 0,
+        i => 1 << (i - 1),
+    }
+}
+
+fn preview(message: &str) -> String {
+    let mut chars = message.char_indices();
+    match chars.nth(PREVIEW_CHARS) {
+        Some((cut, _)) => format!("{}…", &message[..cut]),
+        None => message.to_string(),
+    }
+}
+
+impl HorizonLogger {
+    /// Message size histogram and the largest messages since the last reset
+    pub fn size_report(&self) -> SizeReport {
+        self.inner.sizes.report()
+    }
+
+    /// Start a new size measurement window
+    pub fn reset_size_report(&self) {
+        self.inner.sizes.reset();
+    }
+
+    /// Log the size histogram and the largest messages at INFO under `component`
+    pub fn log_size_report(&self, component: impl ComponentArg) {
+        let report = self.size_report();
+        let widest = report.buckets.iter().map(|bucket| bucket.count).max().unwrap_or(0);
+
+        self.info(&component, &format!("{:>21} {:>10}", "MESSAGE SIZE", "MESSAGES"));
+        for bucket in &report.buckets {
+            let range = match bucket.max_bytes {
+                u64::MAX => format!("{} and up", crate::fmt::bytes(bucket.min_bytes)),
+                max if max == bucket.min_bytes => crate::fmt::bytes(max).to_string(),
+                max => format!("{} - {}", crate::fmt::bytes(bucket.min_bytes), crate::fmt::bytes(max)),
+            };
+            // Bars scaled to the fullest bucket, at least one mark for any count
+            let bar = "#".repeat(((bucket.count * 40).div_ceil(widest.max(1))) as usize);
+            self.info(&component, &format!("{:>21} {:>10} {}", range, bucket.count, bar));
+        }
+
+        self.info(&component, &format!("{:>21} {:<24} {}", "LARGEST", "COMPONENT", "MESSAGE"));
+        for message in &report.largest {
+            let line = format!(
+                "{:>21} {:<24} {}",
+                crate::fmt::bytes(message.bytes as u64).to_string(),
+                message.component,
+                message.preview
+            );
+            self.info(&component, &line);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::CaptureLogger;
+
+    fn bucket(min_bytes: u64, max_bytes: u64, count: u64) -> SizeBucket {
+        SizeBucket { min_bytes, max_bytes, count }
+    }
+
+    #[test]
+    fn test_buckets() {
+        let logger = CaptureLogger::new();
+        for size in [0, 1, 2, 3, 4, 7, 8, 100, 127, 128, 5000] {
+            logger.info("GAME", &"x".repeat(size));
+        }
+
+        let report = logger.size_report();
+        assert_eq!(
+            report.buckets,
+            [
+                bucket(0, 0, 1),
+                bucket(1, 1, 1),
+                bucket(2, 3, 2),
+                bucket(4, 7, 2),
+                bucket(8, 15, 1),
+                bucket(64, 127, 2),
+                bucket(128, 255, 1),
+                bucket(4096, 8191, 1),
+            ]
+        );
+        assert_eq!(report.total(), 11);
+        assert_eq!(bucket_index(usize::MAX), BUCKET_COUNT - 1);
+
+        logger.reset_size_report();
+        assert_eq!(logger.size_report(), SizeReport { buckets: Vec::new(), largest: Vec::new() });
+    }
+
+    #[test]
+    fn test_largest_messages() {
+        let logger = CaptureLogger::new();
+        for size in 1..=30 {
+            // Sizes out of order, so later entries have to push into the middle
+            let size = (size * 7) % 31;
+            logger.debug("AI", &format!("{:>1$}", "x", size));
+        }
+        logger.warn("NETWORK", &"packet dump ".repeat(10));
+
+        let largest = logger.size_report().largest;
+        let sizes: Vec<_> = largest.iter().map(|m| m.bytes).collect();
+        assert_eq!(sizes, [120, 30, 29, 28, 27, 26, 25, 24, 23, 22]);
+        assert_eq!((&*largest[0].component, largest[0].level), ("NETWORK", LogLevel::WARN));
+        assert_eq!(largest[0].preview, format!("{}…", &"packet dump ".repeat(5)));
+        assert_eq!(largest[1].preview.len(), 30);
+
+        logger.log_size_report("STATS");
+        let messages = logger.messages();
+        assert!(messages.iter().any(|m| m.ends_with(" 64 B - 127 B          1 ###")));
+        assert!(messages.iter().any(|m| m.starts_with("                120 B NETWORK") && m.ends_with('…')));
+    }
+}
@@ -0,0 +1,128 @@
+//! Spotting wall-clock jumps between entries, see `LoggerBuilder::detect_clock_skew`
+
+use crate::{FieldValue, Timestamp};
+use std::borrow::Cow;
+use std::fmt;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Attached to the first entry after a jump
+pub(crate) const SKEW_FIELD: (Cow<'static, str>, FieldValue) = (Cow::Borrowed("clock_skew"), FieldValue::Bool(true));
+
+/// Compares each entry's wall-clock time with monotonic time since the entry before
+pub(crate) struct SkewDetector {
+    /// Largest disagreement let through, in microseconds; `None` when detection is off
+    threshold: Option<i64>,
+    /// Wall-clock time of the previous entry, in microseconds
+    last_wall: AtomicI64,
+    /// Monotonic time of the previous entry in microseconds, plus one; zero before the first
+    last_monotonic: AtomicU64,
+}
+
+/// A jump found by `SkewDetector::check`, in microseconds
+pub(crate) struct Skew {
+    wall: i64,
+    monotonic: i64,
+}
+
+impl fmt::Display for Skew {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "wall clock jumped {:+.1}s vs {:.1}s monotonic",
+            self.wall as f64 / 1e6,
+            self.monotonic as f64 / 1e6
+        )
+    }
+}
+
+impl SkewDetector {
+    pub(crate) fn new(threshold: Option<Duration>) -> Self {
+        SkewDetector {
+            threshold: threshold.map(|threshold| i64::try_from(threshold.as_micros()).unwrap_or(i64::MAX)),
+            last_wall: AtomicI64::new(0),
+            last_monotonic: AtomicU64::new(0),
+        }
+    }
+
+    /// Record an entry logged at `wall`, returning the jump since the previous entry if there was one
+    ///
+    /// Entries logged at once on several threads may pair one's wall time
+    /// with another's monotonic time, which is off by far less than any
+    /// useful threshold.
+    #[inline]
+    pub(crate) fn check(&self, wall: Timestamp, monotonic: impl FnOnce() -> Duration) -> Option<Skew> {
+        let threshold = self.threshold?;
+        let monotonic = u64::try_from(monotonic().as_micros()).unwrap_or(u64::MAX - 1) + 1;
+        let last_monotonic = self.last_monotonic.swap(monotonic, Ordering::Relaxed);
+        let last_wall = self.last_wall.swap(wall.as_micros(), Ordering::Relaxed);
+        if last_monotonic == 0 {
+            return None;
+        }
+        let skew = Skew {
+            wall: wall.as_micros().saturating_sub(last_wall),
+            monotonic: monotonic.saturating_sub(last_monotonic) as i64,
+        };
+        (skew.wall.saturating_sub(skew.monotonic).saturating_abs() > threshold).then_some(skew)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::CaptureLogger;
+    use crate::{FieldValue, HorizonLogger, ManualClock, Timestamp};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_jumps_are_noted_and_flagged() {
+        let start = 1_700_000_000_000;
+        let clock = Arc::new(ManualClock::new(Timestamp::from_millis(start)));
+        let logger = CaptureLogger::from_builder(
+            HorizonLogger::builder().clock(clock.clone()).detect_clock_skew(Duration::from_secs(1)),
+        );
+        // A suspend: the wall clock runs on while monotonic time barely moves
+        logger.info("GAME", "first");
+        clock.advance(Duration::from_millis(2_100));
+        clock.set(Timestamp::from_millis(start + 3_602_400));
+        logger.info("GAME", "after suspend");
+        // An NTP step backwards
+        clock.advance(Duration::from_millis(500));
+        clock.set(Timestamp::from_millis(start + 3_602_400 - 29_000));
+        logger.warn("GAME", "after step");
+        clock.set(Timestamp::from_millis(start + 3_602_400 - 28_800));
+        logger.info("GAME", "within threshold");
+
+        assert_eq!(
+            logger.messages(),
+            [
+                "first",
+                "wall clock jumped +3602.4s vs 2.1s monotonic",
+                "after suspend",
+                "wall clock jumped -29.0s vs 0.5s monotonic",
+                "after step",
+                "within threshold"
+            ]
+        );
+        let entries = logger.entries();
+        let skewed: Vec<_> = entries
+            .iter()
+            .filter(|entry| {
+                entry.fields.iter().any(|(name, value)| name == "clock_skew" && *value == FieldValue::Bool(true))
+            })
+            .map(|entry| entry.message.as_str())
+            .collect();
+        assert_eq!(skewed, ["after suspend", "after step"]);
+        assert_eq!(entries[1].component, "LOGGER");
+    }
+
+    #[test]
+    fn test_detection_is_off_by_default() {
+        let clock = Arc::new(ManualClock::new(Timestamp::from_millis(0)));
+        let logger = CaptureLogger::from_builder(HorizonLogger::builder().clock(clock.clone()));
+        logger.info("GAME", "first");
+        clock.set(Timestamp::from_millis(86_400_000));
+        logger.info("GAME", "a day later");
+        assert_eq!(logger.messages(), ["first", "a day later"]);
+    }
+}
@@ -0,0 +1,78 @@
+//! Source lines shown under ERROR and CRITICAL entries on the console, see `LoggerBuilder::source_snippets`
+//!
+//! Files are read from disk relative to the working directory, as the
+//! compiler recorded their paths, and kept in a small cache. A file that
+//! can't be read, such as in a deployed binary without its sources, is
+//! remembered as missing and its entries are shown without a snippet.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// Files kept in the cache; the least recently used is evicted beyond this
+const MAX_FILES: usize = 32;
+
+/// Larger files are treated as unreadable rather than cached
+const MAX_FILE_BYTES: u64 = 1024 * 1024;
+
+/// A file's lines, or `None` if it couldn't be read
+type CachedFile = (String, Option<Arc<[String]>>);
+
+/// Files read so far, least recently used first
+#[derive(Default)]
+pub(crate) struct SourceCache {
+    files: Mutex<VecDeque<CachedFile>>,
+}
+
+impl SourceCache {
+    /// `line` (counting from 1) of `file` with one line either side, as `(line number, text)`
+    pub(crate) fn snippet(&self, file: &str, line: u32) -> Option<Vec<(usize, String)>> {
+        let lines = self.lines(file)?;
+        let index = (line as usize).checked_sub(1).filter(|&index| index < lines.len())?;
+        let range = index.saturating_sub(1)..(index + 2).min(lines.len());
+        Some(range.map(|i| (i + 1, lines[i].clone())).collect())
+    }
+
+    fn lines(&self, file: &str) -> Option<Arc<[String]>> {
+        let mut files = self.files.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(i) = files.iter().position(|(path, _)| path == file) {
+            let cached = files.remove(i)?;
+            let lines = cached.1.clone();
+            files.push_back(cached);
+            return lines;
+        }
+        let lines = read_lines(file);
+        files.push_back((file.to_string(), lines.clone()));
+        if files.len() > MAX_FILES {
+            files.pop_front();
+        }
+        lines
+    }
+}
+
+fn read_lines(file: &str) -> Option<Arc<[String]>> {
+    if std::fs::metadata(file).ok()?.len() > MAX_FILE_BYTES {
+        return None;
+    }
+    let text = std::fs::read_to_string(file).ok()?;
+    Some(text.lines().map(str::to_string).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_is_bounded_and_remembers_missing_files() {
+        let cache = SourceCache::default();
+        for n in 0..MAX_FILES + 5 {
+            assert!(cache.snippet(&format!("no/such/file_{}.rs", n), 1).is_none());
+        }
+        assert_eq!(cache.files.lock().unwrap().len(), MAX_FILES);
+
+        let fixture = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/matchmaker.rs");
+        let first = cache.snippet(fixture, 1).unwrap();
+        assert_eq!(first.iter().map(|(n, _)| *n).collect::<Vec<_>>(), [1, 2]);
+        assert!(cache.snippet(fixture, 10_000).is_none());
+        assert_eq!(cache.files.lock().unwrap().back().unwrap().0, fixture);
+    }
+}
@@ -0,0 +1,177 @@
+use crate::{CallOptions, ComponentArg, HorizonLogger, LogLevel};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+/// Source of process-wide unique span ids
+static NEXT_SPAN_ID: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    /// Ids of the spans currently open on this thread, innermost last
+    static SPAN_STACK: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Number of spans open on the current thread
+pub(crate) fn depth() -> usize {
+    SPAN_STACK.with(|stack| stack.borrow().len())
+}
+
+/// Innermost span id and its parent on the current thread
+pub(crate) fn current_ids() -> (Option<u64>, Option<u64>) {
+    SPAN_STACK.with(|stack| {
+        let stack = stack.borrow();
+        let mut ids = stack.iter().rev();
+        (ids.next().copied(), ids.next().copied())
+    })
+}
+
+/// This thread's open span ids, for `capture_context`
+pub(crate) fn stack() -> Vec<u64> {
+    SPAN_STACK.with(|stack| stack.borrow().clone())
+}
+
+/// Swap this thread's span stack for `ids`, returning the old one
+pub(crate) fn replace_stack(ids: Vec<u64>) -> Vec<u64> {
+    SPAN_STACK.with(|stack| stack.replace(ids))
+}
+
+/// Options for span markers, which are indented at the enclosing span's depth
+fn at_depth(depth: usize) -> CallOptions<'static> {
+    CallOptions {
+        depth: Some(depth),
+        ..CallOptions::default()
+    }
+}
+
+/// A named operation that tags every entry logged on this thread while it is open
+///
+/// Logs `>> name` when created and `<< name (elapsed)` when dropped. Spans
+/// are tied to the thread that opened them and therefore are not `Send`.
+pub struct LogSpan {
+    logger: HorizonLogger,
+    component: Cow<'static, str>,
+    name: String,
+    id: u64,
+    parent_id: Option<u64>,
+    start: Instant,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl LogSpan {
+    /// Id attached to entries logged inside this span
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Id of the enclosing span, if any
+    pub fn parent_id(&self) -> Option<u64> {
+        self.parent_id
+    }
+
+    /// Log an INFO message inside this span
+    pub fn event(&self, message: &str) {
+        self.logger.log(LogLevel::INFO, &self.component, message);
+    }
+}
+
+impl Drop for LogSpan {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed();
+        let outer_depth = depth().saturating_sub(1);
+        self.logger.log_with(
+            LogLevel::INFO,
+            &self.component,
+            &format!("<< {} ({})", self.name, crate::fmt::duration(elapsed)),
+            at_depth(outer_depth),
+        );
+
+        SPAN_STACK.with(|stack| {
+            let mut stack = stack.borrow_mut();
+            if let Some(pos) = stack.iter().rposition(|&id| id == self.id) {
+                stack.remove(pos);
+            }
+        });
+    }
+}
+
+impl HorizonLogger {
+    /// Open a span named `name` under `component`
+    pub fn span(&self, component: impl ComponentArg, name: &str) -> LogSpan {
+        let id = NEXT_SPAN_ID.fetch_add(1, Ordering::Relaxed);
+        let parent_id = SPAN_STACK.with(|stack| {
+            let mut stack = stack.borrow_mut();
+            let parent = stack.last().copied();
+            stack.push(id);
+            parent
+        });
+
+        let span = LogSpan {
+            logger: self.clone(),
+            component: component.to_cow(),
+            name: name.to_string(),
+            id,
+            parent_id,
+            start: Instant::now(),
+            _not_send: PhantomData,
+        };
+
+        self.log_with(
+            LogLevel::INFO,
+            &span.component,
+            &format!(">> {}", name),
+            at_depth(depth().saturating_sub(1)),
+        );
+        span
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::CaptureLogger;
+
+    #[test]
+    fn test_nested_spans_record_ids() {
+        let logger = CaptureLogger::new();
+
+        let (outer_id, inner_id) = {
+            let outer = logger.span("NET", "handle_packet");
+            let inner = logger.span("NET", "decode");
+            inner.event("decoded 42 entities");
+            (outer.id(), inner.id())
+        };
+        logger.info("NET", "after");
+
+        let history = logger.get_history();
+        let summary: Vec<_> = history
+            .iter()
+            .map(|e| (e.message.split(' ').take(2).collect::<Vec<_>>().join(" "), e.span_id, e.parent_id))
+            .collect();
+
+        assert_eq!(
+            summary,
+            vec![
+                (">> handle_packet".to_string(), Some(outer_id), None),
+                (">> decode".to_string(), Some(inner_id), Some(outer_id)),
+                ("decoded 42".to_string(), Some(inner_id), Some(outer_id)),
+                ("<< decode".to_string(), Some(inner_id), Some(outer_id)),
+                ("<< handle_packet".to_string(), Some(outer_id), None),
+                ("after".to_string(), None, None),
+            ]
+        );
+        assert!(history[3].message.ends_with("s)"), "{}", history[3].message);
+    }
+
+    #[test]
+    fn test_span_ids_in_json() {
+        let logger = CaptureLogger::new();
+        let span = logger.span("NET", "decode");
+        span.event("inside");
+
+        let entry = logger.get_history().pop().unwrap();
+        let json: serde_json::Value = serde_json::from_str(&entry.to_json()).unwrap();
+        assert_eq!(json["span_id"], span.id());
+        assert!(json.get("parent_id").is_none());
+    }
+}
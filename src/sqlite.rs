@@ -0,0 +1,543 @@
+//! Queryable local storage in an SQLite database
+//!
+//! `SqliteSink` inserts entries into one table, created on first open:
+//!
+//! ```text
+//! entries(seq, ts_micros, level, component, thread, message, fields, run_id)
+//! ```
+//!
+//! `level` is `LogLevel::to_u8` (0 for DEBUG up to 4 for CRITICAL) and
+//! `fields` is a JSON object holding whichever of `code`, `span_id`,
+//! `parent_id`, `group_id`, `corr`, `template` and `fields` are set, or
+//! NULL. `ts_micros` and `level` are indexed. The schema version is kept in `PRAGMA user_version`
+//! and the database runs in WAL mode, so `SqliteLogReader` or the `sqlite3`
+//! shell can read while the sink writes.
+
+use crate::escape::push_json_str;
+use crate::format::{push_json_fields, FormatOptions};
+use crate::sink::Sink;
+use crate::{LogEntry, LogLevel, Timestamp};
+use rusqlite::{params, Connection, OpenFlags, Row as SqlRow};
+use std::fmt::Write as _;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Current schema version, stored in `PRAGMA user_version`
+const SCHEMA_VERSION: i32 = 1;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS entries (
+        seq INTEGER NOT NULL,
+        ts_micros INTEGER NOT NULL,
+        level INTEGER NOT NULL,
+        component TEXT NOT NULL,
+        thread TEXT,
+        message TEXT NOT NULL,
+        fields TEXT,
+        run_id TEXT
+    );
+    CREATE INDEX IF NOT EXISTS entries_ts ON entries (ts_micros);
+    CREATE INDEX IF NOT EXISTS entries_level ON entries (level);
+";
+
+const INSERT: &str = "INSERT INTO entries (seq, ts_micros, level, component, thread, message, fields, run_id)
+    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)";
+
+const SELECT: &str = "SELECT seq, ts_micros, level, component, message, run_id,
+    json_extract(fields, '$.code'), json_extract(fields, '$.corr'),
+    json_extract(fields, '$.span_id'), json_extract(fields, '$.parent_id'), json_extract(fields, '$.group_id')
+    FROM entries";
+
+/// Entries inserted per transaction, by default
+const DEFAULT_BATCH_SIZE: usize = 256;
+
+/// Longest an entry waits for its batch to fill, by default
+const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Entries waiting to be inserted, by default
+const DEFAULT_QUEUE_CAPACITY: usize = 10_000;
+
+/// Longest `flush` waits for queued entries to be inserted
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+fn sql_error(error: rusqlite::Error) -> io::Error {
+    io::Error::other(error)
+}
+
+/// Open `path` read-write, creating or checking the schema
+fn open_writer(path: &Path) -> rusqlite::Result<Connection> {
+    let connection = Connection::open(path)?;
+    connection.pragma_update(None, "journal_mode", "WAL")?;
+    connection.pragma_update(None, "synchronous", "NORMAL")?;
+    match schema_version(&connection)? {
+        0 => {
+            connection.execute_batch(SCHEMA)?;
+            connection.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        }
+        SCHEMA_VERSION => {}
+        other => return Err(unsupported_version(other)),
+    }
+    Ok(connection)
+}
+
+fn schema_version(connection: &Connection) -> rusqlite::Result<i32> {
+    connection.pragma_query_value(None, "user_version", |row| row.get(0))
+}
+
+fn unsupported_version(version: i32) -> rusqlite::Error {
+    rusqlite::Error::InvalidParameterName(format!("unsupported log database schema version {}", version))
+}
+
+/// An entry as inserted, captured on the logging thread
+struct Row {
+    seq: u64,
+    ts_micros: i64,
+    level: u8,
+    component: String,
+    thread: Option<String>,
+    message: String,
+    fields: Option<String>,
+    run_id: Option<String>,
+}
+
+impl Row {
+    fn of(entry: &LogEntry) -> Self {
+        Row {
+            seq: entry.seq,
+            ts_micros: entry.timestamp.as_micros(),
+            level: entry.level.to_u8(),
+            component: entry.component.to_string(),
+            thread: thread::current().name().map(str::to_string),
+            message: entry.message.clone(),
+            fields: fields_json(entry),
+            run_id: entry.run_id.as_deref().map(str::to_string),
+        }
+    }
+}
+
+/// The `fields` column: an entry's optional context as a JSON object
+fn fields_json(entry: &LogEntry) -> Option<String> {
+    let mut out = String::new();
+    fn key(out: &mut String, name: &str) {
+        out.push(if out.is_empty() { '{' } else { ',' });
+        push_json_str(out, name);
+        out.push(':');
+    }
+    if let Some(code) = &entry.code {
+        key(&mut out, "code");
+        push_json_str(&mut out, code);
+    }
+    for (name, id) in [("span_id", entry.span_id), ("parent_id", entry.parent_id), ("group_id", entry.group_id)] {
+        if let Some(id) = id {
+            key(&mut out, name);
+            let _ = write!(out, "{}", id);
+        }
+    }
+    if let Some(correlation_id) = &entry.correlation_id {
+        key(&mut out, "corr");
+        push_json_str(&mut out, correlation_id);
+    }
+    if let Some(template) = &entry.template {
+        key(&mut out, "template");
+        push_json_str(&mut out, template);
+    }
+    if !entry.fields.is_empty() {
+        key(&mut out, "fields");
+        push_json_fields(&mut out, &entry.fields);
+    }
+    if out.is_empty() {
+        return None;
+    }
+    out.push('}');
+    Some(out)
+}
+
+enum Message {
+    Row(Row),
+    /// Insert everything queued so far, then reply
+    Flush(mpsc::Sender<()>),
+}
+
+/// Configures a `SqliteSink`, see `SqliteSink::builder`
+pub struct SqliteSinkBuilder {
+    path: PathBuf,
+    batch_size: usize,
+    flush_interval: Duration,
+    queue_capacity: usize,
+}
+
+impl SqliteSinkBuilder {
+    /// Entries inserted per transaction (256 by default)
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Longest an entry waits for its batch to fill before it is inserted anyway (500ms by default)
+    pub fn flush_interval(mut self, interval: Duration) -> Self {
+        self.flush_interval = interval;
+        self
+    }
+
+    /// Maximum number of entries waiting to be inserted; newer entries are dropped beyond it
+    pub fn queue_capacity(mut self, capacity: usize) -> Self {
+        self.queue_capacity = capacity;
+        self
+    }
+
+    /// Open the database, creating it and its schema if needed, and start the writer thread
+    pub fn open(self) -> io::Result<SqliteSink> {
+        let dropped = Arc::new(AtomicU64::new(0));
+        let (sender, thread) = self.start_writer(&dropped)?;
+        Ok(SqliteSink {
+            settings: self,
+            sender: RwLock::new(Some(sender)),
+            dropped,
+            thread: Mutex::new(Some(thread)),
+        })
+    }
+
+    /// Open a connection and start a writer thread inserting over it
+    fn start_writer(&self, dropped: &Arc<AtomicU64>) -> io::Result<(SyncSender<Message>, JoinHandle<()>)> {
+        let connection = open_writer(&self.path).map_err(sql_error)?;
+        let (sender, queue) = mpsc::sync_channel(self.queue_capacity);
+        let mut writer = Writer {
+            connection,
+            batch: Vec::with_capacity(self.batch_size),
+            batch_size: self.batch_size,
+            dropped: dropped.clone(),
+        };
+        let flush_interval = self.flush_interval;
+        let thread = thread::Builder::new()
+            .name("horizon-sqlite-sink".into())
+            .spawn(move || writer.run(&queue, flush_interval))?;
+        Ok((sender, thread))
+    }
+}
+
+/// Inserts entries into an SQLite database from a background thread
+///
+/// Entries are queued on the logging thread and inserted in batches, one
+/// transaction per batch. A full queue drops new entries rather than
+/// blocking; see `dropped`.
+pub struct SqliteSink {
+    /// What it was opened with, to start a new writer after `fork()`
+    settings: SqliteSinkBuilder,
+    sender: RwLock<Option<SyncSender<Message>>>,
+    dropped: Arc<AtomicU64>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl SqliteSink {
+    /// Open `path` with the default batching, see `builder`
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::builder(path).open()
+    }
+
+    /// Configure batching and the queue before opening `path`
+    pub fn builder(path: impl AsRef<Path>) -> SqliteSinkBuilder {
+        SqliteSinkBuilder {
+            path: path.as_ref().to_path_buf(),
+            batch_size: DEFAULT_BATCH_SIZE,
+            flush_interval: DEFAULT_FLUSH_INTERVAL,
+            queue_capacity: DEFAULT_QUEUE_CAPACITY,
+        }
+    }
+
+    /// Path of the database
+    pub fn path(&self) -> &Path {
+        &self.settings.path
+    }
+
+    /// Entries dropped because the queue was full or their batch could not be inserted
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    fn sender(&self) -> io::Result<SyncSender<Message>> {
+        self.sender.read().ok().and_then(|sender| sender.clone()).ok_or_else(writer_stopped)
+    }
+}
+
+fn writer_stopped() -> io::Error {
+    io::Error::other("sqlite writer thread stopped")
+}
+
+impl Sink for SqliteSink {
+    fn write(&self, entry: &LogEntry, _options: &FormatOptions) -> io::Result<()> {
+        match self.sender()?.try_send(Message::Row(Row::of(entry))) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                Ok(())
+            }
+            Err(TrySendError::Disconnected(_)) => Err(writer_stopped()),
+        }
+    }
+
+    /// Wait for queued entries to be inserted
+    fn flush(&self) -> io::Result<()> {
+        let (done, inserted) = mpsc::channel();
+        self.sender()?.send(Message::Flush(done)).map_err(|_| writer_stopped())?;
+        inserted.recv_timeout(FLUSH_TIMEOUT).map_err(|_| io::Error::from(io::ErrorKind::TimedOut))
+    }
+
+    /// Insert the probe and check it wasn't dropped
+    fn self_test(&self, probe: &LogEntry, options: &FormatOptions) -> io::Result<()> {
+        let dropped = self.dropped();
+        self.write(probe, options)?;
+        self.flush()?;
+        if self.dropped() != dropped {
+            return Err(io::Error::other(format!("probe not inserted into {}", self.path().display())));
+        }
+        Ok(())
+    }
+
+    /// Insert from now on over a new connection and thread; rows the parent had queued are left to it
+    ///
+    /// An SQLite connection must not be used across a fork, so the child
+    /// never touches the parent's again.
+    fn restart_after_fork(&self) -> io::Result<()> {
+        let (sender, thread) = self.settings.start_writer(&self.dropped)?;
+        if let Ok(mut current) = self.sender.write() {
+            *current = Some(sender);
+        }
+        let mut current = self.thread.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(parents) = current.replace(thread) {
+            // Gone with the fork; joining it would never return
+            std::mem::forget(parents);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for SqliteSink {
+    fn drop(&mut self) {
+        // Disconnecting makes the writer insert what is left and exit
+        self.sender.get_mut().unwrap_or_else(|poisoned| poisoned.into_inner()).take();
+        let thread = self.thread.get_mut().unwrap_or_else(|poisoned| poisoned.into_inner()).take();
+        if let Some(thread) = thread {
+            let _ = thread.join();
+        }
+    }
+}
+
+struct Writer {
+    connection: Connection,
+    batch: Vec<Row>,
+    batch_size: usize,
+    dropped: Arc<AtomicU64>,
+}
+
+impl Writer {
+    fn run(&mut self, queue: &mpsc::Receiver<Message>, flush_interval: Duration) {
+        let mut deadline: Option<Instant> = None;
+        loop {
+            let message = match deadline {
+                None => queue.recv().map_err(|_| RecvTimeoutError::Disconnected),
+                Some(deadline) => queue.recv_timeout(deadline.saturating_duration_since(Instant::now())),
+            };
+            match message {
+                Ok(Message::Row(row)) => {
+                    if self.batch.is_empty() {
+                        deadline = Some(Instant::now() + flush_interval);
+                    }
+                    self.batch.push(row);
+                    if self.batch.len() < self.batch_size {
+                        continue;
+                    }
+                }
+                Ok(Message::Flush(done)) => {
+                    self.insert();
+                    let _ = done.send(());
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    self.insert();
+                    return;
+                }
+            }
+            self.insert();
+            deadline = None;
+        }
+    }
+
+    /// Insert the batch in one transaction; a failed batch is counted as dropped
+    fn insert(&mut self) {
+        if self.batch.is_empty() {
+            return;
+        }
+        if self.try_insert().is_err() {
+            self.dropped.fetch_add(self.batch.len() as u64, Ordering::Relaxed);
+        }
+        self.batch.clear();
+    }
+
+    fn try_insert(&mut self) -> rusqlite::Result<()> {
+        let transaction = self.connection.transaction()?;
+        {
+            let mut insert = transaction.prepare_cached(INSERT)?;
+            for row in &self.batch {
+                insert.execute(params![
+                    row.seq as i64,
+                    row.ts_micros,
+                    row.level,
+                    row.component,
+                    row.thread,
+                    row.message,
+                    row.fields,
+                    row.run_id,
+                ])?;
+            }
+        }
+        transaction.commit()
+    }
+}
+
+/// Canned queries over a database written by `SqliteSink`
+///
+/// Results are in insertion order. Entries come back with their code,
+/// correlation, span and group ids; thread names are only in the table.
+pub struct SqliteLogReader {
+    connection: Connection,
+}
+
+impl SqliteLogReader {
+    /// Open `path` read-only, checking its schema version
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let connection = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX)
+            .map_err(sql_error)?;
+        match schema_version(&connection).map_err(sql_error)? {
+            SCHEMA_VERSION => Ok(SqliteLogReader { connection }),
+            other => Err(sql_error(unsupported_version(other))),
+        }
+    }
+
+    /// The last `count` entries
+    pub fn tail(&self, count: usize) -> io::Result<Vec<LogEntry>> {
+        let sql = format!("SELECT * FROM ({} ORDER BY rowid DESC LIMIT ?1) ORDER BY rowid", SELECT.replacen("SELECT ", "SELECT rowid, ", 1));
+        self.query_offset(&sql, params![count as i64], 1)
+    }
+
+    /// Entries at or above `min`
+    pub fn by_level(&self, min: LogLevel) -> io::Result<Vec<LogEntry>> {
+        self.query(&format!("{} WHERE level >= ?1 ORDER BY rowid", SELECT), params![min.to_u8()])
+    }
+
+    /// Entries under `component`, matched hierarchically as in `history_by_component`
+    pub fn by_component(&self, component: &str) -> io::Result<Vec<LogEntry>> {
+        let sql = format!("{} WHERE component = ?1 OR substr(component, 1, length(?1) + 1) = ?1 || '/' ORDER BY rowid", SELECT);
+        self.query(&sql, params![component])
+    }
+
+    /// Entries logged at or after `start` and before `end`
+    pub fn time_range(&self, start: Timestamp, end: Timestamp) -> io::Result<Vec<LogEntry>> {
+        let sql = format!("{} WHERE ts_micros >= ?1 AND ts_micros < ?2 ORDER BY rowid", SELECT);
+        self.query(&sql, params![start.as_micros(), end.as_micros()])
+    }
+
+    fn query(&self, sql: &str, params: impl rusqlite::Params) -> io::Result<Vec<LogEntry>> {
+        self.query_offset(sql, params, 0)
+    }
+
+    /// Run `sql`, whose entry columns start at `offset`
+    fn query_offset(&self, sql: &str, params: impl rusqlite::Params, offset: usize) -> io::Result<Vec<LogEntry>> {
+        let mut statement = self.connection.prepare(sql).map_err(sql_error)?;
+        let rows = statement.query_map(params, |row| entry_from(row, offset)).map_err(sql_error)?;
+        rows.collect::<rusqlite::Result<_>>().map_err(sql_error)
+    }
+}
+
+fn entry_from(row: &SqlRow<'_>, offset: usize) -> rusqlite::Result<LogEntry> {
+    let level: u8 = row.get(offset + 2)?;
+    let level = LogLevel::from_u8(level).ok_or(rusqlite::Error::IntegralValueOutOfRange(offset + 2, level as i64))?;
+    let component: String = row.get(offset + 3)?;
+    let message: String = row.get(offset + 4)?;
+
+    let mut entry = LogEntry::at(Timestamp::from_micros(row.get(offset + 1)?), level, &component, &message);
+    entry.seq = row.get::<_, i64>(offset)? as u64;
+    entry.run_id = row.get::<_, Option<String>>(offset + 5)?.map(Arc::from);
+    entry.code = row.get::<_, Option<String>>(offset + 6)?.map(Arc::from);
+    entry.correlation_id = row.get::<_, Option<String>>(offset + 7)?.map(Arc::from);
+    entry.span_id = row.get::<_, Option<i64>>(offset + 8)?.map(|id| id as u64);
+    entry.parent_id = row.get::<_, Option<i64>>(offset + 9)?.map(|id| id as u64);
+    entry.group_id = row.get::<_, Option<i64>>(offset + 10)?.map(|id| id as u64);
+    Ok(entry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixtures::temp_path;
+    use crate::testing::CaptureLogger;
+
+    const COMPONENTS: [&str; 3] = ["NETWORK", "NETWORK/WEBSOCKET", "NETWORKING"];
+
+    #[test]
+    fn test_round_trip() {
+        let path = temp_path("round_trip.db");
+        let logger = CaptureLogger::new();
+        logger.add_sink(SqliteSink::builder(&path).batch_size(100).open().unwrap());
+
+        for i in 0..5000i64 {
+            let level = LogLevel::ALL[i as usize % LogLevel::COUNT];
+            logger.log(level, COMPONENTS[i as usize % 3], &format!("message {}", i));
+        }
+        logger.event(LogLevel::WARN, "STORAGE").code("STO-0001").log("disk nearly full");
+        logger.flush();
+
+        let reader = SqliteLogReader::open(&path).unwrap();
+        let tail = reader.tail(3).unwrap();
+        assert_eq!(tail.iter().map(|e| e.message.as_str()).collect::<Vec<_>>(), ["message 4998", "message 4999", "disk nearly full"]);
+        assert_eq!(tail[2].code.as_deref(), Some("STO-0001"));
+        assert_eq!(tail[2].run_id.as_deref(), Some(logger.run_id()));
+        assert!(tail.windows(2).all(|pair| pair[1].seq > pair[0].seq));
+
+        let history = logger.get_history();
+        let stored = reader.tail(10_000).unwrap();
+        assert_eq!(stored.len(), 5001);
+        assert!(stored[stored.len() - history.len()..].iter().zip(&history).all(|(s, h)| s.seq == h.seq && s.timestamp == h.timestamp && s.message == h.message));
+
+        assert_eq!(reader.by_level(LogLevel::ERROR).unwrap().len(), 2000);
+        let network = reader.by_component("NETWORK").unwrap();
+        assert_eq!(network.len(), 3334);
+        assert!(network.iter().all(|e| e.component != "NETWORKING"));
+
+        let range = reader.time_range(history[100].timestamp, history[200].timestamp).unwrap();
+        assert!(range.iter().all(|e| e.timestamp >= history[100].timestamp && e.timestamp < history[200].timestamp));
+        assert!(range.iter().any(|e| e.seq == history[100].seq));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_schema_and_wal() {
+        let path = temp_path("schema.db");
+        let sink = SqliteSink::builder(&path).flush_interval(Duration::from_millis(10)).open().unwrap();
+        sink.write(&LogEntry::new(LogLevel::INFO, "GAME", "started"), &FormatOptions::default()).unwrap();
+
+        // The flush interval inserts the entry without an explicit flush
+        let reader = SqliteLogReader::open(&path).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while reader.tail(1).unwrap().is_empty() {
+            assert!(Instant::now() < deadline);
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        let connection = Connection::open(&path).unwrap();
+        let mode: String = connection.pragma_query_value(None, "journal_mode", |row| row.get(0)).unwrap();
+        assert_eq!(mode, "wal");
+        let thread: Option<String> = connection.query_row("SELECT thread FROM entries", [], |row| row.get(0)).unwrap();
+        assert_eq!(thread.as_deref(), thread::current().name());
+        drop(sink);
+
+        connection.pragma_update(None, "user_version", 7).unwrap();
+        assert!(SqliteSink::open(&path).is_err());
+        assert!(SqliteLogReader::open(&path).is_err());
+        let _ = std::fs::remove_file(&path);
+    }
+}
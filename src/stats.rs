@@ -0,0 +1,364 @@
+use crate::{ComponentArg, HorizonLogger, LogLevel};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use crate::sink::SinkError;
+use std::sync::{Arc, Mutex, RwLock};
+
+/// Number of independently locked shards in the stats map
+const SHARD_COUNT: usize = 16;
+
+/// Maximum number of distinct components tracked before folding into `OTHER_COMPONENT`
+pub(crate) const MAX_TRACKED_COMPONENTS: usize = 256;
+
+/// Bucket used for components beyond `MAX_TRACKED_COMPONENTS`
+pub(crate) const OTHER_COMPONENT: &str = "<other>";
+
+/// Number of components listed by `log_stats_report`
+const REPORT_TOP_N: usize = 10;
+
+/// Snapshot of the log volume produced by one component
+///
+/// Components beyond the tracking limit are aggregated under `"<other>"`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComponentStats {
+    pub component: String,
+    /// Message counts indexed by `LogLevel as usize`
+    pub messages: [u64; LogLevel::COUNT],
+    /// Message bytes indexed by `LogLevel as usize`
+    pub bytes: [u64; LogLevel::COUNT],
+    /// Entries left out by component sampling, see `set_component_sampling`
+    pub sampled_out: u64,
+}
+
+impl ComponentStats {
+    /// Total messages across all levels
+    pub fn total_messages(&self) -> u64 {
+        self.messages.iter().sum()
+    }
+
+    /// Total message bytes across all levels
+    pub fn total_bytes(&self) -> u64 {
+        self.bytes.iter().sum()
+    }
+
+    /// Messages logged at one level
+    pub fn messages_at(&self, level: LogLevel) -> u64 {
+        self.messages[level as usize]
+    }
+
+    /// Bytes logged at one level
+    pub fn bytes_at(&self, level: LogLevel) -> u64 {
+        self.bytes[level as usize]
+    }
+}
+
+/// Lock-free counters for a single component
+#[derive(Default)]
+struct Counters {
+    messages: [AtomicU64; LogLevel::COUNT],
+    bytes: [AtomicU64; LogLevel::COUNT],
+    sampled_out: AtomicU64,
+}
+
+/// Sharded per-component counters
+///
+/// The hot path only takes a shard read lock and bumps atomics; the write
+/// lock is needed the first time a component is seen.
+pub(crate) struct StatsRegistry {
+    shards: Vec<RwLock<HashMap<String, Arc<Counters>>>>,
+    tracked: AtomicUsize,
+    other: Counters,
+    /// Entries a sink failed to write or volume limits held back
+    dropped: AtomicU64,
+    sink_failures: AtomicU64,
+    last_sink_error: Mutex<Option<SinkError>>,
+}
+
+impl StatsRegistry {
+    pub(crate) fn new() -> Self {
+        StatsRegistry {
+            shards: (0..SHARD_COUNT).map(|_| RwLock::new(HashMap::new())).collect(),
+            tracked: AtomicUsize::new(0),
+            other: Counters::default(),
+            dropped: AtomicU64::new(0),
+            sink_failures: AtomicU64::new(0),
+            last_sink_error: Mutex::new(None),
+        }
+    }
+
+    /// Account for one message
+    pub(crate) fn record(&self, level: LogLevel, component: &str, bytes: usize) {
+        bump(&self.counters(component), level, bytes);
+    }
+
+    /// Account for a message left out by sampling
+    pub(crate) fn record_sampled_out(&self, component: &str) {
+        self.counters(component).sampled_out.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The counters for `component`, or the shared ones past the cardinality cap
+    fn counters(&self, component: &str) -> CountersRef<'_> {
+        let shard = &self.shards[shard_index(component)];
+
+        let existing = shard
+            .read()
+            .ok()
+            .and_then(|map| map.get(component).cloned());
+
+        match existing.or_else(|| self.insert(shard, component)) {
+            Some(counters) => CountersRef::Tracked(counters),
+            None => CountersRef::Other(&self.other),
+        }
+    }
+
+    /// Account for an entry that did not reach one of its outputs
+    pub(crate) fn record_dropped(&self) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Account for a failed sink write, which also drops the entry for that sink
+    pub(crate) fn record_sink_error(&self, error: SinkError) {
+        self.record_dropped();
+        self.sink_failures.fetch_add(1, Ordering::Relaxed);
+        if let Ok(mut last) = self.last_sink_error.lock() {
+            *last = Some(error);
+        }
+    }
+
+    pub(crate) fn sink_failures(&self) -> u64 {
+        self.sink_failures.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn last_sink_error(&self) -> Option<SinkError> {
+        self.last_sink_error.lock().ok().and_then(|last| last.clone())
+    }
+
+    /// Start tracking a component, or return `None` once the cardinality cap is hit
+    fn insert(
+        &self,
+        shard: &RwLock<HashMap<String, Arc<Counters>>>,
+        component: &str,
+    ) -> Option<Arc<Counters>> {
+        let mut map = shard.write().ok()?;
+        if let Some(counters) = map.get(component) {
+            return Some(counters.clone());
+        }
+
+        let claimed = self
+            .tracked
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+                (n < MAX_TRACKED_COMPONENTS).then_some(n + 1)
+            });
+        if claimed.is_err() {
+            return None;
+        }
+
+        let counters = Arc::new(Counters::default());
+        map.insert(component.to_string(), counters.clone());
+        Some(counters)
+    }
+
+    /// Snapshot all components sorted by total bytes, largest first
+    pub(crate) fn snapshot(&self) -> Vec<ComponentStats> {
+        let mut stats: Vec<ComponentStats> = self
+            .shards
+            .iter()
+            .filter_map(|shard| shard.read().ok())
+            .flat_map(|map| {
+                map.iter()
+                    .map(|(component, counters)| load(component, counters))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+
+        let other = load(OTHER_COMPONENT, &self.other);
+        if other.total_messages() > 0 || other.sampled_out > 0 {
+            stats.push(other);
+        }
+
+        stats.sort_by(|a, b| {
+            b.total_bytes()
+                .cmp(&a.total_bytes())
+                .then(b.total_messages().cmp(&a.total_messages()))
+                .then(a.component.cmp(&b.component))
+        });
+        stats
+    }
+
+    /// Forget all components and zero the counters
+    pub(crate) fn reset(&self) {
+        for shard in &self.shards {
+            if let Ok(mut map) = shard.write() {
+                map.clear();
+            }
+        }
+        self.tracked.store(0, Ordering::Release);
+        for i in 0..LogLevel::COUNT {
+            self.other.messages[i].store(0, Ordering::Relaxed);
+            self.other.bytes[i].store(0, Ordering::Relaxed);
+        }
+        self.other.sampled_out.store(0, Ordering::Relaxed);
+        self.dropped.store(0, Ordering::Relaxed);
+        self.sink_failures.store(0, Ordering::Relaxed);
+        if let Ok(mut last) = self.last_sink_error.lock() {
+            *last = None;
+        }
+    }
+}
+
+enum CountersRef<'a> {
+    Tracked(Arc<Counters>),
+    Other(&'a Counters),
+}
+
+impl std::ops::Deref for CountersRef<'_> {
+    type Target = Counters;
+
+    fn deref(&self) -> &Counters {
+        match self {
+            CountersRef::Tracked(counters) => counters,
+            CountersRef::Other(counters) => counters,
+        }
+    }
+}
+
+fn shard_index(component: &str) -> usize {
+    let mut hasher = DefaultHasher::new();
+    component.hash(&mut hasher);
+    hasher.finish() as usize % SHARD_COUNT
+}
+
+fn bump(counters: &Counters, level: LogLevel, bytes: usize) {
+    counters.messages[level as usize].fetch_add(1, Ordering::Relaxed);
+    counters.bytes[level as usize].fetch_add(bytes as u64, Ordering::Relaxed);
+}
+
+fn load(component: &str, counters: &Counters) -> ComponentStats {
+    ComponentStats {
+        component: component.to_string(),
+        messages: std::array::from_fn(|i| counters.messages[i].load(Ordering::Relaxed)),
+        bytes: std::array::from_fn(|i| counters.bytes[i].load(Ordering::Relaxed)),
+        sampled_out: counters.sampled_out.load(Ordering::Relaxed),
+    }
+}
+
+impl HorizonLogger {
+    /// Get per-component log volume, noisiest component first
+    pub fn component_stats(&self) -> Vec<ComponentStats> {
+        self.inner.stats.snapshot()
+    }
+
+    /// Entries that a sink failed to write, or that volume limits held back
+    pub fn dropped_entries(&self) -> u64 {
+        self.inner.stats.dropped()
+    }
+
+    /// Sink writes that returned an error or panicked, each also counted in `dropped_entries`
+    pub fn sink_failures(&self) -> u64 {
+        self.inner.stats.sink_failures()
+    }
+
+    /// The most recent sink write failure since the last `reset_stats`
+    pub fn last_sink_error(&self) -> Option<SinkError> {
+        self.inner.stats.last_sink_error()
+    }
+
+    /// Reset per-component counters to start a new measurement window
+    pub fn reset_stats(&self) {
+        self.inner.stats.reset();
+    }
+
+    /// Log a table of the noisiest components at INFO under `component`
+    pub fn log_stats_report(&self, component: impl ComponentArg) {
+        let stats = self.component_stats();
+
+        self.info(
+            &component,
+            &format!(
+                "{:<24} {:>10} {:>12} {:>8} {:>8} {:>8} {:>8} {:>8}",
+                "COMPONENT", "MESSAGES", "BYTES", "DEBUG", "INFO", "WARN", "ERROR", "CRIT"
+            ),
+        );
+
+        for entry in stats.iter().take(REPORT_TOP_N) {
+            self.info(
+                &component,
+                &format!(
+                    "{:<24} {:>10} {:>12} {:>8} {:>8} {:>8} {:>8} {:>8}",
+                    entry.component,
+                    entry.total_messages(),
+                    entry.total_bytes(),
+                    entry.messages_at(LogLevel::DEBUG),
+                    entry.messages_at(LogLevel::INFO),
+                    entry.messages_at(LogLevel::WARN),
+                    entry.messages_at(LogLevel::ERROR),
+                    entry.messages_at(LogLevel::CRITICAL),
+                ),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::CaptureLogger;
+
+    #[test]
+    fn test_component_stats_sorted_by_volume() {
+        let logger = CaptureLogger::new();
+
+        logger.info("NETWORK", "0123456789");
+        logger.warn("NETWORK", "0123456789");
+        logger.debug("GAME", "short");
+
+        let stats = logger.component_stats();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].component, "NETWORK");
+        assert_eq!(stats[0].total_messages(), 2);
+        assert_eq!(stats[0].total_bytes(), 20);
+        assert_eq!(stats[0].messages_at(LogLevel::WARN), 1);
+        assert_eq!(stats[1].component, "GAME");
+        assert_eq!(stats[1].bytes_at(LogLevel::DEBUG), 5);
+    }
+
+    #[test]
+    fn test_component_stats_bounded_and_resettable() {
+        let logger = CaptureLogger::new();
+
+        for i in 0..MAX_TRACKED_COMPONENTS + 10 {
+            logger.info(format!("C{}", i), "x");
+        }
+
+        let stats = logger.component_stats();
+        assert_eq!(stats.len(), MAX_TRACKED_COMPONENTS + 1);
+        let other = stats.iter().find(|s| s.component == OTHER_COMPONENT).unwrap();
+        assert_eq!(other.total_messages(), 10);
+
+        logger.reset_stats();
+        assert!(logger.component_stats().is_empty());
+    }
+
+    #[test]
+    fn test_log_stats_report() {
+        let logger = CaptureLogger::new();
+        logger.info("NETWORK", "hello");
+
+        logger.log_stats_report("STATS");
+
+        let report: Vec<_> = logger
+            .get_history()
+            .into_iter()
+            .filter(|e| e.component == "STATS")
+            .collect();
+        assert_eq!(report.len(), 2);
+        assert!(report[0].message.starts_with("COMPONENT"));
+        assert!(report[1].message.starts_with("NETWORK"));
+    }
+}
@@ -0,0 +1,242 @@
+//! Message templates with named placeholders filled from fields
+//!
+//! `player {player} dealt {amount} damage` with fields `player` and
+//! `amount` renders as the entry's message, while JSON output also keeps the
+//! template and the fields themselves. `{{` and `}}` are literal braces.
+
+use crate::escape::push_logfmt_value;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fmt::{self, Write};
+use std::sync::Mutex;
+
+/// A field value attached with `EventBuilder::field`
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    Str(String),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    Bool(bool),
+}
+
+impl fmt::Display for FieldValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldValue::Str(s) => f.write_str(s),
+            FieldValue::Int(n) => write!(f, "{}", n),
+            FieldValue::UInt(n) => write!(f, "{}", n),
+            FieldValue::Float(x) => write!(f, "{}", x),
+            FieldValue::Bool(b) => write!(f, "{}", b),
+        }
+    }
+}
+
+impl From<&str> for FieldValue {
+    fn from(value: &str) -> Self {
+        FieldValue::Str(value.to_string())
+    }
+}
+
+impl From<String> for FieldValue {
+    fn from(value: String) -> Self {
+        FieldValue::Str(value)
+    }
+}
+
+impl From<&String> for FieldValue {
+    fn from(value: &String) -> Self {
+        FieldValue::Str(value.clone())
+    }
+}
+
+impl From<bool> for FieldValue {
+    fn from(value: bool) -> Self {
+        FieldValue::Bool(value)
+    }
+}
+
+macro_rules! field_value_from {
+    ($variant:ident as $target:ty: $($ty:ty),*) => {
+        $(
+            impl From<$ty> for FieldValue {
+                fn from(value: $ty) -> Self {
+                    FieldValue::$variant(value as $target)
+                }
+            }
+        )*
+    };
+}
+
+field_value_from!(Int as i64: i8, i16, i32, i64, isize);
+field_value_from!(UInt as u64: u8, u16, u32, u64, usize);
+field_value_from!(Float as f64: f32, f64);
+
+/// Named fields in the order they were attached
+pub(crate) type Fields = Vec<(Cow<'static, str>, FieldValue)>;
+
+/// The message for `template`: placeholders filled from `fields`, then unused fields as ` name=value`
+///
+/// Returns the placeholders no field was given for; they render as `{name?}`.
+pub(crate) fn render(template: &str, fields: &Fields) -> (String, Vec<String>) {
+    let mut out = String::with_capacity(template.len());
+    let mut missing = Vec::new();
+    let mut used = vec![false; fields.len()];
+
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                out.push('}');
+            }
+            '{' => {
+                let mut name = String::new();
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        closed = true;
+                        break;
+                    }
+                    name.push(c);
+                }
+                if !closed {
+                    // An unterminated placeholder stays as written
+                    out.push('{');
+                    out.push_str(&name);
+                    continue;
+                }
+                match fields.iter().position(|(field, _)| *field == name) {
+                    Some(i) => {
+                        used[i] = true;
+                        let _ = write!(out, "{}", fields[i].1);
+                    }
+                    None => {
+                        let _ = write!(out, "{{{}?}}", name);
+                        missing.push(name);
+                    }
+                }
+            }
+            c => out.push(c),
+        }
+    }
+
+    append_fields(&mut out, fields.iter().zip(&used).filter(|(_, used)| !**used).map(|(field, _)| field));
+    (out, missing)
+}
+
+/// Append each field as ` name=value`, quoting values as logfmt does
+pub(crate) fn append_fields<'a>(out: &mut String, fields: impl Iterator<Item = &'a (Cow<'static, str>, FieldValue)>) {
+    for (name, value) in fields {
+        if !out.is_empty() {
+            out.push(' ');
+        }
+        out.push_str(name);
+        out.push('=');
+        push_logfmt_value(out, &value.to_string());
+    }
+}
+
+/// Templates already warned about for a missing field
+#[derive(Default)]
+pub(crate) struct WarnedTemplates(Mutex<HashSet<&'static str>>);
+
+impl WarnedTemplates {
+    /// Whether this is the first warning for `template`
+    pub(crate) fn first(&self, template: &'static str) -> bool {
+        self.0.lock().is_ok_and(|mut warned| warned.insert(template))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::CaptureLogger;
+    use crate::LogLevel;
+
+    fn fields(pairs: &[(&'static str, FieldValue)]) -> Fields {
+        pairs.iter().map(|(name, value)| (Cow::Borrowed(*name), value.clone())).collect()
+    }
+
+    #[test]
+    fn test_render() {
+        let given = fields(&[
+            ("player", "bob".into()),
+            ("amount", 50.into()),
+            ("crit", true.into()),
+            ("zone", "north gate".into()),
+        ]);
+        let (message, missing) = render("player {player} dealt {amount} damage {{crit={crit}}}", &given);
+        assert_eq!(message, "player bob dealt 50 damage {crit=true} zone=\"north gate\"");
+        assert!(missing.is_empty());
+
+        let (message, missing) = render("{player} hit {target} for {amount}", &fields(&[("player", "bob".into())]));
+        assert_eq!(message, "bob hit {target?} for {amount?}");
+        assert_eq!(missing, ["target", "amount"]);
+
+        assert_eq!(render("unterminated {name", &Fields::new()).0, "unterminated {name");
+    }
+
+    #[test]
+    fn test_missing_field_warns_once() {
+        let logger = CaptureLogger::new();
+        for _ in 0..2 {
+            logger
+                .event(LogLevel::INFO, "GAME")
+                .template("player {player} dealt {amount} damage")
+                .field("player", "bob")
+                .emit();
+        }
+        logger.event(LogLevel::INFO, "GAME").template("{player} left").emit();
+
+        let entries = logger.entries();
+        let lines: Vec<_> = entries.iter().map(|e| (e.level, &*e.component, e.message.as_str())).collect();
+        assert_eq!(
+            lines,
+            [
+                (LogLevel::INFO, "GAME", "player bob dealt {amount?} damage"),
+                (
+                    LogLevel::WARN,
+                    "LOGGER",
+                    "template \"player {player} dealt {amount} damage\" logged without field(s) amount"
+                ),
+                (LogLevel::INFO, "GAME", "player bob dealt {amount?} damage"),
+                (LogLevel::INFO, "GAME", "{player?} left"),
+                (LogLevel::WARN, "LOGGER", "template \"{player} left\" logged without field(s) player"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_json_keeps_template_and_fields() {
+        let logger = CaptureLogger::new();
+        logger
+            .event(LogLevel::INFO, "GAME")
+            .template("player {player} dealt {amount} damage")
+            .field("player", "bob")
+            .field("amount", 50)
+            .field("ratio", 0.5)
+            .field("crit", false)
+            .field("nan", f64::NAN)
+            .emit();
+        logger.event(LogLevel::INFO, "GAME").field("zone", "north").log("entered");
+
+        let entries = logger.entries();
+        let json: serde_json::Value = serde_json::from_str(&entries[0].to_json()).unwrap();
+        assert_eq!(json["message"], "player bob dealt 50 damage ratio=0.5 crit=false nan=NaN");
+        assert_eq!(json["template"], "player {player} dealt {amount} damage");
+        assert_eq!(
+            json["fields"],
+            serde_json::json!({"player": "bob", "amount": 50, "ratio": 0.5, "crit": false, "nan": "NaN"})
+        );
+
+        let json: serde_json::Value = serde_json::from_str(&entries[1].to_json()).unwrap();
+        assert_eq!(json["message"], "entered zone=north");
+        assert!(json.get("template").is_none());
+        assert_eq!(json["fields"], serde_json::json!({"zone": "north"}));
+    }
+}
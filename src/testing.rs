@@ -0,0 +1,623 @@
+//! Helpers for testing code that logs
+//!
+//! `CaptureLogger` records entries without printing them,
+//! `Expectations` asserts on what was recorded,
+//! `SequentialIdGenerator` makes run and correlation ids predictable, and
+//! `FaultInjector` makes a sink fail on cue.
+
+use crate::console::Console;
+use crate::history::component_matches;
+use crate::format::FormatOptions;
+use crate::{HorizonLogger, IdGenerator, LogEntry, LogLevel, LoggerBuilder, Sink};
+use std::collections::VecDeque;
+use std::fmt;
+use std::io;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// A logger that prints nothing and keeps everything for inspection
+///
+/// Derefs to `HorizonLogger`, so it can be passed to the logging macros.
+#[derive(Clone)]
+pub struct CaptureLogger {
+    logger: HorizonLogger,
+}
+
+impl CaptureLogger {
+    /// Capture logger with default settings
+    pub fn new() -> Self {
+        Self::from_builder(HorizonLogger::builder())
+    }
+
+    /// Capture logger built from a configured builder
+    ///
+    /// Console output is discarded and the run announcement is turned off,
+    /// so only the entries under test are captured.
+    pub fn from_builder(builder: LoggerBuilder) -> Self {
+        CaptureLogger {
+            logger: builder
+                .console(Console::new(Box::new(io::sink()), false))
+                .announce_run(false)
+                .build(),
+        }
+    }
+
+    /// The underlying logger
+    pub fn logger(&self) -> &HorizonLogger {
+        &self.logger
+    }
+
+    /// Everything logged so far, oldest first
+    pub fn entries(&self) -> Vec<LogEntry> {
+        self.logger.get_history()
+    }
+
+    /// Messages logged so far, oldest first
+    pub fn messages(&self) -> Vec<String> {
+        self.entries().into_iter().map(|e| e.message).collect()
+    }
+}
+
+impl Default for CaptureLogger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Deref for CaptureLogger {
+    type Target = HorizonLogger;
+
+    fn deref(&self) -> &HorizonLogger {
+        &self.logger
+    }
+}
+
+/// Ids `00000001`, `00000002` and so on, in the order they are asked for
+///
+/// Set it with `LoggerBuilder::id_generator`; the run id takes the first.
+#[derive(Debug, Default)]
+pub struct SequentialIdGenerator {
+    next: AtomicU64,
+}
+
+impl SequentialIdGenerator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl IdGenerator for SequentialIdGenerator {
+    fn next_id(&self) -> String {
+        format!("{:08x}", self.next.fetch_add(1, Ordering::Relaxed) + 1)
+    }
+}
+
+/// What a `FaultInjector` does to a write
+#[derive(Debug, Clone, Copy)]
+enum Fault {
+    Fail(io::ErrorKind),
+    Delay(Duration),
+    Panic,
+}
+
+/// Calls a `FaultInjector` has intercepted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FaultCounts {
+    /// Writes and self-test probes, faulted or not
+    pub writes: u64,
+    pub failed: u64,
+    pub delayed: u64,
+    pub panicked: u64,
+    pub flushes: u64,
+}
+
+/// Wraps a sink and makes the next writes fail, stall or panic, as scripted
+///
+/// Faults apply in the order they are scripted: `fail_next(3, ..)` then
+/// `panic_next(1)` fails three writes and panics in the fourth, and later
+/// writes reach the wrapped sink again. Failed and panicking writes never
+/// reach it; delayed ones do, after the delay. Register it through an `Arc`
+/// to keep a handle for scripting and `counts`. Other calls pass through.
+pub struct FaultInjector<S> {
+    sink: S,
+    script: Mutex<VecDeque<(Fault, u64)>>,
+    writes: AtomicU64,
+    failed: AtomicU64,
+    delayed: AtomicU64,
+    panicked: AtomicU64,
+    flushes: AtomicU64,
+}
+
+impl<S: Sink> FaultInjector<S> {
+    pub fn new(sink: S) -> Self {
+        FaultInjector {
+            sink,
+            script: Mutex::new(VecDeque::new()),
+            writes: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+            delayed: AtomicU64::new(0),
+            panicked: AtomicU64::new(0),
+            flushes: AtomicU64::new(0),
+        }
+    }
+
+    /// Fail the next `writes` writes with an error of `kind`, e.g. `StorageFull`
+    pub fn fail_next(&self, writes: u64, kind: io::ErrorKind) -> &Self {
+        self.push(Fault::Fail(kind), writes)
+    }
+
+    /// Hold up the next `writes` writes for `delay` each
+    pub fn delay_next(&self, writes: u64, delay: Duration) -> &Self {
+        self.push(Fault::Delay(delay), writes)
+    }
+
+    /// Panic in the next `writes` writes
+    pub fn panic_next(&self, writes: u64) -> &Self {
+        self.push(Fault::Panic, writes)
+    }
+
+    /// Drop the faults not yet applied
+    pub fn clear(&self) {
+        self.lock_script().clear();
+    }
+
+    pub fn counts(&self) -> FaultCounts {
+        FaultCounts {
+            writes: self.writes.load(Ordering::SeqCst),
+            failed: self.failed.load(Ordering::SeqCst),
+            delayed: self.delayed.load(Ordering::SeqCst),
+            panicked: self.panicked.load(Ordering::SeqCst),
+            flushes: self.flushes.load(Ordering::SeqCst),
+        }
+    }
+
+    /// The wrapped sink
+    pub fn inner(&self) -> &S {
+        &self.sink
+    }
+
+    fn push(&self, fault: Fault, writes: u64) -> &Self {
+        if writes > 0 {
+            self.lock_script().push_back((fault, writes));
+        }
+        self
+    }
+
+    // A panic is injected with the lock released, but don't trust the wrapped sink
+    fn lock_script(&self) -> std::sync::MutexGuard<'_, VecDeque<(Fault, u64)>> {
+        self.script.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Count a write and apply the next scripted fault, if any
+    fn intercept(&self) -> io::Result<()> {
+        self.writes.fetch_add(1, Ordering::SeqCst);
+        let fault = {
+            let mut script = self.lock_script();
+            let fault = script.front_mut().map(|(fault, left)| {
+                *left -= 1;
+                (*fault, *left == 0)
+            });
+            if let Some((_, true)) = fault {
+                script.pop_front();
+            }
+            fault.map(|(fault, _)| fault)
+        };
+
+        match fault {
+            None => Ok(()),
+            Some(Fault::Fail(kind)) => {
+                self.failed.fetch_add(1, Ordering::SeqCst);
+                Err(io::Error::new(kind, "injected fault"))
+            }
+            Some(Fault::Delay(delay)) => {
+                self.delayed.fetch_add(1, Ordering::SeqCst);
+                std::thread::sleep(delay);
+                Ok(())
+            }
+            Some(Fault::Panic) => {
+                self.panicked.fetch_add(1, Ordering::SeqCst);
+                panic!("injected sink panic");
+            }
+        }
+    }
+}
+
+impl<S: Sink> Sink for FaultInjector<S> {
+    fn write(&self, entry: &LogEntry, options: &FormatOptions) -> io::Result<()> {
+        self.intercept()?;
+        self.sink.write(entry, options)
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.flushes.fetch_add(1, Ordering::SeqCst);
+        self.sink.flush()
+    }
+
+    fn reopen(&self) -> io::Result<()> {
+        self.sink.reopen()
+    }
+
+    fn restart_after_fork(&self) -> io::Result<()> {
+        self.sink.restart_after_fork()
+    }
+
+    fn sync(&self) -> io::Result<()> {
+        self.sink.sync()
+    }
+
+    fn close(&self) -> io::Result<()> {
+        self.sink.close()
+    }
+
+    fn self_test(&self, probe: &LogEntry, options: &FormatOptions) -> io::Result<()> {
+        self.intercept()?;
+        self.sink.self_test(probe, options)
+    }
+}
+
+/// How an expectation matches an entry's message
+#[derive(Debug, Clone)]
+pub enum MessageMatch {
+    /// The message contains this text
+    Contains(String),
+    /// The message matches this regular expression
+    #[cfg(feature = "regex")]
+    Regex(regex::Regex),
+}
+
+impl MessageMatch {
+    /// Match messages against a regular expression
+    #[cfg(feature = "regex")]
+    pub fn regex(pattern: &str) -> Result<Self, regex::Error> {
+        regex::Regex::new(pattern).map(MessageMatch::Regex)
+    }
+
+    fn matches(&self, message: &str) -> bool {
+        match self {
+            MessageMatch::Contains(text) => message.contains(text.as_str()),
+            #[cfg(feature = "regex")]
+            MessageMatch::Regex(regex) => regex.is_match(message),
+        }
+    }
+}
+
+impl From<&str> for MessageMatch {
+    fn from(text: &str) -> Self {
+        MessageMatch::Contains(text.to_string())
+    }
+}
+
+impl From<String> for MessageMatch {
+    fn from(text: String) -> Self {
+        MessageMatch::Contains(text)
+    }
+}
+
+impl fmt::Display for MessageMatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MessageMatch::Contains(text) => write!(f, "containing {:?}", text),
+            #[cfg(feature = "regex")]
+            MessageMatch::Regex(regex) => write!(f, "matching /{}/", regex.as_str()),
+        }
+    }
+}
+
+/// One entry an expectation looks for
+#[derive(Debug, Clone)]
+struct EntryMatch {
+    level: LogLevel,
+    component: String,
+    message: MessageMatch,
+}
+
+impl EntryMatch {
+    fn matches(&self, entry: &LogEntry) -> bool {
+        entry.level == self.level
+            && component_matches(&entry.component, &self.component)
+            && self.message.matches(&entry.message)
+    }
+}
+
+impl fmt::Display for EntryMatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} [{}] {}", self.level.as_str(), self.component, self.message)
+    }
+}
+
+/// Assertions over what a `CaptureLogger` recorded
+///
+/// `expect` starts a sequence of entries that must appear in order, each
+/// `then` extending it; several sequences may be checked at once. Components
+/// match hierarchically, so `NETWORK` also matches `NETWORK/TCP`.
+#[derive(Debug, Clone, Default)]
+pub struct Expectations {
+    sequences: Vec<Vec<EntryMatch>>,
+    forbidden: Vec<EntryMatch>,
+    forbidden_level: Option<LogLevel>,
+}
+
+impl Expectations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Require an entry, starting a new ordered sequence
+    pub fn expect(mut self, level: LogLevel, component: &str, message: impl Into<MessageMatch>) -> Self {
+        self.sequences.push(vec![EntryMatch {
+            level,
+            component: component.to_string(),
+            message: message.into(),
+        }]);
+        self
+    }
+
+    /// Require an entry after the previous one in the current sequence
+    pub fn then(mut self, level: LogLevel, component: &str, message: impl Into<MessageMatch>) -> Self {
+        let step = EntryMatch {
+            level,
+            component: component.to_string(),
+            message: message.into(),
+        };
+        match self.sequences.last_mut() {
+            Some(sequence) => sequence.push(step),
+            None => self.sequences.push(vec![step]),
+        }
+        self
+    }
+
+    /// Fail if any matching entry was logged
+    pub fn forbid(mut self, level: LogLevel, component: &str, message: impl Into<MessageMatch>) -> Self {
+        self.forbidden.push(EntryMatch {
+            level,
+            component: component.to_string(),
+            message: message.into(),
+        });
+        self
+    }
+
+    /// Fail if anything at or above `level` was logged
+    pub fn forbid_level(mut self, level: LogLevel) -> Self {
+        self.forbidden_level = Some(level);
+        self
+    }
+
+    /// Check everything captured so far
+    pub fn verify(&self, capture: &CaptureLogger) -> Result<(), ExpectationError> {
+        self.verify_entries(&capture.entries())
+    }
+
+    /// Check a list of entries, oldest first
+    pub fn verify_entries(&self, entries: &[LogEntry]) -> Result<(), ExpectationError> {
+        let mut report = Vec::new();
+        let mut failed = false;
+
+        for sequence in &self.sequences {
+            let mut start = 0;
+            let mut missing = false;
+            for (i, step) in sequence.iter().enumerate() {
+                let prefix = if i == 0 { "expect" } else { "  then" };
+                let found = if missing {
+                    None
+                } else {
+                    entries[start..].iter().position(|e| step.matches(e)).map(|p| start + p)
+                };
+                match found {
+                    Some(index) => {
+                        report.push(format!("  ok  {} {}  (#{})", prefix, step, entries[index].seq));
+                        start = index + 1;
+                    }
+                    None if missing => report.push(format!("  --  {} {}  (not checked)", prefix, step)),
+                    None => {
+                        let place = match start.checked_sub(1).map(|previous| &entries[previous]) {
+                            Some(previous) => format!("after #{}", previous.seq),
+                            None => "at all".to_string(),
+                        };
+                        report.push(format!("  ERR {} {}  (not found {})", prefix, step, place));
+                        missing = true;
+                        failed = true;
+                    }
+                }
+            }
+        }
+
+        for rule in &self.forbidden {
+            let hits: Vec<&LogEntry> = entries.iter().filter(|e| rule.matches(e)).collect();
+            failed |= !hits.is_empty();
+            report.push(forbid_line(&format!("{}", rule), &hits));
+        }
+        if let Some(level) = self.forbidden_level {
+            let hits: Vec<&LogEntry> = entries.iter().filter(|e| e.level >= level).collect();
+            failed |= !hits.is_empty();
+            report.push(forbid_line(&format!("{} or above", level.as_str()), &hits));
+        }
+
+        if failed {
+            Err(ExpectationError {
+                report,
+                entries: entries.to_vec(),
+            })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+fn forbid_line(rule: &str, hits: &[&LogEntry]) -> String {
+    if hits.is_empty() {
+        return format!("  ok  forbid {}", rule);
+    }
+    let seqs: Vec<String> = hits.iter().map(|e| format!("#{}", e.seq)).collect();
+    format!("  ERR forbid {}  (found {})", rule, seqs.join(", "))
+}
+
+/// Unmet expectations, with every captured entry for context
+pub struct ExpectationError {
+    /// One line per checked rule, marked `ok`, `ERR` or `--` (not checked)
+    pub report: Vec<String>,
+    /// The entries that were checked
+    pub entries: Vec<LogEntry>,
+}
+
+impl fmt::Display for ExpectationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "log expectations not met:")?;
+        for line in &self.report {
+            writeln!(f, "{}", line)?;
+        }
+        writeln!(f, "captured entries ({}):", self.entries.len())?;
+        for entry in &self.entries {
+            writeln!(f, "  #{} {}", entry.seq, entry)?;
+        }
+        Ok(())
+    }
+}
+
+// Shown by `unwrap()`, so keep it readable in CI logs
+impl fmt::Debug for ExpectationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl std::error::Error for ExpectationError {}
+
+/// Fixtures shared by the crate's own unit tests
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use crate::{FormatOptions, LogEntry, Sink};
+    use std::fs;
+    use std::io;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    /// `horizon_logger_<pid>_<name>` in the temp directory, with whatever an earlier run left there removed
+    ///
+    /// SQLite's `-wal` and `-shm` files beside it go too.
+    pub(crate) fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("horizon_logger_{}_{}", std::process::id(), name));
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+        path
+    }
+
+    /// An empty directory `horizon_logger_<pid>_<name>` in the temp directory
+    pub(crate) fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("horizon_logger_{}_{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Keeps every entry written to it
+    #[derive(Default)]
+    pub(crate) struct CollectSink(Mutex<Vec<LogEntry>>);
+
+    impl CollectSink {
+        pub(crate) fn entries(&self) -> Vec<LogEntry> {
+            self.0.lock().unwrap().clone()
+        }
+
+        pub(crate) fn messages(&self) -> Vec<String> {
+            self.0.lock().unwrap().iter().map(|e| e.message.clone()).collect()
+        }
+    }
+
+    impl Sink for CollectSink {
+        fn write(&self, entry: &LogEntry, _options: &FormatOptions) -> io::Result<()> {
+            self.0.lock().unwrap().push(entry.clone());
+            Ok(())
+        }
+    }
+
+    /// Counts the entries written to it
+    #[derive(Default)]
+    pub(crate) struct CountingSink(AtomicUsize);
+
+    impl CountingSink {
+        pub(crate) fn count(&self) -> usize {
+            self.0.load(Ordering::Relaxed)
+        }
+    }
+
+    impl Sink for CountingSink {
+        fn write(&self, _entry: &LogEntry, _options: &FormatOptions) -> io::Result<()> {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session() -> CaptureLogger {
+        let capture = CaptureLogger::new();
+        capture.info("NETWORK/TCP", "player connected");
+        capture.warn("GAME", "slow tick");
+        capture.info("GAME", "player spawned");
+        capture
+    }
+
+    #[test]
+    fn test_ordered_expectations() {
+        let capture = session();
+        Expectations::new()
+            .expect(LogLevel::INFO, "NETWORK", "connected")
+            .then(LogLevel::INFO, "GAME", "spawned")
+            .forbid_level(LogLevel::CRITICAL)
+            .verify(&capture)
+            .unwrap();
+
+        let err = Expectations::new()
+            .expect(LogLevel::INFO, "GAME", "spawned")
+            .then(LogLevel::INFO, "NETWORK", "connected")
+            .then(LogLevel::INFO, "GAME", "left")
+            .verify(&capture)
+            .unwrap_err();
+        assert_eq!(
+            err.report,
+            vec![
+                "  ok  expect INFO [GAME] containing \"spawned\"  (#2)",
+                "  ERR   then INFO [NETWORK] containing \"connected\"  (not found after #2)",
+                "  --    then INFO [GAME] containing \"left\"  (not checked)",
+            ]
+        );
+        assert!(err.to_string().contains("captured entries (3):\n  #0 "));
+    }
+
+    #[test]
+    fn test_forbidden_entries() {
+        let capture = session();
+        let err = Expectations::new()
+            .forbid(LogLevel::WARN, "GAME", "slow")
+            .forbid_level(LogLevel::WARN)
+            .verify(&capture)
+            .unwrap_err();
+        assert_eq!(
+            err.report,
+            vec![
+                "  ERR forbid WARN [GAME] containing \"slow\"  (found #1)",
+                "  ERR forbid WARN or above  (found #1)",
+            ]
+        );
+
+        assert!(Expectations::new().forbid_level(LogLevel::ERROR).verify(&capture).is_ok());
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_regex_match() {
+        let capture = session();
+        Expectations::new()
+            .expect(LogLevel::INFO, "", MessageMatch::regex(r"^player \w+ed$").unwrap())
+            .then(LogLevel::INFO, "GAME", MessageMatch::regex("spawn").unwrap())
+            .verify(&capture)
+            .unwrap();
+    }
+}
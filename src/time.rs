@@ -0,0 +1,243 @@
+use std::fmt::Write;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A point in time, stored as microseconds since the Unix epoch (UTC)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp {
+    micros: i64,
+}
+
+impl Timestamp {
+    /// The current system time
+    pub fn now() -> Self {
+        SystemTime::now().into()
+    }
+
+    pub const fn from_micros(micros: i64) -> Self {
+        Timestamp { micros }
+    }
+
+    pub const fn from_millis(millis: i64) -> Self {
+        Timestamp {
+            micros: millis.saturating_mul(1000),
+        }
+    }
+
+    /// Microseconds since the Unix epoch
+    pub const fn as_micros(&self) -> i64 {
+        self.micros
+    }
+
+    /// Milliseconds since the Unix epoch, rounded down
+    pub const fn as_millis(&self) -> i64 {
+        self.micros.div_euclid(1000)
+    }
+
+    /// This time moved forward by `by`
+    pub fn saturating_add(self, by: Duration) -> Self {
+        let by = i64::try_from(by.as_micros()).unwrap_or(i64::MAX);
+        Timestamp {
+            micros: self.micros.saturating_add(by),
+        }
+    }
+
+    /// Time elapsed since `earlier`, or zero if `earlier` is later
+    pub fn duration_since(&self, earlier: Timestamp) -> Duration {
+        let micros = self.micros.saturating_sub(earlier.micros).max(0);
+        Duration::from_micros(micros as u64)
+    }
+
+    /// RFC 3339 in UTC with microsecond precision, e.g. `2023-11-14T22:13:20.123456Z`
+    pub fn to_rfc3339(&self) -> String {
+        let mut out = String::new();
+        self.write_utc_seconds(&mut out);
+        let _ = write!(out, ".{:06}Z", self.micros.rem_euclid(1_000_000));
+        out
+    }
+
+    /// ISO 8601 in UTC with millisecond precision, e.g. `2023-11-14T22:13:20.123Z`
+    pub fn to_iso8601_millis(&self) -> String {
+        let mut out = String::new();
+        self.write_iso8601_millis(&mut out);
+        out
+    }
+
+    /// Append `to_iso8601_millis` without allocating
+    pub(crate) fn write_iso8601_millis(&self, out: &mut impl Write) {
+        self.write_utc_seconds(out);
+        let _ = write!(out, ".{:03}Z", self.micros.rem_euclid(1_000_000) / 1000);
+    }
+
+    /// Parse an RFC 3339 time such as `2023-11-14T22:13:20.123456Z` or `2023-11-14T23:13:20+01:00`
+    ///
+    /// A space may stand in for the `T`. Fractions finer than a microsecond are dropped.
+    pub fn parse_rfc3339(text: &str) -> Option<Timestamp> {
+        let digits = |from: usize, len: usize| -> Option<i64> {
+            let part = text.get(from..from + len)?;
+            part.bytes().all(|b| b.is_ascii_digit()).then(|| part.parse().ok())?
+        };
+        let bytes = text.as_bytes();
+        let separators = [(4, b'-'), (7, b'-'), (13, b':'), (16, b':')];
+        if bytes.len() < 20 || separators.iter().any(|&(at, c)| bytes[at] != c) || !b"Tt ".contains(&bytes[10]) {
+            return None;
+        }
+        let (year, month, day) = (digits(0, 4)?, digits(5, 2)?, digits(8, 2)?);
+        let (hour, minute, second) = (digits(11, 2)?, digits(14, 2)?, digits(17, 2)?);
+        if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+            return None;
+        }
+
+        let mut rest = &text[19..];
+        let mut micros = 0;
+        if let Some(fraction) = rest.strip_prefix('.') {
+            let len = fraction.bytes().take_while(u8::is_ascii_digit).count();
+            if len == 0 {
+                return None;
+            }
+            for (i, b) in fraction.bytes().take(len.min(6)).enumerate() {
+                micros += i64::from(b - b'0') * 10_i64.pow(5 - i as u32);
+            }
+            rest = &fraction[len..];
+        }
+        let offset = match rest {
+            "Z" | "z" => 0,
+            _ => {
+                let sign = match rest.as_bytes().first()? {
+                    b'+' => 1,
+                    b'-' => -1,
+                    _ => return None,
+                };
+                let zone = &rest[1..];
+                if zone.len() != 5 || zone.as_bytes()[2] != b':' {
+                    return None;
+                }
+                let (hours, minutes) = (zone[..2].parse::<i64>().ok()?, zone[3..].parse::<i64>().ok()?);
+                sign * (hours * 3600 + minutes * 60)
+            }
+        };
+
+        let days = days_from_civil(year, month as u32, day as u32);
+        let secs = days * 86_400 + hour * 3600 + minute * 60 + second - offset;
+        Some(Timestamp {
+            micros: secs.checked_mul(1_000_000)?.checked_add(micros)?,
+        })
+    }
+
+    /// Append `YYYY-MM-DDTHH:MM:SS` in UTC
+    fn write_utc_seconds(&self, out: &mut impl Write) {
+        let secs = self.micros.div_euclid(1_000_000);
+        let (year, month, day) = civil_from_days(secs.div_euclid(86_400));
+        let of_day = secs.rem_euclid(86_400);
+        let _ = write!(
+            out,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            year,
+            month,
+            day,
+            of_day / 3600,
+            of_day / 60 % 60,
+            of_day % 60
+        );
+    }
+
+    /// This time in the local time zone
+    #[cfg(feature = "chrono")]
+    pub fn to_local(&self) -> chrono::DateTime<chrono::Local> {
+        self.to_utc().with_timezone(&chrono::Local)
+    }
+
+    /// This time as a chrono UTC date-time
+    #[cfg(feature = "chrono")]
+    pub fn to_utc(&self) -> chrono::DateTime<chrono::Utc> {
+        chrono::DateTime::from_timestamp_micros(self.micros).unwrap_or_default()
+    }
+}
+
+impl From<SystemTime> for Timestamp {
+    fn from(time: SystemTime) -> Self {
+        let micros = match time.duration_since(UNIX_EPOCH) {
+            Ok(after) => i64::try_from(after.as_micros()).unwrap_or(i64::MAX),
+            Err(before) => -i64::try_from(before.duration().as_micros()).unwrap_or(i64::MAX),
+        };
+        Timestamp { micros }
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl<Tz: chrono::TimeZone> From<chrono::DateTime<Tz>> for Timestamp {
+    fn from(time: chrono::DateTime<Tz>) -> Self {
+        Timestamp {
+            micros: time.timestamp_micros(),
+        }
+    }
+}
+
+/// Day count since 1970-01-01 for a proleptic Gregorian date, the inverse of `civil_from_days`
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (i64::from(month) + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Proleptic Gregorian date for a day count since 1970-01-01
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    // Howard Hinnant's algorithm, shifted so eras start on March 1st
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_utc_formatting() {
+        let ts = Timestamp::from_micros(1_700_000_000_123_456);
+        assert_eq!(ts.to_rfc3339(), "2023-11-14T22:13:20.123456Z");
+        assert_eq!(ts.to_iso8601_millis(), "2023-11-14T22:13:20.123Z");
+        assert_eq!(Timestamp::from_millis(0).to_rfc3339(), "1970-01-01T00:00:00.000000Z");
+        assert_eq!(Timestamp::from_micros(-1).to_rfc3339(), "1969-12-31T23:59:59.999999Z");
+        assert_eq!(Timestamp::from_millis(951_782_400_000).to_iso8601_millis(), "2000-02-29T00:00:00.000Z");
+        assert_eq!(Timestamp::from_micros(-1).as_millis(), -1);
+    }
+
+    #[test]
+    fn test_parse_rfc3339() {
+        for micros in [0, 1_700_000_000_123_456, -1, 951_782_400_000_000, -86_400_000_001] {
+            let ts = Timestamp::from_micros(micros);
+            assert_eq!(Timestamp::parse_rfc3339(&ts.to_rfc3339()), Some(ts));
+        }
+        let ts = Timestamp::from_millis(1_700_000_000_123);
+        assert_eq!(Timestamp::parse_rfc3339("2023-11-14T22:13:20.123Z"), Some(ts));
+        let offset = Timestamp::parse_rfc3339("2023-11-14 23:13:20.123456789+01:00");
+        assert_eq!(offset, Some(ts.saturating_add(Duration::from_micros(456))));
+        let bad = ["2023-11-14T22:13:20", "2023-13-14T22:13:20Z", "2023-11-14T22:13:20.Z", "2023-11-14T22:13:20+0100"];
+        for bad in bad {
+            assert_eq!(Timestamp::parse_rfc3339(bad), None, "{}", bad);
+        }
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_matches_chrono() {
+        use chrono::SecondsFormat;
+
+        for micros in [0, 1_700_000_000_123_456, -86_400_000_001, 4_102_444_800_999_999] {
+            let ts = Timestamp::from_micros(micros);
+            assert_eq!(ts.to_rfc3339(), ts.to_utc().to_rfc3339_opts(SecondsFormat::Micros, true));
+            assert_eq!(Timestamp::from(ts.to_local()), ts);
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use crate::format::write_human_time;
+use crate::Timestamp;
+use std::fmt::{self, Write};
+
+/// Writes entry times in text output: console lines, `Format::Text` and `{timestamp}` in patterns
+///
+/// Set one for the console with `LoggerBuilder::console_timestamps`, and
+/// per sink with `FormatOptions::timestamps`. `buf` is the line being
+/// built, reused from entry to entry; append to it. With the `chrono`
+/// feature, `Timestamp::to_local` and `to_utc` give date-time fields to
+/// render from. Text files are only read back by `reader` with `HumanTime`.
+pub trait TimestampRenderer: Send + Sync {
+    fn render(&self, timestamp: &Timestamp, buf: &mut String);
+}
+
+impl fmt::Debug for dyn TimestampRenderer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("TimestampRenderer")
+    }
+}
+
+/// `2024-01-02 13:45:06.789` in local time, or ISO 8601 UTC without the `chrono` feature; the default
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HumanTime;
+
+impl TimestampRenderer for HumanTime {
+    fn render(&self, timestamp: &Timestamp, buf: &mut String) {
+        write_human_time(buf, timestamp);
+    }
+}
+
+/// `134506.789`, the time of day in the same zone as `HumanTime`, for narrow consoles
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CompactTime;
+
+impl TimestampRenderer for CompactTime {
+    fn render(&self, timestamp: &Timestamp, buf: &mut String) {
+        #[cfg(feature = "chrono")]
+        {
+            use chrono::Timelike;
+            let local = timestamp.to_local();
+            let millis = local.nanosecond() % 1_000_000_000 / 1_000_000;
+            let _ = write!(buf, "{:02}{:02}{:02}.{:03}", local.hour(), local.minute(), local.second(), millis);
+        }
+
+        #[cfg(not(feature = "chrono"))]
+        {
+            let millis = timestamp.as_millis().rem_euclid(86_400_000);
+            let secs = millis / 1000;
+            let _ = write!(buf, "{:02}{:02}{:02}.{:03}", secs / 3600, secs / 60 % 60, secs % 60, millis % 1000);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::console::tests::SharedBuf;
+    use crate::console::{Console, ConsoleFields};
+    use crate::format::{human_time, FormatOptions};
+    use crate::testing::fixtures::temp_path;
+    use crate::{FileSink, HorizonLogger, ManualClock};
+    use std::sync::Arc;
+
+    /// 年月日 order, as a publisher's locale asks for
+    struct Japanese;
+
+    impl TimestampRenderer for Japanese {
+        fn render(&self, timestamp: &Timestamp, buf: &mut String) {
+            let human = human_time(timestamp);
+            let _ = write!(buf, "{}年{}月{}日 {}", &human[..4], &human[5..7], &human[8..10], &human[11..19]);
+        }
+    }
+
+    #[test]
+    fn test_renderers_per_output() {
+        let timestamp = Timestamp::from_millis(1_700_000_000_042);
+        let human = human_time(&timestamp);
+        let compact = format!("{}{}{}.{}", &human[11..13], &human[14..16], &human[17..19], &human[20..23]);
+        let japanese = format!("{}年{}月{}日 {}", &human[..4], &human[5..7], &human[8..10], &human[11..19]);
+
+        let console = SharedBuf::default();
+        let compact_path = temp_path("compact.log");
+        let human_path = temp_path("human.log");
+        let logger = HorizonLogger::builder()
+            .announce_run(false)
+            .clock(ManualClock::new(timestamp))
+            .console(Console::new(Box::new(console.clone()), false))
+            .console_fields(ConsoleFields::TIMESTAMP | ConsoleFields::MESSAGE)
+            .console_timestamps(Japanese)
+            .build();
+        let options = FormatOptions {
+            timestamps: Some(Arc::new(CompactTime)),
+            ..FormatOptions::default()
+        };
+        logger.add_sink(FileSink::new(&compact_path).unwrap().with_options(options));
+        logger.add_sink(FileSink::new(&human_path).unwrap());
+
+        logger.info("OPS", "shard 3 online");
+        logger.flush();
+
+        assert_eq!(console.contents(), format!("{} shard 3 online\n", japanese));
+        let last_line = |path| std::fs::read_to_string(path).unwrap().lines().last().unwrap().to_string();
+        let line = logger.get_history()[0].to_string();
+        assert!(line.starts_with(&human));
+        assert_eq!(last_line(&human_path), line);
+        assert_eq!(last_line(&compact_path), line.replacen(&human, &compact, 1));
+        let _ = std::fs::remove_file(&compact_path);
+        let _ = std::fs::remove_file(&human_path);
+    }
+}
@@ -0,0 +1,152 @@
+use crate::{ComponentArg, HorizonLogger, LogLevel, Timestamp};
+use std::borrow::Cow;
+use std::time::Duration;
+
+/// Measures a scope against a time budget, logging the result when dropped
+///
+/// Logs at INFO within budget, WARN beyond `warn_at` times the budget (1 by
+/// default) and ERROR from `error_at` times it (2 by default). The message
+/// ends with `elapsed_ms=`, `budget_ms=` and `over_by_ms=` fields. Time is
+/// read from the logger's clock, so a `ManualClock` makes scopes
+/// deterministic in tests.
+#[must_use = "the scope is measured until the timer is dropped"]
+pub struct ScopeTimer {
+    logger: HorizonLogger,
+    component: Cow<'static, str>,
+    name: String,
+    budget: Duration,
+    warn_at: f64,
+    error_at: f64,
+    start: Timestamp,
+}
+
+impl ScopeTimer {
+    /// Log at WARN once the scope takes longer than `multiplier` times its budget
+    pub fn warn_at(mut self, multiplier: f64) -> Self {
+        self.warn_at = multiplier;
+        self
+    }
+
+    /// Log at ERROR once the scope takes `multiplier` times its budget
+    pub fn error_at(mut self, multiplier: f64) -> Self {
+        self.error_at = multiplier;
+        self
+    }
+
+    /// The level a scope that took `elapsed` is logged at
+    fn level_for(&self, elapsed: Duration) -> LogLevel {
+        if elapsed >= self.budget.mul_f64(self.error_at) {
+            LogLevel::ERROR
+        } else if elapsed > self.budget.mul_f64(self.warn_at) {
+            LogLevel::WARN
+        } else {
+            LogLevel::INFO
+        }
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+impl Drop for ScopeTimer {
+    fn drop(&mut self) {
+        let elapsed = self.logger.inner.clock.now().duration_since(self.start);
+        let over_by = elapsed.saturating_sub(self.budget);
+        let message = format!(
+            "{} took {} of {} budget elapsed_ms={:.3} budget_ms={:.3} over_by_ms={:.3}",
+            self.name,
+            crate::fmt::duration(elapsed),
+            crate::fmt::duration(self.budget),
+            millis(elapsed),
+            millis(self.budget),
+            millis(over_by),
+        );
+        self.logger.log(self.level_for(elapsed), &self.component, &message);
+    }
+}
+
+impl HorizonLogger {
+    /// Time the scope until the returned guard is dropped, against `budget`
+    ///
+    /// ```
+    /// # use std::time::Duration;
+    /// let logger = horizon_logger::HorizonLogger::new();
+    /// {
+    ///     let _frame = logger.time_scope_deadline("RENDER", "frame", Duration::from_millis(16));
+    ///     // render
+    /// }
+    /// ```
+    pub fn time_scope_deadline(&self, component: impl ComponentArg, name: &str, budget: Duration) -> ScopeTimer {
+        ScopeTimer {
+            logger: self.clone(),
+            component: component.to_cow(),
+            name: name.to_string(),
+            budget,
+            warn_at: 1.0,
+            error_at: 2.0,
+            start: self.inner.clock.now(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::CaptureLogger;
+    use crate::ManualClock;
+    use std::sync::Arc;
+
+    fn logger_with_clock() -> (CaptureLogger, Arc<ManualClock>) {
+        let clock = Arc::new(ManualClock::new(Timestamp::from_millis(0)));
+        let logger = CaptureLogger::from_builder(HorizonLogger::builder().clock(clock.clone()));
+        (logger, clock)
+    }
+
+    #[test]
+    fn test_escalation_tiers() {
+        let (logger, clock) = logger_with_clock();
+        for taken in [10, 16, 31, 32] {
+            let _frame = logger.time_scope_deadline("RENDER", "frame", Duration::from_millis(16));
+            clock.advance(Duration::from_millis(taken));
+        }
+        {
+            let _load = logger
+                .time_scope_deadline("RENDER", "load", Duration::from_millis(10))
+                .warn_at(1.5)
+                .error_at(3.0);
+            clock.advance(Duration::from_millis(20));
+        }
+
+        let entries = logger.entries();
+        let levels: Vec<_> = entries.iter().map(|e| e.level).collect();
+        assert_eq!(
+            levels,
+            vec![LogLevel::INFO, LogLevel::INFO, LogLevel::WARN, LogLevel::ERROR, LogLevel::WARN]
+        );
+        assert_eq!(
+            entries[2].message,
+            "frame took 31.0ms of 16.0ms budget elapsed_ms=31.000 budget_ms=16.000 over_by_ms=15.000"
+        );
+        assert!(entries[0].message.ends_with("over_by_ms=0.000"));
+    }
+
+    #[test]
+    fn test_nested_scopes_are_independent() {
+        let (logger, clock) = logger_with_clock();
+        {
+            let _frame = logger.time_scope_deadline("RENDER", "frame", Duration::from_millis(16));
+            clock.advance(Duration::from_millis(4));
+            {
+                let _shadows = logger.time_scope_deadline("RENDER", "shadows", Duration::from_millis(2));
+                clock.advance(Duration::from_millis(5));
+            }
+            clock.advance(Duration::from_millis(3));
+        }
+
+        let entries = logger.entries();
+        assert_eq!((entries[0].level, entries[1].level), (LogLevel::ERROR, LogLevel::INFO));
+        assert!(entries[0].message.starts_with("shadows took 5.0ms of 2.0ms budget"));
+        assert!(entries[1].message.starts_with("frame took 12.0ms of 16.0ms budget"));
+    }
+}
@@ -0,0 +1,286 @@
+//! Console output for terminals that don't take UTF-8, see `LoggerBuilder::console_unicode`
+//!
+//! Legacy Windows consoles decode output with their code page, so UTF-8
+//! text shows as mojibake. Only the console is affected: history, files
+//! and every machine format keep the original UTF-8. Detection needs the
+//! `windows-console` feature and only ever finds a legacy console on
+//! Windows; elsewhere the console is always written as it is.
+
+use std::borrow::Cow;
+use std::io::{self, Write};
+
+/// Printed in place of characters the console can't show under `UnicodePolicy::Replace`
+const REPLACEMENT: char = '?';
+
+/// How the console writes text a legacy (non-UTF-8) Windows console can't show
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnicodePolicy {
+    /// Write through the wide-character console API, showing every character the font has
+    #[default]
+    Wide,
+    /// Write anything outside ASCII as `?`, with a one-time note on stderr
+    Replace,
+    /// Write the UTF-8 text to stdout unchanged, as when the console takes UTF-8
+    Passthrough,
+}
+
+/// `text` with every character outside ASCII as `?`, borrowed if there are none
+pub(crate) fn replace_unrepresentable(text: &str) -> Cow<'_, str> {
+    if text.is_ascii() {
+        return Cow::Borrowed(text);
+    }
+    Cow::Owned(text.chars().map(|c| if c.is_ascii() { c } else { REPLACEMENT }).collect())
+}
+
+/// Wrap the console's stdout for `policy`, if stdout is a legacy console
+pub(crate) fn wrap_stdout(out: Box<dyn Write + Send>, policy: UnicodePolicy) -> Box<dyn Write + Send> {
+    match legacy_console() {
+        Some(code_page) => wrap(out, policy, code_page),
+        None => out,
+    }
+}
+
+/// Wrap `out`, a console using `code_page`, for `policy`
+fn wrap(out: Box<dyn Write + Send>, policy: UnicodePolicy, code_page: u32) -> Box<dyn Write + Send> {
+    match policy {
+        UnicodePolicy::Passthrough => out,
+        UnicodePolicy::Replace => Box::new(Chars::new(ReplaceOut::new(out, code_page))),
+        UnicodePolicy::Wide => wide_console(out, code_page),
+    }
+}
+
+/// Receives text whose characters are whole, from a `Chars` writer
+trait TextOut: Send {
+    fn write_text(&mut self, text: &str) -> io::Result<()>;
+    fn flush(&mut self) -> io::Result<()>;
+}
+
+/// Collects written bytes into whole characters for a `TextOut`
+///
+/// A character split between two writes is held back until the rest
+/// arrives; bytes that aren't UTF-8 at all are passed on as U+FFFD.
+struct Chars<T> {
+    out: T,
+    pending: Vec<u8>,
+}
+
+impl<T: TextOut> Chars<T> {
+    fn new(out: T) -> Self {
+        Chars { out, pending: Vec::new() }
+    }
+}
+
+impl<T: TextOut> Write for Chars<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        let mut start = 0;
+        loop {
+            match std::str::from_utf8(&self.pending[start..]) {
+                Ok(text) => {
+                    self.out.write_text(text)?;
+                    start = self.pending.len();
+                    break;
+                }
+                Err(e) => {
+                    let valid = start + e.valid_up_to();
+                    // Checked by from_utf8
+                    let text = std::str::from_utf8(&self.pending[start..valid]).unwrap_or_default();
+                    self.out.write_text(text)?;
+                    match e.error_len() {
+                        Some(len) => {
+                            self.out.write_text("\u{fffd}")?;
+                            start = valid + len;
+                        }
+                        None => {
+                            start = valid;
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        self.pending.drain(..start);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Writes text with unrepresentable characters replaced, see `UnicodePolicy::Replace`
+struct ReplaceOut {
+    out: Box<dyn Write + Send>,
+    code_page: u32,
+    /// The one-time note has been printed
+    noted: bool,
+}
+
+impl ReplaceOut {
+    fn new(out: Box<dyn Write + Send>, code_page: u32) -> Self {
+        ReplaceOut {
+            out,
+            code_page,
+            noted: false,
+        }
+    }
+}
+
+impl TextOut for ReplaceOut {
+    fn write_text(&mut self, text: &str) -> io::Result<()> {
+        let replaced = replace_unrepresentable(text);
+        if matches!(replaced, Cow::Owned(_)) && !self.noted {
+            self.noted = true;
+            let _ = writeln!(
+                io::stderr(),
+                "horizon_logger: console code page {} can't show every character, printing them as {}; \
+                 history and files keep them",
+                self.code_page,
+                REPLACEMENT
+            );
+        }
+        self.out.write_all(replaced.as_bytes())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// The code page of stdout if it is a console that doesn't decode UTF-8
+#[cfg(all(windows, feature = "windows-console"))]
+pub(crate) fn legacy_console() -> Option<u32> {
+    const UTF8_CODE_PAGE: u32 = 65001;
+    let mut mode = 0u32;
+    // SAFETY: plain calls with a handle from GetStdHandle and a valid out pointer
+    let is_console = unsafe { windows::GetConsoleMode(windows::stdout(), &mut mode) } != 0;
+    if !is_console {
+        return None;
+    }
+    // SAFETY: takes no arguments
+    let code_page = unsafe { windows::GetConsoleOutputCP() };
+    (code_page != UTF8_CODE_PAGE).then_some(code_page)
+}
+
+#[cfg(not(all(windows, feature = "windows-console")))]
+pub(crate) fn legacy_console() -> Option<u32> {
+    None
+}
+
+#[cfg(all(windows, feature = "windows-console"))]
+fn wide_console(_out: Box<dyn Write + Send>, _code_page: u32) -> Box<dyn Write + Send> {
+    Box::new(Chars::new(windows::WideOut))
+}
+
+/// Without the console API there is nothing wide to write to
+#[cfg(not(all(windows, feature = "windows-console")))]
+fn wide_console(out: Box<dyn Write + Send>, _code_page: u32) -> Box<dyn Write + Send> {
+    out
+}
+
+#[cfg(all(windows, feature = "windows-console"))]
+mod windows {
+    use super::TextOut;
+    use std::ffi::c_void;
+    use std::io;
+
+    const STD_OUTPUT_HANDLE: u32 = -11i32 as u32;
+
+    extern "system" {
+        fn GetStdHandle(std_handle: u32) -> *mut c_void;
+        pub(super) fn GetConsoleMode(console: *mut c_void, mode: *mut u32) -> i32;
+        pub(super) fn GetConsoleOutputCP() -> u32;
+        fn WriteConsoleW(
+            console: *mut c_void,
+            buffer: *const u16,
+            chars: u32,
+            written: *mut u32,
+            reserved: *mut c_void,
+        ) -> i32;
+    }
+
+    pub(super) fn stdout() -> *mut c_void {
+        // SAFETY: takes a constant and returns a handle owned by the process
+        unsafe { GetStdHandle(STD_OUTPUT_HANDLE) }
+    }
+
+    /// Writes to the stdout console as UTF-16, see `UnicodePolicy::Wide`
+    pub(super) struct WideOut;
+
+    impl TextOut for WideOut {
+        fn write_text(&mut self, text: &str) -> io::Result<()> {
+            let wide: Vec<u16> = text.encode_utf16().collect();
+            let mut rest = &wide[..];
+            while !rest.is_empty() {
+                let mut written = 0u32;
+                let chars = rest.len().min(u32::MAX as usize) as u32;
+                // SAFETY: `rest` is valid for `chars` units and `written` outlives the call
+                let ok = unsafe { WriteConsoleW(stdout(), rest.as_ptr(), chars, &mut written, std::ptr::null_mut()) };
+                if ok == 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                rest = &rest[(written as usize).min(rest.len())..];
+            }
+            Ok(())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::console::tests::SharedBuf;
+    use proptest::prelude::*;
+
+    fn through(policy: UnicodePolicy, chunks: &[&[u8]]) -> Vec<u8> {
+        let buf = SharedBuf::default();
+        let mut out = wrap(Box::new(buf.clone()), policy, 437);
+        for chunk in chunks {
+            out.write_all(chunk).unwrap();
+        }
+        out.flush().unwrap();
+        buf.contents().into_bytes()
+    }
+
+    #[test]
+    fn test_replace_keeps_ascii_and_escapes() {
+        assert!(matches!(replace_unrepresentable("plain \x1b[31mred\x1b[0m"), Cow::Borrowed(_)));
+        assert_eq!(replace_unrepresentable("Zoë joined 🎮 ❄"), "Zo? joined ? ?");
+        // Split inside the two bytes of ë and the four of 🎮
+        let text = "Zoë 🎮\n".as_bytes();
+        let replaced = through(UnicodePolicy::Replace, &[&text[..3], &text[3..7], &text[7..]]);
+        assert_eq!(replaced, b"Zo? ?\n");
+        assert_eq!(through(UnicodePolicy::Replace, &[b"ok \xff\n"]), b"ok ?\n");
+    }
+
+    proptest! {
+        #[test]
+        fn replace_is_ascii_with_one_mark_per_char(text in any::<String>()) {
+            let replaced = replace_unrepresentable(&text);
+            prop_assert!(replaced.is_ascii());
+            prop_assert_eq!(replaced.chars().count(), text.chars().count());
+            for (original, shown) in text.chars().zip(replaced.chars()) {
+                prop_assert_eq!(shown, if original.is_ascii() { original } else { '?' });
+            }
+        }
+
+        #[test]
+        fn passthrough_is_byte_identical(text in any::<String>(), split in any::<prop::sample::Index>()) {
+            let bytes = text.as_bytes();
+            let at = split.index(bytes.len() + 1);
+            prop_assert_eq!(through(UnicodePolicy::Passthrough, &[&bytes[..at], &bytes[at..]]), bytes);
+        }
+
+        #[test]
+        fn replace_ignores_how_writes_are_split(text in any::<String>(), split in any::<prop::sample::Index>()) {
+            let bytes = text.as_bytes();
+            let at = split.index(bytes.len() + 1);
+            let split = through(UnicodePolicy::Replace, &[&bytes[..at], &bytes[at..]]);
+            prop_assert_eq!(split, replace_unrepresentable(&text).into_owned().into_bytes());
+        }
+    }
+}
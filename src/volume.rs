@@ -0,0 +1,469 @@
+//! Self-monitoring of logging volume
+//!
+//! The per-component stats counters are sampled about once a second on the
+//! logging path. Rates are therefore approximate: messages per second are
+//! averaged over the time since the previous sample, and bytes per minute
+//! are counted in fixed one-minute windows.
+
+use crate::run::LOGGER_COMPONENT;
+use crate::stats::{ComponentStats, OTHER_COMPONENT};
+use crate::{HorizonLogger, LogLevel, Timestamp};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
+use std::sync::{Mutex, RwLock};
+use std::time::Duration;
+
+/// How often the counters are sampled
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Length of the window bytes are counted in
+const BYTES_WINDOW: Duration = Duration::from_secs(60);
+
+/// Minimum time between warnings about the same offender
+const WARN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Thresholds for `LoggerBuilder::volume_limits`; unset limits are not checked
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VolumeLimits {
+    /// Messages per second across all components
+    pub total_msgs_per_sec: Option<u64>,
+    /// Message bytes per minute across all components
+    pub total_bytes_per_min: Option<u64>,
+    /// Messages per second from any one component
+    pub component_msgs_per_sec: Option<u64>,
+    /// Message bytes per minute from any one component
+    pub component_bytes_per_min: Option<u64>,
+    /// Raise an offending component's minimum level one step while it is over its limits
+    pub raise_level: bool,
+    /// How long a raised component must stay under its limits before it is restored
+    pub cooldown: Duration,
+}
+
+impl Default for VolumeLimits {
+    fn default() -> Self {
+        VolumeLimits {
+            total_msgs_per_sec: None,
+            total_bytes_per_min: None,
+            component_msgs_per_sec: None,
+            component_bytes_per_min: None,
+            raise_level: false,
+            cooldown: Duration::from_secs(30),
+        }
+    }
+}
+
+impl VolumeLimits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn total_msgs_per_sec(mut self, limit: u64) -> Self {
+        self.total_msgs_per_sec = Some(limit);
+        self
+    }
+
+    pub fn total_bytes_per_min(mut self, limit: u64) -> Self {
+        self.total_bytes_per_min = Some(limit);
+        self
+    }
+
+    pub fn component_msgs_per_sec(mut self, limit: u64) -> Self {
+        self.component_msgs_per_sec = Some(limit);
+        self
+    }
+
+    pub fn component_bytes_per_min(mut self, limit: u64) -> Self {
+        self.component_bytes_per_min = Some(limit);
+        self
+    }
+
+    pub fn raise_level(mut self, enabled: bool) -> Self {
+        self.raise_level = enabled;
+        self
+    }
+
+    pub fn cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+
+    /// Why `rate` breaks these limits, if it does
+    fn exceeded(&self, rate: &Rate, msgs_limit: Option<u64>, bytes_limit: Option<u64>) -> Option<String> {
+        if let Some(limit) = msgs_limit.filter(|&limit| rate.msgs_per_sec > limit) {
+            return Some(format!("{} msgs/s", limit));
+        }
+        bytes_limit
+            .filter(|&limit| rate.bytes_per_min > limit)
+            .map(|limit| format!("{} bytes/min", limit))
+    }
+}
+
+/// Logging volume as of the latest sample
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VolumeStatus {
+    pub msgs_per_sec: u64,
+    /// Bytes logged in the current one-minute window
+    pub bytes_per_min: u64,
+    /// Components active since volume monitoring started, busiest first
+    pub components: Vec<ComponentVolume>,
+}
+
+/// One component's share of `VolumeStatus`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComponentVolume {
+    pub component: String,
+    pub msgs_per_sec: u64,
+    /// Bytes logged in the current one-minute window
+    pub bytes_per_min: u64,
+    /// Minimum level while the component is held back for being too noisy
+    pub raised_level: Option<LogLevel>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Rate {
+    msgs_per_sec: u64,
+    bytes_per_min: u64,
+}
+
+/// A component whose minimum level was raised
+struct Raised {
+    min_level: LogLevel,
+    /// Entries dropped since the last sample, still counted towards the volume
+    dropped_msgs: AtomicU64,
+    dropped_bytes: AtomicU64,
+}
+
+/// Totals and rates carried from one sample to the next
+#[derive(Default)]
+struct SampleState {
+    last_sample: Option<Timestamp>,
+    window_start: Option<Timestamp>,
+    /// Message and byte totals per component at the last sample
+    totals: HashMap<String, (u64, u64)>,
+    rates: HashMap<String, Rate>,
+    total: Rate,
+    /// When each offender, or `""` for the overall volume, was last warned about
+    last_warned: HashMap<String, Timestamp>,
+    /// When each raised component was first seen back under its limits
+    calm_since: HashMap<String, Timestamp>,
+}
+
+impl SampleState {
+    /// Whether `key` may be warned about at `now`, recording the warning if so
+    fn warning_due(&mut self, key: &str, now: Timestamp) -> bool {
+        let due = self
+            .last_warned
+            .get(key)
+            .is_none_or(|last| now.duration_since(*last) >= WARN_INTERVAL);
+        if due {
+            self.last_warned.insert(key.to_string(), now);
+        }
+        due
+    }
+}
+
+/// Volume accounting for one logger; does nothing unless limits are set
+#[derive(Default)]
+pub(crate) struct VolumeMonitor {
+    limits: Option<VolumeLimits>,
+    /// Micros of the earliest time the next sample may be taken
+    next_sample: AtomicI64,
+    state: Mutex<SampleState>,
+    raised: RwLock<HashMap<String, Raised>>,
+    /// `raised` is non-empty; checked first so unthrottled calls skip the lock
+    any_raised: AtomicBool,
+}
+
+impl VolumeMonitor {
+    pub(crate) fn new(limits: Option<VolumeLimits>) -> Self {
+        VolumeMonitor {
+            limits,
+            next_sample: AtomicI64::new(i64::MIN),
+            ..Default::default()
+        }
+    }
+
+    /// Whether an entry passes its component's raised level, counting it if not
+    pub(crate) fn allows(&self, level: LogLevel, component: &str, bytes: usize) -> bool {
+        if !self.any_raised.load(Ordering::Relaxed) {
+            return true;
+        }
+        let Ok(raised) = self.raised.read() else {
+            return true;
+        };
+        match raised.get(component) {
+            Some(raised) if level < raised.min_level => {
+                raised.dropped_msgs.fetch_add(1, Ordering::Relaxed);
+                raised.dropped_bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+                false
+            }
+            _ => true,
+        }
+    }
+
+    /// Claim the next sample if one is due at `now`
+    fn sample_due(&self, now: Timestamp) -> bool {
+        let next = self.next_sample.load(Ordering::Relaxed);
+        now.as_micros() >= next
+            && self
+                .next_sample
+                .compare_exchange(next, now.saturating_add(SAMPLE_INTERVAL).as_micros(), Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+    }
+
+    /// Update rates from the stats counters, returning the entries to log about them
+    fn sample(&self, now: Timestamp, stats: &[ComponentStats], min_level: LogLevel) -> Vec<(LogLevel, String)> {
+        let (Some(limits), Ok(mut state)) = (&self.limits, self.state.lock()) else {
+            return Vec::new();
+        };
+        let state = &mut *state;
+        let Ok(mut raised) = self.raised.write() else {
+            return Vec::new();
+        };
+
+        let elapsed = state.last_sample.map(|last| now.duration_since(last));
+        state.last_sample = Some(now);
+        if state.window_start.is_none_or(|start| now.duration_since(start) >= BYTES_WINDOW) {
+            state.window_start = Some(now);
+            state.total.bytes_per_min = 0;
+            state.rates.values_mut().for_each(|rate| rate.bytes_per_min = 0);
+        }
+
+        let mut total_msgs = 0;
+        for entry in stats {
+            let (msgs, bytes) = (entry.total_messages(), entry.total_bytes());
+            let (last_msgs, last_bytes) = state.totals.insert(entry.component.clone(), (msgs, bytes)).unwrap_or_default();
+            // Totals only shrink when the stats are reset
+            let mut new_msgs = if msgs >= last_msgs { msgs - last_msgs } else { msgs };
+            let mut new_bytes = if bytes >= last_bytes { bytes - last_bytes } else { bytes };
+            if let Some(raised) = raised.get(&entry.component) {
+                new_msgs += raised.dropped_msgs.swap(0, Ordering::Relaxed);
+                new_bytes += raised.dropped_bytes.swap(0, Ordering::Relaxed);
+            }
+
+            total_msgs += new_msgs;
+            state.total.bytes_per_min += new_bytes;
+            let rate = state.rates.entry(entry.component.clone()).or_default();
+            rate.msgs_per_sec = per_second(new_msgs, elapsed);
+            rate.bytes_per_min += new_bytes;
+        }
+        state.total.msgs_per_sec = per_second(total_msgs, elapsed);
+
+        // The first sample only sets the baseline
+        if elapsed.is_none() {
+            return Vec::new();
+        }
+
+        let mut notices = Vec::new();
+        if let Some(limit) = limits.exceeded(&state.total, limits.total_msgs_per_sec, limits.total_bytes_per_min) {
+            if state.warning_due("", now) {
+                notices.push((LogLevel::WARN, format!("logging volume exceeded {}", limit)));
+            }
+        }
+
+        let components: Vec<(String, Rate)> = state.rates.iter().map(|(c, r)| (c.clone(), *r)).collect();
+        for (component, rate) in components {
+            if component == LOGGER_COMPONENT || component == OTHER_COMPONENT {
+                continue;
+            }
+            let over = limits.exceeded(&rate, limits.component_msgs_per_sec, limits.component_bytes_per_min);
+
+            match over {
+                Some(limit) => {
+                    state.calm_since.remove(&component);
+                    let mut message = format!("component {} exceeded {}", component, limit);
+                    if limits.raise_level && !raised.contains_key(&component) {
+                        if let Some(min_level) = next_level(min_level) {
+                            raised.insert(
+                                component.clone(),
+                                Raised {
+                                    min_level,
+                                    dropped_msgs: AtomicU64::new(0),
+                                    dropped_bytes: AtomicU64::new(0),
+                                },
+                            );
+                            message.push_str(&format!("; dropping its entries below {}", min_level.as_str()));
+                            // Raising is always announced, even right after a warning
+                            state.last_warned.remove(&component);
+                        }
+                    }
+                    if state.warning_due(&component, now) {
+                        notices.push((LogLevel::WARN, message));
+                    }
+                }
+                None if raised.contains_key(&component) => {
+                    let calm_since = *state.calm_since.entry(component.clone()).or_insert(now);
+                    if now.duration_since(calm_since) >= limits.cooldown {
+                        raised.remove(&component);
+                        state.calm_since.remove(&component);
+                        notices.push((
+                            LogLevel::INFO,
+                            format!("component {} is back under its volume limits", component),
+                        ));
+                    }
+                }
+                None => {}
+            }
+        }
+
+        self.any_raised.store(!raised.is_empty(), Ordering::Relaxed);
+        notices
+    }
+
+    fn status(&self) -> VolumeStatus {
+        let (Ok(state), Ok(raised)) = (self.state.lock(), self.raised.read()) else {
+            return VolumeStatus::default();
+        };
+        let mut components: Vec<ComponentVolume> = state
+            .rates
+            .iter()
+            .map(|(component, rate)| ComponentVolume {
+                component: component.clone(),
+                msgs_per_sec: rate.msgs_per_sec,
+                bytes_per_min: rate.bytes_per_min,
+                raised_level: raised.get(component).map(|raised| raised.min_level),
+            })
+            .collect();
+        components.sort_by(|a, b| {
+            b.msgs_per_sec
+                .cmp(&a.msgs_per_sec)
+                .then(b.bytes_per_min.cmp(&a.bytes_per_min))
+                .then(a.component.cmp(&b.component))
+        });
+
+        VolumeStatus {
+            msgs_per_sec: state.total.msgs_per_sec,
+            bytes_per_min: state.total.bytes_per_min,
+            components,
+        }
+    }
+}
+
+/// `count` spread over `elapsed`, rounded down
+fn per_second(count: u64, elapsed: Option<Duration>) -> u64 {
+    match elapsed {
+        Some(elapsed) if elapsed >= SAMPLE_INTERVAL => (count as f64 / elapsed.as_secs_f64()) as u64,
+        _ => count,
+    }
+}
+
+/// The level one step above `level`, if there is one
+fn next_level(level: LogLevel) -> Option<LogLevel> {
+    LogLevel::from_u8(level.to_u8() + 1)
+}
+
+impl HorizonLogger {
+    /// Logging volume as of the latest sample; empty unless volume limits are set
+    pub fn volume_status(&self) -> VolumeStatus {
+        self.inner.volume.status()
+    }
+
+    /// Sample the counters if a second has passed, logging any limits crossed
+    pub(crate) fn check_volume(&self, now: Timestamp) {
+        let volume = &self.inner.volume;
+        if volume.limits.is_none() || !volume.sample_due(now) {
+            return;
+        }
+        let stats = self.inner.stats.snapshot();
+        for (level, message) in volume.sample(now, &stats, self.min_level()) {
+            self.log(level, LOGGER_COMPONENT, &message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::testing::CaptureLogger;
+    use std::sync::Arc;
+
+    fn monitored(limits: VolumeLimits) -> (CaptureLogger, Arc<ManualClock>) {
+        let clock = Arc::new(ManualClock::new(Timestamp::from_millis(0)));
+        let builder = HorizonLogger::builder().clock(clock.clone()).volume_limits(limits);
+        (CaptureLogger::from_builder(builder), clock)
+    }
+
+    fn logger_messages(logger: &CaptureLogger) -> Vec<String> {
+        logger
+            .entries()
+            .into_iter()
+            .filter(|e| e.component == LOGGER_COMPONENT)
+            .map(|e| format!("{} {}", e.level.as_str(), e.message))
+            .collect()
+    }
+
+    #[test]
+    fn test_burst_raises_level_until_calm() {
+        let (logger, clock) = monitored(
+            VolumeLimits::new()
+                .component_msgs_per_sec(5)
+                .raise_level(true)
+                .cooldown(Duration::from_secs(10)),
+        );
+        logger.info("GAME", "start");
+
+        clock.set(Timestamp::from_millis(500));
+        for _ in 0..20 {
+            logger.debug("GAME/CHAT", "spam");
+        }
+        clock.set(Timestamp::from_millis(1_000));
+        logger.info("GAME", "tick");
+        assert_eq!(
+            logger_messages(&logger),
+            vec!["WARN component GAME/CHAT exceeded 5 msgs/s; dropping its entries below INFO"]
+        );
+
+        let status = logger.volume_status();
+        assert_eq!(status.components[0].component, "GAME/CHAT");
+        assert_eq!(status.components[0].msgs_per_sec, 20);
+        assert_eq!(status.components[0].raised_level, Some(LogLevel::INFO));
+
+        // Dropped entries still count, so the component stays held back, without a second warning
+        let before = logger.entries().len();
+        for _ in 0..10 {
+            logger.debug("GAME/CHAT", "spam");
+        }
+        logger.info("GAME/CHAT", "important");
+        logger.debug("GAME", "unaffected");
+        assert_eq!(logger.entries().len(), before + 2);
+        clock.set(Timestamp::from_millis(2_000));
+        logger.info("GAME", "tick");
+        assert_eq!(logger.volume_status().components[0].raised_level, Some(LogLevel::INFO));
+        assert_eq!(logger_messages(&logger).len(), 1);
+
+        // Calm from 3s on; restored once the cooldown has passed
+        clock.set(Timestamp::from_millis(3_000));
+        logger.info("GAME", "tick");
+        clock.set(Timestamp::from_millis(12_000));
+        logger.info("GAME", "tick");
+        assert_eq!(logger.volume_status().components[0].raised_level, Some(LogLevel::INFO));
+        clock.set(Timestamp::from_millis(13_000));
+        logger.info("GAME", "tick");
+        assert_eq!(
+            logger_messages(&logger).last().unwrap(),
+            "INFO component GAME/CHAT is back under its volume limits"
+        );
+        logger.debug("GAME/CHAT", "heard again");
+        assert_eq!(logger.entries().last().unwrap().message, "heard again");
+    }
+
+    #[test]
+    fn test_total_bytes_per_minute() {
+        let (logger, clock) = monitored(VolumeLimits::new().total_bytes_per_min(100));
+        logger.info("A", "x");
+
+        clock.set(Timestamp::from_millis(10_000));
+        logger.info("A", &"a".repeat(60));
+        clock.set(Timestamp::from_millis(20_000));
+        logger.info("B", &"b".repeat(60));
+        assert_eq!(logger_messages(&logger), vec!["WARN logging volume exceeded 100 bytes/min"]);
+        assert_eq!(logger.volume_status().bytes_per_min, 121);
+
+        // A new window starts from zero, counting only what was logged since the last sample
+        clock.set(Timestamp::from_millis(61_000));
+        logger.info("A", "y");
+        assert!(logger.volume_status().bytes_per_min < 100);
+        assert_eq!(logger_messages(&logger).len(), 1);
+    }
+}
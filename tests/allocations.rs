@@ -0,0 +1,80 @@
+use horizon_logger::testing::CaptureLogger;
+use horizon_logger::{ComponentArg, HorizonLogger};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+/// Counts allocations made by the current thread
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+fn count_one() {
+    let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count_one();
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count_one();
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const CALLS: usize = 200;
+
+/// Allowance for one-off work inside the measured calls, such as the
+/// local time zone being re-checked about once a second
+const SLACK: usize = 8;
+
+/// Allocations made by `CALLS` info calls under `component`, after warming up every cache
+fn allocations_for(logger: &CaptureLogger, component: impl ComponentArg + Copy) -> usize {
+    // Without a sink every entry would also be copied for the first one
+    logger.discard_preinit_buffer();
+    // Enough to fill the history, so its storage stops growing
+    for _ in 0..1200 {
+        logger.info(component, "tick finished");
+    }
+
+    let before = ALLOCATIONS.with(Cell::get);
+    for _ in 0..CALLS {
+        logger.info(component, "tick finished");
+    }
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+#[test]
+fn two_allocations_per_call_with_history() {
+    // The message is copied and the entry stored behind an `Arc`; a literal component is stored as is
+    let logger = CaptureLogger::new();
+    let allocations = allocations_for(&logger, "GAME");
+    assert!(allocations <= 2 * CALLS + SLACK, "{} allocations for {} calls", allocations, CALLS);
+}
+
+#[test]
+fn three_allocations_per_call_with_built_component() {
+    let logger = CaptureLogger::new();
+    let component = format!("GAME/{}", "ZONE1");
+    let allocations = allocations_for(&logger, &component);
+    assert!(allocations <= 3 * CALLS + SLACK, "{} allocations for {} calls", allocations, CALLS);
+}
+
+#[test]
+fn no_allocations_per_call_without_history() {
+    let logger = CaptureLogger::from_builder(HorizonLogger::builder().history(false));
+    let allocations = allocations_for(&logger, "GAME");
+    assert!(allocations <= SLACK, "{} allocations for {} calls", allocations, CALLS);
+    assert!(logger.entries().is_empty());
+}
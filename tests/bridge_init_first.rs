@@ -0,0 +1,63 @@
+//! The logger's bridges installed before anything else claims the globals
+
+#![cfg(feature = "tracing-bridge")]
+
+use horizon_logger::testing::CaptureLogger;
+use horizon_logger::{init_tracing_bridge, BridgeMode, LogLevel};
+
+#[cfg(feature = "log")]
+#[derive(Default)]
+struct Count(std::sync::atomic::AtomicUsize);
+
+#[cfg(feature = "log")]
+impl log::Log for &'static Count {
+    fn enabled(&self, _metadata: &log::Metadata<'_>) -> bool {
+        true
+    }
+
+    fn log(&self, _record: &log::Record<'_>) {
+        self.0.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    fn flush(&self) {}
+}
+
+#[test]
+fn bridges_installed_first_are_global() {
+    let capture = CaptureLogger::new();
+
+    // The `log` bridge goes first, so the tracing bridge can't route `log` through `tracing`
+    #[cfg(feature = "log")]
+    let also = {
+        let also: &'static Count = Box::leak(Box::default());
+        let bridge = horizon_logger::LogBridge::new(capture.logger().clone()).also_to(Box::new(also));
+        assert_eq!(bridge.install(), BridgeMode::Global);
+        also
+    };
+    assert_eq!(init_tracing_bridge(capture.logger().clone()), BridgeMode::Global);
+
+    tracing::info!(target: "net", "player {} joined", 7);
+    tracing::debug!(target: "net", "below the bridge filter");
+    #[cfg(feature = "log")]
+    log::warn!(target: "db", "slow query");
+
+    // Later installs by other crates fail; ours report that they couldn't attach
+    assert!(tracing::subscriber::set_global_default(tracing_subscriber::registry()).is_err());
+    assert_eq!(init_tracing_bridge(capture.logger().clone()), BridgeMode::Unavailable);
+    #[cfg(feature = "log")]
+    {
+        assert_eq!(horizon_logger::init_log_bridge(capture.logger().clone()), BridgeMode::Unavailable);
+        assert_eq!(also.0.load(std::sync::atomic::Ordering::Relaxed), 1);
+    }
+
+    let logged: Vec<_> = capture
+        .entries()
+        .into_iter()
+        .map(|e| (e.level, e.component.into_owned(), e.message))
+        .collect();
+    let mut expected = vec![(LogLevel::INFO, "net".to_string(), "player 7 joined".to_string())];
+    if cfg!(feature = "log") {
+        expected.push((LogLevel::WARN, "db".to_string(), "slow query".to_string()));
+    }
+    assert_eq!(logged, expected);
+}
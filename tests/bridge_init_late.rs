@@ -0,0 +1,54 @@
+//! The logger's bridges installed after other crates set the globals
+
+#![cfg(feature = "tracing-bridge")]
+
+use horizon_logger::testing::CaptureLogger;
+use horizon_logger::{init_tracing_bridge, BridgeMode, HorizonLayerSlot};
+use tracing_subscriber::layer::SubscriberExt;
+
+#[test]
+fn bridges_installed_late_attach_where_they_can() {
+    // What a dependency's lazy static might do before the application gets to set up logging
+    tracing::subscriber::set_global_default(tracing_subscriber::registry().with(HorizonLayerSlot)).unwrap();
+    #[cfg(feature = "log")]
+    log::set_boxed_logger(Box::new(NoLog)).unwrap();
+    tracing::info!(target: "assets", "loaded before init");
+
+    let capture = CaptureLogger::new();
+    assert_eq!(init_tracing_bridge(capture.logger().clone()), BridgeMode::Layered);
+    #[cfg(feature = "log")]
+    assert_eq!(horizon_logger::init_log_bridge(capture.logger().clone()), BridgeMode::Unavailable);
+
+    let span = tracing::info_span!("tick", component = "GAME/TICK");
+    span.in_scope(|| tracing::warn!(target: "game", "tick overran"));
+    // Filled once; there is nowhere for a second logger to go
+    assert_eq!(init_tracing_bridge(CaptureLogger::new().logger().clone()), BridgeMode::Unavailable);
+    tracing::trace!(target: "game", "the existing subscriber has no filter");
+
+    let logged: Vec<_> = capture
+        .entries()
+        .into_iter()
+        .map(|e| (e.component.into_owned(), e.message))
+        .collect();
+    assert_eq!(
+        logged,
+        [
+            ("GAME/TICK".to_string(), "tick overran".to_string()),
+            ("game".to_string(), "the existing subscriber has no filter".to_string()),
+        ]
+    );
+}
+
+#[cfg(feature = "log")]
+struct NoLog;
+
+#[cfg(feature = "log")]
+impl log::Log for NoLog {
+    fn enabled(&self, _metadata: &log::Metadata<'_>) -> bool {
+        false
+    }
+
+    fn log(&self, _record: &log::Record<'_>) {}
+
+    fn flush(&self) {}
+}
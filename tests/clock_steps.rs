@@ -0,0 +1,137 @@
+mod common;
+
+use common::temp_path;
+use horizon_logger::reader::LogFileReader;
+use horizon_logger::testing::CaptureLogger;
+use horizon_logger::{
+    BinaryLogReader, BinarySink, FileSink, Format, HistoryQuery, HorizonLogger, LogEntry, LogLevel, ManualClock,
+    PersistPolicy, Timestamp,
+};
+use std::sync::Arc;
+use std::thread;
+
+const START: i64 = 1_700_000_000_000;
+
+/// A wall clock stepping back and forth by up to about eight minutes
+fn jumbled(n: u64) -> Timestamp {
+    Timestamp::from_millis(START + ((n * 7919) % 1000) as i64 * 1000 - 500_000)
+}
+
+/// Sequence numbers strictly increasing, so in seq order with nothing repeated
+fn assert_seq_order(entries: &[LogEntry], what: &str) {
+    assert!(entries.windows(2).all(|pair| pair[0].seq < pair[1].seq), "{} out of seq order", what);
+}
+
+fn assert_timestamps_jumbled(entries: &[LogEntry]) {
+    assert!(entries.windows(2).any(|pair| pair[0].timestamp > pair[1].timestamp));
+}
+
+#[test]
+fn history_queries_follow_seq_not_timestamps() {
+    let clock = Arc::new(ManualClock::new(Timestamp::from_millis(START)));
+    let logger = CaptureLogger::from_builder(
+        HorizonLogger::builder().clock(clock.clone()).auto_pin(LogLevel::ERROR, 16),
+    );
+    let checkpoint = logger.history_checkpoint();
+    // One history shard per thread, merged on every read; errors are pinned and outlive eviction
+    let writers: Vec<_> = (0..4u64)
+        .map(|writer| {
+            let (logger, clock) = (logger.clone(), clock.clone());
+            thread::spawn(move || {
+                for n in 0..400 {
+                    clock.set(jumbled(writer * 400 + n));
+                    match n % 100 {
+                        0 => logger.error("NET", &format!("w{} lost {}", writer, n)),
+                        _ => logger.info("NET", &format!("w{} packet {}", writer, n)),
+                    }
+                }
+            })
+        })
+        .collect();
+    for writer in writers {
+        writer.join().unwrap();
+    }
+
+    let history = logger.get_history();
+    assert_seq_order(&history, "get_history");
+    assert_timestamps_jumbled(&history);
+    // The newest 1000 entries, plus the pinned errors evicted from before them
+    let recent: Vec<u64> = history.iter().map(|entry| entry.seq).filter(|&seq| seq >= 600).collect();
+    assert_eq!(recent, (600..1600).collect::<Vec<_>>());
+    assert_eq!(history.len(), 1000 + history.iter().filter(|entry| entry.seq < 600).count());
+    assert!(history.iter().filter(|entry| entry.seq < 600).all(|entry| entry.pinned));
+
+    let pinned = logger.pinned_entries();
+    assert_eq!(pinned.len(), 16);
+    assert_seq_order(&pinned, "pinned_entries");
+
+    let errors = logger.query_history(&HistoryQuery::new().min_level(LogLevel::ERROR));
+    assert_eq!(errors.len(), 16);
+    assert_seq_order(&errors, "query_history");
+    let tail = logger.query_history(&HistoryQuery::new().contains("packet").tail(50));
+    assert_seq_order(&tail, "query_history tail");
+    assert_eq!(tail.last().map(|entry| entry.seq), history.last().map(|entry| entry.seq));
+
+    let blocks = logger.history_context_at_level(LogLevel::ERROR, 3, 3);
+    let in_blocks: Vec<LogEntry> = blocks.iter().flat_map(|block| block.entries.clone()).collect();
+    assert_seq_order(&in_blocks, "history_context");
+    for block in &blocks {
+        assert!(block.matched.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    let since = logger.entries_since(&checkpoint);
+    assert_seq_order(&since, "entries_since");
+    assert_eq!(since.len(), history.len());
+}
+
+#[test]
+fn files_and_persisted_history_keep_seq_order() {
+    let json = temp_path("clock_steps.jsonl");
+    let binary = temp_path("clock_steps.bin");
+    let persisted = temp_path("clock_steps.hist");
+    let clock = Arc::new(ManualClock::new(Timestamp::from_millis(START)));
+    let logger = CaptureLogger::from_builder(
+        HorizonLogger::builder()
+            .clock(clock.clone())
+            .run_id("run-1")
+            .persist_history(&persisted, PersistPolicy::OnShutdown)
+            .sink(FileSink::new(&json).unwrap().with_format(Format::Json))
+            .sink(BinarySink::new(&binary).unwrap()),
+    );
+    for n in 0..200 {
+        clock.set(jumbled(n));
+        logger.info("SAVE", &format!("chunk {}", n));
+    }
+    logger.shutdown_with_summary("restart").unwrap();
+    let logged = logger.entries();
+    assert_timestamps_jumbled(&logged);
+
+    for (name, read) in [
+        ("json", LogFileReader::open(&json).unwrap().collect::<Vec<_>>()),
+        ("binary", BinaryLogReader::open(&binary).unwrap().collect()),
+    ] {
+        assert_seq_order(&read, name);
+        let seqs: Vec<u64> = read.iter().map(|entry| entry.seq).collect();
+        assert_eq!(seqs, logged.iter().map(|entry| entry.seq).collect::<Vec<_>>(), "{}", name);
+    }
+
+    // Warm-loaded entries come first, in their old order, and live ones follow
+    clock.set(Timestamp::from_millis(START - 3_600_000));
+    let restarted = CaptureLogger::from_builder(
+        HorizonLogger::builder()
+            .clock(clock.clone())
+            .run_id("run-2")
+            .persist_history(&persisted, PersistPolicy::OnShutdown),
+    );
+    restarted.info("SAVE", "loaded");
+    let history = restarted.entries();
+    assert_seq_order(&history, "restarted history");
+    assert_eq!(history.len(), logged.len() + 1);
+    let messages = |entries: &[LogEntry]| entries.iter().map(|entry| entry.message.clone()).collect::<Vec<_>>();
+    assert_eq!(messages(&history[..logged.len()]), messages(&logged));
+    assert_eq!(history.last().unwrap().run_id.as_deref(), Some("run-2"));
+
+    for path in [json, binary, persisted] {
+        let _ = std::fs::remove_file(path);
+    }
+}
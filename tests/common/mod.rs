@@ -0,0 +1,10 @@
+//! Fixtures shared by the integration tests
+
+use std::path::PathBuf;
+
+/// `horizon_logger_<pid>_<name>` in the temp directory, with whatever an earlier run left there removed
+pub fn temp_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("horizon_logger_{}_{}", std::process::id(), name));
+    let _ = std::fs::remove_file(&path);
+    path
+}
@@ -0,0 +1,80 @@
+mod common;
+
+use common::temp_path;
+use horizon_logger::testing::{FaultCounts, FaultInjector};
+use horizon_logger::{BinaryLogReader, BinarySink, FileSink, HorizonLogger};
+use std::io;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[test]
+fn full_disk_drops_entries_without_failing_callers() {
+    let path = temp_path("fault_disk_full.log");
+    let injector = Arc::new(FaultInjector::new(FileSink::new(&path).unwrap()));
+    let logger = HorizonLogger::builder().announce_run(false).build();
+    logger.add_sink(injector.clone());
+
+    logger.info("SAVE", "autosave 1");
+    injector.fail_next(2, io::ErrorKind::StorageFull);
+    logger.info("SAVE", "autosave 2");
+    logger.error("SAVE", "autosave 3");
+    logger.info("SAVE", "autosave 4");
+    logger.flush();
+
+    assert_eq!(logger.dropped_entries(), 2);
+    assert_eq!(logger.sink_failures(), 2);
+    let error = logger.last_sink_error().unwrap();
+    assert_eq!((error.kind, &*error.message), (io::ErrorKind::StorageFull, "injected fault"));
+    assert_eq!(
+        injector.counts(),
+        FaultCounts {
+            writes: 4,
+            failed: 2,
+            flushes: 1,
+            ..FaultCounts::default()
+        }
+    );
+
+    // History keeps every entry; the file only the ones written
+    assert_eq!(logger.get_history().len(), 4);
+    let text = std::fs::read_to_string(&path).unwrap();
+    assert!(text.contains("autosave 1") && text.contains("autosave 4"));
+    assert!(!text.contains("autosave 2") && !text.contains("autosave 3"));
+
+    logger.reset_stats();
+    assert_eq!((logger.dropped_entries(), logger.sink_failures()), (0, 0));
+    assert!(logger.last_sink_error().is_none());
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn panicking_and_slow_sinks_stay_contained() {
+    let path = temp_path("fault_panic.bin");
+    let injector = Arc::new(FaultInjector::new(BinarySink::new(&path).unwrap()));
+    let logger = HorizonLogger::builder().announce_run(false).build();
+    logger.add_sink(injector.clone());
+
+    injector.panic_next(1).delay_next(1, Duration::from_millis(20));
+    logger.warn("NETWORK", "collector down");
+    let started = Instant::now();
+    logger.info("NETWORK", "collector slow");
+    assert!(started.elapsed() >= Duration::from_millis(20));
+    logger.info("NETWORK", "collector back");
+    logger.flush();
+
+    assert_eq!(logger.dropped_entries(), 1);
+    let error = logger.last_sink_error().unwrap();
+    assert_eq!(error.message, "sink panicked: injected sink panic");
+    let counts = injector.counts();
+    assert_eq!((counts.writes, counts.panicked, counts.delayed), (3, 1, 1));
+
+    let messages: Vec<_> = BinaryLogReader::open(&path).unwrap().map(|entry| entry.message).collect();
+    assert_eq!(messages, ["collector slow", "collector back"]);
+
+    // The sink itself is unharmed, so later faults and writes work as before
+    injector.fail_next(5, io::ErrorKind::BrokenPipe);
+    injector.clear();
+    logger.info("NETWORK", "steady");
+    assert_eq!(logger.sink_failures(), 1);
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,7 @@
+// Source file the console snippet tests point entries at
+fn form_match(queue: &[u32]) -> Result<(), String> {
+    if queue.len() < 2 {
+        return Err(format!("only {} players queued", queue.len()));
+    }
+    Ok(())
+}
@@ -0,0 +1,120 @@
+#![cfg(all(unix, feature = "fork"))]
+
+mod common;
+
+use common::temp_path;
+use horizon_logger::{BackpressurePolicy, FileSink, HorizonLogger, NetworkSink};
+use std::fs;
+use std::net::UdpSocket;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[test]
+fn test_child_logs_to_reopened_file() {
+    let path = temp_path("fork.log");
+
+    let logger = HorizonLogger::builder().announce_run(false).build();
+    logger.add_sink(FileSink::new(&path).unwrap());
+
+    // Buffered but unflushed; must not be duplicated into the child
+    logger.info("PARENT", "before fork");
+
+    logger.prepare_fork();
+    let pid = unsafe { libc::fork() };
+    assert!(pid >= 0, "fork failed");
+
+    if pid == 0 {
+        let code = match logger.after_fork_child() {
+            Ok(()) => {
+                logger.info("CHILD", "hello from child");
+                logger.flush();
+                0
+            }
+            Err(_) => 1,
+        };
+        unsafe { libc::_exit(code) };
+    }
+
+    logger.after_fork_parent();
+    let mut status = 0;
+    unsafe { libc::waitpid(pid, &mut status, 0) };
+    assert!(libc::WIFEXITED(status) && libc::WEXITSTATUS(status) == 0);
+
+    logger.info("PARENT", "after fork");
+    logger.flush();
+
+    let contents = fs::read_to_string(&path).unwrap();
+    let lines: Vec<&str> = contents.lines().collect();
+    assert_eq!(lines.len(), 4, "unexpected file contents:\n{}", contents);
+    assert!(lines[0].starts_with("# horizon-logger format=text "));
+    assert!(lines[1].ends_with("[PARENT] before fork"));
+    assert!(lines[2].ends_with("[CHILD] hello from child"));
+    assert!(lines[3].ends_with("[PARENT] after fork"));
+    let _ = fs::remove_file(&path);
+}
+
+#[test]
+fn test_child_restarts_background_threads() {
+    let path = temp_path("fork_async.log");
+    let collector = UdpSocket::bind("127.0.0.1:0").unwrap();
+    collector.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    let logger = HorizonLogger::builder()
+        .announce_run(false)
+        .async_sinks(BackpressurePolicy::default())
+        .build();
+    logger.add_sink(FileSink::new(&path).unwrap());
+    logger.add_sink(NetworkSink::udp(collector.local_addr().unwrap()).unwrap());
+    let heartbeat = logger.start_heartbeat(Duration::from_millis(10), "HEARTBEAT");
+    logger.info("PARENT", "before fork");
+    logger.flush();
+
+    logger.prepare_fork();
+    let pid = unsafe { libc::fork() };
+    assert!(pid >= 0, "fork failed");
+
+    if pid == 0 {
+        let code = match logger.after_fork_child() {
+            Ok(()) => {
+                // Only a heartbeat thread started in the child can log here
+                let checkpoint = logger.history_checkpoint();
+                let deadline = Instant::now() + Duration::from_secs(5);
+                let beating = loop {
+                    if logger.entries_since(&checkpoint).iter().any(|e| e.component == "HEARTBEAT") {
+                        break true;
+                    }
+                    if Instant::now() > deadline {
+                        break false;
+                    }
+                    thread::sleep(Duration::from_millis(5));
+                };
+                logger.info("CHILD", "hello from child");
+                logger.flush();
+                if beating { 0 } else { 2 }
+            }
+            Err(_) => 1,
+        };
+        unsafe { libc::_exit(code) };
+    }
+
+    logger.after_fork_parent();
+    let mut status = 0;
+    unsafe { libc::waitpid(pid, &mut status, 0) };
+    assert!(libc::WIFEXITED(status), "child did not exit");
+    assert_eq!(libc::WEXITSTATUS(status), 0, "child exit code");
+    drop(heartbeat);
+
+    // The file and the collector got the child's entry, written by the child's sink threads
+    let contents = fs::read_to_string(&path).unwrap();
+    assert!(contents.lines().any(|line| line.ends_with("[CHILD] hello from child")), "{}", contents);
+    let mut datagram = [0u8; 4096];
+    let received = loop {
+        let len = collector.recv(&mut datagram).expect("no datagram from the child");
+        let line = String::from_utf8_lossy(&datagram[..len]).into_owned();
+        if line.contains("[CHILD]") {
+            break line;
+        }
+    };
+    assert!(received.ends_with("[CHILD] hello from child"), "{}", received);
+    let _ = fs::remove_file(&path);
+}
@@ -0,0 +1,21 @@
+use horizon_logger::testing::CaptureLogger;
+use horizon_logger::LogLevel;
+
+// Alone in its own test binary, since every logger built while the variable is set starts disabled
+#[test]
+fn env_var_builds_disabled_loggers() {
+    std::env::set_var("HORIZON_LOG_DISABLE", "1");
+    let logger = CaptureLogger::new();
+    std::env::remove_var("HORIZON_LOG_DISABLE");
+
+    assert!(logger.is_emergency_disabled());
+    assert!(!logger.enabled(LogLevel::CRITICAL));
+    logger.error("BOOT", "config missing");
+    assert!(logger.entries().is_empty());
+    assert_eq!(logger.suppressed_entries(), 1);
+
+    assert!(logger.emergency_enable());
+    logger.error("BOOT", "config missing");
+    assert_eq!(logger.messages(), ["config missing"]);
+    assert!(!CaptureLogger::new().is_emergency_disabled());
+}
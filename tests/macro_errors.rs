@@ -0,0 +1,7 @@
+//! The logging macros reject calls without a message at compile time
+
+#[test]
+fn missing_messages_fail_to_compile() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/*.rs");
+}
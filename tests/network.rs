@@ -0,0 +1,93 @@
+use horizon_logger::{Format, HorizonLogger, NetworkSink};
+use std::io::{BufRead, BufReader};
+use std::net::{SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+fn read_line(reader: &mut BufReader<TcpStream>) -> String {
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    line
+}
+
+fn accept(listener: &TcpListener) -> BufReader<TcpStream> {
+    let (stream, _) = listener.accept().unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+    BufReader::new(stream)
+}
+
+#[test]
+fn udp_lines_arrive_intact() {
+    let collector = UdpSocket::bind("127.0.0.1:0").unwrap();
+    collector.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+    let logger = HorizonLogger::builder().announce_run(false).build();
+    logger.add_sink(NetworkSink::udp(collector.local_addr().unwrap()).unwrap().with_format(Format::Json));
+
+    logger.info("NETWORK", "player \"one\" joined");
+    logger.warn("GAME", "tick overran");
+
+    let mut buf = [0u8; 2048];
+    let mut messages = Vec::new();
+    for _ in 0..2 {
+        let n = collector.recv(&mut buf).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&buf[..n]).unwrap();
+        messages.push(json["message"].as_str().unwrap().to_string());
+    }
+    assert_eq!(messages, vec!["player \"one\" joined", "tick overran"]);
+}
+
+#[test]
+fn tcp_reconnects_after_collector_restart() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let logger = HorizonLogger::builder().announce_run(false).build();
+    logger.add_sink(NetworkSink::tcp(addr).unwrap());
+
+    logger.info("NETWORK", "before restart");
+    let mut reader = accept(&listener);
+    assert!(read_line(&mut reader).ends_with("[NETWORK] before restart\n"));
+
+    // Collector goes away; entries logged meanwhile are queued
+    drop(reader);
+    drop(listener);
+    thread::sleep(Duration::from_millis(100));
+    logger.info("NETWORK", "while down");
+
+    let listener = TcpListener::bind(addr).unwrap();
+    let mut reader = accept(&listener);
+    assert!(read_line(&mut reader).ends_with("[NETWORK] while down\n"));
+    logger.info("NETWORK", "after restart");
+    assert!(read_line(&mut reader).ends_with("[NETWORK] after restart\n"));
+}
+
+#[test]
+fn full_queue_drops_and_counts() {
+    // Reserve a port nobody listens on
+    let addr: SocketAddr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let sink = Arc::new(NetworkSink::tcp(addr).unwrap().with_queue_capacity(2));
+    let logger = HorizonLogger::builder().announce_run(false).build();
+    logger.add_sink(sink.clone());
+
+    for i in 0..5 {
+        logger.info("NETWORK", &format!("entry {}", i));
+    }
+    assert_eq!(sink.dropped(), 3);
+}
+
+#[test]
+fn self_test_checks_the_connection() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let logger = HorizonLogger::builder().announce_run(false).build();
+    logger.add_sink(NetworkSink::tcp(listener.local_addr().unwrap()).unwrap());
+    let collector = thread::spawn(move || read_line(&mut accept(&listener)));
+    assert!(logger.self_test().is_ok());
+    assert!(collector.join().unwrap().contains("[SELFTEST] self-test probe "));
+
+    // Nothing listens on a port that was just released
+    let closed = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let logger = HorizonLogger::builder().announce_run(false).build();
+    logger.add_sink(NetworkSink::tcp(closed).unwrap());
+    let error = logger.self_test().unwrap_err();
+    assert!(error.to_string().ends_with(&format!("not connected to {}", closed)), "{}", error);
+}
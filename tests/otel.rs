@@ -0,0 +1,64 @@
+#![cfg(feature = "otel")]
+
+use horizon_logger::testing::CaptureLogger;
+use horizon_logger::{LogLevel, OtelSink};
+use opentelemetry::logs::{AnyValue, Severity};
+use opentelemetry::trace::{SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState};
+use opentelemetry::{Context, Key};
+use opentelemetry_sdk::logs::{InMemoryLogExporter, SdkLoggerProvider};
+use std::thread;
+
+fn attribute<'a>(attributes: &'a [(Key, AnyValue)], key: &str) -> Option<&'a AnyValue> {
+    attributes.iter().find(|(k, _)| k.as_str() == key).map(|(_, v)| v)
+}
+
+#[test]
+fn entries_become_log_records() {
+    let exporter = InMemoryLogExporter::default();
+    let provider = SdkLoggerProvider::builder().with_simple_exporter(exporter.clone()).build();
+    let logger = CaptureLogger::new();
+    logger.add_sink(OtelSink::new(&provider));
+
+    let span = SpanContext::new(
+        TraceId::from(0x4bf92f3577b34da6a3ce929d0e0e4736),
+        SpanId::from(0x00f067aa0ba902b7),
+        TraceFlags::SAMPLED,
+        true,
+        TraceState::default(),
+    );
+    thread::Builder::new()
+        .name("tick".into())
+        .spawn({
+            let logger = logger.logger().clone();
+            let span = span.clone();
+            move || {
+                let _cx = Context::new().with_remote_span_context(span).attach();
+                logger.event(LogLevel::ERROR, "NETWORK").severity(45).code("NET-0042").log("player kicked");
+            }
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+    logger.info("", "no span here");
+
+    let logs = exporter.get_emitted_logs().unwrap();
+    assert_eq!(logs.len(), 2);
+    let kicked = &logs[0].record;
+    assert_eq!(kicked.severity_number(), Some(Severity::Error3));
+    assert_eq!(kicked.severity_text(), Some("ERROR"));
+    assert_eq!(kicked.body(), Some(&AnyValue::from("player kicked".to_string())));
+    let attributes: Vec<_> = kicked.attributes_iter().cloned().collect();
+    assert_eq!(attribute(&attributes, "component"), Some(&AnyValue::from("NETWORK".to_string())));
+    assert_eq!(attribute(&attributes, "thread.name"), Some(&AnyValue::from("tick".to_string())));
+    assert_eq!(attribute(&attributes, "code"), Some(&AnyValue::from("NET-0042".to_string())));
+    assert_eq!(attribute(&attributes, "seq"), Some(&AnyValue::from(0i64)));
+    let run_id = logger.run_id().to_string();
+    assert_eq!(attribute(&attributes, "run_id"), Some(&AnyValue::from(run_id)));
+    let trace = kicked.trace_context().unwrap();
+    assert_eq!((trace.trace_id, trace.span_id), (span.trace_id(), span.span_id()));
+
+    let plain = &logs[1].record;
+    assert_eq!(plain.severity_number(), Some(Severity::Info));
+    assert!(plain.trace_context().is_none());
+    assert!(attribute(&plain.attributes_iter().cloned().collect::<Vec<_>>(), "component").is_none());
+}
@@ -0,0 +1,64 @@
+use horizon_logger::testing::CaptureLogger;
+use horizon_logger::{BackpressurePolicy, FormatOptions, HorizonLogger, LogEntry, Sink, SinkOrdering};
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Sequence numbers in the order the sink was handed them
+#[derive(Default)]
+struct SeqRecorder(Mutex<Vec<u64>>);
+
+impl Sink for SeqRecorder {
+    fn write(&self, entry: &LogEntry, _options: &FormatOptions) -> io::Result<()> {
+        self.0.lock().unwrap().push(entry.seq);
+        Ok(())
+    }
+}
+
+fn hammer(logger: &HorizonLogger) -> (Vec<u64>, Vec<u64>) {
+    let first = Arc::new(SeqRecorder::default());
+    let second = Arc::new(SeqRecorder::default());
+    logger.add_sink(first.clone());
+    logger.add_sink(second.clone());
+
+    thread::scope(|scope| {
+        for t in 0..16 {
+            scope.spawn(move || {
+                for n in 0..500 {
+                    logger.info("STRESS", &format!("thread {} entry {}", t, n));
+                }
+            });
+        }
+    });
+    logger.flush();
+    let first = first.0.lock().unwrap().clone();
+    let second = second.0.lock().unwrap().clone();
+    (first, second)
+}
+
+#[test]
+fn strict_ordering_on_the_logging_threads() {
+    let logger = CaptureLogger::new();
+    let (first, second) = hammer(&logger);
+    assert_eq!(first.len(), 16 * 500);
+    assert_eq!(first, second);
+}
+
+#[test]
+fn strict_ordering_through_the_queue() {
+    let policy = BackpressurePolicy::new().capacity(100_000).high_water(100_000);
+    let logger = CaptureLogger::from_builder(HorizonLogger::builder().async_sinks(policy));
+    let (first, second) = hammer(&logger);
+    assert_eq!(first.len(), 16 * 500);
+    assert_eq!(first, second);
+}
+
+#[test]
+fn independent_ordering_needs_async_sinks() {
+    let builder = HorizonLogger::builder().sink_ordering(SinkOrdering::Independent);
+    assert!(builder.try_build().is_err());
+    let builder = HorizonLogger::builder()
+        .async_sinks(BackpressurePolicy::default())
+        .sink_ordering(SinkOrdering::Independent);
+    assert!(builder.try_build().is_ok());
+}
@@ -0,0 +1,50 @@
+use horizon_logger::testing::CaptureLogger;
+use horizon_logger::{format_entry, FieldValue, Format, FormatOptions, HorizonLogger, LogLevel};
+
+// In its own test binary, so setting variables can't race other tests reading the environment
+#[test]
+fn static_fields_from_env_in_json_and_logfmt() {
+    std::env::set_var("HORIZON_TEST_POD_NAME", "matchmaker-7d9f");
+    std::env::set_var("HORIZON_TEST_REGION", "eu-west-1");
+    std::env::remove_var("HORIZON_TEST_NODE");
+    std::env::remove_var("HORIZON_TEST_ZONE");
+    let logger = CaptureLogger::from_builder(
+        HorizonLogger::builder()
+            .static_field("service", "matchmaker")
+            .static_fields_from_env(&[
+                ("pod", "HORIZON_TEST_POD_NAME"),
+                ("node", "HORIZON_TEST_NODE"),
+                ("region", "HORIZON_TEST_REGION"),
+            ])
+            .static_field_from_env_or("zone", "HORIZON_TEST_ZONE", "unknown"),
+    );
+    // Read at build time only
+    std::env::set_var("HORIZON_TEST_NODE", "node-3");
+    std::env::remove_var("HORIZON_TEST_REGION");
+
+    logger.event(LogLevel::INFO, "LOBBY").field("queue", 12u64).log("queue formed");
+    let entry = &logger.entries()[0];
+    let names: Vec<_> = entry.fields.iter().map(|(name, _)| name.as_ref()).collect();
+    assert_eq!(names, ["service", "pod", "region", "zone", "queue"]);
+    assert_eq!(entry.fields[1].1, FieldValue::Str("matchmaker-7d9f".into()));
+
+    let json = entry.to_json();
+    let fields = concat!(
+        r#""fields":{"service":"matchmaker","pod":"matchmaker-7d9f","#,
+        r#""region":"eu-west-1","zone":"unknown","queue":12}"#
+    );
+    assert!(json.contains(fields), "{}", json);
+    let logfmt = format_entry(entry, Format::Logfmt, &FormatOptions::default());
+    assert!(
+        logfmt.ends_with(" service=matchmaker pod=matchmaker-7d9f region=eu-west-1 zone=unknown queue=12"),
+        "{}",
+        logfmt
+    );
+    assert!(!entry.to_string().contains("matchmaker-7d9f"));
+
+    let bare = CaptureLogger::new();
+    bare.info("LOBBY", "queue formed");
+    assert!(bare.entries()[0].fields.is_empty());
+    std::env::remove_var("HORIZON_TEST_POD_NAME");
+    std::env::remove_var("HORIZON_TEST_NODE");
+}
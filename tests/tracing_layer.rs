@@ -0,0 +1,59 @@
+#![cfg(feature = "tracing-bridge")]
+
+use horizon_logger::testing::CaptureLogger;
+use horizon_logger::{HorizonLayer, LogLevel};
+use std::sync::{Arc, Mutex};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::Layer;
+
+/// Stands in for a team's own layer, recording each event's level and target
+#[derive(Clone, Default)]
+struct Recorder(Arc<Mutex<Vec<(tracing::Level, String)>>>);
+
+impl<S: Subscriber> Layer<S> for Recorder {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        self.0.lock().unwrap().push((*metadata.level(), metadata.target().to_string()));
+    }
+}
+
+#[test]
+fn layer_composes_with_other_layers() {
+    let capture = CaptureLogger::new();
+    let recorder = Recorder::default();
+    let subscriber = tracing_subscriber::registry()
+        .with(recorder.clone())
+        .with(HorizonLayer::new(capture.logger().clone()));
+
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!(target: "net", "player {} joined", 7);
+        let span = tracing::info_span!("tick", component = "GAME/TICK");
+        let _entered = span.enter();
+        tracing::warn!(target: "game", overran_by_ms = 3, "tick overran");
+        tracing::trace!(target: "game", component = "GAME/AI", "pathfinding");
+    });
+
+    let recorded = recorder.0.lock().unwrap().clone();
+    let logged: Vec<_> = capture
+        .entries()
+        .into_iter()
+        .map(|e| (e.level, e.component.into_owned(), e.message))
+        .collect();
+    assert_eq!(
+        recorded,
+        vec![
+            (tracing::Level::INFO, "net".to_string()),
+            (tracing::Level::WARN, "game".to_string()),
+            (tracing::Level::TRACE, "game".to_string()),
+        ]
+    );
+    assert_eq!(
+        logged,
+        vec![
+            (LogLevel::INFO, "net".to_string(), "player 7 joined".to_string()),
+            (LogLevel::WARN, "GAME/TICK".to_string(), "tick overran overran_by_ms=3".to_string()),
+            (LogLevel::DEBUG, "GAME/AI".to_string(), "pathfinding".to_string()),
+        ]
+    );
+}
@@ -0,0 +1,7 @@
+use horizon_logger::{log_error, HorizonLogger};
+
+fn main() {
+    let logger = HorizonLogger::new();
+    let component = String::from("NETWORK");
+    log_error!(logger, component);
+}
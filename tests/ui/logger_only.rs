@@ -0,0 +1,6 @@
+use horizon_logger::{log_warn, HorizonLogger};
+
+fn main() {
+    let logger = HorizonLogger::new();
+    log_warn!(logger);
+}
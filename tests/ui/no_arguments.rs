@@ -0,0 +1,5 @@
+use horizon_logger::log_info;
+
+fn main() {
+    log_info!();
+}